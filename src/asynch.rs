@@ -0,0 +1,546 @@
+//! Async SPI transport for the BlueNRG, built on [`embedded_hal_async`].
+//!
+//! The blocking transport spins on the SPI header handshake and on the data ready pin. The async
+//! transport yields to the executor while the controller is asleep, and awaits the data ready pin
+//! instead of polling it.
+//!
+//! Commands are encoded with the same [`Commands`](crate::gap::Commands) traits as the blocking
+//! transport: [`AsyncActiveBlueNRG::send`] gives its closure a [`CommandPacket`], which implements
+//! [`bluetooth_hci::Controller`] by recording the encoded command, and then writes that command
+//! to the controller.
+
+extern crate embedded_hal_async as ehal_async;
+
+use crate::dialect::Dialect;
+use crate::{
+    event, Access, BlueNRG, Error, Firmware, PACKET_TYPE_HCI_COMMAND, PACKET_TYPE_HCI_EVENT,
+};
+use core::cmp::min;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+use ehal_async::digital::Wait;
+use ehal_async::spi::SpiBus;
+use hci::host::uart::{CommandHeader, Packet};
+
+/// Handle for actively communicating with the controller over an async SPI bus.
+///
+/// An `AsyncActiveBlueNRG` should not be created by the application, but is passed by value to
+/// closures given to [`BlueNRG::with_spi_async`].
+pub struct AsyncActiveBlueNRG<
+    'bnrg,
+    'spi,
+    'dbuf,
+    SPI,
+    OutputPin1,
+    OutputPin2,
+    InputPin,
+    GpioError,
+    RxBuffer = &'dbuf mut [u8],
+> {
+    /// Mutably borrow the BlueNRG handle so we can access pin and buffer.
+    d: &'bnrg mut BlueNRG<'dbuf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer>,
+
+    /// Mutably borrow the SPI bus so we can communicate with the controller.
+    spi: &'spi mut SPI,
+}
+
+/// Errors that may occur when sending a command with [`AsyncActiveBlueNRG::send`].
+#[derive(Debug, PartialEq)]
+pub enum SendError<BuildError, CommError> {
+    /// The closure returned an error while building the command, for example because a command
+    /// parameter was invalid.
+    Build(BuildError),
+
+    /// The closure tried to build more than one command, or a command that does not fit in a
+    /// single HCI command packet.
+    PacketFull,
+
+    /// The controller cannot accept another command until a Command Complete or Command Status
+    /// event returns a credit. See [`BlueNRG::command_credits`].
+    NoCommandCredits,
+
+    /// There was an error communicating with the controller.
+    Comm(CommError),
+}
+
+/// Errors that may occur when writing a packet with [`AsyncActiveBlueNRG::write`].
+#[derive(Debug, PartialEq)]
+pub enum WriteError<CommError> {
+    /// The packet is a command, and the controller cannot accept another command until a Command
+    /// Complete or Command Status event returns a credit. See [`BlueNRG::command_credits`].
+    NoCommandCredits,
+
+    /// There was an error communicating with the controller.
+    Comm(CommError),
+}
+
+impl<BuildError, CommError> From<WriteError<CommError>> for SendError<BuildError, CommError> {
+    fn from(e: WriteError<CommError>) -> Self {
+        match e {
+            WriteError::NoCommandCredits => SendError::NoCommandCredits,
+            WriteError::Comm(e) => SendError::Comm(e),
+        }
+    }
+}
+
+/// Maximum length of an HCI command packet: the 4-byte header and up to 255 bytes of parameters.
+const MAX_COMMAND_PACKET_LEN: usize = 4 + 255;
+
+/// Recorder for a single encoded command.
+///
+/// `CommandPacket` implements [`bluetooth_hci::Controller`], so any of the command traits may be
+/// used to encode a command into it. It is passed to the closure given to
+/// [`AsyncActiveBlueNRG::send`]. Writing more than one command returns `nb::Error::WouldBlock`;
+/// reading always returns `nb::Error::WouldBlock`. It implements [`Firmware`] with the version
+/// and dialect of the [`BlueNRG`] the command is sent to.
+pub struct CommandPacket {
+    bytes: [u8; MAX_COMMAND_PACKET_LEN],
+    header_len: usize,
+    len: usize,
+    version: Option<crate::Version>,
+    dialect: Dialect,
+}
+
+impl CommandPacket {
+    fn new(version: Option<crate::Version>, dialect: Dialect) -> CommandPacket {
+        CommandPacket {
+            bytes: [0; MAX_COMMAND_PACKET_LEN],
+            header_len: 0,
+            len: 0,
+            version,
+            dialect,
+        }
+    }
+
+    fn header(&self) -> &[u8] {
+        &self.bytes[..self.header_len]
+    }
+
+    fn payload(&self) -> &[u8] {
+        &self.bytes[self.header_len..self.len]
+    }
+}
+
+impl hci::Controller for CommandPacket {
+    type Error = core::convert::Infallible;
+    type Header = CommandHeader;
+    type Vendor = crate::BlueNRGTypes;
+
+    fn write(&mut self, header: &[u8], payload: &[u8]) -> nb::Result<(), Self::Error> {
+        let len = header.len() + payload.len();
+        if self.len > 0 || len > self.bytes.len() {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        self.bytes[..header.len()].copy_from_slice(header);
+        self.bytes[header.len()..len].copy_from_slice(payload);
+        self.header_len = header.len();
+        self.len = len;
+
+        Ok(())
+    }
+
+    fn read_into(&mut self, _buffer: &mut [u8]) -> nb::Result<(), Self::Error> {
+        Err(nb::Error::WouldBlock)
+    }
+
+    fn peek(&mut self, _n: usize) -> nb::Result<u8, Self::Error> {
+        Err(nb::Error::WouldBlock)
+    }
+}
+
+impl Firmware for CommandPacket {
+    fn version(&self) -> Option<crate::Version> {
+        self.version
+    }
+
+    fn dialect(&self) -> Dialect {
+        self.dialect
+    }
+}
+
+/// Future that returns `Pending` exactly once, giving other tasks a chance to run.
+struct YieldNow {
+    yielded: bool,
+}
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            Poll::Ready(())
+        } else {
+            self.yielded = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+fn yield_now() -> YieldNow {
+    YieldNow { yielded: false }
+}
+
+impl<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, SpiError, GpioError, RxBuffer>
+    AsyncActiveBlueNRG<
+        'bnrg,
+        'spi,
+        'dbuf,
+        SPI,
+        OutputPin1,
+        OutputPin2,
+        InputPin,
+        GpioError,
+        RxBuffer,
+    >
+where
+    SPI: SpiBus<u8, Error = SpiError>,
+    OutputPin1: emhal::digital::v2::OutputPin<Error = GpioError>,
+    OutputPin2: emhal::digital::v2::OutputPin<Error = GpioError>,
+    InputPin: emhal::digital::v2::InputPin<Error = GpioError> + Wait<Error = GpioError>,
+    RxBuffer: AsRef<[u8]> + AsMut<[u8]>,
+{
+    fn set_chip_select(&mut self, high: bool) -> Result<(), Error<SpiError, GpioError>> {
+        if high {
+            self.d.chip_select.set_high().map_err(Error::Gpio)
+        } else {
+            self.d.chip_select.set_low().map_err(Error::Gpio)
+        }
+    }
+
+    /// Records an attempt that failed because the controller was not ready, and returns
+    /// [`Error::Timeout`] once the retry limit set by [`BlueNRG::set_retry_limit`] is reached.
+    fn count_retry(&mut self) -> Result<(), Error<SpiError, GpioError>> {
+        if self.d.count_retry() {
+            Err(Error::Timeout)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Wait for the chip to respond that it is awake and ready. The chip select line is toggled
+    /// between SPI headers, and the task yields to the executor before each retry.
+    ///
+    /// On entry, the chip select line must be low. On exit, the chip select line is low.
+    ///
+    /// Returns the number of bytes that can be written to the chip, and the number of bytes that
+    /// should be read from the chip, or [`Error::Timeout`] if the retry limit runs out first.
+    async fn wait_until_ready(
+        &mut self,
+        access: &Access,
+    ) -> Result<(u16, u16), Error<SpiError, GpioError>> {
+        loop {
+            let mut header = [access.byte(), 0x00, 0x00, 0x00, 0x00];
+            self.spi
+                .transfer_in_place(&mut header)
+                .await
+                .map_err(Error::Spi)?;
+
+            if let Ok(lengths) = crate::parse_spi_header::<()>(&header) {
+                return Ok(lengths);
+            }
+
+            self.set_chip_select(true)?;
+            yield_now().await;
+            self.set_chip_select(false)?;
+            self.count_retry()?;
+        }
+    }
+
+    /// Writes the header and payload to the controller as a single SPI transaction.
+    ///
+    /// Waits until the controller is awake and reports enough room for both the header and the
+    /// payload. If the packet is a command, it uses up one of the controller's command credits.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`WriteError::NoCommandCredits`] without writing anything if the packet is a
+    ///   command and the controller cannot accept another command yet. Read events until a credit
+    ///   comes back, then try again.
+    ///
+    /// - Returns [`WriteError::Comm`] if there is an error communicating over the SPI bus or
+    ///   setting the chip select pin, or with [`Error::Timeout`] if the retry limit set by
+    ///   [`BlueNRG::set_retry_limit`] runs out first.
+    pub async fn write(
+        &mut self,
+        header: &[u8],
+        payload: &[u8],
+    ) -> Result<(), WriteError<Error<SpiError, GpioError>>> {
+        let is_command = header.first() == Some(&PACKET_TYPE_HCI_COMMAND);
+        if is_command && self.d.command_credits == 0 {
+            return Err(WriteError::NoCommandCredits);
+        }
+
+        self.write_when_ready(header, payload)
+            .await
+            .map_err(WriteError::Comm)?;
+        if is_command {
+            self.d.command_credits -= 1;
+            self.d.outstanding_commands = self.d.outstanding_commands.saturating_add(1);
+        }
+
+        Ok(())
+    }
+
+    async fn write_when_ready(
+        &mut self,
+        header: &[u8],
+        payload: &[u8],
+    ) -> Result<(), Error<SpiError, GpioError>> {
+        loop {
+            self.set_chip_select(false)?;
+            let write_len = match self.wait_until_ready(&Access::Write).await {
+                Ok((write_len, _)) => write_len,
+                Err(e) => {
+                    self.set_chip_select(true)?;
+                    return Err(e);
+                }
+            };
+            if (write_len as usize) >= header.len() + payload.len() {
+                break;
+            }
+
+            self.set_chip_select(true)?;
+            self.count_retry()?;
+            yield_now().await;
+        }
+
+        let result = self.try_write(header, payload).await;
+        self.set_chip_select(true)?;
+        self.d.retries = 0;
+
+        result
+    }
+
+    async fn try_write(
+        &mut self,
+        header: &[u8],
+        payload: &[u8],
+    ) -> Result<(), Error<SpiError, GpioError>> {
+        if !header.is_empty() {
+            self.spi.write(header).await.map_err(Error::Spi)?;
+        }
+        if !payload.is_empty() {
+            self.spi.write(payload).await.map_err(Error::Spi)?;
+        }
+
+        self.spi.flush().await.map_err(Error::Spi)
+    }
+
+    /// Encodes a command with the given closure and writes it to the controller.
+    ///
+    /// The closure receives a [`CommandPacket`], which accepts any of the vendor-specific or
+    /// standard HCI commands. If the closure does not write a command, nothing is sent.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`SendError::Build`] if the closure returns an error.
+    /// - Returns [`SendError::PacketFull`] if the closure tries to write more than one command.
+    /// - Returns [`SendError::NoCommandCredits`] if the controller cannot accept another command
+    ///   yet. Read events until a credit comes back, then try again.
+    /// - Returns [`SendError::Comm`] if there is an error communicating with the controller.
+    pub async fn send<F, BuildError>(
+        &mut self,
+        build: F,
+    ) -> Result<(), SendError<BuildError, Error<SpiError, GpioError>>>
+    where
+        F: FnOnce(&mut CommandPacket) -> nb::Result<(), BuildError>,
+    {
+        let mut packet = CommandPacket::new(self.d.version, self.d.dialect);
+        match build(&mut packet) {
+            Ok(()) => (),
+            Err(nb::Error::WouldBlock) => return Err(SendError::PacketFull),
+            Err(nb::Error::Other(e)) => return Err(SendError::Build(e)),
+        }
+
+        if packet.len == 0 {
+            return Ok(());
+        }
+
+        self.write(packet.header(), packet.payload())
+            .await
+            .map_err(SendError::from)
+    }
+
+    /// Waits for the controller to signal that it has data ready, then reads the available data
+    /// into the host's RX buffer. Returns [`Error::RxOverflow`] if the RX buffer fills up first, in
+    /// which case the rest of the data is read and thrown away.
+    async fn read_available_data(&mut self) -> Result<(), Error<SpiError, GpioError>> {
+        self.d
+            .data_ready
+            .wait_for_high()
+            .await
+            .map_err(Error::Gpio)?;
+
+        self.set_chip_select(false)?;
+        let result = self.transfer_available_data().await;
+        let result = self.set_chip_select(true).and(result);
+        if let Err(ref e) = result {
+            self.d.rx_stats.record_error(e);
+        }
+
+        result
+    }
+
+    async fn transfer_available_data(&mut self) -> Result<(), Error<SpiError, GpioError>> {
+        let (_, read_len) = self.wait_until_ready(&Access::Read).await?;
+        let mut bytes_available = read_len as usize;
+        while bytes_available > 0 && self.d.rx_buffer.next_contiguous_slice_len() > 0 {
+            let transfer_count = min(
+                bytes_available,
+                self.d.rx_buffer.next_contiguous_slice_len(),
+            );
+            let started = self.d.rx_time();
+            {
+                let rx = self.d.rx_buffer.next_mut_slice(transfer_count);
+                for byte in rx.iter_mut() {
+                    *byte = 0;
+                }
+                self.spi.transfer_in_place(rx).await.map_err(Error::Spi)?;
+            }
+            self.d.record_rx(transfer_count, started);
+            bytes_available -= transfer_count;
+        }
+
+        self.d.retries = 0;
+        if bytes_available > 0 {
+            self.discard(bytes_available).await?;
+            self.d.drop_rx_overflow(bytes_available);
+            return Err(Error::RxOverflow);
+        }
+
+        Ok(())
+    }
+
+    /// Reads and throws away `len` bytes from the controller.
+    async fn discard(&mut self, mut len: usize) -> Result<(), Error<SpiError, GpioError>> {
+        let mut scratch = [0; 16];
+        while len > 0 {
+            let transfer_count = min(len, scratch.len());
+            let rx = &mut scratch[..transfer_count];
+            for byte in rx.iter_mut() {
+                *byte = 0;
+            }
+            self.spi.transfer_in_place(rx).await.map_err(Error::Spi)?;
+            len -= transfer_count;
+        }
+
+        Ok(())
+    }
+
+    /// Fills the given buffer with data from the controller, waiting for more data to become
+    /// available as necessary.
+    pub async fn read_into(&mut self, buffer: &mut [u8]) -> Result<(), Error<SpiError, GpioError>> {
+        while buffer.len() > self.d.rx_buffer.size() {
+            self.read_available_data().await?;
+        }
+
+        self.d.rx_buffer.take_slice(buffer.len(), buffer);
+        self.d.update_command_credits(buffer);
+        Ok(())
+    }
+
+    /// Returns the byte at index `n` of the data from the controller without consuming it,
+    /// waiting for more data to become available as necessary.
+    pub async fn peek(&mut self, n: usize) -> Result<u8, Error<SpiError, GpioError>> {
+        while n >= self.d.rx_buffer.size() {
+            self.read_available_data().await?;
+        }
+
+        Ok(self.d.rx_buffer.peek(n))
+    }
+
+    /// Reads the next HCI packet from the controller, waiting until a complete packet is
+    /// available, and deserializes it in the controller's [dialect](crate::dialect).
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`BadPacketType`](hci::host::uart::Error::BadPacketType) if the packet is not
+    ///   an HCI event.
+    /// - Returns [`BLE`](hci::host::uart::Error::BLE) if the event cannot be deserialized.
+    /// - Returns [`Comm`](hci::host::uart::Error::Comm) if there is an error communicating with
+    ///   the controller.
+    pub async fn read(
+        &mut self,
+    ) -> Result<
+        Packet<event::BlueNRGEvent>,
+        hci::host::uart::Error<Error<SpiError, GpioError>, event::BlueNRGError>,
+    > {
+        const MAX_EVENT_LENGTH: usize = 255;
+        const PACKET_HEADER_LENGTH: usize = 1;
+        const EVENT_PACKET_HEADER_LENGTH: usize = 3;
+        const PARAM_LEN_BYTE: usize = 2;
+
+        match self.peek(0).await.map_err(hci::host::uart::Error::Comm)? {
+            PACKET_TYPE_HCI_EVENT => (),
+            x => return Err(hci::host::uart::Error::BadPacketType(x)),
+        }
+
+        let param_len = self
+            .peek(PARAM_LEN_BYTE)
+            .await
+            .map_err(hci::host::uart::Error::Comm)? as usize;
+
+        let mut buf = [0; MAX_EVENT_LENGTH + EVENT_PACKET_HEADER_LENGTH];
+        self.read_into(&mut buf[..EVENT_PACKET_HEADER_LENGTH + param_len])
+            .await
+            .map_err(hci::host::uart::Error::Comm)?;
+
+        crate::event::with_dialect(
+            self.d.dialect,
+            &buf[PACKET_HEADER_LENGTH..EVENT_PACKET_HEADER_LENGTH + param_len],
+        )
+        .map(Packet::Event)
+        .map_err(hci::host::uart::Error::BLE)
+    }
+}
+
+impl<'buf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer>
+    BlueNRG<'buf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer>
+where
+    OutputPin1: emhal::digital::v2::OutputPin<Error = GpioError>,
+    OutputPin2: emhal::digital::v2::OutputPin<Error = GpioError>,
+    InputPin: emhal::digital::v2::InputPin<Error = GpioError>,
+    RxBuffer: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Invokes the given body function with an [`AsyncActiveBlueNRG`] that uses this BlueNRG struct
+    /// and the provided async SPI bus handle, and awaits the future it returns.
+    ///
+    /// The body takes the `AsyncActiveBlueNRG` by value, so the returned future can own it:
+    ///
+    /// ```ignore
+    /// bnrg.with_spi_async(&mut spi, |mut controller| async move {
+    ///     controller.read().await
+    /// })
+    /// .await
+    /// ```
+    ///
+    /// Returns the result of the invoked body.
+    pub async fn with_spi_async<'a, T, F, Fut>(&'a mut self, spi: &'a mut SPI, body: F) -> T
+    where
+        F: FnOnce(
+            AsyncActiveBlueNRG<
+                'a,
+                'a,
+                'buf,
+                SPI,
+                OutputPin1,
+                OutputPin2,
+                InputPin,
+                GpioError,
+                RxBuffer,
+            >,
+        ) -> Fut,
+        Fut: Future<Output = T>,
+        SPI: SpiBus<u8>,
+    {
+        let active =
+            AsyncActiveBlueNRG::<SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer> {
+                spi,
+                d: self,
+            };
+        body(active).await
+    }
+}
@@ -0,0 +1,381 @@
+//! Full bring-up sequence for the controller.
+//!
+//! Every application starts the controller the same way: reset it, wait for it to report that it
+//! has initialized, read its version, write the low-level configuration, and initialize the GATT
+//! and GAP layers. [`BlueNRG::bring_up`] runs that sequence and reports which step failed.
+
+use crate::event::command::{GapInit, ReturnParameters};
+use crate::event::{BlueNRGError, BlueNRGEvent, ResetReason};
+use crate::hal::ConfigData;
+use crate::{ActiveBlueNRG, BlueNRG, Error, LocalVersionInfoExt, Timeout, Version};
+use hci::event::command::ReturnParameters as HciReturnParameters;
+use hci::host::uart::Packet;
+use hci::Event;
+
+/// Parameters for [`BlueNRG::bring_up`].
+pub struct BringUpConfig<'a, Time> {
+    /// Time to hold the reset pin low, and then to wait after releasing it. Passed to
+    /// [`BlueNRG::reset`].
+    pub reset_time: Time,
+
+    /// Maximum time to wait for the controller to make progress: to become ready for an SPI
+    /// transfer, to accept a command, or to return an event. The wait starts over whenever the
+    /// controller makes progress.
+    pub timeout: Time,
+
+    /// Configuration data to write, in order. Each entry is written with a separate
+    /// [`write_config_data`](crate::hal::Commands::write_config_data) command.
+    pub config_data: &'a [ConfigData],
+
+    /// Role passed to [`gap::Commands::init`](crate::gap::Commands::init).
+    pub gap_role: crate::gap::Role,
+
+    /// Whether privacy is enabled. Passed to [`gap::Commands::init`](crate::gap::Commands::init).
+    #[cfg(not(feature = "bluenrg2"))]
+    pub privacy_enabled: bool,
+
+    /// Privacy mode. Passed to [`gap::Commands::init`](crate::gap::Commands::init).
+    #[cfg(feature = "bluenrg2")]
+    pub privacy: crate::gap::Privacy,
+
+    /// Length of the device name characteristic. Passed to
+    /// [`gap::Commands::init`](crate::gap::Commands::init).
+    pub dev_name_characteristic_len: u8,
+}
+
+/// Results of a successful [`BlueNRG::bring_up`].
+#[derive(Copy, Clone, Debug)]
+pub struct BringUp {
+    /// Version of the controller hardware and firmware.
+    pub version: Version,
+
+    /// Handles returned by [`gap::Commands::init`](crate::gap::Commands::init).
+    pub gap: GapInit,
+}
+
+/// Steps of the bring-up sequence.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Step {
+    /// Toggling the reset pin.
+    Reset,
+
+    /// Waiting for the [`HalInitialized`](BlueNRGEvent::HalInitialized) event.
+    HalInitialized,
+
+    /// Reading the local version information.
+    ReadLocalVersion,
+
+    /// Writing configuration data. Includes the index of the entry in
+    /// [`config_data`](BringUpConfig::config_data).
+    WriteConfigData(usize),
+
+    /// Initializing the GATT layer.
+    GattInit,
+
+    /// Initializing the GAP layer.
+    GapInit,
+}
+
+/// Reasons a step of the bring-up sequence can fail.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Cause<E> {
+    /// There was an error communicating with the controller.
+    Comm(E),
+
+    /// The controller did not become ready, accept the command, or return the expected event
+    /// before the timeout.
+    Timeout,
+
+    /// The controller returned a packet that is not an HCI event. Includes the packet type byte.
+    BadPacketType(u8),
+
+    /// The controller returned an event that could not be deserialized.
+    BadEvent(hci::event::Error<BlueNRGError>),
+
+    /// The controller initialized for a reason other than a normal start-up.
+    UnexpectedResetReason(ResetReason),
+
+    /// The command failed with the given status.
+    CommandFailed(hci::Status<crate::event::Status>),
+
+    /// The GAP layer could not be initialized with the configured parameters, for example because
+    /// they cannot be encoded in the controller's [dialect](crate::dialect). Includes the error.
+    Gap(crate::gap::Error<E>),
+}
+
+/// Error returned by [`BlueNRG::bring_up`], naming the step that failed and why.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BringUpError<E> {
+    /// The step that failed.
+    pub step: Step,
+
+    /// Why it failed.
+    pub cause: Cause<E>,
+}
+
+impl<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, SpiError, GpioError, RxBuffer>
+    ActiveBlueNRG<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer>
+where
+    SPI: emhal::blocking::spi::Transfer<u8, Error = SpiError>
+        + emhal::blocking::spi::Write<u8, Error = SpiError>,
+    OutputPin1: emhal::digital::v2::OutputPin<Error = GpioError>,
+    OutputPin2: emhal::digital::v2::OutputPin<Error = GpioError>,
+    InputPin: emhal::digital::v2::InputPin<Error = GpioError>,
+    RxBuffer: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Sends a command, retrying until the controller accepts it or the deadline expires.
+    fn send_until<F>(
+        &mut self,
+        step: Step,
+        mut send: F,
+    ) -> Result<(), BringUpError<Error<SpiError, GpioError>>>
+    where
+        F: FnMut(&mut Self) -> nb::Result<(), Cause<Error<SpiError, GpioError>>>,
+    {
+        loop {
+            let cause = match send(self) {
+                Ok(()) => return Ok(()),
+                Err(nb::Error::WouldBlock) => match self.count_wait() {
+                    Ok(()) => continue,
+                    Err(e) => cause_of(e),
+                },
+                Err(nb::Error::Other(cause)) => cause,
+            };
+
+            return Err(BringUpError { step, cause });
+        }
+    }
+
+    /// Reads events until `select` returns a value for one of them, or the deadline expires.
+    /// Events for which `select` returns `None` are discarded.
+    fn wait_for<F, R>(
+        &mut self,
+        step: Step,
+        mut select: F,
+    ) -> Result<R, BringUpError<Error<SpiError, GpioError>>>
+    where
+        F: FnMut(Event<BlueNRGEvent>) -> Option<Result<R, Cause<Error<SpiError, GpioError>>>>,
+    {
+        loop {
+            let cause = match crate::event::read(self) {
+                Ok(Packet::Event(event)) => match select(event) {
+                    Some(Ok(value)) => return Ok(value),
+                    Some(Err(cause)) => cause,
+                    None => continue,
+                },
+                Err(nb::Error::WouldBlock) => match self.count_wait() {
+                    Ok(()) => continue,
+                    Err(e) => cause_of(e),
+                },
+                Err(nb::Error::Other(hci::host::uart::Error::Comm(e))) => cause_of(e),
+                Err(nb::Error::Other(hci::host::uart::Error::BadPacketType(packet_type))) => {
+                    Cause::BadPacketType(packet_type)
+                }
+                Err(nb::Error::Other(hci::host::uart::Error::BLE(e))) => Cause::BadEvent(e),
+            };
+
+            return Err(BringUpError { step, cause });
+        }
+    }
+
+    /// Resets the controller and runs the bring-up sequence on this handle, like
+    /// [`BlueNRG::bring_up`]. Use it in a session started with
+    /// [`with_spi_options`](BlueNRG::with_spi_options), for example to capture the bring-up.
+    ///
+    /// # Errors
+    ///
+    /// See [`BlueNRG::bring_up`].
+    pub fn bring_up<Timer>(
+        &mut self,
+        timer: &mut Timer,
+        config: &BringUpConfig<Timer::Time>,
+    ) -> Result<BringUp, BringUpError<Error<SpiError, GpioError>>>
+    where
+        Timer: emhal::timer::CountDown,
+        Timer::Time: Copy,
+    {
+        self.d
+            .reset(timer, config.reset_time)
+            .map_err(|e| BringUpError {
+                step: Step::Reset,
+                cause: match e {
+                    nb::Error::Other(e) => Cause::Comm(Error::Gpio(e)),
+                    nb::Error::WouldBlock => Cause::Timeout,
+                },
+            })?;
+
+        // Every wait for the controller, including the waits within a single SPI transfer, is
+        // bounded by the timeout, whether or not this handle already has a deadline.
+        let mut deadline = Timeout::new(timer, config.timeout);
+        let mut controller = ActiveBlueNRG {
+            d: &mut *self.d,
+            spi: &mut *self.spi,
+            deadline: Some(&mut deadline),
+            deadline_running: false,
+            capture: match self.capture {
+                Some(ref mut capture) => Some(&mut **capture),
+                None => None,
+            },
+        };
+        controller.initialize(config)
+    }
+
+    /// Runs the bring-up sequence after the reset.
+    fn initialize<Time>(
+        &mut self,
+        config: &BringUpConfig<Time>,
+    ) -> Result<BringUp, BringUpError<Error<SpiError, GpioError>>> {
+        self.wait_for(Step::HalInitialized, |event| match event {
+            Event::Vendor(BlueNRGEvent::HalInitialized(ResetReason::Normal)) => Some(Ok(())),
+            Event::Vendor(BlueNRGEvent::HalInitialized(reason)) => {
+                Some(Err(Cause::UnexpectedResetReason(reason)))
+            }
+            _ => None,
+        })?;
+
+        self.send_until(Step::ReadLocalVersion, |controller| {
+            hci::host::Hci::read_local_version_information(controller).map_err(|e| e.map(cause_of))
+        })?;
+        let version = self.wait_for(Step::ReadLocalVersion, |event| {
+            match return_params(event)? {
+                HciReturnParameters::ReadLocalVersionInformation(info) => {
+                    Some(check_status(info.status).map(|_| info.bluenrg_version()))
+                }
+                _ => None,
+            }
+        })?;
+        self.d.set_version(version);
+
+        for (index, config_data) in config.config_data.iter().enumerate() {
+            let step = Step::WriteConfigData(index);
+            self.send_until(step, |controller| {
+                crate::hal::Commands::write_config_data(controller, config_data)
+                    .map_err(|e| e.map(cause_of))
+            })?;
+            self.wait_for(step, |event| match return_params(event)? {
+                HciReturnParameters::Vendor(ReturnParameters::HalWriteConfigData(status)) => {
+                    Some(check_status(status))
+                }
+                _ => None,
+            })?;
+        }
+
+        self.send_until(Step::GattInit, |controller| {
+            crate::gatt::Commands::init(controller).map_err(|e| e.map(cause_of))
+        })?;
+        self.wait_for(Step::GattInit, |event| match return_params(event)? {
+            HciReturnParameters::Vendor(ReturnParameters::GattInit(status)) => {
+                Some(check_status(status))
+            }
+            _ => None,
+        })?;
+
+        self.send_until(Step::GapInit, |controller| {
+            #[cfg(not(feature = "bluenrg2"))]
+            {
+                crate::gap::Commands::init(
+                    controller,
+                    config.gap_role,
+                    config.privacy_enabled,
+                    config.dev_name_characteristic_len,
+                )
+                .map_err(|e| {
+                    e.map(|e| match e {
+                        crate::gap::Error::Comm(e) => cause_of(e),
+                        e => Cause::Gap(e),
+                    })
+                })
+            }
+            #[cfg(feature = "bluenrg2")]
+            {
+                crate::gap::Commands::init(
+                    controller,
+                    config.gap_role,
+                    config.privacy,
+                    config.dev_name_characteristic_len,
+                )
+                .map_err(|e| e.map(cause_of))
+            }
+        })?;
+        let gap = self.wait_for(Step::GapInit, |event| match return_params(event)? {
+            HciReturnParameters::Vendor(ReturnParameters::GapInit(gap)) => {
+                Some(check_status(gap.status).map(|_| gap))
+            }
+            _ => None,
+        })?;
+
+        Ok(BringUp { version, gap })
+    }
+}
+
+/// Returns the cause for an error communicating with the controller. A timeout means the
+/// controller made no progress before the deadline expired.
+fn cause_of<E, GpioError>(e: Error<E, GpioError>) -> Cause<Error<E, GpioError>> {
+    match e {
+        Error::Timeout => Cause::Timeout,
+        e => Cause::Comm(e),
+    }
+}
+
+/// Returns the return parameters of a command complete event.
+fn return_params(event: Event<BlueNRGEvent>) -> Option<HciReturnParameters<BlueNRGEvent>> {
+    match event {
+        Event::CommandComplete(complete) => Some(complete.return_params),
+        _ => None,
+    }
+}
+
+fn check_status<E>(status: hci::Status<crate::event::Status>) -> Result<(), Cause<E>> {
+    match status {
+        hci::Status::Success => Ok(()),
+        _ => Err(Cause::CommandFailed(status)),
+    }
+}
+
+impl<'buf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer>
+    BlueNRG<'buf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer>
+where
+    OutputPin1: emhal::digital::v2::OutputPin<Error = GpioError>,
+    OutputPin2: emhal::digital::v2::OutputPin<Error = GpioError>,
+    InputPin: emhal::digital::v2::InputPin<Error = GpioError>,
+    RxBuffer: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Resets and initializes the controller.
+    ///
+    /// The sequence is:
+    ///  1. [Reset](BlueNRG::reset) the controller.
+    ///  2. Wait for the [`HalInitialized`](BlueNRGEvent::HalInitialized) event, and check that the
+    ///     reset reason is [`Normal`](ResetReason::Normal).
+    ///  3. Read the local version information, convert it to a [`Version`], and
+    ///     [record it](BlueNRG::set_version), which also sets the dialect it implies.
+    ///  4. Write each entry of the [configuration data](BringUpConfig::config_data).
+    ///  5. Initialize the GATT layer with [`gatt::Commands::init`](crate::gatt::Commands::init).
+    ///  6. Initialize the GAP layer with [`gap::Commands::init`](crate::gap::Commands::init).
+    ///
+    /// Events that arrive while waiting for a step, but are not part of it, are discarded.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`BringUpError`] naming the step that failed and why. A step fails with
+    /// [`Cause::Timeout`] if the controller makes no progress within
+    /// [`timeout`](BringUpConfig::timeout), even in the middle of an SPI transfer. The
+    /// [retry limit](BlueNRG::set_retry_limit) only bounds the SPI handshake with the controller,
+    /// not the waits for its events.
+    ///
+    /// To capture the bring-up, call [`ActiveBlueNRG::bring_up`] in a session started with
+    /// [`with_spi_options`](BlueNRG::with_spi_options) instead.
+    pub fn bring_up<E, Timer>(
+        &mut self,
+        spi: &mut SPI,
+        timer: &mut Timer,
+        config: &BringUpConfig<Timer::Time>,
+    ) -> Result<BringUp, BringUpError<Error<E, GpioError>>>
+    where
+        SPI: emhal::blocking::spi::transfer::Default<u8, Error = E>
+            + emhal::blocking::spi::write::Default<u8, Error = E>,
+        Timer: emhal::timer::CountDown,
+        Timer::Time: Copy,
+    {
+        self.with_spi(spi, |controller| controller.bring_up(timer, config))
+    }
+}
@@ -0,0 +1,63 @@
+//! Commands and events supported by each controller firmware version.
+//!
+//! Newer BlueNRG-MS firmware adds vendor-specific commands and events. Older firmware answers the
+//! new commands with an `UnknownCommand` status, and never generates the new events. Once the
+//! controller's [`Version`] is known, the commands it does not support fail early with an
+//! `Unsupported` error ([`gap::Error::Unsupported`](crate::gap::Error::Unsupported) or
+//! [`gatt::Error::Unsupported`](crate::gatt::Error::Unsupported)) instead of being sent.
+//!
+//! The version is recorded with [`BlueNRG::set_version`](crate::BlueNRG::set_version), which
+//! [`BlueNRG::bring_up`](crate::BlueNRG::bring_up) does itself. The command traits read it back
+//! through the controller's [`Firmware`](crate::Firmware) implementation. Until a version is
+//! recorded, every capability is assumed to be supported.
+
+use crate::Version;
+
+/// A vendor-specific command or event that is not supported by every firmware version.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Capability {
+    /// The [GAP Set Broadcast Mode](crate::gap::Commands::set_broadcast_mode) command.
+    SetBroadcastMode,
+
+    /// The [GAP Start Observation
+    /// Procedure](crate::gap::Commands::start_observation_procedure) command.
+    StartObservationProcedure,
+
+    /// The [GATT Read Handle Value
+    /// Offset](crate::gatt::Commands::read_handle_value_offset) command.
+    ReadHandleValueOffset,
+
+    /// The [GATT Update Long Characteristic
+    /// Value](crate::gatt::Commands::update_long_characteristic_value) command.
+    UpdateLongCharacteristicValue,
+
+    /// The [GATT TX Pool Available](crate::event::BlueNRGEvent::GattTxPoolAvailable) event.
+    GattTxPoolAvailable,
+
+    /// The [Events Lost](crate::event::BlueNRGEvent::EventsLost) event.
+    EventsLost,
+
+    /// The [Crash Report](crate::event::BlueNRGEvent::CrashReport) event.
+    CrashReport,
+}
+
+impl Capability {
+    /// Returns the oldest firmware version, as (major, minor), that supports this capability.
+    pub fn min_version(self) -> (u8, u8) {
+        match self {
+            Capability::SetBroadcastMode
+            | Capability::StartObservationProcedure
+            | Capability::GattTxPoolAvailable => (7, 0),
+            Capability::ReadHandleValueOffset | Capability::UpdateLongCharacteristicValue => (7, 1),
+            Capability::EventsLost | Capability::CrashReport => (7, 2),
+        }
+    }
+
+    /// Returns true if a controller with the given version supports this capability.
+    ///
+    /// The BlueNRG-1 and BlueNRG-2 stacks have their own version numbers, and support every
+    /// capability, so with the `bluenrg2` feature this always returns true.
+    pub fn supported_by(self, version: &Version) -> bool {
+        cfg!(feature = "bluenrg2") || (version.major, version.minor) >= self.min_version()
+    }
+}
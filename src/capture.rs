@@ -0,0 +1,252 @@
+//! Capture of the HCI traffic between the host and the controller.
+//!
+//! A [`Capture`] records every command written to the controller and every packet read from it,
+//! as they cross the SPI bus, in one of two file [formats](Format) that Wireshark opens directly:
+//!
+//! - [btsnoop](Format::Btsnoop), the format of the Android and BlueZ HCI logs, with the HCI UART
+//!   (H4) datalink.
+//! - [pcap](Format::Pcap), with the `LINKTYPE_BLUETOOTH_HCI_H4_WITH_PHDR` link type.
+//!
+//! The capture writes the file to a [`Sink`], and stamps each packet with the time from a
+//! [`Clock`]. To capture the traffic of a [`BlueNRG`](crate::BlueNRG), talk to it with
+//! [`with_spi_options`](crate::BlueNRG::with_spi_options) and the
+//! [`capture`](crate::SpiOptions::capture) option, which combines with a
+//! [timeout](crate::SpiOptions::timeout). The file header is written with the first packet, so a
+//! capture can be kept across sessions to record a single trace.
+//!
+//! Errors from the sink do not fail the HCI operation that was being recorded. The capture counts
+//! them instead (see [`write_errors`](Capture::write_errors)); a trace with write errors is likely
+//! truncated or corrupt.
+
+use byteorder::{BigEndian, ByteOrder, LittleEndian};
+use core::time::Duration;
+
+/// Destination for the bytes of a capture file, such as a UART, a file, or a RAM buffer.
+pub trait Sink {
+    /// Error type returned when the bytes cannot be written.
+    type Error;
+
+    /// Writes all of the given bytes, in order.
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), Self::Error>;
+}
+
+/// Source of the timestamps of captured packets.
+pub trait Clock {
+    /// Returns the current time, as the time elapsed since the Unix epoch (1970-01-01 00:00:00
+    /// UTC). A clock that counts from power up works too, but Wireshark then shows dates in 1970.
+    fn now(&mut self) -> Duration;
+}
+
+/// File formats a [`Capture`] can write.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Format {
+    /// The btsnoop format, version 1, with the HCI UART (H4) datalink (1002).
+    Btsnoop,
+
+    /// The pcap format, version 2.4, with microsecond timestamps and the
+    /// `LINKTYPE_BLUETOOTH_HCI_H4_WITH_PHDR` link type (201).
+    Pcap,
+}
+
+/// Direction of a captured packet.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Direction {
+    /// The packet was written from the host to the controller.
+    Sent,
+
+    /// The packet was read by the host from the controller.
+    Received,
+}
+
+const BTSNOOP_MAGIC: &[u8; 8] = b"btsnoop\0";
+const BTSNOOP_VERSION: u32 = 1;
+const BTSNOOP_DATALINK_H4: u32 = 1002;
+
+/// Offset of the Unix epoch from the btsnoop epoch (midnight, January 1st of year 0), in
+/// microseconds.
+const BTSNOOP_EPOCH_OFFSET_MICROS: u64 = 0x00DC_DDB3_0F2F_8000;
+
+const BTSNOOP_FLAG_RECEIVED: u32 = 0x01;
+const BTSNOOP_FLAG_COMMAND_OR_EVENT: u32 = 0x02;
+
+const PCAP_MAGIC: u32 = 0xA1B2_C3D4;
+const PCAP_VERSION_MAJOR: u16 = 2;
+const PCAP_VERSION_MINOR: u16 = 4;
+const PCAP_SNAPLEN: u32 = 0xFFFF;
+const PCAP_LINKTYPE_BLUETOOTH_HCI_H4_WITH_PHDR: u32 = 201;
+
+/// Length of the pseudo-header that precedes each packet with the
+/// `LINKTYPE_BLUETOOTH_HCI_H4_WITH_PHDR` link type. It holds the direction, as a big-endian `u32`.
+const PCAP_PHDR_LEN: usize = 4;
+
+const PACKET_TYPE_HCI_COMMAND: u8 = 0x01;
+const PACKET_TYPE_HCI_EVENT: u8 = 0x04;
+
+/// Records HCI packets to a [`Sink`] in the given [`Format`], with timestamps from a [`Clock`].
+pub struct Capture<S, C> {
+    format: Format,
+    sink: S,
+    clock: C,
+    header_written: bool,
+    packets: u32,
+    write_errors: u32,
+}
+
+impl<S, C> Capture<S, C>
+where
+    S: Sink,
+    C: Clock,
+{
+    /// Returns a capture that writes the given format to `sink`, with timestamps from `clock`.
+    /// Nothing is written until the first packet is recorded.
+    pub fn new(format: Format, sink: S, clock: C) -> Capture<S, C> {
+        Capture {
+            format,
+            sink,
+            clock,
+            header_written: false,
+            packets: 0,
+            write_errors: 0,
+        }
+    }
+
+    /// Returns the format of the capture.
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Returns the number of packets recorded, including any whose write failed.
+    pub fn packets(&self) -> u32 {
+        self.packets
+    }
+
+    /// Returns the number of packets, or file headers, that could not be written to the sink.
+    pub fn write_errors(&self) -> u32 {
+        self.write_errors
+    }
+
+    /// Releases the sink and the clock.
+    pub fn release(self) -> (S, C) {
+        (self.sink, self.clock)
+    }
+
+    /// Records a packet that crossed the bus in the given direction. The packet is the
+    /// concatenation of `header` and `payload`, and starts with the HCI packet type (for example,
+    /// `0x01` for a command, or `0x04` for an event).
+    pub fn record(&mut self, direction: Direction, header: &[u8], payload: &[u8]) {
+        let timestamp = self.clock.now();
+        if !self.header_written {
+            self.header_written = true;
+            if self.write_file_header().is_err() {
+                self.write_errors = self.write_errors.saturating_add(1);
+            }
+        }
+
+        self.packets = self.packets.saturating_add(1);
+        if self
+            .write_packet(timestamp, direction, header, payload)
+            .is_err()
+        {
+            self.write_errors = self.write_errors.saturating_add(1);
+        }
+    }
+
+    fn write_file_header(&mut self) -> Result<(), S::Error> {
+        match self.format {
+            Format::Btsnoop => {
+                let mut header = [0; 16];
+                header[0..8].copy_from_slice(BTSNOOP_MAGIC);
+                BigEndian::write_u32(&mut header[8..12], BTSNOOP_VERSION);
+                BigEndian::write_u32(&mut header[12..16], BTSNOOP_DATALINK_H4);
+                self.sink.write_all(&header)
+            }
+            Format::Pcap => {
+                let mut header = [0; 24];
+                LittleEndian::write_u32(&mut header[0..4], PCAP_MAGIC);
+                LittleEndian::write_u16(&mut header[4..6], PCAP_VERSION_MAJOR);
+                LittleEndian::write_u16(&mut header[6..8], PCAP_VERSION_MINOR);
+                // Time zone offset and timestamp accuracy (8..16) are always 0.
+                LittleEndian::write_u32(&mut header[16..20], PCAP_SNAPLEN);
+                LittleEndian::write_u32(
+                    &mut header[20..24],
+                    PCAP_LINKTYPE_BLUETOOTH_HCI_H4_WITH_PHDR,
+                );
+                self.sink.write_all(&header)
+            }
+        }
+    }
+
+    fn write_packet(
+        &mut self,
+        timestamp: Duration,
+        direction: Direction,
+        header: &[u8],
+        payload: &[u8],
+    ) -> Result<(), S::Error> {
+        let len = header.len() + payload.len();
+        match self.format {
+            Format::Btsnoop => {
+                let mut flags = match direction {
+                    Direction::Sent => 0,
+                    Direction::Received => BTSNOOP_FLAG_RECEIVED,
+                };
+                let packet_type = header.first().or_else(|| payload.first()).copied();
+                if let Some(PACKET_TYPE_HCI_COMMAND) | Some(PACKET_TYPE_HCI_EVENT) = packet_type {
+                    flags |= BTSNOOP_FLAG_COMMAND_OR_EVENT;
+                }
+
+                let mut record = [0; 24];
+                BigEndian::write_u32(&mut record[0..4], len as u32);
+                BigEndian::write_u32(&mut record[4..8], len as u32);
+                BigEndian::write_u32(&mut record[8..12], flags);
+                // Cumulative drops (12..16) are always 0.
+                BigEndian::write_u64(
+                    &mut record[16..24],
+                    (timestamp.as_micros() as u64).wrapping_add(BTSNOOP_EPOCH_OFFSET_MICROS),
+                );
+                self.sink.write_all(&record)?;
+            }
+            Format::Pcap => {
+                let mut record = [0; 16 + PCAP_PHDR_LEN];
+                LittleEndian::write_u32(&mut record[0..4], timestamp.as_secs() as u32);
+                LittleEndian::write_u32(&mut record[4..8], timestamp.subsec_micros());
+                LittleEndian::write_u32(&mut record[8..12], (PCAP_PHDR_LEN + len) as u32);
+                LittleEndian::write_u32(&mut record[12..16], (PCAP_PHDR_LEN + len) as u32);
+                BigEndian::write_u32(
+                    &mut record[16..20],
+                    match direction {
+                        Direction::Sent => 0,
+                        Direction::Received => 1,
+                    },
+                );
+                self.sink.write_all(&record)?;
+            }
+        }
+
+        if !header.is_empty() {
+            self.sink.write_all(header)?;
+        }
+        if !payload.is_empty() {
+            self.sink.write_all(payload)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Object-safe wrapper around a [`Capture`], so its sink and clock types do not leak into
+/// [`ActiveBlueNRG`](crate::ActiveBlueNRG).
+pub(crate) trait Tap {
+    /// Records a packet. See [`Capture::record`].
+    fn record(&mut self, direction: Direction, header: &[u8], payload: &[u8]);
+}
+
+impl<S, C> Tap for Capture<S, C>
+where
+    S: Sink,
+    C: Clock,
+{
+    fn record(&mut self, direction: Direction, header: &[u8], payload: &[u8]) {
+        Capture::record(self, direction, header, payload);
+    }
+}
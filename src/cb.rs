@@ -1,36 +1,45 @@
 #![allow(dead_code)]
 
-pub struct Buffer<'a, T: 'a> {
-    buffer: &'a mut [T],
+use core::marker::PhantomData;
+
+pub struct Buffer<T, B> {
+    buffer: B,
     read_index: usize,
     write_index: usize,
+    _item: PhantomData<T>,
 }
 
-impl<'a, T> Buffer<'a, T>
+impl<T, B> Buffer<T, B>
 where
     T: Copy,
+    B: AsRef<[T]> + AsMut<[T]>,
 {
-    pub fn new(buffer: &'a mut [T]) -> Buffer<T> {
-        Buffer::<T> {
+    pub fn new(buffer: B) -> Buffer<T, B> {
+        Buffer {
             buffer,
             read_index: 0,
             write_index: 0,
+            _item: PhantomData,
         }
     }
 
+    fn capacity(&self) -> usize {
+        self.buffer.as_ref().len()
+    }
+
     pub fn size(&self) -> usize {
         if self.write_index >= self.read_index {
             self.write_index - self.read_index
         } else {
-            self.write_index + self.buffer.len() - self.read_index
+            self.write_index + self.capacity() - self.read_index
         }
     }
 
     pub fn next_contiguous_slice_len(&self) -> usize {
         if self.read_index == 0 {
-            self.buffer.len() - self.write_index - 1
+            self.capacity() - self.write_index - 1
         } else if self.write_index >= self.read_index {
-            self.buffer.len() - self.write_index
+            self.capacity() - self.write_index
         } else {
             self.read_index - self.write_index - 1
         }
@@ -46,13 +55,45 @@ where
         }
 
         let start = self.write_index;
-        self.write_index = (self.write_index + n) % self.buffer.len();
-        &mut self.buffer[start..start + n]
+        self.write_index = (self.write_index + n) % self.capacity();
+        &mut self.buffer.as_mut()[start..start + n]
+    }
+
+    // Unlike next_mut_slice, the bytes are not added to the buffer until they are committed.
+    pub fn free_mut_slices(&mut self, n: usize) -> (&mut [T], &mut [T]) {
+        if n > self.available_len() {
+            panic!(
+                "Not enough space to write into (wanted {}, have {})",
+                n,
+                self.available_len()
+            );
+        }
+
+        let start = self.write_index;
+        let (head, tail) = self.buffer.as_mut().split_at_mut(start);
+        if n <= tail.len() {
+            (&mut tail[..n], &mut head[..0])
+        } else {
+            let wrapped = n - tail.len();
+            (tail, &mut head[..wrapped])
+        }
+    }
+
+    pub fn commit(&mut self, n: usize) {
+        if n > self.available_len() {
+            panic!(
+                "Not enough space to commit (wanted {}, have {})",
+                n,
+                self.available_len()
+            );
+        }
+
+        self.write_index = (self.write_index + n) % self.capacity();
     }
 
     pub fn available_len(&self) -> usize {
         if self.read_index <= self.write_index {
-            self.read_index + self.buffer.len() - self.write_index - 1
+            self.read_index + self.capacity() - self.write_index - 1
         } else {
             self.read_index - self.write_index - 1
         }
@@ -62,7 +103,7 @@ where
         if n >= self.size() {
             panic!("Peek out of range: {} requested, max {}", n, self.size());
         }
-        self.buffer[(self.read_index + n) % self.buffer.len()]
+        self.buffer.as_ref()[(self.read_index + n) % self.capacity()]
     }
 
     pub fn take_slice(&mut self, n: usize, buf: &mut [T]) {
@@ -74,9 +115,20 @@ where
             );
         }
         for (i, byte) in buf.iter_mut().enumerate().take(n) {
-            *byte = self.buffer[(self.read_index + i) % self.buffer.len()];
+            *byte = self.buffer.as_ref()[(self.read_index + i) % self.capacity()];
+        }
+        self.read_index = (self.read_index + n) % self.capacity();
+    }
+
+    pub fn truncate(&mut self, n: usize) {
+        if n > self.size() {
+            panic!(
+                "Not enough data to truncate (wanted {}, have {})",
+                n,
+                self.size()
+            );
         }
-        self.read_index = (self.read_index + n) % self.buffer.len();
+        self.write_index = (self.read_index + n) % self.capacity();
     }
 }
 
@@ -85,7 +137,7 @@ mod tests {
     fn empty_capacity() {
         const CAPACITY: usize = 8;
         let mut buf: [u8; CAPACITY] = [0; CAPACITY];
-        let cbuf = super::Buffer::<u8>::new(&mut buf);
+        let cbuf = super::Buffer::<u8, _>::new(&mut buf);
         assert_eq!(cbuf.available_len(), CAPACITY - 1);
         assert_eq!(cbuf.next_contiguous_slice_len(), CAPACITY - 1);
     }
@@ -94,12 +146,12 @@ mod tests {
     fn empty_capacity_after_use() {
         const CAPACITY: usize = 8;
         let mut buf: [u8; CAPACITY] = [0; CAPACITY];
-        let mut cbuf = super::Buffer::<u8>::new(&mut buf);
+        let mut cbuf = super::Buffer::<u8, _>::new(&mut buf);
         const TRANSFER_SIZE: usize = 4;
         {
             let writable = cbuf.next_mut_slice(TRANSFER_SIZE);
-            for i in 0..TRANSFER_SIZE {
-                writable[i] = 1 + i as u8;
+            for (i, byte) in writable.iter_mut().enumerate().take(TRANSFER_SIZE) {
+                *byte = 1 + i as u8;
             }
         }
         assert_eq!(cbuf.available_len(), CAPACITY - TRANSFER_SIZE - 1);
@@ -114,8 +166,8 @@ mod tests {
         {
             let mut read_from: [u8; TRANSFER_SIZE] = [0; TRANSFER_SIZE];
             cbuf.take_slice(TRANSFER_SIZE, &mut read_from);
-            for i in 0..TRANSFER_SIZE {
-                assert_eq!(read_from[i], 1 + i as u8);
+            for (i, byte) in read_from.iter().enumerate() {
+                assert_eq!(*byte, 1 + i as u8);
             }
         }
         assert_eq!(cbuf.available_len(), CAPACITY - 1);
@@ -127,12 +179,12 @@ mod tests {
     fn request_too_many_bytes_write() {
         const CAPACITY: usize = 8;
         let mut buf: [u8; CAPACITY] = [0; CAPACITY];
-        let mut cbuf = super::Buffer::<u8>::new(&mut buf);
+        let mut cbuf = super::Buffer::<u8, _>::new(&mut buf);
         const TRANSFER_SIZE: usize = 4;
         {
             let writable = cbuf.next_mut_slice(TRANSFER_SIZE);
-            for i in 0..TRANSFER_SIZE {
-                writable[i] = 1 + i as u8;
+            for (i, byte) in writable.iter_mut().enumerate().take(TRANSFER_SIZE) {
+                *byte = 1 + i as u8;
             }
         }
 
@@ -148,12 +200,12 @@ mod tests {
     fn request_too_many_bytes_read() {
         const CAPACITY: usize = 8;
         let mut buf: [u8; CAPACITY] = [0; CAPACITY];
-        let mut cbuf = super::Buffer::<u8>::new(&mut buf);
+        let mut cbuf = super::Buffer::<u8, _>::new(&mut buf);
         const TRANSFER_SIZE: usize = 4;
         {
             let writable = cbuf.next_mut_slice(TRANSFER_SIZE);
-            for i in 0..TRANSFER_SIZE {
-                writable[i] = 1 + i as u8;
+            for (i, byte) in writable.iter_mut().enumerate().take(TRANSFER_SIZE) {
+                *byte = 1 + i as u8;
             }
         }
 
@@ -166,12 +218,12 @@ mod tests {
     fn request_peek_too_far() {
         const CAPACITY: usize = 8;
         let mut buf: [u8; CAPACITY] = [0; CAPACITY];
-        let mut cbuf = super::Buffer::<u8>::new(&mut buf);
+        let mut cbuf = super::Buffer::<u8, _>::new(&mut buf);
         const TRANSFER_SIZE: usize = 4;
         {
             let writable = cbuf.next_mut_slice(TRANSFER_SIZE);
-            for i in 0..TRANSFER_SIZE {
-                writable[i] = 1 + i as u8;
+            for (i, byte) in writable.iter_mut().enumerate().take(TRANSFER_SIZE) {
+                *byte = 1 + i as u8;
             }
         }
 
@@ -182,13 +234,13 @@ mod tests {
     fn peek() {
         const CAPACITY: usize = 8;
         let mut buf: [u8; CAPACITY] = [0; CAPACITY];
-        let mut cbuf = super::Buffer::<u8>::new(&mut buf);
+        let mut cbuf = super::Buffer::<u8, _>::new(&mut buf);
 
         // Write 5 bytes (2 more available)
         {
             let writable = cbuf.next_mut_slice(5);
-            for i in 0..5 {
-                writable[i] = 1 + i as u8;
+            for (i, byte) in writable.iter_mut().enumerate().take(5) {
+                *byte = 1 + i as u8;
             }
         }
         assert_eq!(cbuf.size(), 5);
@@ -205,15 +257,15 @@ mod tests {
             {
                 let len = cbuf.next_contiguous_slice_len();
                 let writable = cbuf.next_mut_slice(len);
-                for i in 0..len {
-                    writable[i] = 6 + i as u8;
+                for (i, byte) in writable.iter_mut().enumerate().take(len) {
+                    *byte = 6 + i as u8;
                 }
             }
             {
                 let len = cbuf.next_contiguous_slice_len();
                 let writable = cbuf.next_mut_slice(len);
-                for i in 0..len {
-                    writable[i] = 9 + i as u8;
+                for (i, byte) in writable.iter_mut().enumerate().take(len) {
+                    *byte = 9 + i as u8;
                 }
             }
         }
@@ -223,4 +275,78 @@ mod tests {
             assert_eq!(4 + i as u8, cbuf.peek(i), "Index {}", i);
         }
     }
+
+    #[test]
+    fn truncate() {
+        const CAPACITY: usize = 8;
+        let mut buf: [u8; CAPACITY] = [0; CAPACITY];
+        let mut cbuf = super::Buffer::<u8, _>::new(&mut buf);
+
+        // Wrap around the end of the buffer
+        {
+            let writable = cbuf.next_mut_slice(6);
+            for (i, byte) in writable.iter_mut().enumerate() {
+                *byte = 1 + i as u8;
+            }
+        }
+        let mut read_from: [u8; 5] = [0; 5];
+        cbuf.take_slice(5, &mut read_from);
+        {
+            let len = cbuf.next_contiguous_slice_len();
+            let writable = cbuf.next_mut_slice(len);
+            for (i, byte) in writable.iter_mut().enumerate() {
+                *byte = 7 + i as u8;
+            }
+        }
+        {
+            let writable = cbuf.next_mut_slice(3);
+            for (i, byte) in writable.iter_mut().enumerate() {
+                *byte = 9 + i as u8;
+            }
+        }
+        assert_eq!(cbuf.size(), 6);
+
+        cbuf.truncate(3);
+        assert_eq!(cbuf.size(), 3);
+        assert_eq!(cbuf.peek(0), 6);
+        assert_eq!(cbuf.peek(2), 8);
+        assert_eq!(cbuf.available_len(), CAPACITY - 3 - 1);
+
+        cbuf.truncate(0);
+        assert_eq!(cbuf.size(), 0);
+    }
+
+    #[test]
+    fn free_mut_slices_wrapped() {
+        const CAPACITY: usize = 8;
+        let mut buf: [u8; CAPACITY] = [0; CAPACITY];
+        let mut cbuf = super::Buffer::<u8, _>::new(&mut buf);
+
+        // Move the indices to 5, leaving 3 bytes before the end and 4 after the start
+        {
+            cbuf.next_mut_slice(5);
+            let mut read_from: [u8; 5] = [0; 5];
+            cbuf.take_slice(5, &mut read_from);
+        }
+        {
+            let (first, second) = cbuf.free_mut_slices(6);
+            assert_eq!(first.len(), 3);
+            assert_eq!(second.len(), 3);
+            for (i, byte) in first.iter_mut().chain(second.iter_mut()).enumerate() {
+                *byte = 1 + i as u8;
+            }
+        }
+        assert_eq!(cbuf.size(), 0);
+        cbuf.commit(6);
+        assert_eq!(cbuf.size(), 6);
+        assert_eq!(cbuf.available_len(), 1);
+
+        let mut read_from: [u8; 6] = [0; 6];
+        cbuf.take_slice(6, &mut read_from);
+        assert_eq!(read_from, [1, 2, 3, 4, 5, 6]);
+
+        let (first, second) = cbuf.free_mut_slices(2);
+        assert_eq!(first.len(), 2);
+        assert_eq!(second.len(), 0);
+    }
 }
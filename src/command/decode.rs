@@ -0,0 +1,2197 @@
+//! Decoding of vendor-specific commands.
+//!
+//! [`VendorCommand`] reads the opcode and parameters of a vendor-specific command, as written by
+//! the [`hal`](crate::hal), [`gap`](crate::gap), [`gatt`](crate::gatt), [`l2cap`](crate::l2cap)
+//! and [`updater`](crate::updater) command traits, back into the typed parameters of the command.
+//! This can be used to decode captured host traffic, to build mock controllers, or to check the
+//! encoding of commands.
+//!
+//! Parameters that reference a list of addresses or handles borrow it from their command, so the
+//! decoded commands that include such a list store it themselves (for example,
+//! [`AutoConnectionEstablishment`]), and return the parameters with
+//! [`parameters`](AutoConnectionEstablishment::parameters).
+
+extern crate bluetooth_hci as hci;
+
+use crate::dialect::Dialect;
+use crate::gap::{
+    AddressType, AuthenticationRequirements, Authorization, AutoConnectionEstablishmentParameters,
+    BroadcastModeParameters, DirectConnectableParameters, DiscoverableParameters,
+    DiscoveryProcedureParameters, GeneralConnectionEstablishmentParameters, IoCapability,
+    LocalName, NameDiscoveryProcedureParameters, ObservationProcedureParameters,
+    OutOfBandAuthentication, PairingRequest, Pin, Procedure, SecurityRequestParameters,
+    SelectiveConnectionEstablishmentParameters,
+};
+use crate::gatt::{
+    AccessPermission, AddCharacteristicParameters, AddDescriptorParameters, AddServiceParameters,
+    CharacteristicEvent, CharacteristicHandle, CharacteristicPermission, CharacteristicProperty,
+    CharacteristicValue, DeleteIncludedServiceParameters, DescriptorHandle, DescriptorPermission,
+    DescriptorValueParameters, EncryptionKeySize, FindByTypeValueParameters,
+    IncludeServiceParameters, LongCharacteristicReadParameters, LongCharacteristicValue,
+    MultipleCharacteristicReadParameters, Range, ReadByTypeParameters,
+    SecurityPermissionParameters, ServiceHandle, ServiceType, UpdateCharacteristicValueParameters,
+    UpdateLongCharacteristicValueParameters, UpdateType, Uuid, Uuid16, WriteRequest,
+    WriteResponseParameters,
+};
+use crate::hal::{ConfigData, ConfigParameter, PowerLevel};
+use crate::l2cap::{ConnectionParameterUpdateRequest, ConnectionParameterUpdateResponse};
+use byteorder::{ByteOrder, LittleEndian};
+use core::convert::TryFrom;
+use core::time::Duration;
+use hci::host::{AdvertisingFilterPolicy, AdvertisingType, OwnAddressType, PeerAddrType, ScanType};
+use hci::types::{
+    AdvertisingInterval, AdvertisingIntervalError, ConnectionInterval, ConnectionIntervalError,
+    ExpectedConnectionLength, ExpectedConnectionLengthError, ScanWindow, ScanWindowError,
+};
+
+#[cfg(feature = "bluenrg2")]
+use crate::gap::{PassKeyInput, Privacy};
+
+/// Vendor-specific commands, with their decoded parameters.
+///
+/// Commands that take a single value hold it directly; commands that take several values name
+/// them after the parameters of the command method.
+#[allow(clippy::large_enum_variant)]
+#[derive(Debug)]
+pub enum VendorCommand<'a> {
+    /// The [HAL Get Firmware Revision](crate::hal::Commands::get_firmware_revision) command.
+    HalGetFirmwareRevision,
+
+    /// The [HAL Write Config Data](crate::hal::Commands::write_config_data) command.
+    HalWriteConfigData(ConfigData),
+
+    /// The [HAL Read Config Data](crate::hal::Commands::read_config_data) command.
+    HalReadConfigData(ConfigParameter),
+
+    /// The [HAL Set Tx Power Level](crate::hal::Commands::set_tx_power_level) command.
+    HalSetTxPowerLevel(PowerLevel),
+
+    /// The [HAL Device Standby](crate::hal::Commands::device_standby) command.
+    HalDeviceStandby,
+
+    /// The [HAL Get Tx Test Packet Count](crate::hal::Commands::get_tx_test_packet_count) command.
+    HalGetTxTestPacketCount,
+
+    /// The [HAL Start Tone](crate::hal::Commands::start_tone) command. Includes the channel.
+    HalStartTone(u8),
+
+    /// The [HAL Stop Tone](crate::hal::Commands::stop_tone) command.
+    HalStopTone,
+
+    /// The [HAL Get Link Status](crate::hal::Commands::get_link_status) command.
+    HalGetLinkStatus,
+
+    /// The [HAL Get Anchor Period](crate::hal::Commands::get_anchor_period) command.
+    HalGetAnchorPeriod,
+
+    /// The [GAP Set Nondiscoverable](crate::gap::Commands::set_nondiscoverable) command.
+    GapSetNonDiscoverable,
+
+    /// The [GAP Set Limited Discoverable](crate::gap::Commands::set_limited_discoverable) command.
+    GapSetLimitedDiscoverable(DiscoverableParameters<'a, 'a>),
+
+    /// The [GAP Set Discoverable](crate::gap::Commands::set_discoverable) command.
+    GapSetDiscoverable(DiscoverableParameters<'a, 'a>),
+
+    /// The [GAP Set Direct Connectable](crate::gap::Commands::set_direct_connectable) command.
+    GapSetDirectConnectable(DirectConnectableParameters),
+
+    /// The [GAP Set IO Capability](crate::gap::Commands::set_io_capability) command.
+    GapSetIoCapability(IoCapability),
+
+    /// The [GAP Set Authentication
+    /// Requirement](crate::gap::Commands::set_authentication_requirement) command.
+    GapSetAuthenticationRequirement(AuthenticationRequirements),
+
+    /// The [GAP Set Authorization
+    /// Requirement](crate::gap::Commands::set_authorization_requirement) command.
+    GapSetAuthorizationRequirement {
+        /// Handle of the connection.
+        conn_handle: hci::ConnectionHandle,
+
+        /// Is authorization required?
+        authorization_required: bool,
+    },
+
+    /// The [GAP Pass Key Response](crate::gap::Commands::pass_key_response) command.
+    GapPassKeyResponse {
+        /// Handle of the connection.
+        conn_handle: hci::ConnectionHandle,
+
+        /// The pass key.
+        pin: u32,
+    },
+
+    /// The [GAP Authorization Response](crate::gap::Commands::authorization_response) command.
+    GapAuthorizationResponse {
+        /// Handle of the connection.
+        conn_handle: hci::ConnectionHandle,
+
+        /// Is the peer authorized?
+        authorization: Authorization,
+    },
+
+    /// The [GAP Init](crate::gap::Commands::init) command.
+    ///
+    /// The original BlueNRG only sends the role, so the other parameters are `false` and
+    /// [`BLUENRG_DEVICE_NAME_LEN`](crate::gap::BLUENRG_DEVICE_NAME_LEN) in that dialect.
+    #[cfg(not(feature = "bluenrg2"))]
+    GapInit {
+        /// Roles of the device.
+        role: crate::gap::Role,
+
+        /// Is privacy enabled?
+        privacy_enabled: bool,
+
+        /// Length of the device name characteristic.
+        dev_name_characteristic_len: u8,
+    },
+
+    /// The [GAP Init](crate::gap::Commands::init) command.
+    #[cfg(feature = "bluenrg2")]
+    GapInit {
+        /// Roles of the device.
+        role: crate::gap::Role,
+
+        /// Privacy mode of the device.
+        privacy: Privacy,
+
+        /// Length of the device name characteristic.
+        dev_name_characteristic_len: u8,
+    },
+
+    /// The [GAP Set Nonconnectable](crate::gap::Commands::set_nonconnectable) command.
+    ///
+    /// The original BlueNRG does not send the address type, so it is
+    /// [Public](AddressType::Public) in that dialect.
+    GapSetNonConnectable {
+        /// Advertising type.
+        advertising_type: AdvertisingType,
+
+        /// Address type of the device.
+        address_type: AddressType,
+    },
+
+    /// The [GAP Set Undirected
+    /// Connectable](crate::gap::Commands::set_undirected_connectable) command.
+    GapSetUndirectedConnectable {
+        /// Advertising filter policy.
+        filter_policy: AdvertisingFilterPolicy,
+
+        /// Address type of the device.
+        address_type: AddressType,
+    },
+
+    /// The [GAP Peripheral Security
+    /// Request](crate::gap::Commands::peripheral_security_request) command.
+    GapPeripheralSecurityRequest(SecurityRequestParameters),
+
+    /// The [GAP Update Advertising Data](crate::gap::Commands::update_advertising_data) command.
+    /// Includes the advertising data.
+    GapUpdateAdvertisingData(&'a [u8]),
+
+    /// The [GAP Delete AD Type](crate::gap::Commands::delete_ad_type) command.
+    GapDeleteAdType(crate::gap::AdvertisingDataType),
+
+    /// The [GAP Get Security Level](crate::gap::Commands::get_security_level) command.
+    GapGetSecurityLevel,
+
+    /// The [GAP Set Event Mask](crate::gap::Commands::set_event_mask) command.
+    GapSetEventMask(crate::gap::EventFlags),
+
+    /// The [GAP Configure White List](crate::gap::Commands::configure_white_list) command.
+    GapConfigureWhiteList,
+
+    /// The [GAP Terminate](crate::gap::Commands::terminate) command.
+    GapTerminate {
+        /// Handle of the connection.
+        conn_handle: hci::ConnectionHandle,
+
+        /// Reason for terminating the connection.
+        reason: hci::Status<crate::event::Status>,
+    },
+
+    /// The [GAP Clear Security Database](crate::gap::Commands::clear_security_database) command.
+    GapClearSecurityDatabase,
+
+    /// The [GAP Allow Rebond](crate::gap::Commands::allow_rebond) command. Includes the connection
+    /// handle.
+    ///
+    /// The original BlueNRG does not send the connection handle, so it is `None` in that dialect.
+    GapAllowRebond(Option<hci::ConnectionHandle>),
+
+    /// The [GAP Start Limited Discovery
+    /// Procedure](crate::gap::Commands::start_limited_discovery_procedure) command.
+    GapStartLimitedDiscoveryProcedure(DiscoveryProcedureParameters),
+
+    /// The [GAP Start General Discovery
+    /// Procedure](crate::gap::Commands::start_general_discovery_procedure) command.
+    GapStartGeneralDiscoveryProcedure(DiscoveryProcedureParameters),
+
+    /// The [GAP Start Name Discovery
+    /// Procedure](crate::gap::Commands::start_name_discovery_procedure) command.
+    GapStartNameDiscoveryProcedure(NameDiscoveryProcedureParameters),
+
+    /// The [GAP Start Auto Connection
+    /// Establishment](crate::gap::Commands::start_auto_connection_establishment) command.
+    GapStartAutoConnectionEstablishment(AutoConnectionEstablishment),
+
+    /// The [GAP Start General Connection
+    /// Establishment](crate::gap::Commands::start_general_connection_establishment) command.
+    GapStartGeneralConnectionEstablishment(GeneralConnectionEstablishmentParameters),
+
+    /// The [GAP Start Selective Connection
+    /// Establishment](crate::gap::Commands::start_selective_connection_establishment) command.
+    GapStartSelectiveConnectionEstablishment(SelectiveConnectionEstablishment),
+
+    /// The [GAP Create Connection](crate::gap::Commands::create_connection) command.
+    GapCreateConnection(crate::gap::ConnectionParameters),
+
+    /// The [GAP Terminate Procedure](crate::gap::Commands::terminate_procedure) command.
+    GapTerminateProcedure(Procedure),
+
+    /// The [GAP Start Connection Update](crate::gap::Commands::start_connection_update) command.
+    GapStartConnectionUpdate(crate::gap::ConnectionUpdateParameters),
+
+    /// The [GAP Send Pairing Request](crate::gap::Commands::send_pairing_request) command.
+    GapSendPairingRequest(PairingRequest),
+
+    /// The [GAP Resolve Private Address](crate::gap::Commands::resolve_private_address) command.
+    GapResolvePrivateAddress(hci::BdAddr),
+
+    /// The [GAP Set Broadcast Mode](crate::gap::Commands::set_broadcast_mode) command.
+    GapSetBroadcastMode(BroadcastMode<'a>),
+
+    /// The [GAP Start Observation
+    /// Procedure](crate::gap::Commands::start_observation_procedure) command.
+    GapStartObservationProcedure(ObservationProcedureParameters),
+
+    /// The [GAP Get Bonded Devices](crate::gap::Commands::get_bonded_devices) command.
+    GapGetBondedDevices,
+
+    /// The [GAP Is Device Bonded](crate::gap::Commands::is_device_bonded) command.
+    GapIsDeviceBonded(PeerAddrType),
+
+    /// The [GAP Numeric Comparison Value
+    /// Confirm](crate::gap::Commands::numeric_comparison_value_confirm) command.
+    #[cfg(feature = "bluenrg2")]
+    GapNumericComparisonValueConfirm {
+        /// Handle of the connection.
+        conn_handle: hci::ConnectionHandle,
+
+        /// Did the user confirm the value?
+        confirmed: bool,
+    },
+
+    /// The [GAP Pass Key Input](crate::gap::Commands::pass_key_input) command.
+    #[cfg(feature = "bluenrg2")]
+    GapPassKeyInput {
+        /// Handle of the connection.
+        conn_handle: hci::ConnectionHandle,
+
+        /// The key press.
+        input: PassKeyInput,
+    },
+
+    /// The [GAP Remove Bonded Device](crate::gap::Commands::remove_bonded_device) command.
+    #[cfg(feature = "bluenrg2")]
+    GapRemoveBondedDevice(PeerAddrType),
+
+    /// The [GATT Init](crate::gatt::Commands::init) command.
+    GattInit,
+
+    /// The [GATT Add Service](crate::gatt::Commands::add_service) command.
+    GattAddService(AddServiceParameters),
+
+    /// The [GATT Include Service](crate::gatt::Commands::include_service) command.
+    GattIncludeService(IncludeServiceParameters),
+
+    /// The [GATT Add Characteristic](crate::gatt::Commands::add_characteristic) command.
+    GattAddCharacteristic(AddCharacteristicParameters),
+
+    /// The [GATT Add Characteristic
+    /// Descriptor](crate::gatt::Commands::add_characteristic_descriptor) command.
+    GattAddCharacteristicDescriptor(AddDescriptorParameters<'a>),
+
+    /// The [GATT Update Characteristic
+    /// Value](crate::gatt::Commands::update_characteristic_value) command.
+    GattUpdateCharacteristicValue(UpdateCharacteristicValueParameters<'a>),
+
+    /// The [GATT Delete Characteristic](crate::gatt::Commands::delete_characteristic) command.
+    GattDeleteCharacteristic {
+        /// Handle of the service that contains the characteristic.
+        service: ServiceHandle,
+
+        /// Handle of the characteristic.
+        characteristic: CharacteristicHandle,
+    },
+
+    /// The [GATT Delete Service](crate::gatt::Commands::delete_service) command.
+    GattDeleteService(ServiceHandle),
+
+    /// The [GATT Delete Included Service](crate::gatt::Commands::delete_included_service)
+    /// command.
+    GattDeleteIncludedService(DeleteIncludedServiceParameters),
+
+    /// The [GATT Set Event Mask](crate::gatt::Commands::set_event_mask) command.
+    GattSetEventMask(crate::gatt::Event),
+
+    /// The [GATT Exchange Configuration](crate::gatt::Commands::exchange_configuration) command.
+    /// Includes the connection handle.
+    GattExchangeConfiguration(hci::ConnectionHandle),
+
+    /// The [GATT Find Information Request](crate::gatt::Commands::find_information_request)
+    /// command.
+    GattFindInformationRequest {
+        /// Handle of the connection.
+        conn_handle: hci::ConnectionHandle,
+
+        /// Range of attributes to find.
+        attribute_range: Range<CharacteristicHandle>,
+    },
+
+    /// The [GATT Find By Type Value Request](crate::gatt::Commands::find_by_type_value_request)
+    /// command.
+    GattFindByTypeValueRequest(FindByTypeValueParameters<'a>),
+
+    /// The [GATT Read By Type Request](crate::gatt::Commands::read_by_type_request) command.
+    GattReadByTypeRequest(ReadByTypeParameters),
+
+    /// The [GATT Read By Group Type Request](crate::gatt::Commands::read_by_group_type_request)
+    /// command.
+    GattReadByGroupTypeRequest(ReadByTypeParameters),
+
+    /// The [GATT Prepare Write Request](crate::gatt::Commands::prepare_write_request) command.
+    GattPrepareWriteRequest(WriteRequest<'a>),
+
+    /// The [GATT Execute Write Request](crate::gatt::Commands::execute_write_request) command.
+    /// Includes the connection handle.
+    GattExecuteWriteRequest(hci::ConnectionHandle),
+
+    /// The [GATT Cancel Write Request](crate::gatt::Commands::cancel_write_request) command,
+    /// which shares its opcode with the [GATT Execute Write
+    /// Request](VendorCommand::GattExecuteWriteRequest) command. Includes the connection handle.
+    GattCancelWriteRequest(hci::ConnectionHandle),
+
+    /// The [GATT Discover All Primary
+    /// Services](crate::gatt::Commands::discover_all_primary_services) command. Includes the
+    /// connection handle.
+    GattDiscoverAllPrimaryServices(hci::ConnectionHandle),
+
+    /// The [GATT Discover Primary Services By
+    /// UUID](crate::gatt::Commands::discover_primary_services_by_uuid) command.
+    GattDiscoverPrimaryServicesByUuid {
+        /// Handle of the connection.
+        conn_handle: hci::ConnectionHandle,
+
+        /// UUID of the services to find.
+        uuid: Uuid,
+    },
+
+    /// The [GATT Find Included Services](crate::gatt::Commands::find_included_services) command.
+    GattFindIncludedServices {
+        /// Handle of the connection.
+        conn_handle: hci::ConnectionHandle,
+
+        /// Range of services to search.
+        service_handle_range: Range<ServiceHandle>,
+    },
+
+    /// The [GATT Discover All Characteristics of
+    /// Service](crate::gatt::Commands::discover_all_characteristics_of_service) command.
+    GattDiscoverAllCharacteristicsOfService {
+        /// Handle of the connection.
+        conn_handle: hci::ConnectionHandle,
+
+        /// Range of attributes to search.
+        attribute_handle_range: Range<CharacteristicHandle>,
+    },
+
+    /// The [GATT Discover Characteristics By
+    /// UUID](crate::gatt::Commands::discover_characteristics_by_uuid) command.
+    GattDiscoverCharacteristicsByUuid {
+        /// Handle of the connection.
+        conn_handle: hci::ConnectionHandle,
+
+        /// Range of attributes to search.
+        attribute_handle_range: Range<CharacteristicHandle>,
+
+        /// UUID of the characteristics to find.
+        uuid: Uuid,
+    },
+
+    /// The [GATT Discover All Characteristic
+    /// Descriptors](crate::gatt::Commands::discover_all_characteristic_descriptors) command.
+    GattDiscoverAllCharacteristicDescriptors {
+        /// Handle of the connection.
+        conn_handle: hci::ConnectionHandle,
+
+        /// Range of attributes to search.
+        characteristic_handle_range: Range<CharacteristicHandle>,
+    },
+
+    /// The [GATT Read Characteristic Value](crate::gatt::Commands::read_characteristic_value)
+    /// command.
+    GattReadCharacteristicValue {
+        /// Handle of the connection.
+        conn_handle: hci::ConnectionHandle,
+
+        /// Handle of the characteristic.
+        characteristic_handle: CharacteristicHandle,
+    },
+
+    /// The [GATT Read Characteristic Using
+    /// UUID](crate::gatt::Commands::read_characteristic_using_uuid) command.
+    GattReadCharacteristicUsingUuid {
+        /// Handle of the connection.
+        conn_handle: hci::ConnectionHandle,
+
+        /// Range of attributes to search.
+        characteristic_handle_range: Range<CharacteristicHandle>,
+
+        /// UUID of the characteristics to read.
+        uuid: Uuid,
+    },
+
+    /// The [GATT Read Long Characteristic
+    /// Value](crate::gatt::Commands::read_long_characteristic_value) command.
+    GattReadLongCharacteristicValue(LongCharacteristicReadParameters),
+
+    /// The [GATT Read Multiple Characteristic
+    /// Values](crate::gatt::Commands::read_multiple_characteristic_values) command.
+    GattReadMultipleCharacteristicValues(MultipleCharacteristicRead),
+
+    /// The [GATT Write Characteristic Value](crate::gatt::Commands::write_characteristic_value)
+    /// command.
+    GattWriteCharacteristicValue(CharacteristicValue<'a>),
+
+    /// The [GATT Write Long Characteristic
+    /// Value](crate::gatt::Commands::write_long_characteristic_value) command.
+    GattWriteLongCharacteristicValue(LongCharacteristicValue<'a>),
+
+    /// The [GATT Write Characteristic Value
+    /// Reliably](crate::gatt::Commands::write_characteristic_value_reliably) command.
+    GattWriteCharacteristicValueReliably(LongCharacteristicValue<'a>),
+
+    /// The [GATT Write Long Characteristic
+    /// Descriptor](crate::gatt::Commands::write_long_characteristic_descriptor) command.
+    GattWriteLongCharacteristicDescriptor(LongCharacteristicValue<'a>),
+
+    /// The [GATT Read Long Characteristic
+    /// Descriptor](crate::gatt::Commands::read_long_characteristic_descriptor) command.
+    GattReadLongCharacteristicDescriptor(LongCharacteristicReadParameters),
+
+    /// The [GATT Write Characteristic
+    /// Descriptor](crate::gatt::Commands::write_characteristic_descriptor) command.
+    GattWriteCharacteristicDescriptor(CharacteristicValue<'a>),
+
+    /// The [GATT Read Characteristic
+    /// Descriptor](crate::gatt::Commands::read_characteristic_descriptor) command.
+    GattReadCharacteristicDescriptor {
+        /// Handle of the connection.
+        conn_handle: hci::ConnectionHandle,
+
+        /// Handle of the descriptor.
+        characteristic_handle: CharacteristicHandle,
+    },
+
+    /// The [GATT Write Without Response](crate::gatt::Commands::write_without_response) command.
+    GattWriteWithoutResponse(CharacteristicValue<'a>),
+
+    /// The [GATT Signed Write Without
+    /// Response](crate::gatt::Commands::signed_write_without_response) command.
+    GattSignedWriteWithoutResponse(CharacteristicValue<'a>),
+
+    /// The [GATT Confirm Indication](crate::gatt::Commands::confirm_indication) command. Includes
+    /// the connection handle.
+    GattConfirmIndication(hci::ConnectionHandle),
+
+    /// The [GATT Write Response](crate::gatt::Commands::write_response) command.
+    GattWriteResponse(WriteResponseParameters<'a>),
+
+    /// The [GATT Allow Read](crate::gatt::Commands::allow_read) command. Includes the connection
+    /// handle.
+    GattAllowRead(hci::ConnectionHandle),
+
+    /// The [GATT Set Security Permission](crate::gatt::Commands::set_security_permission)
+    /// command.
+    GattSetSecurityPermission(SecurityPermissionParameters),
+
+    /// The [GATT Set Descriptor Value](crate::gatt::Commands::set_descriptor_value) command.
+    GattSetDescriptorValue(DescriptorValueParameters<'a>),
+
+    /// The [GATT Read Handle Value](crate::gatt::Commands::read_handle_value) command. Includes
+    /// the handle of the attribute.
+    GattReadHandleValue(CharacteristicHandle),
+
+    /// The [GATT Read Handle Value Offset](crate::gatt::Commands::read_handle_value_offset)
+    /// command.
+    GattReadHandleValueOffset {
+        /// Handle of the attribute.
+        handle: CharacteristicHandle,
+
+        /// Offset of the first byte to read.
+        offset: usize,
+    },
+
+    /// The [GATT Update Long Characteristic
+    /// Value](crate::gatt::Commands::update_long_characteristic_value) command.
+    GattUpdateLongCharacteristicValue(UpdateLongCharacteristicValueParameters<'a>),
+
+    /// The [L2CAP Connection Parameter Update
+    /// Request](crate::l2cap::Commands::connection_parameter_update_request) command.
+    L2CapConnectionParameterUpdateRequest(ConnectionParameterUpdateRequest),
+
+    /// The [L2CAP Connection Parameter Update
+    /// Response](crate::l2cap::Commands::connection_parameter_update_response) command.
+    L2CapConnectionParameterUpdateResponse(ConnectionParameterUpdateResponse),
+
+    /// The [Updater Start](crate::updater::Commands::start) command.
+    UpdaterStart,
+
+    /// The [Updater Reboot](crate::updater::Commands::reboot) command.
+    UpdaterReboot,
+
+    /// The [Updater Get Version](crate::updater::Commands::get_version) command.
+    UpdaterGetVersion,
+
+    /// The [Updater Get Buffer Size](crate::updater::Commands::get_buffer_size) command.
+    UpdaterGetBufferSize,
+
+    /// The [Updater Erase Blue Flag](crate::updater::Commands::erase_blue_flag) command.
+    UpdaterEraseBlueFlag,
+
+    /// The [Updater Reset Blue Flag](crate::updater::Commands::reset_blue_flag) command.
+    UpdaterResetBlueFlag,
+
+    /// The [Updater Erase Sector](crate::updater::Commands::erase_sector) command. Includes the
+    /// address of the sector.
+    UpdaterEraseSector(u32),
+
+    /// The [Updater Program Data Block](crate::updater::Commands::program_data_block) command.
+    UpdaterProgramDataBlock {
+        /// Address of the first byte to write.
+        address: u32,
+
+        /// Data to write.
+        data: &'a [u8],
+    },
+
+    /// The [Updater Read Data Block](crate::updater::Commands::read_data_block) command.
+    UpdaterReadDataBlock {
+        /// Address of the first byte to read.
+        address: u32,
+
+        /// Number of bytes to read.
+        len: usize,
+    },
+
+    /// The [Updater Calc CRC](crate::updater::Commands::calc_crc) command.
+    UpdaterCalcCrc {
+        /// Address of the first sector.
+        address: u32,
+
+        /// Number of sectors.
+        sector_count: u8,
+    },
+
+    /// The [Updater HW Version](crate::updater::Commands::hw_version) command.
+    UpdaterHwVersion,
+}
+
+/// Errors that may occur when decoding a vendor-specific command.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Error {
+    /// The opcode is not a vendor-specific command supported by this crate. Includes the opcode.
+    UnknownOpcode(hci::Opcode),
+
+    /// For [`from_packet`](VendorCommand::from_packet): the packet is not a command packet.
+    /// Includes the packet type.
+    BadPacketType(u8),
+
+    /// The length of the parameters is not valid for the command. The first field is the actual
+    /// length, and the second is the expected length.
+    BadLength(usize, usize),
+
+    /// The configuration parameter is not recognized. Includes the unrecognized byte.
+    BadConfigParameter(u8),
+
+    /// The power level is not recognized. Includes the unrecognized value.
+    BadPowerLevel(u16),
+
+    /// The I/O capability is not recognized. Includes the unrecognized byte.
+    BadIoCapability(u8),
+
+    /// The authorization response is not recognized. Includes the unrecognized byte.
+    BadAuthorization(u8),
+
+    /// The GAP roles include unrecognized flags. Includes the entire bitfield.
+    BadRole(u8),
+
+    /// The privacy mode is not recognized. Includes the unrecognized byte.
+    #[cfg(feature = "bluenrg2")]
+    BadPrivacy(u8),
+
+    /// The pass key input is not recognized. Includes the unrecognized byte.
+    #[cfg(feature = "bluenrg2")]
+    BadPassKeyInput(u8),
+
+    /// The GAP address type is not recognized. Includes the unrecognized byte.
+    BadAddressType(u8),
+
+    /// The own address type is not recognized. Includes the unrecognized byte.
+    BadOwnAddressType(u8),
+
+    /// The type of a peer address is not recognized. Includes the unrecognized byte.
+    BadPeerAddressType(u8),
+
+    /// The advertising type is not recognized. Includes the unrecognized byte.
+    BadAdvertisingType(u8),
+
+    /// The advertising filter policy is not recognized. Includes the unrecognized byte.
+    BadAdvertisingFilterPolicy(u8),
+
+    /// The scan type is not recognized. Includes the unrecognized byte.
+    BadScanType(u8),
+
+    /// The advertising data type is not recognized. Includes the unrecognized byte.
+    BadAdvertisingDataType(u8),
+
+    /// The type of the local name is neither shortened nor complete. Includes the unrecognized
+    /// byte.
+    BadLocalNameType(u8),
+
+    /// The GAP event mask includes unrecognized flags. Includes the entire bitfield.
+    BadEventFlags(u16),
+
+    /// The status is not recognized. Includes the unrecognized byte.
+    BadStatus(u8),
+
+    /// The UUID type is neither 16-bit nor 128-bit. Includes the unrecognized byte.
+    BadUuidType(u8),
+
+    /// The service type is not recognized. Includes the unrecognized byte.
+    BadServiceType(u8),
+
+    /// The characteristic permissions include unrecognized flags. Includes the entire bitfield.
+    BadCharacteristicPermission(u8),
+
+    /// The characteristic event mask includes unrecognized flags. Includes the entire bitfield.
+    BadCharacteristicEvent(u8),
+
+    /// The descriptor permissions include unrecognized flags. Includes the entire bitfield.
+    BadDescriptorPermission(u8),
+
+    /// The access permissions include unrecognized flags. Includes the entire bitfield.
+    BadAccessPermission(u8),
+
+    /// The GATT event mask includes unrecognized flags. Includes the entire bitfield.
+    BadGattEventMask(u32),
+
+    /// The update type includes unrecognized flags. Includes the entire bitfield.
+    BadUpdateType(u8),
+
+    /// The encryption key size is out of range. Includes the invalid size.
+    BadEncryptionKeySize(u8),
+
+    /// The beginning of a handle range is after its end. Includes the beginning and end,
+    /// respectively.
+    BadHandleRange(u16, u16),
+
+    /// The scan window is invalid. Includes the underlying error.
+    BadScanWindow(ScanWindowError),
+
+    /// The connection interval is invalid. Includes the underlying error.
+    BadConnectionInterval(ConnectionIntervalError),
+
+    /// The expected connection length is invalid. Includes the underlying error.
+    BadExpectedConnectionLength(ExpectedConnectionLengthError),
+
+    /// The advertising interval is invalid. Includes the underlying error.
+    BadAdvertisingInterval(AdvertisingIntervalError),
+}
+
+// Command parameters are at most 255 bytes long, since their length is written in one byte.
+const MAX_PARAMETERS_LEN: usize = 255;
+
+const PACKET_TYPE_COMMAND: u8 = 0x01;
+const PACKET_HEADER_LEN: usize = 4;
+
+impl<'a> VendorCommand<'a> {
+    /// Decodes the parameters of the vendor-specific command with the given opcode, in the given
+    /// [`Dialect`].
+    ///
+    /// # Errors
+    ///
+    /// - [`UnknownOpcode`](Error::UnknownOpcode) if the opcode is not a vendor-specific command,
+    ///   or if the command is only supported by the BlueNRG-MS and the dialect is the original
+    ///   BlueNRG's.
+    /// - [`BadLength`](Error::BadLength) if the length of the parameters does not match the
+    ///   command, or if the lengths included in the parameters do not match the parameters.
+    /// - Any of the other errors if the parameters include a value that the command does not
+    ///   accept.
+    pub fn with_dialect(
+        dialect: Dialect,
+        opcode: hci::Opcode,
+        params: &'a [u8],
+    ) -> Result<VendorCommand<'a>, Error> {
+        if params.len() > MAX_PARAMETERS_LEN {
+            return Err(Error::BadLength(params.len(), MAX_PARAMETERS_LEN));
+        }
+        if !dialect.is_ms() && is_ms_only(opcode) {
+            return Err(Error::UnknownOpcode(opcode));
+        }
+
+        match opcode {
+            crate::opcode::HAL_GET_FIRMWARE_REVISION => {
+                require_len(params, 0).map(|_| VendorCommand::HalGetFirmwareRevision)
+            }
+            crate::opcode::HAL_WRITE_CONFIG_DATA => {
+                Ok(VendorCommand::HalWriteConfigData(to_config_data(params)?))
+            }
+            crate::opcode::HAL_READ_CONFIG_DATA => {
+                require_len(params, 1)?;
+                Ok(VendorCommand::HalReadConfigData(
+                    ConfigParameter::try_from(params[0]).map_err(Error::BadConfigParameter)?,
+                ))
+            }
+            crate::opcode::HAL_SET_TX_POWER_LEVEL => {
+                require_len(params, 2)?;
+                Ok(VendorCommand::HalSetTxPowerLevel(
+                    PowerLevel::try_from(LittleEndian::read_u16(params))
+                        .map_err(Error::BadPowerLevel)?,
+                ))
+            }
+            crate::opcode::HAL_DEVICE_STANDBY => {
+                require_len(params, 0).map(|_| VendorCommand::HalDeviceStandby)
+            }
+            crate::opcode::HAL_TX_TEST_PACKET_COUNT => {
+                require_len(params, 0).map(|_| VendorCommand::HalGetTxTestPacketCount)
+            }
+            crate::opcode::HAL_START_TONE => {
+                require_len(params, 1)?;
+                Ok(VendorCommand::HalStartTone(params[0]))
+            }
+            crate::opcode::HAL_STOP_TONE => {
+                require_len(params, 0).map(|_| VendorCommand::HalStopTone)
+            }
+            crate::opcode::HAL_GET_LINK_STATUS => {
+                require_len(params, 0).map(|_| VendorCommand::HalGetLinkStatus)
+            }
+            crate::opcode::HAL_GET_ANCHOR_PERIOD => {
+                require_len(params, 0).map(|_| VendorCommand::HalGetAnchorPeriod)
+            }
+            crate::opcode::GAP_SET_NONDISCOVERABLE => {
+                require_len(params, 0).map(|_| VendorCommand::GapSetNonDiscoverable)
+            }
+            crate::opcode::GAP_SET_LIMITED_DISCOVERABLE => Ok(
+                VendorCommand::GapSetLimitedDiscoverable(to_discoverable_parameters(params)?),
+            ),
+            crate::opcode::GAP_SET_DISCOVERABLE => Ok(VendorCommand::GapSetDiscoverable(
+                to_discoverable_parameters(params)?,
+            )),
+            crate::opcode::GAP_SET_DIRECT_CONNECTABLE => {
+                Ok(VendorCommand::GapSetDirectConnectable(
+                    to_direct_connectable_parameters(dialect, params)?,
+                ))
+            }
+            crate::opcode::GAP_SET_IO_CAPABILITY => {
+                require_len(params, 1)?;
+                Ok(VendorCommand::GapSetIoCapability(
+                    IoCapability::try_from(params[0]).map_err(Error::BadIoCapability)?,
+                ))
+            }
+            crate::opcode::GAP_SET_AUTHENTICATION_REQUIREMENT => {
+                Ok(VendorCommand::GapSetAuthenticationRequirement(
+                    to_authentication_requirements(params)?,
+                ))
+            }
+            crate::opcode::GAP_SET_AUTHORIZATION_REQUIREMENT => {
+                require_len(params, 3)?;
+                Ok(VendorCommand::GapSetAuthorizationRequirement {
+                    conn_handle: to_conn_handle(params),
+                    authorization_required: to_bool(params[2]),
+                })
+            }
+            crate::opcode::GAP_PASS_KEY_RESPONSE => {
+                require_len(params, 6)?;
+                Ok(VendorCommand::GapPassKeyResponse {
+                    conn_handle: to_conn_handle(params),
+                    pin: LittleEndian::read_u32(&params[2..6]),
+                })
+            }
+            crate::opcode::GAP_AUTHORIZATION_RESPONSE => {
+                require_len(params, 3)?;
+                Ok(VendorCommand::GapAuthorizationResponse {
+                    conn_handle: to_conn_handle(params),
+                    authorization: Authorization::try_from(params[2])
+                        .map_err(Error::BadAuthorization)?,
+                })
+            }
+            crate::opcode::GAP_INIT => to_gap_init(dialect, params),
+            crate::opcode::GAP_SET_NONCONNECTABLE => {
+                require_len(params, if dialect.is_ms() { 2 } else { 1 })?;
+                Ok(VendorCommand::GapSetNonConnectable {
+                    advertising_type: to_advertising_type(params[0])?,
+                    address_type: if dialect.is_ms() {
+                        to_address_type(params[1])?
+                    } else {
+                        AddressType::Public
+                    },
+                })
+            }
+            crate::opcode::GAP_SET_UNDIRECTED_CONNECTABLE => {
+                require_len(params, 2)?;
+                Ok(VendorCommand::GapSetUndirectedConnectable {
+                    filter_policy: to_advertising_filter_policy(params[0])?,
+                    address_type: to_address_type(params[1])?,
+                })
+            }
+            crate::opcode::GAP_PERIPHERAL_SECURITY_REQUEST => {
+                require_len(params, 4)?;
+                Ok(VendorCommand::GapPeripheralSecurityRequest(
+                    SecurityRequestParameters {
+                        conn_handle: to_conn_handle(params),
+                        bonding: to_bool(params[2]),
+                        mitm_protection: to_bool(params[3]),
+                    },
+                ))
+            }
+            crate::opcode::GAP_UPDATE_ADVERTISING_DATA => {
+                require_len_at_least(params, 1)?;
+                require_len(params, 1 + params[0] as usize)?;
+                Ok(VendorCommand::GapUpdateAdvertisingData(&params[1..]))
+            }
+            crate::opcode::GAP_DELETE_AD_TYPE => {
+                require_len(params, 1)?;
+                Ok(VendorCommand::GapDeleteAdType(
+                    crate::gap::AdvertisingDataType::try_from(params[0])
+                        .map_err(Error::BadAdvertisingDataType)?,
+                ))
+            }
+            crate::opcode::GAP_GET_SECURITY_LEVEL => {
+                require_len(params, 0).map(|_| VendorCommand::GapGetSecurityLevel)
+            }
+            crate::opcode::GAP_SET_EVENT_MASK => {
+                require_len(params, 2)?;
+                let bits = LittleEndian::read_u16(params);
+                Ok(VendorCommand::GapSetEventMask(
+                    crate::gap::EventFlags::from_bits(bits).ok_or(Error::BadEventFlags(bits))?,
+                ))
+            }
+            crate::opcode::GAP_CONFIGURE_WHITE_LIST => {
+                require_len(params, 0).map(|_| VendorCommand::GapConfigureWhiteList)
+            }
+            crate::opcode::GAP_TERMINATE => {
+                require_len(params, 3)?;
+                Ok(VendorCommand::GapTerminate {
+                    conn_handle: to_conn_handle(params),
+                    reason: to_status(params[2])?,
+                })
+            }
+            crate::opcode::GAP_CLEAR_SECURITY_DATABASE => {
+                require_len(params, 0).map(|_| VendorCommand::GapClearSecurityDatabase)
+            }
+            crate::opcode::GAP_ALLOW_REBOND => {
+                if dialect.is_ms() {
+                    require_len(params, 2)?;
+                    Ok(VendorCommand::GapAllowRebond(Some(to_conn_handle(params))))
+                } else {
+                    require_len(params, 0)?;
+                    Ok(VendorCommand::GapAllowRebond(None))
+                }
+            }
+            crate::opcode::GAP_START_LIMITED_DISCOVERY_PROCEDURE => {
+                Ok(VendorCommand::GapStartLimitedDiscoveryProcedure(
+                    to_discovery_procedure_parameters(params)?,
+                ))
+            }
+            crate::opcode::GAP_START_GENERAL_DISCOVERY_PROCEDURE => {
+                Ok(VendorCommand::GapStartGeneralDiscoveryProcedure(
+                    to_discovery_procedure_parameters(params)?,
+                ))
+            }
+            crate::opcode::GAP_START_NAME_DISCOVERY_PROCEDURE => {
+                Ok(VendorCommand::GapStartNameDiscoveryProcedure(
+                    to_name_discovery_procedure_parameters(params)?,
+                ))
+            }
+            crate::opcode::GAP_START_AUTO_CONNECTION_ESTABLISHMENT => {
+                Ok(VendorCommand::GapStartAutoConnectionEstablishment(
+                    to_auto_connection_establishment(dialect, params)?,
+                ))
+            }
+            crate::opcode::GAP_START_GENERAL_CONNECTION_ESTABLISHMENT => {
+                Ok(VendorCommand::GapStartGeneralConnectionEstablishment(
+                    to_general_connection_establishment_parameters(dialect, params)?,
+                ))
+            }
+            crate::opcode::GAP_START_SELECTIVE_CONNECTION_ESTABLISHMENT => {
+                Ok(VendorCommand::GapStartSelectiveConnectionEstablishment(
+                    to_selective_connection_establishment(params)?,
+                ))
+            }
+            crate::opcode::GAP_CREATE_CONNECTION => Ok(VendorCommand::GapCreateConnection(
+                to_name_discovery_procedure_parameters(params)?,
+            )),
+            crate::opcode::GAP_TERMINATE_PROCEDURE => {
+                require_len(params, 1)?;
+                Ok(VendorCommand::GapTerminateProcedure(
+                    Procedure::from_bits_truncate(params[0]),
+                ))
+            }
+            crate::opcode::GAP_START_CONNECTION_UPDATE => {
+                require_len(params, 14)?;
+                Ok(VendorCommand::GapStartConnectionUpdate(
+                    crate::gap::ConnectionUpdateParameters {
+                        conn_handle: to_conn_handle(params),
+                        conn_interval: to_conn_interval(&params[2..10])?,
+                        expected_connection_length: to_expected_connection_length(&params[10..14])?,
+                    },
+                ))
+            }
+            crate::opcode::GAP_SEND_PAIRING_REQUEST => {
+                require_len(params, 3)?;
+                Ok(VendorCommand::GapSendPairingRequest(PairingRequest {
+                    conn_handle: to_conn_handle(params),
+                    force_rebond: params[2] & 0x01 != 0,
+                    force_reencrypt: params[2] & 0x02 != 0,
+                }))
+            }
+            crate::opcode::GAP_RESOLVE_PRIVATE_ADDRESS => {
+                require_len(params, 6)?;
+                Ok(VendorCommand::GapResolvePrivateAddress(to_bd_addr(params)))
+            }
+            crate::opcode::GAP_SET_BROADCAST_MODE => Ok(VendorCommand::GapSetBroadcastMode(
+                to_broadcast_mode(params)?,
+            )),
+            crate::opcode::GAP_START_OBSERVATION_PROCEDURE => {
+                require_len(params, 7)?;
+                Ok(VendorCommand::GapStartObservationProcedure(
+                    ObservationProcedureParameters {
+                        scan_window: to_scan_window(&params[0..4])?,
+                        scan_type: to_scan_type(params[4])?,
+                        own_address_type: to_address_type(params[5])?,
+                        filter_duplicates: to_bool(params[6]),
+                    },
+                ))
+            }
+            crate::opcode::GAP_GET_BONDED_DEVICES => {
+                require_len(params, 0).map(|_| VendorCommand::GapGetBondedDevices)
+            }
+            crate::opcode::GAP_IS_DEVICE_BONDED => {
+                require_len(params, 7)?;
+                Ok(VendorCommand::GapIsDeviceBonded(to_peer_address(params)?))
+            }
+            #[cfg(feature = "bluenrg2")]
+            crate::opcode::GAP_NUMERIC_COMPARISON_VALUE_CONFIRM => {
+                require_len(params, 3)?;
+                Ok(VendorCommand::GapNumericComparisonValueConfirm {
+                    conn_handle: to_conn_handle(params),
+                    confirmed: to_bool(params[2]),
+                })
+            }
+            #[cfg(feature = "bluenrg2")]
+            crate::opcode::GAP_PASS_KEY_INPUT => {
+                require_len(params, 3)?;
+                Ok(VendorCommand::GapPassKeyInput {
+                    conn_handle: to_conn_handle(params),
+                    input: PassKeyInput::try_from(params[2]).map_err(Error::BadPassKeyInput)?,
+                })
+            }
+            #[cfg(feature = "bluenrg2")]
+            crate::opcode::GAP_REMOVE_BONDED_DEVICE => {
+                require_len(params, 7)?;
+                Ok(VendorCommand::GapRemoveBondedDevice(to_peer_address(
+                    params,
+                )?))
+            }
+            crate::opcode::GATT_INIT => require_len(params, 0).map(|_| VendorCommand::GattInit),
+            crate::opcode::GATT_ADD_SERVICE => {
+                let (uuid, next) = to_uuid(params, 0)?;
+                require_len(params, next + 2)?;
+                Ok(VendorCommand::GattAddService(AddServiceParameters {
+                    uuid,
+                    service_type: ServiceType::try_from(params[next])
+                        .map_err(Error::BadServiceType)?,
+                    max_attribute_records: params[next + 1] as usize,
+                }))
+            }
+            crate::opcode::GATT_INCLUDE_SERVICE => {
+                require_len_at_least(params, 6)?;
+                let (include_uuid, next) = to_uuid(params, 6)?;
+                require_len(params, next)?;
+                Ok(VendorCommand::GattIncludeService(
+                    IncludeServiceParameters {
+                        service_handle: to_service_handle(params),
+                        include_handle_range: to_handle_range(&params[2..6], ServiceHandle)?,
+                        include_uuid,
+                    },
+                ))
+            }
+            crate::opcode::GATT_ADD_CHARACTERISTIC => Ok(VendorCommand::GattAddCharacteristic(
+                to_add_characteristic_parameters(params)?,
+            )),
+            crate::opcode::GATT_ADD_CHARACTERISTIC_DESCRIPTOR => {
+                Ok(VendorCommand::GattAddCharacteristicDescriptor(
+                    to_add_descriptor_parameters(params)?,
+                ))
+            }
+            crate::opcode::GATT_UPDATE_CHARACTERISTIC_VALUE => {
+                require_len_at_least(params, 6)?;
+                require_len(params, 6 + params[5] as usize)?;
+                Ok(VendorCommand::GattUpdateCharacteristicValue(
+                    UpdateCharacteristicValueParameters {
+                        service_handle: to_service_handle(params),
+                        characteristic_handle: to_characteristic_handle(&params[2..]),
+                        offset: params[4] as usize,
+                        value: &params[6..],
+                    },
+                ))
+            }
+            crate::opcode::GATT_DELETE_CHARACTERISTIC => {
+                require_len(params, 4)?;
+                Ok(VendorCommand::GattDeleteCharacteristic {
+                    service: to_service_handle(params),
+                    characteristic: to_characteristic_handle(&params[2..]),
+                })
+            }
+            crate::opcode::GATT_DELETE_SERVICE => {
+                require_len(params, 2)?;
+                Ok(VendorCommand::GattDeleteService(to_service_handle(params)))
+            }
+            crate::opcode::GATT_DELETE_INCLUDED_SERVICE => {
+                require_len(params, 4)?;
+                Ok(VendorCommand::GattDeleteIncludedService(
+                    DeleteIncludedServiceParameters {
+                        service: to_service_handle(params),
+                        included_service: to_service_handle(&params[2..]),
+                    },
+                ))
+            }
+            crate::opcode::GATT_SET_EVENT_MASK => {
+                require_len(params, 4)?;
+                let bits = LittleEndian::read_u32(params);
+                Ok(VendorCommand::GattSetEventMask(
+                    crate::gatt::Event::from_bits(bits).ok_or(Error::BadGattEventMask(bits))?,
+                ))
+            }
+            crate::opcode::GATT_EXCHANGE_CONFIGURATION => {
+                require_len(params, 2)?;
+                Ok(VendorCommand::GattExchangeConfiguration(to_conn_handle(
+                    params,
+                )))
+            }
+            crate::opcode::GATT_FIND_INFORMATION_REQUEST => {
+                require_len(params, 6)?;
+                Ok(VendorCommand::GattFindInformationRequest {
+                    conn_handle: to_conn_handle(params),
+                    attribute_range: to_handle_range(&params[2..6], CharacteristicHandle)?,
+                })
+            }
+            crate::opcode::GATT_FIND_BY_TYPE_VALUE_REQUEST => {
+                require_len_at_least(params, 9)?;
+                require_len(params, 9 + params[8] as usize)?;
+                Ok(VendorCommand::GattFindByTypeValueRequest(
+                    FindByTypeValueParameters {
+                        conn_handle: to_conn_handle(params),
+                        attribute_handle_range: to_handle_range(
+                            &params[2..6],
+                            CharacteristicHandle,
+                        )?,
+                        uuid: Uuid16(LittleEndian::read_u16(&params[6..8])),
+                        value: &params[9..],
+                    },
+                ))
+            }
+            crate::opcode::GATT_READ_BY_TYPE_REQUEST => Ok(VendorCommand::GattReadByTypeRequest(
+                to_read_by_type_parameters(params)?,
+            )),
+            crate::opcode::GATT_READ_BY_GROUP_TYPE_REQUEST => Ok(
+                VendorCommand::GattReadByGroupTypeRequest(to_read_by_type_parameters(params)?),
+            ),
+            crate::opcode::GATT_PREPARE_WRITE_REQUEST => {
+                require_len_at_least(params, 7)?;
+                require_len(params, 7 + params[6] as usize)?;
+                Ok(VendorCommand::GattPrepareWriteRequest(WriteRequest {
+                    conn_handle: to_conn_handle(params),
+                    attribute_handle: to_characteristic_handle(&params[2..]),
+                    offset: LittleEndian::read_u16(&params[4..6]) as usize,
+                    value: &params[7..],
+                }))
+            }
+            crate::opcode::GATT_EXECUTE_WRITE_REQUEST => {
+                require_len(params, 3)?;
+                if to_bool(params[2]) {
+                    Ok(VendorCommand::GattExecuteWriteRequest(to_conn_handle(
+                        params,
+                    )))
+                } else {
+                    Ok(VendorCommand::GattCancelWriteRequest(to_conn_handle(
+                        params,
+                    )))
+                }
+            }
+            crate::opcode::GATT_DISCOVER_ALL_PRIMARY_SERVICES => {
+                require_len(params, 2)?;
+                Ok(VendorCommand::GattDiscoverAllPrimaryServices(
+                    to_conn_handle(params),
+                ))
+            }
+            crate::opcode::GATT_DISCOVER_PRIMARY_SERVICES_BY_UUID => {
+                require_len_at_least(params, 2)?;
+                let (uuid, next) = to_uuid(params, 2)?;
+                require_len(params, next)?;
+                Ok(VendorCommand::GattDiscoverPrimaryServicesByUuid {
+                    conn_handle: to_conn_handle(params),
+                    uuid,
+                })
+            }
+            crate::opcode::GATT_FIND_INCLUDED_SERVICES => {
+                require_len(params, 6)?;
+                Ok(VendorCommand::GattFindIncludedServices {
+                    conn_handle: to_conn_handle(params),
+                    service_handle_range: to_handle_range(&params[2..6], ServiceHandle)?,
+                })
+            }
+            crate::opcode::GATT_DISCOVER_ALL_CHARACTERISTICS_OF_SERVICE => {
+                require_len(params, 6)?;
+                Ok(VendorCommand::GattDiscoverAllCharacteristicsOfService {
+                    conn_handle: to_conn_handle(params),
+                    attribute_handle_range: to_handle_range(&params[2..6], CharacteristicHandle)?,
+                })
+            }
+            crate::opcode::GATT_DISCOVER_CHARACTERISTICS_BY_UUID => {
+                require_len_at_least(params, 6)?;
+                let (uuid, next) = to_uuid(params, 6)?;
+                require_len(params, next)?;
+                Ok(VendorCommand::GattDiscoverCharacteristicsByUuid {
+                    conn_handle: to_conn_handle(params),
+                    attribute_handle_range: to_handle_range(&params[2..6], CharacteristicHandle)?,
+                    uuid,
+                })
+            }
+            crate::opcode::GATT_DISCOVER_ALL_CHARACTERISTIC_DESCRIPTORS => {
+                require_len(params, 6)?;
+                Ok(VendorCommand::GattDiscoverAllCharacteristicDescriptors {
+                    conn_handle: to_conn_handle(params),
+                    characteristic_handle_range: to_handle_range(
+                        &params[2..6],
+                        CharacteristicHandle,
+                    )?,
+                })
+            }
+            crate::opcode::GATT_READ_CHARACTERISTIC_VALUE => {
+                require_len(params, 4)?;
+                Ok(VendorCommand::GattReadCharacteristicValue {
+                    conn_handle: to_conn_handle(params),
+                    characteristic_handle: to_characteristic_handle(&params[2..]),
+                })
+            }
+            crate::opcode::GATT_READ_CHARACTERISTIC_BY_UUID => {
+                require_len_at_least(params, 6)?;
+                let (uuid, next) = to_uuid(params, 6)?;
+                require_len(params, next)?;
+                Ok(VendorCommand::GattReadCharacteristicUsingUuid {
+                    conn_handle: to_conn_handle(params),
+                    characteristic_handle_range: to_handle_range(
+                        &params[2..6],
+                        CharacteristicHandle,
+                    )?,
+                    uuid,
+                })
+            }
+            crate::opcode::GATT_READ_LONG_CHARACTERISTIC_VALUE => {
+                Ok(VendorCommand::GattReadLongCharacteristicValue(
+                    to_long_characteristic_read_parameters(params)?,
+                ))
+            }
+            crate::opcode::GATT_READ_MULTIPLE_CHARACTERISTIC_VALUES => {
+                Ok(VendorCommand::GattReadMultipleCharacteristicValues(
+                    to_multiple_characteristic_read(params)?,
+                ))
+            }
+            crate::opcode::GATT_WRITE_CHARACTERISTIC_VALUE => Ok(
+                VendorCommand::GattWriteCharacteristicValue(to_characteristic_value(params)?),
+            ),
+            crate::opcode::GATT_WRITE_LONG_CHARACTERISTIC_VALUE => {
+                Ok(VendorCommand::GattWriteLongCharacteristicValue(
+                    to_long_characteristic_value(params)?,
+                ))
+            }
+            crate::opcode::GATT_WRITE_CHARACTERISTIC_VALUE_RELIABLY => {
+                Ok(VendorCommand::GattWriteCharacteristicValueReliably(
+                    to_long_characteristic_value(params)?,
+                ))
+            }
+            crate::opcode::GATT_WRITE_LONG_CHARACTERISTIC_DESCRIPTOR => {
+                Ok(VendorCommand::GattWriteLongCharacteristicDescriptor(
+                    to_long_characteristic_value(params)?,
+                ))
+            }
+            crate::opcode::GATT_READ_LONG_CHARACTERISTIC_DESCRIPTOR => {
+                Ok(VendorCommand::GattReadLongCharacteristicDescriptor(
+                    to_long_characteristic_read_parameters(params)?,
+                ))
+            }
+            crate::opcode::GATT_WRITE_CHARACTERISTIC_DESCRIPTOR => Ok(
+                VendorCommand::GattWriteCharacteristicDescriptor(to_characteristic_value(params)?),
+            ),
+            crate::opcode::GATT_READ_CHARACTERISTIC_DESCRIPTOR => {
+                require_len(params, 4)?;
+                Ok(VendorCommand::GattReadCharacteristicDescriptor {
+                    conn_handle: to_conn_handle(params),
+                    characteristic_handle: to_characteristic_handle(&params[2..]),
+                })
+            }
+            crate::opcode::GATT_WRITE_WITHOUT_RESPONSE => Ok(
+                VendorCommand::GattWriteWithoutResponse(to_characteristic_value(params)?),
+            ),
+            crate::opcode::GATT_SIGNED_WRITE_WITHOUT_RESPONSE => Ok(
+                VendorCommand::GattSignedWriteWithoutResponse(to_characteristic_value(params)?),
+            ),
+            crate::opcode::GATT_CONFIRM_INDICATION => {
+                require_len(params, 2)?;
+                Ok(VendorCommand::GattConfirmIndication(to_conn_handle(params)))
+            }
+            crate::opcode::GATT_WRITE_RESPONSE => {
+                require_len_at_least(params, 7)?;
+                require_len(params, 7 + params[6] as usize)?;
+                Ok(VendorCommand::GattWriteResponse(WriteResponseParameters {
+                    conn_handle: to_conn_handle(params),
+                    attribute_handle: to_characteristic_handle(&params[2..]),
+                    status: if to_bool(params[4]) {
+                        Err(to_status(params[5])?)
+                    } else {
+                        Ok(())
+                    },
+                    value: &params[7..],
+                }))
+            }
+            crate::opcode::GATT_ALLOW_READ => {
+                require_len(params, 2)?;
+                Ok(VendorCommand::GattAllowRead(to_conn_handle(params)))
+            }
+            crate::opcode::GATT_SET_SECURITY_PERMISSION => {
+                require_len(params, 5)?;
+                Ok(VendorCommand::GattSetSecurityPermission(
+                    SecurityPermissionParameters {
+                        service_handle: to_service_handle(params),
+                        attribute_handle: to_characteristic_handle(&params[2..]),
+                        permission: to_characteristic_permission(params[4])?,
+                    },
+                ))
+            }
+            crate::opcode::GATT_SET_DESCRIPTOR_VALUE => {
+                require_len_at_least(params, 9)?;
+                require_len(params, 9 + params[8] as usize)?;
+                Ok(VendorCommand::GattSetDescriptorValue(
+                    DescriptorValueParameters {
+                        service_handle: to_service_handle(params),
+                        characteristic_handle: to_characteristic_handle(&params[2..]),
+                        descriptor_handle: DescriptorHandle(LittleEndian::read_u16(&params[4..6])),
+                        offset: LittleEndian::read_u16(&params[6..8]) as usize,
+                        value: &params[9..],
+                    },
+                ))
+            }
+            crate::opcode::GATT_READ_HANDLE_VALUE => {
+                require_len(params, 2)?;
+                Ok(VendorCommand::GattReadHandleValue(
+                    to_characteristic_handle(params),
+                ))
+            }
+            crate::opcode::GATT_READ_HANDLE_VALUE_OFFSET => {
+                require_len(params, 3)?;
+                Ok(VendorCommand::GattReadHandleValueOffset {
+                    handle: to_characteristic_handle(params),
+                    offset: params[2] as usize,
+                })
+            }
+            crate::opcode::GATT_UPDATE_LONG_CHARACTERISTIC_VALUE => {
+                require_len_at_least(params, 10)?;
+                require_len(params, 10 + params[9] as usize)?;
+                Ok(VendorCommand::GattUpdateLongCharacteristicValue(
+                    UpdateLongCharacteristicValueParameters {
+                        service_handle: to_service_handle(params),
+                        characteristic_handle: to_characteristic_handle(&params[2..]),
+                        update_type: UpdateType::from_bits(params[4])
+                            .ok_or(Error::BadUpdateType(params[4]))?,
+                        total_len: LittleEndian::read_u16(&params[5..7]) as usize,
+                        offset: LittleEndian::read_u16(&params[7..9]) as usize,
+                        value: &params[10..],
+                    },
+                ))
+            }
+            crate::opcode::L2CAP_CONN_PARAM_UPDATE_REQ => {
+                require_len(params, 10)?;
+                Ok(VendorCommand::L2CapConnectionParameterUpdateRequest(
+                    ConnectionParameterUpdateRequest {
+                        conn_handle: to_conn_handle(params),
+                        conn_interval: to_conn_interval(&params[2..10])?,
+                    },
+                ))
+            }
+            crate::opcode::L2CAP_CONN_PARAM_UPDATE_RESP => {
+                require_len(params, 16)?;
+                Ok(VendorCommand::L2CapConnectionParameterUpdateResponse(
+                    ConnectionParameterUpdateResponse {
+                        conn_handle: to_conn_handle(params),
+                        conn_interval: to_conn_interval(&params[2..10])?,
+                        expected_connection_length_range: to_expected_connection_length(
+                            &params[10..14],
+                        )?,
+                        identifier: params[14],
+                        accepted: to_bool(params[15]),
+                    },
+                ))
+            }
+            crate::opcode::UPDATER_START => {
+                require_len(params, 0).map(|_| VendorCommand::UpdaterStart)
+            }
+            crate::opcode::UPDATER_REBOOT => {
+                require_len(params, 0).map(|_| VendorCommand::UpdaterReboot)
+            }
+            crate::opcode::UPDATER_GET_VERSION => {
+                require_len(params, 0).map(|_| VendorCommand::UpdaterGetVersion)
+            }
+            crate::opcode::UPDATER_GET_BUFFER_SIZE => {
+                require_len(params, 0).map(|_| VendorCommand::UpdaterGetBufferSize)
+            }
+            crate::opcode::UPDATER_ERASE_BLUE_FLAG => {
+                require_len(params, 0).map(|_| VendorCommand::UpdaterEraseBlueFlag)
+            }
+            crate::opcode::UPDATER_RESET_BLUE_FLAG => {
+                require_len(params, 0).map(|_| VendorCommand::UpdaterResetBlueFlag)
+            }
+            crate::opcode::UPDATER_ERASE_SECTOR => {
+                require_len(params, 4)?;
+                Ok(VendorCommand::UpdaterEraseSector(LittleEndian::read_u32(
+                    params,
+                )))
+            }
+            crate::opcode::UPDATER_PROGRAM_DATA_BLOCK => {
+                require_len_at_least(params, 6)?;
+                require_len(params, 6 + LittleEndian::read_u16(&params[4..6]) as usize)?;
+                Ok(VendorCommand::UpdaterProgramDataBlock {
+                    address: LittleEndian::read_u32(&params[0..4]),
+                    data: &params[6..],
+                })
+            }
+            crate::opcode::UPDATER_READ_DATA_BLOCK => {
+                require_len(params, 6)?;
+                Ok(VendorCommand::UpdaterReadDataBlock {
+                    address: LittleEndian::read_u32(&params[0..4]),
+                    len: LittleEndian::read_u16(&params[4..6]) as usize,
+                })
+            }
+            crate::opcode::UPDATER_CALC_CRC => {
+                require_len(params, 5)?;
+                Ok(VendorCommand::UpdaterCalcCrc {
+                    address: LittleEndian::read_u32(&params[0..4]),
+                    sector_count: params[4],
+                })
+            }
+            crate::opcode::UPDATER_HW_VERSION => {
+                require_len(params, 0).map(|_| VendorCommand::UpdaterHwVersion)
+            }
+            _ => Err(Error::UnknownOpcode(opcode)),
+        }
+    }
+
+    /// Decodes a complete command packet, as written to the controller: the packet type
+    /// (`0x01`), the opcode, the parameter length and the parameters. The parameters are decoded
+    /// in the given [`Dialect`].
+    ///
+    /// # Errors
+    ///
+    /// - [`BadPacketType`](Error::BadPacketType) if the packet is not a command packet.
+    /// - [`BadLength`](Error::BadLength) if the packet is shorter than its header, or if the
+    ///   parameter length does not match the packet.
+    /// - Any of the errors returned by [`with_dialect`](VendorCommand::with_dialect).
+    pub fn from_packet(dialect: Dialect, packet: &'a [u8]) -> Result<VendorCommand<'a>, Error> {
+        require_len_at_least(packet, PACKET_HEADER_LEN)?;
+        if packet[0] != PACKET_TYPE_COMMAND {
+            return Err(Error::BadPacketType(packet[0]));
+        }
+        require_len(packet, PACKET_HEADER_LEN + packet[3] as usize)?;
+
+        VendorCommand::with_dialect(
+            dialect,
+            hci::Opcode(LittleEndian::read_u16(&packet[1..3])),
+            &packet[PACKET_HEADER_LEN..],
+        )
+    }
+}
+
+// Longest white list that fits in the parameters of any command.
+const MAX_WHITE_LIST_LEN: usize = 35;
+
+// Peer addresses of a white list, with a buffer that can hold the longest white list.
+#[derive(Copy, Clone, Debug)]
+struct WhiteList {
+    len: usize,
+    buffer: [PeerAddrType; MAX_WHITE_LIST_LEN],
+}
+
+impl WhiteList {
+    fn as_slice(&self) -> &[PeerAddrType] {
+        &self.buffer[..self.len]
+    }
+}
+
+/// Decoded parameters of the [GAP Start Auto Connection
+/// Establishment](crate::gap::Commands::start_auto_connection_establishment) command.
+#[derive(Debug)]
+pub struct AutoConnectionEstablishment {
+    /// Scanning intervals.
+    pub scan_window: ScanWindow,
+
+    /// Address type of this device.
+    pub own_address_type: OwnAddressType,
+
+    /// Connection interval parameters.
+    pub conn_interval: ConnectionInterval,
+
+    /// Expected connection length.
+    pub expected_connection_length: ExpectedConnectionLength,
+
+    /// Reconnection address, if any. Always `None` for the BlueNRG-MS.
+    pub reconnection_address: Option<hci::BdAddr>,
+
+    white_list: WhiteList,
+}
+
+impl AutoConnectionEstablishment {
+    /// Returns the addresses of the devices to connect to.
+    pub fn white_list(&self) -> &[PeerAddrType] {
+        self.white_list.as_slice()
+    }
+
+    /// Returns the parameters of the command.
+    pub fn parameters(&self) -> AutoConnectionEstablishmentParameters<'_> {
+        AutoConnectionEstablishmentParameters {
+            scan_window: self.scan_window.clone(),
+            own_address_type: self.own_address_type,
+            conn_interval: self.conn_interval,
+            expected_connection_length: self.expected_connection_length.clone(),
+            reconnection_address: self.reconnection_address,
+            white_list: self.white_list(),
+        }
+    }
+}
+
+/// Decoded parameters of the [GAP Start Selective Connection
+/// Establishment](crate::gap::Commands::start_selective_connection_establishment) command.
+#[derive(Debug)]
+pub struct SelectiveConnectionEstablishment {
+    /// Type of scanning.
+    pub scan_type: ScanType,
+
+    /// Scanning intervals.
+    pub scan_window: ScanWindow,
+
+    /// Address type of this device.
+    pub own_address_type: OwnAddressType,
+
+    /// Are duplicate devices filtered out?
+    pub filter_duplicates: bool,
+
+    white_list: WhiteList,
+}
+
+impl SelectiveConnectionEstablishment {
+    /// Returns the addresses of the devices to connect to.
+    pub fn white_list(&self) -> &[PeerAddrType] {
+        self.white_list.as_slice()
+    }
+
+    /// Returns the parameters of the command.
+    pub fn parameters(&self) -> SelectiveConnectionEstablishmentParameters<'_> {
+        SelectiveConnectionEstablishmentParameters {
+            scan_type: self.scan_type,
+            scan_window: self.scan_window.clone(),
+            own_address_type: self.own_address_type,
+            filter_duplicates: self.filter_duplicates,
+            white_list: self.white_list(),
+        }
+    }
+}
+
+/// Decoded parameters of the [GAP Set Broadcast Mode](crate::gap::Commands::set_broadcast_mode)
+/// command.
+#[derive(Debug)]
+pub struct BroadcastMode<'a> {
+    /// Advertising type and interval.
+    pub advertising_interval: AdvertisingInterval,
+
+    /// Address type of this device.
+    pub own_address_type: AddressType,
+
+    /// Advertising data.
+    pub advertising_data: &'a [u8],
+
+    white_list: WhiteList,
+}
+
+impl<'a> BroadcastMode<'a> {
+    /// Returns the addresses of the devices to add to the white list.
+    pub fn white_list(&self) -> &[PeerAddrType] {
+        self.white_list.as_slice()
+    }
+
+    /// Returns the parameters of the command.
+    pub fn parameters(&self) -> BroadcastModeParameters<'a, '_> {
+        BroadcastModeParameters {
+            advertising_interval: self.advertising_interval.clone(),
+            own_address_type: self.own_address_type,
+            advertising_data: self.advertising_data,
+            white_list: self.white_list(),
+        }
+    }
+}
+
+// Most handles that fit in the parameters of the GATT Read Multiple Characteristic Values command.
+const MAX_HANDLES: usize = 126;
+
+/// Decoded parameters of the [GATT Read Multiple Characteristic
+/// Values](crate::gatt::Commands::read_multiple_characteristic_values) command.
+#[derive(Debug)]
+pub struct MultipleCharacteristicRead {
+    /// Handle of the connection.
+    pub conn_handle: hci::ConnectionHandle,
+
+    handle_count: usize,
+    handle_buffer: [CharacteristicHandle; MAX_HANDLES],
+}
+
+impl MultipleCharacteristicRead {
+    /// Returns the handles of the characteristics to read.
+    pub fn handles(&self) -> &[CharacteristicHandle] {
+        &self.handle_buffer[..self.handle_count]
+    }
+
+    /// Returns the parameters of the command.
+    pub fn parameters(&self) -> MultipleCharacteristicReadParameters<'_> {
+        MultipleCharacteristicReadParameters {
+            conn_handle: self.conn_handle,
+            handles: self.handles(),
+        }
+    }
+}
+
+fn require_len(bytes: &[u8], len: usize) -> Result<(), Error> {
+    if bytes.len() != len {
+        return Err(Error::BadLength(bytes.len(), len));
+    }
+
+    Ok(())
+}
+
+fn require_len_at_least(bytes: &[u8], len: usize) -> Result<(), Error> {
+    if bytes.len() < len {
+        return Err(Error::BadLength(bytes.len(), len));
+    }
+
+    Ok(())
+}
+
+fn to_bool(byte: u8) -> bool {
+    byte != 0
+}
+
+fn to_conn_handle(bytes: &[u8]) -> hci::ConnectionHandle {
+    hci::ConnectionHandle(LittleEndian::read_u16(&bytes[0..2]))
+}
+
+fn to_service_handle(bytes: &[u8]) -> ServiceHandle {
+    ServiceHandle(LittleEndian::read_u16(&bytes[0..2]))
+}
+
+fn to_characteristic_handle(bytes: &[u8]) -> CharacteristicHandle {
+    CharacteristicHandle(LittleEndian::read_u16(&bytes[0..2]))
+}
+
+fn to_handle_range<T: PartialOrd>(bytes: &[u8], handle: fn(u16) -> T) -> Result<Range<T>, Error> {
+    let from = LittleEndian::read_u16(&bytes[0..2]);
+    let to = LittleEndian::read_u16(&bytes[2..4]);
+
+    Range::new(handle(from), handle(to)).map_err(|_| Error::BadHandleRange(from, to))
+}
+
+fn to_bd_addr(bytes: &[u8]) -> hci::BdAddr {
+    let mut addr = [0; 6];
+    addr.copy_from_slice(&bytes[0..6]);
+
+    hci::BdAddr(addr)
+}
+
+fn to_peer_address(bytes: &[u8]) -> Result<PeerAddrType, Error> {
+    let addr = to_bd_addr(&bytes[1..7]);
+    match bytes[0] {
+        0x00 => Ok(PeerAddrType::PublicDeviceAddress(addr)),
+        0x01 => Ok(PeerAddrType::RandomDeviceAddress(addr)),
+        other => Err(Error::BadPeerAddressType(other)),
+    }
+}
+
+fn to_status(byte: u8) -> Result<hci::Status<crate::event::Status>, Error> {
+    hci::Status::try_from(byte).map_err(|_| Error::BadStatus(byte))
+}
+
+fn to_address_type(byte: u8) -> Result<AddressType, Error> {
+    AddressType::try_from(byte).map_err(Error::BadAddressType)
+}
+
+fn to_own_address_type(byte: u8) -> Result<OwnAddressType, Error> {
+    match byte {
+        0x00 => Ok(OwnAddressType::Public),
+        0x01 => Ok(OwnAddressType::Random),
+        other => Err(Error::BadOwnAddressType(other)),
+    }
+}
+
+fn to_advertising_type(byte: u8) -> Result<AdvertisingType, Error> {
+    match byte {
+        0x00 => Ok(AdvertisingType::ConnectableUndirected),
+        0x01 => Ok(AdvertisingType::ConnectableDirectedHighDutyCycle),
+        0x02 => Ok(AdvertisingType::ScannableUndirected),
+        0x03 => Ok(AdvertisingType::NonConnectableUndirected),
+        0x04 => Ok(AdvertisingType::ConnectableDirectedLowDutyCycle),
+        other => Err(Error::BadAdvertisingType(other)),
+    }
+}
+
+fn to_advertising_filter_policy(byte: u8) -> Result<AdvertisingFilterPolicy, Error> {
+    match byte {
+        0x00 => Ok(AdvertisingFilterPolicy::AllowConnectionAndScan),
+        0x01 => Ok(AdvertisingFilterPolicy::AllowConnectionWhiteListScan),
+        0x02 => Ok(AdvertisingFilterPolicy::WhiteListConnectionAllowScan),
+        0x03 => Ok(AdvertisingFilterPolicy::WhiteListConnectionAndScan),
+        other => Err(Error::BadAdvertisingFilterPolicy(other)),
+    }
+}
+
+fn to_scan_type(byte: u8) -> Result<ScanType, Error> {
+    match byte {
+        0x00 => Ok(ScanType::Passive),
+        0x01 => Ok(ScanType::Active),
+        other => Err(Error::BadScanType(other)),
+    }
+}
+
+// Inverse of `to_connection_length_value` in the GAP commands: T = N * 0.625 ms.
+fn from_connection_length_value(value: u16) -> Duration {
+    Duration::from_micros(625 * u64::from(value))
+}
+
+// Inverse of `to_conn_interval_value` in the GAP commands: T = N * 1.25 ms.
+fn from_conn_interval_value(value: u16) -> Duration {
+    Duration::from_micros(1250 * u64::from(value))
+}
+
+fn to_scan_window(bytes: &[u8]) -> Result<ScanWindow, Error> {
+    ScanWindow::start_every(from_connection_length_value(LittleEndian::read_u16(
+        &bytes[0..2],
+    )))
+    .and_then(|builder| {
+        builder.open_for(from_connection_length_value(LittleEndian::read_u16(
+            &bytes[2..4],
+        )))
+    })
+    .map_err(Error::BadScanWindow)
+}
+
+fn to_conn_interval(bytes: &[u8]) -> Result<ConnectionInterval, Error> {
+    ConnectionInterval::from_bytes(&bytes[0..8]).map_err(Error::BadConnectionInterval)
+}
+
+fn to_expected_connection_length(bytes: &[u8]) -> Result<ExpectedConnectionLength, Error> {
+    ExpectedConnectionLength::new(
+        from_connection_length_value(LittleEndian::read_u16(&bytes[0..2])),
+        from_connection_length_value(LittleEndian::read_u16(&bytes[2..4])),
+    )
+    .map_err(Error::BadExpectedConnectionLength)
+}
+
+// Reads a UUID that starts at `index`. Returns the UUID and the index of the next parameter.
+fn to_uuid(bytes: &[u8], index: usize) -> Result<(Uuid, usize), Error> {
+    require_len_at_least(bytes, index + 1)?;
+    match bytes[index] {
+        0x01 => {
+            require_len_at_least(bytes, index + 3)?;
+            Ok((
+                Uuid::Uuid16(LittleEndian::read_u16(&bytes[index + 1..index + 3])),
+                index + 3,
+            ))
+        }
+        0x02 => {
+            require_len_at_least(bytes, index + 17)?;
+            let mut uuid = [0; 16];
+            uuid.copy_from_slice(&bytes[index + 1..index + 17]);
+            Ok((Uuid::Uuid128(uuid), index + 17))
+        }
+        other => Err(Error::BadUuidType(other)),
+    }
+}
+
+// Reads a white list whose length is at `index`, and which ends the parameters.
+fn to_white_list(bytes: &[u8], index: usize) -> Result<WhiteList, Error> {
+    const ADDR_LEN: usize = 7;
+
+    require_len_at_least(bytes, index + 1)?;
+    let len = bytes[index] as usize;
+    require_len(bytes, index + 1 + ADDR_LEN * len)?;
+
+    let mut white_list = WhiteList {
+        len,
+        buffer: [PeerAddrType::PublicDeviceAddress(hci::BdAddr([0; 6])); MAX_WHITE_LIST_LEN],
+    };
+    for (i, addr) in white_list.buffer.iter_mut().enumerate().take(len) {
+        let start = index + 1 + ADDR_LEN * i;
+        *addr = to_peer_address(&bytes[start..start + ADDR_LEN])?;
+    }
+
+    Ok(white_list)
+}
+
+fn to_config_data(bytes: &[u8]) -> Result<ConfigData, Error> {
+    require_len_at_least(bytes, 2)?;
+    let len = 2 + bytes[1] as usize;
+    require_len(bytes, len)?;
+    if len > ConfigData::MAX_LENGTH {
+        return Err(Error::BadLength(len, ConfigData::MAX_LENGTH));
+    }
+
+    Ok(ConfigData::from_parts(bytes[0], &bytes[2..]))
+}
+
+fn to_discoverable_parameters(bytes: &[u8]) -> Result<DiscoverableParameters<'_, '_>, Error> {
+    const NO_SPECIFIC_CONN_INTERVAL: u16 = 0xFFFF;
+    const AD_TYPE_SHORTENED_LOCAL_NAME: u8 = 0x08;
+    const AD_TYPE_COMPLETE_LOCAL_NAME: u8 = 0x09;
+
+    require_len_at_least(bytes, 8)?;
+    let advertising_interval = match (
+        LittleEndian::read_u16(&bytes[1..3]),
+        LittleEndian::read_u16(&bytes[3..5]),
+    ) {
+        (0, 0) => None,
+        (min, max) => Some((
+            from_connection_length_value(min),
+            from_connection_length_value(max),
+        )),
+    };
+
+    let name_len = bytes[7] as usize;
+    let advertising_data_len_index = 8 + name_len;
+    require_len_at_least(bytes, advertising_data_len_index + 1)?;
+    let local_name = if name_len == 0 {
+        None
+    } else {
+        let name = &bytes[9..8 + name_len];
+        match bytes[8] {
+            AD_TYPE_SHORTENED_LOCAL_NAME => Some(LocalName::Shortened(name)),
+            AD_TYPE_COMPLETE_LOCAL_NAME => Some(LocalName::Complete(name)),
+            other => return Err(Error::BadLocalNameType(other)),
+        }
+    };
+
+    let advertising_data_index = advertising_data_len_index + 1;
+    let conn_interval_index = advertising_data_index + bytes[advertising_data_len_index] as usize;
+    require_len(bytes, conn_interval_index + 4)?;
+    let to_conn_interval_bound = |value| {
+        if value == NO_SPECIFIC_CONN_INTERVAL {
+            None
+        } else {
+            Some(from_conn_interval_value(value))
+        }
+    };
+
+    Ok(DiscoverableParameters {
+        advertising_type: to_advertising_type(bytes[0])?,
+        advertising_interval,
+        address_type: to_own_address_type(bytes[5])?,
+        filter_policy: to_advertising_filter_policy(bytes[6])?,
+        local_name,
+        advertising_data: &bytes[advertising_data_index..conn_interval_index],
+        conn_interval: (
+            to_conn_interval_bound(LittleEndian::read_u16(
+                &bytes[conn_interval_index..conn_interval_index + 2],
+            )),
+            to_conn_interval_bound(LittleEndian::read_u16(
+                &bytes[conn_interval_index + 2..conn_interval_index + 4],
+            )),
+        ),
+    })
+}
+
+// The original BlueNRG only supports high duty cycle advertising, and does not send the advertising
+// interval, so it is decoded as the shortest interval.
+fn to_direct_connectable_parameters(
+    dialect: Dialect,
+    bytes: &[u8],
+) -> Result<DirectConnectableParameters, Error> {
+    if !dialect.is_ms() {
+        require_len(bytes, 8)?;
+        return Ok(DirectConnectableParameters {
+            own_address_type: to_own_address_type(bytes[0])?,
+            advertising_type: AdvertisingType::ConnectableDirectedHighDutyCycle,
+            initiator_address: to_initiator_address(&bytes[1..8])?,
+            advertising_interval: (Duration::from_millis(20), Duration::from_millis(20)),
+        });
+    }
+
+    require_len(bytes, 13)?;
+    Ok(DirectConnectableParameters {
+        own_address_type: to_own_address_type(bytes[0])?,
+        advertising_type: to_advertising_type(bytes[1])?,
+        initiator_address: to_initiator_address(&bytes[2..9])?,
+        advertising_interval: (
+            from_connection_length_value(LittleEndian::read_u16(&bytes[9..11])),
+            from_connection_length_value(LittleEndian::read_u16(&bytes[11..13])),
+        ),
+    })
+}
+
+fn to_initiator_address(bytes: &[u8]) -> Result<hci::BdAddrType, Error> {
+    hci::to_bd_addr_type(bytes[0], to_bd_addr(&bytes[1..7]))
+        .map_err(|e| Error::BadPeerAddressType(e.0))
+}
+
+fn to_authentication_requirements(bytes: &[u8]) -> Result<AuthenticationRequirements, Error> {
+    require_len(bytes, 26)?;
+
+    Ok(AuthenticationRequirements {
+        mitm_protection_required: to_bool(bytes[0]),
+        out_of_band_auth: if to_bool(bytes[1]) {
+            let mut data = [0; 16];
+            data.copy_from_slice(&bytes[2..18]);
+            OutOfBandAuthentication::Enabled(data)
+        } else {
+            OutOfBandAuthentication::Disabled
+        },
+        encryption_key_size_range: (bytes[18], bytes[19]),
+        fixed_pin: if to_bool(bytes[20]) {
+            Pin::Requested
+        } else {
+            Pin::Fixed(LittleEndian::read_u32(&bytes[21..25]))
+        },
+        bonding_required: to_bool(bytes[25]),
+    })
+}
+
+// Commands that only the BlueNRG-MS supports.
+fn is_ms_only(opcode: hci::Opcode) -> bool {
+    matches!(
+        opcode,
+        crate::opcode::GAP_SET_BROADCAST_MODE
+            | crate::opcode::GAP_START_OBSERVATION_PROCEDURE
+            | crate::opcode::GATT_READ_HANDLE_VALUE_OFFSET
+            | crate::opcode::GATT_UPDATE_LONG_CHARACTERISTIC_VALUE
+    )
+}
+
+fn to_role(byte: u8) -> Result<crate::gap::Role, Error> {
+    crate::gap::Role::from_bits(byte).ok_or(Error::BadRole(byte))
+}
+
+#[cfg(not(feature = "bluenrg2"))]
+fn to_gap_init(dialect: Dialect, bytes: &[u8]) -> Result<VendorCommand<'_>, Error> {
+    if !dialect.is_ms() {
+        require_len(bytes, 1)?;
+        return Ok(VendorCommand::GapInit {
+            role: to_role(bytes[0])?,
+            privacy_enabled: false,
+            dev_name_characteristic_len: crate::gap::BLUENRG_DEVICE_NAME_LEN,
+        });
+    }
+
+    require_len(bytes, 3)?;
+    Ok(VendorCommand::GapInit {
+        role: to_role(bytes[0])?,
+        privacy_enabled: to_bool(bytes[1]),
+        dev_name_characteristic_len: bytes[2],
+    })
+}
+
+#[cfg(feature = "bluenrg2")]
+fn to_gap_init(_dialect: Dialect, bytes: &[u8]) -> Result<VendorCommand<'_>, Error> {
+    require_len(bytes, 3)?;
+    Ok(VendorCommand::GapInit {
+        role: to_role(bytes[0])?,
+        privacy: Privacy::try_from(bytes[1]).map_err(Error::BadPrivacy)?,
+        dev_name_characteristic_len: bytes[2],
+    })
+}
+
+fn to_discovery_procedure_parameters(bytes: &[u8]) -> Result<DiscoveryProcedureParameters, Error> {
+    require_len(bytes, 6)?;
+
+    Ok(DiscoveryProcedureParameters {
+        scan_window: to_scan_window(&bytes[0..4])?,
+        own_address_type: to_own_address_type(bytes[4])?,
+        filter_duplicates: to_bool(bytes[5]),
+    })
+}
+
+fn to_name_discovery_procedure_parameters(
+    bytes: &[u8],
+) -> Result<NameDiscoveryProcedureParameters, Error> {
+    require_len(bytes, 24)?;
+
+    Ok(NameDiscoveryProcedureParameters {
+        scan_window: to_scan_window(&bytes[0..4])?,
+        peer_address: to_peer_address(&bytes[4..11])?,
+        own_address_type: to_own_address_type(bytes[11])?,
+        conn_interval: to_conn_interval(&bytes[12..20])?,
+        expected_connection_length: to_expected_connection_length(&bytes[20..24])?,
+    })
+}
+
+// Reads the optional reconnection address, which the original BlueNRG sends as a flag followed by
+// the address.
+fn to_reconnection_address(bytes: &[u8]) -> Option<hci::BdAddr> {
+    if to_bool(bytes[0]) {
+        Some(to_bd_addr(&bytes[1..7]))
+    } else {
+        None
+    }
+}
+
+fn to_auto_connection_establishment(
+    dialect: Dialect,
+    bytes: &[u8],
+) -> Result<AutoConnectionEstablishment, Error> {
+    let white_list_index = if dialect.is_ms() { 17 } else { 24 };
+    require_len_at_least(bytes, white_list_index)?;
+
+    Ok(AutoConnectionEstablishment {
+        scan_window: to_scan_window(&bytes[0..4])?,
+        own_address_type: to_own_address_type(bytes[4])?,
+        conn_interval: to_conn_interval(&bytes[5..13])?,
+        expected_connection_length: to_expected_connection_length(&bytes[13..17])?,
+        reconnection_address: if dialect.is_ms() {
+            None
+        } else {
+            to_reconnection_address(&bytes[17..24])
+        },
+        white_list: to_white_list(bytes, white_list_index)?,
+    })
+}
+
+fn to_general_connection_establishment_parameters(
+    dialect: Dialect,
+    bytes: &[u8],
+) -> Result<GeneralConnectionEstablishmentParameters, Error> {
+    require_len(bytes, if dialect.is_ms() { 6 } else { 13 })?;
+
+    Ok(GeneralConnectionEstablishmentParameters {
+        scan_window: to_scan_window(&bytes[0..4])?,
+        own_address_type: to_own_address_type(bytes[4])?,
+        filter_duplicates: to_bool(bytes[5]),
+        reconnection_address: if dialect.is_ms() {
+            None
+        } else {
+            to_reconnection_address(&bytes[6..13])
+        },
+    })
+}
+
+fn to_selective_connection_establishment(
+    bytes: &[u8],
+) -> Result<SelectiveConnectionEstablishment, Error> {
+    require_len_at_least(bytes, 7)?;
+
+    Ok(SelectiveConnectionEstablishment {
+        scan_type: to_scan_type(bytes[0])?,
+        scan_window: to_scan_window(&bytes[1..5])?,
+        own_address_type: to_own_address_type(bytes[5])?,
+        filter_duplicates: to_bool(bytes[6]),
+        white_list: to_white_list(bytes, 7)?,
+    })
+}
+
+fn to_broadcast_mode(bytes: &[u8]) -> Result<BroadcastMode<'_>, Error> {
+    require_len_at_least(bytes, 7)?;
+    let advertising_type = to_advertising_type(bytes[4])?;
+    let advertising_interval =
+        if advertising_type == AdvertisingType::ConnectableDirectedHighDutyCycle {
+            AdvertisingInterval::for_type(advertising_type).build()
+        } else {
+            AdvertisingInterval::for_type(advertising_type).with_range(
+                from_connection_length_value(LittleEndian::read_u16(&bytes[0..2])),
+                from_connection_length_value(LittleEndian::read_u16(&bytes[2..4])),
+            )
+        }
+        .map_err(Error::BadAdvertisingInterval)?;
+
+    let white_list_index = 7 + bytes[6] as usize;
+    require_len_at_least(bytes, white_list_index)?;
+
+    Ok(BroadcastMode {
+        advertising_interval,
+        own_address_type: to_address_type(bytes[5])?,
+        advertising_data: &bytes[7..white_list_index],
+        white_list: to_white_list(bytes, white_list_index)?,
+    })
+}
+
+fn to_characteristic_permission(byte: u8) -> Result<CharacteristicPermission, Error> {
+    CharacteristicPermission::from_bits(byte).ok_or(Error::BadCharacteristicPermission(byte))
+}
+
+fn to_characteristic_event(byte: u8) -> Result<CharacteristicEvent, Error> {
+    CharacteristicEvent::from_bits(byte).ok_or(Error::BadCharacteristicEvent(byte))
+}
+
+fn to_encryption_key_size(byte: u8) -> Result<EncryptionKeySize, Error> {
+    EncryptionKeySize::with_value(byte as usize).map_err(|_| Error::BadEncryptionKeySize(byte))
+}
+
+fn to_add_characteristic_parameters(bytes: &[u8]) -> Result<AddCharacteristicParameters, Error> {
+    // The characteristic value length, and the five one-byte parameters that follow it.
+    const LONG_TAIL_LEN: usize = 7;
+
+    require_len_at_least(bytes, 2)?;
+    let (characteristic_uuid, next) = to_uuid(bytes, 2)?;
+
+    // Firmware before version 7.2 takes a one-byte value length.
+    #[cfg(not(feature = "bluenrg2"))]
+    let fw_version_before_v72 = bytes.len() == next + LONG_TAIL_LEN - 1;
+    #[cfg(not(feature = "bluenrg2"))]
+    let short_value_len = fw_version_before_v72;
+    #[cfg(feature = "bluenrg2")]
+    let short_value_len = false;
+
+    let (characteristic_value_len, next) = if short_value_len {
+        (bytes[next] as usize, next + 1)
+    } else {
+        require_len(bytes, next + LONG_TAIL_LEN)?;
+        (
+            LittleEndian::read_u16(&bytes[next..next + 2]) as usize,
+            next + 2,
+        )
+    };
+
+    Ok(AddCharacteristicParameters {
+        service_handle: to_service_handle(bytes),
+        characteristic_uuid,
+        characteristic_value_len,
+        characteristic_properties: CharacteristicProperty::from_bits_truncate(bytes[next]),
+        security_permissions: to_characteristic_permission(bytes[next + 1])?,
+        gatt_event_mask: to_characteristic_event(bytes[next + 2])?,
+        encryption_key_size: to_encryption_key_size(bytes[next + 3])?,
+        is_variable: to_bool(bytes[next + 4]),
+        #[cfg(not(feature = "bluenrg2"))]
+        fw_version_before_v72,
+    })
+}
+
+// Reads the maximum and actual lengths of the descriptor value that start at `index`. Returns the
+// lengths and the index of the value.
+#[cfg(not(feature = "bluenrg2"))]
+fn to_descriptor_value_lengths(bytes: &[u8], index: usize) -> Result<(usize, usize, usize), Error> {
+    require_len_at_least(bytes, index + 2)?;
+
+    Ok((bytes[index] as usize, bytes[index + 1] as usize, index + 2))
+}
+
+#[cfg(feature = "bluenrg2")]
+fn to_descriptor_value_lengths(bytes: &[u8], index: usize) -> Result<(usize, usize, usize), Error> {
+    require_len_at_least(bytes, index + 4)?;
+
+    Ok((
+        LittleEndian::read_u16(&bytes[index..index + 2]) as usize,
+        LittleEndian::read_u16(&bytes[index + 2..index + 4]) as usize,
+        index + 4,
+    ))
+}
+
+fn to_add_descriptor_parameters(bytes: &[u8]) -> Result<AddDescriptorParameters<'_>, Error> {
+    require_len_at_least(bytes, 4)?;
+    let (descriptor_uuid, next) = to_uuid(bytes, 4)?;
+    let (descriptor_value_max_len, value_len, value_index) =
+        to_descriptor_value_lengths(bytes, next)?;
+    let next = value_index + value_len;
+    require_len(bytes, next + 5)?;
+
+    Ok(AddDescriptorParameters {
+        service_handle: to_service_handle(bytes),
+        characteristic_handle: to_characteristic_handle(&bytes[2..]),
+        descriptor_uuid,
+        descriptor_value_max_len,
+        descriptor_value: &bytes[value_index..next],
+        security_permissions: DescriptorPermission::from_bits(bytes[next])
+            .ok_or(Error::BadDescriptorPermission(bytes[next]))?,
+        access_permissions: AccessPermission::from_bits(bytes[next + 1])
+            .ok_or(Error::BadAccessPermission(bytes[next + 1]))?,
+        gatt_event_mask: to_characteristic_event(bytes[next + 2])?,
+        encryption_key_size: to_encryption_key_size(bytes[next + 3])?,
+        is_variable: to_bool(bytes[next + 4]),
+    })
+}
+
+fn to_read_by_type_parameters(bytes: &[u8]) -> Result<ReadByTypeParameters, Error> {
+    require_len_at_least(bytes, 6)?;
+    let (uuid, next) = to_uuid(bytes, 6)?;
+    require_len(bytes, next)?;
+
+    Ok(ReadByTypeParameters {
+        conn_handle: to_conn_handle(bytes),
+        attribute_handle_range: to_handle_range(&bytes[2..6], CharacteristicHandle)?,
+        uuid,
+    })
+}
+
+fn to_long_characteristic_read_parameters(
+    bytes: &[u8],
+) -> Result<LongCharacteristicReadParameters, Error> {
+    require_len(bytes, 6)?;
+
+    Ok(LongCharacteristicReadParameters {
+        conn_handle: to_conn_handle(bytes),
+        attribute: to_characteristic_handle(&bytes[2..]),
+        offset: LittleEndian::read_u16(&bytes[4..6]) as usize,
+    })
+}
+
+fn to_multiple_characteristic_read(bytes: &[u8]) -> Result<MultipleCharacteristicRead, Error> {
+    require_len_at_least(bytes, 3)?;
+    let handle_count = bytes[2] as usize;
+    require_len(bytes, 3 + 2 * handle_count)?;
+
+    let mut handle_buffer = [CharacteristicHandle(0); MAX_HANDLES];
+    for (i, handle) in handle_buffer.iter_mut().enumerate().take(handle_count) {
+        *handle = to_characteristic_handle(&bytes[3 + 2 * i..]);
+    }
+
+    Ok(MultipleCharacteristicRead {
+        conn_handle: to_conn_handle(bytes),
+        handle_count,
+        handle_buffer,
+    })
+}
+
+fn to_characteristic_value(bytes: &[u8]) -> Result<CharacteristicValue<'_>, Error> {
+    require_len_at_least(bytes, 5)?;
+    require_len(bytes, 5 + bytes[4] as usize)?;
+
+    Ok(CharacteristicValue {
+        conn_handle: to_conn_handle(bytes),
+        characteristic_handle: to_characteristic_handle(&bytes[2..]),
+        value: &bytes[5..],
+    })
+}
+
+fn to_long_characteristic_value(bytes: &[u8]) -> Result<LongCharacteristicValue<'_>, Error> {
+    require_len_at_least(bytes, 7)?;
+    require_len(bytes, 7 + bytes[6] as usize)?;
+
+    Ok(LongCharacteristicValue {
+        conn_handle: to_conn_handle(bytes),
+        characteristic_handle: to_characteristic_handle(&bytes[2..]),
+        offset: LittleEndian::read_u16(&bytes[4..6]) as usize,
+        value: &bytes[7..],
+    })
+}
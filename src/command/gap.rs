@@ -2,10 +2,13 @@
 
 extern crate bluetooth_hci as hci;
 extern crate byteorder;
-extern crate embedded_hal as hal;
 extern crate nb;
 
+use super::WriteCommand;
+use crate::capability::Capability;
+use crate::dialect::Dialect;
 use byteorder::{ByteOrder, LittleEndian};
+use core::convert::TryFrom;
 use core::time::Duration;
 pub use hci::host::{AdvertisingFilterPolicy, AdvertisingType, OwnAddressType};
 pub use hci::types::{ConnectionInterval, ExpectedConnectionLength, ScanWindow};
@@ -58,7 +61,6 @@ pub trait Commands {
     /// this and when advertising timeout happens (i.e. limited discovery period has elapsed),
     /// the controller generates an [GAP Limited Discoverable
     /// Complete](crate::event::BlueNRGEvent::GapLimitedDiscoverableTimeout) event.
-
     fn set_limited_discoverable<'a, 'b>(
         &mut self,
         params: &DiscoverableParameters<'a, 'b>,
@@ -103,12 +105,12 @@ pub trait Commands {
     /// what is specified in the Own Address Type parameter. The Advertising Type parameter in
     /// the command specifies the type of the advertising used.
     ///
-    /// When the `ms` feature is _not_ enabled, the device will be in directed connectable mode only
-    /// for 1.28 seconds. If no connection is established within this duration, the device enters
-    /// non discoverable mode and advertising will have to be again enabled explicitly.
-    ///
-    /// When the `ms` feature _is_ enabled, the advertising interval is explicitly provided in the
-    /// [parameters][DirectConnectableParameters].
+    /// The original BlueNRG [dialect](crate::dialect) only supports high duty cycle advertising, and
+    /// the device will be in directed connectable mode only for 1.28 seconds. If no connection is
+    /// established within this duration, the device enters non discoverable mode and advertising
+    /// will have to be again enabled explicitly. High duty cycle advertising has no interval, so
+    /// the advertising interval in the [parameters][DirectConnectableParameters] is only sent to
+    /// the BlueNRG-MS.
     ///
     /// # Errors
     ///
@@ -118,9 +120,12 @@ pub trait Commands {
     ///   [ConnectableUndirected](bluetooth_hci::host::AdvertisingType::ConnectableUndirected),
     ///   [ScannableUndirected](bluetooth_hci::host::AdvertisingType::ScannableUndirected), or
     ///   [NonConnectableUndirected](bluetooth_hci::host::AdvertisingType::NonConnectableUndirected),
-    /// - (`ms` feature only) [`BadAdvertisingInterval`](Error::BadAdvertisingInterval) if
+    /// - [`BadAdvertisingInterval`](Error::BadAdvertisingInterval) if
     ///   [`advertising_interval`](DiscoverableParameters::advertising_interval) is
     ///   out of range (20 ms to 10.24 s) or inverted (the min is greater than the max).
+    /// - [`UnsupportedInDialect`](Error::UnsupportedInDialect) if the advertising type is
+    ///   [ConnectableDirectedLowDutyCycle](bluetooth_hci::host::AdvertisingType::ConnectableDirectedLowDutyCycle)
+    ///   and the controller speaks the original BlueNRG dialect.
     ///
     /// # Generated evenst
     ///
@@ -232,36 +237,53 @@ pub trait Commands {
         authorization: Authorization,
     ) -> nb::Result<(), Self::Error>;
 
-    #[cfg(not(feature = "ms"))]
+    #[cfg(not(feature = "bluenrg2"))]
     /// Register the GAP service with the GATT.
     ///
     /// The device name characteristic and appearance characteristic are added by default and the
     /// handles of these characteristics are returned in the [event
-    /// data](::event::command::GapInit).
+    /// data](crate::event::command::GapInit).
+    ///
+    /// The original BlueNRG [dialect](crate::dialect) does not support privacy, and always adds a
+    /// device name characteristic of [`BLUENRG_DEVICE_NAME_LEN`] bytes.
     ///
     /// # Errors
     ///
-    /// Only underlying communication errors are reported.
+    /// - [`UnsupportedInDialect`](Error::UnsupportedInDialect) if the controller speaks the
+    ///   original BlueNRG dialect, and `privacy_enabled` is true or `dev_name_characteristic_len`
+    ///   is not [`BLUENRG_DEVICE_NAME_LEN`].
+    /// - Underlying communication errors.
     ///
     /// # Generated events
     ///
-    /// A [Command Complete](::event::command::ReturnParameters::GapInit) event is generated.
-    fn init(&mut self, role: Role) -> nb::Result<(), Self::Error>;
+    /// A [Command Complete](crate::event::command::ReturnParameters::GapInit) event is generated.
+    fn init(
+        &mut self,
+        role: Role,
+        privacy_enabled: bool,
+        dev_name_characteristic_len: u8,
+    ) -> nb::Result<(), Error<Self::Error>>;
 
-    #[cfg(not(feature = "ms"))]
+    #[cfg(not(feature = "bluenrg2"))]
     /// Register the GAP service with the GATT.
     ///
     /// This function exists to prevent name conflicts with other Commands traits' init methods.
-    fn init_gap(&mut self, role: Role) -> nb::Result<(), Self::Error> {
-        self.init(role)
+    fn init_gap(
+        &mut self,
+        role: Role,
+        privacy_enabled: bool,
+        dev_name_characteristic_len: u8,
+    ) -> nb::Result<(), Error<Self::Error>> {
+        self.init(role, privacy_enabled, dev_name_characteristic_len)
     }
 
-    #[cfg(feature = "ms")]
+    #[cfg(feature = "bluenrg2")]
     /// Register the GAP service with the GATT.
     ///
     /// The device name characteristic and appearance characteristic are added by default and the
     /// handles of these characteristics are returned in the [event
-    /// data](crate::event::command::GapInit).
+    /// data](crate::event::command::GapInit). The BlueNRG-1 and BlueNRG-2 can resolve private
+    /// addresses in either the host or the controller, so privacy is selected with [`Privacy`].
     ///
     /// # Errors
     ///
@@ -273,45 +295,23 @@ pub trait Commands {
     fn init(
         &mut self,
         role: Role,
-        privacy_enabled: bool,
+        privacy: Privacy,
         dev_name_characteristic_len: u8,
     ) -> nb::Result<(), Self::Error>;
 
-    #[cfg(feature = "ms")]
+    #[cfg(feature = "bluenrg2")]
     /// Register the GAP service with the GATT.
     ///
     /// This function exists to prevent name conflicts with other Commands traits' init methods.
     fn init_gap(
         &mut self,
         role: Role,
-        privacy_enabled: bool,
+        privacy: Privacy,
         dev_name_characteristic_len: u8,
     ) -> nb::Result<(), Self::Error> {
-        self.init(role, privacy_enabled, dev_name_characteristic_len)
+        self.init(role, privacy, dev_name_characteristic_len)
     }
 
-    #[cfg(not(feature = "ms"))]
-    /// Put the device into non-connectable mode.
-    ///
-    /// This mode does not support connection.
-    ///
-    /// # Errors
-    ///
-    /// - [BadAdvertisingType](Error::BadAdvertisingType) if the advertising type is not one
-    ///   of the supported modes. It must be
-    ///   [ScannableUndirected](AdvertisingType::ScannableUndirected) or
-    ///   (NonConnectableUndirected)[AdvertisingType::NonConnectableUndirected).
-    /// - Underlying communication errors.
-    ///
-    /// # Generated events
-    ///
-    /// A [Command Complete](::event::command::ReturnParameters::GapInit) event is generated.
-    fn set_nonconnectable(
-        &mut self,
-        advertising_type: AdvertisingType,
-    ) -> nb::Result<(), Error<Self::Error>>;
-
-    #[cfg(feature = "ms")]
     /// Put the device into non-connectable mode.
     ///
     /// This mode does not support connection. The privacy setting done in the
@@ -319,7 +319,9 @@ pub trait Commands {
     /// parameters for this command. If privacy was not enabled, `address_type` may be
     /// [Public](AddressType::Public) or [Random](AddressType::Random).  If privacy was
     /// enabled, `address_type` may be [ResolvablePrivate](AddressType::ResolvablePrivate) or
-    /// [NonResolvablePrivate](AddressType::NonResolvablePrivate).
+    /// [NonResolvablePrivate](AddressType::NonResolvablePrivate). The original BlueNRG
+    /// [dialect](crate::dialect) has no address type parameter, and always advertises with the
+    /// [Public](AddressType::Public) address.
     ///
     /// # Errors
     ///
@@ -327,6 +329,8 @@ pub trait Commands {
     ///   of the supported modes. It must be
     ///   [ScannableUndirected](AdvertisingType::ScannableUndirected) or
     ///   (NonConnectableUndirected)[AdvertisingType::NonConnectableUndirected).
+    /// - [UnsupportedInDialect](Error::UnsupportedInDialect) if the controller speaks the original
+    ///   BlueNRG dialect and `address_type` is not [Public](AddressType::Public).
     /// - Underlying communication errors.
     ///
     /// # Generated events
@@ -496,39 +500,28 @@ pub trait Commands {
     /// event is generated.
     fn clear_security_database(&mut self) -> nb::Result<(), Self::Error>;
 
-    #[cfg(not(feature = "ms"))]
-    /// This command should be given by the application when it receives the
-    /// [GAP Bond Lost](::event::BlueNRGEvent::GapBondLost) event if it wants the re-bonding to happen
-    /// successfully. If this command is not given on receiving the event, the bonding procedure
-    /// will timeout.
-    ///
-    /// # Errors
-    ///
-    /// Only underlying communication errors are reported.
-    ///
-    /// # Generated events
-    ///
-    /// A [Command Complete](::event::command::ReturnParameters::GapAllowRebond) event is
-    /// generated. Even if the command is given when it is not valid, success will be returned but
-    /// internally it will have no effect.
-    fn allow_rebond(&mut self) -> nb::Result<(), Self::Error>;
-
-    #[cfg(feature = "ms")]
     /// This command should be given by the application when it receives the [GAP Bond
     /// Lost](crate::event::BlueNRGEvent::GapBondLost) event if it wants the re-bonding to happen
     /// successfully. If this command is not given on receiving the event, the bonding procedure
-    /// will timeout.
+    /// will timeout. The original BlueNRG [dialect](crate::dialect) only supports one connection,
+    /// and takes no connection handle, so `conn_handle` must be `None` when talking to it, and
+    /// `Some` when talking to the BlueNRG-MS.
     ///
     /// # Errors
     ///
-    /// Only underlying communication errors are reported.
+    /// - [UnsupportedInDialect](Error::UnsupportedInDialect) if `conn_handle` does not match the
+    ///   controller's dialect.
+    /// - Underlying communication errors.
     ///
     /// # Generated events
     ///
     /// A [Command Complete](crate::event::command::ReturnParameters::GapAllowRebond) event is
     /// generated. Even if the command is given when it is not valid, success will be returned but
     /// internally it will have no effect.
-    fn allow_rebond(&mut self, conn_handle: hci::ConnectionHandle) -> nb::Result<(), Self::Error>;
+    fn allow_rebond(
+        &mut self,
+        conn_handle: Option<hci::ConnectionHandle>,
+    ) -> nb::Result<(), Error<Self::Error>>;
 
     /// Start the limited discovery procedure.
     ///
@@ -631,7 +624,10 @@ pub trait Commands {
     /// - If the [`white_list`](AutoConnectionEstablishmentParameters::white_list) is too long
     ///   (such that the serialized command would not fit in 255 bytes), a
     ///   [WhiteListTooLong](Error::WhiteListTooLong) is returned. The list cannot have more than 33
-    ///   elements.
+    ///   elements, or 32 with the original BlueNRG [dialect](crate::dialect).
+    /// - If a [`reconnection_address`](AutoConnectionEstablishmentParameters::reconnection_address)
+    ///   is given to the BlueNRG-MS, an [UnsupportedInDialect](Error::UnsupportedInDialect) error
+    ///   is returned.
     fn start_auto_connection_establishment<'a>(
         &mut self,
         params: &AutoConnectionEstablishmentParameters<'a>,
@@ -653,11 +649,14 @@ pub trait Commands {
     ///
     /// # Errors
     ///
-    /// Only underlying communication errors are reported.
+    /// - [UnsupportedInDialect](Error::UnsupportedInDialect) if a
+    ///   [`reconnection_address`](GeneralConnectionEstablishmentParameters::reconnection_address)
+    ///   is given to the BlueNRG-MS.
+    /// - Underlying communication errors.
     fn start_general_connection_establishment(
         &mut self,
         params: &GeneralConnectionEstablishmentParameters,
-    ) -> nb::Result<(), Self::Error>;
+    ) -> nb::Result<(), Error<Self::Error>>;
 
     /// Start a selective connection establishment procedure.
     ///
@@ -794,7 +793,6 @@ pub trait Commands {
     /// generated.
     fn get_bonded_devices(&mut self) -> nb::Result<(), Self::Error>;
 
-    #[cfg(feature = "ms")]
     /// This command puts the device into broadcast mode.
     ///
     /// # Errors
@@ -807,6 +805,8 @@ pub trait Commands {
     /// - [WhiteListTooLong](Error::WhiteListTooLong) if the length of the white list would put the
     ///   packet length over 255 bytes. The exact number of addresses that can be in the white list
     ///   can range from 35 to 31, depending on the length of the advertising data.
+    /// - [Unsupported](Error::Unsupported) if the controller's firmware does not support the
+    ///   command, or if it speaks the original BlueNRG [dialect](crate::dialect).
     /// - Underlying communication errors.
     ///
     /// # Generated events
@@ -818,7 +818,6 @@ pub trait Commands {
         params: &BroadcastModeParameters,
     ) -> nb::Result<(), Error<Self::Error>>;
 
-    #[cfg(feature = "ms")]
     /// Starts an Observation procedure, when the device is in Observer Role.
     ///
     /// The host enables scanning in the controller. The advertising reports are sent to the upper
@@ -827,7 +826,9 @@ pub trait Commands {
     ///
     /// # Errors
     ///
-    /// Only underlying communication errors are reported.
+    /// - [Unsupported](Error::Unsupported) if the controller's firmware does not support the
+    ///   command, or if it speaks the original BlueNRG [dialect](crate::dialect).
+    /// - Underlying communication errors.
     ///
     /// # Generated events
     ///
@@ -836,7 +837,7 @@ pub trait Commands {
     fn start_observation_procedure(
         &mut self,
         params: &ObservationProcedureParameters,
-    ) -> nb::Result<(), Self::Error>;
+    ) -> nb::Result<(), Error<Self::Error>>;
 
     /// The command finds whether the device, whose address is specified in the command, is
     /// bonded. If the device is using a resolvable private address and it has been bonded, then the
@@ -851,18 +852,69 @@ pub trait Commands {
     /// A [command complete](crate::event::command::ReturnParameters::GapIsDeviceBonded) event is
     /// generated.
     fn is_device_bonded(&mut self, addr: hci::host::PeerAddrType) -> nb::Result<(), Self::Error>;
+
+    #[cfg(feature = "bluenrg2")]
+    /// This command confirms or rejects the numeric comparison value shown to the user, in response
+    /// to a [GAP Numeric Comparison
+    /// Value](crate::event::BlueNRGEvent::GapNumericComparisonValue) event during LE Secure
+    /// Connections pairing.
+    ///
+    /// # Errors
+    ///
+    /// Only underlying communication errors are reported.
+    ///
+    /// # Generated events
+    ///
+    /// A [command
+    /// complete](crate::event::command::ReturnParameters::GapNumericComparisonValueConfirm) event
+    /// is generated.
+    fn numeric_comparison_value_confirm(
+        &mut self,
+        conn_handle: hci::ConnectionHandle,
+        confirmed: bool,
+    ) -> nb::Result<(), Self::Error>;
+
+    #[cfg(feature = "bluenrg2")]
+    /// This command notifies the peer of the user's progress entering the pass key, during LE
+    /// Secure Connections pairing with keypress notifications.
+    ///
+    /// # Errors
+    ///
+    /// Only underlying communication errors are reported.
+    ///
+    /// # Generated events
+    ///
+    /// A [command complete](crate::event::command::ReturnParameters::GapPassKeyInput) event is
+    /// generated.
+    fn pass_key_input(
+        &mut self,
+        conn_handle: hci::ConnectionHandle,
+        input: PassKeyInput,
+    ) -> nb::Result<(), Self::Error>;
+
+    #[cfg(feature = "bluenrg2")]
+    /// This command removes the device with the given address from the security database.
+    ///
+    /// # Errors
+    ///
+    /// Only underlying communication errors are reported.
+    ///
+    /// # Generated events
+    ///
+    /// A [command complete](crate::event::command::ReturnParameters::GapRemoveBondedDevice) event
+    /// is generated.
+    fn remove_bonded_device(
+        &mut self,
+        addr: hci::host::PeerAddrType,
+    ) -> nb::Result<(), Self::Error>;
 }
 
-impl<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, SpiError, GpioError> Commands
-    for crate::ActiveBlueNRG<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, GpioError>
+impl<T> Commands for T
 where
-    SPI: hal::blocking::spi::Transfer<u8, Error = SpiError>
-        + hal::blocking::spi::Write<u8, Error = SpiError>,
-    OutputPin1: hal::digital::v2::OutputPin<Error = GpioError>,
-    OutputPin2: hal::digital::v2::OutputPin<Error = GpioError>,
-    InputPin: hal::digital::v2::InputPin<Error = GpioError>,
+    T: hci::Controller<Header = hci::host::uart::CommandHeader, Vendor = crate::BlueNRGTypes>
+        + crate::Firmware,
 {
-    type Error = crate::Error<SpiError, GpioError>;
+    type Error = T::Error;
 
     fn set_nondiscoverable(&mut self) -> nb::Result<(), Self::Error> {
         self.write_command(crate::opcode::GAP_SET_NONDISCOVERABLE, &[])
@@ -880,11 +932,19 @@ where
         crate::opcode::GAP_SET_DISCOVERABLE
     );
 
-    impl_validate_params!(
-        set_direct_connectable,
-        DirectConnectableParameters,
-        crate::opcode::GAP_SET_DIRECT_CONNECTABLE
-    );
+    fn set_direct_connectable(
+        &mut self,
+        params: &DirectConnectableParameters,
+    ) -> nb::Result<(), Error<Self::Error>> {
+        let dialect = self.dialect();
+        params.validate(dialect).map_err(nb::Error::Other)?;
+
+        let mut bytes = [0; DirectConnectableParameters::MAX_LENGTH];
+        let len = params.copy_into_slice(dialect, &mut bytes);
+
+        self.write_command(crate::opcode::GAP_SET_DIRECT_CONNECTABLE, &bytes[..len])
+            .map_err(rewrap_error)
+    }
 
     fn set_io_capability(&mut self, capability: IoCapability) -> nb::Result<(), Self::Error> {
         self.write_command(crate::opcode::GAP_SET_IO_CAPABILITY, &[capability as u8])
@@ -937,48 +997,48 @@ where
         self.write_command(crate::opcode::GAP_AUTHORIZATION_RESPONSE, &bytes)
     }
 
-    #[cfg(not(feature = "ms"))]
-    fn init(&mut self, role: Role) -> nb::Result<(), Self::Error> {
-        self.write_command(crate::opcode::GAP_INIT, &[role.bits()])
-    }
-
-    #[cfg(feature = "ms")]
+    #[cfg(not(feature = "bluenrg2"))]
     fn init(
         &mut self,
         role: Role,
         privacy_enabled: bool,
         dev_name_characteristic_len: u8,
-    ) -> nb::Result<(), Self::Error> {
+    ) -> nb::Result<(), Error<Self::Error>> {
+        let dialect = self.dialect();
+        if !dialect.is_ms() {
+            if privacy_enabled || dev_name_characteristic_len != BLUENRG_DEVICE_NAME_LEN {
+                return Err(nb::Error::Other(Error::UnsupportedInDialect(dialect)));
+            }
+
+            return self
+                .write_command(crate::opcode::GAP_INIT, &[role.bits()])
+                .map_err(rewrap_error);
+        }
+
         let mut bytes = [0; 3];
         bytes[0] = role.bits();
         bytes[1] = privacy_enabled as u8;
-        bytes[2] = dev_name_characteristic_len as u8;
+        bytes[2] = dev_name_characteristic_len;
 
         self.write_command(crate::opcode::GAP_INIT, &bytes)
+            .map_err(rewrap_error)
     }
 
-    #[cfg(not(feature = "ms"))]
-    fn set_nonconnectable(
+    #[cfg(feature = "bluenrg2")]
+    fn init(
         &mut self,
-        advertising_type: AdvertisingType,
-    ) -> nb::Result<(), Error<Self::Error>> {
-        match advertising_type {
-            AdvertisingType::ScannableUndirected | AdvertisingType::NonConnectableUndirected => (),
-            _ => {
-                return Err(nb::Error::Other(Error::BadAdvertisingType(
-                    advertising_type,
-                )));
-            }
-        }
+        role: Role,
+        privacy: Privacy,
+        dev_name_characteristic_len: u8,
+    ) -> nb::Result<(), Self::Error> {
+        let mut bytes = [0; 3];
+        bytes[0] = role.bits();
+        bytes[1] = privacy as u8;
+        bytes[2] = dev_name_characteristic_len;
 
-        self.write_command(
-            crate::opcode::GAP_SET_NONCONNECTABLE,
-            &[advertising_type as u8],
-        )
-        .map_err(rewrap_error)
+        self.write_command(crate::opcode::GAP_INIT, &bytes)
     }
 
-    #[cfg(feature = "ms")]
     fn set_nonconnectable(
         &mut self,
         advertising_type: AdvertisingType,
@@ -993,11 +1053,15 @@ where
             }
         }
 
-        self.write_command(
-            crate::opcode::GAP_SET_NONCONNECTABLE,
-            &[advertising_type as u8, address_type as u8],
-        )
-        .map_err(rewrap_error)
+        let dialect = self.dialect();
+        if !dialect.is_ms() && address_type != AddressType::Public {
+            return Err(nb::Error::Other(Error::UnsupportedInDialect(dialect)));
+        }
+
+        let bytes = [advertising_type as u8, address_type as u8];
+        let len = if dialect.is_ms() { 2 } else { 1 };
+        self.write_command(crate::opcode::GAP_SET_NONCONNECTABLE, &bytes[..len])
+            .map_err(rewrap_error)
     }
 
     fn set_undirected_connectable(
@@ -1094,16 +1158,23 @@ where
         self.write_command(crate::opcode::GAP_CLEAR_SECURITY_DATABASE, &[])
     }
 
-    #[cfg(not(feature = "ms"))]
-    fn allow_rebond(&mut self) -> nb::Result<(), Self::Error> {
-        self.write_command(crate::opcode::GAP_ALLOW_REBOND, &[])
-    }
-
-    #[cfg(feature = "ms")]
-    fn allow_rebond(&mut self, conn_handle: hci::ConnectionHandle) -> nb::Result<(), Self::Error> {
+    fn allow_rebond(
+        &mut self,
+        conn_handle: Option<hci::ConnectionHandle>,
+    ) -> nb::Result<(), Error<Self::Error>> {
+        let dialect = self.dialect();
         let mut bytes = [0; 2];
-        LittleEndian::write_u16(&mut bytes, conn_handle.0);
-        self.write_command(crate::opcode::GAP_ALLOW_REBOND, &bytes)
+        let len = match (dialect.is_ms(), conn_handle) {
+            (true, Some(conn_handle)) => {
+                LittleEndian::write_u16(&mut bytes, conn_handle.0);
+                2
+            }
+            (false, None) => 0,
+            _ => return Err(nb::Error::Other(Error::UnsupportedInDialect(dialect))),
+        };
+
+        self.write_command(crate::opcode::GAP_ALLOW_REBOND, &bytes[..len])
+            .map_err(rewrap_error)
     }
 
     impl_params!(
@@ -1124,17 +1195,39 @@ where
         crate::opcode::GAP_START_NAME_DISCOVERY_PROCEDURE
     );
 
-    impl_validate_variable_length_params!(
-        start_auto_connection_establishment<'a>,
-        AutoConnectionEstablishmentParameters<'a>,
-        crate::opcode::GAP_START_AUTO_CONNECTION_ESTABLISHMENT
-    );
+    fn start_auto_connection_establishment<'a>(
+        &mut self,
+        params: &AutoConnectionEstablishmentParameters<'a>,
+    ) -> nb::Result<(), Error<Self::Error>> {
+        let dialect = self.dialect();
+        params.validate(dialect).map_err(nb::Error::Other)?;
 
-    impl_params!(
-        start_general_connection_establishment,
-        GeneralConnectionEstablishmentParameters,
-        crate::opcode::GAP_START_GENERAL_CONNECTION_ESTABLISHMENT
-    );
+        let mut bytes = [0; AutoConnectionEstablishmentParameters::MAX_LENGTH];
+        let len = params.copy_into_slice(dialect, &mut bytes);
+
+        self.write_command(
+            crate::opcode::GAP_START_AUTO_CONNECTION_ESTABLISHMENT,
+            &bytes[..len],
+        )
+        .map_err(rewrap_error)
+    }
+
+    fn start_general_connection_establishment(
+        &mut self,
+        params: &GeneralConnectionEstablishmentParameters,
+    ) -> nb::Result<(), Error<Self::Error>> {
+        let dialect = self.dialect();
+        params.validate(dialect).map_err(nb::Error::Other)?;
+
+        let mut bytes = [0; GeneralConnectionEstablishmentParameters::MAX_LENGTH];
+        let len = params.copy_into_slice(dialect, &mut bytes);
+
+        self.write_command(
+            crate::opcode::GAP_START_GENERAL_CONNECTION_ESTABLISHMENT,
+            &bytes[..len],
+        )
+        .map_err(rewrap_error)
+    }
 
     impl_validate_variable_length_params!(
         start_selective_connection_establishment<'a>,
@@ -1176,19 +1269,32 @@ where
         self.write_command(crate::opcode::GAP_GET_BONDED_DEVICES, &[])
     }
 
-    #[cfg(feature = "ms")]
-    impl_validate_variable_length_params!(
-        set_broadcast_mode,
-        BroadcastModeParameters,
-        crate::opcode::GAP_SET_BROADCAST_MODE
-    );
+    fn set_broadcast_mode(
+        &mut self,
+        params: &BroadcastModeParameters,
+    ) -> nb::Result<(), Error<Self::Error>> {
+        check_supported(self, Capability::SetBroadcastMode)?;
+        params.validate().map_err(nb::Error::Other)?;
 
-    #[cfg(feature = "ms")]
-    impl_params!(
-        start_observation_procedure,
-        ObservationProcedureParameters,
-        crate::opcode::GAP_START_OBSERVATION_PROCEDURE
-    );
+        let mut bytes = [0; BroadcastModeParameters::MAX_LENGTH];
+        let len = params.copy_into_slice(&mut bytes);
+
+        self.write_command(crate::opcode::GAP_SET_BROADCAST_MODE, &bytes[..len])
+            .map_err(rewrap_error)
+    }
+
+    fn start_observation_procedure(
+        &mut self,
+        params: &ObservationProcedureParameters,
+    ) -> nb::Result<(), Error<Self::Error>> {
+        check_supported(self, Capability::StartObservationProcedure)?;
+
+        let mut bytes = [0; ObservationProcedureParameters::LENGTH];
+        params.copy_into_slice(&mut bytes);
+
+        self.write_command(crate::opcode::GAP_START_OBSERVATION_PROCEDURE, &bytes)
+            .map_err(rewrap_error)
+    }
 
     fn is_device_bonded(&mut self, addr: hci::host::PeerAddrType) -> nb::Result<(), Self::Error> {
         let mut bytes = [0; 7];
@@ -1196,6 +1302,43 @@ where
 
         self.write_command(crate::opcode::GAP_IS_DEVICE_BONDED, &bytes)
     }
+
+    #[cfg(feature = "bluenrg2")]
+    fn numeric_comparison_value_confirm(
+        &mut self,
+        conn_handle: hci::ConnectionHandle,
+        confirmed: bool,
+    ) -> nb::Result<(), Self::Error> {
+        let mut bytes = [0; 3];
+        LittleEndian::write_u16(&mut bytes[0..2], conn_handle.0);
+        bytes[2] = confirmed as u8;
+
+        self.write_command(crate::opcode::GAP_NUMERIC_COMPARISON_VALUE_CONFIRM, &bytes)
+    }
+
+    #[cfg(feature = "bluenrg2")]
+    fn pass_key_input(
+        &mut self,
+        conn_handle: hci::ConnectionHandle,
+        input: PassKeyInput,
+    ) -> nb::Result<(), Self::Error> {
+        let mut bytes = [0; 3];
+        LittleEndian::write_u16(&mut bytes[0..2], conn_handle.0);
+        bytes[2] = input as u8;
+
+        self.write_command(crate::opcode::GAP_PASS_KEY_INPUT, &bytes)
+    }
+
+    #[cfg(feature = "bluenrg2")]
+    fn remove_bonded_device(
+        &mut self,
+        addr: hci::host::PeerAddrType,
+    ) -> nb::Result<(), Self::Error> {
+        let mut bytes = [0; 7];
+        addr.copy_into_slice(&mut bytes);
+
+        self.write_command(crate::opcode::GAP_REMOVE_BONDED_DEVICE, &bytes)
+    }
 }
 
 /// Potential errors from parameter validation.
@@ -1264,6 +1407,14 @@ pub enum Error<E> {
     /// provided bitfield had no bits set.
     NoProcedure,
 
+    /// The controller's firmware does not support the command. See the
+    /// [`capability`](crate::capability) module.
+    Unsupported(Capability),
+
+    /// The arguments cannot be encoded in the controller's [dialect](crate::dialect). Includes the
+    /// dialect.
+    UnsupportedInDialect(Dialect),
+
     /// Underlying communication error.
     Comm(E),
 }
@@ -1275,6 +1426,25 @@ fn rewrap_error<E>(e: nb::Error<E>) -> nb::Error<Error<E>> {
     }
 }
 
+/// Fails with `Unsupported` if the controller speaks the original BlueNRG dialect, or its firmware
+/// is known not to support the capability.
+/// The original BlueNRG dialect supports none of them.
+fn check_supported<T, E>(controller: &T, capability: Capability) -> nb::Result<(), Error<E>>
+where
+    T: crate::Firmware,
+{
+    if !controller.dialect().is_ms() {
+        return Err(nb::Error::Other(Error::Unsupported(capability)));
+    }
+
+    match controller.version() {
+        Some(version) if !capability.supported_by(&version) => {
+            Err(nb::Error::Other(Error::Unsupported(capability)))
+        }
+        _ => Ok(()),
+    }
+}
+
 fn to_conn_interval_value(d: Duration) -> u16 {
     // Connection interval value: T = N * 1.25 ms
     // We have T, we need to return N.
@@ -1296,6 +1466,7 @@ fn to_connection_length_value(d: Duration) -> u16 {
 /// Parameters for the
 /// [`set_limited_discoverable`](Commands::set_limited_discoverable) and
 /// [`set_discoverable`](Commands::set_discoverable) commands.
+#[derive(Debug)]
 pub struct DiscoverableParameters<'a, 'b> {
     /// Advertising method for the device.
     ///
@@ -1381,7 +1552,7 @@ impl<'a, 'b> DiscoverableParameters<'a, 'b> {
         let advertising_data_len_index = match self.local_name {
             None => {
                 bytes[7] = 0;
-                7
+                8
             }
             Some(LocalName::Shortened(name)) => {
                 const AD_TYPE_SHORTENED_LOCAL_NAME: u8 = 0x08;
@@ -1405,19 +1576,15 @@ impl<'a, 'b> DiscoverableParameters<'a, 'b> {
         let conn_interval_index = advertising_data_len_index + 1 + self.advertising_data.len();
         LittleEndian::write_u16(
             &mut bytes[conn_interval_index..],
-            if self.conn_interval.0.is_some() {
-                to_conn_interval_value(self.conn_interval.0.unwrap())
-            } else {
-                NO_SPECIFIC_CONN_INTERVAL
-            },
+            self.conn_interval
+                .0
+                .map_or(NO_SPECIFIC_CONN_INTERVAL, to_conn_interval_value),
         );
         LittleEndian::write_u16(
             &mut bytes[(conn_interval_index + 2)..],
-            if self.conn_interval.1.is_some() {
-                to_conn_interval_value(self.conn_interval.1.unwrap())
-            } else {
-                NO_SPECIFIC_CONN_INTERVAL
-            },
+            self.conn_interval
+                .1
+                .map_or(NO_SPECIFIC_CONN_INTERVAL, to_conn_interval_value),
         );
 
         len
@@ -1440,6 +1607,7 @@ impl<'a, 'b> DiscoverableParameters<'a, 'b> {
 }
 
 /// Allowed types for the local name.
+#[derive(Debug)]
 pub enum LocalName<'a> {
     /// The shortened local name.
     Shortened(&'a [u8]),
@@ -1450,86 +1618,81 @@ pub enum LocalName<'a> {
 
 /// Parameters for the
 /// [`set_direct_connectable`](Commands::set_direct_connectable) command.
+#[derive(Debug)]
 pub struct DirectConnectableParameters {
     /// Address type of this device.
     pub own_address_type: OwnAddressType,
 
-    #[cfg(feature = "ms")]
     /// Advertising method for the device.
     ///
     /// Must be
     /// [ConnectableDirectedHighDutyCycle](bluetooth_hci::host::AdvertisingType::ConnectableDirectedHighDutyCycle),
     /// or
     /// [ConnectableDirectedLowDutyCycle](bluetooth_hci::host::AdvertisingType::ConnectableDirectedLowDutyCycle).
+    /// The original BlueNRG [dialect](crate::dialect) only supports high duty cycle advertising.
     pub advertising_type: AdvertisingType,
 
     /// Initiator's Bluetooth address.
     pub initiator_address: BdAddrType,
 
-    #[cfg(feature = "ms")]
     /// Range of advertising interval for advertising.
     ///
     /// Range for both limits: 20 ms to 10.24 seconds.  The second value must be greater than or
-    /// equal to the first.
+    /// equal to the first. Not sent to the original BlueNRG.
     pub advertising_interval: (Duration, Duration),
 }
 
 impl DirectConnectableParameters {
-    #[cfg(not(feature = "ms"))]
-    const LENGTH: usize = 8;
+    const MAX_LENGTH: usize = 13;
 
-    #[cfg(feature = "ms")]
-    const LENGTH: usize = 13;
+    fn validate<E>(&self, dialect: Dialect) -> Result<(), Error<E>> {
+        const MIN_DURATION: Duration = Duration::from_millis(20);
+        const MAX_DURATION: Duration = Duration::from_millis(10240);
 
-    fn validate<E>(&self) -> Result<(), Error<E>> {
-        #[cfg(feature = "ms")]
-        {
-            const MIN_DURATION: Duration = Duration::from_millis(20);
-            const MAX_DURATION: Duration = Duration::from_millis(10240);
-
-            match self.advertising_type {
-                AdvertisingType::ConnectableDirectedHighDutyCycle
-                | AdvertisingType::ConnectableDirectedLowDutyCycle => (),
-                _ => return Err(Error::BadAdvertisingType(self.advertising_type)),
+        match self.advertising_type {
+            AdvertisingType::ConnectableDirectedHighDutyCycle => (),
+            AdvertisingType::ConnectableDirectedLowDutyCycle => {
+                if !dialect.is_ms() {
+                    return Err(Error::UnsupportedInDialect(dialect));
+                }
             }
+            _ => return Err(Error::BadAdvertisingType(self.advertising_type)),
+        }
 
-            if self.advertising_interval.0 < MIN_DURATION
-                || self.advertising_interval.1 > MAX_DURATION
-                || self.advertising_interval.0 > self.advertising_interval.1
-            {
-                return Err(Error::BadAdvertisingInterval(
-                    self.advertising_interval.0,
-                    self.advertising_interval.1,
-                ));
-            }
+        if self.advertising_interval.0 < MIN_DURATION
+            || self.advertising_interval.1 > MAX_DURATION
+            || self.advertising_interval.0 > self.advertising_interval.1
+        {
+            return Err(Error::BadAdvertisingInterval(
+                self.advertising_interval.0,
+                self.advertising_interval.1,
+            ));
         }
 
         Ok(())
     }
 
-    fn copy_into_slice(&self, bytes: &mut [u8]) {
-        assert_eq!(bytes.len(), Self::LENGTH);
+    fn copy_into_slice(&self, dialect: Dialect, bytes: &mut [u8]) -> usize {
+        assert!(bytes.len() >= Self::MAX_LENGTH);
 
         bytes[0] = self.own_address_type as u8;
-
-        #[cfg(not(feature = "ms"))]
-        {
+        if !dialect.is_ms() {
             self.initiator_address.copy_into_slice(&mut bytes[1..8]);
+            return 8;
         }
 
-        #[cfg(feature = "ms")]
-        {
-            bytes[1] = self.advertising_type as u8;
-            self.initiator_address.copy_into_slice(&mut bytes[2..9]);
-            LittleEndian::write_u16(
-                &mut bytes[9..],
-                to_connection_length_value(self.advertising_interval.0),
-            );
-            LittleEndian::write_u16(
-                &mut bytes[11..],
-                to_connection_length_value(self.advertising_interval.1),
-            );
-        }
+        bytes[1] = self.advertising_type as u8;
+        self.initiator_address.copy_into_slice(&mut bytes[2..9]);
+        LittleEndian::write_u16(
+            &mut bytes[9..],
+            to_connection_length_value(self.advertising_interval.0),
+        );
+        LittleEndian::write_u16(
+            &mut bytes[11..],
+            to_connection_length_value(self.advertising_interval.1),
+        );
+
+        Self::MAX_LENGTH
     }
 }
 
@@ -1550,8 +1713,24 @@ pub enum IoCapability {
     KeyboardDisplay = 0x04,
 }
 
+impl TryFrom<u8> for IoCapability {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<IoCapability, Self::Error> {
+        match value {
+            0x00 => Ok(IoCapability::Display),
+            0x01 => Ok(IoCapability::DisplayConfirm),
+            0x02 => Ok(IoCapability::Keyboard),
+            0x03 => Ok(IoCapability::None),
+            0x04 => Ok(IoCapability::KeyboardDisplay),
+            _ => Err(value),
+        }
+    }
+}
+
 /// Parameters for the [GAP Set Authentication
 /// Requirement](Commands::set_authentication_requirement) command.
+#[derive(Debug)]
 pub struct AuthenticationRequirements {
     /// Is MITM (man-in-the-middle) protection required?
     pub mitm_protection_required: bool,
@@ -1622,6 +1801,7 @@ impl AuthenticationRequirements {
 }
 
 /// Options for [`out_of_band_auth`](AuthenticationRequirements::out_of_band_auth).
+#[derive(Debug)]
 pub enum OutOfBandAuthentication {
     /// Out Of Band authentication not enabled
     Disabled,
@@ -1630,6 +1810,7 @@ pub enum OutOfBandAuthentication {
 }
 
 /// Options for [`fixed_pin`](AuthenticationRequirements::fixed_pin).
+#[derive(Debug)]
 pub enum Pin {
     /// Do not use fixed pin during the pairing process.  In this case, GAP will generate a [GAP
     /// Pass Key Request](crate::event::BlueNRGEvent::GapPassKeyRequest) event to the host.
@@ -1641,6 +1822,7 @@ pub enum Pin {
 }
 
 /// Options for the [GAP Authorization Response](Commands::authorization_response).
+#[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(u8)]
 pub enum Authorization {
     /// Accept the connection.
@@ -1649,6 +1831,78 @@ pub enum Authorization {
     Rejected = 0x02,
 }
 
+impl TryFrom<u8> for Authorization {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Authorization, Self::Error> {
+        match value {
+            0x01 => Ok(Authorization::Authorized),
+            0x02 => Ok(Authorization::Rejected),
+            _ => Err(value),
+        }
+    }
+}
+
+/// Privacy options for the [GAP Init](Commands::init) command on the BlueNRG-1 and BlueNRG-2.
+#[cfg(feature = "bluenrg2")]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum Privacy {
+    /// Privacy is disabled.
+    Disabled = 0x00,
+    /// Privacy is enabled, and the host resolves private addresses.
+    Host = 0x01,
+    /// Privacy is enabled, and the controller resolves private addresses (LL privacy).
+    Controller = 0x02,
+}
+
+#[cfg(feature = "bluenrg2")]
+impl TryFrom<u8> for Privacy {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Privacy, Self::Error> {
+        match value {
+            0x00 => Ok(Privacy::Disabled),
+            0x01 => Ok(Privacy::Host),
+            0x02 => Ok(Privacy::Controller),
+            _ => Err(value),
+        }
+    }
+}
+
+/// Progress of pass key entry, for the [GAP Pass Key Input](Commands::pass_key_input) command.
+#[cfg(feature = "bluenrg2")]
+#[derive(Copy, Clone, Debug, PartialEq)]
+#[repr(u8)]
+pub enum PassKeyInput {
+    /// The user started entering the pass key.
+    EntryStarted = 0x00,
+    /// The user entered a digit.
+    DigitEntered = 0x01,
+    /// The user erased a digit.
+    DigitErased = 0x02,
+    /// The user cleared the pass key.
+    Cleared = 0x03,
+    /// The user finished entering the pass key.
+    EntryCompleted = 0x04,
+}
+
+#[cfg(feature = "bluenrg2")]
+impl TryFrom<u8> for PassKeyInput {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<PassKeyInput, Self::Error> {
+        match value {
+            0x00 => Ok(PassKeyInput::EntryStarted),
+            0x01 => Ok(PassKeyInput::DigitEntered),
+            0x02 => Ok(PassKeyInput::DigitErased),
+            0x03 => Ok(PassKeyInput::Cleared),
+            0x04 => Ok(PassKeyInput::EntryCompleted),
+            _ => Err(value),
+        }
+    }
+}
+
 bitflags! {
     /// Roles for a [GAP service](Commands::init).
     pub struct Role: u8 {
@@ -1663,6 +1917,10 @@ bitflags! {
     }
 }
 
+/// Length of the device name characteristic that the original BlueNRG's [GAP Init](Commands::init)
+/// always adds.
+pub const BLUENRG_DEVICE_NAME_LEN: u8 = 7;
+
 /// Indicates the type of address being used in the advertising packets, for the
 /// [`set_nonconnectable`](Commands::set_nonconnectable).
 #[repr(u8)]
@@ -1679,8 +1937,23 @@ pub enum AddressType {
     NonResolvablePrivate = 0x03,
 }
 
+impl TryFrom<u8> for AddressType {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<AddressType, Self::Error> {
+        match value {
+            0x00 => Ok(AddressType::Public),
+            0x01 => Ok(AddressType::Random),
+            0x02 => Ok(AddressType::ResolvablePrivate),
+            0x03 => Ok(AddressType::NonResolvablePrivate),
+            _ => Err(value),
+        }
+    }
+}
+
 /// Parameters for the [GAP Peripheral Security
 /// Request](Commands::peripheral_security_request) parameters.
+#[derive(Debug)]
 pub struct SecurityRequestParameters {
     /// Handle of the connection on which the peripheral security request will
     /// be sent (ignored in peripheral-only role).
@@ -1706,6 +1979,7 @@ impl SecurityRequestParameters {
 }
 
 /// Available types of advertising data.
+#[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(u8)]
 pub enum AdvertisingDataType {
     /// Flags
@@ -1744,6 +2018,33 @@ pub enum AdvertisingDataType {
     ManufacturerSpecificData = 0xFF,
 }
 
+impl TryFrom<u8> for AdvertisingDataType {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<AdvertisingDataType, Self::Error> {
+        match value {
+            0x01 => Ok(AdvertisingDataType::Flags),
+            0x02 => Ok(AdvertisingDataType::Uuid16),
+            0x03 => Ok(AdvertisingDataType::UuidCompleteList16),
+            0x04 => Ok(AdvertisingDataType::Uuid32),
+            0x05 => Ok(AdvertisingDataType::UuidCompleteList32),
+            0x06 => Ok(AdvertisingDataType::Uuid128),
+            0x07 => Ok(AdvertisingDataType::UuidCompleteList128),
+            0x08 => Ok(AdvertisingDataType::ShortenedLocalName),
+            0x09 => Ok(AdvertisingDataType::CompleteLocalName),
+            0x0A => Ok(AdvertisingDataType::TxPowerLevel),
+            0x10 => Ok(AdvertisingDataType::SecurityManagerTkValue),
+            0x11 => Ok(AdvertisingDataType::SecurityManagerOutOfBandFlags),
+            0x12 => Ok(AdvertisingDataType::PeripheralConnectionInterval),
+            0x14 => Ok(AdvertisingDataType::SolicitUuidList16),
+            0x15 => Ok(AdvertisingDataType::SolicitUuidList32),
+            0x16 => Ok(AdvertisingDataType::ServiceData),
+            0xFF => Ok(AdvertisingDataType::ManufacturerSpecificData),
+            _ => Err(value),
+        }
+    }
+}
+
 bitflags! {
     /// Event types for [GAP Set Event Mask](Commands::set_event_mask).
     pub struct EventFlags: u16 {
@@ -1765,6 +2066,7 @@ bitflags! {
 /// Parameters for the [GAP Limited
 /// Discovery](Commands::start_limited_discovery_procedure) and [GAP General
 /// Discovery](Commands::start_general_discovery_procedure) procedures.
+#[derive(Debug)]
 pub struct DiscoveryProcedureParameters {
     /// Scanning window for the discovery procedure.
     pub scan_window: ScanWindow,
@@ -1790,6 +2092,7 @@ impl DiscoveryProcedureParameters {
 
 /// Parameters for the [GAP Name Discovery](Commands::start_name_discovery_procedure)
 /// procedure.
+#[derive(Debug)]
 pub struct NameDiscoveryProcedureParameters {
     /// Scanning window for the discovery procedure.
     pub scan_window: ScanWindow,
@@ -1837,10 +2140,11 @@ pub struct AutoConnectionEstablishmentParameters<'a> {
     /// Expected connection length
     pub expected_connection_length: ExpectedConnectionLength,
 
-    #[cfg(not(feature = "ms"))]
     /// Reconnection address is used as our address during the procedure. The address has been
     /// previously notified to the application through the
-    /// [ReconnectionAddress](::event::Event::ReconnectionAddress) event.
+    /// [GapReconnectionAddress](crate::event::BlueNRGEvent::GapReconnectionAddress) event. Only the
+    /// original BlueNRG [dialect](crate::dialect) supports it; it must be `None` for the
+    /// BlueNRG-MS.
     pub reconnection_address: Option<hci::BdAddr>,
 
     /// Addresses to white-list for automatic connection.
@@ -1850,18 +2154,21 @@ pub struct AutoConnectionEstablishmentParameters<'a> {
 impl<'a> AutoConnectionEstablishmentParameters<'a> {
     const MAX_LENGTH: usize = 249;
 
-    fn validate<E>(&self) -> Result<(), Error<E>> {
+    fn validate<E>(&self, dialect: Dialect) -> Result<(), Error<E>> {
         const MAX_WHITE_LIST_LENGTH: usize = 33;
-        if self.white_list.len() > MAX_WHITE_LIST_LENGTH - if cfg!(feature = "ms") { 0 } else { 1 }
-        {
+        if dialect.is_ms() && self.reconnection_address.is_some() {
+            return Err(Error::UnsupportedInDialect(dialect));
+        }
+
+        if self.white_list.len() > MAX_WHITE_LIST_LENGTH - if dialect.is_ms() { 0 } else { 1 } {
             return Err(Error::WhiteListTooLong);
         }
 
         Ok(())
     }
 
-    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
-        let len = self.len();
+    fn copy_into_slice(&self, dialect: Dialect, bytes: &mut [u8]) -> usize {
+        let len = self.len(dialect);
         assert!(bytes.len() >= len);
 
         self.scan_window.copy_into_slice(&mut bytes[0..4]);
@@ -1870,17 +2177,12 @@ impl<'a> AutoConnectionEstablishmentParameters<'a> {
         self.expected_connection_length
             .copy_into_slice(&mut bytes[13..17]);
 
-        #[cfg(not(feature = "ms"))]
-        {
-            if let Some(addr) = self.reconnection_address {
-                bytes[17] = 1;
-                bytes[18..24].copy_from_slice(&addr.0);
-            } else {
-                bytes[17..24].copy_from_slice(&[0; 7]);
-            }
-        }
-
-        let index = if cfg!(feature = "ms") { 17 } else { 24 };
+        let index = if dialect.is_ms() {
+            17
+        } else {
+            copy_reconnection_address(self.reconnection_address, &mut bytes[17..24]);
+            24
+        };
 
         bytes[index] = self.white_list.len() as u8;
         let index = index + 1;
@@ -1891,14 +2193,26 @@ impl<'a> AutoConnectionEstablishmentParameters<'a> {
         len
     }
 
-    fn len(&self) -> usize {
-        let reconn_addr_len = if cfg!(feature = "ms") { 0 } else { 7 };
+    fn len(&self, dialect: Dialect) -> usize {
+        let reconn_addr_len = if dialect.is_ms() { 0 } else { 7 };
         18 + reconn_addr_len + 7 * self.white_list.len()
     }
 }
 
+// Writes the optional reconnection address, which the original BlueNRG takes as a flag followed by
+// the address.
+fn copy_reconnection_address(addr: Option<hci::BdAddr>, bytes: &mut [u8]) {
+    if let Some(addr) = addr {
+        bytes[0] = 1;
+        bytes[1..7].copy_from_slice(&addr.0);
+    } else {
+        bytes[0..7].copy_from_slice(&[0; 7]);
+    }
+}
+
 /// Parameters for the [GAP Start General Connection
 /// Establishment](Commands::start_general_connection_establishment) command.
+#[derive(Debug)]
 pub struct GeneralConnectionEstablishmentParameters {
     /// Scanning window for connection establishment.
     pub scan_window: ScanWindow,
@@ -1909,36 +2223,37 @@ pub struct GeneralConnectionEstablishmentParameters {
     /// If true, only report unique devices.
     pub filter_duplicates: bool,
 
-    #[cfg(not(feature = "ms"))]
     /// Reconnection address is used as our address during the procedure. The address has been
     /// previously notified to the application through the
-    /// [ReconnectionAddress](::event::Event::ReconnectionAddress) event.
+    /// [GapReconnectionAddress](crate::event::BlueNRGEvent::GapReconnectionAddress) event. Only the
+    /// original BlueNRG [dialect](crate::dialect) supports it; it must be `None` for the
+    /// BlueNRG-MS.
     pub reconnection_address: Option<hci::BdAddr>,
 }
 
 impl GeneralConnectionEstablishmentParameters {
-    #[cfg(not(feature = "ms"))]
-    const LENGTH: usize = 13;
+    const MAX_LENGTH: usize = 13;
 
-    #[cfg(feature = "ms")]
-    const LENGTH: usize = 6;
+    fn validate<E>(&self, dialect: Dialect) -> Result<(), Error<E>> {
+        if dialect.is_ms() && self.reconnection_address.is_some() {
+            return Err(Error::UnsupportedInDialect(dialect));
+        }
 
-    fn copy_into_slice(&self, bytes: &mut [u8]) {
-        assert!(bytes.len() >= Self::LENGTH);
+        Ok(())
+    }
+
+    fn copy_into_slice(&self, dialect: Dialect, bytes: &mut [u8]) -> usize {
+        assert!(bytes.len() >= Self::MAX_LENGTH);
 
         self.scan_window.copy_into_slice(&mut bytes[0..4]);
         bytes[4] = self.own_address_type as u8;
         bytes[5] = self.filter_duplicates as u8;
-
-        #[cfg(not(feature = "ms"))]
-        {
-            if let Some(addr) = self.reconnection_address {
-                bytes[6] = 1;
-                bytes[7..13].copy_from_slice(&addr.0)
-            } else {
-                bytes[6..13].copy_from_slice(&[0; 7])
-            }
+        if dialect.is_ms() {
+            return 6;
         }
+
+        copy_reconnection_address(self.reconnection_address, &mut bytes[6..13]);
+        Self::MAX_LENGTH
     }
 }
 
@@ -2025,6 +2340,7 @@ bitflags! {
 
 /// Parameters for the [`start_connection_update`](Commands::start_connection_update)
 /// command.
+#[derive(Debug)]
 pub struct ConnectionUpdateParameters {
     /// Handle of the connection for which the update procedure has to be started.
     pub conn_handle: hci::ConnectionHandle,
@@ -2049,6 +2365,7 @@ impl ConnectionUpdateParameters {
 
 /// Parameters for the [`send_pairing_request`](Commands::send_pairing_request)
 /// command.
+#[derive(Debug)]
 pub struct PairingRequest {
     /// Handle of the connection for which the pairing request has to be sent.
     pub conn_handle: hci::ConnectionHandle,
@@ -2072,7 +2389,6 @@ impl PairingRequest {
     }
 }
 
-#[cfg(feature = "ms")]
 /// Parameters for the [GAP Set Broadcast Mode](Commands::set_broadcast_mode) command.
 pub struct BroadcastModeParameters<'a, 'b> {
     /// Advertising type and interval.
@@ -2103,7 +2419,6 @@ pub struct BroadcastModeParameters<'a, 'b> {
     pub white_list: &'b [hci::host::PeerAddrType],
 }
 
-#[cfg(feature = "ms")]
 impl<'a, 'b> BroadcastModeParameters<'a, 'b> {
     const MAX_LENGTH: usize = 255;
 
@@ -2153,9 +2468,9 @@ impl<'a, 'b> BroadcastModeParameters<'a, 'b> {
     }
 }
 
-#[cfg(feature = "ms")]
 /// Parameters for the [GAP Start Observation Procedure](Commands::start_observation_procedure)
 /// command.
+#[derive(Debug)]
 pub struct ObservationProcedureParameters {
     /// Scanning window.
     pub scan_window: hci::types::ScanWindow,
@@ -2171,7 +2486,6 @@ pub struct ObservationProcedureParameters {
     pub filter_duplicates: bool,
 }
 
-#[cfg(feature = "ms")]
 impl ObservationProcedureParameters {
     const LENGTH: usize = 7;
 
@@ -2,10 +2,12 @@
 
 extern crate bluetooth_hci as hci;
 extern crate byteorder;
-extern crate embedded_hal as hal;
 extern crate nb;
 
+use super::WriteCommand;
+use crate::capability::Capability;
 use byteorder::{ByteOrder, LittleEndian};
+use core::convert::TryFrom;
 
 /// GATT-specific commands for the [`ActiveBlueNRG`](crate::ActiveBlueNRG).
 pub trait Commands {
@@ -93,7 +95,8 @@ pub trait Commands {
     ///   value length](AddDescriptorParameters::descriptor_value_max_len).
     /// - [DescriptorBufferTooLong](Error::DescriptorBufferTooLong) if the [descriptor
     ///   value maximum length](AddDescriptorParameters::descriptor_value_max_len) is so large that
-    ///   the serialized structure may be more than 255 bytes. The maximum size is 227.
+    ///   the serialized structure may be more than 255 bytes. The maximum size is 227
+    ///   (225 with the `bluenrg2` feature).
     /// - Underlying communication errors.
     ///
     /// # Generated events
@@ -834,18 +837,19 @@ pub trait Commands {
     ///
     /// # Errors
     ///
-    /// Only underlying communication errors are reported.
+    /// - [Unsupported](Error::Unsupported) if the controller speaks the original BlueNRG
+    ///   [dialect](crate::dialect), or its firmware does not support the command.
+    /// - Underlying communication errors.
     ///
     /// # Generated events
     ///
     /// A [command complete](crate::event::command::ReturnParameters::GattReadHandleValueOffset)
     /// event is generated when this command is processed.
-    #[cfg(feature = "ms")]
     fn read_handle_value_offset(
         &mut self,
         handle: CharacteristicHandle,
         offset: usize,
-    ) -> nb::Result<(), Self::Error>;
+    ) -> nb::Result<(), Error<Self::Error>>;
 
     /// Update the Attribute Value of a Characteristic belonging to a specified service.
     ///
@@ -857,29 +861,26 @@ pub trait Commands {
     ///
     /// - [ValueBufferTooLong](Error::ValueBufferTooLong) if the characteristic value is so long
     ///   that the command would not fit in one packet. The maximum length is 245 bytes.
+    /// - [Unsupported](Error::Unsupported) if the controller speaks the original BlueNRG
+    ///   [dialect](crate::dialect), or its firmware does not support the command.
     /// - Underlying communication errors are reported.
     ///
     /// # Generated events
     ///
     /// When the command has completed, the controller will generate a [command
     /// complete](crate::event::command::ReturnParameters::GattUpdateLongCharacteristicValue) event.
-    #[cfg(feature = "ms")]
     fn update_long_characteristic_value<'a>(
         &mut self,
         params: &UpdateLongCharacteristicValueParameters<'a>,
     ) -> nb::Result<(), Error<Self::Error>>;
 }
 
-impl<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, SpiError, GpioError> Commands
-    for crate::ActiveBlueNRG<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, GpioError>
+impl<T> Commands for T
 where
-    SPI: hal::blocking::spi::Transfer<u8, Error = SpiError>
-        + hal::blocking::spi::Write<u8, Error = SpiError>,
-    OutputPin1: hal::digital::v2::OutputPin<Error = GpioError>,
-    OutputPin2: hal::digital::v2::OutputPin<Error = GpioError>,
-    InputPin: hal::digital::v2::InputPin<Error = GpioError>,
+    T: hci::Controller<Header = hci::host::uart::CommandHeader, Vendor = crate::BlueNRGTypes>
+        + crate::Firmware,
 {
-    type Error = crate::Error<SpiError, GpioError>;
+    type Error = T::Error;
 
     fn init(&mut self) -> nb::Result<(), Self::Error> {
         self.write_command(crate::opcode::GATT_INIT, &[])
@@ -1243,25 +1244,37 @@ where
         self.write_command(crate::opcode::GATT_READ_HANDLE_VALUE, &bytes)
     }
 
-    #[cfg(feature = "ms")]
     fn read_handle_value_offset(
         &mut self,
         handle: CharacteristicHandle,
         offset: usize,
-    ) -> nb::Result<(), Self::Error> {
+    ) -> nb::Result<(), Error<Self::Error>> {
+        check_supported(self, Capability::ReadHandleValueOffset)?;
+
         let mut bytes = [0; 3];
         LittleEndian::write_u16(&mut bytes, handle.0);
         bytes[2] = offset as u8;
 
         self.write_command(crate::opcode::GATT_READ_HANDLE_VALUE_OFFSET, &bytes)
+            .map_err(rewrap_error)
     }
 
-    #[cfg(feature = "ms")]
-    impl_validate_variable_length_params!(
-        update_long_characteristic_value<'a>,
-        UpdateLongCharacteristicValueParameters<'a>,
-        crate::opcode::GATT_UPDATE_LONG_CHARACTERISTIC_VALUE
-    );
+    fn update_long_characteristic_value<'a>(
+        &mut self,
+        params: &UpdateLongCharacteristicValueParameters<'a>,
+    ) -> nb::Result<(), Error<Self::Error>> {
+        check_supported(self, Capability::UpdateLongCharacteristicValue)?;
+        params.validate().map_err(nb::Error::Other)?;
+
+        let mut bytes = [0; UpdateLongCharacteristicValueParameters::MAX_LENGTH];
+        let len = params.copy_into_slice(&mut bytes);
+
+        self.write_command(
+            crate::opcode::GATT_UPDATE_LONG_CHARACTERISTIC_VALUE,
+            &bytes[..len],
+        )
+        .map_err(rewrap_error)
+    }
 }
 
 /// Potential errors from parameter validation.
@@ -1291,6 +1304,10 @@ pub enum Error<E> {
     /// the serialized command to be more than 255 bytes. The maximum length is 126 handles.
     TooManyHandlesToRead,
 
+    /// The controller's firmware does not support the command. See the
+    /// [`capability`](crate::capability) module.
+    Unsupported(Capability),
+
     /// Underlying communication error.
     Comm(E),
 }
@@ -1302,6 +1319,24 @@ fn rewrap_error<E>(e: nb::Error<E>) -> nb::Error<Error<E>> {
     }
 }
 
+/// Fails with `Unsupported` if the controller speaks the original BlueNRG dialect, or its firmware
+/// is known not to support the capability.
+fn check_supported<T, E>(controller: &T, capability: Capability) -> nb::Result<(), Error<E>>
+where
+    T: crate::Firmware,
+{
+    if !controller.dialect().is_ms() {
+        return Err(nb::Error::Other(Error::Unsupported(capability)));
+    }
+
+    match controller.version() {
+        Some(version) if !capability.supported_by(&version) => {
+            Err(nb::Error::Other(Error::Unsupported(capability)))
+        }
+        _ => Ok(()),
+    }
+}
+
 /// Parameters for the [GATT Add Service](Commands::add_service) command.
 #[derive(Debug)]
 pub struct AddServiceParameters {
@@ -1374,7 +1409,20 @@ pub enum ServiceType {
     Secondary = 0x02,
 }
 
+impl TryFrom<u8> for ServiceType {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<ServiceType, Self::Error> {
+        match value {
+            0x01 => Ok(ServiceType::Primary),
+            0x02 => Ok(ServiceType::Secondary),
+            _ => Err(value),
+        }
+    }
+}
+
 /// Parameters for the [GATT Include Service](Commands::include_service) command.
+#[derive(Debug)]
 pub struct IncludeServiceParameters {
     /// Handle of the service to which another service has to be included
     pub service_handle: ServiceHandle,
@@ -1407,6 +1455,7 @@ pub struct ServiceHandle(pub u16);
 
 /// Two ordered points that represent a range. The points may be identical to represent a range with
 /// only one value.
+#[derive(Debug)]
 pub struct Range<T> {
     from: T,
     to: T,
@@ -1425,6 +1474,16 @@ impl<T: PartialOrd> Range<T> {
 
         Ok(Self { from, to })
     }
+
+    /// Returns the beginning of the range.
+    pub fn from(&self) -> &T {
+        &self.from
+    }
+
+    /// Returns the end of the range.
+    pub fn to(&self) -> &T {
+        &self.to
+    }
 }
 
 /// Potential errors that can occer when creating a [Range].
@@ -1466,6 +1525,10 @@ pub struct AddCharacteristicParameters {
     /// If true, the
     /// [`characteristic_value_len`](AddCharacteristicParameters::characteristic_value_len)
     /// parameter only takes 1 byte.
+    ///
+    /// The BlueNRG-1 and BlueNRG-2 always use 2 bytes, so this is not available with the
+    /// `bluenrg2` feature.
+    #[cfg(not(feature = "bluenrg2"))]
     pub fw_version_before_v72: bool,
 }
 
@@ -1478,7 +1541,11 @@ impl AddCharacteristicParameters {
         LittleEndian::write_u16(&mut bytes[0..2], self.service_handle.0);
         let uuid_len = self.characteristic_uuid.copy_into_slice(&mut bytes[2..19]);
         let mut next = 2 + uuid_len;
-        if self.fw_version_before_v72 {
+        #[cfg(not(feature = "bluenrg2"))]
+        let short_value_len = self.fw_version_before_v72;
+        #[cfg(feature = "bluenrg2")]
+        let short_value_len = false;
+        if short_value_len {
             bytes[next] = self.characteristic_value_len as u8;
             next += 1;
         } else {
@@ -1632,6 +1699,7 @@ pub struct CharacteristicHandle(pub u16);
 
 /// Parameters for the [GATT Add Characteristic Descriptor](Commands::add_characteristic_descriptor)
 /// command.
+#[derive(Debug)]
 pub struct AddDescriptorParameters<'a> {
     /// Handle of the service to which characteristic belongs.
     pub service_handle: ServiceHandle,
@@ -1671,12 +1739,21 @@ pub struct AddDescriptorParameters<'a> {
 impl<'a> AddDescriptorParameters<'a> {
     const MAX_LENGTH: usize = 255;
 
+    /// Length of the fixed fields, assuming a 128-bit UUID.
+    #[cfg(not(feature = "bluenrg2"))]
+    const HEADER_LENGTH: usize = 28;
+
+    /// Length of the fixed fields, assuming a 128-bit UUID. The BlueNRG-1 and BlueNRG-2 use 2 bytes
+    /// each for the maximum and current value lengths.
+    #[cfg(feature = "bluenrg2")]
+    const HEADER_LENGTH: usize = 30;
+
     fn validate<E>(&self) -> Result<(), Error<E>> {
         if self.descriptor_value.len() > self.descriptor_value_max_len {
             return Err(Error::DescriptorTooLong);
         }
 
-        if 28 + self.descriptor_value_max_len > Self::MAX_LENGTH {
+        if Self::HEADER_LENGTH + self.descriptor_value_max_len > Self::MAX_LENGTH {
             return Err(Error::DescriptorBufferTooLong);
         }
 
@@ -1685,16 +1762,15 @@ impl<'a> AddDescriptorParameters<'a> {
 
     fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
         // The buffer should be big enough to hold this descriptor, assuming a 128-bit UUID.
-        assert!(bytes.len() >= 28 + self.descriptor_value.len());
+        assert!(bytes.len() >= Self::HEADER_LENGTH + self.descriptor_value.len());
 
         LittleEndian::write_u16(&mut bytes[0..2], self.service_handle.0);
         LittleEndian::write_u16(&mut bytes[2..4], self.characteristic_handle.0);
         let uuid_len = self.descriptor_uuid.copy_into_slice(&mut bytes[4..]);
-        bytes[4 + uuid_len] = self.descriptor_value_max_len as u8;
-        bytes[5 + uuid_len] = self.descriptor_value.len() as u8;
-        bytes[6 + uuid_len..6 + uuid_len + self.descriptor_value.len()]
+        let value_start = self.copy_value_lengths(&mut bytes[4 + uuid_len..]) + 4 + uuid_len;
+        bytes[value_start..value_start + self.descriptor_value.len()]
             .copy_from_slice(self.descriptor_value);
-        let next = 6 + uuid_len + self.descriptor_value.len();
+        let next = value_start + self.descriptor_value.len();
         bytes[next] = self.security_permissions.bits();
         bytes[1 + next] = self.access_permissions.bits();
         bytes[2 + next] = self.gatt_event_mask.bits();
@@ -1703,6 +1779,22 @@ impl<'a> AddDescriptorParameters<'a> {
 
         5 + next
     }
+
+    #[cfg(not(feature = "bluenrg2"))]
+    fn copy_value_lengths(&self, bytes: &mut [u8]) -> usize {
+        bytes[0] = self.descriptor_value_max_len as u8;
+        bytes[1] = self.descriptor_value.len() as u8;
+
+        2
+    }
+
+    #[cfg(feature = "bluenrg2")]
+    fn copy_value_lengths(&self, bytes: &mut [u8]) -> usize {
+        LittleEndian::write_u16(&mut bytes[0..2], self.descriptor_value_max_len as u16);
+        LittleEndian::write_u16(&mut bytes[2..4], self.descriptor_value.len() as u16);
+
+        4
+    }
 }
 
 /// Common characteristic descriptor UUIDs.
@@ -1762,6 +1854,7 @@ pub struct DescriptorHandle(pub u16);
 
 /// Parameters for the [Update Characteristic Value](Commands::update_characteristic_value)
 /// command.
+#[derive(Debug)]
 pub struct UpdateCharacteristicValueParameters<'a> {
     /// Handle of the service to which characteristic belongs.
     pub service_handle: ServiceHandle,
@@ -1808,6 +1901,7 @@ impl<'a> UpdateCharacteristicValueParameters<'a> {
 }
 
 /// Parameters for the [GATT Delete Included Service](Commands::delete_included_service) command.
+#[derive(Debug)]
 pub struct DeleteIncludedServiceParameters {
     /// Handle of the service to which Include definition belongs
     pub service: ServiceHandle,
@@ -1867,7 +1961,6 @@ bitflags! {
         /// [GATT Discover Characteristic by UUID or Read Using Characteristic
         /// UUID](crate::event::BlueNRGEvent::GattDiscoverOrReadCharacteristicByUuidResponse).
         const DISCOVER_OR_READ_CHARACTERISTIC_BY_UUID_RESPONSE = 0x0002_0000;
-        #[cfg(feature = "ms")]
         /// [GATT Tx Pool Available](crate::event::BlueNRGEvent::GattTxPoolAvailable)
         const TX_POOL_AVAILABLE = 0x0004_0000;
     }
@@ -1885,6 +1978,7 @@ impl Event {
 
 /// Parameters for the [GATT Find by Type Value Request](Commands::find_by_type_value_request)
 /// command.
+#[derive(Debug)]
 pub struct FindByTypeValueParameters<'a> {
     /// Connection handle for which the command is given.
     pub conn_handle: hci::ConnectionHandle,
@@ -1929,9 +2023,11 @@ impl<'a> FindByTypeValueParameters<'a> {
 }
 
 /// 16-bit UUID
+#[derive(Debug)]
 pub struct Uuid16(pub u16);
 
 /// Parameters for the [Read by Group Type Request](Commands::read_by_group_type_request) command.
+#[derive(Debug)]
 pub struct ReadByTypeParameters {
     /// Connection handle for which the command is given.
     pub conn_handle: hci::ConnectionHandle,
@@ -1957,6 +2053,7 @@ impl ReadByTypeParameters {
 }
 
 /// Parameters for the [Prepare Write Request](Commands::prepare_write_request) command.
+#[derive(Debug)]
 pub struct WriteRequest<'a> {
     /// Connection handle for which the command is given.
     pub conn_handle: hci::ConnectionHandle,
@@ -1997,6 +2094,7 @@ impl<'a> WriteRequest<'a> {
 
 /// Parameters for the [Read long characteristic value](Commands::read_long_characteristic_value)
 /// command.
+#[derive(Debug)]
 pub struct LongCharacteristicReadParameters {
     /// Connection handle for which the command is given.
     pub conn_handle: hci::ConnectionHandle,
@@ -2058,6 +2156,7 @@ impl<'a> MultipleCharacteristicReadParameters<'a> {
 }
 
 /// Parameters for the [Write Characteristic Value](Commands::write_characteristic_value) command.
+#[derive(Debug)]
 pub struct CharacteristicValue<'a> {
     /// Connection handle for which the command is given.
     pub conn_handle: hci::ConnectionHandle,
@@ -2098,6 +2197,7 @@ impl<'a> CharacteristicValue<'a> {
 
 /// Parameters for the [Write Long Characteristic Value](Commands::write_long_characteristic_value)
 /// command.
+#[derive(Debug)]
 pub struct LongCharacteristicValue<'a> {
     /// Connection handle for which the command is given.
     pub conn_handle: hci::ConnectionHandle,
@@ -2141,6 +2241,7 @@ impl<'a> LongCharacteristicValue<'a> {
 }
 
 /// Parameters for the [Write Response](Commands::write_response) command.
+#[derive(Debug)]
 pub struct WriteResponseParameters<'a> {
     /// Connection handle for which the command is given
     pub conn_handle: hci::ConnectionHandle,
@@ -2194,6 +2295,7 @@ impl<'a> WriteResponseParameters<'a> {
 }
 
 /// Parameters for the [Set Security Permission](Commands::set_security_permission) command.
+#[derive(Debug)]
 pub struct SecurityPermissionParameters {
     /// Handle of the service which contains the attribute whose security permission has to be
     /// modified.
@@ -2219,6 +2321,7 @@ impl SecurityPermissionParameters {
 }
 
 /// Parameters for the [Set Descriptor Value](Commands::set_descriptor_value) command.
+#[derive(Debug)]
 pub struct DescriptorValueParameters<'a> {
     /// Handle of the service which contains the descriptor.
     pub service_handle: ServiceHandle,
@@ -2266,7 +2369,7 @@ impl<'a> DescriptorValueParameters<'a> {
 
 /// Parameters for the [Update Long Characteristic
 /// Value](Commands::update_long_characteristic_value) command.
-#[cfg(feature = "ms")]
+#[derive(Debug)]
 pub struct UpdateLongCharacteristicValueParameters<'a> {
     /// Handle of the service to which characteristic belongs.
     pub service_handle: ServiceHandle,
@@ -2291,7 +2394,6 @@ pub struct UpdateLongCharacteristicValueParameters<'a> {
     pub value: &'a [u8],
 }
 
-#[cfg(feature = "ms")]
 impl<'a> UpdateLongCharacteristicValueParameters<'a> {
     const MAX_LENGTH: usize = 255;
 
@@ -2322,7 +2424,6 @@ impl<'a> UpdateLongCharacteristicValueParameters<'a> {
     }
 }
 
-#[cfg(feature = "ms")]
 bitflags! {
     /// Flags for types of updates that the controller should signal when a characteristic value is
     /// [updated](Commands::update_long_characteristic_value).
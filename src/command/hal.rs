@@ -2,10 +2,11 @@
 
 extern crate bluetooth_hci as hci;
 extern crate byteorder;
-extern crate embedded_hal as hal;
 extern crate nb;
 
+use super::WriteCommand;
 use byteorder::{ByteOrder, LittleEndian};
+use core::convert::TryFrom;
 
 /// Vendor-specific HCI commands for the [`ActiveBlueNRG`](crate::ActiveBlueNRG).
 pub trait Commands {
@@ -176,16 +177,11 @@ pub trait Commands {
     fn get_anchor_period(&mut self) -> nb::Result<(), Self::Error>;
 }
 
-impl<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, SpiError, GpioError> Commands
-    for crate::ActiveBlueNRG<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, GpioError>
+impl<T> Commands for T
 where
-    SPI: hal::blocking::spi::Transfer<u8, Error = SpiError>
-        + hal::blocking::spi::Write<u8, Error = SpiError>,
-    OutputPin1: hal::digital::v2::OutputPin<Error = GpioError>,
-    OutputPin2: hal::digital::v2::OutputPin<Error = GpioError>,
-    InputPin: hal::digital::v2::InputPin<Error = GpioError>,
+    T: hci::Controller<Header = hci::host::uart::CommandHeader, Vendor = crate::BlueNRGTypes>,
 {
-    type Error = crate::Error<SpiError, GpioError>;
+    type Error = T::Error;
 
     fn get_firmware_revision(&mut self) -> nb::Result<(), Self::Error> {
         self.write_command(crate::opcode::HAL_GET_FIRMWARE_REVISION, &[])
@@ -222,7 +218,7 @@ where
             return Err(nb::Error::Other(Error::InvalidChannel(channel)));
         }
 
-        self.write_command(crate::opcode::HAL_START_TONE, &[channel as u8])
+        self.write_command(crate::opcode::HAL_START_TONE, &[channel])
             .map_err(rewrap_error)
     }
 
@@ -262,6 +258,7 @@ fn rewrap_error<E>(e: nb::Error<E>) -> nb::Error<Error<E>> {
 }
 
 /// Low-level configuration parameters for the controller.
+#[derive(Debug)]
 pub struct ConfigData {
     offset: u8,
     length: u8,
@@ -290,6 +287,32 @@ impl ConfigData {
         2 + len
     }
 
+    /// Returns the offset of the first byte of the data within the configuration structure.
+    pub fn offset(&self) -> u8 {
+        self.offset
+    }
+
+    /// Returns the data, starting at the [offset](ConfigData::offset).
+    pub fn value(&self) -> &[u8] {
+        &self.value_buf[..self.length as usize]
+    }
+
+    /// Creates the data from its offset and value, as read back from a serialized command.
+    ///
+    /// # Panics
+    ///
+    /// The value must be no longer than [`MAX_LENGTH`](ConfigData::MAX_LENGTH) bytes.
+    pub(crate) fn from_parts(offset: u8, value: &[u8]) -> ConfigData {
+        let mut data = Self {
+            offset,
+            length: value.len() as u8,
+            value_buf: [0; Self::MAX_LENGTH],
+        };
+        data.value_buf[..value.len()].copy_from_slice(value);
+
+        data
+    }
+
     /// Builder for [ConfigData].
     ///
     /// The controller allows us to write any _contiguous_ portion of the [ConfigData] structure in
@@ -515,6 +538,7 @@ impl ConfigDataCompleteBuilder {
 }
 
 /// Roles that the server can adopt.
+#[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(u8)]
 pub enum Role {
     /// Peripheral and primary device.
@@ -539,8 +563,23 @@ pub enum Role {
     SimultaneousAdvertisingScanning = 4,
 }
 
+impl TryFrom<u8> for Role {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Role, Self::Error> {
+        match value {
+            1 => Ok(Role::Peripheral6Kb),
+            2 => Ok(Role::Peripheral12Kb),
+            3 => Ok(Role::Primary12Kb),
+            4 => Ok(Role::SimultaneousAdvertisingScanning),
+            _ => Err(value),
+        }
+    }
+}
+
 /// Configuration parameters that are readable by the
 /// [`read_config_data`](Commands::read_config_data) command.
+#[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(u8)]
 pub enum ConfigParameter {
     /// Bluetooth public address.
@@ -564,11 +603,28 @@ pub enum ConfigParameter {
     Role = 41,
 }
 
+impl TryFrom<u8> for ConfigParameter {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<ConfigParameter, Self::Error> {
+        match value {
+            0 => Ok(ConfigParameter::PublicAddress),
+            6 => Ok(ConfigParameter::Diversifier),
+            8 => Ok(ConfigParameter::EncryptionRoot),
+            24 => Ok(ConfigParameter::IdentityRoot),
+            40 => Ok(ConfigParameter::LinkLayerOnly),
+            41 => Ok(ConfigParameter::Role),
+            _ => Err(value),
+        }
+    }
+}
+
 /// Transmitter power levels available for the system.
 ///
 /// The controller uses two parameters to determine the actual power level: enable high power, and
 /// PA level. This enum combines the two parameters. The high byte is the PA level; the low byte is
 /// the enable high power flag.
+#[derive(Copy, Clone, Debug, PartialEq)]
 #[repr(u16)]
 pub enum PowerLevel {
     /// PA level 0, low power.
@@ -604,3 +660,29 @@ pub enum PowerLevel {
     /// PA level 7, high power.
     Dbm8_0 = 0x701,
 }
+
+impl TryFrom<u16> for PowerLevel {
+    type Error = u16;
+
+    fn try_from(value: u16) -> Result<PowerLevel, Self::Error> {
+        match value {
+            0x000 => Ok(PowerLevel::DbmNeg18),
+            0x001 => Ok(PowerLevel::DbmNeg15),
+            0x100 => Ok(PowerLevel::DbmNeg14_7),
+            0x101 => Ok(PowerLevel::DbmNeg11_7),
+            0x200 => Ok(PowerLevel::DbmNeg11_4),
+            0x201 => Ok(PowerLevel::DbmNeg8_4),
+            0x300 => Ok(PowerLevel::DbmNeg8_1),
+            0x301 => Ok(PowerLevel::DbmNeg5_1),
+            0x400 => Ok(PowerLevel::DbmNeg4_9),
+            0x401 => Ok(PowerLevel::DbmNeg2_1),
+            0x500 => Ok(PowerLevel::DbmNeg1_6),
+            0x501 => Ok(PowerLevel::Dbm1_4),
+            0x600 => Ok(PowerLevel::Dbm1_7),
+            0x601 => Ok(PowerLevel::Dbm4_7),
+            0x700 => Ok(PowerLevel::Dbm5_0),
+            0x701 => Ok(PowerLevel::Dbm8_0),
+            _ => Err(value),
+        }
+    }
+}
@@ -2,9 +2,9 @@
 
 extern crate bluetooth_hci as hci;
 extern crate byteorder;
-extern crate embedded_hal as hal;
 extern crate nb;
 
+use super::WriteCommand;
 use byteorder::{ByteOrder, LittleEndian};
 use hci::types::{ConnectionInterval, ExpectedConnectionLength};
 
@@ -51,16 +51,11 @@ pub trait Commands {
     ) -> nb::Result<(), Self::Error>;
 }
 
-impl<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, SpiError, GpioError> Commands
-    for crate::ActiveBlueNRG<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, GpioError>
+impl<T> Commands for T
 where
-    SPI: hal::blocking::spi::Transfer<u8, Error = SpiError>
-        + hal::blocking::spi::Write<u8, Error = SpiError>,
-    OutputPin1: hal::digital::v2::OutputPin<Error = GpioError>,
-    OutputPin2: hal::digital::v2::OutputPin<Error = GpioError>,
-    InputPin: hal::digital::v2::InputPin<Error = GpioError>,
+    T: hci::Controller<Header = hci::host::uart::CommandHeader, Vendor = crate::BlueNRGTypes>,
 {
-    type Error = crate::Error<SpiError, GpioError>;
+    type Error = T::Error;
 
     impl_params!(
         connection_parameter_update_request,
@@ -78,6 +73,7 @@ where
 /// Parameters for the
 /// [`connection_parameter_update_request`](Commands::connection_parameter_update_request)
 /// command.
+#[derive(Debug)]
 pub struct ConnectionParameterUpdateRequest {
     /// Connection handle of the link which the connection parameter update request has to be sent.
     pub conn_handle: hci::ConnectionHandle,
@@ -100,6 +96,7 @@ impl ConnectionParameterUpdateRequest {
 /// Parameters for the
 /// [`connection_parameter_update_response`](Commands::connection_parameter_update_response)
 /// command.
+#[derive(Debug)]
 pub struct ConnectionParameterUpdateResponse {
     /// [Connection handle](crate::event::L2CapConnectionUpdateRequest::conn_handle) received in the
     /// [`L2CapConnectionUpdateRequest`](crate::event::BlueNRGEvent::L2CapConnectionUpdateRequest)
@@ -1,3 +1,32 @@
+use hci::host::HciHeader;
+
+/// Writes a vendor-specific command to any controller that uses the UART-style command header,
+/// which includes the packet type byte.
+trait WriteCommand: hci::Controller {
+    fn write_command(
+        &mut self,
+        opcode: crate::opcode::Opcode,
+        params: &[u8],
+    ) -> nb::Result<(), Self::Error>;
+}
+
+impl<T> WriteCommand for T
+where
+    T: hci::Controller<Header = hci::host::uart::CommandHeader>,
+{
+    fn write_command(
+        &mut self,
+        opcode: crate::opcode::Opcode,
+        params: &[u8],
+    ) -> nb::Result<(), Self::Error> {
+        const HEADER_LEN: usize = 4;
+        let mut header = [0; HEADER_LEN];
+        hci::host::uart::CommandHeader::new(opcode, params.len()).copy_into_slice(&mut header);
+
+        self.write(&header, params)
+    }
+}
+
 macro_rules! impl_params {
     ($method:ident, $param_type:ident, $opcode:path) => {
         fn $method(&mut self, params: &$param_type) -> nb::Result<(), Self::Error> {
@@ -72,7 +101,9 @@ macro_rules! impl_validate_variable_length_params {
     };
 }
 
+pub mod decode;
 pub mod gap;
 pub mod gatt;
 pub mod hal;
 pub mod l2cap;
+pub mod updater;
@@ -0,0 +1,288 @@
+//! Vendor-specific HCI commands for the updater, which rewrites the controller's firmware.
+//!
+//! The updater runs instead of the Bluetooth stack after the [Start](Commands::start) command, or
+//! when the controller starts with the blue flag (which marks a valid stack image) erased. While
+//! the updater runs, the controller only accepts these commands. See the
+//! [`flasher`](crate::flasher) module for the sequence that uses them to replace the stack.
+
+extern crate bluetooth_hci as hci;
+extern crate byteorder;
+extern crate nb;
+
+use super::WriteCommand;
+use byteorder::{ByteOrder, LittleEndian};
+
+/// Address of the start of the controller's flash.
+pub const FLASH_BASE_ADDRESS: u32 = 0x1001_0000;
+
+/// Address of the Bluetooth stack image, after the sector that holds the updater itself.
+pub const FIRMWARE_ADDRESS: u32 = FLASH_BASE_ADDRESS + SECTOR_SIZE as u32;
+
+/// Size of a flash sector, which is the unit of [erasing](Commands::erase_sector) and of
+/// [CRC calculation](Commands::calc_crc).
+pub const SECTOR_SIZE: usize = 2048;
+
+/// Maximum length of the Bluetooth stack image.
+pub const MAX_FIRMWARE_LEN: usize = 32 * SECTOR_SIZE;
+
+/// Maximum length of the data in a [Program Data Block](Commands::program_data_block) or [Read
+/// Data Block](Commands::read_data_block) command. The command packet is limited to 255 bytes, and
+/// 6 of them hold the address and length.
+pub const MAX_DATA_BLOCK_LEN: usize = 249;
+
+/// Vendor-specific HCI commands for the updater.
+pub trait Commands {
+    /// Type of communication errors.
+    type Error;
+
+    /// Starts the updater. The controller reboots into the updater, and reports it with a [HAL
+    /// Initialized](crate::event::BlueNRGEvent::HalInitialized) event with the
+    /// [Updater](crate::event::ResetReason::Updater) reason.
+    ///
+    /// # Errors
+    ///
+    /// Only underlying communication errors are reported.
+    ///
+    /// # Generated events
+    ///
+    /// A [Command Complete](crate::event::command::ReturnParameters::UpdaterStart) event is
+    /// generated.
+    fn start(&mut self) -> nb::Result<(), Self::Error>;
+
+    /// Reboots the controller. If the blue flag is set, the Bluetooth stack runs; otherwise the
+    /// updater runs again.
+    ///
+    /// # Errors
+    ///
+    /// Only underlying communication errors are reported.
+    ///
+    /// # Generated events
+    ///
+    /// A [Command Complete](crate::event::command::ReturnParameters::UpdaterReboot) event is
+    /// generated.
+    fn reboot(&mut self) -> nb::Result<(), Self::Error>;
+
+    /// Requests the version of the updater.
+    ///
+    /// # Errors
+    ///
+    /// Only underlying communication errors are reported.
+    ///
+    /// # Generated events
+    ///
+    /// A [Command Complete](crate::event::command::ReturnParameters::UpdaterGetVersion) event is
+    /// generated.
+    fn get_version(&mut self) -> nb::Result<(), Self::Error>;
+
+    /// Requests the size of the updater's buffer, which limits the length of a data block.
+    ///
+    /// # Errors
+    ///
+    /// Only underlying communication errors are reported.
+    ///
+    /// # Generated events
+    ///
+    /// A [Command Complete](crate::event::command::ReturnParameters::UpdaterGetBufferSize) event is
+    /// generated.
+    fn get_buffer_size(&mut self) -> nb::Result<(), Self::Error>;
+
+    /// Erases the blue flag, which marks the Bluetooth stack image as valid. Until the flag is
+    /// [reset](Commands::reset_blue_flag), the controller starts in the updater.
+    ///
+    /// # Errors
+    ///
+    /// Only underlying communication errors are reported.
+    ///
+    /// # Generated events
+    ///
+    /// A [Command Complete](crate::event::command::ReturnParameters::UpdaterEraseBlueFlag) event is
+    /// generated.
+    fn erase_blue_flag(&mut self) -> nb::Result<(), Self::Error>;
+
+    /// Restores the blue flag, which marks the Bluetooth stack image as valid.
+    ///
+    /// # Errors
+    ///
+    /// Only underlying communication errors are reported.
+    ///
+    /// # Generated events
+    ///
+    /// A [Command Complete](crate::event::command::ReturnParameters::UpdaterResetBlueFlag) event is
+    /// generated.
+    fn reset_blue_flag(&mut self) -> nb::Result<(), Self::Error>;
+
+    /// Erases the flash sector at the given address.
+    ///
+    /// # Errors
+    ///
+    /// Only underlying communication errors are reported.
+    ///
+    /// # Generated events
+    ///
+    /// A [Command Complete](crate::event::command::ReturnParameters::UpdaterEraseSector) event is
+    /// generated.
+    fn erase_sector(&mut self, address: u32) -> nb::Result<(), Self::Error>;
+
+    /// Writes a block of data to flash at the given address. The flash must have been erased.
+    ///
+    /// # Errors
+    ///
+    /// - [DataBlockTooLong](Error::DataBlockTooLong) if the data is longer than
+    ///   [`MAX_DATA_BLOCK_LEN`].
+    /// - Underlying communication errors.
+    ///
+    /// # Generated events
+    ///
+    /// A [Command Complete](crate::event::command::ReturnParameters::UpdaterProgramDataBlock) event
+    /// is generated.
+    fn program_data_block(
+        &mut self,
+        address: u32,
+        data: &[u8],
+    ) -> nb::Result<(), Error<Self::Error>>;
+
+    /// Reads `len` bytes of flash at the given address.
+    ///
+    /// # Errors
+    ///
+    /// - [DataBlockTooLong](Error::DataBlockTooLong) if `len` is greater than
+    ///   [`MAX_DATA_BLOCK_LEN`].
+    /// - Underlying communication errors.
+    ///
+    /// # Generated events
+    ///
+    /// A [Command Complete](crate::event::command::ReturnParameters::UpdaterReadDataBlock) event is
+    /// generated, with the data.
+    fn read_data_block(&mut self, address: u32, len: usize) -> nb::Result<(), Error<Self::Error>>;
+
+    /// Calculates the CRC of `sector_count` flash sectors, starting at the given address.
+    ///
+    /// # Errors
+    ///
+    /// Only underlying communication errors are reported.
+    ///
+    /// # Generated events
+    ///
+    /// A [Command Complete](crate::event::command::ReturnParameters::UpdaterCalcCrc) event is
+    /// generated, with the CRC.
+    fn calc_crc(&mut self, address: u32, sector_count: u8) -> nb::Result<(), Self::Error>;
+
+    /// Requests the version of the controller hardware.
+    ///
+    /// # Errors
+    ///
+    /// Only underlying communication errors are reported.
+    ///
+    /// # Generated events
+    ///
+    /// A [Command Complete](crate::event::command::ReturnParameters::UpdaterHardwareVersion) event
+    /// is generated.
+    fn hw_version(&mut self) -> nb::Result<(), Self::Error>;
+}
+
+impl<T> Commands for T
+where
+    T: hci::Controller<Header = hci::host::uart::CommandHeader, Vendor = crate::BlueNRGTypes>,
+{
+    type Error = T::Error;
+
+    fn start(&mut self) -> nb::Result<(), Self::Error> {
+        self.write_command(crate::opcode::UPDATER_START, &[])
+    }
+
+    fn reboot(&mut self) -> nb::Result<(), Self::Error> {
+        self.write_command(crate::opcode::UPDATER_REBOOT, &[])
+    }
+
+    fn get_version(&mut self) -> nb::Result<(), Self::Error> {
+        self.write_command(crate::opcode::UPDATER_GET_VERSION, &[])
+    }
+
+    fn get_buffer_size(&mut self) -> nb::Result<(), Self::Error> {
+        self.write_command(crate::opcode::UPDATER_GET_BUFFER_SIZE, &[])
+    }
+
+    fn erase_blue_flag(&mut self) -> nb::Result<(), Self::Error> {
+        self.write_command(crate::opcode::UPDATER_ERASE_BLUE_FLAG, &[])
+    }
+
+    fn reset_blue_flag(&mut self) -> nb::Result<(), Self::Error> {
+        self.write_command(crate::opcode::UPDATER_RESET_BLUE_FLAG, &[])
+    }
+
+    fn erase_sector(&mut self, address: u32) -> nb::Result<(), Self::Error> {
+        let mut bytes = [0; 4];
+        LittleEndian::write_u32(&mut bytes, address);
+
+        self.write_command(crate::opcode::UPDATER_ERASE_SECTOR, &bytes)
+    }
+
+    fn program_data_block(
+        &mut self,
+        address: u32,
+        data: &[u8],
+    ) -> nb::Result<(), Error<Self::Error>> {
+        if data.len() > MAX_DATA_BLOCK_LEN {
+            return Err(nb::Error::Other(Error::DataBlockTooLong(data.len())));
+        }
+
+        let mut bytes = [0; 6 + MAX_DATA_BLOCK_LEN];
+        LittleEndian::write_u32(&mut bytes[0..4], address);
+        LittleEndian::write_u16(&mut bytes[4..6], data.len() as u16);
+        bytes[6..6 + data.len()].copy_from_slice(data);
+
+        self.write_command(
+            crate::opcode::UPDATER_PROGRAM_DATA_BLOCK,
+            &bytes[..6 + data.len()],
+        )
+        .map_err(rewrap_error)
+    }
+
+    fn read_data_block(&mut self, address: u32, len: usize) -> nb::Result<(), Error<Self::Error>> {
+        if len > MAX_DATA_BLOCK_LEN {
+            return Err(nb::Error::Other(Error::DataBlockTooLong(len)));
+        }
+
+        let mut bytes = [0; 6];
+        LittleEndian::write_u32(&mut bytes[0..4], address);
+        LittleEndian::write_u16(&mut bytes[4..6], len as u16);
+
+        self.write_command(crate::opcode::UPDATER_READ_DATA_BLOCK, &bytes)
+            .map_err(rewrap_error)
+    }
+
+    fn calc_crc(&mut self, address: u32, sector_count: u8) -> nb::Result<(), Self::Error> {
+        let mut bytes = [0; 5];
+        LittleEndian::write_u32(&mut bytes[0..4], address);
+        bytes[4] = sector_count;
+
+        self.write_command(crate::opcode::UPDATER_CALC_CRC, &bytes)
+    }
+
+    fn hw_version(&mut self) -> nb::Result<(), Self::Error> {
+        self.write_command(crate::opcode::UPDATER_HW_VERSION, &[])
+    }
+}
+
+/// Potential errors from parameter validation.
+///
+/// Before some commands are sent to the controller, the parameters are validated. This type
+/// enumerates the potential validation errors. Must be specialized on the types of communication
+/// errors.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Error<E> {
+    /// For the [Program Data Block](Commands::program_data_block) and [Read Data
+    /// Block](Commands::read_data_block) commands, the data block is longer than
+    /// [`MAX_DATA_BLOCK_LEN`]. Includes the requested length.
+    DataBlockTooLong(usize),
+
+    /// Underlying communication error.
+    Comm(E),
+}
+
+fn rewrap_error<E>(e: nb::Error<E>) -> nb::Error<Error<E>> {
+    match e {
+        nb::Error::WouldBlock => nb::Error::WouldBlock,
+        nb::Error::Other(c) => nb::Error::Other(Error::Comm(c)),
+    }
+}
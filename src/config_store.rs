@@ -0,0 +1,337 @@
+//! Reading and updating the whole low-level configuration area.
+//!
+//! [`hal::Commands::read_config_data`](crate::hal::Commands::read_config_data) returns one
+//! parameter at a time, and [`ConfigData`] writes a run of consecutive parameters. [`ConfigStore`]
+//! reads every parameter into a [`ConfigArea`], compares it with the desired configuration, and
+//! writes only the parameters that differ. Each written parameter is read back to verify it, and
+//! the result reports which parameters were written.
+//!
+//! ```ignore
+//! let mut requester: Requester<4> = Requester::new();
+//! let desired = ConfigArea {
+//!     public_address: Some(hci::BdAddr([1, 2, 3, 4, 5, 6])),
+//!     role: Some(Role::Peripheral12Kb),
+//!     ..ConfigArea::default()
+//! };
+//! let written = ConfigStore::new(&mut requester).apply(controller, &desired)?;
+//! ```
+
+use crate::event::command::{HalConfigData, HalConfigParameter};
+use crate::hal::{Commands as HalCommands, ConfigData, ConfigParameter, Role};
+use crate::request::{self, Requester};
+use core::convert::TryFrom;
+use hci::host::EncryptionKey;
+use hci::BdAddr;
+
+/// Contents of the low-level configuration area.
+///
+/// Each field is `None` if its value is unknown. In a desired configuration passed to
+/// [`ConfigStore::apply`], `None` means the parameter is left as it is.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ConfigArea {
+    /// Bluetooth public address.
+    pub public_address: Option<BdAddr>,
+
+    /// Diversifier used to derive CSRK (connection signature resolving key).
+    pub diversifier: Option<u16>,
+
+    /// Encryption root key used to derive the LTK (long-term key) and CSRK (connection signature
+    /// resolving key).
+    pub encryption_root: Option<EncryptionKey>,
+
+    /// Identity root key used to derive the LTK (long-term key) and CSRK (connection signature
+    /// resolving key).
+    pub identity_root: Option<EncryptionKey>,
+
+    /// Whether the controller runs in Link Layer only mode.
+    pub link_layer_only: Option<bool>,
+
+    /// Role and mode configuration. Reads as `None` if the controller returns a value that is not
+    /// a valid [`Role`].
+    pub role: Option<Role>,
+}
+
+/// All of the parameters in the configuration area, in order.
+const PARAMETERS: [ConfigParameter; 6] = [
+    ConfigParameter::PublicAddress,
+    ConfigParameter::Diversifier,
+    ConfigParameter::EncryptionRoot,
+    ConfigParameter::IdentityRoot,
+    ConfigParameter::LinkLayerOnly,
+    ConfigParameter::Role,
+];
+
+bitflags! {
+    /// Set of parameters in the configuration area.
+    pub struct ConfigFields: u8 {
+        /// [PublicAddress](ConfigParameter::PublicAddress)
+        const PUBLIC_ADDRESS = 0x01;
+        /// [Diversifier](ConfigParameter::Diversifier)
+        const DIVERSIFIER = 0x02;
+        /// [EncryptionRoot](ConfigParameter::EncryptionRoot)
+        const ENCRYPTION_ROOT = 0x04;
+        /// [IdentityRoot](ConfigParameter::IdentityRoot)
+        const IDENTITY_ROOT = 0x08;
+        /// [LinkLayerOnly](ConfigParameter::LinkLayerOnly)
+        const LINK_LAYER_ONLY = 0x10;
+        /// [Role](ConfigParameter::Role)
+        const ROLE = 0x20;
+    }
+}
+
+impl From<ConfigParameter> for ConfigFields {
+    fn from(param: ConfigParameter) -> ConfigFields {
+        match param {
+            ConfigParameter::PublicAddress => ConfigFields::PUBLIC_ADDRESS,
+            ConfigParameter::Diversifier => ConfigFields::DIVERSIFIER,
+            ConfigParameter::EncryptionRoot => ConfigFields::ENCRYPTION_ROOT,
+            ConfigParameter::IdentityRoot => ConfigFields::IDENTITY_ROOT,
+            ConfigParameter::LinkLayerOnly => ConfigFields::LINK_LAYER_ONLY,
+            ConfigParameter::Role => ConfigFields::ROLE,
+        }
+    }
+}
+
+impl ConfigArea {
+    /// Returns the parameters that are set in `desired` and have a different value (or an unknown
+    /// value) in `self`.
+    pub fn diff(&self, desired: &ConfigArea) -> ConfigFields {
+        PARAMETERS
+            .iter()
+            .filter(|&&param| desired.is_set(param) && !self.matches(desired, param))
+            .fold(ConfigFields::empty(), |fields, &param| {
+                fields | ConfigFields::from(param)
+            })
+    }
+
+    fn is_set(&self, param: ConfigParameter) -> bool {
+        match param {
+            ConfigParameter::PublicAddress => self.public_address.is_some(),
+            ConfigParameter::Diversifier => self.diversifier.is_some(),
+            ConfigParameter::EncryptionRoot => self.encryption_root.is_some(),
+            ConfigParameter::IdentityRoot => self.identity_root.is_some(),
+            ConfigParameter::LinkLayerOnly => self.link_layer_only.is_some(),
+            ConfigParameter::Role => self.role.is_some(),
+        }
+    }
+
+    /// Returns true if the parameter has the same value in both areas.
+    fn matches(&self, other: &ConfigArea, param: ConfigParameter) -> bool {
+        match param {
+            ConfigParameter::PublicAddress => self.public_address == other.public_address,
+            ConfigParameter::Diversifier => self.diversifier == other.diversifier,
+            ConfigParameter::EncryptionRoot => self.encryption_root == other.encryption_root,
+            ConfigParameter::IdentityRoot => self.identity_root == other.identity_root,
+            ConfigParameter::LinkLayerOnly => self.link_layer_only == other.link_layer_only,
+            ConfigParameter::Role => self.role == other.role,
+        }
+    }
+
+    /// Returns the command that writes the parameter, or `None` if it is not set.
+    fn config_data(&self, param: ConfigParameter) -> Option<ConfigData> {
+        match param {
+            ConfigParameter::PublicAddress => self
+                .public_address
+                .map(|addr| ConfigData::public_address(addr).build()),
+            ConfigParameter::Diversifier => {
+                self.diversifier.map(|d| ConfigData::diversifier(d).build())
+            }
+            ConfigParameter::EncryptionRoot => self
+                .encryption_root
+                .as_ref()
+                .map(|key| ConfigData::encryption_root(key).build()),
+            ConfigParameter::IdentityRoot => self
+                .identity_root
+                .as_ref()
+                .map(|key| ConfigData::identity_root(key).build()),
+            ConfigParameter::LinkLayerOnly => self
+                .link_layer_only
+                .map(|ll_only| ConfigData::link_layer_only(ll_only).build()),
+            ConfigParameter::Role => self.role.map(|role| ConfigData::role(role).build()),
+        }
+    }
+
+    /// Stores a value read from the controller. Returns false if the value does not have the type
+    /// of the parameter.
+    fn store(&mut self, param: ConfigParameter, value: HalConfigParameter) -> bool {
+        match (param, value) {
+            (ConfigParameter::PublicAddress, HalConfigParameter::PublicAddress(addr)) => {
+                self.public_address = Some(addr)
+            }
+            (ConfigParameter::Diversifier, HalConfigParameter::Diversifier(d)) => {
+                self.diversifier = Some(d)
+            }
+            (ConfigParameter::EncryptionRoot, HalConfigParameter::EncryptionKey(key)) => {
+                self.encryption_root = Some(key)
+            }
+            (ConfigParameter::IdentityRoot, HalConfigParameter::EncryptionKey(key)) => {
+                self.identity_root = Some(key)
+            }
+            (ConfigParameter::LinkLayerOnly, HalConfigParameter::Byte(b)) => {
+                self.link_layer_only = Some(b != 0)
+            }
+            (ConfigParameter::Role, HalConfigParameter::Byte(b)) => {
+                self.role = Role::try_from(b).ok()
+            }
+            _ => return false,
+        }
+
+        true
+    }
+}
+
+/// Reasons reading or writing a parameter can fail.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Cause<E> {
+    /// The command failed, or its response could not be read.
+    Request(request::Error<E>),
+
+    /// The controller returned a value of the wrong length for the parameter.
+    BadValue,
+
+    /// The parameter was written, but reading it back returned a different value.
+    VerifyFailed,
+}
+
+/// Error returned by [`ConfigStore`], naming the parameter that failed and why.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Error<E> {
+    /// The parameter that was being read or written.
+    pub param: ConfigParameter,
+
+    /// The parameters that were written and verified before the failure.
+    pub persisted: ConfigFields,
+
+    /// Why it failed.
+    pub cause: Cause<E>,
+}
+
+/// Reads the configuration area, and writes the parameters that differ from a desired
+/// configuration. Commands are sent with a [`Requester`], which buffers unrelated events.
+pub struct ConfigStore<'r, const N: usize> {
+    requester: &'r mut Requester<N>,
+}
+
+impl<'r, const N: usize> ConfigStore<'r, N> {
+    /// Returns a store that sends commands with the given requester.
+    pub fn new(requester: &'r mut Requester<N>) -> ConfigStore<'r, N> {
+        ConfigStore { requester }
+    }
+
+    /// Reads every parameter of the configuration area.
+    ///
+    /// A parameter that the controller does not support, so that reading it fails with an error
+    /// status, is left as `None`.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error`] naming the parameter that could not be read.
+    pub fn read<C>(&mut self, controller: &mut C) -> Result<ConfigArea, Error<C::Error>>
+    where
+        C: hci::Controller<Header = hci::host::uart::CommandHeader, Vendor = crate::BlueNRGTypes>
+            + crate::Firmware,
+    {
+        let mut area = ConfigArea::default();
+        for &param in PARAMETERS.iter() {
+            match self.read_param(controller, &mut area, param) {
+                Ok(()) | Err(Cause::Request(request::Error::CommandFailed(_))) => (),
+                Err(cause) => {
+                    return Err(Error {
+                        param,
+                        persisted: ConfigFields::empty(),
+                        cause,
+                    })
+                }
+            }
+        }
+
+        Ok(area)
+    }
+
+    /// Reads the configuration area, then writes each parameter that is set in `desired` and
+    /// differs from the controller's value, and reads it back to verify it.
+    ///
+    /// Returns the parameters that were written. Parameters that already had the desired value are
+    /// not written.
+    ///
+    /// # Errors
+    ///
+    /// Returns an [`Error`] naming the parameter that failed, and the parameters that were written
+    /// before it. Parameters after it are not written.
+    pub fn apply<C>(
+        &mut self,
+        controller: &mut C,
+        desired: &ConfigArea,
+    ) -> Result<ConfigFields, Error<C::Error>>
+    where
+        C: hci::Controller<Header = hci::host::uart::CommandHeader, Vendor = crate::BlueNRGTypes>
+            + crate::Firmware,
+    {
+        let current = self.read(controller)?;
+        let changed = current.diff(desired);
+
+        let mut persisted = ConfigFields::empty();
+        for &param in PARAMETERS.iter() {
+            if !changed.contains(ConfigFields::from(param)) {
+                continue;
+            }
+
+            self.write_param(controller, desired, param)
+                .map_err(|cause| Error {
+                    param,
+                    persisted,
+                    cause,
+                })?;
+            persisted |= ConfigFields::from(param);
+        }
+
+        Ok(persisted)
+    }
+
+    fn read_param<C>(
+        &mut self,
+        controller: &mut C,
+        area: &mut ConfigArea,
+        param: ConfigParameter,
+    ) -> Result<(), Cause<C::Error>>
+    where
+        C: hci::Controller<Header = hci::host::uart::CommandHeader, Vendor = crate::BlueNRGTypes>
+            + crate::Firmware,
+    {
+        let data: HalConfigData = self
+            .requester
+            .call(controller, |c| c.read_config_data(param))
+            .map_err(Cause::Request)?;
+        if area.store(param, data.value) {
+            Ok(())
+        } else {
+            Err(Cause::BadValue)
+        }
+    }
+
+    fn write_param<C>(
+        &mut self,
+        controller: &mut C,
+        desired: &ConfigArea,
+        param: ConfigParameter,
+    ) -> Result<(), Cause<C::Error>>
+    where
+        C: hci::Controller<Header = hci::host::uart::CommandHeader, Vendor = crate::BlueNRGTypes>
+            + crate::Firmware,
+    {
+        if let Some(config) = desired.config_data(param) {
+            let () = self
+                .requester
+                .call(controller, |c| c.write_config_data(&config))
+                .map_err(Cause::Request)?;
+        }
+
+        let mut written = ConfigArea::default();
+        self.read_param(controller, &mut written, param)?;
+        if written.matches(desired, param) {
+            Ok(())
+        } else {
+            Err(Cause::VerifyFailed)
+        }
+    }
+}
@@ -0,0 +1,337 @@
+//! Persistence and decoding of crash reports.
+//!
+//! The controller sends the [`CrashReport`](crate::event::BlueNRGEvent::CrashReport) event only
+//! once, right after it restarts from a crash. To look at it later, the application stores it with
+//! [`FaultData::serialize`], for example in flash, and uploads the stored records to a host. The
+//! host reads them back with [`Records`], formats each one with [`Report`], and counts identical
+//! crashes with [`Groups`].
+//!
+//! # Record format
+//!
+//! A record is 39 bytes plus the debug data. Multi-byte values are little-endian.
+//!
+//! - Byte 0: format version, [`FORMAT_VERSION`].
+//! - Byte 1: crash reason. 0 for an assertion, 1 for an NMI fault, and 2 for a hard fault.
+//! - Bytes 2-37: the SP, R0, R1, R2, R3, R12, LR, PC, and xPSR registers, 4 bytes each.
+//! - Byte 38: length of the debug data, at most 215.
+//! - Bytes 39 and on: the debug data.
+//!
+//! Records can be stored back to back. A version byte of `0xFF`, as left in erased flash, ends a
+//! sequence of records.
+
+use crate::event::{CrashReason, FaultData, MAX_DEBUG_DATA_LEN};
+use byteorder::{ByteOrder, LittleEndian};
+use core::fmt::{Display, Formatter, Result as FmtResult};
+
+/// Version of the record format written by [`FaultData::serialize`].
+pub const FORMAT_VERSION: u8 = 1;
+
+/// Length of a record, not including the debug data.
+const HEADER_LEN: usize = 39;
+
+/// Maximum length of a record.
+pub const MAX_RECORD_LEN: usize = HEADER_LEN + MAX_DEBUG_DATA_LEN;
+
+/// Version byte of erased flash, which ends a sequence of records.
+const ERASED: u8 = 0xFF;
+
+/// Errors that can occur when writing or reading a record.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Error {
+    /// The buffer is too small for the record. Includes the length of the record.
+    BufferTooSmall(usize),
+
+    /// The record ends before all of its fields.
+    Truncated,
+
+    /// The record was written with a format version this crate cannot read. Includes the version.
+    UnsupportedVersion(u8),
+
+    /// The crash reason is not valid. Includes the byte.
+    BadCrashReason(u8),
+
+    /// The length of the debug data is longer than the controller can report. Includes the length.
+    BadDebugDataLength(u8),
+}
+
+fn reason_code(reason: CrashReason) -> u8 {
+    match reason {
+        CrashReason::Assertion => 0,
+        CrashReason::NmiFault => 1,
+        CrashReason::HardFault => 2,
+    }
+}
+
+fn reason_from_code(code: u8) -> Result<CrashReason, Error> {
+    match code {
+        0 => Ok(CrashReason::Assertion),
+        1 => Ok(CrashReason::NmiFault),
+        2 => Ok(CrashReason::HardFault),
+        _ => Err(Error::BadCrashReason(code)),
+    }
+}
+
+impl FaultData {
+    /// Returns the length of the record [`serialize`](FaultData::serialize) writes.
+    pub fn serialized_len(&self) -> usize {
+        HEADER_LEN + self.debug_data_len
+    }
+
+    /// Writes the fault data to `buffer` in the versioned [record format](crate::crash).
+    ///
+    /// Returns the number of bytes written.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferTooSmall`](Error::BufferTooSmall) if the record does not fit in `buffer`.
+    /// Nothing is written in that case.
+    pub fn serialize(&self, buffer: &mut [u8]) -> Result<usize, Error> {
+        let len = self.serialized_len();
+        if buffer.len() < len {
+            return Err(Error::BufferTooSmall(len));
+        }
+
+        buffer[0] = FORMAT_VERSION;
+        buffer[1] = reason_code(self.reason);
+        let registers = [
+            self.sp, self.r0, self.r1, self.r2, self.r3, self.r12, self.lr, self.pc, self.xpsr,
+        ];
+        for (index, register) in registers.iter().enumerate() {
+            LittleEndian::write_u32(&mut buffer[2 + 4 * index..], *register);
+        }
+        buffer[38] = self.debug_data_len as u8;
+        buffer[HEADER_LEN..len].copy_from_slice(self.debug_data());
+
+        Ok(len)
+    }
+
+    /// Reads fault data from a record at the start of `bytes`, written by
+    /// [`serialize`](FaultData::serialize).
+    ///
+    /// Returns the fault data and the length of the record, which is where the next record starts.
+    ///
+    /// # Errors
+    ///
+    /// - [`Truncated`](Error::Truncated) if `bytes` ends before the record does.
+    /// - [`UnsupportedVersion`](Error::UnsupportedVersion) if the record was not written with
+    ///   [`FORMAT_VERSION`].
+    /// - [`BadCrashReason`](Error::BadCrashReason) or
+    ///   [`BadDebugDataLength`](Error::BadDebugDataLength) if a field is out of range.
+    pub fn deserialize(bytes: &[u8]) -> Result<(FaultData, usize), Error> {
+        let version = *bytes.first().ok_or(Error::Truncated)?;
+        if version != FORMAT_VERSION {
+            return Err(Error::UnsupportedVersion(version));
+        }
+        if bytes.len() < HEADER_LEN {
+            return Err(Error::Truncated);
+        }
+
+        let debug_data_len = bytes[38];
+        if debug_data_len as usize > MAX_DEBUG_DATA_LEN {
+            return Err(Error::BadDebugDataLength(debug_data_len));
+        }
+        let debug_data_len = debug_data_len as usize;
+        let len = HEADER_LEN + debug_data_len;
+        if bytes.len() < len {
+            return Err(Error::Truncated);
+        }
+
+        let mut fault_data = FaultData {
+            reason: reason_from_code(bytes[1])?,
+            sp: LittleEndian::read_u32(&bytes[2..]),
+            r0: LittleEndian::read_u32(&bytes[6..]),
+            r1: LittleEndian::read_u32(&bytes[10..]),
+            r2: LittleEndian::read_u32(&bytes[14..]),
+            r3: LittleEndian::read_u32(&bytes[18..]),
+            r12: LittleEndian::read_u32(&bytes[22..]),
+            lr: LittleEndian::read_u32(&bytes[26..]),
+            pc: LittleEndian::read_u32(&bytes[30..]),
+            xpsr: LittleEndian::read_u32(&bytes[34..]),
+            debug_data_len,
+            debug_data_buf: [0; MAX_DEBUG_DATA_LEN],
+        };
+        fault_data.debug_data_buf[..debug_data_len].copy_from_slice(&bytes[HEADER_LEN..len]);
+
+        Ok((fault_data, len))
+    }
+
+    /// Returns the signature of the crash, which identical crashes share.
+    pub fn signature(&self) -> Signature {
+        Signature {
+            reason: self.reason,
+            pc: self.pc,
+            lr: self.lr,
+        }
+    }
+}
+
+/// Iterator over records stored back to back, as returned by [`records`].
+///
+/// It stops at the end of the bytes, at erased flash, or after the first record that cannot be
+/// read.
+pub struct Records<'a> {
+    bytes: &'a [u8],
+}
+
+/// Returns an iterator over the records stored back to back in `bytes`.
+pub fn records(bytes: &[u8]) -> Records<'_> {
+    Records { bytes }
+}
+
+impl<'a> Iterator for Records<'a> {
+    type Item = Result<FaultData, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.bytes.first() {
+            None | Some(&ERASED) => return None,
+            Some(_) => (),
+        }
+
+        match FaultData::deserialize(self.bytes) {
+            Ok((fault_data, len)) => {
+                self.bytes = &self.bytes[len..];
+                Some(Ok(fault_data))
+            }
+            Err(e) => {
+                self.bytes = &[];
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+/// Formats fault data for people: the reason, the PC and LR, the other stacked registers, and a
+/// hex dump of the debug data.
+pub struct Report<'a>(pub &'a FaultData);
+
+impl<'a> Display for Report<'a> {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        let data = self.0;
+        let reason = match data.reason {
+            CrashReason::Assertion => "assertion failed",
+            CrashReason::NmiFault => "NMI fault",
+            CrashReason::HardFault => "hard fault",
+        };
+        writeln!(
+            f,
+            "{} at pc {:#010x}, lr {:#010x}",
+            reason, data.pc, data.lr
+        )?;
+        writeln!(f, "  sp   {:#010x}  xpsr {:#010x}", data.sp, data.xpsr)?;
+        writeln!(
+            f,
+            "  r0   {:#010x}  r1   {:#010x}  r2   {:#010x}  r3   {:#010x}",
+            data.r0, data.r1, data.r2, data.r3
+        )?;
+        writeln!(f, "  r12  {:#010x}", data.r12)?;
+
+        let debug_data = data.debug_data();
+        if debug_data.is_empty() {
+            return writeln!(f, "  no debug data");
+        }
+        writeln!(f, "  debug data ({} bytes):", debug_data.len())?;
+        for (line, chunk) in debug_data.chunks(16).enumerate() {
+            write!(f, "    {:04x} ", 16 * line)?;
+            for byte in chunk {
+                write!(f, " {:02x}", byte)?;
+            }
+            writeln!(f)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// What identical crashes have in common: the reason, and where the controller was when it
+/// crashed.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Signature {
+    /// Reason for the crash.
+    pub reason: CrashReason,
+
+    /// Program counter at the crash.
+    pub pc: u32,
+
+    /// Link register at the crash.
+    pub lr: u32,
+}
+
+/// Crashes with the same [`Signature`], counted by [`Groups`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Group {
+    /// The signature the crashes share.
+    pub signature: Signature,
+
+    /// Number of crashes with the signature.
+    pub count: u32,
+}
+
+/// Error returned by [`Groups::add`] when a crash has a new signature, but all of the groups are
+/// used. Includes the signature.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GroupsFull(pub Signature);
+
+/// Counts of identical crashes, for up to `N` different signatures, in the order they were first
+/// seen.
+pub struct Groups<const N: usize> {
+    groups: [Option<Group>; N],
+}
+
+impl<const N: usize> Groups<N> {
+    /// Returns an empty set of groups.
+    pub const fn new() -> Groups<N> {
+        Groups { groups: [None; N] }
+    }
+
+    /// Counts a crash in the group for its [signature](FaultData::signature), starting a new group
+    /// if needed.
+    ///
+    /// Returns the updated group.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GroupsFull`] if the crash needs a new group, but there are already `N`.
+    pub fn add(&mut self, fault_data: &FaultData) -> Result<Group, GroupsFull> {
+        let signature = fault_data.signature();
+        for slot in self.groups.iter_mut() {
+            match slot {
+                Some(group) if group.signature == signature => {
+                    group.count = group.count.saturating_add(1);
+                    return Ok(*group);
+                }
+                Some(_) => (),
+                None => {
+                    let group = Group {
+                        signature,
+                        count: 1,
+                    };
+                    *slot = Some(group);
+                    return Ok(group);
+                }
+            }
+        }
+
+        Err(GroupsFull(signature))
+    }
+
+    /// Returns the number of groups.
+    pub fn len(&self) -> usize {
+        self.iter().count()
+    }
+
+    /// Returns true if no crash has been added.
+    pub fn is_empty(&self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// Returns an iterator over the groups, in the order they were first seen.
+    pub fn iter(&self) -> impl Iterator<Item = &Group> {
+        self.groups.iter().filter_map(Option::as_ref)
+    }
+}
+
+impl<const N: usize> Default for Groups<N> {
+    fn default() -> Groups<N> {
+        Groups::new()
+    }
+}
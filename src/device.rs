@@ -0,0 +1,414 @@
+//! Shared SPI bus support, built on the embedded-hal 1.0 [`SpiDevice`] trait.
+//!
+//! [`BlueNRG::with_spi`] borrows the whole bus for the duration of its closure and drives the chip
+//! select pin itself. When the BlueNRG shares a bus with other devices, the bus manager usually
+//! owns chip select, and locks the bus for each transaction. [`BlueNRGDevice`] works that way: it
+//! owns an [`SpiDevice`] (or borrows one, since `&mut D` is also an `SpiDevice`), and implements
+//! [`bluetooth_hci::Controller`] directly, so commands can be sent and events read without a
+//! closure.
+//!
+//! The BlueNRG SPI protocol decides what to do after the SPI header in the same chip select
+//! frame, but an `SpiDevice` transaction is a fixed list of operations. So each write or read first
+//! sends a transaction with just the SPI header to learn how much space or data the controller
+//! has. It then sends the header again, followed by the data, in a second transaction, and checks
+//! the second header before accepting the result. The controller only ever gains write space or
+//! read data between the two transactions, so the second check only fails if the controller went
+//! back to sleep in between, in which case the operation is retried.
+
+extern crate embedded_hal_1 as ehal1;
+
+use crate::{
+    parse_spi_header, Access, ActiveBlueNRG, BlueNRG, BlueNRGTypes, Error, Firmware,
+    PACKET_TYPE_HCI_COMMAND,
+};
+use core::cmp::min;
+use core::marker::PhantomData;
+use ehal1::spi::{Operation, SpiDevice};
+
+/// Chip select "pin" for a [`BlueNRG`] on an [`SpiDevice`], which handles chip select itself.
+struct DeviceChipSelect<GpioError>(PhantomData<GpioError>);
+
+impl<GpioError> emhal::digital::v2::OutputPin for DeviceChipSelect<GpioError> {
+    type Error = GpioError;
+
+    fn set_low(&mut self) -> Result<(), GpioError> {
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), GpioError> {
+        Ok(())
+    }
+}
+
+/// Handle for communicating with the controller through an [`SpiDevice`] on a shared bus.
+///
+/// Unlike [`ActiveBlueNRG`], a `BlueNRGDevice` owns its bus handle, so it
+/// implements [`bluetooth_hci::Controller`] (and therefore all of the command traits) by itself.
+pub struct BlueNRGDevice<'buf, DEV, OutputPin2, InputPin, GpioError, RxBuffer = &'buf mut [u8]> {
+    /// Pins, RX buffer, and protocol state. The chip select pin is handled by the device.
+    d: BlueNRG<'buf, DEV, DeviceChipSelect<GpioError>, OutputPin2, InputPin, GpioError, RxBuffer>,
+
+    /// The SPI device, which locks the bus and drives chip select for each transaction.
+    device: DEV,
+}
+
+impl<'buf, DEV, OutputPin2, InputPin, GpioError>
+    BlueNRGDevice<'buf, DEV, OutputPin2, InputPin, GpioError>
+where
+    OutputPin2: emhal::digital::v2::OutputPin<Error = GpioError>,
+    InputPin: emhal::digital::v2::InputPin<Error = GpioError>,
+{
+    /// Returns a new `BlueNRGDevice` with the given RX buffer, SPI device, and pins. See
+    /// [`BlueNRG::new`].
+    pub fn new(
+        rx_buffer: &'buf mut [u8],
+        device: DEV,
+        dr: InputPin,
+        rst: OutputPin2,
+    ) -> BlueNRGDevice<'buf, DEV, OutputPin2, InputPin, GpioError> {
+        BlueNRGDevice {
+            d: BlueNRG::new(rx_buffer, DeviceChipSelect(PhantomData), dr, rst),
+            device,
+        }
+    }
+}
+
+impl<'buf, DEV, OutputPin2, InputPin, GpioError, const N: usize>
+    BlueNRGDevice<'buf, DEV, OutputPin2, InputPin, GpioError, [u8; N]>
+where
+    OutputPin2: emhal::digital::v2::OutputPin<Error = GpioError>,
+    InputPin: emhal::digital::v2::InputPin<Error = GpioError>,
+{
+    /// Returns a new `BlueNRGDevice` that owns the given RX buffer, with the given SPI device and
+    /// pins. See [`BlueNRG::new_owned`]; the buffer must be at least 259 bytes.
+    pub fn new_owned(
+        rx_buffer: [u8; N],
+        device: DEV,
+        dr: InputPin,
+        rst: OutputPin2,
+    ) -> BlueNRGDevice<'buf, DEV, OutputPin2, InputPin, GpioError, [u8; N]> {
+        BlueNRGDevice {
+            d: BlueNRG::new_owned(rx_buffer, DeviceChipSelect(PhantomData), dr, rst),
+            device,
+        }
+    }
+}
+
+impl<'buf, DEV, OutputPin2, InputPin, GpioError, RxBuffer>
+    BlueNRGDevice<'buf, DEV, OutputPin2, InputPin, GpioError, RxBuffer>
+where
+    OutputPin2: emhal::digital::v2::OutputPin<Error = GpioError>,
+    InputPin: emhal::digital::v2::InputPin<Error = GpioError>,
+    RxBuffer: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Returns the SPI device, consuming the `BlueNRGDevice`.
+    pub fn release(self) -> DEV {
+        self.device
+    }
+
+    /// Resets the controller. See [`BlueNRG::reset`].
+    pub fn reset<T, Time>(&mut self, timer: &mut T, freq: Time) -> nb::Result<(), GpioError>
+    where
+        T: emhal::timer::CountDown<Time = Time>,
+        Time: Copy,
+    {
+        self.d.reset(timer, freq)
+    }
+
+    /// Sets the maximum number of consecutive attempts to communicate with the controller that
+    /// may fail because it is not ready. See [`BlueNRG::set_retry_limit`].
+    pub fn set_retry_limit(&mut self, limit: Option<u32>) {
+        self.d.set_retry_limit(limit)
+    }
+
+    /// Returns the number of HCI command packets the controller can currently accept. See
+    /// [`BlueNRG::command_credits`].
+    pub fn command_credits(&self) -> u8 {
+        self.d.command_credits()
+    }
+
+    /// Returns the number of commands that have been written, but whose Command Complete or Command
+    /// Status event has not been read. See [`BlueNRG::outstanding_commands`].
+    pub fn outstanding_commands(&self) -> u8 {
+        self.d.outstanding_commands()
+    }
+
+    /// Returns the total number of bytes from the controller that were dropped because they did not
+    /// fit in the RX buffer. See [`BlueNRG::dropped_bytes`].
+    pub fn dropped_bytes(&self) -> u32 {
+        self.d.dropped_bytes()
+    }
+
+    /// Returns the HCI dialect spoken by the controller. See [`BlueNRG::dialect`].
+    pub fn dialect(&self) -> crate::dialect::Dialect {
+        self.d.dialect()
+    }
+
+    /// Sets the HCI dialect spoken by the controller. See [`BlueNRG::set_dialect`].
+    pub fn set_dialect(&mut self, dialect: crate::dialect::Dialect) {
+        self.d.set_dialect(dialect)
+    }
+
+    /// Returns the version reported by the controller, if it has been recorded. See
+    /// [`BlueNRG::version`].
+    pub fn version(&self) -> Option<crate::Version> {
+        self.d.version()
+    }
+
+    /// Records the version reported by the controller. See [`BlueNRG::set_version`].
+    pub fn set_version(&mut self, version: crate::Version) {
+        self.d.set_version(version)
+    }
+}
+
+impl<'buf, DEV, OutputPin2, InputPin, GpioError, RxBuffer>
+    BlueNRGDevice<'buf, DEV, OutputPin2, InputPin, GpioError, RxBuffer>
+{
+    /// Returns an [`ActiveBlueNRG`] that talks to the controller through the SPI device, so the
+    /// device shares the retry, RX buffer, and command credit handling of the other handles.
+    fn active(
+        &mut self,
+    ) -> ActiveBlueNRG<
+        '_,
+        '_,
+        'buf,
+        DEV,
+        DeviceChipSelect<GpioError>,
+        OutputPin2,
+        InputPin,
+        GpioError,
+        RxBuffer,
+    > {
+        ActiveBlueNRG {
+            d: &mut self.d,
+            spi: &mut self.device,
+            deadline: None,
+            deadline_running: false,
+            capture: None,
+        }
+    }
+}
+
+impl<'bnrg, 'spi, 'dbuf, DEV, OutputPin2, InputPin, GpioError, RxBuffer>
+    ActiveBlueNRG<
+        'bnrg,
+        'spi,
+        'dbuf,
+        DEV,
+        DeviceChipSelect<GpioError>,
+        OutputPin2,
+        InputPin,
+        GpioError,
+        RxBuffer,
+    >
+where
+    DEV: SpiDevice<u8>,
+    OutputPin2: emhal::digital::v2::OutputPin<Error = GpioError>,
+    InputPin: emhal::digital::v2::InputPin<Error = GpioError>,
+    RxBuffer: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Sends SPI headers, each in its own transaction, until the controller reports that it is
+    /// ready. Returns the number of bytes that can be written to the chip, and the number of bytes
+    /// that should be read from the chip.
+    fn block_until_device_ready(
+        &mut self,
+        access: &Access,
+    ) -> nb::Result<(u16, u16), Error<DEV::Error, GpioError>> {
+        loop {
+            let mut header = [access.byte(), 0x00, 0x00, 0x00, 0x00];
+            self.spi
+                .transfer_in_place(&mut header)
+                .map_err(Error::Spi)
+                .map_err(nb::Error::Other)?;
+
+            match parse_spi_header(&header) {
+                Ok(lengths) => return Ok(lengths),
+                Err(nb::Error::WouldBlock) => self.count_retry().map_err(nb::Error::Other)?,
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn write_device_when_ready(
+        &mut self,
+        header: &[u8],
+        payload: &[u8],
+    ) -> nb::Result<(), Error<DEV::Error, GpioError>> {
+        let len = header.len() + payload.len();
+        let (write_len, _) = self.block_until_device_ready(&Access::Write)?;
+        if (write_len as usize) < len {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        let mut spi_header = [Access::Write.byte(), 0x00, 0x00, 0x00, 0x00];
+        self.spi
+            .transaction(&mut [
+                Operation::TransferInPlace(&mut spi_header),
+                Operation::Write(header),
+                Operation::Write(payload),
+            ])
+            .map_err(Error::Spi)
+            .map_err(nb::Error::Other)?;
+        match parse_spi_header::<()>(&spi_header) {
+            Ok((write_len, _)) if write_len as usize >= len => Ok(()),
+            _ => Err(nb::Error::WouldBlock),
+        }
+    }
+
+    /// Implements [`hci::Controller::write`] for [`BlueNRGDevice`].
+    fn write_device(
+        &mut self,
+        header: &[u8],
+        payload: &[u8],
+    ) -> nb::Result<(), Error<DEV::Error, GpioError>> {
+        let is_command = header.first() == Some(&PACKET_TYPE_HCI_COMMAND);
+        if is_command && self.d.command_credits == 0 {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        match self.write_device_when_ready(header, payload) {
+            Ok(()) => self.reset_retries(),
+            Err(nb::Error::WouldBlock) => {
+                // Not enough write space counts against the same retry budget as not being
+                // awake.
+                self.count_retry().map_err(nb::Error::Other)?;
+                return Err(nb::Error::WouldBlock);
+            }
+            Err(e) => return Err(e),
+        }
+        if is_command {
+            self.d.command_credits -= 1;
+            self.d.outstanding_commands = self.d.outstanding_commands.saturating_add(1);
+        }
+
+        Ok(())
+    }
+
+    /// Reads the data the controller has available into the RX buffer, one contiguous chunk per
+    /// transaction, until either there is no more data or the RX buffer is full. Used as the
+    /// `fill` function of the shared read path.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`Error::RxOverflow`] if the RX buffer filled up before all of the available data
+    ///   was read. The rest of the data is read and thrown away.
+    ///
+    /// - Returns a communication error if there is an error communicating over the SPI bus.
+    fn read_device_data(&mut self) -> nb::Result<(), Error<DEV::Error, GpioError>> {
+        let (_, read_len) = self.block_until_device_ready(&Access::Read)?;
+        let mut bytes_available = read_len as usize;
+        while bytes_available > 0 && self.d.rx_buffer.next_contiguous_slice_len() > 0 {
+            let transfer_count = min(
+                bytes_available,
+                self.d.rx_buffer.next_contiguous_slice_len(),
+            );
+            let size = self.d.rx_buffer.size();
+            let started = self.d.rx_time();
+            let rx = self.d.rx_buffer.next_mut_slice(transfer_count);
+            if read_chunk(self.spi, rx)
+                .map_err(Error::Spi)
+                .map_err(nb::Error::Other)?
+            {
+                self.d.record_rx(transfer_count, started);
+                bytes_available -= transfer_count;
+            } else {
+                self.d.rx_buffer.truncate(size);
+                self.count_retry().map_err(nb::Error::Other)?;
+            }
+        }
+
+        self.reset_retries();
+        if bytes_available > 0 {
+            self.discard_device_data(bytes_available)?;
+            self.d.drop_rx_overflow(bytes_available);
+            return Err(nb::Error::Other(Error::RxOverflow));
+        }
+
+        Ok(())
+    }
+
+    /// Reads and throws away `len` bytes from the controller.
+    fn discard_device_data(
+        &mut self,
+        mut len: usize,
+    ) -> nb::Result<(), Error<DEV::Error, GpioError>> {
+        let mut scratch = [0; 16];
+        while len > 0 {
+            let transfer_count = min(len, scratch.len());
+            if read_chunk(self.spi, &mut scratch[..transfer_count])
+                .map_err(Error::Spi)
+                .map_err(nb::Error::Other)?
+            {
+                len -= transfer_count;
+            } else {
+                self.count_retry().map_err(nb::Error::Other)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Reads `rx.len()` bytes from the controller in a single transaction. Returns false if the SPI
+/// header shows that the controller did not have that much data ready, in which case the contents
+/// of `rx` are not valid.
+fn read_chunk<DEV>(device: &mut DEV, rx: &mut [u8]) -> Result<bool, DEV::Error>
+where
+    DEV: SpiDevice<u8>,
+{
+    for byte in rx.iter_mut() {
+        *byte = 0;
+    }
+    let len = rx.len();
+    let mut header = [Access::Read.byte(), 0x00, 0x00, 0x00, 0x00];
+    device.transaction(&mut [
+        Operation::TransferInPlace(&mut header),
+        Operation::TransferInPlace(rx),
+    ])?;
+
+    Ok(matches!(
+        parse_spi_header::<()>(&header),
+        Ok((_, read_len)) if read_len as usize >= len
+    ))
+}
+
+impl<'buf, DEV, OutputPin2, InputPin, GpioError, RxBuffer> hci::Controller
+    for BlueNRGDevice<'buf, DEV, OutputPin2, InputPin, GpioError, RxBuffer>
+where
+    DEV: SpiDevice<u8>,
+    OutputPin2: emhal::digital::v2::OutputPin<Error = GpioError>,
+    InputPin: emhal::digital::v2::InputPin<Error = GpioError>,
+    RxBuffer: AsRef<[u8]> + AsMut<[u8]>,
+{
+    type Error = Error<DEV::Error, GpioError>;
+    type Header = hci::host::uart::CommandHeader;
+    type Vendor = BlueNRGTypes;
+
+    fn write(&mut self, header: &[u8], payload: &[u8]) -> nb::Result<(), Self::Error> {
+        self.active().write_device(header, payload)
+    }
+
+    fn read_into(&mut self, buffer: &mut [u8]) -> nb::Result<(), Self::Error> {
+        self.active().read_into_with(buffer, |active| {
+            active.fill_rx_buffer(|active| active.read_device_data())
+        })
+    }
+
+    fn peek(&mut self, n: usize) -> nb::Result<u8, Self::Error> {
+        self.active().peek_with(n, |active| {
+            active.fill_rx_buffer(|active| active.read_device_data())
+        })
+    }
+}
+
+impl<'buf, DEV, OutputPin2, InputPin, GpioError, RxBuffer> Firmware
+    for BlueNRGDevice<'buf, DEV, OutputPin2, InputPin, GpioError, RxBuffer>
+{
+    fn version(&self) -> Option<crate::Version> {
+        self.d.version
+    }
+
+    fn dialect(&self) -> crate::dialect::Dialect {
+        self.d.dialect
+    }
+}
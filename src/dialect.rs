@@ -0,0 +1,65 @@
+//! Run-time selection of the HCI dialect.
+//!
+//! The original BlueNRG firmware (version 6) and the BlueNRG-MS firmware (version 7) encode a few
+//! vendor-specific commands and events differently. The `ms` feature only selects the default
+//! [`Dialect`]; both encodings are always built in, so one application image can talk to either
+//! radio. After reading the [`Version`] from the controller, the application (or
+//! [`BlueNRG::bring_up`](crate::BlueNRG::bring_up), which does this itself) picks the dialect with
+//! [`BlueNRG::set_dialect`](crate::BlueNRG::set_dialect).
+//!
+//! The dialect changes the encoding of [`gap::Commands::init`](crate::gap::Commands::init),
+//! [`set_nonconnectable`](crate::gap::Commands::set_nonconnectable),
+//! [`allow_rebond`](crate::gap::Commands::allow_rebond) and the parameters of a few other GAP
+//! commands, and the parsing of vendor events and return parameters. Arguments that the original
+//! BlueNRG cannot encode are rejected with
+//! [`gap::Error::UnsupportedInDialect`](crate::gap::Error::UnsupportedInDialect), and commands that
+//! only exist on the BlueNRG-MS fail with an `Unsupported` error, as for firmware that is too old
+//! (see the [`capability`](crate::capability) module).
+//!
+//! The command traits read the dialect from the controller's [`Firmware`](crate::Firmware)
+//! implementation, and [`event::read`](crate::event::read) parses events in it. The parsers that
+//! are not given a dialect, like [`VendorEvent::new`](hci::event::VendorEvent::new) (used by
+//! [`hci::host::uart::Hci::read`]), use the [default](Dialect::default) dialect.
+
+use crate::Version;
+
+/// Version of the HCI spoken by the controller.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Dialect {
+    /// The original BlueNRG, with firmware version 6.
+    BlueNRG,
+
+    /// The BlueNRG-MS, with firmware version 7.
+    BlueNRGMS,
+}
+
+impl Dialect {
+    /// Returns the dialect spoken by a controller with the given version.
+    ///
+    /// The BlueNRG-1 and BlueNRG-2 stacks have their own version numbers, but always speak the
+    /// BlueNRG-MS dialect, so with the `bluenrg2` feature this always returns
+    /// [`BlueNRGMS`](Dialect::BlueNRGMS).
+    pub fn from_version(version: &Version) -> Dialect {
+        if cfg!(feature = "bluenrg2") || version.major >= 7 {
+            Dialect::BlueNRGMS
+        } else {
+            Dialect::BlueNRG
+        }
+    }
+
+    /// Returns true for the BlueNRG-MS dialect.
+    pub fn is_ms(self) -> bool {
+        self == Dialect::BlueNRGMS
+    }
+}
+
+impl Default for Dialect {
+    /// Returns the dialect selected by the `ms` feature.
+    fn default() -> Dialect {
+        if cfg!(feature = "ms") {
+            Dialect::BlueNRGMS
+        } else {
+            Dialect::BlueNRG
+        }
+    }
+}
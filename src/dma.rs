@@ -0,0 +1,367 @@
+//! DMA transfer path for reading from the controller.
+//!
+//! [`BlueNRG::with_spi`] reads into the RX buffer one contiguous slice at a time, zeroing each
+//! slice first so the SPI peripheral has something to send, and the CPU waits for every byte. When
+//! the RX buffer wraps around, that takes two transfers. With a DMA-capable SPI peripheral,
+//! [`BlueNRG::with_spi_dma`] instead starts a single [`DmaRead`] transfer into both halves of a
+//! wrapped RX buffer, leaves the dummy bytes to the peripheral, and returns `WouldBlock` until the
+//! transfer completes, so the CPU is free in the meantime. The chip select line stays low from the
+//! start of the transfer until the host sees it complete.
+//!
+//! Writes still go through the blocking [`Write`](emhal::blocking::spi::Write) trait, since the
+//! packets are short. A write waits for a read in progress to complete first.
+//!
+//! [`BlueNRG::rx_stats`] counts the bytes, transfers and time used by either path, so the two can
+//! be compared.
+
+use crate::{hci, Access, ActiveBlueNRG, BlueNRG, BlueNRGTypes, Error, Firmware};
+use core::cmp::min;
+use core::time::Duration;
+use embedded_dma::WriteBuffer;
+
+/// An SPI read done with DMA, into two buffers in a single transfer.
+///
+/// The implementation must clock out dummy bytes (the controller ignores them) while it fills
+/// `first` and then `second`, without releasing the bus in between. At most one transfer is in
+/// progress at a time.
+pub trait DmaRead {
+    /// Error type returned by the SPI peripheral.
+    type Error;
+
+    /// Starts reading `first.len() + second.len()` bytes from the bus, into `first` and then
+    /// `second`, and returns without waiting for the transfer to complete. `second` may be empty.
+    ///
+    /// The buffers remain valid until [`poll_read`](DmaRead::poll_read) returns something other
+    /// than `WouldBlock`.
+    fn start_read<B>(&mut self, first: B, second: B) -> Result<(), Self::Error>
+    where
+        B: WriteBuffer<Word = u8>;
+
+    /// Returns `WouldBlock` while the transfer started by [`start_read`](DmaRead::start_read) is in
+    /// progress, and `Ok` once it has completed.
+    fn poll_read(&mut self) -> nb::Result<(), Self::Error>;
+}
+
+/// Part of the free space of the RX buffer, given to [`DmaRead::start_read`].
+struct RxRegion {
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl RxRegion {
+    fn new(slice: &mut [u8]) -> RxRegion {
+        RxRegion {
+            ptr: slice.as_mut_ptr(),
+            len: slice.len(),
+        }
+    }
+}
+
+// Safety: the region is in the RX buffer of the BlueNRG that the DmaActiveBlueNRG borrows, and is
+// not part of the buffered data until the transfer completes. BlueNRG::with_spi_dma waits for the
+// transfer to complete before it gives up the borrow, so the region stays valid for the whole
+// transfer.
+unsafe impl WriteBuffer for RxRegion {
+    type Word = u8;
+
+    unsafe fn write_buffer(&mut self) -> (*mut u8, usize) {
+        (self.ptr, self.len)
+    }
+}
+
+/// A DMA read from the controller, started but not yet seen to complete.
+struct Transfer {
+    /// Number of bytes read into the RX buffer.
+    len: usize,
+
+    /// Number of bytes the controller had ready that did not fit in the RX buffer.
+    unread: usize,
+
+    /// Time the transfer started, if there is an RX clock.
+    started: Option<Duration>,
+}
+
+/// Handle for actively communicating with the controller over the SPI bus, reading with DMA.
+///
+/// A `DmaActiveBlueNRG` should not be created by the application, but is passed to closures given
+/// to [`BlueNRG::with_spi_dma`]. Like [`ActiveBlueNRG`], it implements
+/// [`bluetooth_hci::Controller`], so it is used to access the HCI functions for the controller.
+pub struct DmaActiveBlueNRG<
+    'bnrg,
+    'spi,
+    'dbuf,
+    SPI,
+    OutputPin1,
+    OutputPin2,
+    InputPin,
+    GpioError,
+    RxBuffer = &'dbuf mut [u8],
+> {
+    active: ActiveBlueNRG<
+        'bnrg,
+        'spi,
+        'dbuf,
+        SPI,
+        OutputPin1,
+        OutputPin2,
+        InputPin,
+        GpioError,
+        RxBuffer,
+    >,
+
+    /// Read in progress, if any.
+    transfer: Option<Transfer>,
+}
+
+impl<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, SpiError, GpioError, RxBuffer>
+    ActiveBlueNRG<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer>
+where
+    SPI: emhal::blocking::spi::Transfer<u8, Error = SpiError>
+        + emhal::blocking::spi::Write<u8, Error = SpiError>
+        + DmaRead<Error = SpiError>,
+    OutputPin1: emhal::digital::v2::OutputPin<Error = GpioError>,
+    OutputPin2: emhal::digital::v2::OutputPin<Error = GpioError>,
+    InputPin: emhal::digital::v2::InputPin<Error = GpioError>,
+    RxBuffer: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Like `fill_rx_buffer` with `read_available_data`, but reads all of the data that fits in the
+    /// RX buffer in a single DMA transfer, even if the free space wraps around the end of the
+    /// buffer.
+    ///
+    /// If no read is in progress and the controller has data ready, lowers the chip select line
+    /// and starts a read. Then polls the read in progress, as
+    /// [`poll_dma_read`](ActiveBlueNRG::poll_dma_read) does.
+    fn fill_rx_buffer_dma(
+        &mut self,
+        transfer: &mut Option<Transfer>,
+    ) -> nb::Result<(), Error<SpiError, GpioError>> {
+        if transfer.is_none() {
+            if !self
+                .d
+                .data_ready()
+                .map_err(Error::Gpio)
+                .map_err(nb::Error::Other)?
+            {
+                return Err(nb::Error::WouldBlock);
+            }
+
+            self.d
+                .chip_select
+                .set_low()
+                .map_err(Error::Gpio)
+                .map_err(nb::Error::Other)?;
+            match self.start_dma_read() {
+                Ok(started) => *transfer = Some(started),
+                Err(e) => return self.release_chip_select(Err(e)),
+            }
+        }
+
+        self.poll_dma_read(transfer)
+    }
+
+    /// Reads the SPI header and starts reading the data the controller has ready into the free
+    /// space of the RX buffer. The chip select line must be low.
+    fn start_dma_read(&mut self) -> nb::Result<Transfer, Error<SpiError, GpioError>> {
+        let read_len = self.block_until_ready_for(Access::Read)? as usize;
+        let len = min(read_len, self.d.rx_buffer.available_len());
+        let started = self.d.rx_time();
+        if len > 0 {
+            let (first, second) = self.d.rx_buffer.free_mut_slices(len);
+            self.spi
+                .start_read(RxRegion::new(first), RxRegion::new(second))
+                .map_err(Error::Spi)
+                .map_err(nb::Error::Other)?;
+        }
+
+        Ok(Transfer {
+            len,
+            unread: read_len - len,
+            started,
+        })
+    }
+
+    /// Checks whether the read in progress, if any, has completed.
+    ///
+    /// Returns `WouldBlock` while it is in progress. Once it has completed, adds the data to the RX
+    /// buffer, throws away the data that did not fit, and raises the chip select line.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`Error::RxOverflow`] if some of the data did not fit in the RX buffer.
+    ///
+    /// - Returns a communication error if there is an error communicating over the SPI bus. The
+    ///   data of the failed read is thrown away.
+    fn poll_dma_read(
+        &mut self,
+        transfer: &mut Option<Transfer>,
+    ) -> nb::Result<(), Error<SpiError, GpioError>> {
+        let (len, started) = match *transfer {
+            Some(ref transfer) => (transfer.len, transfer.started),
+            None => return Ok(()),
+        };
+        if len > 0 {
+            match self.spi.poll_read() {
+                Ok(()) => {
+                    self.d.rx_buffer.commit(len);
+                    self.d.record_rx(len, started);
+                }
+                Err(nb::Error::WouldBlock) => return Err(nb::Error::WouldBlock),
+                Err(nb::Error::Other(e)) => {
+                    *transfer = None;
+                    return self.release_chip_select(Err(nb::Error::Other(Error::Spi(e))));
+                }
+            }
+        }
+
+        let unread = transfer.take().map_or(0, |transfer| transfer.unread);
+        let result = self.finish_read(unread);
+        self.release_chip_select(result)
+    }
+
+    /// Raises the chip select line at the end of a read, and returns the result of the read. A
+    /// failed read is counted in [`RxStats::failed_reads`](crate::RxStats::failed_reads).
+    fn release_chip_select(
+        &mut self,
+        result: nb::Result<(), Error<SpiError, GpioError>>,
+    ) -> nb::Result<(), Error<SpiError, GpioError>> {
+        let result = self
+            .d
+            .chip_select
+            .set_high()
+            .map_err(Error::Gpio)
+            .map_err(nb::Error::Other)
+            .and(result);
+        if let Err(nb::Error::Other(ref e)) = result {
+            self.d.rx_stats.record_error(e);
+        }
+
+        result
+    }
+}
+
+impl<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, SpiError, GpioError, RxBuffer>
+    hci::Controller
+    for DmaActiveBlueNRG<
+        'bnrg,
+        'spi,
+        'dbuf,
+        SPI,
+        OutputPin1,
+        OutputPin2,
+        InputPin,
+        GpioError,
+        RxBuffer,
+    >
+where
+    SPI: emhal::blocking::spi::Transfer<u8, Error = SpiError>
+        + emhal::blocking::spi::Write<u8, Error = SpiError>
+        + DmaRead<Error = SpiError>,
+    OutputPin1: emhal::digital::v2::OutputPin<Error = GpioError>,
+    OutputPin2: emhal::digital::v2::OutputPin<Error = GpioError>,
+    InputPin: emhal::digital::v2::InputPin<Error = GpioError>,
+    RxBuffer: AsRef<[u8]> + AsMut<[u8]>,
+{
+    type Error = Error<SpiError, GpioError>;
+    type Header = hci::host::uart::CommandHeader;
+    type Vendor = BlueNRGTypes;
+
+    fn write(&mut self, header: &[u8], payload: &[u8]) -> nb::Result<(), Self::Error> {
+        // The bytes that did not fit are still counted in dropped_bytes, and the write is not the
+        // place to report them.
+        match self.active.poll_dma_read(&mut self.transfer) {
+            Ok(()) | Err(nb::Error::Other(Error::RxOverflow)) => (),
+            Err(e) => return Err(e),
+        }
+
+        self.active.write(header, payload)
+    }
+
+    fn read_into(&mut self, buffer: &mut [u8]) -> nb::Result<(), Self::Error> {
+        let transfer = &mut self.transfer;
+        self.active
+            .read_into_with(buffer, |active| active.fill_rx_buffer_dma(transfer))
+    }
+
+    fn peek(&mut self, n: usize) -> nb::Result<u8, Self::Error> {
+        let transfer = &mut self.transfer;
+        self.active
+            .peek_with(n, |active| active.fill_rx_buffer_dma(transfer))
+    }
+}
+
+impl<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer> Firmware
+    for DmaActiveBlueNRG<
+        'bnrg,
+        'spi,
+        'dbuf,
+        SPI,
+        OutputPin1,
+        OutputPin2,
+        InputPin,
+        GpioError,
+        RxBuffer,
+    >
+{
+    fn version(&self) -> Option<crate::Version> {
+        self.active.version()
+    }
+
+    fn dialect(&self) -> crate::dialect::Dialect {
+        self.active.dialect()
+    }
+}
+
+impl<'buf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer>
+    BlueNRG<'buf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer>
+where
+    OutputPin1: emhal::digital::v2::OutputPin<Error = GpioError>,
+    OutputPin2: emhal::digital::v2::OutputPin<Error = GpioError>,
+    InputPin: emhal::digital::v2::InputPin<Error = GpioError>,
+    RxBuffer: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Invokes the given body function with a [`DmaActiveBlueNRG`] that uses this BlueNRG struct
+    /// and the provided SPI bus handle, like [`with_spi`](BlueNRG::with_spi), but reads from the
+    /// controller with [`DmaRead`].
+    ///
+    /// If a read is still in progress when the body returns, this waits for it to complete, since
+    /// it writes into the RX buffer. Its data stays in the RX buffer for the next call. If it
+    /// fails, there is no caller left to return the error to, so it is counted in
+    /// [`RxStats::failed_reads`](crate::RxStats::failed_reads) (and overflowing data in
+    /// [`dropped_bytes`](BlueNRG::dropped_bytes)) instead.
+    ///
+    /// Returns the result of the invoked body.
+    pub fn with_spi_dma<T, F, E>(&mut self, spi: &mut SPI, body: F) -> T
+    where
+        F: FnOnce(
+            &mut DmaActiveBlueNRG<
+                '_,
+                '_,
+                'buf,
+                SPI,
+                OutputPin1,
+                OutputPin2,
+                InputPin,
+                GpioError,
+                RxBuffer,
+            >,
+        ) -> T,
+        SPI: emhal::blocking::spi::transfer::Default<u8, Error = E>
+            + emhal::blocking::spi::write::Default<u8, Error = E>
+            + DmaRead<Error = E>,
+    {
+        let mut active = DmaActiveBlueNRG {
+            active: ActiveBlueNRG {
+                spi,
+                d: self,
+                deadline: None,
+                deadline_running: false,
+                capture: None,
+            },
+            transfer: None,
+        };
+        let result = body(&mut active);
+        while let Err(nb::Error::WouldBlock) = active.active.poll_dma_read(&mut active.transfer) {}
+
+        result
+    }
+}
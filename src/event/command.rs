@@ -6,6 +6,7 @@
 
 extern crate bluetooth_hci as hci;
 
+use crate::dialect::Dialect;
 use byteorder::{ByteOrder, LittleEndian};
 use core::convert::{TryFrom, TryInto};
 use core::fmt::{Debug, Formatter, Result as FmtResult};
@@ -15,7 +16,7 @@ use core::time::Duration;
 /// Complete](hci::event::command::ReturnParameters::Vendor) event. If the commands have defined
 /// return parameters, they are included in the enum.
 #[allow(clippy::large_enum_variant)]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub enum ReturnParameters {
     /// Parameters returned by the [HAL Get Firmware
     /// Revision](crate::hal::Commands::get_firmware_revision) command.
@@ -127,12 +128,6 @@ pub enum ReturnParameters {
     /// Procedure](crate::gap::Commands::terminate_procedure) command.
     GapTerminateProcedure(hci::Status<crate::event::Status>),
 
-    #[cfg(not(feature = "ms"))]
-    /// Parameters returned by the [GAP Resolve Private
-    /// Address](crate::gap::Commands::resolve_private_address) command.
-    GapResolvePrivateAddress(hci::Status<crate::event::Status>),
-
-    #[cfg(feature = "ms")]
     /// Parameters returned by the [GAP Resolve Private
     /// Address](crate::gap::Commands::resolve_private_address) command.
     GapResolvePrivateAddress(GapResolvePrivateAddress),
@@ -141,12 +136,10 @@ pub enum ReturnParameters {
     /// Devices](crate::gap::Commands::get_bonded_devices) command.
     GapGetBondedDevices(GapBondedDevices),
 
-    #[cfg(feature = "ms")]
     /// Parameters returned by the [GAP Set Broadcast
     /// Mode](crate::gap::Commands::set_broadcast_mode) command.
     GapSetBroadcastMode(hci::Status<crate::event::Status>),
 
-    #[cfg(feature = "ms")]
     /// Parameters returned by the [GAP Start Observation
     /// Procedure](crate::gap::Commands::start_observation_procedure) command.
     GapStartObservationProcedure(hci::Status<crate::event::Status>),
@@ -155,6 +148,21 @@ pub enum ReturnParameters {
     /// command.
     GapIsDeviceBonded(hci::Status<crate::event::Status>),
 
+    #[cfg(feature = "bluenrg2")]
+    /// Parameters returned by the [GAP Numeric Comparison Value
+    /// Confirm](crate::gap::Commands::numeric_comparison_value_confirm) command.
+    GapNumericComparisonValueConfirm(hci::Status<crate::event::Status>),
+
+    #[cfg(feature = "bluenrg2")]
+    /// Parameters returned by the [GAP Pass Key Input](crate::gap::Commands::pass_key_input)
+    /// command.
+    GapPassKeyInput(hci::Status<crate::event::Status>),
+
+    #[cfg(feature = "bluenrg2")]
+    /// Parameters returned by the [GAP Remove Bonded
+    /// Device](crate::gap::Commands::remove_bonded_device) command.
+    GapRemoveBondedDevice(hci::Status<crate::event::Status>),
+
     /// Parameters returned by the [GATT Init](crate::gatt::Commands::init) command.
     GattInit(hci::Status<crate::event::Status>),
 
@@ -226,23 +234,80 @@ pub enum ReturnParameters {
 
     /// Parameters returned by the [GATT Read Handle
     /// Value](crate::gatt::Commands::read_handle_value_offset) command.
-    #[cfg(feature = "ms")]
     GattReadHandleValueOffset(GattHandleValue),
 
     /// Parameters returned by the [GATT Update Long Characteristic
     /// Value](crate::gatt::Commands::update_long_characteristic_value) command.
-    #[cfg(feature = "ms")]
     GattUpdateLongCharacteristicValue(hci::Status<crate::event::Status>),
 
     /// Status returned by the [L2CAP Connection Parameter Update
     /// Response](crate::l2cap::Commands::connection_parameter_update_response) command.
     L2CapConnectionParameterUpdateResponse(hci::Status<crate::event::Status>),
+
+    /// Status returned by the [Updater Start](crate::updater::Commands::start) command.
+    UpdaterStart(hci::Status<crate::event::Status>),
+
+    /// Status returned by the [Updater Reboot](crate::updater::Commands::reboot) command.
+    UpdaterReboot(hci::Status<crate::event::Status>),
+
+    /// Parameters returned by the [Updater Get Version](crate::updater::Commands::get_version)
+    /// command.
+    UpdaterGetVersion(UpdaterVersion),
+
+    /// Parameters returned by the [Updater Get Buffer
+    /// Size](crate::updater::Commands::get_buffer_size) command.
+    UpdaterGetBufferSize(UpdaterBufferSize),
+
+    /// Status returned by the [Updater Erase Blue
+    /// Flag](crate::updater::Commands::erase_blue_flag) command.
+    UpdaterEraseBlueFlag(hci::Status<crate::event::Status>),
+
+    /// Status returned by the [Updater Reset Blue
+    /// Flag](crate::updater::Commands::reset_blue_flag) command.
+    UpdaterResetBlueFlag(hci::Status<crate::event::Status>),
+
+    /// Status returned by the [Updater Erase Sector](crate::updater::Commands::erase_sector)
+    /// command.
+    UpdaterEraseSector(hci::Status<crate::event::Status>),
+
+    /// Status returned by the [Updater Program Data
+    /// Block](crate::updater::Commands::program_data_block) command.
+    UpdaterProgramDataBlock(hci::Status<crate::event::Status>),
+
+    /// Parameters returned by the [Updater Read Data
+    /// Block](crate::updater::Commands::read_data_block) command.
+    UpdaterReadDataBlock(UpdaterData),
+
+    /// Parameters returned by the [Updater Calc CRC](crate::updater::Commands::calc_crc) command.
+    UpdaterCalcCrc(UpdaterCrc),
+
+    /// Parameters returned by the [Updater HW Version](crate::updater::Commands::hw_version)
+    /// command.
+    UpdaterHardwareVersion(UpdaterVersion),
 }
 
 impl hci::event::VendorReturnParameters for ReturnParameters {
     type Error = super::BlueNRGError;
 
     fn new(bytes: &[u8]) -> Result<Self, hci::event::Error<Self::Error>> {
+        ReturnParameters::with_dialect(Dialect::default(), bytes)
+    }
+}
+
+impl ReturnParameters {
+    /// Deserializes the return parameters of a vendor-specific command in the given [`Dialect`].
+    /// [`VendorReturnParameters::new`] uses the [default](Dialect::default) dialect;
+    /// [`read`](crate::event::read) uses the controller's.
+    ///
+    /// [`VendorReturnParameters::new`]: hci::event::VendorReturnParameters::new
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`VendorReturnParameters::new`].
+    pub fn with_dialect(
+        dialect: Dialect,
+        bytes: &[u8],
+    ) -> Result<ReturnParameters, hci::event::Error<super::BlueNRGError>> {
         check_len_at_least(bytes, 3)?;
 
         match hci::Opcode(LittleEndian::read_u16(&bytes[1..])) {
@@ -334,48 +399,39 @@ impl hci::event::VendorReturnParameters for ReturnParameters {
                 to_status(&bytes[3..])?,
             )),
             crate::opcode::GAP_RESOLVE_PRIVATE_ADDRESS => {
-                #[cfg(not(feature = "ms"))]
-                {
-                    Ok(ReturnParameters::GapResolvePrivateAddress(to_status(
-                        &bytes[3..],
-                    )?))
-                }
-
-                #[cfg(feature = "ms")]
-                {
+                if dialect.is_ms() {
                     Ok(ReturnParameters::GapResolvePrivateAddress(
                         to_gap_resolve_private_address(&bytes[3..])?,
                     ))
+                } else {
+                    Ok(ReturnParameters::GapResolvePrivateAddress(
+                        GapResolvePrivateAddress {
+                            status: to_status(&bytes[3..])?,
+                            bd_addr: None,
+                        },
+                    ))
                 }
             }
             crate::opcode::GAP_GET_BONDED_DEVICES => Ok(ReturnParameters::GapGetBondedDevices(
                 to_gap_bonded_devices(&bytes[3..])?,
             )),
             crate::opcode::GAP_SET_BROADCAST_MODE => {
-                #[cfg(feature = "ms")]
-                {
+                if dialect.is_ms() {
                     Ok(ReturnParameters::GapSetBroadcastMode(to_status(
                         &bytes[3..],
                     )?))
-                }
-
-                #[cfg(not(feature = "ms"))]
-                {
+                } else {
                     Err(hci::event::Error::UnknownOpcode(
                         crate::opcode::GAP_SET_BROADCAST_MODE,
                     ))
                 }
             }
             crate::opcode::GAP_START_OBSERVATION_PROCEDURE => {
-                #[cfg(feature = "ms")]
-                {
+                if dialect.is_ms() {
                     Ok(ReturnParameters::GapStartObservationProcedure(to_status(
                         &bytes[3..],
                     )?))
-                }
-
-                #[cfg(not(feature = "ms"))]
-                {
+                } else {
                     Err(hci::event::Error::UnknownOpcode(
                         crate::opcode::GAP_START_OBSERVATION_PROCEDURE,
                     ))
@@ -384,6 +440,18 @@ impl hci::event::VendorReturnParameters for ReturnParameters {
             crate::opcode::GAP_IS_DEVICE_BONDED => {
                 Ok(ReturnParameters::GapIsDeviceBonded(to_status(&bytes[3..])?))
             }
+            #[cfg(feature = "bluenrg2")]
+            crate::opcode::GAP_NUMERIC_COMPARISON_VALUE_CONFIRM => Ok(
+                ReturnParameters::GapNumericComparisonValueConfirm(to_status(&bytes[3..])?),
+            ),
+            #[cfg(feature = "bluenrg2")]
+            crate::opcode::GAP_PASS_KEY_INPUT => {
+                Ok(ReturnParameters::GapPassKeyInput(to_status(&bytes[3..])?))
+            }
+            #[cfg(feature = "bluenrg2")]
+            crate::opcode::GAP_REMOVE_BONDED_DEVICE => Ok(ReturnParameters::GapRemoveBondedDevice(
+                to_status(&bytes[3..])?,
+            )),
             crate::opcode::GATT_INIT => Ok(ReturnParameters::GattInit(to_status(&bytes[3..])?)),
             crate::opcode::GATT_ADD_SERVICE => Ok(ReturnParameters::GattAddService(
                 to_gatt_service(&bytes[3..])?,
@@ -439,30 +507,22 @@ impl hci::event::VendorReturnParameters for ReturnParameters {
                 to_gatt_handle_value(&bytes[3..])?,
             )),
             crate::opcode::GATT_READ_HANDLE_VALUE_OFFSET => {
-                #[cfg(feature = "ms")]
-                {
+                if dialect.is_ms() {
                     Ok(ReturnParameters::GattReadHandleValueOffset(
                         to_gatt_handle_value(&bytes[3..])?,
                     ))
-                }
-
-                #[cfg(not(feature = "ms"))]
-                {
+                } else {
                     Err(hci::event::Error::UnknownOpcode(
                         crate::opcode::GATT_READ_HANDLE_VALUE_OFFSET,
                     ))
                 }
             }
             crate::opcode::GATT_UPDATE_LONG_CHARACTERISTIC_VALUE => {
-                #[cfg(feature = "ms")]
-                {
+                if dialect.is_ms() {
                     Ok(ReturnParameters::GattUpdateLongCharacteristicValue(
                         to_status(&bytes[3..])?,
                     ))
-                }
-
-                #[cfg(not(feature = "ms"))]
-                {
+                } else {
                     Err(hci::event::Error::UnknownOpcode(
                         crate::opcode::GATT_UPDATE_LONG_CHARACTERISTIC_VALUE,
                     ))
@@ -471,9 +531,313 @@ impl hci::event::VendorReturnParameters for ReturnParameters {
             crate::opcode::L2CAP_CONN_PARAM_UPDATE_RESP => Ok(
                 ReturnParameters::L2CapConnectionParameterUpdateResponse(to_status(&bytes[3..])?),
             ),
+            crate::opcode::UPDATER_START => {
+                Ok(ReturnParameters::UpdaterStart(to_status(&bytes[3..])?))
+            }
+            crate::opcode::UPDATER_REBOOT => {
+                Ok(ReturnParameters::UpdaterReboot(to_status(&bytes[3..])?))
+            }
+            crate::opcode::UPDATER_GET_VERSION => Ok(ReturnParameters::UpdaterGetVersion(
+                to_updater_version(&bytes[3..])?,
+            )),
+            crate::opcode::UPDATER_GET_BUFFER_SIZE => Ok(ReturnParameters::UpdaterGetBufferSize(
+                to_updater_buffer_size(&bytes[3..])?,
+            )),
+            crate::opcode::UPDATER_ERASE_BLUE_FLAG => Ok(ReturnParameters::UpdaterEraseBlueFlag(
+                to_status(&bytes[3..])?,
+            )),
+            crate::opcode::UPDATER_RESET_BLUE_FLAG => Ok(ReturnParameters::UpdaterResetBlueFlag(
+                to_status(&bytes[3..])?,
+            )),
+            crate::opcode::UPDATER_ERASE_SECTOR => Ok(ReturnParameters::UpdaterEraseSector(
+                to_status(&bytes[3..])?,
+            )),
+            crate::opcode::UPDATER_PROGRAM_DATA_BLOCK => Ok(
+                ReturnParameters::UpdaterProgramDataBlock(to_status(&bytes[3..])?),
+            ),
+            crate::opcode::UPDATER_READ_DATA_BLOCK => Ok(ReturnParameters::UpdaterReadDataBlock(
+                to_updater_data(&bytes[3..])?,
+            )),
+            crate::opcode::UPDATER_CALC_CRC => Ok(ReturnParameters::UpdaterCalcCrc(
+                to_updater_crc(&bytes[3..])?,
+            )),
+            crate::opcode::UPDATER_HW_VERSION => Ok(ReturnParameters::UpdaterHardwareVersion(
+                to_updater_version(&bytes[3..])?,
+            )),
             other => Err(hci::event::Error::UnknownOpcode(other)),
         }
     }
+
+    /// Returns the opcode of the command that returned these parameters.
+    pub fn opcode(&self) -> hci::Opcode {
+        match *self {
+            ReturnParameters::HalGetFirmwareRevision(_) => crate::opcode::HAL_GET_FIRMWARE_REVISION,
+            ReturnParameters::HalWriteConfigData(_) => crate::opcode::HAL_WRITE_CONFIG_DATA,
+            ReturnParameters::HalReadConfigData(_) => crate::opcode::HAL_READ_CONFIG_DATA,
+            ReturnParameters::HalSetTxPowerLevel(_) => crate::opcode::HAL_SET_TX_POWER_LEVEL,
+            ReturnParameters::HalDeviceStandby(_) => crate::opcode::HAL_DEVICE_STANDBY,
+            ReturnParameters::HalGetTxTestPacketCount(_) => crate::opcode::HAL_TX_TEST_PACKET_COUNT,
+            ReturnParameters::HalStartTone(_) => crate::opcode::HAL_START_TONE,
+            ReturnParameters::HalStopTone(_) => crate::opcode::HAL_STOP_TONE,
+            ReturnParameters::HalGetLinkStatus(_) => crate::opcode::HAL_GET_LINK_STATUS,
+            ReturnParameters::HalGetAnchorPeriod(_) => crate::opcode::HAL_GET_ANCHOR_PERIOD,
+            ReturnParameters::GapSetNonDiscoverable(_) => crate::opcode::GAP_SET_NONDISCOVERABLE,
+            ReturnParameters::GapSetDiscoverable(_) => crate::opcode::GAP_SET_DISCOVERABLE,
+            ReturnParameters::GapSetDirectConnectable(_) => {
+                crate::opcode::GAP_SET_DIRECT_CONNECTABLE
+            }
+            ReturnParameters::GapSetIoCapability(_) => crate::opcode::GAP_SET_IO_CAPABILITY,
+            ReturnParameters::GapSetAuthenticationRequirement(_) => {
+                crate::opcode::GAP_SET_AUTHENTICATION_REQUIREMENT
+            }
+            ReturnParameters::GapSetAuthorizationRequirement(_) => {
+                crate::opcode::GAP_SET_AUTHORIZATION_REQUIREMENT
+            }
+            ReturnParameters::GapPassKeyResponse(_) => crate::opcode::GAP_PASS_KEY_RESPONSE,
+            ReturnParameters::GapAuthorizationResponse(_) => {
+                crate::opcode::GAP_AUTHORIZATION_RESPONSE
+            }
+            ReturnParameters::GapInit(_) => crate::opcode::GAP_INIT,
+            ReturnParameters::GapSetNonConnectable(_) => crate::opcode::GAP_SET_NONCONNECTABLE,
+            ReturnParameters::GapSetUndirectedConnectable(_) => {
+                crate::opcode::GAP_SET_UNDIRECTED_CONNECTABLE
+            }
+            ReturnParameters::GapUpdateAdvertisingData(_) => {
+                crate::opcode::GAP_UPDATE_ADVERTISING_DATA
+            }
+            ReturnParameters::GapDeleteAdType(_) => crate::opcode::GAP_DELETE_AD_TYPE,
+            ReturnParameters::GapGetSecurityLevel(_) => crate::opcode::GAP_GET_SECURITY_LEVEL,
+            ReturnParameters::GapSetEventMask(_) => crate::opcode::GAP_SET_EVENT_MASK,
+            ReturnParameters::GapConfigureWhiteList(_) => crate::opcode::GAP_CONFIGURE_WHITE_LIST,
+            ReturnParameters::GapClearSecurityDatabase(_) => {
+                crate::opcode::GAP_CLEAR_SECURITY_DATABASE
+            }
+            ReturnParameters::GapAllowRebond(_) => crate::opcode::GAP_ALLOW_REBOND,
+            ReturnParameters::GapTerminateProcedure(_) => crate::opcode::GAP_TERMINATE_PROCEDURE,
+            ReturnParameters::GapResolvePrivateAddress(_) => {
+                crate::opcode::GAP_RESOLVE_PRIVATE_ADDRESS
+            }
+            ReturnParameters::GapGetBondedDevices(_) => crate::opcode::GAP_GET_BONDED_DEVICES,
+            ReturnParameters::GapSetBroadcastMode(_) => crate::opcode::GAP_SET_BROADCAST_MODE,
+            ReturnParameters::GapStartObservationProcedure(_) => {
+                crate::opcode::GAP_START_OBSERVATION_PROCEDURE
+            }
+            ReturnParameters::GapIsDeviceBonded(_) => crate::opcode::GAP_IS_DEVICE_BONDED,
+            #[cfg(feature = "bluenrg2")]
+            ReturnParameters::GapNumericComparisonValueConfirm(_) => {
+                crate::opcode::GAP_NUMERIC_COMPARISON_VALUE_CONFIRM
+            }
+            #[cfg(feature = "bluenrg2")]
+            ReturnParameters::GapPassKeyInput(_) => crate::opcode::GAP_PASS_KEY_INPUT,
+            #[cfg(feature = "bluenrg2")]
+            ReturnParameters::GapRemoveBondedDevice(_) => crate::opcode::GAP_REMOVE_BONDED_DEVICE,
+            ReturnParameters::GattInit(_) => crate::opcode::GATT_INIT,
+            ReturnParameters::GattAddService(_) => crate::opcode::GATT_ADD_SERVICE,
+            ReturnParameters::GattIncludeService(_) => crate::opcode::GATT_INCLUDE_SERVICE,
+            ReturnParameters::GattAddCharacteristic(_) => crate::opcode::GATT_ADD_CHARACTERISTIC,
+            ReturnParameters::GattAddCharacteristicDescriptor(_) => {
+                crate::opcode::GATT_ADD_CHARACTERISTIC_DESCRIPTOR
+            }
+            ReturnParameters::GattUpdateCharacteristicValue(_) => {
+                crate::opcode::GATT_UPDATE_CHARACTERISTIC_VALUE
+            }
+            ReturnParameters::GattDeleteCharacteristic(_) => {
+                crate::opcode::GATT_DELETE_CHARACTERISTIC
+            }
+            ReturnParameters::GattDeleteService(_) => crate::opcode::GATT_DELETE_SERVICE,
+            ReturnParameters::GattDeleteIncludedService(_) => {
+                crate::opcode::GATT_DELETE_INCLUDED_SERVICE
+            }
+            ReturnParameters::GattSetEventMask(_) => crate::opcode::GATT_SET_EVENT_MASK,
+            ReturnParameters::GattWriteWithoutResponse(_) => {
+                crate::opcode::GATT_WRITE_WITHOUT_RESPONSE
+            }
+            ReturnParameters::GattSignedWriteWithoutResponse(_) => {
+                crate::opcode::GATT_SIGNED_WRITE_WITHOUT_RESPONSE
+            }
+            ReturnParameters::GattConfirmIndication(_) => crate::opcode::GATT_CONFIRM_INDICATION,
+            ReturnParameters::GattWriteResponse(_) => crate::opcode::GATT_WRITE_RESPONSE,
+            ReturnParameters::GattAllowRead(_) => crate::opcode::GATT_ALLOW_READ,
+            ReturnParameters::GattSetSecurityPermission(_) => {
+                crate::opcode::GATT_SET_SECURITY_PERMISSION
+            }
+            ReturnParameters::GattSetDescriptorValue(_) => crate::opcode::GATT_SET_DESCRIPTOR_VALUE,
+            ReturnParameters::GattReadHandleValue(_) => crate::opcode::GATT_READ_HANDLE_VALUE,
+            ReturnParameters::GattReadHandleValueOffset(_) => {
+                crate::opcode::GATT_READ_HANDLE_VALUE_OFFSET
+            }
+            ReturnParameters::GattUpdateLongCharacteristicValue(_) => {
+                crate::opcode::GATT_UPDATE_LONG_CHARACTERISTIC_VALUE
+            }
+            ReturnParameters::L2CapConnectionParameterUpdateResponse(_) => {
+                crate::opcode::L2CAP_CONN_PARAM_UPDATE_RESP
+            }
+            ReturnParameters::UpdaterStart(_) => crate::opcode::UPDATER_START,
+            ReturnParameters::UpdaterReboot(_) => crate::opcode::UPDATER_REBOOT,
+            ReturnParameters::UpdaterGetVersion(_) => crate::opcode::UPDATER_GET_VERSION,
+            ReturnParameters::UpdaterGetBufferSize(_) => crate::opcode::UPDATER_GET_BUFFER_SIZE,
+            ReturnParameters::UpdaterEraseBlueFlag(_) => crate::opcode::UPDATER_ERASE_BLUE_FLAG,
+            ReturnParameters::UpdaterResetBlueFlag(_) => crate::opcode::UPDATER_RESET_BLUE_FLAG,
+            ReturnParameters::UpdaterEraseSector(_) => crate::opcode::UPDATER_ERASE_SECTOR,
+            ReturnParameters::UpdaterProgramDataBlock(_) => {
+                crate::opcode::UPDATER_PROGRAM_DATA_BLOCK
+            }
+            ReturnParameters::UpdaterReadDataBlock(_) => crate::opcode::UPDATER_READ_DATA_BLOCK,
+            ReturnParameters::UpdaterCalcCrc(_) => crate::opcode::UPDATER_CALC_CRC,
+            ReturnParameters::UpdaterHardwareVersion(_) => crate::opcode::UPDATER_HW_VERSION,
+        }
+    }
+
+    /// Serializes the return parameters in the given [`Dialect`] into `buffer`, in the form read
+    /// by [`with_dialect`](ReturnParameters::with_dialect): the number of HCI command packets the
+    /// controller can accept (always 1), the [opcode](ReturnParameters::opcode), and the return
+    /// parameters. Returns the number of bytes written.
+    ///
+    /// The original BlueNRG does not return the address resolved by the
+    /// [`resolve_private_address`](crate::gap::Commands::resolve_private_address) command, so it
+    /// is dropped in that dialect.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`BufferTooSmall`](super::SerializeError::BufferTooSmall) if the parameters do not
+    /// fit in `buffer`.
+    pub fn serialize(
+        &self,
+        dialect: Dialect,
+        buffer: &mut [u8],
+    ) -> Result<usize, super::SerializeError> {
+        let mut bytes = [0; super::MAX_EVENT_PARAMETERS_LEN];
+        let len = self.copy_into_slice(dialect, &mut bytes);
+        super::copy_serialized(&bytes[..len], buffer)
+    }
+
+    /// Serializes the return parameters in the given [`Dialect`] into `buffer` as a complete HCI
+    /// Command Complete event packet, as the controller sends it: the packet type (`0x04`), the
+    /// event code (`0x0E`), the parameter length, and the
+    /// [serialized](ReturnParameters::serialize) return parameters. Returns the number of bytes
+    /// written.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`serialize`](ReturnParameters::serialize).
+    pub fn serialize_packet(
+        &self,
+        dialect: Dialect,
+        buffer: &mut [u8],
+    ) -> Result<usize, super::SerializeError> {
+        let mut bytes = [0; super::MAX_EVENT_PARAMETERS_LEN];
+        let len = self.copy_into_slice(dialect, &mut bytes);
+        super::copy_serialized_packet(super::COMMAND_COMPLETE_EVENT, &bytes[..len], buffer)
+    }
+
+    // Writes the return parameters into `bytes`, mirroring `with_dialect`. Returns the number of
+    // bytes written.
+    fn copy_into_slice(&self, dialect: Dialect, bytes: &mut [u8]) -> usize {
+        bytes[0] = 1;
+        LittleEndian::write_u16(&mut bytes[1..], self.opcode().0);
+
+        let parameters = &mut bytes[3..];
+        let len = match *self {
+            ReturnParameters::HalWriteConfigData(status)
+            | ReturnParameters::HalSetTxPowerLevel(status)
+            | ReturnParameters::HalDeviceStandby(status)
+            | ReturnParameters::HalStartTone(status)
+            | ReturnParameters::HalStopTone(status)
+            | ReturnParameters::GapSetNonDiscoverable(status)
+            | ReturnParameters::GapSetDiscoverable(status)
+            | ReturnParameters::GapSetDirectConnectable(status)
+            | ReturnParameters::GapSetIoCapability(status)
+            | ReturnParameters::GapSetAuthenticationRequirement(status)
+            | ReturnParameters::GapSetAuthorizationRequirement(status)
+            | ReturnParameters::GapPassKeyResponse(status)
+            | ReturnParameters::GapAuthorizationResponse(status)
+            | ReturnParameters::GapSetNonConnectable(status)
+            | ReturnParameters::GapSetUndirectedConnectable(status)
+            | ReturnParameters::GapUpdateAdvertisingData(status)
+            | ReturnParameters::GapDeleteAdType(status)
+            | ReturnParameters::GapSetEventMask(status)
+            | ReturnParameters::GapConfigureWhiteList(status)
+            | ReturnParameters::GapClearSecurityDatabase(status)
+            | ReturnParameters::GapAllowRebond(status)
+            | ReturnParameters::GapTerminateProcedure(status)
+            | ReturnParameters::GapIsDeviceBonded(status)
+            | ReturnParameters::GattInit(status)
+            | ReturnParameters::GattUpdateCharacteristicValue(status)
+            | ReturnParameters::GattDeleteCharacteristic(status)
+            | ReturnParameters::GattDeleteService(status)
+            | ReturnParameters::GattDeleteIncludedService(status)
+            | ReturnParameters::GattSetEventMask(status)
+            | ReturnParameters::GattWriteWithoutResponse(status)
+            | ReturnParameters::GattSignedWriteWithoutResponse(status)
+            | ReturnParameters::GattConfirmIndication(status)
+            | ReturnParameters::GattWriteResponse(status)
+            | ReturnParameters::GattAllowRead(status)
+            | ReturnParameters::GattSetSecurityPermission(status)
+            | ReturnParameters::GattSetDescriptorValue(status)
+            | ReturnParameters::L2CapConnectionParameterUpdateResponse(status)
+            | ReturnParameters::UpdaterStart(status)
+            | ReturnParameters::UpdaterReboot(status)
+            | ReturnParameters::UpdaterEraseBlueFlag(status)
+            | ReturnParameters::UpdaterResetBlueFlag(status)
+            | ReturnParameters::UpdaterEraseSector(status)
+            | ReturnParameters::UpdaterProgramDataBlock(status) => copy_status(status, parameters),
+            ReturnParameters::HalGetFirmwareRevision(ref params) => {
+                params.copy_into_slice(parameters)
+            }
+            ReturnParameters::HalReadConfigData(ref params) => params.copy_into_slice(parameters),
+            ReturnParameters::HalGetTxTestPacketCount(ref params) => {
+                params.copy_into_slice(parameters)
+            }
+            ReturnParameters::HalGetLinkStatus(ref params) => params.copy_into_slice(parameters),
+            ReturnParameters::HalGetAnchorPeriod(ref params) => params.copy_into_slice(parameters),
+            ReturnParameters::GapInit(ref params) => params.copy_into_slice(parameters),
+            ReturnParameters::GapGetSecurityLevel(ref params) => params.copy_into_slice(parameters),
+            ReturnParameters::GapResolvePrivateAddress(ref params) => {
+                params.copy_into_slice(dialect, parameters)
+            }
+            ReturnParameters::GapGetBondedDevices(ref params) => params.copy_into_slice(parameters),
+            ReturnParameters::GapSetBroadcastMode(status) => copy_status(status, parameters),
+            ReturnParameters::GapStartObservationProcedure(status) => {
+                copy_status(status, parameters)
+            }
+            #[cfg(feature = "bluenrg2")]
+            ReturnParameters::GapNumericComparisonValueConfirm(status) => {
+                copy_status(status, parameters)
+            }
+            #[cfg(feature = "bluenrg2")]
+            ReturnParameters::GapPassKeyInput(status) => copy_status(status, parameters),
+            #[cfg(feature = "bluenrg2")]
+            ReturnParameters::GapRemoveBondedDevice(status) => copy_status(status, parameters),
+            ReturnParameters::GattAddService(ref params) => params.copy_into_slice(parameters),
+            ReturnParameters::GattIncludeService(ref params) => params.copy_into_slice(parameters),
+            ReturnParameters::GattAddCharacteristic(ref params) => {
+                params.copy_into_slice(parameters)
+            }
+            ReturnParameters::GattAddCharacteristicDescriptor(ref params) => {
+                params.copy_into_slice(parameters)
+            }
+            ReturnParameters::GattReadHandleValue(ref params) => params.copy_into_slice(parameters),
+            ReturnParameters::GattReadHandleValueOffset(ref params) => {
+                params.copy_into_slice(parameters)
+            }
+            ReturnParameters::GattUpdateLongCharacteristicValue(status) => {
+                copy_status(status, parameters)
+            }
+            ReturnParameters::UpdaterGetVersion(ref params) => params.copy_into_slice(parameters),
+            ReturnParameters::UpdaterGetBufferSize(ref params) => {
+                params.copy_into_slice(parameters)
+            }
+            ReturnParameters::UpdaterReadDataBlock(ref params) => {
+                params.copy_into_slice(parameters)
+            }
+            ReturnParameters::UpdaterCalcCrc(ref params) => params.copy_into_slice(parameters),
+            ReturnParameters::UpdaterHardwareVersion(ref params) => {
+                params.copy_into_slice(parameters)
+            }
+        };
+
+        3 + len
+    }
 }
 
 fn check_len_at_least(
@@ -494,9 +858,15 @@ fn to_status(
     bytes[0].try_into().map_err(hci::event::rewrap_bad_status)
 }
 
+fn copy_status(status: hci::Status<crate::event::Status>, bytes: &mut [u8]) -> usize {
+    bytes[0] = status.into();
+
+    1
+}
+
 /// Parameters returned by the [HAL Get Firmware
 /// Revision](crate::hal::Commands::get_firmware_revision) command.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct HalFirmwareRevision {
     /// Did the command fail, and if so, how?
     pub status: hci::Status<crate::event::Status>,
@@ -516,9 +886,18 @@ fn to_hal_firmware_revision(
     })
 }
 
+impl HalFirmwareRevision {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        bytes[0] = self.status.into();
+        LittleEndian::write_u16(&mut bytes[1..], self.revision);
+
+        3
+    }
+}
+
 /// Parameters returned by the [HAL Read Config Data](crate::hal::Commands::read_config_data)
 /// command.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct HalConfigData {
     /// Did the command fail, and if so, how?
     pub status: hci::Status<crate::event::Status>,
@@ -595,9 +974,35 @@ fn to_hal_config_parameter(
     }
 }
 
+impl HalConfigData {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        bytes[0] = self.status.into();
+        let value_len = match self.value {
+            HalConfigParameter::PublicAddress(addr) => {
+                bytes[1..7].copy_from_slice(&addr.0);
+                6
+            }
+            HalConfigParameter::Diversifier(value) => {
+                LittleEndian::write_u16(&mut bytes[1..], value);
+                2
+            }
+            HalConfigParameter::EncryptionKey(ref key) => {
+                bytes[1..17].copy_from_slice(&key.0);
+                16
+            }
+            HalConfigParameter::Byte(value) => {
+                bytes[1] = value;
+                1
+            }
+        };
+
+        1 + value_len
+    }
+}
+
 /// Parameters returned by the [HAL Get Tx Test Packet
 /// Count](crate::hal::Commands::get_tx_test_packet_count) command.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct HalTxTestPacketCount {
     /// Did the command fail, and if so, how?
     pub status: hci::Status<crate::event::Status>,
@@ -616,8 +1021,17 @@ fn to_hal_tx_test_packet_count(
     })
 }
 
+impl HalTxTestPacketCount {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        bytes[0] = self.status.into();
+        LittleEndian::write_u32(&mut bytes[1..], self.packet_count);
+
+        5
+    }
+}
+
 /// Parameters returned by the [HAL Get Link Status](crate::hal::Commands::get_link_status) command.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct HalLinkStatus {
     /// Did the command fail, and if so, how?
     pub status: hci::Status<crate::event::Status>,
@@ -675,6 +1089,21 @@ impl TryFrom<u8> for LinkState {
     }
 }
 
+impl From<LinkState> for u8 {
+    fn from(state: LinkState) -> Self {
+        match state {
+            LinkState::Idle => 0,
+            LinkState::Advertising => 1,
+            LinkState::ConnectedAsPeripheral => 2,
+            LinkState::Scanning => 3,
+            LinkState::Reserved => 4,
+            LinkState::ConnectedAsPrimary => 5,
+            LinkState::TxTest => 6,
+            LinkState::RxTest => 7,
+        }
+    }
+}
+
 fn to_hal_link_status(
     bytes: &[u8],
 ) -> Result<HalLinkStatus, hci::event::Error<super::BlueNRGError>> {
@@ -700,9 +1129,21 @@ fn to_hal_link_status(
     Ok(status)
 }
 
+impl HalLinkStatus {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        bytes[0] = self.status.into();
+        for (client, client_status) in self.clients.iter().enumerate() {
+            bytes[1 + client] = client_status.state.into();
+            LittleEndian::write_u16(&mut bytes[9 + 2 * client..], client_status.conn_handle.0);
+        }
+
+        25
+    }
+}
+
 /// Parameters returned by the [HAL Get Anchor Period](crate::hal::Commands::get_anchor_period)
 /// command.
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct HalAnchorPeriod {
     /// Did the command fail, and if so, how?
     pub status: hci::Status<crate::event::Status>,
@@ -728,8 +1169,23 @@ fn to_hal_anchor_period(
     })
 }
 
+impl HalAnchorPeriod {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        bytes[0] = self.status.into();
+        LittleEndian::write_u32(&mut bytes[1..5], to_slots(self.anchor_interval));
+        LittleEndian::write_u32(&mut bytes[5..9], to_slots(self.max_slot));
+
+        9
+    }
+}
+
+// Converts a duration to the number of 625-microsecond slots the controller reports.
+fn to_slots(duration: Duration) -> u32 {
+    (duration.as_micros() / 625) as u32
+}
+
 /// Parameters returned by the [GAP Init](crate::gap::Commands::init) command.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct GapInit {
     /// Did the command fail, and if so, how?
     ///
@@ -759,9 +1215,20 @@ fn to_gap_init(bytes: &[u8]) -> Result<GapInit, hci::event::Error<super::BlueNRG
     })
 }
 
+impl GapInit {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        bytes[0] = self.status.into();
+        LittleEndian::write_u16(&mut bytes[1..], self.service_handle.0);
+        LittleEndian::write_u16(&mut bytes[3..], self.dev_name_handle.0);
+        LittleEndian::write_u16(&mut bytes[5..], self.appearance_handle.0);
+
+        7
+    }
+}
+
 /// Parameters returned by the [GAP Get Security Level](crate::gap::Commands::get_security_level)
 /// command.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct GapSecurityLevel {
     /// Did the command fail, and if so, how?
     pub status: hci::Status<crate::event::Status>,
@@ -803,6 +1270,16 @@ impl TryFrom<u8> for PassKeyRequirement {
     }
 }
 
+impl From<PassKeyRequirement> for u8 {
+    fn from(requirement: PassKeyRequirement) -> Self {
+        match requirement {
+            PassKeyRequirement::NotRequired => 0x00,
+            PassKeyRequirement::FixedPin => 0x01,
+            PassKeyRequirement::Generated => 0x02,
+        }
+    }
+}
+
 fn to_boolean(value: u8) -> Result<bool, super::BlueNRGError> {
     match value {
         0 => Ok(false),
@@ -825,20 +1302,30 @@ fn to_gap_security_level(
     })
 }
 
-#[cfg(feature = "ms")]
+impl GapSecurityLevel {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        bytes[0] = self.status.into();
+        bytes[1] = self.mitm_protection_required as u8;
+        bytes[2] = self.bonding_required as u8;
+        bytes[3] = self.out_of_band_data_present as u8;
+        bytes[4] = self.pass_key_required.into();
+
+        5
+    }
+}
+
 /// Parameters returned by the [GAP Resolve Private
 /// Address](crate::gap::Commands::resolve_private_address) command.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct GapResolvePrivateAddress {
     /// Did the command fail, and if so, how?
     pub status: hci::Status<crate::event::Status>,
 
     /// If the address was successfully resolved, the peer address is returned.  This value is
-    /// `None` if the address could not be resolved.
+    /// `None` if the address could not be resolved, and always `None` for the original BlueNRG.
     pub bd_addr: Option<hci::BdAddr>,
 }
 
-#[cfg(feature = "ms")]
 fn to_gap_resolve_private_address(
     bytes: &[u8],
 ) -> Result<GapResolvePrivateAddress, hci::event::Error<super::BlueNRGError>> {
@@ -861,6 +1348,19 @@ fn to_gap_resolve_private_address(
     }
 }
 
+impl GapResolvePrivateAddress {
+    fn copy_into_slice(&self, dialect: Dialect, bytes: &mut [u8]) -> usize {
+        bytes[0] = self.status.into();
+        match self.bd_addr {
+            Some(addr) if dialect.is_ms() => {
+                bytes[1..7].copy_from_slice(&addr.0);
+                7
+            }
+            _ => 1,
+        }
+    }
+}
+
 /// Parameters returned by the [GAP Get Bonded Devices](crate::gap::Commands::get_bonded_devices)
 /// command.
 #[derive(Copy, Clone)]
@@ -934,9 +1434,35 @@ fn to_gap_bonded_devices(
     }
 }
 
+impl GapBondedDevices {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        const HEADER_LEN: usize = 2;
+        const ADDR_LEN: usize = 7;
+
+        bytes[0] = self.status.into();
+        if self.status != hci::Status::Success {
+            return 1;
+        }
+
+        bytes[1] = self.address_count as u8;
+        for (i, addr) in self.bonded_addresses().iter().enumerate() {
+            let index = HEADER_LEN + i * ADDR_LEN;
+            addr.copy_into_slice(&mut bytes[index..index + ADDR_LEN]);
+        }
+
+        HEADER_LEN + ADDR_LEN * self.address_count
+    }
+}
+
+impl PartialEq for GapBondedDevices {
+    fn eq(&self, other: &Self) -> bool {
+        self.status == other.status && self.bonded_addresses() == other.bonded_addresses()
+    }
+}
+
 /// Parameters returned by the [GATT Add Service](crate::gatt::Commands::add_service) and [GATT
 /// Include Service](crate::gatt::Commands::include_service) commands.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct GattService {
     /// Did the command fail, and if so, how?
     pub status: hci::Status<crate::event::Status>,
@@ -959,9 +1485,18 @@ fn to_gatt_service(bytes: &[u8]) -> Result<GattService, hci::event::Error<super:
     })
 }
 
+impl GattService {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        bytes[0] = self.status.into();
+        LittleEndian::write_u16(&mut bytes[1..3], self.service_handle.0);
+
+        3
+    }
+}
+
 /// Parameters returned by the [GATT Add Characteristic](crate::gatt::Commands::add_characteristic)
 /// command.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct GattCharacteristic {
     /// Did the command fail, and if so, how?
     pub status: hci::Status<crate::event::Status>,
@@ -983,9 +1518,18 @@ fn to_gatt_characteristic(
     })
 }
 
+impl GattCharacteristic {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        bytes[0] = self.status.into();
+        LittleEndian::write_u16(&mut bytes[1..3], self.characteristic_handle.0);
+
+        3
+    }
+}
+
 /// Parameters returned by the [GATT Add Characteristic
 /// Descriptor](crate::gatt::Commands::add_characteristic_descriptor) command.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct GattCharacteristicDescriptor {
     /// Did the command fail, and if so, how?
     pub status: hci::Status<crate::event::Status>,
@@ -1005,6 +1549,15 @@ fn to_gatt_characteristic_descriptor(
     })
 }
 
+impl GattCharacteristicDescriptor {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        bytes[0] = self.status.into();
+        LittleEndian::write_u16(&mut bytes[1..3], self.descriptor_handle.0);
+
+        3
+    }
+}
+
 /// Parameters returned by the [GATT Read Handle Value](crate::gatt::Commands::read_handle_value)
 /// command.
 #[derive(Copy, Clone)]
@@ -1057,3 +1610,178 @@ fn to_gatt_handle_value(
 
     Ok(handle_value)
 }
+
+impl GattHandleValue {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        bytes[0] = self.status.into();
+        LittleEndian::write_u16(&mut bytes[1..3], self.value_len as u16);
+        bytes[3..3 + self.value_len].copy_from_slice(self.value());
+
+        3 + self.value_len
+    }
+}
+
+impl PartialEq for GattHandleValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.status == other.status && self.value() == other.value()
+    }
+}
+
+/// Parameters returned by the [Updater Get Version](crate::updater::Commands::get_version) and
+/// [Updater HW Version](crate::updater::Commands::hw_version) commands.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UpdaterVersion {
+    /// Did the command fail, and if so, how?
+    pub status: hci::Status<crate::event::Status>,
+
+    /// Version of the updater or of the hardware.
+    pub version: u8,
+}
+
+fn to_updater_version(
+    bytes: &[u8],
+) -> Result<UpdaterVersion, hci::event::Error<super::BlueNRGError>> {
+    require_len!(bytes, 2);
+
+    Ok(UpdaterVersion {
+        status: to_status(bytes)?,
+        version: bytes[1],
+    })
+}
+
+impl UpdaterVersion {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        bytes[0] = self.status.into();
+        bytes[1] = self.version;
+
+        2
+    }
+}
+
+/// Parameters returned by the [Updater Get Buffer
+/// Size](crate::updater::Commands::get_buffer_size) command.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UpdaterBufferSize {
+    /// Did the command fail, and if so, how?
+    pub status: hci::Status<crate::event::Status>,
+
+    /// Size of the updater's buffer, in bytes.
+    pub buffer_size: u8,
+}
+
+fn to_updater_buffer_size(
+    bytes: &[u8],
+) -> Result<UpdaterBufferSize, hci::event::Error<super::BlueNRGError>> {
+    require_len!(bytes, 2);
+
+    Ok(UpdaterBufferSize {
+        status: to_status(bytes)?,
+        buffer_size: bytes[1],
+    })
+}
+
+impl UpdaterBufferSize {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        bytes[0] = self.status.into();
+        bytes[1] = self.buffer_size;
+
+        2
+    }
+}
+
+/// Parameters returned by the [Updater Read Data
+/// Block](crate::updater::Commands::read_data_block) command.
+#[derive(Copy, Clone)]
+pub struct UpdaterData {
+    /// Did the command fail, and if so, how?
+    pub status: hci::Status<crate::event::Status>,
+
+    data_buf: [u8; UpdaterData::MAX_DATA_BUF],
+    data_len: usize,
+}
+
+impl Debug for UpdaterData {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{{")?;
+        write!(f, "status: {:?}; data: {{", self.status)?;
+        for byte in self.data().iter() {
+            write!(f, "{:?}, ", byte)?;
+        }
+        write!(f, "}}}}")
+    }
+}
+
+impl UpdaterData {
+    // The return parameters hold up to 255 bytes, and 4 of them are used for the number of HCI
+    // command packets, the opcode, and the status.
+    const MAX_DATA_BUF: usize = 251;
+
+    /// Returns the data that was read.
+    pub fn data(&self) -> &[u8] {
+        &self.data_buf[..self.data_len]
+    }
+}
+
+fn to_updater_data(bytes: &[u8]) -> Result<UpdaterData, hci::event::Error<super::BlueNRGError>> {
+    require_len_at_least!(bytes, 1);
+
+    let data_len = bytes.len() - 1;
+    if data_len > UpdaterData::MAX_DATA_BUF {
+        return Err(hci::event::Error::BadLength(
+            bytes.len(),
+            1 + UpdaterData::MAX_DATA_BUF,
+        ));
+    }
+
+    let mut data = UpdaterData {
+        status: to_status(bytes)?,
+        data_buf: [0; UpdaterData::MAX_DATA_BUF],
+        data_len,
+    };
+    data.data_buf[..data_len].copy_from_slice(&bytes[1..]);
+
+    Ok(data)
+}
+
+impl UpdaterData {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        bytes[0] = self.status.into();
+        bytes[1..1 + self.data_len].copy_from_slice(self.data());
+
+        1 + self.data_len
+    }
+}
+
+impl PartialEq for UpdaterData {
+    fn eq(&self, other: &Self) -> bool {
+        self.status == other.status && self.data() == other.data()
+    }
+}
+
+/// Parameters returned by the [Updater Calc CRC](crate::updater::Commands::calc_crc) command.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct UpdaterCrc {
+    /// Did the command fail, and if so, how?
+    pub status: hci::Status<crate::event::Status>,
+
+    /// CRC of the flash sectors.
+    pub crc: u32,
+}
+
+fn to_updater_crc(bytes: &[u8]) -> Result<UpdaterCrc, hci::event::Error<super::BlueNRGError>> {
+    require_len!(bytes, 5);
+
+    Ok(UpdaterCrc {
+        status: to_status(bytes)?,
+        crc: LittleEndian::read_u32(&bytes[1..]),
+    })
+}
+
+impl UpdaterCrc {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        bytes[0] = self.status.into();
+        LittleEndian::write_u32(&mut bytes[1..], self.crc);
+
+        5
+    }
+}
@@ -1,17 +1,18 @@
 //! Vendor-specific events for BlueNRG controllers.
 //!
 //! The BlueNRG implementation defines several additional events that are packaged as
-//! vendor-specific events by the Bluetooth HCI. This module defines those events and functions to
-//! deserialize buffers into them.
+//! vendor-specific events by the Bluetooth HCI. This module defines those events, functions to
+//! deserialize buffers into them, and functions to serialize them back into the bytes the
+//! controller sends.
 extern crate bluetooth_hci as hci;
 
 pub mod command;
 
+use crate::dialect::Dialect;
 use byteorder::{ByteOrder, LittleEndian};
 use core::cmp::PartialEq;
 use core::convert::{TryFrom, TryInto};
 use core::fmt::{Debug, Formatter, Result as FmtResult};
-use core::mem;
 use core::time::Duration;
 
 pub use hci::types::{ConnectionInterval, ConnectionIntervalError};
@@ -19,7 +20,7 @@ pub use hci::{BdAddr, BdAddrType, ConnectionHandle};
 
 /// Vendor-specific events for the BlueNRG-MS controllers.
 #[allow(clippy::large_enum_variant)]
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum BlueNRGEvent {
     /// When the BlueNRG-MS firmware is started normally, it gives this event to the user to
     /// indicate the system has started.
@@ -28,13 +29,11 @@ pub enum BlueNRGEvent {
     /// If the host fails to read events from the controller quickly enough, the controller will
     /// generate this event. This event is never lost; it is inserted as soon as space is available
     /// in the Tx queue.
-    #[cfg(feature = "ms")]
     EventsLost(EventFlags),
 
     /// The fault data event is automatically sent after the
     /// [HalInitialized](BlueNRGEvent::HalInitialized) event in case of [NMI or Hard
     /// fault](ResetReason::Crash).
-    #[cfg(feature = "ms")]
     CrashReport(FaultData),
 
     /// This event is generated by the controller when the limited discoverable mode ends due to
@@ -83,7 +82,6 @@ pub enum BlueNRGEvent {
     /// This event is sent only by a privacy enabled peripheral. The event is sent to the upper
     /// layers when the peripheral is unsuccessful in resolving the resolvable address of the peer
     /// device after connecting to it.
-    #[cfg(feature = "ms")]
     GapAddressNotResolved(ConnectionHandle),
 
     /// This event is generated when the reconnection address is generated during the general
@@ -92,9 +90,14 @@ pub enum BlueNRGEvent {
     /// reconnection address the next time while connecting to the bonded peripheral, the
     /// application needs to set its own address as well as the peer address to which it wants to
     /// connect to this reconnection address.
-    #[cfg(not(feature = "ms"))]
     GapReconnectionAddress(BdAddr),
 
+    /// This event is generated during LE Secure Connections pairing with numeric comparison. The
+    /// application shows the value to the user, and answers with
+    /// [`numeric_comparison_value_confirm`](crate::gap::Commands::numeric_comparison_value_confirm).
+    #[cfg(feature = "bluenrg2")]
+    GapNumericComparisonValue(GapNumericComparisonValue),
+
     /// This event is generated when the central device responds to the L2CAP connection update
     /// request packet. For more info see
     /// [ConnectionParameterUpdateResponse](crate::l2cap::ConnectionParameterUpdateResponse)
@@ -224,11 +227,9 @@ pub enum BlueNRGEvent {
     /// 2).  The event will be given only if a previous ACI command returned with
     /// [InsufficientResources](AttError::InsufficientResources).  On receiving this event, the
     /// application can continue to send notifications by calling `gatt_update_char_value`.
-    #[cfg(feature = "ms")]
     GattTxPoolAvailable(GattTxPoolAvailable),
 
     /// This event is raised on the server when the client confirms the reception of an indication.
-    #[cfg(feature = "ms")]
     GattServerConfirmation(ConnectionHandle),
 
     /// This event is given to the application when a prepare write request is received by the
@@ -240,7 +241,6 @@ pub enum BlueNRGEvent {
     /// write is rejected by the application, then the value of the attribute will not be modified
     /// and an error response will be sent to the client, with the error code as specified by the
     /// application.
-    #[cfg(feature = "ms")]
     AttPrepareWritePermitRequest(AttPrepareWritePermitRequest),
 }
 
@@ -383,12 +383,10 @@ pub enum BlueNRGError {
 
     /// For the [EventsLost](BlueNRGEvent::EventsLost) event: The event included unrecognized event
     /// flags. Includes the entire bitfield.
-    #[cfg(feature = "ms")]
     BadEventFlags(u64),
 
     /// For the [CrashReport](BlueNRGEvent::CrashReport) event: The crash reason was not
     /// recognized. Includes the unrecognized byte.
-    #[cfg(feature = "ms")]
     UnknownCrashReason(u8),
 
     /// For the [GAP Pairing Complete](BlueNRGEvent::GapPairingComplete) event: The status was not
@@ -543,6 +541,67 @@ pub enum BlueNRGError {
     BadBdAddrType(u8),
 }
 
+/// Errors that can occur when serializing an event or return parameters.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SerializeError {
+    /// The buffer is too small to hold the serialized bytes. Includes the required length.
+    BufferTooSmall(usize),
+
+    /// The event does not exist in the requested [`Dialect`].
+    NotInDialect,
+
+    /// The event parameters are longer than the 255 bytes an HCI event can hold. This can only
+    /// happen when a [modified attribute](BlueNRGEvent::GattAttributeModified) that was parsed in
+    /// the original BlueNRG layout is serialized in the longer BlueNRG-MS layout. Includes the
+    /// length of the parameters.
+    TooLong(usize),
+}
+
+// HCI packet type of events, which precedes the event code in an event packet.
+const PACKET_TYPE_EVENT: u8 = 0x04;
+
+// HCI event codes that carry vendor-specific data.
+const COMMAND_COMPLETE_EVENT: u8 = 0x0E;
+const VENDOR_EVENT: u8 = 0xFF;
+
+// Maximum length of the parameters of an HCI event.
+const MAX_EVENT_PARAMETERS_LEN: usize = 255;
+
+// Copies serialized bytes into the caller's buffer.
+fn copy_serialized(bytes: &[u8], buffer: &mut [u8]) -> Result<usize, SerializeError> {
+    if bytes.len() > MAX_EVENT_PARAMETERS_LEN {
+        return Err(SerializeError::TooLong(bytes.len()));
+    }
+    if buffer.len() < bytes.len() {
+        return Err(SerializeError::BufferTooSmall(bytes.len()));
+    }
+
+    buffer[..bytes.len()].copy_from_slice(bytes);
+    Ok(bytes.len())
+}
+
+// Writes an HCI event packet with the given event code and parameters into the caller's buffer.
+fn copy_serialized_packet(
+    event_code: u8,
+    parameters: &[u8],
+    buffer: &mut [u8],
+) -> Result<usize, SerializeError> {
+    if parameters.len() > MAX_EVENT_PARAMETERS_LEN {
+        return Err(SerializeError::TooLong(parameters.len()));
+    }
+
+    let len = 3 + parameters.len();
+    if buffer.len() < len {
+        return Err(SerializeError::BufferTooSmall(len));
+    }
+
+    buffer[0] = PACKET_TYPE_EVENT;
+    buffer[1] = event_code;
+    buffer[2] = parameters.len() as u8;
+    buffer[3..len].copy_from_slice(parameters);
+    Ok(len)
+}
+
 macro_rules! require_len {
     ($left:expr, $right:expr) => {
         if $left.len() != $right {
@@ -573,32 +632,126 @@ impl hci::event::VendorEvent for BlueNRGEvent {
     type Status = Status;
 
     fn new(buffer: &[u8]) -> Result<Self, hci::event::Error<BlueNRGError>> {
+        BlueNRGEvent::with_dialect(Dialect::default(), buffer)
+    }
+}
+
+/// Length of the header of an HCI event: the event code and the parameter length.
+const EVENT_HEADER_LEN: usize = 2;
+
+const EVENT_COMMAND_COMPLETE: u8 = 0x0E;
+const EVENT_VENDOR: u8 = 0xFF;
+const VENDOR_OGF: u16 = 0x3F;
+
+/// Deserializes an HCI event (without its packet type byte) in the given [`Dialect`].
+///
+/// Like [`hci::Event::new`], but vendor-specific events and the return parameters of
+/// vendor-specific commands are deserialized in the given dialect, instead of the
+/// [default](Dialect::default) one.
+///
+/// # Errors
+///
+/// Returns the same errors as [`hci::Event::new`].
+pub fn with_dialect(
+    dialect: Dialect,
+    packet: &[u8],
+) -> Result<hci::Event<BlueNRGEvent>, hci::event::Error<BlueNRGError>> {
+    require_len_at_least!(packet, EVENT_HEADER_LEN);
+    require_len!(packet, EVENT_HEADER_LEN + packet[1] as usize);
+
+    let payload = &packet[EVENT_HEADER_LEN..];
+    match packet[0] {
+        EVENT_VENDOR => Ok(hci::Event::Vendor(BlueNRGEvent::with_dialect(
+            dialect, payload,
+        )?)),
+        EVENT_COMMAND_COMPLETE
+            if payload.len() >= 3
+                && hci::Opcode(LittleEndian::read_u16(&payload[1..])).ogf() == VENDOR_OGF =>
+        {
+            Ok(hci::Event::CommandComplete(
+                hci::event::command::CommandComplete {
+                    num_hci_command_packets: payload[0],
+                    return_params: hci::event::command::ReturnParameters::Vendor(
+                        command::ReturnParameters::with_dialect(dialect, payload)?,
+                    ),
+                },
+            ))
+        }
+        _ => hci::Event::new(hci::event::Packet(packet)),
+    }
+}
+
+/// Reads the next HCI packet from the controller, and deserializes it in the controller's
+/// [dialect](crate::dialect).
+///
+/// Use this instead of [`hci::host::uart::Hci::read`], which always deserializes vendor-specific
+/// events in the [default](Dialect::default) dialect.
+///
+/// # Errors
+///
+/// Returns the same errors as [`hci::host::uart::Hci::read`].
+pub fn read<C>(
+    controller: &mut C,
+) -> nb::Result<hci::host::uart::Packet<BlueNRGEvent>, hci::host::uart::Error<C::Error, BlueNRGError>>
+where
+    C: hci::Controller + crate::Firmware,
+{
+    const MAX_EVENT_LEN: usize = 255;
+    const PACKET_TYPE_LEN: usize = 1;
+    const PARAM_LEN_BYTE: usize = 2;
+
+    let rewrap = |e| match e {
+        nb::Error::WouldBlock => nb::Error::WouldBlock,
+        nb::Error::Other(e) => nb::Error::Other(hci::host::uart::Error::Comm(e)),
+    };
+
+    match controller.peek(0).map_err(rewrap)? {
+        crate::PACKET_TYPE_HCI_EVENT => (),
+        x => return Err(nb::Error::Other(hci::host::uart::Error::BadPacketType(x))),
+    }
+
+    let len = PACKET_TYPE_LEN
+        + EVENT_HEADER_LEN
+        + controller.peek(PARAM_LEN_BYTE).map_err(rewrap)? as usize;
+    let mut buf = [0; PACKET_TYPE_LEN + EVENT_HEADER_LEN + MAX_EVENT_LEN];
+    controller.read_into(&mut buf[..len]).map_err(rewrap)?;
+
+    with_dialect(controller.dialect(), &buf[PACKET_TYPE_LEN..len])
+        .map(hci::host::uart::Packet::Event)
+        .map_err(|e| nb::Error::Other(hci::host::uart::Error::BLE(e)))
+}
+
+impl BlueNRGEvent {
+    /// Deserializes a vendor-specific event in the given [`Dialect`]. [`VendorEvent::new`] uses
+    /// the [default](Dialect::default) dialect; [`read`] uses the controller's.
+    ///
+    /// [`VendorEvent::new`]: hci::event::VendorEvent::new
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`VendorEvent::new`].
+    pub fn with_dialect(
+        dialect: Dialect,
+        buffer: &[u8],
+    ) -> Result<BlueNRGEvent, hci::event::Error<BlueNRGError>> {
         require_len_at_least!(buffer, 2);
 
         let event_code = LittleEndian::read_u16(&buffer[0..=1]);
         match event_code {
             0x0001 => Ok(BlueNRGEvent::HalInitialized(to_hal_initialized(buffer)?)),
             0x0002 => {
-                #[cfg(feature = "ms")]
-                {
+                if dialect.is_ms() {
                     Ok(BlueNRGEvent::EventsLost(to_lost_event(buffer)?))
-                }
-
-                #[cfg(not(feature = "ms"))]
-                {
+                } else {
                     Err(hci::event::Error::Vendor(BlueNRGError::UnknownEvent(
                         event_code,
                     )))
                 }
             }
             0x0003 => {
-                #[cfg(feature = "ms")]
-                {
+                if dialect.is_ms() {
                     Ok(BlueNRGEvent::CrashReport(to_crash_report(buffer)?))
-                }
-
-                #[cfg(not(feature = "ms"))]
-                {
+                } else {
                     Err(hci::event::Error::Vendor(BlueNRGError::UnknownEvent(
                         event_code,
                     )))
@@ -619,18 +772,18 @@ impl hci::event::VendorEvent for BlueNRGEvent {
                 to_gap_procedure_complete(buffer)?,
             )),
             0x0408 => {
-                #[cfg(feature = "ms")]
-                {
+                if dialect.is_ms() {
                     Ok(BlueNRGEvent::GapAddressNotResolved(to_conn_handle(buffer)?))
-                }
-
-                #[cfg(not(feature = "ms"))]
-                {
+                } else {
                     Ok(BlueNRGEvent::GapReconnectionAddress(
                         to_gap_reconnection_address(buffer)?,
                     ))
                 }
             }
+            #[cfg(feature = "bluenrg2")]
+            0x0409 => Ok(BlueNRGEvent::GapNumericComparisonValue(
+                to_gap_numeric_comparison_value(buffer)?,
+            )),
             0x0800 => Ok(BlueNRGEvent::L2CapConnectionUpdateResponse(
                 to_l2cap_connection_update_response(buffer)?,
             )),
@@ -640,9 +793,17 @@ impl hci::event::VendorEvent for BlueNRGEvent {
             0x0802 => Ok(BlueNRGEvent::L2CapConnectionUpdateRequest(
                 to_l2cap_connection_update_request(buffer)?,
             )),
-            0x0C01 => Ok(BlueNRGEvent::GattAttributeModified(
-                to_gatt_attribute_modified(buffer)?,
-            )),
+            0x0C01 => {
+                if dialect.is_ms() {
+                    Ok(BlueNRGEvent::GattAttributeModified(
+                        to_gatt_attribute_modified(buffer)?,
+                    ))
+                } else {
+                    Ok(BlueNRGEvent::GattAttributeModified(
+                        to_original_gatt_attribute_modified(buffer)?,
+                    ))
+                }
+            }
             0x0C02 => Ok(BlueNRGEvent::GattProcedureTimeout(to_conn_handle(buffer)?)),
             0x0C03 => Ok(BlueNRGEvent::AttExchangeMtuResponse(
                 to_att_exchange_mtu_resp(buffer)?,
@@ -695,45 +856,33 @@ impl hci::event::VendorEvent for BlueNRGEvent {
                 to_att_read_multiple_permit_request(buffer)?,
             )),
             0x0C16 => {
-                #[cfg(feature = "ms")]
-                {
+                if dialect.is_ms() {
                     Ok(BlueNRGEvent::GattTxPoolAvailable(
                         to_gatt_tx_pool_available(buffer)?,
                     ))
-                }
-
-                #[cfg(not(feature = "ms"))]
-                {
+                } else {
                     Err(hci::event::Error::Vendor(BlueNRGError::UnknownEvent(
                         event_code,
                     )))
                 }
             }
             0x0C17 => {
-                #[cfg(feature = "ms")]
-                {
+                if dialect.is_ms() {
                     Ok(BlueNRGEvent::GattServerConfirmation(to_conn_handle(
                         buffer,
                     )?))
-                }
-
-                #[cfg(not(feature = "ms"))]
-                {
+                } else {
                     Err(hci::event::Error::Vendor(BlueNRGError::UnknownEvent(
                         event_code,
                     )))
                 }
             }
             0x0C18 => {
-                #[cfg(feature = "ms")]
-                {
+                if dialect.is_ms() {
                     Ok(BlueNRGEvent::AttPrepareWritePermitRequest(
                         to_att_prepare_write_permit_request(buffer)?,
                     ))
-                }
-
-                #[cfg(not(feature = "ms"))]
-                {
+                } else {
                     Err(hci::event::Error::Vendor(BlueNRGError::UnknownEvent(
                         event_code,
                     )))
@@ -744,6 +893,183 @@ impl hci::event::VendorEvent for BlueNRGEvent {
             ))),
         }
     }
+
+    /// Serializes the event in the given [`Dialect`] into `buffer`, in the form read by
+    /// [`with_dialect`](BlueNRGEvent::with_dialect): the vendor-specific event code followed by the
+    /// event data. Returns the number of bytes written.
+    ///
+    /// Fields that the parser ignores are written as 0; for example, the L2CAP identifier of an
+    /// [L2CAP Connection Update Response](BlueNRGEvent::L2CapConnectionUpdateResponse). The
+    /// original BlueNRG does not report the [offset](GattAttributeModified::offset) of a modified
+    /// attribute, so it is dropped in that dialect.
+    ///
+    /// # Errors
+    ///
+    /// - [`BufferTooSmall`](SerializeError::BufferTooSmall) if the event does not fit in `buffer`.
+    /// - [`NotInDialect`](SerializeError::NotInDialect) if the event does not exist in `dialect`.
+    /// - [`TooLong`](SerializeError::TooLong) if the event does not fit in an HCI event.
+    pub fn serialize(&self, dialect: Dialect, buffer: &mut [u8]) -> Result<usize, SerializeError> {
+        let mut bytes = [0; MAX_SERIALIZED_EVENT_LEN];
+        let len = self.copy_into_slice(dialect, &mut bytes)?;
+        copy_serialized(&bytes[..len], buffer)
+    }
+
+    /// Serializes the event in the given [`Dialect`] into `buffer` as a complete HCI event packet,
+    /// as the controller sends it: the packet type (`0x04`), the vendor-specific event code
+    /// (`0xFF`), the parameter length, and the [serialized](BlueNRGEvent::serialize) event. Returns
+    /// the number of bytes written.
+    ///
+    /// # Errors
+    ///
+    /// Returns the same errors as [`serialize`](BlueNRGEvent::serialize).
+    pub fn serialize_packet(
+        &self,
+        dialect: Dialect,
+        buffer: &mut [u8],
+    ) -> Result<usize, SerializeError> {
+        let mut bytes = [0; MAX_SERIALIZED_EVENT_LEN];
+        let len = self.copy_into_slice(dialect, &mut bytes)?;
+        copy_serialized_packet(VENDOR_EVENT, &bytes[..len], buffer)
+    }
+
+    // Writes the event into `bytes`, mirroring `with_dialect`. Returns the number of bytes written.
+    fn copy_into_slice(&self, dialect: Dialect, bytes: &mut [u8]) -> Result<usize, SerializeError> {
+        let (event_code, len) = match *self {
+            BlueNRGEvent::HalInitialized(reason) => {
+                bytes[2] = reason.into();
+                (0x0001, 3)
+            }
+            BlueNRGEvent::EventsLost(flags) => {
+                require_ms(dialect)?;
+                LittleEndian::write_u64(&mut bytes[2..], flags.bits());
+                (0x0002, 10)
+            }
+            BlueNRGEvent::CrashReport(ref fault_data) => {
+                require_ms(dialect)?;
+                (0x0003, fault_data.copy_into_slice(bytes))
+            }
+            BlueNRGEvent::GapLimitedDiscoverableTimeout => (0x0400, 2),
+            BlueNRGEvent::GapPairingComplete(ref event) => (0x0401, event.copy_into_slice(bytes)),
+            BlueNRGEvent::GapPassKeyRequest(conn_handle) => {
+                (0x0402, copy_conn_handle(conn_handle, bytes))
+            }
+            BlueNRGEvent::GapAuthorizationRequest(conn_handle) => {
+                (0x0403, copy_conn_handle(conn_handle, bytes))
+            }
+            BlueNRGEvent::GapPeripheralSecurityInitiated => (0x0404, 2),
+            BlueNRGEvent::GapBondLost => (0x0405, 2),
+            BlueNRGEvent::GapDeviceFound(ref event) => (0x0406, event.copy_into_slice(bytes)),
+            BlueNRGEvent::GapProcedureComplete(ref event) => (0x0407, event.copy_into_slice(bytes)),
+            BlueNRGEvent::GapAddressNotResolved(conn_handle) => {
+                require_ms(dialect)?;
+                (0x0408, copy_conn_handle(conn_handle, bytes))
+            }
+            BlueNRGEvent::GapReconnectionAddress(addr) => {
+                if dialect.is_ms() {
+                    return Err(SerializeError::NotInDialect);
+                }
+                bytes[2..8].copy_from_slice(&addr.0);
+                (0x0408, 8)
+            }
+            #[cfg(feature = "bluenrg2")]
+            BlueNRGEvent::GapNumericComparisonValue(ref event) => {
+                (0x0409, event.copy_into_slice(bytes))
+            }
+            BlueNRGEvent::L2CapConnectionUpdateResponse(ref event) => {
+                (0x0800, event.copy_into_slice(bytes))
+            }
+            BlueNRGEvent::L2CapProcedureTimeout(conn_handle) => {
+                copy_conn_handle(conn_handle, bytes);
+                bytes[4] = 0;
+                (0x0801, 5)
+            }
+            BlueNRGEvent::L2CapConnectionUpdateRequest(ref event) => {
+                (0x0802, event.copy_into_slice(bytes))
+            }
+            BlueNRGEvent::GattAttributeModified(ref event) => {
+                if dialect.is_ms() {
+                    (0x0C01, event.copy_into_slice(bytes))
+                } else {
+                    (0x0C01, event.copy_into_original_slice(bytes))
+                }
+            }
+            BlueNRGEvent::GattProcedureTimeout(conn_handle) => {
+                (0x0C02, copy_conn_handle(conn_handle, bytes))
+            }
+            BlueNRGEvent::AttExchangeMtuResponse(ref event) => {
+                (0x0C03, event.copy_into_slice(bytes))
+            }
+            BlueNRGEvent::AttFindInformationResponse(ref event) => {
+                (0x0C04, event.copy_into_slice(bytes))
+            }
+            BlueNRGEvent::AttFindByTypeValueResponse(ref event) => {
+                (0x0C05, event.copy_into_slice(bytes))
+            }
+            BlueNRGEvent::AttReadByTypeResponse(ref event) => {
+                (0x0C06, event.copy_into_slice(bytes))
+            }
+            BlueNRGEvent::AttReadResponse(ref event) => (0x0C07, event.copy_into_slice(bytes)),
+            BlueNRGEvent::AttReadBlobResponse(ref event) => (0x0C08, event.copy_into_slice(bytes)),
+            BlueNRGEvent::AttReadMultipleResponse(ref event) => {
+                (0x0C09, event.copy_into_slice(bytes))
+            }
+            BlueNRGEvent::AttReadByGroupTypeResponse(ref event) => {
+                (0x0C0A, event.copy_into_slice(bytes))
+            }
+            BlueNRGEvent::AttPrepareWriteResponse(ref event) => {
+                (0x0C0C, event.copy_into_slice(bytes))
+            }
+            BlueNRGEvent::AttExecuteWriteResponse(conn_handle) => {
+                copy_conn_handle(conn_handle, bytes);
+                bytes[4] = 0;
+                (0x0C0D, 5)
+            }
+            BlueNRGEvent::GattIndication(ref event) => (0x0C0E, event.copy_into_slice(bytes)),
+            BlueNRGEvent::GattNotification(ref event) => (0x0C0F, event.copy_into_slice(bytes)),
+            BlueNRGEvent::GattProcedureComplete(ref event) => {
+                (0x0C10, event.copy_into_slice(bytes))
+            }
+            BlueNRGEvent::AttErrorResponse(ref event) => (0x0C11, event.copy_into_slice(bytes)),
+            BlueNRGEvent::GattDiscoverOrReadCharacteristicByUuidResponse(ref event) => {
+                (0x0C12, event.copy_into_slice(bytes))
+            }
+            BlueNRGEvent::AttWritePermitRequest(ref event) => {
+                (0x0C13, event.copy_into_write_permit_slice(bytes))
+            }
+            BlueNRGEvent::AttReadPermitRequest(ref event) => (0x0C14, event.copy_into_slice(bytes)),
+            BlueNRGEvent::AttReadMultiplePermitRequest(ref event) => {
+                (0x0C15, event.copy_into_slice(bytes))
+            }
+            BlueNRGEvent::GattTxPoolAvailable(ref event) => {
+                require_ms(dialect)?;
+                (0x0C16, event.copy_into_slice(bytes))
+            }
+            BlueNRGEvent::GattServerConfirmation(conn_handle) => {
+                require_ms(dialect)?;
+                (0x0C17, copy_conn_handle(conn_handle, bytes))
+            }
+            BlueNRGEvent::AttPrepareWritePermitRequest(ref event) => {
+                require_ms(dialect)?;
+                (0x0C18, event.copy_into_slice(bytes))
+            }
+        };
+
+        LittleEndian::write_u16(&mut bytes[0..], event_code);
+        Ok(len)
+    }
+}
+
+// Large enough for any serialized event, including a modified attribute that was parsed in the
+// original BlueNRG layout and is serialized in the longer BlueNRG-1 layout. Longer events are
+// rejected before they are copied out.
+const MAX_SERIALIZED_EVENT_LEN: usize = 10 + MAX_ATTRIBUTE_LEN;
+
+fn require_ms(dialect: Dialect) -> Result<(), SerializeError> {
+    if dialect.is_ms() {
+        Ok(())
+    } else {
+        Err(SerializeError::NotInDialect)
+    }
 }
 
 /// Potential reasons the controller sent the [`HalInitialized`](BlueNRGEvent::HalInitialized)
@@ -789,6 +1115,22 @@ impl TryFrom<u8> for ResetReason {
     }
 }
 
+impl From<ResetReason> for u8 {
+    fn from(reason: ResetReason) -> Self {
+        match reason {
+            ResetReason::Normal => 1,
+            ResetReason::Updater => 2,
+            ResetReason::UpdaterBadFlag => 3,
+            ResetReason::UpdaterPin => 4,
+            ResetReason::Watchdog => 5,
+            ResetReason::Lockup => 6,
+            ResetReason::Brownout => 7,
+            ResetReason::Crash => 8,
+            ResetReason::EccError => 9,
+        }
+    }
+}
+
 /// Convert a buffer to the `HalInitialized` `BlueNRGEvent`.
 ///
 /// # Errors
@@ -802,7 +1144,6 @@ fn to_hal_initialized(buffer: &[u8]) -> Result<ResetReason, hci::event::Error<Bl
     buffer[2].try_into().map_err(hci::event::Error::Vendor)
 }
 
-#[cfg(feature = "ms")]
 bitflags! {
     /// Bitfield for the [Events Lost](BlueNRGEvent::EventsLost) event. Each bit indicates a
     /// different type of event that was not handled.
@@ -930,7 +1271,6 @@ bitflags! {
 /// - Returns a `BadLength` HCI error if the buffer is not exactly 10 bytes long
 /// - Returns [`BadEventFlags`](BlueNRGError::BadEventFlags) if a bit is set that does not represent
 ///   a lost event.
-#[cfg(feature = "ms")]
 fn to_lost_event(buffer: &[u8]) -> Result<EventFlags, hci::event::Error<BlueNRGError>> {
     require_len!(buffer, 10);
 
@@ -940,12 +1280,10 @@ fn to_lost_event(buffer: &[u8]) -> Result<EventFlags, hci::event::Error<BlueNRGE
 
 // The maximum length of [`FaultData::debug_data`]. The maximum length of an event is 255 bytes,
 // and the non-variable data of the event takes up 40 bytes.
-#[cfg(feature = "ms")]
-const MAX_DEBUG_DATA_LEN: usize = 215;
+pub(crate) const MAX_DEBUG_DATA_LEN: usize = 215;
 
 /// Specific reason for the fault reported with [`FaultData`].
-#[cfg(feature = "ms")]
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub enum CrashReason {
     /// The controller reset because an assertion failed.
     Assertion,
@@ -955,7 +1293,6 @@ pub enum CrashReason {
     HardFault,
 }
 
-#[cfg(feature = "ms")]
 impl TryFrom<u8> for CrashReason {
     type Error = BlueNRGError;
 
@@ -975,8 +1312,18 @@ impl TryFrom<u8> for CrashReason {
     }
 }
 
+impl From<CrashReason> for u8 {
+    /// Returns the value used by the CubeExpansion source code.
+    fn from(reason: CrashReason) -> Self {
+        match reason {
+            CrashReason::Assertion => 0,
+            CrashReason::NmiFault => 1,
+            CrashReason::HardFault => 2,
+        }
+    }
+}
+
 /// Fault data reported after a crash.
-#[cfg(feature = "ms")]
 #[derive(Clone, Copy)]
 pub struct FaultData {
     /// Fault reason.
@@ -1002,13 +1349,12 @@ pub struct FaultData {
     pub xpsr: u32,
 
     // Number of valid bytes in debug_data
-    debug_data_len: usize,
+    pub(crate) debug_data_len: usize,
 
     // Additional crash dump data
-    debug_data_buf: [u8; MAX_DEBUG_DATA_LEN],
+    pub(crate) debug_data_buf: [u8; MAX_DEBUG_DATA_LEN],
 }
 
-#[cfg(feature = "ms")]
 impl Debug for FaultData {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
         write!(
@@ -1028,7 +1374,6 @@ impl Debug for FaultData {
     }
 }
 
-#[cfg(feature = "ms")]
 impl FaultData {
     /// Returns the valid debug data.
     pub fn debug_data(&self) -> &[u8] {
@@ -1036,7 +1381,6 @@ impl FaultData {
     }
 }
 
-#[cfg(feature = "ms")]
 fn to_crash_report(buffer: &[u8]) -> Result<FaultData, hci::event::Error<BlueNRGError>> {
     require_len_at_least!(buffer, 40);
 
@@ -1062,6 +1406,38 @@ fn to_crash_report(buffer: &[u8]) -> Result<FaultData, hci::event::Error<BlueNRG
     Ok(fault_data)
 }
 
+impl FaultData {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        bytes[2] = self.reason.into();
+        let registers = [
+            self.sp, self.r0, self.r1, self.r2, self.r3, self.r12, self.lr, self.pc, self.xpsr,
+        ];
+        for (i, register) in registers.iter().enumerate() {
+            LittleEndian::write_u32(&mut bytes[3 + 4 * i..], *register);
+        }
+        bytes[39] = self.debug_data_len as u8;
+        bytes[40..40 + self.debug_data_len].copy_from_slice(self.debug_data());
+
+        40 + self.debug_data_len
+    }
+}
+
+impl PartialEq for FaultData {
+    fn eq(&self, other: &Self) -> bool {
+        self.reason == other.reason
+            && self.sp == other.sp
+            && self.r0 == other.r0
+            && self.r1 == other.r1
+            && self.r2 == other.r2
+            && self.r3 == other.r3
+            && self.r12 == other.r12
+            && self.lr == other.lr
+            && self.pc == other.pc
+            && self.xpsr == other.xpsr
+            && self.debug_data() == other.debug_data()
+    }
+}
+
 macro_rules! require_l2cap_event_data_len {
     ($left:expr, $right:expr) => {
         let actual = $left[4];
@@ -1088,7 +1464,7 @@ macro_rules! require_l2cap_len {
 ///
 /// For more info see connection parameter update response and command reject in Bluetooth Core v4.0
 /// spec.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct L2CapConnectionUpdateResponse {
     /// The connection handle related to the event
     pub conn_handle: ConnectionHandle,
@@ -1123,6 +1499,16 @@ impl TryFrom<u16> for L2CapRejectionReason {
     }
 }
 
+impl From<L2CapRejectionReason> for u16 {
+    fn from(reason: L2CapRejectionReason) -> Self {
+        match reason {
+            L2CapRejectionReason::CommandNotUnderstood => 0,
+            L2CapRejectionReason::SignalingMtuExceeded => 1,
+            L2CapRejectionReason::InvalidCid => 2,
+        }
+    }
+}
+
 /// Potential results that can be used in the L2CAP connection update response.
 #[derive(Copy, Clone, Debug, PartialEq)]
 pub enum L2CapConnectionUpdateResult {
@@ -1174,9 +1560,28 @@ fn to_l2cap_connection_update_response(
     })
 }
 
+impl L2CapConnectionUpdateResponse {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        let (code, result) = match self.result {
+            L2CapConnectionUpdateResult::CommandRejected(reason) => (0x01, reason.into()),
+            L2CapConnectionUpdateResult::ParametersRejected => (0x13, 0x0001),
+            L2CapConnectionUpdateResult::ParametersUpdated => (0x13, 0x0000),
+        };
+
+        LittleEndian::write_u16(&mut bytes[2..], self.conn_handle.0);
+        bytes[4] = 6;
+        bytes[5] = code;
+        bytes[6] = 0; // The identifier is not kept.
+        LittleEndian::write_u16(&mut bytes[7..], 2);
+        LittleEndian::write_u16(&mut bytes[9..], result);
+
+        11
+    }
+}
+
 /// This event is generated when the central device does not respond to the connection update
 /// request within 30 seconds.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct L2CapProcedureTimeout {
     /// The connection handle related to the event.
     pub conn_handle: ConnectionHandle,
@@ -1234,11 +1639,33 @@ fn to_l2cap_connection_update_request(
     })
 }
 
+impl L2CapConnectionUpdateRequest {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        LittleEndian::write_u16(&mut bytes[2..], self.conn_handle.0);
+        bytes[4] = 11;
+        bytes[5] = self.identifier;
+        LittleEndian::write_u16(&mut bytes[6..], 8);
+        self.conn_interval.copy_into_slice(&mut bytes[8..16]);
+
+        16
+    }
+}
+
+impl PartialEq for L2CapConnectionUpdateRequest {
+    fn eq(&self, other: &Self) -> bool {
+        self.conn_handle == other.conn_handle
+            && self.identifier == other.identifier
+            && self.conn_interval.interval() == other.conn_interval.interval()
+            && self.conn_interval.conn_latency() == other.conn_interval.conn_latency()
+            && self.conn_interval.supervision_timeout() == other.conn_interval.supervision_timeout()
+    }
+}
+
 /// This event is generated when the pairing process has completed successfully or a pairing
 /// procedure timeout has occurred or the pairing has failed. This is to notify the application that
 /// we have paired with a remote device so that it can take further actions or to notify that a
 /// timeout has occurred so that the upper layer can decide to disconnect the link.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct GapPairingComplete {
     /// Connection handle on which the pairing procedure completed
     pub conn_handle: ConnectionHandle,
@@ -1272,6 +1699,16 @@ impl TryFrom<u8> for GapPairingStatus {
     }
 }
 
+impl From<GapPairingStatus> for u8 {
+    fn from(status: GapPairingStatus) -> Self {
+        match status {
+            GapPairingStatus::Success => 0,
+            GapPairingStatus::Timeout => 1,
+            GapPairingStatus::Failed => 2,
+        }
+    }
+}
+
 fn to_gap_pairing_complete(
     buffer: &[u8],
 ) -> Result<GapPairingComplete, hci::event::Error<BlueNRGError>> {
@@ -1282,11 +1719,26 @@ fn to_gap_pairing_complete(
     })
 }
 
+impl GapPairingComplete {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        LittleEndian::write_u16(&mut bytes[2..], self.conn_handle.0);
+        bytes[4] = self.status.into();
+
+        5
+    }
+}
+
 fn to_conn_handle(buffer: &[u8]) -> Result<ConnectionHandle, hci::event::Error<BlueNRGError>> {
     require_len_at_least!(buffer, 4);
     Ok(ConnectionHandle(LittleEndian::read_u16(&buffer[2..])))
 }
 
+fn copy_conn_handle(conn_handle: ConnectionHandle, bytes: &mut [u8]) -> usize {
+    LittleEndian::write_u16(&mut bytes[2..], conn_handle.0);
+
+    4
+}
+
 /// The event is given by the GAP layer to the upper layers when a device is discovered during
 /// scanning as a consequence of one of the GAP procedures started by the upper layers.
 #[derive(Copy, Clone, Debug)]
@@ -1324,7 +1776,7 @@ fn to_gap_device_found(buffer: &[u8]) -> Result<GapDeviceFound, hci::event::Erro
     let data_len = buffer[10] as usize;
     require_len!(buffer, 12 + data_len);
 
-    let rssi = unsafe { mem::transmute::<u8, i8>(buffer[buffer.len() - 1]) };
+    let rssi = buffer[buffer.len() - 1] as i8;
 
     let mut addr = BdAddr([0; 6]);
     addr.0.copy_from_slice(&buffer[4..10]);
@@ -1351,9 +1803,38 @@ fn to_gap_device_found(buffer: &[u8]) -> Result<GapDeviceFound, hci::event::Erro
     Ok(event)
 }
 
+impl GapDeviceFound {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        const RSSI_UNAVAILABLE: i8 = 127;
+
+        bytes[2] = match self.event {
+            GapDeviceFoundEvent::Advertisement => 0,
+            GapDeviceFoundEvent::DirectAdvertisement => 1,
+            GapDeviceFoundEvent::Scan => 2,
+            GapDeviceFoundEvent::NonConnectableAdvertisement => 3,
+            GapDeviceFoundEvent::ScanResponse => 4,
+        };
+        self.bdaddr.copy_into_slice(&mut bytes[3..10]);
+        bytes[10] = self.data_len as u8;
+        bytes[11..11 + self.data_len].copy_from_slice(self.data());
+        bytes[11 + self.data_len] = self.rssi.unwrap_or(RSSI_UNAVAILABLE) as u8;
+
+        12 + self.data_len
+    }
+}
+
+impl PartialEq for GapDeviceFound {
+    fn eq(&self, other: &Self) -> bool {
+        self.event == other.event
+            && self.bdaddr == other.bdaddr
+            && self.data() == other.data()
+            && self.rssi == other.rssi
+    }
+}
+
 /// This event is sent by the GAP to the upper layers when a procedure previously started has been
 /// terminated by the upper layer or has completed for any other reason
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct GapProcedureComplete {
     /// Type of procedure that completed
     pub procedure: GapProcedure,
@@ -1438,6 +1919,16 @@ impl TryFrom<u8> for GapProcedureStatus {
     }
 }
 
+impl From<GapProcedureStatus> for u8 {
+    fn from(status: GapProcedureStatus) -> Self {
+        match status {
+            GapProcedureStatus::Success => 0x00,
+            GapProcedureStatus::Failed => 0x41,
+            GapProcedureStatus::AuthFailure => 0x05,
+        }
+    }
+}
+
 fn to_gap_procedure_complete(
     buffer: &[u8],
 ) -> Result<GapProcedureComplete, hci::event::Error<BlueNRGError>> {
@@ -1476,7 +1967,44 @@ fn to_gap_procedure_complete(
     })
 }
 
-#[cfg(not(feature = "ms"))]
+impl GapProcedureComplete {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        bytes[3] = self.status.into();
+        match self.procedure {
+            GapProcedure::LimitedDiscovery => {
+                bytes[2] = 0x01;
+                4
+            }
+            GapProcedure::GeneralDiscovery => {
+                bytes[2] = 0x02;
+                4
+            }
+            GapProcedure::NameDiscovery(name_len, ref name) => {
+                bytes[2] = 0x04;
+                bytes[4..4 + name_len].copy_from_slice(&name.0[..name_len]);
+                4 + name_len
+            }
+            GapProcedure::AutoConnectionEstablishment => {
+                bytes[2] = 0x08;
+                4
+            }
+            GapProcedure::GeneralConnectionEstablishment(addr) => {
+                bytes[2] = 0x10;
+                bytes[4..10].copy_from_slice(&addr.0);
+                10
+            }
+            GapProcedure::SelectiveConnectionEstablishment => {
+                bytes[2] = 0x20;
+                4
+            }
+            GapProcedure::DirectConnectionEstablishment => {
+                bytes[2] = 0x40;
+                4
+            }
+        }
+    }
+}
+
 fn to_gap_reconnection_address(buffer: &[u8]) -> Result<BdAddr, hci::event::Error<BlueNRGError>> {
     require_len!(buffer, 8);
     let mut addr = BdAddr([0; 6]);
@@ -1484,6 +2012,38 @@ fn to_gap_reconnection_address(buffer: &[u8]) -> Result<BdAddr, hci::event::Erro
     Ok(addr)
 }
 
+/// This event is generated during LE Secure Connections pairing with numeric comparison.
+#[cfg(feature = "bluenrg2")]
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct GapNumericComparisonValue {
+    /// Connection handle on which pairing is in progress.
+    pub conn_handle: ConnectionHandle,
+
+    /// The value to show to the user, from 0 to 999999.
+    pub value: u32,
+}
+
+#[cfg(feature = "bluenrg2")]
+fn to_gap_numeric_comparison_value(
+    buffer: &[u8],
+) -> Result<GapNumericComparisonValue, hci::event::Error<BlueNRGError>> {
+    require_len!(buffer, 8);
+    Ok(GapNumericComparisonValue {
+        conn_handle: ConnectionHandle(LittleEndian::read_u16(&buffer[2..])),
+        value: LittleEndian::read_u32(&buffer[4..]),
+    })
+}
+
+#[cfg(feature = "bluenrg2")]
+impl GapNumericComparisonValue {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        LittleEndian::write_u16(&mut bytes[2..], self.conn_handle.0);
+        LittleEndian::write_u32(&mut bytes[4..], self.value);
+
+        8
+    }
+}
+
 /// This event is generated to the application by the ATT server when a client modifies any
 /// attribute on the server, as consequence of one of the following ATT procedures:
 /// - write without response
@@ -1498,14 +2058,12 @@ pub struct GattAttributeModified {
     ///  Handle of the attribute that was modified
     pub attr_handle: AttributeHandle,
 
-    /// Offset of the reported value inside the attribute.
-    #[cfg(feature = "ms")]
+    /// Offset of the reported value inside the attribute. Always 0 for the original BlueNRG.
     pub offset: usize,
 
     /// If the entire value of the attribute does not fit inside a single GattAttributeModified
     /// event, this is true to notify that other GattAttributeModified events will follow to report
-    /// the remaining value.
-    #[cfg(feature = "ms")]
+    /// the remaining value. Always false for the original BlueNRG.
     pub continued: bool,
 
     /// Number of valid bytes in |data|.
@@ -1533,7 +2091,6 @@ pub struct AttributeHandle(pub u16);
 const MAX_ATTRIBUTE_LEN: usize = 248;
 
 impl Debug for GattAttributeModified {
-    #[cfg(feature = "ms")]
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
         write!(
             f,
@@ -1545,20 +2102,9 @@ impl Debug for GattAttributeModified {
             first_16(self.data()),
         )
     }
-
-    #[cfg(not(feature = "ms"))]
-    fn fmt(&self, f: &mut Formatter) -> FmtResult {
-        write!(
-            f,
-            "{{conn_handle: {:?}, attr_handle: {:?}, data: {:?}}}",
-            self.conn_handle,
-            self.attr_handle,
-            first_16(self.data()),
-        )
-    }
 }
 
-#[cfg(feature = "ms")]
+#[cfg(not(feature = "bluenrg2"))]
 fn to_gatt_attribute_modified(
     buffer: &[u8],
 ) -> Result<GattAttributeModified, hci::event::Error<BlueNRGError>> {
@@ -1581,9 +2127,34 @@ fn to_gatt_attribute_modified(
     })
 }
 
-#[cfg(not(feature = "ms"))]
+// The BlueNRG-1 and BlueNRG-2 put the offset before the data length, and use 2 bytes for the data
+// length.
+#[cfg(feature = "bluenrg2")]
 fn to_gatt_attribute_modified(
     buffer: &[u8],
+) -> Result<GattAttributeModified, hci::event::Error<BlueNRGError>> {
+    require_len_at_least!(buffer, 10);
+
+    let data_len = LittleEndian::read_u16(&buffer[8..]) as usize;
+    require_len!(buffer, 10 + data_len);
+
+    let mut data = [0; MAX_ATTRIBUTE_LEN];
+    data[..data_len].copy_from_slice(&buffer[10..]);
+
+    let offset_field = LittleEndian::read_u16(&buffer[6..]);
+    Ok(GattAttributeModified {
+        conn_handle: ConnectionHandle(LittleEndian::read_u16(&buffer[2..])),
+        attr_handle: AttributeHandle(LittleEndian::read_u16(&buffer[4..])),
+        offset: (offset_field & 0x7FFF) as usize,
+        continued: (offset_field & 0x8000) > 0,
+        data_len,
+        data_buf: data,
+    })
+}
+
+// The original BlueNRG does not report the offset.
+fn to_original_gatt_attribute_modified(
+    buffer: &[u8],
 ) -> Result<GattAttributeModified, hci::event::Error<BlueNRGError>> {
     require_len_at_least!(buffer, 7);
 
@@ -1596,13 +2167,65 @@ fn to_gatt_attribute_modified(
     Ok(GattAttributeModified {
         conn_handle: ConnectionHandle(LittleEndian::read_u16(&buffer[2..])),
         attr_handle: AttributeHandle(LittleEndian::read_u16(&buffer[4..])),
+        offset: 0,
+        continued: false,
         data_len,
         data_buf: data,
     })
 }
 
+impl GattAttributeModified {
+    // Packs the offset and the continued flag the way the BlueNRG-MS and BlueNRG-1 report them.
+    fn offset_field(&self) -> u16 {
+        let continued = if self.continued { 0x8000 } else { 0 };
+
+        (self.offset as u16 & 0x7FFF) | continued
+    }
+
+    #[cfg(not(feature = "bluenrg2"))]
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        LittleEndian::write_u16(&mut bytes[2..], self.conn_handle.0);
+        LittleEndian::write_u16(&mut bytes[4..], self.attr_handle.0);
+        bytes[6] = self.data_len as u8;
+        LittleEndian::write_u16(&mut bytes[7..], self.offset_field());
+        bytes[9..9 + self.data_len].copy_from_slice(self.data());
+
+        9 + self.data_len
+    }
+
+    #[cfg(feature = "bluenrg2")]
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        LittleEndian::write_u16(&mut bytes[2..], self.conn_handle.0);
+        LittleEndian::write_u16(&mut bytes[4..], self.attr_handle.0);
+        LittleEndian::write_u16(&mut bytes[6..], self.offset_field());
+        LittleEndian::write_u16(&mut bytes[8..], self.data_len as u16);
+        bytes[10..10 + self.data_len].copy_from_slice(self.data());
+
+        10 + self.data_len
+    }
+
+    fn copy_into_original_slice(&self, bytes: &mut [u8]) -> usize {
+        LittleEndian::write_u16(&mut bytes[2..], self.conn_handle.0);
+        LittleEndian::write_u16(&mut bytes[4..], self.attr_handle.0);
+        bytes[6] = self.data_len as u8;
+        bytes[7..7 + self.data_len].copy_from_slice(self.data());
+
+        7 + self.data_len
+    }
+}
+
+impl PartialEq for GattAttributeModified {
+    fn eq(&self, other: &Self) -> bool {
+        self.conn_handle == other.conn_handle
+            && self.attr_handle == other.attr_handle
+            && self.offset == other.offset
+            && self.continued == other.continued
+            && self.data() == other.data()
+    }
+}
+
 /// This event is generated in response to an Exchange MTU request.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct AttExchangeMtuResponse {
     ///  The connection handle related to the response.
     pub conn_handle: ConnectionHandle,
@@ -1621,9 +2244,19 @@ fn to_att_exchange_mtu_resp(
     })
 }
 
+impl AttExchangeMtuResponse {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        LittleEndian::write_u16(&mut bytes[2..], self.conn_handle.0);
+        bytes[4] = 1;
+        LittleEndian::write_u16(&mut bytes[5..], self.server_rx_mtu as u16);
+
+        7
+    }
+}
+
 /// This event is generated in response to a Find Information Request. See Find Information Response
 /// in Bluetooth Core v4.0 spec.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct AttFindInformationResponse {
     /// The connection handle related to the response
     pub conn_handle: ConnectionHandle,
@@ -1639,7 +2272,7 @@ impl AttFindInformationResponse {
     /// split across response packets; this also implies that a handleUUID pair shall fit into a
     /// single response packet. The handle-UUID pairs shall be returned in ascending order of
     /// attribute handles.
-    pub fn handle_uuid_pair_iter(&self) -> HandleUuidPairIterator {
+    pub fn handle_uuid_pair_iter(&self) -> HandleUuidPairIterator<'_> {
         match self.handle_uuid_pairs {
             HandleUuidPairs::Format16(count, ref data) => {
                 HandleUuidPairIterator::Format16(HandleUuid16PairIterator {
@@ -1670,7 +2303,7 @@ const MAX_FORMAT128_PAIR_COUNT: usize = 13;
 
 /// One format of the handle-UUID pairs in the [`AttFindInformationResponse`] event. The UUIDs are
 /// 16 bits.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct HandleUuid16Pair {
     /// Attribute handle
     pub handle: AttributeHandle,
@@ -1680,7 +2313,7 @@ pub struct HandleUuid16Pair {
 
 /// One format of the handle-UUID pairs in the [`AttFindInformationResponse`] event. The UUIDs are
 /// 128 bits.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct HandleUuid128Pair {
     /// Attribute handle
     pub handle: AttributeHandle,
@@ -1729,6 +2362,22 @@ impl Debug for HandleUuidPairs {
     }
 }
 
+impl PartialEq for HandleUuidPairs {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                HandleUuidPairs::Format16(count, pairs),
+                HandleUuidPairs::Format16(other_count, other_pairs),
+            ) => pairs[..*count] == other_pairs[..*other_count],
+            (
+                HandleUuidPairs::Format128(count, pairs),
+                HandleUuidPairs::Format128(other_count, other_pairs),
+            ) => pairs[..*count] == other_pairs[..*other_count],
+            _ => false,
+        }
+    }
+}
+
 /// Possible iterators over handle-UUID pairs that can be returnedby the [ATT find information
 /// response](AttFindInformationResponse). All pairs from the same event have the same format.
 pub enum HandleUuidPairIterator<'a> {
@@ -1841,6 +2490,39 @@ fn to_handle_uuid128_pairs(buffer: &[u8]) -> Result<HandleUuidPairs, BlueNRGErro
     Ok(HandleUuidPairs::Format128(count, pairs))
 }
 
+impl AttFindInformationResponse {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        LittleEndian::write_u16(&mut bytes[2..], self.conn_handle.0);
+        let pairs_len = match self.handle_uuid_pairs {
+            HandleUuidPairs::Format16(count, ref pairs) => {
+                const PAIR_LEN: usize = 4;
+
+                bytes[5] = 1;
+                for (i, pair) in pairs[..count].iter().enumerate() {
+                    let index = 6 + i * PAIR_LEN;
+                    LittleEndian::write_u16(&mut bytes[index..], pair.handle.0);
+                    LittleEndian::write_u16(&mut bytes[2 + index..], pair.uuid.0);
+                }
+                count * PAIR_LEN
+            }
+            HandleUuidPairs::Format128(count, ref pairs) => {
+                const PAIR_LEN: usize = 18;
+
+                bytes[5] = 2;
+                for (i, pair) in pairs[..count].iter().enumerate() {
+                    let index = 6 + i * PAIR_LEN;
+                    LittleEndian::write_u16(&mut bytes[index..], pair.handle.0);
+                    bytes[2 + index..PAIR_LEN + index].copy_from_slice(&pair.uuid.0);
+                }
+                count * PAIR_LEN
+            }
+        };
+        bytes[4] = (1 + pairs_len) as u8;
+
+        6 + pairs_len
+    }
+}
+
 /// This event is generated in response to a Find By Type Value Request.
 #[derive(Copy, Clone)]
 pub struct AttFindByTypeValueResponse {
@@ -1857,7 +2539,7 @@ pub struct AttFindByTypeValueResponse {
 impl AttFindByTypeValueResponse {
     /// Returns an iterator over the Handles Information List as defined in Bluetooth Core v4.1
     /// spec.
-    pub fn handle_pairs_iter(&self) -> HandleInfoPairIterator {
+    pub fn handle_pairs_iter(&self) -> HandleInfoPairIterator<'_> {
         HandleInfoPairIterator {
             event: self,
             next_index: 0,
@@ -1884,7 +2566,7 @@ impl Debug for AttFindByTypeValueResponse {
 const MAX_HANDLE_INFO_PAIR_COUNT: usize = 62;
 
 /// Simple container for the handle information returned in [`AttFindByTypeValueResponse`].
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct HandleInfoPair {
     /// Attribute handle
     pub attribute: AttributeHandle,
@@ -1951,6 +2633,29 @@ fn to_att_find_by_value_type_response(
     })
 }
 
+impl AttFindByTypeValueResponse {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        const PAIR_LEN: usize = 4;
+
+        LittleEndian::write_u16(&mut bytes[2..], self.conn_handle.0);
+        bytes[4] = (self.handle_pair_count * PAIR_LEN) as u8;
+        for (i, pair) in self.handle_pairs_iter().enumerate() {
+            let index = 5 + i * PAIR_LEN;
+            LittleEndian::write_u16(&mut bytes[index..], pair.attribute.0);
+            LittleEndian::write_u16(&mut bytes[2 + index..], pair.group_end.0);
+        }
+
+        5 + self.handle_pair_count * PAIR_LEN
+    }
+}
+
+impl PartialEq for AttFindByTypeValueResponse {
+    fn eq(&self, other: &Self) -> bool {
+        self.conn_handle == other.conn_handle
+            && self.handles[..self.handle_pair_count] == other.handles[..other.handle_pair_count]
+    }
+}
+
 /// This event is generated in response to a Read By Type Request.
 #[derive(Copy, Clone)]
 pub struct AttReadByTypeResponse {
@@ -1987,7 +2692,7 @@ impl Debug for AttReadByTypeResponse {
 
 impl AttReadByTypeResponse {
     /// Return an iterator over all valid handle-value pairs returned with the response.
-    pub fn handle_value_pair_iter(&self) -> HandleValuePairIterator {
+    pub fn handle_value_pair_iter(&self) -> HandleValuePairIterator<'_> {
         HandleValuePairIterator {
             event: self,
             index: 0,
@@ -2059,6 +2764,26 @@ fn to_att_read_by_type_response(
     })
 }
 
+impl AttReadByTypeResponse {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        LittleEndian::write_u16(&mut bytes[2..], self.conn_handle.0);
+        bytes[4] = (1 + self.data_len) as u8;
+        bytes[5] = (2 + self.value_len) as u8;
+        bytes[6..6 + self.data_len].copy_from_slice(&self.handle_value_pair_buf[..self.data_len]);
+
+        6 + self.data_len
+    }
+}
+
+impl PartialEq for AttReadByTypeResponse {
+    fn eq(&self, other: &Self) -> bool {
+        self.conn_handle == other.conn_handle
+            && self.value_len == other.value_len
+            && self.handle_value_pair_buf[..self.data_len]
+                == other.handle_value_pair_buf[..other.data_len]
+    }
+}
+
 /// This event is generated in response to a Read Request.
 #[derive(Copy, Clone)]
 pub struct AttReadResponse {
@@ -2110,6 +2835,22 @@ fn to_att_read_response(buffer: &[u8]) -> Result<AttReadResponse, hci::event::Er
     })
 }
 
+impl AttReadResponse {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        LittleEndian::write_u16(&mut bytes[2..], self.conn_handle.0);
+        bytes[4] = self.value_len as u8;
+        bytes[5..5 + self.value_len].copy_from_slice(self.value());
+
+        5 + self.value_len
+    }
+}
+
+impl PartialEq for AttReadResponse {
+    fn eq(&self, other: &Self) -> bool {
+        self.conn_handle == other.conn_handle && self.value() == other.value()
+    }
+}
+
 /// This event is generated in response to a Read By Group Type Request. See the Bluetooth Core v4.1
 /// spec, Vol 3, section 3.4.4.9 and 3.4.4.10.
 #[derive(Copy, Clone)]
@@ -2137,7 +2878,7 @@ const MAX_ATTRIBUTE_DATA_BUF_LEN: usize = 249;
 
 impl AttReadByGroupTypeResponse {
     /// Create and return an iterator for the attribute data returned with the response.
-    pub fn attribute_data_iter(&self) -> AttributeDataIterator {
+    pub fn attribute_data_iter(&self) -> AttributeDataIterator<'_> {
         AttributeDataIterator {
             event: self,
             next_index: 0,
@@ -2226,6 +2967,26 @@ fn to_att_read_by_group_type_response(
     })
 }
 
+impl AttReadByGroupTypeResponse {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        LittleEndian::write_u16(&mut bytes[2..], self.conn_handle.0);
+        bytes[4] = (1 + self.data_len) as u8;
+        bytes[5] = self.attribute_group_len as u8;
+        bytes[6..6 + self.data_len].copy_from_slice(&self.attribute_data_buf[..self.data_len]);
+
+        6 + self.data_len
+    }
+}
+
+impl PartialEq for AttReadByGroupTypeResponse {
+    fn eq(&self, other: &Self) -> bool {
+        self.conn_handle == other.conn_handle
+            && self.attribute_group_len == other.attribute_group_len
+            && self.attribute_data_buf[..self.data_len]
+                == other.attribute_data_buf[..other.data_len]
+    }
+}
+
 /// This event is generated in response to a Prepare Write Request. See the Bluetooth Core v4.1
 /// spec, Vol 3, Part F, section 3.4.6.1 and 3.4.6.2
 #[derive(Copy, Clone)]
@@ -2286,6 +3047,27 @@ fn to_att_prepare_write_response(
     })
 }
 
+impl AttPrepareWriteResponse {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        LittleEndian::write_u16(&mut bytes[2..], self.conn_handle.0);
+        bytes[4] = (4 + self.value_len) as u8;
+        LittleEndian::write_u16(&mut bytes[5..], self.attribute_handle.0);
+        LittleEndian::write_u16(&mut bytes[7..], self.offset as u16);
+        bytes[9..9 + self.value_len].copy_from_slice(self.value());
+
+        9 + self.value_len
+    }
+}
+
+impl PartialEq for AttPrepareWriteResponse {
+    fn eq(&self, other: &Self) -> bool {
+        self.conn_handle == other.conn_handle
+            && self.attribute_handle == other.attribute_handle
+            && self.offset == other.offset
+            && self.value() == other.value()
+    }
+}
+
 /// Defines the attribute value returned by a [GATT Indication](BlueNRGEvent::GattIndication) or
 /// [GATT Notification](BlueNRGEvent::GattNotification) event.
 #[derive(Copy, Clone)]
@@ -2360,9 +3142,37 @@ fn to_write_permit_request(
     })
 }
 
+impl AttributeValue {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        LittleEndian::write_u16(&mut bytes[2..], self.conn_handle.0);
+        bytes[4] = (2 + self.value_len) as u8;
+        LittleEndian::write_u16(&mut bytes[5..], self.attribute_handle.0);
+        bytes[7..7 + self.value_len].copy_from_slice(self.value());
+
+        7 + self.value_len
+    }
+
+    fn copy_into_write_permit_slice(&self, bytes: &mut [u8]) -> usize {
+        LittleEndian::write_u16(&mut bytes[2..], self.conn_handle.0);
+        LittleEndian::write_u16(&mut bytes[4..], self.attribute_handle.0);
+        bytes[6] = self.value_len as u8;
+        bytes[7..7 + self.value_len].copy_from_slice(self.value());
+
+        7 + self.value_len
+    }
+}
+
+impl PartialEq for AttributeValue {
+    fn eq(&self, other: &Self) -> bool {
+        self.conn_handle == other.conn_handle
+            && self.attribute_handle == other.attribute_handle
+            && self.value() == other.value()
+    }
+}
+
 /// This event is generated when a GATT client procedure completes either with error or
 /// successfully.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct GattProcedureComplete {
     /// The connection handle for which the GATT procedure has completed.
     pub conn_handle: ConnectionHandle,
@@ -2394,6 +3204,15 @@ impl TryFrom<u8> for GattProcedureStatus {
     }
 }
 
+impl From<GattProcedureStatus> for u8 {
+    fn from(status: GattProcedureStatus) -> Self {
+        match status {
+            GattProcedureStatus::Success => 0x00,
+            GattProcedureStatus::Failed => 0x41,
+        }
+    }
+}
+
 fn to_gatt_procedure_complete(
     buffer: &[u8],
 ) -> Result<GattProcedureComplete, hci::event::Error<BlueNRGError>> {
@@ -2405,9 +3224,19 @@ fn to_gatt_procedure_complete(
     })
 }
 
+impl GattProcedureComplete {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        LittleEndian::write_u16(&mut bytes[2..], self.conn_handle.0);
+        bytes[4] = 1;
+        bytes[5] = self.status.into();
+
+        6
+    }
+}
+
 /// The Error Response is used to state that a given request cannot be performed, and to provide the
 /// reason. See the Bluetooth Core Specification, v4.1, Vol 3, Part F, Section 3.4.1.1.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct AttErrorResponse {
     /// The connection handle related to the event.
     pub conn_handle: ConnectionHandle,
@@ -2716,6 +3545,18 @@ fn to_att_error_response(
     })
 }
 
+impl AttErrorResponse {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        LittleEndian::write_u16(&mut bytes[2..], self.conn_handle.0);
+        bytes[4] = 4;
+        bytes[5] = self.request as u8;
+        LittleEndian::write_u16(&mut bytes[6..], self.attribute_handle.0);
+        bytes[8] = self.error as u8;
+
+        9
+    }
+}
+
 /// This event is given to the application when a read request or read blob request is received by
 /// the server from the client. This event will be given to the application only if the event bit
 /// for this event generation is set when the characteristic was added. On receiving this event, the
@@ -2724,7 +3565,7 @@ fn to_att_error_response(
 /// send the response to the client.
 ///
 /// See the Bluetooth Core v4.1 spec, Vol 3, Part F, section 3.4.4.
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct AttReadPermitRequest {
     /// Handle of the connection on which there was the request to read the attribute
     pub conn_handle: ConnectionHandle,
@@ -2747,6 +3588,17 @@ fn to_att_read_permit_request(
     })
 }
 
+impl AttReadPermitRequest {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        LittleEndian::write_u16(&mut bytes[2..], self.conn_handle.0);
+        LittleEndian::write_u16(&mut bytes[4..], self.attribute_handle.0);
+        bytes[6] = 2;
+        LittleEndian::write_u16(&mut bytes[7..], self.offset as u16);
+
+        9
+    }
+}
+
 /// This event is given to the application when a read multiple request or read by type request is
 /// received by the server from the client. This event will be given to the application only if the
 /// event bit for this event generation is set when the characteristic was added.  On receiving this
@@ -2815,11 +3667,28 @@ fn to_att_read_multiple_permit_request(
     })
 }
 
+impl AttReadMultiplePermitRequest {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        LittleEndian::write_u16(&mut bytes[2..], self.conn_handle.0);
+        bytes[4] = (2 * self.handles_len) as u8;
+        for (i, handle) in self.handles().iter().enumerate() {
+            LittleEndian::write_u16(&mut bytes[5 + 2 * i..], handle.0);
+        }
+
+        5 + 2 * self.handles_len
+    }
+}
+
+impl PartialEq for AttReadMultiplePermitRequest {
+    fn eq(&self, other: &Self) -> bool {
+        self.conn_handle == other.conn_handle && self.handles() == other.handles()
+    }
+}
+
 /// This event is raised when the number of available TX buffers is above a threshold TH (TH = 2).
 /// The event will be given only if a previous ACI command returned with
 /// [`InsufficientResources`](AttError::InsufficientResources).
-#[cfg(feature = "ms")]
-#[derive(Copy, Clone, Debug)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct GattTxPoolAvailable {
     /// Connection handle on which the GATT procedure is running.
     pub conn_handle: ConnectionHandle,
@@ -2827,7 +3696,6 @@ pub struct GattTxPoolAvailable {
     pub available_buffers: usize,
 }
 
-#[cfg(feature = "ms")]
 fn to_gatt_tx_pool_available(
     buffer: &[u8],
 ) -> Result<GattTxPoolAvailable, hci::event::Error<BlueNRGError>> {
@@ -2838,6 +3706,15 @@ fn to_gatt_tx_pool_available(
     })
 }
 
+impl GattTxPoolAvailable {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        LittleEndian::write_u16(&mut bytes[2..], self.conn_handle.0);
+        LittleEndian::write_u16(&mut bytes[4..], self.available_buffers as u16);
+
+        6
+    }
+}
+
 /// This event is given to the application when a prepare write request is received by the server
 /// from the client.
 ///
@@ -2848,7 +3725,6 @@ fn to_gatt_tx_pool_available(
 /// will be modified by the stack.  If the write is rejected by the application, then the value of
 /// the attribute will not be modified and an error response will be sent to the client, with the
 /// error code as specified by the application.
-#[cfg(feature = "ms")]
 #[derive(Copy, Clone)]
 pub struct AttPrepareWritePermitRequest {
     /// Connection handle on which the GATT procedure is running.
@@ -2866,10 +3742,8 @@ pub struct AttPrepareWritePermitRequest {
 
 // The maximum number of bytes in the buffer is the max HCI packet size (255) less the other data in
 // the packet.
-#[cfg(feature = "ms")]
 const MAX_PREPARE_WRITE_PERMIT_REQ_VALUE_LEN: usize = 246;
 
-#[cfg(feature = "ms")]
 impl Debug for AttPrepareWritePermitRequest {
     fn fmt(&self, f: &mut Formatter) -> FmtResult {
         write!(
@@ -2883,7 +3757,6 @@ impl Debug for AttPrepareWritePermitRequest {
     }
 }
 
-#[cfg(feature = "ms")]
 impl AttPrepareWritePermitRequest {
     /// Returns the data to be written.
     pub fn value(&self) -> &[u8] {
@@ -2891,7 +3764,6 @@ impl AttPrepareWritePermitRequest {
     }
 }
 
-#[cfg(feature = "ms")]
 fn to_att_prepare_write_permit_request(
     buffer: &[u8],
 ) -> Result<AttPrepareWritePermitRequest, hci::event::Error<BlueNRGError>> {
@@ -2910,3 +3782,24 @@ fn to_att_prepare_write_permit_request(
         value_buf,
     })
 }
+
+impl AttPrepareWritePermitRequest {
+    fn copy_into_slice(&self, bytes: &mut [u8]) -> usize {
+        LittleEndian::write_u16(&mut bytes[2..], self.conn_handle.0);
+        LittleEndian::write_u16(&mut bytes[4..], self.attribute_handle.0);
+        LittleEndian::write_u16(&mut bytes[6..], self.offset as u16);
+        bytes[8] = self.value_len as u8;
+        bytes[9..9 + self.value_len].copy_from_slice(self.value());
+
+        9 + self.value_len
+    }
+}
+
+impl PartialEq for AttPrepareWritePermitRequest {
+    fn eq(&self, other: &Self) -> bool {
+        self.conn_handle == other.conn_handle
+            && self.attribute_handle == other.attribute_handle
+            && self.offset == other.offset
+            && self.value() == other.value()
+    }
+}
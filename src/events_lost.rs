@@ -0,0 +1,206 @@
+//! Accounting for, and recovery from, events the controller dropped.
+//!
+//! If the host does not read events quickly enough, the controller drops them, and later reports
+//! which kinds it dropped with the [`EventsLost`](BlueNRGEvent::EventsLost) event. A [`Monitor`]
+//! counts the lost events of each kind, reports them through [`Hooks`], and recovers the host
+//! state that some of the losses corrupt:
+//!
+//! - After a lost [Disconnection Complete](hci::Event::DisconnectionComplete) event, the host may
+//!   believe a connection is still up. The monitor re-queries the state of every link with
+//!   [`get_link_status`](crate::hal::Commands::get_link_status).
+//! - After a lost [GATT Procedure Complete](BlueNRGEvent::GattProcedureComplete) event, a GATT
+//!   client procedure may never finish. The monitor fails every procedure that is still waiting
+//!   for it.
+//!
+//! The monitor only knows about GATT client procedures the application
+//! [registers](Monitor::start_gatt_procedure) when it starts them.
+
+use crate::event::command::HalLinkStatus;
+use crate::event::{BlueNRGEvent, EventFlags};
+use crate::request::{self, Requester};
+use hci::{ConnectionHandle, Event};
+
+/// Number of kinds of events the controller can report as lost: one for each bit of
+/// [`EventFlags`].
+const EVENT_KINDS: usize = 64;
+
+/// Callbacks for a [`Monitor`]. Every method does nothing by default.
+pub trait Hooks {
+    /// Called for every [`EventsLost`](BlueNRGEvent::EventsLost) event, after the counters are
+    /// updated and before the monitor recovers.
+    fn events_lost(&mut self, _flags: EventFlags) {}
+
+    /// Called with the state of every link, after a lost [Disconnection
+    /// Complete](hci::Event::DisconnectionComplete) event.
+    fn link_status(&mut self, _status: &HalLinkStatus) {}
+
+    /// Called for each GATT client procedure that was waiting for a lost [GATT Procedure
+    /// Complete](BlueNRGEvent::GattProcedureComplete) event. The procedure is no longer pending.
+    fn gatt_procedure_failed(&mut self, _conn_handle: ConnectionHandle) {}
+}
+
+/// Hooks that do nothing.
+impl Hooks for () {}
+
+/// Error returned by [`Monitor::start_gatt_procedure`] when `N` procedures are already pending.
+/// Includes the connection handle of the procedure that could not be registered.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct TooManyProcedures(pub ConnectionHandle);
+
+/// Counts lost events, and recovers from their loss. Tracks up to `N` pending GATT client
+/// procedures.
+///
+/// `new` is `const`, so the monitor can be placed in a `static`.
+pub struct Monitor<const N: usize> {
+    counts: [u32; EVENT_KINDS],
+    procedures: [Option<ConnectionHandle>; N],
+}
+
+impl<const N: usize> Default for Monitor<N> {
+    fn default() -> Monitor<N> {
+        Monitor::new()
+    }
+}
+
+impl<const N: usize> Monitor<N> {
+    /// Returns a monitor with all counters at zero and no pending GATT procedures.
+    pub const fn new() -> Monitor<N> {
+        Monitor {
+            counts: [0; EVENT_KINDS],
+            procedures: [None; N],
+        }
+    }
+
+    /// Returns the number of times events of the given kinds were reported lost. If `flags` has
+    /// more than one flag set, returns the sum of their counters.
+    ///
+    /// The controller reports each kind at most once per [`EventsLost`](BlueNRGEvent::EventsLost)
+    /// event, however many events of that kind it dropped.
+    pub fn count(&self, flags: EventFlags) -> u32 {
+        (0..EVENT_KINDS)
+            .filter(|bit| flags.bits() & (1 << bit) != 0)
+            .fold(0, |sum, bit| sum.saturating_add(self.counts[bit]))
+    }
+
+    /// Returns an iterator over the kinds of events that were reported lost, each with its count.
+    pub fn counts(&self) -> impl Iterator<Item = (EventFlags, u32)> + '_ {
+        self.counts
+            .iter()
+            .enumerate()
+            .filter(|(_, count)| **count > 0)
+            .map(|(bit, count)| (EventFlags::from_bits_truncate(1 << bit), *count))
+    }
+
+    /// Resets every counter to zero.
+    pub fn clear_counts(&mut self) {
+        self.counts = [0; EVENT_KINDS];
+    }
+
+    /// Registers a GATT client procedure that was started on the given connection, and will end
+    /// with a [GATT Procedure Complete](BlueNRGEvent::GattProcedureComplete) event.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`TooManyProcedures`] if `N` procedures are already pending.
+    pub fn start_gatt_procedure(
+        &mut self,
+        conn_handle: ConnectionHandle,
+    ) -> Result<(), TooManyProcedures> {
+        let slot = self
+            .procedures
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(TooManyProcedures(conn_handle))?;
+        *slot = Some(conn_handle);
+
+        Ok(())
+    }
+
+    /// Returns true if a GATT client procedure is pending on the given connection.
+    pub fn gatt_procedure_pending(&self, conn_handle: ConnectionHandle) -> bool {
+        self.procedures.contains(&Some(conn_handle))
+    }
+
+    fn end_gatt_procedures(&mut self, conn_handle: ConnectionHandle) {
+        for slot in self.procedures.iter_mut() {
+            if *slot == Some(conn_handle) {
+                *slot = None;
+            }
+        }
+    }
+
+    /// Checks an event read from the controller.
+    ///
+    /// - A [GATT Procedure Complete](BlueNRGEvent::GattProcedureComplete) event ends the pending
+    ///   procedures on its connection, and so does a successful [Disconnection
+    ///   Complete](hci::Event::DisconnectionComplete) event.
+    /// - An [`EventsLost`](BlueNRGEvent::EventsLost) event increments the counter of each lost
+    ///   kind of event and is passed to [`Hooks::events_lost`]. Then, if a GATT Procedure Complete
+    ///   event was lost, every pending procedure is passed to [`Hooks::gatt_procedure_failed`]
+    ///   and forgotten. Finally, if a Disconnection Complete event was lost, the link status is
+    ///   read with `requester` and passed to [`Hooks::link_status`]. The read gives up when
+    ///   `timer`, started with `timeout`, expires (see [`Requester::call_with_timeout`]).
+    ///
+    /// Other events are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns any error from reading the link status, including
+    /// [`Timeout`](request::Error::Timeout). Events that arrive before its response are buffered
+    /// by `requester`, and should be passed to `handle` when the application reads them.
+    pub fn handle<C, H, Timer, const M: usize>(
+        &mut self,
+        requester: &mut Requester<M>,
+        controller: &mut C,
+        event: &Event<BlueNRGEvent>,
+        hooks: &mut H,
+        timer: &mut Timer,
+        timeout: Timer::Time,
+    ) -> Result<(), request::Error<C::Error>>
+    where
+        C: hci::Controller<Header = hci::host::uart::CommandHeader, Vendor = crate::BlueNRGTypes>
+            + crate::Firmware,
+        H: Hooks,
+        Timer: emhal::timer::CountDown,
+    {
+        let flags = match event {
+            Event::Vendor(BlueNRGEvent::EventsLost(flags)) => *flags,
+            Event::Vendor(BlueNRGEvent::GattProcedureComplete(complete)) => {
+                self.end_gatt_procedures(complete.conn_handle);
+                return Ok(());
+            }
+            Event::DisconnectionComplete(complete) if complete.status == hci::Status::Success => {
+                self.end_gatt_procedures(complete.conn_handle);
+                return Ok(());
+            }
+            _ => return Ok(()),
+        };
+
+        for (bit, count) in self.counts.iter_mut().enumerate() {
+            if flags.bits() & (1 << bit) != 0 {
+                *count = count.saturating_add(1);
+            }
+        }
+        hooks.events_lost(flags);
+
+        if flags.contains(EventFlags::GATT_PROCEDURE_COMPLETE) {
+            for slot in self.procedures.iter_mut() {
+                if let Some(conn_handle) = slot.take() {
+                    hooks.gatt_procedure_failed(conn_handle);
+                }
+            }
+        }
+
+        if flags.contains(EventFlags::DISCONNECTION_COMPLETE) {
+            let status: HalLinkStatus = requester.call_with_timeout(
+                controller,
+                |controller| crate::hal::Commands::get_link_status(controller),
+                timer,
+                timeout,
+            )?;
+            hooks.link_status(&status);
+        }
+
+        Ok(())
+    }
+}
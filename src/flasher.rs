@@ -0,0 +1,456 @@
+//! Replacing the controller's Bluetooth stack image with the [`updater`] commands.
+//!
+//! [`Flasher`] drives the updater one step at a time: each call to [`Flasher::poll`] sends at most
+//! one command and reads the events that are available, so it can run from the application's main
+//! loop without blocking. The sequence is:
+//!  1. [Start](crate::updater::Commands::start) the updater, and wait for the controller to report
+//!     that it is running it.
+//!  2. [Erase the blue flag](crate::updater::Commands::erase_blue_flag), so the controller keeps
+//!     starting in the updater until the new image is complete.
+//!  3. For each sector of the image: [erase](crate::updater::Commands::erase_sector) it,
+//!     [program](crate::updater::Commands::program_data_block) it in blocks of [`BLOCK_LEN`]
+//!     bytes, and check its [CRC](crate::updater::Commands::calc_crc) against [`crc32`]. A sector
+//!     whose CRC does not match is written again, up to [`SECTOR_ATTEMPTS`] times.
+//!  4. [Restore the blue flag](crate::updater::Commands::reset_blue_flag), once every sector is
+//!     verified.
+//!  5. [Reboot](crate::updater::Commands::reboot) into the new stack.
+//!
+//! If the update is interrupted (for example, by a power loss), the controller starts in the
+//! updater, because the blue flag is still erased. [`Flasher::resume`] continues after the sectors
+//! that were already [verified](Flasher::verified_sectors).
+
+use crate::event::command::ReturnParameters;
+use crate::event::{BlueNRGError, BlueNRGEvent, ResetReason};
+use crate::updater::{self, Commands as UpdaterCommands, FIRMWARE_ADDRESS, SECTOR_SIZE};
+use crate::BlueNRGTypes;
+use core::cmp::min;
+use hci::event::command::ReturnParameters as HciReturnParameters;
+use hci::host::uart::{CommandHeader, Packet};
+use hci::Event;
+
+/// Length of each [Program Data Block](crate::updater::Commands::program_data_block) command
+/// sent by the [`Flasher`].
+pub const BLOCK_LEN: usize = 64;
+
+// The updater rejects longer blocks.
+const _: () = assert!(BLOCK_LEN <= updater::MAX_DATA_BLOCK_LEN);
+
+/// Number of times the [`Flasher`] writes a sector before giving up on a CRC mismatch.
+pub const SECTOR_ATTEMPTS: u8 = 3;
+
+/// Generator polynomial of the updater's CRC.
+const CRC_POLYNOMIAL: u32 = 0x04C1_1DB7;
+
+/// Returns the CRC of the data, as computed by the updater's [Calc
+/// CRC](crate::updater::Commands::calc_crc) command.
+///
+/// This is the algorithm of `updater_calc_crc` in ST's BlueNRG utilities (`bluenrg_utils.c`, in
+/// X-CUBE-BLE1): the data is read as little-endian 32-bit words, and each word is XORed into the
+/// CRC and shifted through the polynomial 0x04C11DB7, most significant bit first. The CRC starts
+/// at 0, and is neither reflected nor inverted at the end. A trailing partial word is padded with
+/// `0xFF`, as erased flash reads.
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_update(0, data)
+}
+
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for chunk in data.chunks(4) {
+        let mut word = [0xFF; 4];
+        word[..chunk.len()].copy_from_slice(chunk);
+        crc ^= u32::from_le_bytes(word);
+        for _ in 0..32 {
+            crc = if crc & 0x8000_0000 != 0 {
+                (crc << 1) ^ CRC_POLYNOMIAL
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+/// Steps of the flashing sequence.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Step {
+    /// Starting the updater.
+    Start,
+
+    /// Waiting for the [`HalInitialized`](BlueNRGEvent::HalInitialized) event that reports the
+    /// updater is running.
+    UpdaterInitialized,
+
+    /// Erasing the blue flag.
+    EraseBlueFlag,
+
+    /// Erasing a sector. Includes the index of the sector in the image.
+    EraseSector(usize),
+
+    /// Programming a block of data. Includes the flash address of the block.
+    ProgramDataBlock(u32),
+
+    /// Checking the CRC of a sector. Includes the index of the sector in the image.
+    CalcCrc(usize),
+
+    /// Restoring the blue flag.
+    ResetBlueFlag,
+
+    /// Rebooting into the new image.
+    Reboot,
+}
+
+/// Reasons a step of the flashing sequence can fail.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Cause<E> {
+    /// There was an error communicating with the controller.
+    Comm(E),
+
+    /// The controller returned a packet that is not an HCI event. Includes the packet type byte.
+    BadPacketType(u8),
+
+    /// The controller returned an event that could not be deserialized.
+    BadEvent(hci::event::Error<BlueNRGError>),
+
+    /// The controller initialized for a reason other than starting the updater.
+    UnexpectedResetReason(ResetReason),
+
+    /// The command failed with the given status.
+    CommandFailed(hci::Status<crate::event::Status>),
+
+    /// The updater rejected a data block as longer than
+    /// [`MAX_DATA_BLOCK_LEN`](updater::MAX_DATA_BLOCK_LEN). Includes the length of the block.
+    DataBlockTooLong(usize),
+
+    /// The CRC of the sector still did not match after [`SECTOR_ATTEMPTS`] writes.
+    CrcMismatch {
+        /// CRC of the sector in the image.
+        expected: u32,
+
+        /// CRC of the sector in flash, returned by the controller.
+        actual: u32,
+    },
+}
+
+/// Error returned by [`Flasher::poll`], naming the step that failed and why.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct FlashError<E> {
+    /// The step that failed.
+    pub step: Step,
+
+    /// Why it failed.
+    pub cause: Cause<E>,
+}
+
+/// The image passed to [`Flasher::new`] or [`Flasher::resume`] is longer than
+/// [`MAX_FIRMWARE_LEN`](crate::updater::MAX_FIRMWARE_LEN). Includes the length of the image.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ImageTooLarge(pub usize);
+
+#[derive(Copy, Clone, Debug, PartialEq)]
+enum Phase {
+    Start,
+    UpdaterInitialized,
+    EraseBlueFlag,
+    EraseSector,
+    ProgramDataBlock(usize),
+    CalcCrc,
+    ResetBlueFlag,
+    Reboot,
+    Done,
+}
+
+/// Non-blocking state machine that writes a new Bluetooth stack image to the controller. See the
+/// [module documentation](self) for the sequence.
+pub struct Flasher<'img> {
+    image: &'img [u8],
+    phase: Phase,
+
+    /// True once the command for the current phase has been sent, and its result is awaited.
+    sent: bool,
+    sector: usize,
+    attempts: u8,
+}
+
+impl<'img> Flasher<'img> {
+    /// Returns a flasher that writes the whole image, starting by starting the updater.
+    ///
+    /// # Errors
+    ///
+    /// [`ImageTooLarge`] if the image is longer than
+    /// [`MAX_FIRMWARE_LEN`](crate::updater::MAX_FIRMWARE_LEN).
+    pub fn new(image: &'img [u8]) -> Result<Flasher<'img>, ImageTooLarge> {
+        Flasher::with_phase(image, Phase::Start, 0)
+    }
+
+    /// Returns a flasher that continues an interrupted update, skipping the first
+    /// `verified_sectors` sectors of the image.
+    ///
+    /// The controller must already be running the updater, as it does when it starts with the
+    /// blue flag erased (it reports a [`HalInitialized`](BlueNRGEvent::HalInitialized) event with
+    /// the [`UpdaterBadFlag`](ResetReason::UpdaterBadFlag) reason). The flasher starts by erasing
+    /// the blue flag again, in case it was restored.
+    ///
+    /// # Errors
+    ///
+    /// [`ImageTooLarge`] if the image is longer than
+    /// [`MAX_FIRMWARE_LEN`](crate::updater::MAX_FIRMWARE_LEN).
+    pub fn resume(
+        image: &'img [u8],
+        verified_sectors: usize,
+    ) -> Result<Flasher<'img>, ImageTooLarge> {
+        Flasher::with_phase(image, Phase::EraseBlueFlag, verified_sectors)
+    }
+
+    fn with_phase(
+        image: &'img [u8],
+        phase: Phase,
+        sector: usize,
+    ) -> Result<Flasher<'img>, ImageTooLarge> {
+        if image.len() > updater::MAX_FIRMWARE_LEN {
+            return Err(ImageTooLarge(image.len()));
+        }
+
+        let sector = min(sector, sector_count(image));
+        Ok(Flasher {
+            image,
+            phase,
+            sent: false,
+            sector,
+            attempts: 0,
+        })
+    }
+
+    /// Returns the number of sectors in the image.
+    pub fn sector_count(&self) -> usize {
+        sector_count(self.image)
+    }
+
+    /// Returns the number of sectors, from the start of the image, that have been written and
+    /// verified. Pass it to [`Flasher::resume`] to continue an interrupted update.
+    pub fn verified_sectors(&self) -> usize {
+        self.sector
+    }
+
+    /// Returns true once the controller has accepted the reboot into the new image.
+    pub fn is_done(&self) -> bool {
+        self.phase == Phase::Done
+    }
+
+    /// Advances the flashing sequence as far as possible without blocking.
+    ///
+    /// Returns `Ok(())` once the controller has accepted the reboot into the new image. Events
+    /// that arrive while waiting for a step, but are not part of it, are discarded.
+    ///
+    /// # Errors
+    ///
+    /// - `WouldBlock` if the sequence is not done yet. Call `poll` again, for example when the
+    ///   controller has data ready.
+    /// - A [`FlashError`] naming the step that failed and why. Calling `poll` again retries the
+    ///   failed step; a sector whose CRC did not match is written again from the start.
+    pub fn poll<C>(&mut self, controller: &mut C) -> nb::Result<(), FlashError<C::Error>>
+    where
+        C: hci::Controller<Header = CommandHeader, Vendor = BlueNRGTypes> + crate::Firmware,
+    {
+        loop {
+            if self.phase == Phase::Done {
+                return Ok(());
+            }
+
+            if !self.sent {
+                self.send(controller)?;
+                self.sent = true;
+                continue;
+            }
+
+            let event = match crate::event::read(controller) {
+                Ok(Packet::Event(event)) => event,
+                Err(nb::Error::WouldBlock) => return Err(nb::Error::WouldBlock),
+                Err(nb::Error::Other(e)) => {
+                    return Err(nb::Error::Other(self.fail(match e {
+                        hci::host::uart::Error::Comm(e) => Cause::Comm(e),
+                        hci::host::uart::Error::BadPacketType(packet_type) => {
+                            Cause::BadPacketType(packet_type)
+                        }
+                        hci::host::uart::Error::BLE(e) => Cause::BadEvent(e),
+                    })))
+                }
+            };
+
+            self.handle(event).map_err(nb::Error::Other)?;
+        }
+    }
+
+    fn step(&self) -> Step {
+        match self.phase {
+            Phase::Start => Step::Start,
+            Phase::UpdaterInitialized => Step::UpdaterInitialized,
+            Phase::EraseBlueFlag => Step::EraseBlueFlag,
+            Phase::EraseSector => Step::EraseSector(self.sector),
+            Phase::ProgramDataBlock(offset) => Step::ProgramDataBlock(self.address(offset)),
+            Phase::CalcCrc => Step::CalcCrc(self.sector),
+            Phase::ResetBlueFlag => Step::ResetBlueFlag,
+            Phase::Reboot | Phase::Done => Step::Reboot,
+        }
+    }
+
+    /// Returns an error for the current step, and arranges for the next poll to retry it.
+    fn fail<E>(&mut self, cause: Cause<E>) -> FlashError<E> {
+        let step = self.step();
+        self.sent = false;
+        FlashError { step, cause }
+    }
+
+    /// Returns the flash address of the given offset into the current sector.
+    fn address(&self, offset: usize) -> u32 {
+        FIRMWARE_ADDRESS + (self.sector * SECTOR_SIZE + offset) as u32
+    }
+
+    /// Returns the part of the current sector that is in the image.
+    fn sector_data(&self) -> &'img [u8] {
+        let start = self.sector * SECTOR_SIZE;
+        &self.image[start..min(start + SECTOR_SIZE, self.image.len())]
+    }
+
+    /// Returns the CRC of the current sector, with the part past the end of the image left erased.
+    fn sector_crc(&self) -> u32 {
+        let data = self.sector_data();
+        let mut crc = crc32_update(0, data);
+        for _ in data.len().div_ceil(4)..SECTOR_SIZE / 4 {
+            crc = crc32_update(crc, &[0xFF; 4]);
+        }
+        crc
+    }
+
+    fn send<C>(&mut self, controller: &mut C) -> nb::Result<(), FlashError<C::Error>>
+    where
+        C: hci::Controller<Header = CommandHeader, Vendor = BlueNRGTypes> + crate::Firmware,
+    {
+        let result = match self.phase {
+            Phase::Start => controller.start(),
+            Phase::UpdaterInitialized | Phase::Done => Ok(()),
+            Phase::EraseBlueFlag => controller.erase_blue_flag(),
+            Phase::EraseSector => controller.erase_sector(self.address(0)),
+            Phase::ProgramDataBlock(offset) => {
+                let data = &self.sector_data()[offset..];
+                let mut block = [0xFF; BLOCK_LEN];
+                let len = min(data.len(), BLOCK_LEN);
+                block[..len].copy_from_slice(&data[..len]);
+
+                let address = self.address(offset);
+                return controller
+                    .program_data_block(address, &block)
+                    .map_err(|e| match e {
+                        nb::Error::WouldBlock => nb::Error::WouldBlock,
+                        nb::Error::Other(updater::Error::Comm(e)) => {
+                            nb::Error::Other(self.fail(Cause::Comm(e)))
+                        }
+                        nb::Error::Other(updater::Error::DataBlockTooLong(len)) => {
+                            nb::Error::Other(self.fail(Cause::DataBlockTooLong(len)))
+                        }
+                    });
+            }
+            Phase::CalcCrc => controller.calc_crc(self.address(0), 1),
+            Phase::ResetBlueFlag => controller.reset_blue_flag(),
+            Phase::Reboot => controller.reboot(),
+        };
+
+        result.map_err(|e| match e {
+            nb::Error::WouldBlock => nb::Error::WouldBlock,
+            nb::Error::Other(e) => nb::Error::Other(self.fail(Cause::Comm(e))),
+        })
+    }
+
+    /// Advances to the next phase if the event completes the current one.
+    fn handle<E>(&mut self, event: Event<BlueNRGEvent>) -> Result<(), FlashError<E>> {
+        if self.phase == Phase::UpdaterInitialized {
+            return match event {
+                Event::Vendor(BlueNRGEvent::HalInitialized(ResetReason::Updater))
+                | Event::Vendor(BlueNRGEvent::HalInitialized(ResetReason::UpdaterBadFlag))
+                | Event::Vendor(BlueNRGEvent::HalInitialized(ResetReason::UpdaterPin)) => {
+                    self.advance(Phase::EraseBlueFlag);
+                    Ok(())
+                }
+                Event::Vendor(BlueNRGEvent::HalInitialized(reason)) => {
+                    Err(self.fail(Cause::UnexpectedResetReason(reason)))
+                }
+                _ => Ok(()),
+            };
+        }
+
+        let params = match event {
+            Event::CommandComplete(complete) => match complete.return_params {
+                HciReturnParameters::Vendor(params) => params,
+                _ => return Ok(()),
+            },
+            _ => return Ok(()),
+        };
+
+        let (status, crc) = match (self.phase, params) {
+            (Phase::Start, ReturnParameters::UpdaterStart(status))
+            | (Phase::EraseBlueFlag, ReturnParameters::UpdaterEraseBlueFlag(status))
+            | (Phase::EraseSector, ReturnParameters::UpdaterEraseSector(status))
+            | (Phase::ProgramDataBlock(_), ReturnParameters::UpdaterProgramDataBlock(status))
+            | (Phase::ResetBlueFlag, ReturnParameters::UpdaterResetBlueFlag(status))
+            | (Phase::Reboot, ReturnParameters::UpdaterReboot(status)) => (status, None),
+            (Phase::CalcCrc, ReturnParameters::UpdaterCalcCrc(crc)) => (crc.status, Some(crc.crc)),
+            _ => return Ok(()),
+        };
+        if status != hci::Status::Success {
+            return Err(self.fail(Cause::CommandFailed(status)));
+        }
+
+        let next = match self.phase {
+            Phase::Start => Phase::UpdaterInitialized,
+            Phase::EraseBlueFlag => self.next_sector(),
+            Phase::CalcCrc => {
+                let expected = self.sector_crc();
+                let actual = crc.unwrap_or(expected);
+                if actual == expected {
+                    self.sector += 1;
+                    self.attempts = 0;
+                } else {
+                    self.attempts += 1;
+                    if self.attempts >= SECTOR_ATTEMPTS {
+                        self.attempts = 0;
+                        let error = self.fail(Cause::CrcMismatch { expected, actual });
+                        self.phase = Phase::EraseSector;
+                        return Err(error);
+                    }
+                }
+                self.next_sector()
+            }
+            Phase::EraseSector => Phase::ProgramDataBlock(0),
+            Phase::ProgramDataBlock(offset) => {
+                let next = offset + BLOCK_LEN;
+                if next < self.sector_data().len() {
+                    Phase::ProgramDataBlock(next)
+                } else {
+                    Phase::CalcCrc
+                }
+            }
+            Phase::ResetBlueFlag => Phase::Reboot,
+            Phase::Reboot | Phase::UpdaterInitialized | Phase::Done => Phase::Done,
+        };
+        self.advance(next);
+
+        Ok(())
+    }
+
+    /// Returns the phase that writes the current sector, or restores the blue flag once every
+    /// sector is verified.
+    fn next_sector(&self) -> Phase {
+        if self.sector < self.sector_count() {
+            Phase::EraseSector
+        } else {
+            Phase::ResetBlueFlag
+        }
+    }
+
+    fn advance(&mut self, phase: Phase) {
+        self.phase = phase;
+        self.sent = false;
+    }
+}
+
+fn sector_count(image: &[u8]) -> usize {
+    image.len().div_ceil(SECTOR_SIZE)
+}
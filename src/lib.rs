@@ -1,6 +1,12 @@
 //! Bluetooth HCI for STMicro's BlueNRG-MS Bluetooth controllers.
 //!
-//! *Note*: This crate does not provide support for the BlueNRG-1 or BlueNRG-2 SoCs.
+//! *Note*: The BlueNRG-1 and BlueNRG-2 SoCs run the network processor firmware with an extended
+//! version of the BlueNRG-MS ACI. Enable the `bluenrg2` feature to use the BlueNRG-1/BlueNRG-2
+//! opcodes, parameter layouts, and events instead.
+//!
+//! The original BlueNRG speaks an older dialect of the vendor-specific commands and events. Both
+//! dialects are built in, and the one in use is picked at run time from the controller's
+//! [`Version`]; the `ms` feature only selects the default. See the [`dialect`] module.
 //!
 //! # Design
 //!
@@ -20,17 +26,41 @@
 //! which invokes its closure on at [`ActiveBlueNRG`], so sending HCI commands and reading HCI
 //! events can only be done from within that closure.
 //!
+//! With the `async` feature enabled, `BlueNRG::with_spi_async` does the same over an
+//! [`embedded-hal-async`](https://docs.rs/embedded-hal-async) SPI bus; see the `asynch` module.
+//!
+//! With the `spi-device` feature enabled, `device::BlueNRGDevice` talks to the controller through
+//! an embedded-hal 1.0 `SpiDevice`, for buses shared with other devices. It owns its bus handle and
+//! implements [`bluetooth_hci::Controller`] itself, without a closure.
+//!
+//! With the `dma` feature enabled, `BlueNRG::with_spi_dma` reads from the controller with a
+//! DMA-capable SPI peripheral, through the [`embedded-dma`](https://docs.rs/embedded-dma) buffer
+//! traits; see the `dma` module.
+//!
+//! With the `mock` feature enabled, `mock::Mock` stands in for the controller: it answers the SPI
+//! protocol and models enough of the stack to test an application on the host. `mock::Radio`
+//! connects two mocks, so a peripheral and a central can find each other and exchange ATT traffic.
+//!
 //! # Vendor-Specific Commands
 //!
 //! BlueNRG-MS provides several vendor-specific commands that control the behavior of the
 //! controller.
 //!
+//! The [`updater`] commands rewrite the controller's firmware, and the [`flasher`] module uses them
+//! to replace the Bluetooth stack image.
+//!
 //! # Vendor-Specific Events
 //!
 //! BlueNRG-MS provides several vendor-specific events that provide data related to the
 //! controller. Many of these events are forwarded from the link layer, and these are documented
 //! with a reference to the appropriate section of the Bluetooth specification.
 //!
+//! The [`events_lost`] module counts the events the controller dropped, and recovers from their
+//! loss. When the controller reports a hardware error or a crash, a [`supervisor`] can reset it and
+//! replay the application's initialization. The [`crash`] module stores and decodes the reports of
+//! crashes. To see what went over the wire, a [`capture`] records the HCI traffic in btsnoop or
+//! pcap format, for Wireshark.
+//!
 //! # Example
 //!
 //! TODO
@@ -45,6 +75,8 @@ extern crate bitflags;
 #[macro_use]
 extern crate bluetooth_hci as hci;
 extern crate byteorder;
+#[cfg(feature = "dma")]
+extern crate embedded_dma;
 extern crate embedded_hal as emhal;
 #[macro_use(block)]
 extern crate nb;
@@ -53,18 +85,39 @@ use byteorder::{ByteOrder, LittleEndian};
 use core::cmp::min;
 use core::convert::TryFrom;
 use core::marker::PhantomData;
-use hci::host::HciHeader;
-use hci::Controller;
+use core::time::Duration;
 
+#[cfg(feature = "async")]
+pub mod asynch;
+pub mod bring_up;
+pub mod capability;
+pub mod capture;
 mod cb;
 mod command;
+pub mod config_store;
+pub mod crash;
+#[cfg(feature = "spi-device")]
+pub mod device;
+pub mod dialect;
+#[cfg(feature = "dma")]
+pub mod dma;
 pub mod event;
+pub mod events_lost;
+pub mod flasher;
+#[cfg(feature = "mock")]
+pub mod mock;
 mod opcode;
+pub mod probe;
+pub mod queue;
+pub mod request;
+pub mod supervisor;
 
+pub use command::decode;
 pub use command::gap;
 pub use command::gatt;
 pub use command::hal;
 pub use command::l2cap;
+pub use command::updater;
 
 pub use hci::host::{AdvertisingFilterPolicy, AdvertisingType, OwnAddressType};
 
@@ -77,10 +130,69 @@ pub enum Error<SpiError, GpioError> {
     /// GPIO errors occur if there is an underlying error resetting the pin, setting the chip select
     /// pin, or reading if data is available.
     Gpio(GpioError),
+
+    /// The controller did not become ready before the retry limit (see
+    /// [`BlueNRG::set_retry_limit`]) or the deadline (see [`SpiOptions::timeout`]) ran out.
+    /// This happens if the controller is not powered, is held in reset, or is not responding.
+    Timeout,
+
+    /// The controller had more data to send than fit in the RX buffer. The data that did not fit,
+    /// along with the incomplete packet it belonged to, was dropped (see
+    /// [`BlueNRG::dropped_bytes`]). Complete packets already in the RX buffer are kept, and the
+    /// next read starts at a packet boundary.
+    RxOverflow,
+}
+
+/// Counters for data read from the controller into the RX buffer, to compare the cost of the
+/// transfer paths. See [`BlueNRG::rx_stats`].
+///
+/// The counters wrap around on overflow; the time saturates.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct RxStats {
+    /// Number of bytes read into the RX buffer.
+    pub bytes: u32,
+
+    /// Number of SPI transfers used to read those bytes, not counting SPI headers.
+    pub transfers: u32,
+
+    /// Time taken by those transfers, from the start of each transfer until the host saw it
+    /// complete, as measured by the clock given to [`BlueNRG::set_rx_clock`]. Stays zero without a
+    /// clock.
+    pub time: Duration,
+
+    /// Number of reads that failed with an SPI or GPIO error after they started. The data of a
+    /// failed read is thrown away.
+    pub failed_reads: u32,
+}
+
+impl RxStats {
+    /// Counts a read that ended with `error`, if it is an SPI or GPIO error.
+    fn record_error<SpiError, GpioError>(&mut self, error: &Error<SpiError, GpioError>) {
+        if let Error::Spi(_) | Error::Gpio(_) = error {
+            self.failed_reads = self.failed_reads.wrapping_add(1);
+        }
+    }
+
+    fn record(&mut self, bytes: usize, time: Duration) {
+        self.bytes = self.bytes.wrapping_add(bytes as u32);
+        self.transfers = self.transfers.wrapping_add(1);
+        self.time = self.time.saturating_add(time);
+    }
 }
 
 /// Handle for interfacing with the BlueNRG-MS.
-pub struct BlueNRG<'buf, SPI, OutputPin1, OutputPin2, InputPin, GpioError> {
+///
+/// The RX buffer is either borrowed, with [`BlueNRG::new`], or owned as an array of `N` bytes
+/// (`RxBuffer = [u8; N]`), with [`BlueNRG::new_owned`].
+pub struct BlueNRG<
+    'buf,
+    SPI,
+    OutputPin1,
+    OutputPin2,
+    InputPin,
+    GpioError,
+    RxBuffer = &'buf mut [u8],
+> {
     /// Dedicated GPIO pin that is used to select the BlueNRG-MS chip on the SPI bus. This allows
     /// multiple chips to share the same SPI bus.
     chip_select: OutputPin1,
@@ -93,14 +205,49 @@ pub struct BlueNRG<'buf, SPI, OutputPin1, OutputPin2, InputPin, GpioError> {
     data_ready: InputPin,
 
     /// Buffer used to hold bytes read from the controller until the application can process them.
-    /// Should be at least 257 bytes (to hold a header and maximum BLE payload of 255 bytes).
-    rx_buffer: cb::Buffer<'buf, u8>,
+    /// Should be at least 259 bytes: the buffer holds one byte less than its length, and the
+    /// longest event packet is 258 bytes (a 3-byte header and 255 bytes of parameters).
+    rx_buffer: cb::Buffer<u8, RxBuffer>,
+
+    /// Maximum number of consecutive attempts to communicate with the controller that may fail
+    /// because it is not ready, or `None` to retry forever.
+    retry_limit: Option<u32>,
+
+    /// Number of consecutive attempts that have failed because the controller was not ready.
+    retries: u32,
+
+    /// Number of HCI command packets the controller can currently accept, as reported by the
+    /// last Command Complete or Command Status event.
+    command_credits: u8,
+
+    /// Number of commands written for which no Command Complete or Command Status event has been
+    /// read.
+    outstanding_commands: u8,
+
+    /// Number of bytes from the controller that were dropped because they did not fit in the RX
+    /// buffer.
+    dropped_bytes: u32,
+
+    /// Counters for data read into the RX buffer.
+    rx_stats: RxStats,
+
+    /// Clock used to time the transfers counted in `rx_stats`, if any.
+    rx_clock: Option<fn() -> Duration>,
+
+    /// HCI dialect spoken by the controller.
+    dialect: dialect::Dialect,
+
+    /// Version reported by the controller, if it has been read.
+    version: Option<Version>,
 
     #[doc(hidden)]
     _spi: PhantomData<SPI>,
 
     #[doc(hidden)]
     _gpio_error: PhantomData<GpioError>,
+
+    #[doc(hidden)]
+    _rx_buffer: PhantomData<&'buf mut [u8]>,
 }
 
 /// Handle for actively communicating with the controller over the SPI bus.
@@ -108,12 +255,128 @@ pub struct BlueNRG<'buf, SPI, OutputPin1, OutputPin2, InputPin, GpioError> {
 /// An `ActiveBlueNRG` should not be created by the application, but is passed to closures given to
 /// [`BlueNRG::with_spi`].  `ActiveBlueNRG` implements [`bluetooth_hci::Controller`], so it is used
 /// to access the HCI functions for the controller.
-pub struct ActiveBlueNRG<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, GpioError> {
+pub struct ActiveBlueNRG<
+    'bnrg,
+    'spi,
+    'dbuf,
+    SPI,
+    OutputPin1,
+    OutputPin2,
+    InputPin,
+    GpioError,
+    RxBuffer = &'dbuf mut [u8],
+> {
     /// Mutably borrow the BlueNRG handle so we can access pin and buffer.
-    d: &'bnrg mut BlueNRG<'dbuf, SPI, OutputPin1, OutputPin2, InputPin, GpioError>,
+    d: &'bnrg mut BlueNRG<'dbuf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer>,
 
     /// Mutably borrow the SPI bus so we can communicate with the controller.
     spi: &'spi mut SPI,
+
+    /// Optional deadline for waiting on the controller.
+    deadline: Option<&'spi mut dyn Deadline>,
+
+    /// True if the deadline has been started for the current wait.
+    deadline_running: bool,
+
+    /// Optional capture of the packets written to and read from the controller.
+    capture: Option<&'spi mut dyn capture::Tap>,
+}
+
+/// Object-safe wrapper around a [`CountDown`](emhal::timer::CountDown) timer and its timeout, so
+/// the timer type does not leak into [`ActiveBlueNRG`].
+trait Deadline {
+    /// Starts (or restarts) the timer.
+    fn start(&mut self);
+
+    /// Returns true if the timer has expired since it was started.
+    fn expired(&mut self) -> bool;
+}
+
+/// A [`CountDown`](emhal::timer::CountDown) timer and the timeout it is started with, to bound the
+/// waits for the controller. See [`SpiOptions::timeout`].
+pub struct Timeout<'timer, Timer>
+where
+    Timer: emhal::timer::CountDown,
+{
+    timer: &'timer mut Timer,
+    timeout: Timer::Time,
+}
+
+impl<'timer, Timer> Timeout<'timer, Timer>
+where
+    Timer: emhal::timer::CountDown,
+{
+    /// Returns a timeout that starts `timer` with `timeout`.
+    pub fn new(timer: &'timer mut Timer, timeout: Timer::Time) -> Timeout<'timer, Timer> {
+        Timeout { timer, timeout }
+    }
+}
+
+impl<'timer, Timer> Deadline for Timeout<'timer, Timer>
+where
+    Timer: emhal::timer::CountDown,
+    Timer::Time: Copy,
+{
+    fn start(&mut self) {
+        self.timer.start(self.timeout);
+    }
+
+    fn expired(&mut self) -> bool {
+        self.timer.wait().is_ok()
+    }
+}
+
+/// Options for a session with the controller, given to [`BlueNRG::with_spi_options`].
+///
+/// By default, a session has no deadline and no capture, like one started with
+/// [`BlueNRG::with_spi`]. The options may be combined:
+///
+/// ```ignore
+/// let mut timeout = Timeout::new(&mut timer, 10.ms());
+/// let options = SpiOptions::new().timeout(&mut timeout).capture(&mut capture);
+/// bnrg.with_spi_options(&mut spi, options, |controller| {
+///     // ...
+/// });
+/// ```
+#[derive(Default)]
+pub struct SpiOptions<'a> {
+    deadline: Option<&'a mut dyn Deadline>,
+    capture: Option<&'a mut dyn capture::Tap>,
+}
+
+impl<'a> SpiOptions<'a> {
+    /// Returns the default options: no deadline and no capture.
+    pub fn new() -> SpiOptions<'a> {
+        SpiOptions::default()
+    }
+
+    /// Bounds the waits for the controller with `timeout`. Any wait for the controller to become
+    /// ready, or to report enough space for a write, fails with [`Error::Timeout`] if the
+    /// controller has not made progress when the timer expires. The timer is started at the first
+    /// failed attempt of each wait.
+    pub fn timeout<'timer, Timer>(
+        mut self,
+        timeout: &'a mut Timeout<'timer, Timer>,
+    ) -> SpiOptions<'a>
+    where
+        Timer: emhal::timer::CountDown,
+        Timer::Time: Copy,
+        'timer: 'a,
+    {
+        self.deadline = Some(timeout);
+        self
+    }
+
+    /// Records every packet written to the controller, and every packet read from it, to
+    /// `capture`. See the [`capture`] module.
+    pub fn capture<S, C>(mut self, capture: &'a mut capture::Capture<S, C>) -> SpiOptions<'a>
+    where
+        S: capture::Sink,
+        C: capture::Clock,
+    {
+        self.capture = Some(capture);
+        self
+    }
 }
 
 /// Read the SPI header.
@@ -138,6 +401,14 @@ fn parse_spi_header<E>(header: &[u8; 5]) -> Result<(u16, u16), nb::Error<E>> {
     }
 }
 
+const PACKET_TYPE_HCI_COMMAND: u8 = 0x01;
+const PACKET_TYPE_HCI_EVENT: u8 = 0x04;
+const EVENT_COMMAND_COMPLETE: u8 = 0x0E;
+const EVENT_COMMAND_STATUS: u8 = 0x0F;
+
+/// Length of an HCI event packet header: packet type, event code, and parameter length.
+const EVENT_PACKET_HEADER_LEN: usize = 3;
+
 enum Access {
     Read,
     Write,
@@ -152,14 +423,176 @@ impl Access {
     }
 }
 
-impl<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, SpiError, GpioError>
-    ActiveBlueNRG<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, GpioError>
+impl<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer>
+    ActiveBlueNRG<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer>
+where
+    OutputPin1: emhal::digital::v2::OutputPin<Error = GpioError>,
+    OutputPin2: emhal::digital::v2::OutputPin<Error = GpioError>,
+    InputPin: emhal::digital::v2::InputPin<Error = GpioError>,
+    RxBuffer: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Records an attempt that failed because the controller was not ready.
+    ///
+    /// Returns [`Error::Timeout`] if the retry limit has been reached or the deadline has
+    /// expired. The deadline starts at the first failed attempt, so it bounds how long the host
+    /// waits, not how long the whole operation takes.
+    fn count_retry<SpiError>(&mut self) -> Result<(), Error<SpiError, GpioError>> {
+        let timed_out = self.d.count_retry() || self.deadline_expired();
+        if timed_out {
+            self.reset_retries();
+            return Err(Error::Timeout);
+        }
+
+        Ok(())
+    }
+
+    /// Records a poll that found nothing to read, or a command that could not be sent yet, while
+    /// waiting for the controller to make progress. Unlike
+    /// [`count_retry`](ActiveBlueNRG::count_retry), this does not use up the retry limit, which
+    /// only bounds the SPI handshake: only the deadline bounds the wait.
+    ///
+    /// Returns [`Error::Timeout`] if the deadline has expired.
+    fn count_wait<SpiError>(&mut self) -> Result<(), Error<SpiError, GpioError>> {
+        if self.deadline_expired() {
+            self.reset_retries();
+            return Err(Error::Timeout);
+        }
+
+        Ok(())
+    }
+
+    /// Returns true if the deadline has expired. Starts the deadline if it is not running yet.
+    fn deadline_expired(&mut self) -> bool {
+        match self.deadline {
+            Some(ref mut deadline) if self.deadline_running => deadline.expired(),
+            Some(ref mut deadline) => {
+                deadline.start();
+                self.deadline_running = true;
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Records that the controller made progress, so the next wait gets a fresh retry budget and
+    /// deadline.
+    fn reset_retries(&mut self) {
+        self.d.retries = 0;
+        self.deadline_running = false;
+    }
+
+    /// If the controller has data ready, lowers the chip select line, reads more data from the
+    /// controller with `fill`, and raises the chip select line again.
+    ///
+    /// Returns nb::Error::WouldBlock if the controller has no data ready.
+    fn fill_rx_buffer<F, SpiError>(&mut self, fill: F) -> nb::Result<(), Error<SpiError, GpioError>>
+    where
+        F: FnOnce(&mut Self) -> nb::Result<(), Error<SpiError, GpioError>>,
+    {
+        if !self
+            .d
+            .data_ready()
+            .map_err(Error::Gpio)
+            .map_err(nb::Error::Other)?
+        {
+            return Err(nb::Error::WouldBlock);
+        }
+
+        self.d
+            .chip_select
+            .set_low()
+            .map_err(Error::Gpio)
+            .map_err(nb::Error::Other)?;
+        let result = fill(self);
+        let result = self
+            .d
+            .chip_select
+            .set_high()
+            .map_err(Error::Gpio)
+            .map_err(nb::Error::Other)
+            .and(result);
+        if let Err(nb::Error::Other(ref e)) = result {
+            self.d.rx_stats.record_error(e);
+        }
+
+        result
+    }
+
+    /// Implements [`hci::Controller::read_into`], using `fill` to read more data from the
+    /// controller if the RX buffer does not hold enough. `fill` is responsible for the chip select
+    /// line.
+    fn read_into_with<F, SpiError>(
+        &mut self,
+        buffer: &mut [u8],
+        fill: F,
+    ) -> nb::Result<(), Error<SpiError, GpioError>>
+    where
+        F: FnOnce(&mut Self) -> nb::Result<(), Error<SpiError, GpioError>>,
+    {
+        let result = if buffer.len() > self.d.rx_buffer.size() {
+            fill(self)
+        } else {
+            Ok(())
+        };
+
+        if let Err(nb::Error::Other(Error::RxOverflow)) = result {
+            return result;
+        }
+
+        if buffer.len() <= self.d.rx_buffer.size() {
+            self.d.rx_buffer.take_slice(buffer.len(), buffer);
+            self.d.update_command_credits(buffer);
+            if let Some(ref mut capture) = self.capture {
+                capture.record(capture::Direction::Received, buffer, &[]);
+            }
+            Ok(())
+        } else if let Err(e) = result {
+            Err(e)
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+
+    /// Implements [`hci::Controller::peek`], using `fill` to read more data from the controller if
+    /// the RX buffer does not hold enough. `fill` is responsible for the chip select line.
+    fn peek_with<F, SpiError>(
+        &mut self,
+        n: usize,
+        fill: F,
+    ) -> nb::Result<u8, Error<SpiError, GpioError>>
+    where
+        F: FnOnce(&mut Self) -> nb::Result<(), Error<SpiError, GpioError>>,
+    {
+        if n >= self.d.rx_buffer.size() {
+            let result = fill(self);
+            if let Err(nb::Error::Other(Error::RxOverflow)) = result {
+                return Err(nb::Error::Other(Error::RxOverflow));
+            }
+
+            if n >= self.d.rx_buffer.size() {
+                result?;
+
+                // Returns WouldBlock below
+            }
+        }
+
+        if n < self.d.rx_buffer.size() {
+            Ok(self.d.rx_buffer.peek(n))
+        } else {
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
+
+impl<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, SpiError, GpioError, RxBuffer>
+    ActiveBlueNRG<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer>
 where
     SPI: emhal::blocking::spi::Transfer<u8, Error = SpiError>
         + emhal::blocking::spi::Write<u8, Error = SpiError>,
     OutputPin1: emhal::digital::v2::OutputPin<Error = GpioError>,
     OutputPin2: emhal::digital::v2::OutputPin<Error = GpioError>,
     InputPin: emhal::digital::v2::InputPin<Error = GpioError>,
+    RxBuffer: AsRef<[u8]> + AsMut<[u8]>,
 {
     /// Wait for the chip to respond that it is awake and ready.  The chip select line must be
     /// toggled before sending another SPI header.
@@ -169,7 +602,8 @@ where
     /// Empirically, the loop runs 2 to 4 times when the chip is not awake.
     ///
     /// Returns the number of bytes that can be written to the chip, and the number of bytes that
-    /// should be read from the chip.  Returns an error if there is an underlying SPI error.
+    /// should be read from the chip.  Returns an error if there is an underlying SPI error, or
+    /// [`Error::Timeout`] if the retry limit or deadline runs out first.
     fn block_until_ready(
         &mut self,
         access_byte: u8,
@@ -194,12 +628,25 @@ where
                         .set_low()
                         .map_err(Error::Gpio)
                         .map_err(nb::Error::Other)?;
+                    self.count_retry().map_err(nb::Error::Other)?;
                 }
                 Err(err) => return Err(err),
             }
         }
     }
 
+    /// Returns the number of HCI command packets the controller can currently accept. See
+    /// [`BlueNRG::command_credits`].
+    pub fn command_credits(&self) -> u8 {
+        self.d.command_credits
+    }
+
+    /// Returns the number of commands that have been written, but whose Command Complete or Command
+    /// Status event has not been read. See [`BlueNRG::outstanding_commands`].
+    pub fn outstanding_commands(&self) -> u8 {
+        self.d.outstanding_commands
+    }
+
     fn block_until_ready_for(
         &mut self,
         access: Access,
@@ -259,19 +706,11 @@ where
     ///
     /// # Errors
     ///
-    /// - Returns nb::Error::WouldBlock if the controller is not ready.
+    /// - Returns [`Error::RxOverflow`] if the RX buffer filled up before all of the available data
+    ///   was read. The rest of the data is read and thrown away.
     ///
     /// - Returns a communication error if there is an error communicating over the SPI bus.
     fn read_available_data(&mut self) -> nb::Result<(), Error<SpiError, GpioError>> {
-        if !self
-            .d
-            .data_ready()
-            .map_err(Error::Gpio)
-            .map_err(nb::Error::Other)?
-        {
-            return Err(nb::Error::WouldBlock);
-        }
-
         let read_len = self.block_until_ready_for(Access::Read)?;
         let mut bytes_available = read_len as usize;
         while bytes_available > 0 && self.d.rx_buffer.next_contiguous_slice_len() > 0 {
@@ -279,6 +718,7 @@ where
                 bytes_available,
                 self.d.rx_buffer.next_contiguous_slice_len(),
             );
+            let started = self.d.rx_time();
             {
                 let rx = self.d.rx_buffer.next_mut_slice(transfer_count);
                 for byte in rx.iter_mut() {
@@ -289,125 +729,155 @@ where
                     .map_err(Error::Spi)
                     .map_err(nb::Error::Other)?;
             }
+            self.d.record_rx(transfer_count, started);
             bytes_available -= transfer_count;
         }
 
+        self.finish_read(bytes_available)
+    }
+
+    /// Finishes reading data from the controller, once the RX buffer is full or `unread` is 0.
+    /// Throws away the `unread` bytes that did not fit, and returns [`Error::RxOverflow`] if there
+    /// were any.
+    fn finish_read(&mut self, unread: usize) -> nb::Result<(), Error<SpiError, GpioError>> {
+        self.reset_retries();
+        if unread > 0 {
+            self.discard(unread)?;
+            self.d.drop_rx_overflow(unread);
+            return Err(nb::Error::Other(Error::RxOverflow));
+        }
+
+        Ok(())
+    }
+
+    /// Reads and throws away `len` bytes from the controller.
+    fn discard(&mut self, mut len: usize) -> nb::Result<(), Error<SpiError, GpioError>> {
+        let mut scratch = [0; 16];
+        while len > 0 {
+            let transfer_count = min(len, scratch.len());
+            let rx = &mut scratch[..transfer_count];
+            for byte in rx.iter_mut() {
+                *byte = 0;
+            }
+            self.spi
+                .transfer(rx)
+                .map_err(Error::Spi)
+                .map_err(nb::Error::Other)?;
+            len -= transfer_count;
+        }
+
         Ok(())
     }
 
-    fn write_command(
+    fn write_when_ready(
         &mut self,
-        opcode: opcode::Opcode,
-        params: &[u8],
+        header: &[u8],
+        payload: &[u8],
     ) -> nb::Result<(), Error<SpiError, GpioError>> {
-        const HEADER_LEN: usize = 4;
-        let mut header = [0; HEADER_LEN];
-        hci::host::uart::CommandHeader::new(opcode, params.len()).copy_into_slice(&mut header);
+        let write_len = self.block_until_ready_for(Access::Write)?;
+        if (write_len as usize) < header.len() + payload.len() {
+            return Err(nb::Error::WouldBlock);
+        }
 
-        self.write(&header, params)
+        self.try_write(header, payload)
     }
 }
 
-impl<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, SpiError, GpioError> hci::Controller
-    for ActiveBlueNRG<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, GpioError>
+impl<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, SpiError, GpioError, RxBuffer>
+    hci::Controller
+    for ActiveBlueNRG<
+        'bnrg,
+        'spi,
+        'dbuf,
+        SPI,
+        OutputPin1,
+        OutputPin2,
+        InputPin,
+        GpioError,
+        RxBuffer,
+    >
 where
     SPI: emhal::blocking::spi::Transfer<u8, Error = SpiError>
         + emhal::blocking::spi::Write<u8, Error = SpiError>,
     OutputPin1: emhal::digital::v2::OutputPin<Error = GpioError>,
     OutputPin2: emhal::digital::v2::OutputPin<Error = GpioError>,
     InputPin: emhal::digital::v2::InputPin<Error = GpioError>,
+    RxBuffer: AsRef<[u8]> + AsMut<[u8]>,
 {
     type Error = Error<SpiError, GpioError>;
     type Header = hci::host::uart::CommandHeader;
     type Vendor = BlueNRGTypes;
 
     fn write(&mut self, header: &[u8], payload: &[u8]) -> nb::Result<(), Self::Error> {
+        let is_command = header.first() == Some(&PACKET_TYPE_HCI_COMMAND);
+        if is_command && self.d.command_credits == 0 {
+            return Err(nb::Error::WouldBlock);
+        }
+
         self.d
             .chip_select
             .set_low()
             .map_err(Error::Gpio)
             .map_err(nb::Error::Other)?;
-        let write_len = self.block_until_ready_for(Access::Write)?;
-        if (write_len as usize) < header.len() + payload.len() {
-            return Err(nb::Error::WouldBlock);
-        }
-
-        let result = self.try_write(header, payload);
+        let result = self.write_when_ready(header, payload);
         self.d
             .chip_select
             .set_high()
             .map_err(Error::Gpio)
             .map_err(nb::Error::Other)?;
 
+        match result {
+            Ok(()) => {
+                self.reset_retries();
+                if let Some(ref mut capture) = self.capture {
+                    capture.record(capture::Direction::Sent, header, payload);
+                }
+                if is_command {
+                    self.d.command_credits -= 1;
+                    self.d.outstanding_commands = self.d.outstanding_commands.saturating_add(1);
+                }
+            }
+            Err(nb::Error::WouldBlock) => {
+                // Not enough write space counts against the same retry budget as not being
+                // awake.
+                self.count_retry().map_err(nb::Error::Other)?;
+            }
+            Err(_) => (),
+        }
+
         result
     }
 
     fn read_into(&mut self, buffer: &mut [u8]) -> nb::Result<(), Self::Error> {
-        let result = if buffer.len() > self.d.rx_buffer.size() {
-            self.d
-                .chip_select
-                .set_low()
-                .map_err(Error::Gpio)
-                .map_err(nb::Error::Other)?;
-            let r = self.read_available_data();
-            self.d
-                .chip_select
-                .set_high()
-                .map_err(Error::Gpio)
-                .map_err(nb::Error::Other)?;
-
-            r
-        } else {
-            Ok(())
-        };
-
-        if buffer.len() <= self.d.rx_buffer.size() {
-            self.d.rx_buffer.take_slice(buffer.len(), buffer);
-            Ok(())
-        } else if let Err(e) = result {
-            Err(e)
-        } else {
-            Err(nb::Error::WouldBlock)
-        }
+        self.read_into_with(buffer, |active| {
+            active.fill_rx_buffer(Self::read_available_data)
+        })
     }
 
     fn peek(&mut self, n: usize) -> nb::Result<u8, Self::Error> {
-        if n >= self.d.rx_buffer.size() {
-            if !self
-                .d
-                .data_ready()
-                .map_err(Error::Gpio)
-                .map_err(nb::Error::Other)?
-            {
-                return Err(nb::Error::WouldBlock);
-            }
-
-            self.d
-                .chip_select
-                .set_low()
-                .map_err(Error::Gpio)
-                .map_err(nb::Error::Other)?;
-            let result = self.read_available_data();
-            self.d
-                .chip_select
-                .set_high()
-                .map_err(Error::Gpio)
-                .map_err(nb::Error::Other)?;
-
-            if n >= self.d.rx_buffer.size() {
-                if let Err(e) = result {
-                    return Err(e);
-                }
+        self.peek_with(n, |active| active.fill_rx_buffer(Self::read_available_data))
+    }
+}
 
-                // Returns WouldBlock below
-            }
-        }
+impl<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer> Firmware
+    for ActiveBlueNRG<
+        'bnrg,
+        'spi,
+        'dbuf,
+        SPI,
+        OutputPin1,
+        OutputPin2,
+        InputPin,
+        GpioError,
+        RxBuffer,
+    >
+{
+    fn version(&self) -> Option<Version> {
+        self.d.version
+    }
 
-        if n < self.d.rx_buffer.size() {
-            Ok(self.d.rx_buffer.peek(n))
-        } else {
-            Err(nb::Error::WouldBlock)
-        }
+    fn dialect(&self) -> dialect::Dialect {
+        self.d.dialect
     }
 }
 
@@ -418,6 +888,22 @@ impl hci::Vendor for BlueNRGTypes {
     type Event = event::BlueNRGEvent;
 }
 
+/// What the host knows about the firmware of a BlueNRG controller.
+///
+/// The [GAP](gap::Commands) and [GATT](gatt::Commands) commands are implemented for controllers
+/// that also implement this trait, so they can encode each command in the controller's
+/// [dialect], and fail early with an `Unsupported` error for commands that the firmware
+/// does not support (see the [`capability`] module). [`event::read`] parses events in the
+/// controller's dialect.
+pub trait Firmware {
+    /// Returns the version reported by the controller, if it has been
+    /// [recorded](BlueNRG::set_version).
+    fn version(&self) -> Option<Version>;
+
+    /// Returns the HCI dialect spoken by the controller. See [`BlueNRG::dialect`].
+    fn dialect(&self) -> dialect::Dialect;
+}
+
 /// Master trait that encompasses all commands, and communicates over UART.
 pub trait UartController<E>:
     crate::gap::Commands<Error = E>
@@ -444,19 +930,106 @@ where
     InputPin: emhal::digital::v2::InputPin<Error = GpioError>,
 {
     /// Returns a new BlueNRG struct with the given RX Buffer and pins. Resets the controller.
+    ///
+    /// The RX buffer should be at least 259 bytes, or the longest events can never be read. To have the BlueNRG struct own its RX buffer
+    /// instead, see [`new_owned`](BlueNRG::new_owned).
     pub fn new(
         rx_buffer: &'buf mut [u8],
         cs: OutputPin1,
         dr: InputPin,
         rst: OutputPin2,
     ) -> BlueNRG<'buf, SPI, OutputPin1, OutputPin2, InputPin, GpioError> {
+        BlueNRG::with_rx_buffer(rx_buffer, cs, dr, rst)
+    }
+}
+
+/// Minimum length of an RX buffer owned by the BlueNRG struct. The buffer holds one byte less than
+/// its length, and the longest event packet is 258 bytes: the packet type, event code and
+/// parameter length, and 255 bytes of parameters.
+const MIN_RX_BUFFER_LEN: usize = 259;
+
+struct RxBufferLen<const N: usize>;
+
+impl<const N: usize> RxBufferLen<N> {
+    const CHECK: () = assert!(
+        N >= MIN_RX_BUFFER_LEN,
+        "The RX buffer must be at least 259 bytes"
+    );
+}
+
+impl<'buf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, const N: usize>
+    BlueNRG<'buf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, [u8; N]>
+where
+    OutputPin1: emhal::digital::v2::OutputPin<Error = GpioError>,
+    OutputPin2: emhal::digital::v2::OutputPin<Error = GpioError>,
+    InputPin: emhal::digital::v2::InputPin<Error = GpioError>,
+{
+    /// Returns a new BlueNRG struct that owns the given RX buffer, with the given pins. Since it
+    /// does not borrow its buffer, the struct can be stored as
+    /// `BlueNRG<'static, SPI, OutputPin1, OutputPin2, InputPin, GpioError, [u8; N]>` without a
+    /// `static mut` buffer.
+    ///
+    /// The buffer must be at least 259 bytes, so that it can hold the longest event packet (258
+    /// bytes) with the one byte the buffer always leaves free. This is checked at compile time:
+    ///
+    /// ```compile_fail
+    /// # use bluenrg::BlueNRG;
+    /// # struct Pin;
+    /// # impl embedded_hal::digital::v2::OutputPin for Pin {
+    /// #     type Error = ();
+    /// #     fn set_low(&mut self) -> Result<(), ()> { Ok(()) }
+    /// #     fn set_high(&mut self) -> Result<(), ()> { Ok(()) }
+    /// # }
+    /// # impl embedded_hal::digital::v2::InputPin for Pin {
+    /// #     type Error = ();
+    /// #     fn is_high(&self) -> Result<bool, ()> { Ok(true) }
+    /// #     fn is_low(&self) -> Result<bool, ()> { Ok(false) }
+    /// # }
+    /// let bnrg: BlueNRG<(), Pin, Pin, Pin, (), [u8; 258]> = BlueNRG::new_owned([0; 258], Pin, Pin, Pin);
+    /// ```
+    pub fn new_owned(
+        rx_buffer: [u8; N],
+        cs: OutputPin1,
+        dr: InputPin,
+        rst: OutputPin2,
+    ) -> BlueNRG<'buf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, [u8; N]> {
+        #[allow(clippy::let_unit_value)]
+        let () = RxBufferLen::<N>::CHECK;
+        BlueNRG::with_rx_buffer(rx_buffer, cs, dr, rst)
+    }
+}
+
+impl<'buf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer>
+    BlueNRG<'buf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer>
+where
+    OutputPin1: emhal::digital::v2::OutputPin<Error = GpioError>,
+    OutputPin2: emhal::digital::v2::OutputPin<Error = GpioError>,
+    InputPin: emhal::digital::v2::InputPin<Error = GpioError>,
+    RxBuffer: AsRef<[u8]> + AsMut<[u8]>,
+{
+    fn with_rx_buffer(
+        rx_buffer: RxBuffer,
+        cs: OutputPin1,
+        dr: InputPin,
+        rst: OutputPin2,
+    ) -> BlueNRG<'buf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer> {
         BlueNRG {
             chip_select: cs,
             rx_buffer: cb::Buffer::new(rx_buffer),
             data_ready: dr,
             reset: rst,
+            retry_limit: None,
+            retries: 0,
+            command_credits: 1,
+            outstanding_commands: 0,
+            dropped_bytes: 0,
+            rx_stats: RxStats::default(),
+            rx_clock: None,
+            dialect: dialect::Dialect::default(),
+            version: None,
             _spi: PhantomData,
             _gpio_error: PhantomData,
+            _rx_buffer: PhantomData,
         }
     }
 
@@ -466,15 +1039,88 @@ where
     /// Returns the result of the invoked body.
     pub fn with_spi<T, F, E>(&mut self, spi: &mut SPI, body: F) -> T
     where
-        F: FnOnce(&mut ActiveBlueNRG<SPI, OutputPin1, OutputPin2, InputPin, GpioError>) -> T,
+        F: FnOnce(
+            &mut ActiveBlueNRG<
+                '_,
+                '_,
+                'buf,
+                SPI,
+                OutputPin1,
+                OutputPin2,
+                InputPin,
+                GpioError,
+                RxBuffer,
+            >,
+        ) -> T,
+        SPI: emhal::blocking::spi::transfer::Default<u8, Error = E>
+            + emhal::blocking::spi::write::Default<u8, Error = E>,
+    {
+        self.with_spi_options(spi, SpiOptions::new(), body)
+    }
+
+    /// Invokes the given body function with an ActiveBlueNRG that uses this BlueNRG struct and the
+    /// provided SPI bus handle, like [`with_spi`](BlueNRG::with_spi), with the given
+    /// [options](SpiOptions): a deadline for the waits for the controller, a capture of the
+    /// traffic, or both.
+    ///
+    /// Returns the result of the invoked body.
+    pub fn with_spi_options<'a, T, F, E>(
+        &mut self,
+        spi: &'a mut SPI,
+        options: SpiOptions<'a>,
+        body: F,
+    ) -> T
+    where
+        F: FnOnce(
+            &mut ActiveBlueNRG<
+                '_,
+                '_,
+                'buf,
+                SPI,
+                OutputPin1,
+                OutputPin2,
+                InputPin,
+                GpioError,
+                RxBuffer,
+            >,
+        ) -> T,
         SPI: emhal::blocking::spi::transfer::Default<u8, Error = E>
             + emhal::blocking::spi::write::Default<u8, Error = E>,
     {
         let mut active =
-            ActiveBlueNRG::<SPI, OutputPin1, OutputPin2, InputPin, GpioError> { spi, d: self };
+            ActiveBlueNRG::<SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer> {
+                spi,
+                d: self,
+                deadline: options.deadline,
+                deadline_running: false,
+                capture: options.capture,
+            };
         body(&mut active)
     }
 
+    /// Sets the maximum number of consecutive attempts to communicate with the controller that
+    /// may fail because it is not ready (either not awake, or without enough space for a write).
+    /// Once the limit is reached, the operation fails with [`Error::Timeout`].
+    ///
+    /// The default, `None`, retries forever.
+    pub fn set_retry_limit(&mut self, limit: Option<u32>) {
+        self.retry_limit = limit;
+        self.retries = 0;
+    }
+
+    /// Records an attempt that failed because the controller was not ready. Returns true if the
+    /// retry limit has been reached, and resets the count if so.
+    fn count_retry(&mut self) -> bool {
+        self.retries = self.retries.saturating_add(1);
+        match self.retry_limit {
+            Some(limit) if self.retries >= limit => {
+                self.retries = 0;
+                true
+            }
+            _ => false,
+        }
+    }
+
     /// Resets the BlueNRG Controller. Uses the given timer to delay 1 cycle at `freq` Hz after
     /// toggling the reset pin.
     pub fn reset<T, Time>(&mut self, timer: &mut T, freq: Time) -> nb::Result<(), OutputPin2::Error>
@@ -484,15 +1130,154 @@ where
     {
         self.reset.set_low().map_err(nb::Error::Other)?;
         timer.start(freq);
-        block!(timer.wait()).unwrap();
+        block!(timer.wait()).unwrap_or_else(|never| match never {});
 
         self.reset.set_high().map_err(nb::Error::Other)?;
         timer.start(freq);
-        block!(timer.wait()).unwrap();
+        block!(timer.wait()).unwrap_or_else(|never| match never {});
+
+        self.command_credits = 1;
+        self.outstanding_commands = 0;
 
         Ok(())
     }
 
+    /// Returns the number of HCI command packets the controller can currently accept.
+    ///
+    /// The controller reports this in every Command Complete and Command Status event. Until a
+    /// credit comes back, writing a command returns `nb::Error::WouldBlock`. After a reset, the
+    /// controller can accept one command.
+    pub fn command_credits(&self) -> u8 {
+        self.command_credits
+    }
+
+    /// Returns the number of commands that have been written, but whose Command Complete or Command
+    /// Status event has not been read.
+    pub fn outstanding_commands(&self) -> u8 {
+        self.outstanding_commands
+    }
+
+    /// Returns the total number of bytes from the controller that were dropped because they did not
+    /// fit in the RX buffer. Each time bytes are dropped, the read returns [`Error::RxOverflow`].
+    ///
+    /// A count that keeps growing means the RX buffer is too small for the traffic, or the
+    /// application does not read events often enough.
+    pub fn dropped_bytes(&self) -> u32 {
+        self.dropped_bytes
+    }
+
+    /// Returns the counters for data read from the controller into the RX buffer.
+    ///
+    /// Together with a timer, these measure read throughput, and the number of transfers it took;
+    /// for example, to compare [`with_spi`](BlueNRG::with_spi) with `with_spi_dma` (with the `dma`
+    /// feature).
+    pub fn rx_stats(&self) -> RxStats {
+        self.rx_stats
+    }
+
+    /// Resets the counters returned by [`rx_stats`](BlueNRG::rx_stats) to zero.
+    pub fn reset_rx_stats(&mut self) {
+        self.rx_stats = RxStats::default();
+    }
+
+    /// Sets the clock used to measure the [time](RxStats::time) taken by reads. `now` returns the
+    /// time elapsed since any fixed point, e.g. from a free-running timer.
+    ///
+    /// The default, `None`, leaves the time at zero.
+    pub fn set_rx_clock(&mut self, now: Option<fn() -> Duration>) {
+        self.rx_clock = now;
+    }
+
+    /// Returns the current time of the clock given to [`set_rx_clock`](BlueNRG::set_rx_clock), or
+    /// `None` if there is no clock.
+    fn rx_time(&self) -> Option<Duration> {
+        self.rx_clock.map(|now| now())
+    }
+
+    /// Adds a transfer of `bytes` bytes that started at `started` (see
+    /// [`rx_time`](BlueNRG::rx_time)) to the RX stats.
+    fn record_rx(&mut self, bytes: usize, started: Option<Duration>) {
+        let time = match (started, self.rx_time()) {
+            (Some(started), Some(now)) => now.saturating_sub(started),
+            _ => Duration::default(),
+        };
+        self.rx_stats.record(bytes, time);
+    }
+
+    /// Returns the HCI dialect used to encode commands for, and parse events from, the controller.
+    ///
+    /// Until it is [set](BlueNRG::set_dialect), this is the [default](dialect::Dialect::default)
+    /// dialect selected by the `ms` feature.
+    pub fn dialect(&self) -> dialect::Dialect {
+        self.dialect
+    }
+
+    /// Sets the HCI dialect spoken by the controller, usually from the [`Version`] it reports (see
+    /// [`Dialect::from_version`](dialect::Dialect::from_version)). Commands are then encoded, and
+    /// events parsed, in this dialect.
+    pub fn set_dialect(&mut self, dialect: dialect::Dialect) {
+        self.dialect = dialect;
+    }
+
+    /// Returns the version reported by the controller, if it has been
+    /// [recorded](BlueNRG::set_version).
+    pub fn version(&self) -> Option<Version> {
+        self.version
+    }
+
+    /// Records the version reported by the controller, and [sets the dialect](BlueNRG::set_dialect)
+    /// it implies. Commands that the controller's firmware does not support then fail with an
+    /// `Unsupported` error; see the [`capability`] module.
+    pub fn set_version(&mut self, version: Version) {
+        self.version = Some(version);
+        self.set_dialect(dialect::Dialect::from_version(&version));
+    }
+
+    /// Recovers from an RX buffer overflow after the `unread` bytes that did not fit have been
+    /// read from the controller and thrown away.
+    ///
+    /// The last packet in the RX buffer is incomplete, because the rest of it was thrown away.
+    /// Drops it, so the next read starts at a packet boundary, and counts the dropped bytes.
+    fn drop_rx_overflow(&mut self, unread: usize) {
+        let size = self.rx_buffer.size();
+        let mut boundary = 0;
+        while boundary + EVENT_PACKET_HEADER_LEN <= size
+            && self.rx_buffer.peek(boundary) == PACKET_TYPE_HCI_EVENT
+        {
+            let packet_len = EVENT_PACKET_HEADER_LEN + self.rx_buffer.peek(boundary + 2) as usize;
+            if boundary + packet_len > size {
+                break;
+            }
+            boundary += packet_len;
+        }
+        self.rx_buffer.truncate(boundary);
+
+        let dropped = (size - boundary + unread) as u32;
+        self.dropped_bytes = self.dropped_bytes.saturating_add(dropped);
+    }
+
+    /// Updates the command credits from a packet read from the controller. `packet` must be a
+    /// whole packet, as read by [`hci::host::uart::Hci::read`].
+    fn update_command_credits(&mut self, packet: &[u8]) {
+        // Command Complete: packet type, event code, length, credits, opcode, ...
+        // Command Status: packet type, event code, length, status, credits, opcode
+        let (credits_index, opcode_index) = match packet {
+            [PACKET_TYPE_HCI_EVENT, EVENT_COMMAND_COMPLETE, ..] => (3, 4),
+            [PACKET_TYPE_HCI_EVENT, EVENT_COMMAND_STATUS, ..] => (4, 5),
+            _ => return,
+        };
+        if packet.len() < opcode_index + 2 {
+            return;
+        }
+
+        self.command_credits = packet[credits_index];
+
+        // A zero opcode means the event was not caused by a command, and only updates the credits.
+        if LittleEndian::read_u16(&packet[opcode_index..]) != 0 {
+            self.outstanding_commands = self.outstanding_commands.saturating_sub(1);
+        }
+    }
+
     /// Returns true if the controller has data ready to transmit to the host.
     fn data_ready(&self) -> Result<bool, InputPin::Error> {
         self.data_ready.is_high()
@@ -500,7 +1285,7 @@ where
 }
 
 /// Vendor-specific interpretation of the local version information from the controller.
-#[derive(Clone)]
+#[derive(Copy, Clone, Debug, PartialEq)]
 pub struct Version {
     /// Version of the controller hardware.
     pub hw_version: u8,
@@ -544,11 +1329,19 @@ pub enum HardwareError {
     /// Caused by a slow crystal startup and they are an indication that the HS_STARTUP_TIME in the
     /// device configuration needs to be tuned. After this event is recommended to hardware reset
     /// the device.
+    ///
+    /// HS_STARTUP_TIME is part of the stack configuration in the controller's information
+    /// register, not of the config data written by
+    /// [`write_config_data`](crate::hal::Commands::write_config_data).
     RadioState,
 
     /// Caused by a slow crystal startup and they are an indication that the HS_STARTUP_TIME in the
     /// device configuration needs to be tuned. After this event is recommended to hardware reset
     /// the device.
+    ///
+    /// HS_STARTUP_TIME is part of the stack configuration in the controller's information
+    /// register, not of the config data written by
+    /// [`write_config_data`](crate::hal::Commands::write_config_data).
     TimerOverrun,
 }
 
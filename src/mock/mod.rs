@@ -0,0 +1,1038 @@
+//! A scripted, in-memory BlueNRG controller for testing applications without hardware.
+//!
+//! A [`Mock`] fakes the controller's side of the SPI protocol: the header handshake, the data
+//! ready line, and the reset pin. Behind it, a small model of the stack answers the commands an
+//! application needs to start up and build its GATT database:
+//!
+//! - [Read Local Version Information](hci::host::Hci::read_local_version_information) returns the
+//!   mock's [`Version`].
+//! - The HAL commands [get_firmware_revision](crate::hal::Commands::get_firmware_revision),
+//!   [write_config_data](crate::hal::Commands::write_config_data),
+//!   [read_config_data](crate::hal::Commands::read_config_data) and
+//!   [get_link_status](crate::hal::Commands::get_link_status) work on a plain copy of the
+//!   configuration area, with every link idle.
+//! - The GATT commands [init](crate::gatt::Commands::init),
+//!   [add_service](crate::gatt::Commands::add_service),
+//!   [add_characteristic](crate::gatt::Commands::add_characteristic),
+//!   [add_characteristic_descriptor](crate::gatt::Commands::add_characteristic_descriptor),
+//!   [update_characteristic_value](crate::gatt::Commands::update_characteristic_value) and
+//!   [read_handle_value](crate::gatt::Commands::read_handle_value) allocate handles and store
+//!   attribute values the way the controller does.
+//! - [GAP init](crate::gap::Commands::init) adds the GAP service.
+//!
+//! - The GAP and GATT client commands listed in [`Radio`] reach a second mock over a virtual
+//!   radio.
+//!
+//! Every other command succeeds without doing anything, and returns only a status. Commands are
+//! answered with a Command Complete event, except for the procedures that the controller answers
+//! with a Command Status event. The mock speaks the BlueNRG-MS dialect.
+//!
+//! The pins and the SPI bus borrow the mock, so the test can script events and inspect the state
+//! while the [`BlueNRG`](crate::BlueNRG) uses it:
+//!
+//! ```ignore
+//! let mock = Mock::new();
+//! let mut rx_buffer = [0; 259];
+//! let mut bnrg = BlueNRG::new(
+//!     &mut rx_buffer,
+//!     mock.chip_select(),
+//!     mock.data_ready(),
+//!     mock.reset_pin(),
+//! );
+//! let bring_up = bnrg.bring_up(&mut mock.spi(), &mut timer, &config)?;
+//! mock.write_attribute(ConnectionHandle(0x0801), AttributeHandle(0x000E), &[1]);
+//! ```
+//!
+//! The mock starts powered on, with a [`HalInitialized`](crate::event::BlueNRGEvent::HalInitialized)
+//! event waiting to be read. Lowering the reset pin loses all of its state; raising it again
+//! starts over.
+
+mod radio;
+
+pub use self::radio::Radio;
+
+use crate::event::Status;
+use crate::Version;
+use byteorder::{ByteOrder, LittleEndian};
+use core::cell::RefCell;
+use core::convert::Infallible;
+use hci::Opcode;
+
+/// Number of bytes the mock accepts in one write: a command header and the longest parameters.
+const INPUT_LEN: usize = 4 + 255;
+
+/// Number of bytes of events the mock can hold until the host reads them.
+const OUTPUT_LEN: usize = 1024;
+
+/// Length of the configuration area: the documented offsets, 0x00 to 0x2D.
+pub const CONFIG_DATA_LEN: usize = crate::hal::ConfigData::MAX_LENGTH;
+
+/// Offset and length of each parameter that [`read_config_data`] can return.
+///
+/// [`read_config_data`]: crate::hal::Commands::read_config_data
+const CONFIG_PARAMETERS: [(u8, usize); 6] = [(0, 6), (6, 2), (8, 16), (24, 16), (40, 1), (41, 1)];
+
+/// Maximum number of services, including the GATT and GAP services.
+const MAX_SERVICES: usize = 16;
+
+/// Maximum number of attributes with a value: characteristic values and descriptors.
+const MAX_ATTRIBUTES: usize = 64;
+
+/// Number of bytes available for attribute values.
+const VALUE_POOL_LEN: usize = 2048;
+
+const HCI_VERSION: u8 = 7; // Bluetooth 4.1
+const MANUFACTURER_ST: u16 = 0x0030;
+
+const READY: u8 = 0x02;
+const WRITE: u8 = 0x0A;
+const READ: u8 = 0x0B;
+
+const PACKET_TYPE_COMMAND: u8 = 0x01;
+const PACKET_TYPE_EVENT: u8 = 0x04;
+const EVENT_COMMAND_COMPLETE: u8 = 0x0E;
+const EVENT_COMMAND_STATUS: u8 = 0x0F;
+const EVENT_VENDOR: u8 = 0xFF;
+
+const READ_LOCAL_VERSION_INFORMATION: Opcode = Opcode::new(0x04, 0x0001);
+
+// Characteristic properties that add a Client Characteristic Configuration descriptor.
+const NOTIFY: u8 = 0x10;
+const INDICATE: u8 = 0x20;
+
+/// A fake BlueNRG controller. See the [module](crate::mock) documentation.
+pub struct Mock {
+    state: RefCell<State>,
+}
+
+impl Default for Mock {
+    fn default() -> Mock {
+        Mock::new()
+    }
+}
+
+impl Mock {
+    /// Returns a mock of a BlueNRG-MS with firmware version 7.2a, that has just started.
+    pub fn new() -> Mock {
+        Mock::with_version(Version {
+            hw_version: 0x31,
+            major: 7,
+            minor: 2,
+            patch: 1,
+        })
+    }
+
+    /// Returns a mock that reports the given version, and has just started.
+    pub fn with_version(version: Version) -> Mock {
+        let mut state = State {
+            version,
+            in_reset: false,
+            resets: 0,
+            frame: Frame::Idle,
+            header: [0; 5],
+            input: [0; INPUT_LEN],
+            input_len: 0,
+            output: Queue::new(),
+            reply: 0,
+            stack: Stack::new(),
+            failure: None,
+            last_opcode: None,
+            command_count: 0,
+        };
+        state.start();
+
+        Mock {
+            state: RefCell::new(state),
+        }
+    }
+
+    /// Returns the SPI bus to the mock.
+    pub fn spi(&self) -> Spi<'_> {
+        Spi { mock: self }
+    }
+
+    /// Returns the chip select pin of the mock.
+    pub fn chip_select(&self) -> ChipSelect<'_> {
+        ChipSelect { mock: self }
+    }
+
+    /// Returns the data ready pin of the mock. It is high while the mock has an event to send.
+    pub fn data_ready(&self) -> DataReady<'_> {
+        DataReady { mock: self }
+    }
+
+    /// Returns the reset pin of the mock.
+    pub fn reset_pin(&self) -> ResetPin<'_> {
+        ResetPin { mock: self }
+    }
+
+    /// Queues an HCI event packet, including the packet type (`0x04`), for the host to read.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the packet is not an event, or if the mock cannot hold it.
+    pub fn push_event(&self, packet: &[u8]) {
+        assert!(
+            packet.len() >= 3
+                && packet[0] == PACKET_TYPE_EVENT
+                && packet.len() == 3 + packet[2] as usize,
+            "not an HCI event packet"
+        );
+        self.state.borrow_mut().output.push(packet);
+    }
+
+    /// Queues a vendor-specific event with the given event code and parameters for the host to
+    /// read.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the mock cannot hold the event.
+    pub fn push_vendor_event(&self, event_code: u16, params: &[u8]) {
+        self.state
+            .borrow_mut()
+            .output
+            .push_vendor_event(event_code, params);
+    }
+
+    /// Makes the next command with the given opcode fail with `status`. The command does not
+    /// change the state of the mock, and returns zeros after the status.
+    ///
+    /// Only one failure can be armed at a time; arming another replaces it.
+    pub fn fail_next(&self, opcode: Opcode, status: hci::Status<Status>) {
+        self.state.borrow_mut().failure = Some((opcode, status));
+    }
+
+    /// Simulates a write from the peer on the given connection: stores `data` in the attribute,
+    /// and queues the [`GattAttributeModified`](crate::event::BlueNRGEvent::GattAttributeModified)
+    /// event.
+    ///
+    /// # Panics
+    ///
+    /// Panics if there is no attribute with the handle, if `data` does not fit in it, or if the
+    /// mock cannot hold the event.
+    pub fn write_attribute(
+        &self,
+        conn_handle: hci::ConnectionHandle,
+        attr_handle: crate::event::AttributeHandle,
+        data: &[u8],
+    ) {
+        let mut state = self.state.borrow_mut();
+        let status = state.stack.write_value(attr_handle.0, 0, data);
+        assert_eq!(status, Ok(()), "cannot write attribute {:?}", attr_handle);
+
+        state
+            .stack
+            .attribute_modified(conn_handle.0, attr_handle.0, data);
+        state.deliver();
+    }
+
+    /// Calls `f` with the value of the attribute with the given handle, and returns its result.
+    /// Returns `None` if there is no attribute with a value at the handle.
+    pub fn attribute_value<T, F>(&self, handle: crate::event::AttributeHandle, f: F) -> Option<T>
+    where
+        F: FnOnce(&[u8]) -> T,
+    {
+        self.state.borrow().stack.value(handle.0).map(f)
+    }
+
+    /// Returns a copy of the configuration area.
+    pub fn config_data(&self) -> [u8; CONFIG_DATA_LEN] {
+        self.state.borrow().stack.config_data
+    }
+
+    /// Returns the opcode of the last command the mock received.
+    pub fn last_opcode(&self) -> Option<Opcode> {
+        self.state.borrow().last_opcode
+    }
+
+    /// Returns the number of commands the mock received.
+    pub fn command_count(&self) -> usize {
+        self.state.borrow().command_count
+    }
+
+    /// Returns the number of times the mock was reset with its reset pin.
+    pub fn reset_count(&self) -> usize {
+        self.state.borrow().resets
+    }
+
+    /// Returns true if the mock has an event the host has not read.
+    pub fn has_pending_events(&self) -> bool {
+        !self.state.borrow().output.is_empty()
+    }
+}
+
+/// SPI bus to a [`Mock`]. Implements the blocking SPI traits, one byte at a time.
+pub struct Spi<'m> {
+    mock: &'m Mock,
+}
+
+impl<'m> emhal::spi::FullDuplex<u8> for Spi<'m> {
+    type Error = Infallible;
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        Ok(self.mock.state.borrow().reply)
+    }
+
+    fn send(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+        self.mock.state.borrow_mut().exchange(byte);
+        Ok(())
+    }
+}
+
+impl<'m> emhal::blocking::spi::transfer::Default<u8> for Spi<'m> {}
+
+impl<'m> emhal::blocking::spi::write::Default<u8> for Spi<'m> {}
+
+/// Chip select pin of a [`Mock`]. Lowering it starts an SPI frame; raising it ends the frame, and
+/// executes the command written in it.
+pub struct ChipSelect<'m> {
+    mock: &'m Mock,
+}
+
+impl<'m> emhal::digital::v2::OutputPin for ChipSelect<'m> {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        self.mock.state.borrow_mut().begin_frame();
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        self.mock.state.borrow_mut().end_frame();
+        Ok(())
+    }
+}
+
+/// Data ready pin of a [`Mock`].
+pub struct DataReady<'m> {
+    mock: &'m Mock,
+}
+
+impl<'m> emhal::digital::v2::InputPin for DataReady<'m> {
+    type Error = Infallible;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        let state = self.mock.state.borrow();
+        Ok(!state.in_reset && !state.output.is_empty())
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        self.is_high().map(|high| !high)
+    }
+}
+
+/// Reset pin of a [`Mock`]. While it is low, the mock is not ready for SPI frames. Raising it
+/// starts the mock over, and queues the
+/// [`HalInitialized`](crate::event::BlueNRGEvent::HalInitialized) event.
+pub struct ResetPin<'m> {
+    mock: &'m Mock,
+}
+
+impl<'m> emhal::digital::v2::OutputPin for ResetPin<'m> {
+    type Error = Infallible;
+
+    fn set_low(&mut self) -> Result<(), Self::Error> {
+        let mut state = self.mock.state.borrow_mut();
+        state.in_reset = true;
+        state.output.clear();
+        Ok(())
+    }
+
+    fn set_high(&mut self) -> Result<(), Self::Error> {
+        let mut state = self.mock.state.borrow_mut();
+        if state.in_reset {
+            state.in_reset = false;
+            state.resets += 1;
+            state.start();
+        }
+        Ok(())
+    }
+}
+
+/// Progress through an SPI frame.
+#[derive(Copy, Clone, PartialEq)]
+enum Frame {
+    /// The chip select line is high.
+    Idle,
+
+    /// The host is exchanging the SPI header. Includes the number of bytes exchanged, and the
+    /// access byte once it is known.
+    Header(usize, u8),
+
+    /// The host is writing a packet.
+    Write,
+
+    /// The host is reading. Includes the number of bytes left to read.
+    Read(usize),
+}
+
+struct State {
+    version: Version,
+    in_reset: bool,
+    resets: usize,
+    frame: Frame,
+    header: [u8; 5],
+    input: [u8; INPUT_LEN],
+    input_len: usize,
+    output: Queue,
+    reply: u8,
+    stack: Stack,
+    failure: Option<(Opcode, hci::Status<Status>)>,
+    last_opcode: Option<Opcode>,
+    command_count: usize,
+}
+
+impl State {
+    /// Starts the controller with a clean slate, as after power on or a reset.
+    fn start(&mut self) {
+        self.stack = Stack::new();
+        self.output.clear();
+        self.output.push_vendor_event(0x0001, &[0x01]);
+    }
+
+    fn begin_frame(&mut self) {
+        self.header = if self.in_reset {
+            [0; 5]
+        } else {
+            let mut header = [READY, 0, 0, 0, 0];
+            LittleEndian::write_u16(&mut header[1..], INPUT_LEN as u16);
+            LittleEndian::write_u16(&mut header[3..], self.output.next_packet_len() as u16);
+            header
+        };
+        self.frame = Frame::Header(0, 0);
+        self.input_len = 0;
+    }
+
+    fn end_frame(&mut self) {
+        if self.frame == Frame::Write && self.input_len > 0 {
+            self.execute();
+        }
+        self.frame = Frame::Idle;
+    }
+
+    /// Receives one byte from the host, and prepares the byte to return.
+    fn exchange(&mut self, byte: u8) {
+        self.reply = 0;
+        match self.frame {
+            Frame::Idle => (),
+            Frame::Header(index, access) => {
+                self.reply = self.header[index];
+                let access = if index == 0 { byte } else { access };
+                self.frame = if index + 1 < self.header.len() {
+                    Frame::Header(index + 1, access)
+                } else if self.header[0] != READY {
+                    Frame::Idle
+                } else {
+                    match access {
+                        WRITE => Frame::Write,
+                        READ => Frame::Read(LittleEndian::read_u16(&self.header[3..]) as usize),
+                        _ => Frame::Idle,
+                    }
+                };
+            }
+            Frame::Write => {
+                if self.input_len < INPUT_LEN {
+                    self.input[self.input_len] = byte;
+                    self.input_len += 1;
+                }
+            }
+            Frame::Read(remaining) => {
+                if remaining > 0 {
+                    self.reply = self.output.pop().unwrap_or(0);
+                    self.frame = Frame::Read(remaining - 1);
+                }
+            }
+        }
+    }
+
+    /// Executes the command written in the last frame, and queues its Command Complete or Command
+    /// Status event, followed by any events the command caused.
+    /// Ignores anything that is not a complete command packet.
+    fn execute(&mut self) {
+        let packet = &self.input[..self.input_len];
+        if packet.len() < 4
+            || packet[0] != PACKET_TYPE_COMMAND
+            || packet.len() != 4 + packet[3] as usize
+        {
+            return;
+        }
+        let opcode = Opcode(LittleEndian::read_u16(&packet[1..]));
+        let mut params = [0; 255];
+        let params_len = packet.len() - 4;
+        params[..params_len].copy_from_slice(&packet[4..]);
+        let params = &params[..params_len];
+
+        self.command_count += 1;
+        self.last_opcode = Some(opcode);
+
+        let failure = match self.failure {
+            Some((failed, status)) if failed == opcode => {
+                self.failure = None;
+                Some((self.stack.clone(), status))
+            }
+            _ => None,
+        };
+
+        let mut ret = [0; 252];
+        let len = self.stack.execute(&self.version, opcode, params, &mut ret);
+        if let Some((stack, status)) = failure {
+            self.stack = stack;
+            ret[0] = status.into();
+            for byte in ret[1..len].iter_mut() {
+                *byte = 0;
+            }
+        }
+
+        if radio::completes_with_status(opcode) {
+            let mut event = [PACKET_TYPE_EVENT, EVENT_COMMAND_STATUS, 4, ret[0], 1, 0, 0];
+            LittleEndian::write_u16(&mut event[5..], opcode.0);
+            self.output.push(&event);
+        } else {
+            let mut event = [0; 3 + 255];
+            event[0] = PACKET_TYPE_EVENT;
+            event[1] = EVENT_COMMAND_COMPLETE;
+            event[2] = (3 + len) as u8;
+            event[3] = 1; // Number of HCI command packets
+            LittleEndian::write_u16(&mut event[4..], opcode.0);
+            event[6..6 + len].copy_from_slice(&ret[..len]);
+            self.output.push(&event[..6 + len]);
+        }
+        self.deliver();
+    }
+
+    /// Moves the events the stack generated to the host's queue.
+    fn deliver(&mut self) {
+        while let Some(byte) = self.stack.events.pop() {
+            self.output.push(&[byte]);
+        }
+    }
+}
+
+/// Events waiting to be read by the host.
+#[derive(Clone)]
+struct Queue {
+    buffer: [u8; OUTPUT_LEN],
+    head: usize,
+    len: usize,
+}
+
+impl Queue {
+    fn new() -> Queue {
+        Queue {
+            buffer: [0; OUTPUT_LEN],
+            head: 0,
+            len: 0,
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    fn clear(&mut self) {
+        self.head = 0;
+        self.len = 0;
+    }
+
+    fn push(&mut self, packet: &[u8]) {
+        assert!(
+            self.len + packet.len() <= OUTPUT_LEN,
+            "mock event queue is full"
+        );
+        for byte in packet {
+            self.buffer[(self.head + self.len) % OUTPUT_LEN] = *byte;
+            self.len += 1;
+        }
+    }
+
+    fn push_vendor_event(&mut self, event_code: u16, params: &[u8]) {
+        let mut event = [0; 3 + 255];
+        event[0] = PACKET_TYPE_EVENT;
+        event[1] = EVENT_VENDOR;
+        event[2] = (2 + params.len()) as u8;
+        LittleEndian::write_u16(&mut event[3..], event_code);
+        event[5..5 + params.len()].copy_from_slice(params);
+        self.push(&event[..5 + params.len()]);
+    }
+
+    fn pop(&mut self) -> Option<u8> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let byte = self.buffer[self.head];
+        self.head = (self.head + 1) % OUTPUT_LEN;
+        self.len -= 1;
+        Some(byte)
+    }
+
+    /// Returns the number of bytes left in the next packet. The mock offers one packet per read.
+    fn next_packet_len(&self) -> usize {
+        if self.len < 3 {
+            return self.len;
+        }
+
+        let param_len = self.buffer[(self.head + 2) % OUTPUT_LEN] as usize;
+        core::cmp::min(self.len, 3 + param_len)
+    }
+}
+
+#[derive(Copy, Clone)]
+struct Service {
+    handle: u16,
+    end: u16,
+    next: u16,
+}
+
+#[derive(Copy, Clone)]
+struct Attribute {
+    handle: u16,
+    start: usize,
+    capacity: usize,
+    len: usize,
+    variable: bool,
+    cccd: bool,
+}
+
+/// State of the modeled parts of the stack.
+#[derive(Clone)]
+struct Stack {
+    config_data: [u8; CONFIG_DATA_LEN],
+    gatt_initialized: bool,
+    gap_initialized: bool,
+    next_handle: u16,
+    services: [Option<Service>; MAX_SERVICES],
+    attributes: [Option<Attribute>; MAX_ATTRIBUTES],
+    values: [u8; VALUE_POOL_LEN],
+    values_used: usize,
+    link: radio::Link,
+
+    /// Events for the host, generated while executing a command or by the radio.
+    events: Queue,
+}
+
+type StackResult<T> = Result<T, hci::Status<Status>>;
+
+fn uuid_len(uuid_type: u8) -> StackResult<usize> {
+    match uuid_type {
+        0x01 => Ok(2),
+        0x02 => Ok(16),
+        _ => Err(hci::Status::Vendor(Status::InvalidParameters)),
+    }
+}
+
+impl Stack {
+    fn new() -> Stack {
+        Stack {
+            config_data: [0; CONFIG_DATA_LEN],
+            gatt_initialized: false,
+            gap_initialized: false,
+            next_handle: 1,
+            services: [None; MAX_SERVICES],
+            attributes: [None; MAX_ATTRIBUTES],
+            values: [0; VALUE_POOL_LEN],
+            values_used: 0,
+            link: radio::Link::new(),
+            events: Queue::new(),
+        }
+    }
+
+    /// Executes a command. Writes its return parameters, starting with the status, to `ret`, and
+    /// returns their length.
+    fn execute(
+        &mut self,
+        version: &Version,
+        opcode: Opcode,
+        params: &[u8],
+        ret: &mut [u8],
+    ) -> usize {
+        let (result, len) = match opcode {
+            READ_LOCAL_VERSION_INFORMATION => {
+                ret[1] = HCI_VERSION;
+                LittleEndian::write_u16(
+                    &mut ret[2..],
+                    (u16::from(version.hw_version) << 8) | u16::from(version.major),
+                );
+                ret[4] = HCI_VERSION;
+                LittleEndian::write_u16(&mut ret[5..], MANUFACTURER_ST);
+                LittleEndian::write_u16(
+                    &mut ret[7..],
+                    (u16::from(version.minor) << 4) | u16::from(version.patch),
+                );
+                (Ok(()), 9)
+            }
+            crate::opcode::HAL_GET_FIRMWARE_REVISION => {
+                LittleEndian::write_u16(
+                    &mut ret[1..],
+                    (u16::from(version.major) << 8)
+                        | (u16::from(version.minor) << 4)
+                        | u16::from(version.patch),
+                );
+                (Ok(()), 3)
+            }
+            crate::opcode::HAL_WRITE_CONFIG_DATA => (self.write_config_data(params), 1),
+            crate::opcode::HAL_READ_CONFIG_DATA => {
+                match self.read_config_data(params, &mut ret[1..]) {
+                    Ok(len) => (Ok(()), 1 + len),
+                    Err(e) => (Err(e), 2),
+                }
+            }
+            // Every link is idle.
+            crate::opcode::HAL_GET_LINK_STATUS => (Ok(()), 25),
+            crate::opcode::GATT_INIT => (self.gatt_init(), 1),
+            crate::opcode::GATT_ADD_SERVICE => (self.add_service_command(params, &mut ret[1..]), 3),
+            crate::opcode::GATT_ADD_CHARACTERISTIC => {
+                (self.add_characteristic_command(params, &mut ret[1..]), 3)
+            }
+            crate::opcode::GATT_ADD_CHARACTERISTIC_DESCRIPTOR => {
+                (self.add_descriptor_command(params, &mut ret[1..]), 3)
+            }
+            crate::opcode::GATT_UPDATE_CHARACTERISTIC_VALUE => {
+                (self.update_characteristic_value(params), 1)
+            }
+            crate::opcode::GATT_READ_HANDLE_VALUE => {
+                match self.read_handle_value(params, &mut ret[3..]) {
+                    Ok(len) => {
+                        LittleEndian::write_u16(&mut ret[1..], len as u16);
+                        (Ok(()), 3 + len)
+                    }
+                    Err(e) => (Err(e), 3),
+                }
+            }
+            crate::opcode::GAP_INIT => (self.gap_init(params, &mut ret[1..]), 7),
+            _ => (
+                self.execute_link_command(opcode, params).unwrap_or(Ok(())),
+                1,
+            ),
+        };
+
+        match result {
+            Ok(()) => ret[0] = hci::Status::<Status>::Success.into(),
+            Err(status) => {
+                ret[0] = status.into();
+                for byte in ret[1..len].iter_mut() {
+                    *byte = 0;
+                }
+            }
+        }
+
+        len
+    }
+
+    fn write_config_data(&mut self, params: &[u8]) -> StackResult<()> {
+        if params.len() < 2 {
+            return Err(hci::Status::Vendor(Status::InvalidParameters));
+        }
+        let offset = params[0] as usize;
+        let len = params[1] as usize;
+        if params.len() != 2 + len || offset + len > CONFIG_DATA_LEN {
+            return Err(hci::Status::Vendor(Status::InvalidParameters));
+        }
+
+        self.config_data[offset..offset + len].copy_from_slice(&params[2..]);
+        Ok(())
+    }
+
+    fn read_config_data(&self, params: &[u8], value: &mut [u8]) -> StackResult<usize> {
+        let offset = *params
+            .first()
+            .ok_or(hci::Status::Vendor(Status::InvalidParameters))?;
+        let len = CONFIG_PARAMETERS
+            .iter()
+            .find(|(param, _)| *param == offset)
+            .map(|(_, len)| *len)
+            .ok_or(hci::Status::Vendor(Status::InvalidParameters))?;
+
+        let offset = offset as usize;
+        value[..len].copy_from_slice(&self.config_data[offset..offset + len]);
+        Ok(len)
+    }
+
+    fn gatt_init(&mut self) -> StackResult<()> {
+        if self.gatt_initialized {
+            return Err(hci::Status::CommandDisallowed);
+        }
+
+        // The GATT service, with the Service Changed characteristic.
+        let service = self.add_service(4)?;
+        self.add_characteristic(service, 4, false, true)?;
+        self.gatt_initialized = true;
+        Ok(())
+    }
+
+    fn gap_init(&mut self, params: &[u8], ret: &mut [u8]) -> StackResult<()> {
+        if !self.gatt_initialized || self.gap_initialized {
+            return Err(hci::Status::CommandDisallowed);
+        }
+
+        // The original BlueNRG does not take the length of the device name.
+        let dev_name_len = params.get(2).map_or(7, |len| *len as usize);
+
+        // The GAP service, with the Device Name, Appearance, and Peripheral Preferred Connection
+        // Parameters characteristics.
+        let service = self.add_service(7)?;
+        let dev_name = self.add_characteristic(service, dev_name_len, true, false)?;
+        let appearance = self.add_characteristic(service, 2, false, false)?;
+        self.add_characteristic(service, 8, false, false)?;
+        self.gap_initialized = true;
+
+        LittleEndian::write_u16(&mut ret[0..], service);
+        LittleEndian::write_u16(&mut ret[2..], dev_name);
+        LittleEndian::write_u16(&mut ret[4..], appearance);
+        Ok(())
+    }
+
+    fn add_service_command(&mut self, params: &[u8], ret: &mut [u8]) -> StackResult<()> {
+        if !self.gatt_initialized {
+            return Err(hci::Status::CommandDisallowed);
+        }
+        let uuid_type = *params
+            .first()
+            .ok_or(hci::Status::Vendor(Status::InvalidParameters))?;
+        let uuid_len = uuid_len(uuid_type)?;
+        if params.len() != 1 + uuid_len + 2 {
+            return Err(hci::Status::Vendor(Status::InvalidParameters));
+        }
+        let max_attribute_records = params[params.len() - 1];
+
+        let service = self.add_service(max_attribute_records)?;
+        LittleEndian::write_u16(ret, service);
+        Ok(())
+    }
+
+    fn add_characteristic_command(&mut self, params: &[u8], ret: &mut [u8]) -> StackResult<()> {
+        if params.len() < 3 {
+            return Err(hci::Status::Vendor(Status::InvalidParameters));
+        }
+        let service = LittleEndian::read_u16(params);
+        let next = 3 + uuid_len(params[2])?;
+
+        // Firmware before 7.2 takes a 1-byte value length; later firmware takes 2 bytes.
+        let (value_len, next) = match params.len().checked_sub(next) {
+            Some(6) => (params[next] as usize, next + 1),
+            Some(7) => (LittleEndian::read_u16(&params[next..]) as usize, next + 2),
+            _ => return Err(hci::Status::Vendor(Status::InvalidParameters)),
+        };
+        let properties = params[next];
+        let is_variable = params[next + 4] != 0;
+
+        let handle = self.add_characteristic(
+            service,
+            value_len,
+            is_variable,
+            properties & (NOTIFY | INDICATE) != 0,
+        )?;
+        LittleEndian::write_u16(ret, handle);
+        Ok(())
+    }
+
+    fn add_descriptor_command(&mut self, params: &[u8], ret: &mut [u8]) -> StackResult<()> {
+        if params.len() < 5 {
+            return Err(hci::Status::Vendor(Status::InvalidParameters));
+        }
+        let service = LittleEndian::read_u16(params);
+        let next = 5 + uuid_len(params[4])?;
+
+        #[cfg(not(feature = "bluenrg2"))]
+        let lengths = params
+            .get(next..next + 2)
+            .map(|bytes| (bytes[0] as usize, bytes[1] as usize, next + 2));
+        #[cfg(feature = "bluenrg2")]
+        let lengths = params.get(next..next + 4).map(|bytes| {
+            (
+                LittleEndian::read_u16(bytes) as usize,
+                LittleEndian::read_u16(&bytes[2..]) as usize,
+                next + 4,
+            )
+        });
+        let (max_len, len, next) = lengths.ok_or(hci::Status::Vendor(Status::InvalidParameters))?;
+        if len > max_len || params.len() != next + len + 5 {
+            return Err(hci::Status::Vendor(Status::InvalidParameters));
+        }
+        let is_variable = params[next + len + 4] != 0;
+
+        let handle = self.allocate(service, 1)?;
+        self.add_value(handle, max_len, is_variable)?;
+        self.write_value(handle, 0, &params[next..next + len])?;
+        LittleEndian::write_u16(ret, handle);
+        Ok(())
+    }
+
+    fn update_characteristic_value(&mut self, params: &[u8]) -> StackResult<()> {
+        if params.len() < 6 || params.len() != 6 + params[5] as usize {
+            return Err(hci::Status::Vendor(Status::InvalidParameters));
+        }
+        let service = LittleEndian::read_u16(params);
+        let characteristic = LittleEndian::read_u16(&params[2..]);
+        let offset = params[4] as usize;
+
+        let service = self.service(service)?;
+        if characteristic <= service.handle || characteristic >= service.end {
+            return Err(hci::Status::Vendor(Status::InvalidHandle));
+        }
+        self.write_value(characteristic + 1, offset, &params[6..])?;
+        self.notify(characteristic + 1)
+    }
+
+    fn read_handle_value(&self, params: &[u8], value: &mut [u8]) -> StackResult<usize> {
+        if params.len() != 2 {
+            return Err(hci::Status::Vendor(Status::InvalidParameters));
+        }
+
+        let stored = self
+            .value(LittleEndian::read_u16(params))
+            .ok_or(hci::Status::Vendor(Status::InvalidHandle))?;
+        // The return parameters cannot hold a longer value.
+        let len = core::cmp::min(stored.len(), value.len());
+        value[..len].copy_from_slice(&stored[..len]);
+        Ok(len)
+    }
+
+    /// Adds a service that can hold `max_attribute_records` attributes, including its declaration.
+    fn add_service(&mut self, max_attribute_records: u8) -> StackResult<u16> {
+        let handle = self.next_handle;
+        let end = u32::from(handle) + u32::from(max_attribute_records);
+        if max_attribute_records == 0 || end > 0xFFFF {
+            return Err(hci::Status::Vendor(Status::InsufficientResources));
+        }
+        let slot = self
+            .services
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(hci::Status::Vendor(Status::InsufficientResources))?;
+
+        *slot = Some(Service {
+            handle,
+            end: end as u16,
+            next: handle + 1,
+        });
+        self.next_handle = end as u16;
+        Ok(handle)
+    }
+
+    /// Adds a characteristic declaration, its value, and a Client Characteristic Configuration
+    /// descriptor if `cccd` is set. Returns the handle of the declaration.
+    fn add_characteristic(
+        &mut self,
+        service: u16,
+        value_len: usize,
+        is_variable: bool,
+        cccd: bool,
+    ) -> StackResult<u16> {
+        let handle = self.allocate(service, if cccd { 3 } else { 2 })?;
+        self.add_value(handle + 1, value_len, is_variable)?;
+        if cccd {
+            self.add_value(handle + 2, 2, false)?;
+            if let Some(attribute) = self
+                .attributes
+                .iter_mut()
+                .flatten()
+                .find(|attribute| attribute.handle == handle + 2)
+            {
+                attribute.cccd = true;
+            }
+        }
+
+        Ok(handle)
+    }
+
+    fn service(&self, handle: u16) -> StackResult<Service> {
+        self.services
+            .iter()
+            .flatten()
+            .find(|service| service.handle == handle)
+            .copied()
+            .ok_or(hci::Status::Vendor(Status::InvalidHandle))
+    }
+
+    /// Reserves `count` consecutive handles in the service, and returns the first.
+    fn allocate(&mut self, service: u16, count: u16) -> StackResult<u16> {
+        let slot = self
+            .services
+            .iter_mut()
+            .flatten()
+            .find(|slot| slot.handle == service)
+            .ok_or(hci::Status::Vendor(Status::InvalidHandle))?;
+        if u32::from(slot.next) + u32::from(count) > u32::from(slot.end) {
+            return Err(hci::Status::Vendor(Status::InsufficientResources));
+        }
+
+        let handle = slot.next;
+        slot.next += count;
+        Ok(handle)
+    }
+
+    /// Adds storage for the value of the attribute with the given handle. A fixed-length value
+    /// starts as `capacity` zeros; a variable-length value starts empty.
+    fn add_value(&mut self, handle: u16, capacity: usize, variable: bool) -> StackResult<()> {
+        if self.values_used + capacity > VALUE_POOL_LEN {
+            return Err(hci::Status::Vendor(Status::InsufficientResources));
+        }
+        let slot = self
+            .attributes
+            .iter_mut()
+            .find(|slot| slot.is_none())
+            .ok_or(hci::Status::Vendor(Status::InsufficientResources))?;
+
+        *slot = Some(Attribute {
+            handle,
+            start: self.values_used,
+            capacity,
+            len: if variable { 0 } else { capacity },
+            variable,
+            cccd: false,
+        });
+        self.values_used += capacity;
+        Ok(())
+    }
+
+    fn write_value(&mut self, handle: u16, offset: usize, data: &[u8]) -> StackResult<()> {
+        let attribute = self
+            .attributes
+            .iter_mut()
+            .flatten()
+            .find(|attribute| attribute.handle == handle)
+            .ok_or(hci::Status::Vendor(Status::InvalidHandle))?;
+        let end = offset + data.len();
+        if end > attribute.capacity {
+            return Err(hci::Status::Vendor(Status::InvalidParameters));
+        }
+
+        self.values[attribute.start + offset..attribute.start + end].copy_from_slice(data);
+        if attribute.variable {
+            attribute.len = end;
+        }
+        Ok(())
+    }
+
+    /// Queues the [`GattAttributeModified`](crate::event::BlueNRGEvent::GattAttributeModified)
+    /// event for a write from the peer.
+    fn attribute_modified(&mut self, conn_handle: u16, attr_handle: u16, data: &[u8]) {
+        let mut params = [0; 10 + 255];
+        LittleEndian::write_u16(&mut params[0..], conn_handle);
+        LittleEndian::write_u16(&mut params[2..], attr_handle);
+        #[cfg(not(feature = "bluenrg2"))]
+        let len = {
+            params[4] = data.len() as u8;
+            LittleEndian::write_u16(&mut params[5..], 0);
+            params[7..7 + data.len()].copy_from_slice(data);
+            7 + data.len()
+        };
+        #[cfg(feature = "bluenrg2")]
+        let len = {
+            LittleEndian::write_u16(&mut params[4..], 0);
+            LittleEndian::write_u16(&mut params[6..], data.len() as u16);
+            params[8..8 + data.len()].copy_from_slice(data);
+            8 + data.len()
+        };
+        self.events.push_vendor_event(0x0C01, &params[..len]);
+    }
+
+    fn value(&self, handle: u16) -> Option<&[u8]> {
+        self.attributes
+            .iter()
+            .flatten()
+            .find(|attribute| attribute.handle == handle)
+            .map(|attribute| &self.values[attribute.start..attribute.start + attribute.len])
+    }
+}
@@ -0,0 +1,677 @@
+//! A virtual radio between two mocks.
+//!
+//! Each [`Mock`] models enough of the link layer and of ATT to talk to one peer:
+//!
+//! - [set_discoverable](crate::gap::Commands::set_discoverable) advertises the local name and
+//!   advertising data, and [set_nondiscoverable](crate::gap::Commands::set_nondiscoverable) stops.
+//! - [start_general_discovery_procedure](crate::gap::Commands::start_general_discovery_procedure)
+//!   reports an advertising peer once with
+//!   [`GapDeviceFound`](crate::event::BlueNRGEvent::GapDeviceFound). The procedure runs until
+//!   [terminate_procedure](crate::gap::Commands::terminate_procedure) ends it.
+//! - [create_connection](crate::gap::Commands::create_connection) connects to a connectable,
+//!   advertising peer with the given public address. Both sides get an LE Connection Complete
+//!   event for connection handle `0x0801`.
+//! - [terminate](crate::gap::Commands::terminate) and [Disconnect](hci::host::Hci::disconnect)
+//!   end the connection with a Disconnection Complete event on both sides. A peer that is reset
+//!   while connected times out.
+//! - On the client,
+//!   [read_characteristic_value](crate::gatt::Commands::read_characteristic_value),
+//!   [write_characteristic_value](crate::gatt::Commands::write_characteristic_value) and
+//!   [write_without_response](crate::gatt::Commands::write_without_response) reach the peer's
+//!   attributes, and report the result with
+//!   [`AttReadResponse`](crate::event::BlueNRGEvent::AttReadResponse),
+//!   [`AttErrorResponse`](crate::event::BlueNRGEvent::AttErrorResponse) and
+//!   [`GattProcedureComplete`](crate::event::BlueNRGEvent::GattProcedureComplete).
+//! - On the server, writes from the peer update the attribute and report
+//!   [`GattAttributeModified`](crate::event::BlueNRGEvent::GattAttributeModified). Once the
+//!   client enables notifications in a characteristic's Client Characteristic Configuration
+//!   descriptor, [update_characteristic_value](crate::gatt::Commands::update_characteristic_value)
+//!   sends a [`GattNotification`](crate::event::BlueNRGEvent::GattNotification).
+//!
+//! The procedures answer with a Command Status event, as the controller does. Nothing goes on air
+//! by itself: the test calls [`Radio::run`] between steps of the hosts.
+
+use super::{Mock, Stack, StackResult, State, PACKET_TYPE_EVENT};
+use crate::event::Status;
+use byteorder::{ByteOrder, LittleEndian};
+use hci::Opcode;
+
+/// The handle of the one connection a mock can have.
+const CONN_HANDLE: u16 = 0x0801;
+
+/// Number of PDUs a mock can hold before they go on air.
+const MAX_PDUS: usize = 8;
+
+/// Longest attribute value in a read response, with the default ATT_MTU of 23 bytes.
+const MAX_READ_LEN: usize = 22;
+
+/// Longest attribute value in a write or notification, with the default ATT_MTU of 23 bytes.
+const MAX_WRITE_LEN: usize = 20;
+
+/// Longest advertising data.
+const MAX_ADVERTISING_DATA_LEN: usize = 31;
+
+/// Signal strength of every advertising report, in dBm.
+const RSSI: i8 = -50;
+
+const DISCONNECT: Opcode = Opcode::new(0x01, 0x0006);
+
+const EVENT_DISCONNECTION_COMPLETE: u8 = 0x05;
+const EVENT_LE_META: u8 = 0x3E;
+const LE_CONNECTION_COMPLETE: u8 = 0x01;
+
+const ADV_IND: u8 = 0x00;
+const ADDRESS_TYPE_PUBLIC: u8 = 0x00;
+const ROLE_CENTRAL: u8 = 0x00;
+const ROLE_PERIPHERAL: u8 = 0x01;
+const CENTRAL_CLOCK_ACCURACY_500_PPM: u8 = 0x00;
+
+const CONNECTION_TIMEOUT: u8 = 0x08;
+const CONNECTION_TERMINATED_BY_LOCAL_HOST: u8 = 0x16;
+
+// GAP procedures, as in terminate_procedure and GapProcedureComplete.
+const GENERAL_DISCOVERY: u8 = 0x02;
+const DIRECT_CONNECTION_ESTABLISHMENT: u8 = 0x40;
+
+const ATT_ERROR_RESPONSE: u8 = 0x01;
+const ATT_READ_REQUEST: u8 = 0x0A;
+const ATT_READ_RESPONSE: u8 = 0x0B;
+const ATT_WRITE_REQUEST: u8 = 0x12;
+const ATT_WRITE_RESPONSE: u8 = 0x13;
+const ATT_HANDLE_VALUE_NOTIFICATION: u8 = 0x1B;
+const ATT_WRITE_COMMAND: u8 = 0x52;
+
+const ATT_INVALID_HANDLE: u8 = 0x01;
+const ATT_INVALID_ATTRIBUTE_VALUE_LENGTH: u8 = 0x0D;
+
+const GATT_PROCEDURE_SUCCESS: u8 = 0x00;
+const GATT_PROCEDURE_FAILED: u8 = 0x41;
+
+/// Carries advertising, connections and ATT traffic between two mocks.
+///
+/// ```ignore
+/// let radio = Radio::new(&peripheral, &central);
+/// requester.call(&mut peripheral_controller, |c| c.set_discoverable(&params))?;
+/// requester.call(&mut central_controller, |c| c.start_general_discovery_procedure(&params))?;
+/// radio.run();
+/// // The central reads GapDeviceFound.
+/// ```
+pub struct Radio<'m> {
+    a: &'m Mock,
+    b: &'m Mock,
+}
+
+impl<'m> Radio<'m> {
+    /// Returns a radio between the two mocks.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `a` and `b` are the same mock.
+    pub fn new(a: &'m Mock, b: &'m Mock) -> Radio<'m> {
+        assert!(!core::ptr::eq(a, b), "a mock cannot talk to itself");
+        Radio { a, b }
+    }
+
+    /// Delivers everything the mocks have to send each other, including the replies, and queues
+    /// the resulting events for the hosts. Returns true if anything went on air.
+    pub fn run(&self) -> bool {
+        let mut sent = false;
+        while self.step() {
+            sent = true;
+        }
+        sent
+    }
+
+    fn step(&self) -> bool {
+        let mut a = self.a.state.borrow_mut();
+        let mut b = self.b.state.borrow_mut();
+
+        let mut sent = false;
+        if !a.in_reset && !b.in_reset {
+            sent |= a.stack.transmit(&mut b.stack);
+            sent |= b.stack.transmit(&mut a.stack);
+        }
+        sent |= supervise(&mut a, &b);
+        sent |= supervise(&mut b, &a);
+
+        a.deliver();
+        b.deliver();
+        sent
+    }
+}
+
+/// Drops the connection of `local` if its peer is gone. Returns true if it did.
+fn supervise(local: &mut State, peer: &State) -> bool {
+    if local.in_reset || local.stack.link.connection.is_none() {
+        return false;
+    }
+    if !peer.in_reset && peer.stack.link.connection.is_some() {
+        return false;
+    }
+
+    local.stack.disconnect(CONNECTION_TIMEOUT);
+    true
+}
+
+/// Returns true if the command completes with a Command Status event instead of a Command
+/// Complete event.
+pub(super) fn completes_with_status(opcode: Opcode) -> bool {
+    matches!(
+        opcode,
+        DISCONNECT
+            | crate::opcode::GAP_TERMINATE
+            | crate::opcode::GAP_START_GENERAL_DISCOVERY_PROCEDURE
+            | crate::opcode::GAP_CREATE_CONNECTION
+            | crate::opcode::GATT_READ_CHARACTERISTIC_VALUE
+            | crate::opcode::GATT_WRITE_CHARACTERISTIC_VALUE
+    )
+}
+
+#[derive(Copy, Clone)]
+struct Advertising {
+    event_type: u8,
+    data: [u8; MAX_ADVERTISING_DATA_LEN],
+    data_len: usize,
+}
+
+#[derive(Copy, Clone)]
+struct Initiating {
+    peer: [u8; 6],
+
+    /// Connection interval, peripheral latency and supervision timeout, as in the LE Connection
+    /// Complete event.
+    conn_interval: [u8; 6],
+}
+
+#[derive(Copy, Clone)]
+struct Connection {
+    /// The reason given by the host that terminates the connection.
+    terminating: Option<u8>,
+}
+
+#[derive(Copy, Clone)]
+struct Pdu {
+    opcode: u8,
+    handle: u16,
+    value: [u8; MAX_READ_LEN],
+    len: usize,
+}
+
+impl Pdu {
+    const EMPTY: Pdu = Pdu {
+        opcode: 0,
+        handle: 0,
+        value: [0; MAX_READ_LEN],
+        len: 0,
+    };
+
+    fn new(opcode: u8, handle: u16, value: &[u8]) -> Pdu {
+        let mut pdu = Pdu {
+            opcode,
+            handle,
+            len: value.len(),
+            ..Pdu::EMPTY
+        };
+        pdu.value[..value.len()].copy_from_slice(value);
+        pdu
+    }
+
+    fn value(&self) -> &[u8] {
+        &self.value[..self.len]
+    }
+}
+
+/// ATT PDUs waiting to go on air, oldest first.
+#[derive(Copy, Clone)]
+struct Outbox {
+    pdus: [Pdu; MAX_PDUS],
+    len: usize,
+}
+
+impl Outbox {
+    fn push(&mut self, pdu: Pdu) -> StackResult<()> {
+        if self.len == MAX_PDUS {
+            return Err(hci::Status::Vendor(Status::InsufficientResources));
+        }
+
+        self.pdus[self.len] = pdu;
+        self.len += 1;
+        Ok(())
+    }
+
+    fn pop(&mut self) -> Option<Pdu> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let pdu = self.pdus[0];
+        self.pdus.copy_within(1..self.len, 0);
+        self.len -= 1;
+        Some(pdu)
+    }
+}
+
+/// State of the link layer and of the GATT client.
+#[derive(Copy, Clone)]
+pub(super) struct Link {
+    advertising: Option<Advertising>,
+    scanning: bool,
+    reported: bool,
+    initiating: Option<Initiating>,
+    connection: Option<Connection>,
+    client_procedure: bool,
+    outbox: Outbox,
+}
+
+impl Link {
+    pub(super) fn new() -> Link {
+        Link {
+            advertising: None,
+            scanning: false,
+            reported: false,
+            initiating: None,
+            connection: None,
+            client_procedure: false,
+            outbox: Outbox {
+                pdus: [Pdu::EMPTY; MAX_PDUS],
+                len: 0,
+            },
+        }
+    }
+}
+
+impl Stack {
+    /// Executes the GAP and GATT client commands that use the radio. Returns `None` for any other
+    /// command.
+    pub(super) fn execute_link_command(
+        &mut self,
+        opcode: Opcode,
+        params: &[u8],
+    ) -> Option<StackResult<()>> {
+        let result = match opcode {
+            crate::opcode::GAP_SET_DISCOVERABLE => self.set_discoverable(params),
+            crate::opcode::GAP_SET_NONDISCOVERABLE => {
+                self.link.advertising = None;
+                Ok(())
+            }
+            crate::opcode::GAP_START_GENERAL_DISCOVERY_PROCEDURE => {
+                self.start_general_discovery(params)
+            }
+            crate::opcode::GAP_CREATE_CONNECTION => self.create_connection(params),
+            crate::opcode::GAP_TERMINATE_PROCEDURE => self.terminate_procedure(params),
+            crate::opcode::GAP_TERMINATE | DISCONNECT => self.terminate(params),
+            crate::opcode::GATT_READ_CHARACTERISTIC_VALUE => self.read_characteristic_value(params),
+            crate::opcode::GATT_WRITE_CHARACTERISTIC_VALUE => {
+                self.write_characteristic_value(params, ATT_WRITE_REQUEST)
+            }
+            crate::opcode::GATT_WRITE_WITHOUT_RESPONSE => {
+                self.write_characteristic_value(params, ATT_WRITE_COMMAND)
+            }
+            _ => return None,
+        };
+
+        Some(result)
+    }
+
+    /// Sends a notification with the value of the attribute, if the peer enabled notifications in
+    /// the Client Characteristic Configuration descriptor that follows it.
+    pub(super) fn notify(&mut self, handle: u16) -> StackResult<()> {
+        if self.link.connection.is_none() || !self.notifications_enabled(handle + 1) {
+            return Ok(());
+        }
+
+        let value = self.value(handle).unwrap_or(&[]);
+        let len = core::cmp::min(value.len(), MAX_WRITE_LEN);
+        let pdu = Pdu::new(ATT_HANDLE_VALUE_NOTIFICATION, handle, &value[..len]);
+        self.link.outbox.push(pdu)
+    }
+
+    fn notifications_enabled(&self, cccd: u16) -> bool {
+        self.attributes
+            .iter()
+            .flatten()
+            .any(|attribute| attribute.handle == cccd && attribute.cccd)
+            && matches!(self.value(cccd), Some(value) if value[0] & 0x01 != 0)
+    }
+
+    fn address(&self) -> [u8; 6] {
+        let mut address = [0; 6];
+        address.copy_from_slice(&self.config_data[0..6]);
+        address
+    }
+
+    fn set_discoverable(&mut self, params: &[u8]) -> StackResult<()> {
+        if !self.gap_initialized || self.link.connection.is_some() {
+            return Err(hci::Status::CommandDisallowed);
+        }
+
+        // The local name and the advertising data follow the fixed parameters, and the connection
+        // interval follows them.
+        let name_len = *params
+            .get(7)
+            .ok_or(hci::Status::Vendor(Status::InvalidParameters))? as usize;
+        let data_len_index = 8 + name_len;
+        let data_len = *params
+            .get(data_len_index)
+            .ok_or(hci::Status::Vendor(Status::InvalidParameters))? as usize;
+        let ad_len = if name_len > 0 { 1 + name_len } else { 0 } + data_len;
+        if params.len() < data_len_index + 1 + data_len + 4 || ad_len > MAX_ADVERTISING_DATA_LEN {
+            return Err(hci::Status::Vendor(Status::InvalidParameters));
+        }
+
+        let mut advertising = Advertising {
+            event_type: params[0],
+            data: [0; MAX_ADVERTISING_DATA_LEN],
+            data_len: ad_len,
+        };
+        let mut next = 0;
+        if name_len > 0 {
+            advertising.data[0] = name_len as u8;
+            advertising.data[1..=name_len].copy_from_slice(&params[8..data_len_index]);
+            next = 1 + name_len;
+        }
+        advertising.data[next..ad_len]
+            .copy_from_slice(&params[data_len_index + 1..data_len_index + 1 + data_len]);
+
+        self.link.advertising = Some(advertising);
+        Ok(())
+    }
+
+    fn start_general_discovery(&mut self, params: &[u8]) -> StackResult<()> {
+        if params.len() != 6 {
+            return Err(hci::Status::Vendor(Status::InvalidParameters));
+        }
+        if !self.gap_initialized || self.link.scanning || self.link.initiating.is_some() {
+            return Err(hci::Status::CommandDisallowed);
+        }
+
+        self.link.scanning = true;
+        self.link.reported = false;
+        Ok(())
+    }
+
+    fn create_connection(&mut self, params: &[u8]) -> StackResult<()> {
+        if params.len() != 24 {
+            return Err(hci::Status::Vendor(Status::InvalidParameters));
+        }
+        if !self.gap_initialized
+            || self.link.scanning
+            || self.link.initiating.is_some()
+            || self.link.connection.is_some()
+        {
+            return Err(hci::Status::CommandDisallowed);
+        }
+
+        let mut initiating = Initiating {
+            peer: [0; 6],
+            conn_interval: [0; 6],
+        };
+        initiating.peer.copy_from_slice(&params[5..11]);
+        // Use the minimum connection interval, then the latency and supervision timeout.
+        initiating.conn_interval[0..2].copy_from_slice(&params[12..14]);
+        initiating.conn_interval[2..6].copy_from_slice(&params[16..20]);
+        self.link.initiating = Some(initiating);
+        Ok(())
+    }
+
+    fn terminate_procedure(&mut self, params: &[u8]) -> StackResult<()> {
+        let procedure = *params
+            .first()
+            .ok_or(hci::Status::Vendor(Status::InvalidParameters))?;
+
+        let mut terminated = false;
+        if procedure & GENERAL_DISCOVERY != 0 && self.link.scanning {
+            self.link.scanning = false;
+            self.gap_procedure_complete(GENERAL_DISCOVERY);
+            terminated = true;
+        }
+        if procedure & DIRECT_CONNECTION_ESTABLISHMENT != 0 && self.link.initiating.is_some() {
+            self.link.initiating = None;
+            self.gap_procedure_complete(DIRECT_CONNECTION_ESTABLISHMENT);
+            terminated = true;
+        }
+
+        if terminated {
+            Ok(())
+        } else {
+            Err(hci::Status::CommandDisallowed)
+        }
+    }
+
+    fn terminate(&mut self, params: &[u8]) -> StackResult<()> {
+        if params.len() != 3 {
+            return Err(hci::Status::Vendor(Status::InvalidParameters));
+        }
+        let connection = self.connection(LittleEndian::read_u16(params))?;
+        if connection.terminating.is_some() {
+            return Err(hci::Status::CommandDisallowed);
+        }
+
+        connection.terminating = Some(params[2]);
+        Ok(())
+    }
+
+    fn read_characteristic_value(&mut self, params: &[u8]) -> StackResult<()> {
+        if params.len() != 4 {
+            return Err(hci::Status::Vendor(Status::InvalidParameters));
+        }
+        self.connection(LittleEndian::read_u16(params))?;
+        if self.link.client_procedure {
+            return Err(hci::Status::CommandDisallowed);
+        }
+
+        let handle = LittleEndian::read_u16(&params[2..]);
+        self.link
+            .outbox
+            .push(Pdu::new(ATT_READ_REQUEST, handle, &[]))?;
+        self.link.client_procedure = true;
+        Ok(())
+    }
+
+    fn write_characteristic_value(&mut self, params: &[u8], opcode: u8) -> StackResult<()> {
+        if params.len() < 5
+            || params.len() != 5 + params[4] as usize
+            || params[4] as usize > MAX_WRITE_LEN
+        {
+            return Err(hci::Status::Vendor(Status::InvalidParameters));
+        }
+        self.connection(LittleEndian::read_u16(params))?;
+        let is_request = opcode == ATT_WRITE_REQUEST;
+        if is_request && self.link.client_procedure {
+            return Err(hci::Status::CommandDisallowed);
+        }
+
+        let handle = LittleEndian::read_u16(&params[2..]);
+        self.link
+            .outbox
+            .push(Pdu::new(opcode, handle, &params[5..]))?;
+        self.link.client_procedure |= is_request;
+        Ok(())
+    }
+
+    fn connection(&mut self, conn_handle: u16) -> StackResult<&mut Connection> {
+        match self.link.connection {
+            Some(ref mut connection) if conn_handle == CONN_HANDLE => Ok(connection),
+            _ => Err(hci::Status::UnknownConnectionId),
+        }
+    }
+
+    /// Sends everything this stack has for the peer: advertising, a connection, and the PDUs on
+    /// the connection. Returns true if anything went on air.
+    fn transmit(&mut self, peer: &mut Stack) -> bool {
+        let mut sent = false;
+        if let Some(advertising) = self.link.advertising {
+            if peer.link.scanning && !peer.link.reported {
+                peer.link.reported = true;
+                peer.device_found(&advertising, self.address());
+                sent = true;
+            }
+
+            match peer.link.initiating {
+                Some(initiating)
+                    if advertising.event_type == ADV_IND && initiating.peer == self.address() =>
+                {
+                    peer.connect(ROLE_CENTRAL, self.address(), &initiating.conn_interval);
+                    self.connect(ROLE_PERIPHERAL, peer.address(), &initiating.conn_interval);
+                    return true;
+                }
+                _ => (),
+            }
+        }
+
+        let connection = match self.link.connection {
+            Some(connection) if peer.link.connection.is_some() => connection,
+            _ => return sent,
+        };
+        if let Some(reason) = connection.terminating {
+            self.disconnect(CONNECTION_TERMINATED_BY_LOCAL_HOST);
+            peer.disconnect(reason);
+            return true;
+        }
+        while let Some(pdu) = self.link.outbox.pop() {
+            peer.receive(&pdu);
+            sent = true;
+        }
+
+        sent
+    }
+
+    fn receive(&mut self, pdu: &Pdu) {
+        match pdu.opcode {
+            ATT_READ_REQUEST => {
+                let reply = match self.value(pdu.handle) {
+                    Some(value) => {
+                        let len = core::cmp::min(value.len(), MAX_READ_LEN);
+                        Pdu::new(ATT_READ_RESPONSE, pdu.handle, &value[..len])
+                    }
+                    None => error_response(ATT_READ_REQUEST, pdu.handle, ATT_INVALID_HANDLE),
+                };
+                self.reply(reply);
+            }
+            ATT_WRITE_REQUEST | ATT_WRITE_COMMAND => {
+                let result = self.write_value(pdu.handle, 0, pdu.value());
+                if result.is_ok() {
+                    self.attribute_modified(CONN_HANDLE, pdu.handle, pdu.value());
+                }
+                if pdu.opcode == ATT_WRITE_REQUEST {
+                    self.reply(match result {
+                        Ok(()) => Pdu::new(ATT_WRITE_RESPONSE, pdu.handle, &[]),
+                        Err(hci::Status::Vendor(Status::InvalidHandle)) => {
+                            error_response(ATT_WRITE_REQUEST, pdu.handle, ATT_INVALID_HANDLE)
+                        }
+                        Err(_) => error_response(
+                            ATT_WRITE_REQUEST,
+                            pdu.handle,
+                            ATT_INVALID_ATTRIBUTE_VALUE_LENGTH,
+                        ),
+                    });
+                }
+            }
+            ATT_READ_RESPONSE => {
+                let mut params = [0; 3 + MAX_READ_LEN];
+                LittleEndian::write_u16(&mut params[0..], CONN_HANDLE);
+                params[2] = pdu.len as u8;
+                params[3..3 + pdu.len].copy_from_slice(pdu.value());
+                self.events
+                    .push_vendor_event(0x0C07, &params[..3 + pdu.len]);
+                self.gatt_procedure_complete(GATT_PROCEDURE_SUCCESS);
+            }
+            ATT_WRITE_RESPONSE => self.gatt_procedure_complete(GATT_PROCEDURE_SUCCESS),
+            ATT_ERROR_RESPONSE => {
+                let mut params = [0; 7];
+                LittleEndian::write_u16(&mut params[0..], CONN_HANDLE);
+                params[2] = 4;
+                params[3] = pdu.value[0];
+                LittleEndian::write_u16(&mut params[4..], pdu.handle);
+                params[6] = pdu.value[1];
+                self.events.push_vendor_event(0x0C11, &params);
+                self.gatt_procedure_complete(GATT_PROCEDURE_FAILED);
+            }
+            ATT_HANDLE_VALUE_NOTIFICATION => {
+                let mut params = [0; 5 + MAX_WRITE_LEN];
+                LittleEndian::write_u16(&mut params[0..], CONN_HANDLE);
+                params[2] = 2 + pdu.len as u8;
+                LittleEndian::write_u16(&mut params[3..], pdu.handle);
+                params[5..5 + pdu.len].copy_from_slice(pdu.value());
+                self.events
+                    .push_vendor_event(0x0C0F, &params[..5 + pdu.len]);
+            }
+            _ => (),
+        }
+    }
+
+    /// Queues a reply to the peer. A reply that does not fit is lost.
+    fn reply(&mut self, pdu: Pdu) {
+        self.link.outbox.push(pdu).ok();
+    }
+
+    fn device_found(&mut self, advertising: &Advertising, address: [u8; 6]) {
+        let mut params = [0; 10 + MAX_ADVERTISING_DATA_LEN];
+        params[0] = advertising.event_type;
+        params[1] = ADDRESS_TYPE_PUBLIC;
+        params[2..8].copy_from_slice(&address);
+        params[8] = advertising.data_len as u8;
+        params[9..9 + advertising.data_len]
+            .copy_from_slice(&advertising.data[..advertising.data_len]);
+        params[9 + advertising.data_len] = RSSI as u8;
+        self.events
+            .push_vendor_event(0x0406, &params[..10 + advertising.data_len]);
+    }
+
+    fn connect(&mut self, role: u8, peer: [u8; 6], conn_interval: &[u8; 6]) {
+        self.link.advertising = None;
+        self.link.initiating = None;
+        self.link.connection = Some(Connection { terminating: None });
+
+        let mut event = [0; 3 + 19];
+        event[0] = PACKET_TYPE_EVENT;
+        event[1] = EVENT_LE_META;
+        event[2] = 19;
+        event[3] = LE_CONNECTION_COMPLETE;
+        event[4] = hci::Status::<Status>::Success.into();
+        LittleEndian::write_u16(&mut event[5..], CONN_HANDLE);
+        event[7] = role;
+        event[8] = ADDRESS_TYPE_PUBLIC;
+        event[9..15].copy_from_slice(&peer);
+        event[15..21].copy_from_slice(conn_interval);
+        event[21] = CENTRAL_CLOCK_ACCURACY_500_PPM;
+        self.events.push(&event);
+    }
+
+    fn disconnect(&mut self, reason: u8) {
+        self.link.connection = None;
+        self.link.client_procedure = false;
+        self.link.outbox.len = 0;
+
+        let mut event = [
+            PACKET_TYPE_EVENT,
+            EVENT_DISCONNECTION_COMPLETE,
+            4,
+            hci::Status::<Status>::Success.into(),
+            0,
+            0,
+            reason,
+        ];
+        LittleEndian::write_u16(&mut event[4..], CONN_HANDLE);
+        self.events.push(&event);
+    }
+
+    fn gap_procedure_complete(&mut self, procedure: u8) {
+        self.events
+            .push_vendor_event(0x0407, &[procedure, hci::Status::<Status>::Success.into()]);
+    }
+
+    fn gatt_procedure_complete(&mut self, status: u8) {
+        self.link.client_procedure = false;
+
+        let mut params = [0; 4];
+        LittleEndian::write_u16(&mut params[0..], CONN_HANDLE);
+        params[2] = 1;
+        params[3] = status;
+        self.events.push_vendor_event(0x0C10, &params);
+    }
+}
+
+fn error_response(request: u8, handle: u16, error: u8) -> Pdu {
+    Pdu::new(ATT_ERROR_RESPONSE, handle, &[request, error])
+}
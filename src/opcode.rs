@@ -11,11 +11,12 @@ macro_rules! opcodes {
         $(
             $_cgid_comment:ident = $cgid:expr;
             {
-                $(pub const $var:ident = $cid:expr;)+
+                $($(#[$attr:meta])* pub const $var:ident = $cid:expr;)+
             }
         )+
     ) => {
         $($(
+            $(#[$attr])*
             pub const $var: Opcode = Opcode::new(VENDOR_OGF, ocf($cgid, $cid));
         )+)+
     }
@@ -76,6 +77,12 @@ opcodes! {
         pub const GAP_START_OBSERVATION_PROCEDURE = 0x22;
         pub const GAP_GET_BONDED_DEVICES = 0x23;
         pub const GAP_IS_DEVICE_BONDED = 0x24;
+        #[cfg(feature = "bluenrg2")]
+        pub const GAP_NUMERIC_COMPARISON_VALUE_CONFIRM = 0x25;
+        #[cfg(feature = "bluenrg2")]
+        pub const GAP_PASS_KEY_INPUT = 0x26;
+        #[cfg(feature = "bluenrg2")]
+        pub const GAP_REMOVE_BONDED_DEVICE = 0x2A;
     }
     Gatt = 0x2;
     {
@@ -129,4 +136,19 @@ opcodes! {
         pub const L2CAP_CONN_PARAM_UPDATE_REQ = 0x01;
         pub const L2CAP_CONN_PARAM_UPDATE_RESP = 0x02;
     }
+    // The updater commands share the HAL command group.
+    Updater = 0x0;
+    {
+        pub const UPDATER_START = 0x20;
+        pub const UPDATER_REBOOT = 0x21;
+        pub const UPDATER_GET_VERSION = 0x22;
+        pub const UPDATER_GET_BUFFER_SIZE = 0x23;
+        pub const UPDATER_ERASE_BLUE_FLAG = 0x24;
+        pub const UPDATER_RESET_BLUE_FLAG = 0x25;
+        pub const UPDATER_ERASE_SECTOR = 0x26;
+        pub const UPDATER_PROGRAM_DATA_BLOCK = 0x27;
+        pub const UPDATER_READ_DATA_BLOCK = 0x28;
+        pub const UPDATER_CALC_CRC = 0x29;
+        pub const UPDATER_HW_VERSION = 0x2A;
+    }
 }
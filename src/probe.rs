@@ -0,0 +1,316 @@
+//! Controller presence detection and SPI link self-test.
+//!
+//! [`BlueNRG::probe`] is intended for bring-up and factory testing: it tells apart a controller
+//! that is missing from one that is asleep or ready, and checks that commands and events make the
+//! round trip over the SPI bus.
+
+use crate::event::command::ReturnParameters;
+use crate::{parse_spi_header, Access, ActiveBlueNRG, BlueNRG, Error};
+use hci::host::uart::Packet;
+
+/// Number of SPI headers sent before deciding the controller is not ready. Empirically, a
+/// sleeping controller wakes within 2 to 4 headers.
+const HEADER_ATTEMPTS: u32 = 16;
+
+/// Level of the MISO line observed when no controller answered the SPI header.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum MisoLevel {
+    /// Every header byte read back as `0xFF`: the line is floating or pulled high.
+    High,
+
+    /// Every header byte read back as `0x00`: the line is stuck low.
+    Low,
+
+    /// Headers read back as all `0xFF` sometimes and all `0x00` at other times.
+    Unstable,
+}
+
+/// Result of sending SPI headers to the controller.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Presence {
+    /// No controller answered. The MISO line only ever read back as all ones or all zeros.
+    NotPresent(MisoLevel),
+
+    /// A controller answered, but it never reported that it was ready.
+    Asleep,
+
+    /// The controller reported that it was ready.
+    Ready {
+        /// Number of bytes the controller could receive.
+        write_len: u16,
+
+        /// Number of bytes the controller had ready to transmit.
+        read_len: u16,
+    },
+}
+
+/// Result of the command/event round trip, which sends
+/// [`get_firmware_revision`](crate::hal::Commands::get_firmware_revision) and waits for its
+/// command complete event.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Loopback {
+    /// The controller returned its firmware revision.
+    Passed {
+        /// The firmware revision number.
+        revision: u16,
+    },
+
+    /// The controller returned the command complete event, with a failure status.
+    CommandFailed(hci::Status<crate::event::Status>),
+
+    /// The controller had no command credits, so the command could not be sent. Credits return
+    /// with the command complete or command status event of an earlier command.
+    NoCommandCredits,
+
+    /// The controller did not accept the command before the timeout.
+    WriteTimeout,
+
+    /// The controller did not return the command complete event before the timeout.
+    ReadTimeout,
+
+    /// The controller returned a packet that is not an HCI event. Includes the packet type byte.
+    BadPacketType(u8),
+
+    /// The controller returned an event that could not be deserialized.
+    BadEvent(hci::event::Error<crate::event::BlueNRGError>),
+}
+
+/// Diagnostic returned by [`BlueNRG::probe`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct ProbeReport {
+    /// Whether the controller is present and ready.
+    pub presence: Presence,
+
+    /// The last SPI header read back from the controller.
+    pub header: [u8; 5],
+
+    /// Number of SPI headers sent before the controller reported that it was ready, or the total
+    /// number sent if it never did.
+    pub header_attempts: u32,
+
+    /// Result of the command/event round trip. `None` if the controller was not ready, so the
+    /// round trip was not attempted.
+    pub loopback: Option<Loopback>,
+
+    /// Number of unrelated events read while waiting for the command complete event.
+    pub skipped_events: u32,
+}
+
+impl ProbeReport {
+    /// Returns true if the controller is present, ready, and returned its firmware revision.
+    pub fn passed(&self) -> bool {
+        matches!(self.loopback, Some(Loopback::Passed { .. }))
+    }
+}
+
+impl<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, SpiError, GpioError, RxBuffer>
+    ActiveBlueNRG<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer>
+where
+    SPI: emhal::blocking::spi::Transfer<u8, Error = SpiError>
+        + emhal::blocking::spi::Write<u8, Error = SpiError>,
+    OutputPin1: emhal::digital::v2::OutputPin<Error = GpioError>,
+    OutputPin2: emhal::digital::v2::OutputPin<Error = GpioError>,
+    InputPin: emhal::digital::v2::InputPin<Error = GpioError>,
+    RxBuffer: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Sends SPI headers until the controller reports that it is ready, or until
+    /// [`HEADER_ATTEMPTS`] headers have been sent. Unlike `block_until_ready`, the chip select
+    /// line is raised after every header, and the last header is returned for the diagnostic.
+    fn probe_header(&mut self) -> Result<(Presence, [u8; 5], u32), Error<SpiError, GpioError>> {
+        let mut header = [0; 5];
+        let mut all_high = 0;
+        let mut all_low = 0;
+        let mut answered = false;
+        for attempt in 1..=HEADER_ATTEMPTS {
+            header = [Access::Write.byte(), 0x00, 0x00, 0x00, 0x00];
+            self.d.chip_select.set_low().map_err(Error::Gpio)?;
+            let result = self.spi.transfer(&mut header).map(|_| ());
+            self.d.chip_select.set_high().map_err(Error::Gpio)?;
+            result.map_err(Error::Spi)?;
+
+            if let Ok((write_len, read_len)) = parse_spi_header::<()>(&header) {
+                return Ok((
+                    Presence::Ready {
+                        write_len,
+                        read_len,
+                    },
+                    header,
+                    attempt,
+                ));
+            }
+
+            if header == [0xFF; 5] {
+                all_high += 1;
+            } else if header == [0x00; 5] {
+                all_low += 1;
+            } else {
+                answered = true;
+            }
+        }
+
+        let presence = if answered {
+            Presence::Asleep
+        } else if all_low == 0 {
+            Presence::NotPresent(MisoLevel::High)
+        } else if all_high == 0 {
+            Presence::NotPresent(MisoLevel::Low)
+        } else {
+            Presence::NotPresent(MisoLevel::Unstable)
+        };
+
+        Ok((presence, header, HEADER_ATTEMPTS))
+    }
+
+    /// Sends the get firmware revision command and waits for its command complete event. Each
+    /// phase (writing the command and reading the reply) is bounded by `timeout`. Within a
+    /// phase, each wait for the controller to become ready is bounded by [`HEADER_ATTEMPTS`]
+    /// headers, so the timer is checked even if the controller never becomes ready.
+    ///
+    /// Returns the result of the round trip, and the number of unrelated events that were read
+    /// and discarded while waiting.
+    fn probe_loopback<Timer>(
+        &mut self,
+        timer: &mut Timer,
+        timeout: Timer::Time,
+    ) -> Result<(Loopback, u32), Error<SpiError, GpioError>>
+    where
+        Timer: emhal::timer::CountDown,
+        Timer::Time: Copy,
+    {
+        if self.d.command_credits == 0 {
+            return Ok((Loopback::NoCommandCredits, 0));
+        }
+
+        timer.start(timeout);
+        loop {
+            match crate::hal::Commands::get_firmware_revision(self) {
+                Ok(()) => break,
+                Err(nb::Error::WouldBlock) | Err(nb::Error::Other(Error::Timeout)) => {
+                    if timer.wait().is_ok() {
+                        return Ok((Loopback::WriteTimeout, 0));
+                    }
+                }
+                Err(nb::Error::Other(e)) => return Err(e),
+            }
+        }
+
+        let mut skipped_events = 0;
+        timer.start(timeout);
+        loop {
+            match crate::event::read(self) {
+                Ok(Packet::Event(hci::Event::CommandComplete(complete))) => {
+                    match complete.return_params {
+                        hci::event::command::ReturnParameters::Vendor(
+                            ReturnParameters::HalGetFirmwareRevision(params),
+                        ) => {
+                            let loopback = if params.status == hci::Status::Success {
+                                Loopback::Passed {
+                                    revision: params.revision,
+                                }
+                            } else {
+                                Loopback::CommandFailed(params.status)
+                            };
+                            return Ok((loopback, skipped_events));
+                        }
+                        _ => skipped_events += 1,
+                    }
+                }
+                Ok(_) => skipped_events += 1,
+                Err(nb::Error::WouldBlock)
+                | Err(nb::Error::Other(hci::host::uart::Error::Comm(Error::Timeout))) => {
+                    if timer.wait().is_ok() {
+                        return Ok((Loopback::ReadTimeout, skipped_events));
+                    }
+                }
+                Err(nb::Error::Other(hci::host::uart::Error::Comm(e))) => return Err(e),
+                Err(nb::Error::Other(hci::host::uart::Error::BadPacketType(packet_type))) => {
+                    return Ok((Loopback::BadPacketType(packet_type), skipped_events));
+                }
+                Err(nb::Error::Other(hci::host::uart::Error::BLE(e))) => {
+                    return Ok((Loopback::BadEvent(e), skipped_events));
+                }
+            }
+        }
+    }
+
+    /// Checks whether the controller is present, and if it is ready, runs an SPI loopback test on
+    /// this handle, like [`BlueNRG::probe`]. Use it in a session started with
+    /// [`with_spi_options`](BlueNRG::with_spi_options), for example to capture the probe.
+    ///
+    /// # Errors
+    ///
+    /// See [`BlueNRG::probe`].
+    pub fn probe<Timer>(
+        &mut self,
+        timer: &mut Timer,
+        timeout: Timer::Time,
+    ) -> Result<ProbeReport, Error<SpiError, GpioError>>
+    where
+        Timer: emhal::timer::CountDown,
+        Timer::Time: Copy,
+    {
+        let (presence, header, header_attempts) = self.probe_header()?;
+        let (loopback, skipped_events) = match presence {
+            Presence::Ready { .. } => {
+                let retry_limit = self.d.retry_limit;
+                self.d.retry_limit =
+                    Some(retry_limit.map_or(HEADER_ATTEMPTS, |limit| limit.min(HEADER_ATTEMPTS)));
+                let result = self.probe_loopback(timer, timeout);
+                self.d.retry_limit = retry_limit;
+
+                let (loopback, skipped_events) = result?;
+                (Some(loopback), skipped_events)
+            }
+            _ => (None, 0),
+        };
+
+        Ok(ProbeReport {
+            presence,
+            header,
+            header_attempts,
+            loopback,
+            skipped_events,
+        })
+    }
+}
+
+impl<'buf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer>
+    BlueNRG<'buf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer>
+where
+    OutputPin1: emhal::digital::v2::OutputPin<Error = GpioError>,
+    OutputPin2: emhal::digital::v2::OutputPin<Error = GpioError>,
+    InputPin: emhal::digital::v2::InputPin<Error = GpioError>,
+    RxBuffer: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Checks whether the controller is present, and if it is ready, runs an SPI loopback test.
+    ///
+    /// First sends SPI headers to tell apart a missing controller (the MISO line reads back as all
+    /// ones or all zeros), one that answers but never reports that it is ready, and one that is
+    /// ready. If it is ready, sends the [get firmware
+    /// revision](crate::hal::Commands::get_firmware_revision) command and waits for its command
+    /// complete event, discarding any unrelated events. Writing the command and reading the reply
+    /// are each bounded by `timeout`, measured with `timer`, including the waits for the
+    /// controller to become ready within an SPI transfer.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error only if there is an underlying SPI or GPIO error. Every other outcome is
+    /// described by the returned [`ProbeReport`].
+    ///
+    /// To capture the probe, call [`ActiveBlueNRG::probe`] in a session started with
+    /// [`with_spi_options`](BlueNRG::with_spi_options) instead.
+    pub fn probe<E, Timer>(
+        &mut self,
+        spi: &mut SPI,
+        timer: &mut Timer,
+        timeout: Timer::Time,
+    ) -> Result<ProbeReport, Error<E, GpioError>>
+    where
+        SPI: emhal::blocking::spi::transfer::Default<u8, Error = E>
+            + emhal::blocking::spi::write::Default<u8, Error = E>,
+        Timer: emhal::timer::CountDown,
+        Timer::Time: Copy,
+    {
+        self.with_spi(spi, |controller| controller.probe(timer, timeout))
+    }
+}
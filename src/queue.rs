@@ -0,0 +1,256 @@
+//! Interrupt-driven event ingestion.
+//!
+//! Normally, events are only read from the controller when the application asks for one, so they
+//! wait in the controller while the application is busy. Instead, the data-ready interrupt handler
+//! can call [`BlueNRG::drain_events`] to move every available event into an [`EventQueue`], and
+//! the main loop can pop fully framed events from the queue with [`Consumer::pop`].
+//!
+//! The queue is a lock-free single-producer, single-consumer ring buffer: the interrupt handler
+//! owns the [`Producer`] half, and the main loop owns the [`Consumer`] half. Only atomic loads and
+//! stores are used, so it also works on cores without compare-and-swap.
+//!
+//! The interrupt handler needs the [`BlueNRG`] and the SPI bus to drain events, and the main loop
+//! needs them to send commands. The main loop must therefore send commands with the data-ready
+//! interrupt masked (for example, in a critical section).
+
+use crate::dialect::Dialect;
+use crate::event::{BlueNRGError, BlueNRGEvent};
+use crate::{ActiveBlueNRG, BlueNRG, Error, EVENT_PACKET_HEADER_LEN, PACKET_TYPE_HCI_EVENT};
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use hci::Controller;
+
+/// Maximum length of an HCI event packet: the packet type, the event code, the parameter length,
+/// and up to 255 bytes of parameters.
+const MAX_EVENT_PACKET_LEN: usize = EVENT_PACKET_HEADER_LEN + 255;
+
+/// Index of the parameter length in an HCI event packet.
+const PARAM_LEN_BYTE: usize = 2;
+
+/// Lock-free single-producer, single-consumer queue of HCI event packets.
+///
+/// The queue holds up to `N - 1` bytes of packets. Each packet takes 3 bytes plus the length of
+/// its parameters, so `N` should be larger than the longest event the application expects (258
+/// bytes for the longest possible event), or that event can never be queued.
+///
+/// `new` is `const`, so the queue can be placed in a `static`.
+pub struct EventQueue<const N: usize> {
+    buffer: UnsafeCell<[u8; N]>,
+
+    /// Index of the next byte to read. Only written by the consumer.
+    read_index: AtomicUsize,
+
+    /// Index of the next byte to write. Only written by the producer.
+    write_index: AtomicUsize,
+}
+
+// The producer only writes the part of the buffer between the write index and the read index,
+// and the consumer only reads the part between the read index and the write index. Each index is
+// published with release ordering after the bytes it covers are written or read.
+unsafe impl<const N: usize> Sync for EventQueue<N> {}
+
+impl<const N: usize> EventQueue<N> {
+    /// Returns an empty queue.
+    pub const fn new() -> EventQueue<N> {
+        EventQueue {
+            buffer: UnsafeCell::new([0; N]),
+            read_index: AtomicUsize::new(0),
+            write_index: AtomicUsize::new(0),
+        }
+    }
+
+    /// Splits the queue into the producer half, for the interrupt handler, and the consumer half,
+    /// for the main loop.
+    pub fn split(&mut self) -> (Producer<'_, N>, Consumer<'_, N>) {
+        (Producer { queue: self }, Consumer { queue: self })
+    }
+
+    fn byte(&self, index: usize) -> u8 {
+        unsafe { (*self.buffer.get())[index % N] }
+    }
+}
+
+impl<const N: usize> Default for EventQueue<N> {
+    fn default() -> EventQueue<N> {
+        EventQueue::new()
+    }
+}
+
+/// The half of an [`EventQueue`] that adds events. It is passed to [`BlueNRG::drain_events`] from
+/// the data-ready interrupt handler.
+pub struct Producer<'q, const N: usize> {
+    queue: &'q EventQueue<N>,
+}
+
+impl<'q, const N: usize> Producer<'q, N> {
+    /// Returns the number of bytes that can be added to the queue.
+    fn available_len(&self) -> usize {
+        let read_index = self.queue.read_index.load(Ordering::Acquire);
+        let write_index = self.queue.write_index.load(Ordering::Relaxed);
+        (read_index + N - write_index - 1) % N
+    }
+
+    /// Adds a packet to the queue. The caller must check there is enough room first.
+    fn push(&mut self, packet: &[u8]) {
+        let write_index = self.queue.write_index.load(Ordering::Relaxed);
+        let buffer = self.queue.buffer.get();
+        for (i, byte) in packet.iter().enumerate() {
+            unsafe {
+                (*buffer)[(write_index + i) % N] = *byte;
+            }
+        }
+        self.queue
+            .write_index
+            .store((write_index + packet.len()) % N, Ordering::Release);
+    }
+}
+
+/// The half of an [`EventQueue`] that removes events, used by the main loop.
+pub struct Consumer<'q, const N: usize> {
+    queue: &'q EventQueue<N>,
+}
+
+impl<'q, const N: usize> Consumer<'q, N> {
+    /// Returns true if there are no events in the queue.
+    pub fn is_empty(&self) -> bool {
+        self.queue.read_index.load(Ordering::Relaxed)
+            == self.queue.write_index.load(Ordering::Acquire)
+    }
+
+    /// Removes the oldest event from the queue and deserializes it in the given
+    /// [dialect](crate::dialect), usually [`BlueNRG::dialect`]. Returns `None` if the queue is
+    /// empty.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the event cannot be deserialized. The event is removed from the queue
+    /// either way.
+    pub fn pop(
+        &mut self,
+        dialect: Dialect,
+    ) -> Option<Result<hci::Event<BlueNRGEvent>, hci::event::Error<BlueNRGError>>> {
+        if self.is_empty() {
+            return None;
+        }
+
+        let read_index = self.queue.read_index.load(Ordering::Relaxed);
+        let packet_len =
+            EVENT_PACKET_HEADER_LEN + self.queue.byte(read_index + PARAM_LEN_BYTE) as usize;
+        let mut packet = [0; MAX_EVENT_PACKET_LEN];
+        for (i, byte) in packet.iter_mut().enumerate().take(packet_len) {
+            *byte = self.queue.byte(read_index + i);
+        }
+        self.queue
+            .read_index
+            .store((read_index + packet_len) % N, Ordering::Release);
+
+        Some(crate::event::with_dialect(dialect, &packet[1..packet_len]))
+    }
+}
+
+/// Errors that may occur while draining events with [`BlueNRG::drain_events`].
+#[derive(Debug, PartialEq)]
+pub enum DrainError<E> {
+    /// There was an error communicating with the controller.
+    Comm(E),
+
+    /// The controller sent a packet that is not an HCI event. Includes the packet type byte. The
+    /// packet is left in the RX buffer.
+    BadPacketType(u8),
+
+    /// The next event did not fit in the queue. It is left in the RX buffer, and the rest of the
+    /// data stays in the controller.
+    ///
+    /// The data-ready line stays high, so an edge-triggered interrupt will not fire again. Once
+    /// events have been popped from the queue, the main loop should call
+    /// [`BlueNRG::drain_events`] itself (with the interrupt masked).
+    QueueFull,
+}
+
+impl<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, SpiError, GpioError, RxBuffer>
+    ActiveBlueNRG<'bnrg, 'spi, 'dbuf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer>
+where
+    SPI: emhal::blocking::spi::Transfer<u8, Error = SpiError>
+        + emhal::blocking::spi::Write<u8, Error = SpiError>,
+    OutputPin1: emhal::digital::v2::OutputPin<Error = GpioError>,
+    OutputPin2: emhal::digital::v2::OutputPin<Error = GpioError>,
+    InputPin: emhal::digital::v2::InputPin<Error = GpioError>,
+    RxBuffer: AsRef<[u8]> + AsMut<[u8]>,
+{
+    fn drain_events<const N: usize>(
+        &mut self,
+        producer: &mut Producer<N>,
+    ) -> Result<usize, DrainError<Error<SpiError, GpioError>>> {
+        let mut queued = 0;
+        loop {
+            let packet_len = match self.peek_event_len() {
+                Ok(packet_len) => packet_len,
+                Err(nb::Error::WouldBlock) => return Ok(queued),
+                Err(nb::Error::Other(e)) => return Err(e),
+            };
+            if packet_len > producer.available_len() {
+                return Err(DrainError::QueueFull);
+            }
+
+            let mut packet = [0; MAX_EVENT_PACKET_LEN];
+            match self.read_into(&mut packet[..packet_len]) {
+                Ok(()) => (),
+                Err(nb::Error::WouldBlock) => return Ok(queued),
+                Err(nb::Error::Other(e)) => return Err(DrainError::Comm(e)),
+            }
+            producer.push(&packet[..packet_len]);
+            queued += 1;
+        }
+    }
+
+    /// Returns the length of the next event packet, reading from the controller if necessary.
+    fn peek_event_len(&mut self) -> nb::Result<usize, DrainError<Error<SpiError, GpioError>>> {
+        let rewrap = |e| match e {
+            nb::Error::WouldBlock => nb::Error::WouldBlock,
+            nb::Error::Other(e) => nb::Error::Other(DrainError::Comm(e)),
+        };
+        match self.peek(0).map_err(rewrap)? {
+            PACKET_TYPE_HCI_EVENT => (),
+            x => return Err(nb::Error::Other(DrainError::BadPacketType(x))),
+        }
+
+        Ok(EVENT_PACKET_HEADER_LEN + self.peek(PARAM_LEN_BYTE).map_err(rewrap)? as usize)
+    }
+}
+
+impl<'buf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer>
+    BlueNRG<'buf, SPI, OutputPin1, OutputPin2, InputPin, GpioError, RxBuffer>
+where
+    OutputPin1: emhal::digital::v2::OutputPin<Error = GpioError>,
+    OutputPin2: emhal::digital::v2::OutputPin<Error = GpioError>,
+    InputPin: emhal::digital::v2::InputPin<Error = GpioError>,
+    RxBuffer: AsRef<[u8]> + AsMut<[u8]>,
+{
+    /// Reads every event the controller has ready into the queue. Intended to be called from the
+    /// interrupt handler for the data-ready line.
+    ///
+    /// Stops when the controller has no more data (an incomplete event is kept in the RX buffer
+    /// until the rest arrives), or when the next event does not fit in the queue. Command credits
+    /// (see [`BlueNRG::command_credits`]) are updated as events are read.
+    ///
+    /// Returns the number of events added to the queue.
+    ///
+    /// # Errors
+    ///
+    /// - Returns [`DrainError::QueueFull`] if the next event does not fit in the queue.
+    /// - Returns [`DrainError::BadPacketType`] if the controller sent a packet that is not an
+    ///   event.
+    /// - Returns [`DrainError::Comm`] if there is an error communicating with the controller,
+    ///   including [`Error::RxOverflow`].
+    pub fn drain_events<E, const N: usize>(
+        &mut self,
+        spi: &mut SPI,
+        producer: &mut Producer<N>,
+    ) -> Result<usize, DrainError<Error<E, GpioError>>>
+    where
+        SPI: emhal::blocking::spi::transfer::Default<u8, Error = E>
+            + emhal::blocking::spi::write::Default<u8, Error = E>,
+    {
+        self.with_spi(spi, |controller| controller.drain_events(producer))
+    }
+}
@@ -0,0 +1,570 @@
+//! Typed request/response layer on top of the command traits.
+//!
+//! The command traits ([`gap::Commands`](crate::gap::Commands),
+//! [`gatt::Commands`](crate::gatt::Commands), [`hal::Commands`](crate::hal::Commands),
+//! [`l2cap::Commands`](crate::l2cap::Commands) and [`hci::host::Hci`]) only send bytes to the
+//! controller. [`Requester`] sends a command, then reads events until it finds the Command
+//! Complete or Command Status event with the same opcode, and returns the typed result. Unrelated
+//! events that arrive in the meantime are buffered, and returned by [`Requester::read`].
+//!
+//! ```ignore
+//! let mut requester: Requester<4> = Requester::new();
+//! let gap: GapInit = requester.call(controller, |c| c.init(Role::PERIPHERAL, false, 7))?;
+//! let revision: HalFirmwareRevision = requester.call(controller, |c| c.get_firmware_revision())?;
+//! ```
+
+use crate::event::command::{self as vendor, ReturnParameters as VendorReturnParameters};
+use crate::event::{BlueNRGError, BlueNRGEvent, Status};
+use crate::{EVENT_COMMAND_COMPLETE, EVENT_COMMAND_STATUS, PACKET_TYPE_HCI_EVENT};
+use core::convert::TryFrom;
+use hci::event::command::ReturnParameters;
+use hci::host::uart::Packet;
+use hci::{Event, Opcode};
+
+// Byte offsets in a Command Complete packet, which is: packet type (1 byte), event code (1),
+// parameter length (1), number of HCI command packets (1), opcode (2), return parameters.
+const COMMAND_COMPLETE_OPCODE: usize = 4;
+const COMMAND_COMPLETE_STATUS: usize = 6;
+
+// Byte offset of the opcode in a Command Status packet, which is: packet type (1 byte), event code
+// (1), parameter length (1), status (1), number of HCI command packets (1), opcode (2).
+const COMMAND_STATUS_OPCODE: usize = 5;
+
+/// Result of a command, extracted from the event that completes it.
+pub trait Response: Sized {
+    /// Returns the response from the return parameters of the command complete event, or `None`
+    /// if the return parameters are not of the expected type.
+    fn from_return_params(params: ReturnParameters<BlueNRGEvent>) -> Option<Self>;
+
+    /// Returns the response from a successful command status event, or `None` if the command is
+    /// not complete until its command complete event arrives.
+    fn from_command_status() -> Option<Self> {
+        None
+    }
+}
+
+/// Response for commands that only return a status. The status is checked by the [`Requester`],
+/// so the command succeeded if this is returned. Completes on either a command complete or a
+/// successful command status event.
+impl Response for () {
+    fn from_return_params(_params: ReturnParameters<BlueNRGEvent>) -> Option<()> {
+        Some(())
+    }
+
+    fn from_command_status() -> Option<()> {
+        Some(())
+    }
+}
+
+/// Response for any command that completes with a command complete event, including standard HCI
+/// commands.
+impl Response for ReturnParameters<BlueNRGEvent> {
+    fn from_return_params(params: ReturnParameters<BlueNRGEvent>) -> Option<Self> {
+        Some(params)
+    }
+}
+
+macro_rules! impl_response {
+    ($type:ty, $($variant:ident),+) => {
+        impl Response for $type {
+            fn from_return_params(params: ReturnParameters<BlueNRGEvent>) -> Option<Self> {
+                match params {
+                    $(ReturnParameters::Vendor(VendorReturnParameters::$variant(value)) => {
+                        Some(value)
+                    })+
+                    _ => None,
+                }
+            }
+        }
+    };
+}
+
+impl_response!(vendor::HalFirmwareRevision, HalGetFirmwareRevision);
+impl_response!(vendor::HalConfigData, HalReadConfigData);
+impl_response!(vendor::HalTxTestPacketCount, HalGetTxTestPacketCount);
+impl_response!(vendor::HalLinkStatus, HalGetLinkStatus);
+impl_response!(vendor::HalAnchorPeriod, HalGetAnchorPeriod);
+impl_response!(vendor::GapInit, GapInit);
+impl_response!(vendor::GapSecurityLevel, GapGetSecurityLevel);
+impl_response!(vendor::GapResolvePrivateAddress, GapResolvePrivateAddress);
+impl_response!(vendor::GapBondedDevices, GapGetBondedDevices);
+impl_response!(vendor::GattService, GattAddService, GattIncludeService);
+impl_response!(vendor::GattCharacteristic, GattAddCharacteristic);
+impl_response!(
+    vendor::GattCharacteristicDescriptor,
+    GattAddCharacteristicDescriptor
+);
+impl_response!(
+    vendor::GattHandleValue,
+    GattReadHandleValue,
+    GattReadHandleValueOffset
+);
+impl_response!(
+    vendor::UpdaterVersion,
+    UpdaterGetVersion,
+    UpdaterHardwareVersion
+);
+impl_response!(vendor::UpdaterBufferSize, UpdaterGetBufferSize);
+impl_response!(vendor::UpdaterData, UpdaterReadDataBlock);
+impl_response!(vendor::UpdaterCrc, UpdaterCalcCrc);
+
+/// Errors that may occur while waiting for the response to a command.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error<E, CommandError = E> {
+    /// Sending the command failed. For commands that validate their parameters, this includes
+    /// validation errors.
+    Command(CommandError),
+
+    /// There was an error communicating with the controller while reading events.
+    Comm(E),
+
+    /// The controller returned a packet that is not an HCI event. Includes the packet type byte.
+    BadPacketType(u8),
+
+    /// The controller returned an event that could not be deserialized.
+    BadEvent(hci::event::Error<BlueNRGError>),
+
+    /// The command completed with a failure status.
+    CommandFailed(hci::Status<Status>),
+
+    /// The command completed, but its return parameters were not of the requested response type.
+    UnexpectedResponse,
+
+    /// The buffer for unrelated events is full. Drain it with [`Requester::read`] and try again;
+    /// no event has been lost.
+    EventBufferFull,
+
+    /// [`poll`](Requester::poll) was called without a command pending.
+    NoPendingCommand,
+
+    /// The response did not arrive before the timer given to
+    /// [`call_with_timeout`](Requester::call_with_timeout) expired.
+    Timeout,
+}
+
+/// Controller wrapper that records the opcode of the command written through it.
+///
+/// Passed to the closures given to [`Requester::send`] and [`Requester::call`], so that any of the
+/// command traits may be used.
+pub struct Recorder<'c, C> {
+    controller: &'c mut C,
+    opcode: Option<Opcode>,
+}
+
+impl<'c, C> hci::Controller for Recorder<'c, C>
+where
+    C: hci::Controller<Header = hci::host::uart::CommandHeader, Vendor = crate::BlueNRGTypes>,
+{
+    type Error = C::Error;
+    type Header = hci::host::uart::CommandHeader;
+    type Vendor = crate::BlueNRGTypes;
+
+    fn write(&mut self, header: &[u8], payload: &[u8]) -> nb::Result<(), Self::Error> {
+        self.controller.write(header, payload)?;
+        self.opcode = Some(Opcode(u16::from_le_bytes([header[1], header[2]])));
+
+        Ok(())
+    }
+
+    fn read_into(&mut self, buffer: &mut [u8]) -> nb::Result<(), Self::Error> {
+        self.controller.read_into(buffer)
+    }
+
+    fn peek(&mut self, n: usize) -> nb::Result<u8, Self::Error> {
+        self.controller.peek(n)
+    }
+}
+
+impl<'c, C> crate::Firmware for Recorder<'c, C>
+where
+    C: crate::Firmware,
+{
+    fn version(&self) -> Option<crate::Version> {
+        self.controller.version()
+    }
+
+    fn dialect(&self) -> crate::dialect::Dialect {
+        self.controller.dialect()
+    }
+}
+
+/// Sends commands and returns their typed results, buffering up to `N` unrelated events.
+pub struct Requester<const N: usize> {
+    pending: Option<Opcode>,
+    events: [Option<Event<BlueNRGEvent>>; N],
+    first: usize,
+    len: usize,
+}
+
+impl<const N: usize> Default for Requester<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<const N: usize> Requester<N> {
+    /// Returns a new requester, with no pending command and no buffered events.
+    pub fn new() -> Requester<N> {
+        Requester {
+            pending: None,
+            events: core::array::from_fn(|_| None),
+            first: 0,
+            len: 0,
+        }
+    }
+
+    /// Returns the opcode of the command whose response has not yet been returned by
+    /// [`poll`](Requester::poll), if any.
+    pub fn pending(&self) -> Option<Opcode> {
+        self.pending
+    }
+
+    /// Returns the number of buffered events.
+    pub fn buffered_events(&self) -> usize {
+        self.len
+    }
+
+    /// Sends a command with the given closure, and records its opcode so
+    /// [`poll`](Requester::poll) can find its response.
+    ///
+    /// # Errors
+    ///
+    /// Returns the closure's error, including `nb::Error::WouldBlock` if the controller could not
+    /// accept the command.
+    pub fn send<C, F, CommandError>(
+        &mut self,
+        controller: &mut C,
+        command: F,
+    ) -> nb::Result<(), CommandError>
+    where
+        C: hci::Controller<Header = hci::host::uart::CommandHeader, Vendor = crate::BlueNRGTypes>
+            + crate::Firmware,
+        F: FnOnce(&mut Recorder<C>) -> nb::Result<(), CommandError>,
+    {
+        let mut recorder = Recorder {
+            controller,
+            opcode: None,
+        };
+        command(&mut recorder)?;
+        if let Some(opcode) = recorder.opcode {
+            self.pending = Some(opcode);
+        }
+
+        Ok(())
+    }
+
+    /// Reads one event from the controller. If it is the response to the pending command, returns
+    /// the typed response. Otherwise, buffers the event and returns `nb::Error::WouldBlock`.
+    ///
+    /// # Errors
+    ///
+    /// - `nb::Error::WouldBlock` if the response has not arrived yet.
+    /// - [`CommandFailed`](Error::CommandFailed) if the command completed with a failure status.
+    /// - [`BadEvent`](Error::BadEvent) if the command completed with a status that is not known.
+    /// - [`UnexpectedResponse`](Error::UnexpectedResponse) if the command completed, but the return
+    ///   parameters are not of type `R`.
+    /// - [`EventBufferFull`](Error::EventBufferFull) if an unrelated event arrived, but there is no
+    ///   room to buffer it. The event is left in the controller's receive buffer.
+    /// - [`NoPendingCommand`](Error::NoPendingCommand) if no command has been sent.
+    /// - Other errors if the event could not be read.
+    pub fn poll<C, R, CommandError>(
+        &mut self,
+        controller: &mut C,
+    ) -> nb::Result<R, Error<C::Error, CommandError>>
+    where
+        C: hci::Controller<Header = hci::host::uart::CommandHeader, Vendor = crate::BlueNRGTypes>
+            + crate::Firmware,
+        R: Response,
+    {
+        let pending = self
+            .pending
+            .ok_or(nb::Error::Other(Error::NoPendingCommand))?;
+
+        let packet_type = controller.peek(0).map_err(rewrap_comm)?;
+        let mut completion_status = None;
+        if packet_type == PACKET_TYPE_HCI_EVENT
+            && controller.peek(1).map_err(rewrap_comm)? == EVENT_COMMAND_COMPLETE
+        {
+            let opcode = Opcode(u16::from_le_bytes([
+                controller
+                    .peek(COMMAND_COMPLETE_OPCODE)
+                    .map_err(rewrap_comm)?,
+                controller
+                    .peek(COMMAND_COMPLETE_OPCODE + 1)
+                    .map_err(rewrap_comm)?,
+            ]));
+            if opcode == pending {
+                completion_status = Some(
+                    controller
+                        .peek(COMMAND_COMPLETE_STATUS)
+                        .map_err(rewrap_comm)?,
+                );
+            }
+        }
+
+        if completion_status.is_none() && self.len == N {
+            return Err(nb::Error::Other(Error::EventBufferFull));
+        }
+
+        let event = match crate::event::read(controller) {
+            Ok(Packet::Event(event)) => event,
+            Err(nb::Error::WouldBlock) => return Err(nb::Error::WouldBlock),
+            Err(nb::Error::Other(e)) => {
+                // A command that fails may return only its status, which is too short to parse as
+                // its return parameters. Report the failure rather than the parse error.
+                if let Some(Ok(status)) = completion_status.map(hci::Status::<Status>::try_from) {
+                    if status != hci::Status::Success {
+                        self.pending = None;
+                        return Err(nb::Error::Other(Error::CommandFailed(status)));
+                    }
+                }
+                return Err(nb::Error::Other(rewrap_read_error(e)));
+            }
+        };
+
+        match event {
+            Event::CommandComplete(complete) if completion_status.is_some() => {
+                self.pending = None;
+                if let Some(byte) = completion_status {
+                    match hci::Status::<Status>::try_from(byte) {
+                        Ok(hci::Status::Success) => (),
+                        Ok(status) => return Err(nb::Error::Other(Error::CommandFailed(status))),
+                        Err(_) => {
+                            return Err(nb::Error::Other(Error::BadEvent(
+                                hci::event::Error::BadStatus(byte),
+                            )))
+                        }
+                    }
+                }
+                R::from_return_params(complete.return_params)
+                    .ok_or(nb::Error::Other(Error::UnexpectedResponse))
+            }
+            Event::CommandStatus(status) if status.opcode == pending => {
+                if status.status != hci::Status::Success {
+                    self.pending = None;
+                    return Err(nb::Error::Other(Error::CommandFailed(status.status)));
+                }
+                match R::from_command_status() {
+                    Some(response) => {
+                        self.pending = None;
+                        Ok(response)
+                    }
+                    None => Err(nb::Error::WouldBlock),
+                }
+            }
+            event => {
+                self.push(event);
+                Err(nb::Error::WouldBlock)
+            }
+        }
+    }
+
+    /// Sends a command with the given closure, like [`send`](Requester::send), but if the
+    /// controller cannot accept the command yet, reads one event so that the Command Complete or
+    /// Command Status event that returns a command credit is not left unread.
+    ///
+    /// The event that completes the pending command, whose response is no longer awaited (for
+    /// example after [`call_with_timeout`](Requester::call_with_timeout) timed out), is dropped.
+    /// Other events are buffered.
+    fn send_or_read<C, F, CommandError>(
+        &mut self,
+        controller: &mut C,
+        command: F,
+    ) -> nb::Result<(), Error<C::Error, CommandError>>
+    where
+        C: hci::Controller<Header = hci::host::uart::CommandHeader, Vendor = crate::BlueNRGTypes>
+            + crate::Firmware,
+        F: FnOnce(&mut Recorder<C>) -> nb::Result<(), CommandError>,
+    {
+        match self.send(controller, command) {
+            Ok(()) => return Ok(()),
+            Err(nb::Error::Other(e)) => return Err(nb::Error::Other(Error::Command(e))),
+            Err(nb::Error::WouldBlock) => (),
+        }
+
+        let completes_pending = self.pending.is_some()
+            && self.pending == completed_opcode(controller).map_err(rewrap_comm)?;
+        if !completes_pending && self.len == N {
+            return Err(nb::Error::Other(Error::EventBufferFull));
+        }
+
+        match crate::event::read(controller) {
+            Ok(_) | Err(nb::Error::Other(hci::host::uart::Error::BLE(_))) if completes_pending => {
+                self.pending = None;
+            }
+            Ok(Packet::Event(event)) => self.push(event),
+            Err(nb::Error::WouldBlock) => return Err(nb::Error::WouldBlock),
+            Err(nb::Error::Other(e)) => return Err(nb::Error::Other(rewrap_read_error(e))),
+        }
+
+        Err(nb::Error::WouldBlock)
+    }
+
+    /// Sends a command with the given closure and waits for its typed response. Unrelated events
+    /// that arrive in the meantime are buffered. While the controller has no command credit to
+    /// accept the command, events are read so the credit can return.
+    ///
+    /// The wait is not bounded: if the controller never responds, this function never returns. The
+    /// retry limit and deadline of the controller (see [`BlueNRG::set_retry_limit`] and
+    /// [`SpiOptions::timeout`]) only bound each SPI transfer, not the time until the response
+    /// arrives. Use [`call_with_timeout`](Requester::call_with_timeout) to bound the wait.
+    ///
+    /// # Errors
+    ///
+    /// - [`Command`](Error::Command) if the closure returns an error.
+    /// - Any error returned by [`poll`](Requester::poll).
+    ///
+    /// [`BlueNRG::set_retry_limit`]: crate::BlueNRG::set_retry_limit
+    /// [`SpiOptions::timeout`]: crate::SpiOptions::timeout
+    pub fn call<C, F, R, CommandError>(
+        &mut self,
+        controller: &mut C,
+        command: F,
+    ) -> Result<R, Error<C::Error, CommandError>>
+    where
+        C: hci::Controller<Header = hci::host::uart::CommandHeader, Vendor = crate::BlueNRGTypes>
+            + crate::Firmware,
+        F: FnMut(&mut Recorder<C>) -> nb::Result<(), CommandError>,
+        R: Response,
+    {
+        let mut command = command;
+        block!(self.send_or_read(controller, &mut command))?;
+        block!(self.poll(controller))
+    }
+
+    /// Sends a command with the given closure and waits for its typed response, like
+    /// [`call`](Requester::call), but gives up if the response has not arrived when `timer`
+    /// expires. The timer is started with `timeout` before the command is sent, so it bounds the
+    /// wait for a command credit as well as the wait for the response.
+    ///
+    /// If the wait for the response times out, the command is still
+    /// [pending](Requester::pending), so its response may be read later with
+    /// [`poll`](Requester::poll). Otherwise, it is dropped when the next command is sent.
+    ///
+    /// # Errors
+    ///
+    /// - [`Command`](Error::Command) if the closure returns an error.
+    /// - [`Timeout`](Error::Timeout) if the timer expires before the response arrives.
+    /// - Any error returned by [`poll`](Requester::poll).
+    pub fn call_with_timeout<C, F, R, CommandError, Timer>(
+        &mut self,
+        controller: &mut C,
+        command: F,
+        timer: &mut Timer,
+        timeout: Timer::Time,
+    ) -> Result<R, Error<C::Error, CommandError>>
+    where
+        C: hci::Controller<Header = hci::host::uart::CommandHeader, Vendor = crate::BlueNRGTypes>
+            + crate::Firmware,
+        F: FnMut(&mut Recorder<C>) -> nb::Result<(), CommandError>,
+        R: Response,
+        Timer: emhal::timer::CountDown,
+    {
+        let mut command = command;
+        timer.start(timeout);
+        loop {
+            match self.send_or_read(controller, &mut command) {
+                Ok(()) => break,
+                Err(nb::Error::Other(e)) => return Err(e),
+                Err(nb::Error::WouldBlock) => {
+                    if timer.wait().is_ok() {
+                        return Err(Error::Timeout);
+                    }
+                }
+            }
+        }
+        loop {
+            match self.poll(controller) {
+                Ok(response) => return Ok(response),
+                Err(nb::Error::Other(e)) => return Err(e),
+                Err(nb::Error::WouldBlock) => {
+                    if timer.wait().is_ok() {
+                        return Err(Error::Timeout);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Returns the next event: first any buffered events, then events from the controller.
+    ///
+    /// If a command is pending, use [`poll`](Requester::poll) instead; this function does not
+    /// look for its response.
+    ///
+    /// # Errors
+    ///
+    /// - `nb::Error::WouldBlock` if no event is available.
+    /// - Other errors if the event could not be read.
+    pub fn read<C>(
+        &mut self,
+        controller: &mut C,
+    ) -> nb::Result<Event<BlueNRGEvent>, Error<C::Error>>
+    where
+        C: hci::Controller<Header = hci::host::uart::CommandHeader, Vendor = crate::BlueNRGTypes>
+            + crate::Firmware,
+    {
+        if let Some(event) = self.pop() {
+            return Ok(event);
+        }
+
+        match crate::event::read(controller) {
+            Ok(Packet::Event(event)) => Ok(event),
+            Err(nb::Error::WouldBlock) => Err(nb::Error::WouldBlock),
+            Err(nb::Error::Other(e)) => Err(nb::Error::Other(rewrap_read_error(e))),
+        }
+    }
+
+    fn push(&mut self, event: Event<BlueNRGEvent>) {
+        self.events[(self.first + self.len) % N] = Some(event);
+        self.len += 1;
+    }
+
+    fn pop(&mut self) -> Option<Event<BlueNRGEvent>> {
+        if self.len == 0 {
+            return None;
+        }
+
+        let event = self.events[self.first].take();
+        self.first = (self.first + 1) % N;
+        self.len -= 1;
+        event
+    }
+}
+
+/// Returns the opcode of the command completed by the next event from the controller, if it is a
+/// Command Complete or Command Status event.
+fn completed_opcode<C>(controller: &mut C) -> nb::Result<Option<Opcode>, C::Error>
+where
+    C: hci::Controller,
+{
+    if controller.peek(0)? != PACKET_TYPE_HCI_EVENT {
+        return Ok(None);
+    }
+
+    let offset = match controller.peek(1)? {
+        EVENT_COMMAND_COMPLETE => COMMAND_COMPLETE_OPCODE,
+        EVENT_COMMAND_STATUS => COMMAND_STATUS_OPCODE,
+        _ => return Ok(None),
+    };
+    Ok(Some(Opcode(u16::from_le_bytes([
+        controller.peek(offset)?,
+        controller.peek(offset + 1)?,
+    ]))))
+}
+
+fn rewrap_comm<E, CommandError>(e: nb::Error<E>) -> nb::Error<Error<E, CommandError>> {
+    match e {
+        nb::Error::WouldBlock => nb::Error::WouldBlock,
+        nb::Error::Other(e) => nb::Error::Other(Error::Comm(e)),
+    }
+}
+
+fn rewrap_read_error<E, CommandError>(
+    e: hci::host::uart::Error<E, BlueNRGError>,
+) -> Error<E, CommandError> {
+    match e {
+        hci::host::uart::Error::Comm(e) => Error::Comm(e),
+        hci::host::uart::Error::BadPacketType(packet_type) => Error::BadPacketType(packet_type),
+        hci::host::uart::Error::BLE(e) => Error::BadEvent(e),
+    }
+}
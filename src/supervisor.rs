@@ -0,0 +1,312 @@
+//! Automatic recovery from controller faults.
+//!
+//! The controller reports some faults with the HCI [Hardware Error](hci::Event::HardwareError)
+//! event (see [`HardwareError`]), and reports a firmware crash with the
+//! [`CrashReport`](BlueNRGEvent::CrashReport) event after it restarts. Either way, the state the
+//! application set up (configuration data, GATT database, advertising) is lost or can no longer be
+//! trusted, and the remedy is a hardware reset.
+//!
+//! A [`Supervisor`] watches the events the application reads. When one of them reports such a
+//! fault, it resets the controller, runs the [bring-up](crate::BlueNRG::bring_up) sequence again,
+//! and calls back into the application to replay the rest of its initialization. A list of delays
+//! bounds how many times in a row it does so, so a persistent fault does not reset the controller
+//! forever.
+
+use crate::bring_up::{BringUp, BringUpConfig, BringUpError};
+use crate::event::{BlueNRGEvent, FaultData};
+use crate::{ActiveBlueNRG, Error, HardwareError};
+use core::convert::TryFrom;
+use hci::Event;
+
+/// A fault reported by the controller.
+#[allow(clippy::large_enum_variant)]
+#[derive(Copy, Clone, Debug)]
+pub enum Fault {
+    /// The controller returned a [Hardware Error](hci::Event::HardwareError) event.
+    Hardware(HardwareError),
+
+    /// The controller returned a [Hardware Error](hci::Event::HardwareError) event with a code
+    /// that is not a known [`HardwareError`]. Includes the code.
+    UnknownHardware(u8),
+
+    /// The controller crashed and restarted. Includes the fault data it reported.
+    Crash(FaultData),
+}
+
+impl Fault {
+    /// Returns the fault reported by `event`, if it reports one.
+    pub fn from_event(event: &Event<BlueNRGEvent>) -> Option<Fault> {
+        match event {
+            Event::HardwareError(error) => Some(match HardwareError::try_from(error.code) {
+                Ok(error) => Fault::Hardware(error),
+                Err(_) => Fault::UnknownHardware(error.code),
+            }),
+            Event::Vendor(BlueNRGEvent::CrashReport(data)) => Some(Fault::Crash(*data)),
+            _ => None,
+        }
+    }
+
+    /// Returns true if the controller must be reset to recover from the fault.
+    ///
+    /// A [`SpiFraming`](HardwareError::SpiFraming) error points at the SPI configuration of the
+    /// host rather than at the controller, so a reset would not fix it. Every other fault, including
+    /// unknown hardware errors, requires a reset.
+    pub fn needs_reset(&self) -> bool {
+        !matches!(self, Fault::Hardware(HardwareError::SpiFraming))
+    }
+}
+
+/// A completed recovery, returned by [`Supervisor::handle`] and [`Supervisor::recover`].
+#[derive(Copy, Clone, Debug)]
+pub struct Recovery {
+    /// The fault that triggered the recovery.
+    pub fault: Fault,
+
+    /// Number of consecutive recovery attempts, including this one. See
+    /// [`Supervisor::attempts`].
+    pub attempt: usize,
+
+    /// Results of the bring-up sequence.
+    pub bring_up: BringUp,
+}
+
+/// Reasons a recovery can fail.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Cause<E, R> {
+    /// Every recovery attempt allowed by the back-off policy was used, so the controller was not
+    /// reset.
+    GaveUp,
+
+    /// The bring-up sequence failed.
+    BringUp(BringUpError<E>),
+
+    /// The application failed to replay its initialization. Includes the error it returned.
+    Restore(R),
+}
+
+/// Error returned by [`Supervisor::handle`] and [`Supervisor::recover`].
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct RecoveryError<E, R> {
+    /// Number of consecutive recovery attempts, including this one if the controller was reset.
+    pub attempt: usize,
+
+    /// Why the recovery failed.
+    pub cause: Cause<E, R>,
+}
+
+/// Resets and re-initializes the controller when it reports a fault.
+///
+/// The back-off policy is a list of delays: before the first of a run of consecutive recovery
+/// attempts, the supervisor waits for the first delay; before the second, for the second delay;
+/// and so on. Once the list is used up, it gives up instead of resetting the controller, and keeps
+/// giving up until the application calls [`reset_back_off`](Supervisor::reset_back_off), typically
+/// once the controller has run without faults for long enough.
+pub struct Supervisor<'a, Time> {
+    config: &'a BringUpConfig<'a, Time>,
+    delays: &'a [Time],
+    attempts: usize,
+}
+
+impl<'a, Time> Supervisor<'a, Time>
+where
+    Time: Copy,
+{
+    /// Creates a supervisor that brings up the controller with `config` and backs off with
+    /// `delays`.
+    pub fn new(config: &'a BringUpConfig<'a, Time>, delays: &'a [Time]) -> Supervisor<'a, Time> {
+        Supervisor {
+            config,
+            delays,
+            attempts: 0,
+        }
+    }
+
+    /// Returns the number of consecutive recovery attempts since the supervisor was created or its
+    /// back-off was [reset](Supervisor::reset_back_off).
+    pub fn attempts(&self) -> usize {
+        self.attempts
+    }
+
+    /// Returns true if the back-off policy allows no more recovery attempts.
+    pub fn gave_up(&self) -> bool {
+        self.attempts >= self.delays.len()
+    }
+
+    /// Resets the back-off policy, so the next recovery starts again from the first delay.
+    pub fn reset_back_off(&mut self) {
+        self.attempts = 0;
+    }
+
+    /// Checks an event read from the controller, and recovers from the fault it reports, if it
+    /// reports one that [needs a reset](Fault::needs_reset).
+    ///
+    /// See [`recover`](Supervisor::recover) for the recovery itself.
+    ///
+    /// Returns `Ok(None)` if the event does not require a recovery, and the completed
+    /// [`Recovery`] otherwise.
+    ///
+    /// # Errors
+    ///
+    /// See [`recover`](Supervisor::recover).
+    pub fn handle<
+        SPI,
+        OutputPin1,
+        OutputPin2,
+        InputPin,
+        SpiError,
+        GpioError,
+        RxBuffer,
+        Timer,
+        F,
+        R,
+    >(
+        &mut self,
+        controller: &mut ActiveBlueNRG<
+            '_,
+            '_,
+            '_,
+            SPI,
+            OutputPin1,
+            OutputPin2,
+            InputPin,
+            GpioError,
+            RxBuffer,
+        >,
+        timer: &mut Timer,
+        event: &Event<BlueNRGEvent>,
+        restore: F,
+    ) -> Result<Option<Recovery>, RecoveryError<Error<SpiError, GpioError>, R>>
+    where
+        SPI: emhal::blocking::spi::Transfer<u8, Error = SpiError>
+            + emhal::blocking::spi::Write<u8, Error = SpiError>,
+        OutputPin1: emhal::digital::v2::OutputPin<Error = GpioError>,
+        OutputPin2: emhal::digital::v2::OutputPin<Error = GpioError>,
+        InputPin: emhal::digital::v2::InputPin<Error = GpioError>,
+        RxBuffer: AsRef<[u8]> + AsMut<[u8]>,
+        Timer: emhal::timer::CountDown<Time = Time>,
+        F: FnOnce(
+            &mut ActiveBlueNRG<
+                '_,
+                '_,
+                '_,
+                SPI,
+                OutputPin1,
+                OutputPin2,
+                InputPin,
+                GpioError,
+                RxBuffer,
+            >,
+            &BringUp,
+        ) -> Result<(), R>,
+    {
+        match Fault::from_event(event) {
+            Some(fault) if fault.needs_reset() => {
+                self.recover(controller, timer, fault, restore).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Recovers from a fault.
+    ///
+    /// The recovery:
+    ///  1. Waits for the next delay of the back-off policy.
+    ///  2. [Resets](crate::BlueNRG::reset) the controller and runs the
+    ///     [bring-up](crate::BlueNRG::bring_up) sequence with the supervisor's configuration.
+    ///  3. Calls `restore` with the controller and the results of the bring-up, to replay the rest
+    ///     of the application's initialization, such as building the GATT database and starting
+    ///     to advertise.
+    ///
+    /// A failed attempt still counts against the back-off policy, so the application may call
+    /// `recover` again to retry.
+    ///
+    /// # Errors
+    ///
+    /// Returns a [`RecoveryError`] saying why the recovery failed:
+    /// - [`GaveUp`](Cause::GaveUp) if the back-off policy allows no more attempts,
+    /// - [`BringUp`](Cause::BringUp) if the reset or bring-up sequence failed, or
+    /// - [`Restore`](Cause::Restore) if `restore` returned an error.
+    pub fn recover<
+        SPI,
+        OutputPin1,
+        OutputPin2,
+        InputPin,
+        SpiError,
+        GpioError,
+        RxBuffer,
+        Timer,
+        F,
+        R,
+    >(
+        &mut self,
+        controller: &mut ActiveBlueNRG<
+            '_,
+            '_,
+            '_,
+            SPI,
+            OutputPin1,
+            OutputPin2,
+            InputPin,
+            GpioError,
+            RxBuffer,
+        >,
+        timer: &mut Timer,
+        fault: Fault,
+        restore: F,
+    ) -> Result<Recovery, RecoveryError<Error<SpiError, GpioError>, R>>
+    where
+        SPI: emhal::blocking::spi::Transfer<u8, Error = SpiError>
+            + emhal::blocking::spi::Write<u8, Error = SpiError>,
+        OutputPin1: emhal::digital::v2::OutputPin<Error = GpioError>,
+        OutputPin2: emhal::digital::v2::OutputPin<Error = GpioError>,
+        InputPin: emhal::digital::v2::InputPin<Error = GpioError>,
+        RxBuffer: AsRef<[u8]> + AsMut<[u8]>,
+        Timer: emhal::timer::CountDown<Time = Time>,
+        F: FnOnce(
+            &mut ActiveBlueNRG<
+                '_,
+                '_,
+                '_,
+                SPI,
+                OutputPin1,
+                OutputPin2,
+                InputPin,
+                GpioError,
+                RxBuffer,
+            >,
+            &BringUp,
+        ) -> Result<(), R>,
+    {
+        let delay = match self.delays.get(self.attempts) {
+            Some(delay) => *delay,
+            None => {
+                return Err(RecoveryError {
+                    attempt: self.attempts,
+                    cause: Cause::GaveUp,
+                })
+            }
+        };
+        self.attempts += 1;
+        let attempt = self.attempts;
+
+        timer.start(delay);
+        block!(timer.wait()).unwrap_or_else(|never| match never {});
+
+        let bring_up = controller
+            .bring_up(timer, self.config)
+            .map_err(|e| RecoveryError {
+                attempt,
+                cause: Cause::BringUp(e),
+            })?;
+        restore(controller, &bring_up).map_err(|e| RecoveryError {
+            attempt,
+            cause: Cause::Restore(e),
+        })?;
+
+        Ok(Recovery {
+            fault,
+            attempt,
+            bring_up,
+        })
+    }
+}
@@ -0,0 +1,169 @@
+#![cfg(feature = "async")]
+
+extern crate bluenrg;
+extern crate bluetooth_hci as hci;
+extern crate nb;
+
+mod fixture;
+
+use bluenrg::asynch::{SendError, WriteError};
+use bluenrg::gap::*;
+use bluenrg::BlueNRG;
+use fixture::{block_on, DummyPin, RecordingSink};
+use hci::host::uart::Packet;
+use hci::Event;
+
+#[test]
+fn send() {
+    let mut sink = RecordingSink::new();
+    let mut rx_buffer = [0; 8];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    block_on(bnrg.with_spi_async(&mut sink, |mut controller| async move {
+        controller
+            .send(|packet| packet.set_nondiscoverable())
+            .await
+            .unwrap()
+    }));
+    assert!(sink.wrote_header());
+    assert!(sink.wrote(&[1, 0x81, 0xFC, 0]));
+}
+
+#[test]
+fn send_build_error() {
+    let mut sink = RecordingSink::new();
+    let mut rx_buffer = [0; 8];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    let err = block_on(bnrg.with_spi_async(&mut sink, |mut controller| async move {
+        controller
+            .send(|packet| {
+                packet.set_limited_discoverable(&DiscoverableParameters {
+                    advertising_type: AdvertisingType::ConnectableDirectedHighDutyCycle,
+                    advertising_interval: None,
+                    address_type: OwnAddressType::Public,
+                    filter_policy: AdvertisingFilterPolicy::AllowConnectionAndScan,
+                    local_name: None,
+                    advertising_data: &[],
+                    conn_interval: (None, None),
+                })
+            })
+            .await
+            .err()
+    }));
+    assert_eq!(
+        err,
+        Some(SendError::Build(Error::BadAdvertisingType(
+            AdvertisingType::ConnectableDirectedHighDutyCycle
+        )))
+    );
+    assert!(!sink.wrote_header());
+}
+
+#[test]
+fn send_two_commands() {
+    let mut sink = RecordingSink::new();
+    let mut rx_buffer = [0; 8];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    let err = block_on(bnrg.with_spi_async(&mut sink, |mut controller| async move {
+        controller
+            .send(|packet| {
+                packet.set_nondiscoverable()?;
+                packet.set_nondiscoverable()
+            })
+            .await
+            .err()
+    }));
+    assert_eq!(err, Some(SendError::PacketFull));
+    assert!(sink.written_data.is_empty());
+}
+
+#[test]
+fn send_without_command_credits() {
+    let mut sink = RecordingSink::new();
+    let mut rx_buffer = [0; 8];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    let err = block_on(bnrg.with_spi_async(&mut sink, |mut controller| async move {
+        controller
+            .send(|packet| packet.set_nondiscoverable())
+            .await
+            .unwrap();
+        controller
+            .send(|packet| packet.set_nondiscoverable())
+            .await
+            .err()
+    }));
+    assert_eq!(err, Some(SendError::NoCommandCredits));
+    assert_eq!(bnrg.command_credits(), 0);
+    assert_eq!(bnrg.outstanding_commands(), 1);
+    assert_eq!(sink.written_data, [1, 0x81, 0xFC, 0]);
+}
+
+#[test]
+fn write_command_without_command_credits() {
+    let mut sink = RecordingSink::new();
+    let mut rx_buffer = [0; 8];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    let results = block_on(bnrg.with_spi_async(&mut sink, |mut controller| async move {
+        let first = controller.write(&[1, 0x81, 0xFC, 0], &[]).await;
+        let second = controller.write(&[1, 0x81, 0xFC, 0], &[]).await;
+        (first, second)
+    }));
+    assert_eq!(results, (Ok(()), Err(WriteError::NoCommandCredits)));
+    assert_eq!(bnrg.command_credits(), 0);
+    assert_eq!(bnrg.outstanding_commands(), 1);
+    assert_eq!(sink.written_data, [1, 0x81, 0xFC, 0]);
+}
+
+#[test]
+fn read_event() {
+    let mut sink = RecordingSink::with_reply(&[0x04, 0x0F, 0x04, 0x00, 0x01, 0x81, 0xFC]);
+    let mut rx_buffer = [0; 8];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    let packet = block_on(bnrg.with_spi_async(&mut sink, |mut controller| async move {
+        controller.read().await
+    }));
+    match packet {
+        Ok(Packet::Event(Event::CommandStatus(status))) => {
+            assert_eq!(status.num_hci_command_packets, 1);
+            assert_eq!(status.opcode, hci::Opcode(0xFC81));
+        }
+        other => panic!("Did not get command status: {:?}", other),
+    }
+    assert_eq!(bnrg.command_credits(), 1);
+}
+
+#[test]
+fn read_bad_packet_type() {
+    let mut sink = RecordingSink::with_reply(&[0x02, 0x00, 0x00]);
+    let mut rx_buffer = [0; 8];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    let err = block_on(bnrg.with_spi_async(&mut sink, |mut controller| async move {
+        controller.read().await.err()
+    }));
+    match err {
+        Some(hci::host::uart::Error::BadPacketType(0x02)) => (),
+        other => panic!("Did not get bad packet type: {:?}", other),
+    }
+}
+
+#[test]
+fn read_overflow() {
+    let mut sink =
+        RecordingSink::with_reply(&[0x04, 0x0F, 0x04, 0x00, 0x01, 0x81, 0xFC, 0x04, 0x0F]);
+    let mut rx_buffer = [0; 8];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    let (err, packet) = block_on(bnrg.with_spi_async(&mut sink, |mut controller| async move {
+        let err = controller.read().await.err();
+        (err, controller.read().await)
+    }));
+    assert_eq!(
+        err,
+        Some(hci::host::uart::Error::Comm(bluenrg::Error::RxOverflow))
+    );
+    match packet {
+        Ok(Packet::Event(Event::CommandStatus(status))) => {
+            assert_eq!(status.opcode, hci::Opcode(0xFC81));
+        }
+        other => panic!("Did not get command status: {:?}", other),
+    }
+    assert_eq!(bnrg.dropped_bytes(), 2);
+}
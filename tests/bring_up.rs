@@ -0,0 +1,239 @@
+extern crate bluenrg;
+extern crate bluetooth_hci as hci;
+extern crate embedded_hal as hal;
+extern crate nb;
+
+mod fixture;
+
+use bluenrg::bring_up::*;
+use bluenrg::event::ResetReason;
+use bluenrg::hal::ConfigData;
+use bluenrg::BlueNRG;
+use fixture::{DataReadyAfter, DummyPin, NeverError, PollCountTimer, ScriptedSink};
+
+fn hal_initialized(sink: &mut ScriptedSink) {
+    sink.event(&[0x04, 0xFF, 0x03, 0x01, 0x00, 0x01]);
+}
+
+fn read_local_version(sink: &mut ScriptedSink) {
+    sink.accept_command(4);
+    sink.event(&[
+        0x04, 0x0E, 0x0C, 0x01, 0x01, 0x10, 0x00, 0x07, 0x07, 0x31, 0x07, 0x30, 0x00, 0x12, 0x00,
+    ]);
+}
+
+fn write_config_data(sink: &mut ScriptedSink, status: u8) {
+    sink.accept_command(12);
+    sink.event(&[0x04, 0x0E, 0x04, 0x01, 0x0C, 0xFC, status]);
+}
+
+fn gatt_init(sink: &mut ScriptedSink) {
+    sink.accept_command(4);
+    sink.event(&[0x04, 0x0E, 0x04, 0x01, 0x01, 0xFD, 0x00]);
+}
+
+fn gap_init(sink: &mut ScriptedSink) {
+    sink.accept_command(7);
+    sink.event(&[
+        0x04, 0x0E, 0x0A, 0x01, 0x8A, 0xFC, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06,
+    ]);
+}
+
+fn bring_up(
+    sink: &mut ScriptedSink,
+    config_data: &[ConfigData],
+) -> Result<BringUp, BringUpError<bluenrg::Error<(), NeverError>>> {
+    bring_up_with_retry_limit(sink, config_data, Some(4))
+}
+
+fn bring_up_with_retry_limit(
+    sink: &mut ScriptedSink,
+    config_data: &[ConfigData],
+    retry_limit: Option<u32>,
+) -> Result<BringUp, BringUpError<bluenrg::Error<(), NeverError>>> {
+    bring_up_with_data_ready(sink, config_data, retry_limit, DummyPin)
+}
+
+fn bring_up_with_data_ready<InputPin>(
+    sink: &mut ScriptedSink,
+    config_data: &[ConfigData],
+    retry_limit: Option<u32>,
+    data_ready: InputPin,
+) -> Result<BringUp, BringUpError<bluenrg::Error<(), NeverError>>>
+where
+    InputPin: hal::digital::v2::InputPin<Error = NeverError>,
+{
+    let mut timer = PollCountTimer::new();
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, data_ready, DummyPin);
+    bnrg.set_retry_limit(retry_limit);
+    bnrg.bring_up(
+        sink,
+        &mut timer,
+        &BringUpConfig {
+            reset_time: 0,
+            timeout: 10,
+            config_data,
+            gap_role: bluenrg::gap::Role::PERIPHERAL,
+            #[cfg(not(feature = "bluenrg2"))]
+            privacy_enabled: false,
+            #[cfg(feature = "bluenrg2")]
+            privacy: bluenrg::gap::Privacy::Disabled,
+            dev_name_characteristic_len: 8,
+        },
+    )
+}
+
+fn public_address() -> ConfigData {
+    ConfigData::public_address(hci::BdAddr([1, 2, 3, 4, 5, 6])).build()
+}
+
+#[test]
+fn success() {
+    let mut sink = ScriptedSink::new(0x00);
+    hal_initialized(&mut sink);
+    read_local_version(&mut sink);
+    write_config_data(&mut sink, 0x00);
+    gatt_init(&mut sink);
+    gap_init(&mut sink);
+
+    let result = bring_up(&mut sink, &[public_address()]).unwrap();
+    assert_eq!(result.version.hw_version, 0x31);
+    assert_eq!(result.version.major, 0x07);
+    assert_eq!(result.version.minor, 0x1);
+    assert_eq!(result.version.patch, 0x2);
+    assert_eq!(
+        result.gap.service_handle,
+        bluenrg::gatt::ServiceHandle(0x0201)
+    );
+    assert_eq!(
+        result.gap.dev_name_handle,
+        bluenrg::gatt::CharacteristicHandle(0x0403)
+    );
+    assert_eq!(
+        result.gap.appearance_handle,
+        bluenrg::gatt::CharacteristicHandle(0x0605)
+    );
+}
+
+#[test]
+fn skips_unrelated_events() {
+    let mut sink = ScriptedSink::new(0x00);
+    // Command status for an unrelated command
+    sink.event(&[0x04, 0x0F, 0x04, 0x00, 0x01, 0x81, 0xFC]);
+    hal_initialized(&mut sink);
+    read_local_version(&mut sink);
+    gatt_init(&mut sink);
+    gap_init(&mut sink);
+
+    assert!(bring_up(&mut sink, &[]).is_ok());
+}
+
+#[test]
+fn unexpected_reset_reason() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.event(&[0x04, 0xFF, 0x03, 0x01, 0x00, 0x05]);
+
+    let err = bring_up(&mut sink, &[]).err().unwrap();
+    assert_eq!(err.step, Step::HalInitialized);
+    assert_eq!(
+        err.cause,
+        Cause::UnexpectedResetReason(ResetReason::Watchdog)
+    );
+}
+
+#[test]
+fn hal_initialized_timeout() {
+    let mut sink = ScriptedSink::new(0x00);
+
+    let err = bring_up(&mut sink, &[]).err().unwrap();
+    assert_eq!(err.step, Step::HalInitialized);
+    assert_eq!(err.cause, Cause::Timeout);
+}
+
+#[test]
+fn write_config_data_failed() {
+    let mut sink = ScriptedSink::new(0x00);
+    hal_initialized(&mut sink);
+    read_local_version(&mut sink);
+    write_config_data(&mut sink, 0x00);
+    write_config_data(&mut sink, 0x12);
+
+    let err = bring_up(&mut sink, &[public_address(), public_address()])
+        .err()
+        .unwrap();
+    assert_eq!(err.step, Step::WriteConfigData(1));
+    assert_eq!(
+        err.cause,
+        Cause::CommandFailed(hci::Status::InvalidParameters)
+    );
+}
+
+#[test]
+fn gap_init_timeout() {
+    let mut sink = ScriptedSink::new(0x00);
+    hal_initialized(&mut sink);
+    read_local_version(&mut sink);
+    gatt_init(&mut sink);
+
+    let err = bring_up(&mut sink, &[]).err().unwrap();
+    assert_eq!(err.step, Step::GapInit);
+    assert_eq!(err.cause, Cause::Timeout);
+}
+
+#[test]
+fn timeout_without_retry_limit() {
+    let mut sink = ScriptedSink::new(0x00);
+    hal_initialized(&mut sink);
+    read_local_version(&mut sink);
+    gatt_init(&mut sink);
+
+    // The controller never becomes ready again, so only the deadline ends the wait.
+    let err = bring_up_with_retry_limit(&mut sink, &[], None)
+        .err()
+        .unwrap();
+    assert_eq!(err.step, Step::GapInit);
+    assert_eq!(err.cause, Cause::Timeout);
+}
+
+#[test]
+fn waiting_for_data_ready_does_not_use_retry_limit() {
+    let mut sink = ScriptedSink::new(0x00);
+    hal_initialized(&mut sink);
+    read_local_version(&mut sink);
+    gatt_init(&mut sink);
+    gap_init(&mut sink);
+
+    // Data ready stays low for more polls than the retry limit, but not longer than the timeout.
+    let data_ready = DataReadyAfter::new(8);
+    assert!(bring_up_with_data_ready(&mut sink, &[], Some(2), &data_ready).is_ok());
+}
+
+#[test]
+fn waiting_for_data_ready_times_out() {
+    let mut sink = ScriptedSink::new(0x00);
+    let data_ready = DataReadyAfter::new(u32::MAX);
+    let err = bring_up_with_data_ready(&mut sink, &[], Some(2), &data_ready)
+        .err()
+        .unwrap();
+    assert_eq!(err.step, Step::HalInitialized);
+    assert_eq!(err.cause, Cause::Timeout);
+
+    // The first poll starts the timer, which expires after 10 more.
+    assert_eq!(data_ready.polls.get(), 12);
+}
+
+#[test]
+fn bad_event() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.event(&[0x04, 0xFF, 0x03, 0x01, 0x00, 0x00]);
+
+    let err = bring_up(&mut sink, &[]).err().unwrap();
+    assert_eq!(err.step, Step::HalInitialized);
+    assert_eq!(
+        err.cause,
+        Cause::BadEvent(hci::event::Error::Vendor(
+            bluenrg::event::BlueNRGError::UnknownResetReason(0)
+        ))
+    );
+}
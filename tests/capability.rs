@@ -0,0 +1,176 @@
+extern crate bluenrg;
+extern crate bluetooth_hci as hci;
+extern crate embedded_hal as hal;
+extern crate nb;
+
+mod fixture;
+
+use bluenrg::capability::Capability;
+use bluenrg::Version;
+
+fn version(major: u8, minor: u8) -> Version {
+    Version {
+        hw_version: 0x31,
+        major,
+        minor,
+        patch: 0,
+    }
+}
+
+#[cfg(not(feature = "bluenrg2"))]
+#[test]
+fn supported_by() {
+    assert!(!Capability::SetBroadcastMode.supported_by(&version(6, 4)));
+    assert!(Capability::SetBroadcastMode.supported_by(&version(7, 0)));
+    assert!(!Capability::ReadHandleValueOffset.supported_by(&version(7, 0)));
+    assert!(Capability::ReadHandleValueOffset.supported_by(&version(7, 1)));
+    assert!(!Capability::EventsLost.supported_by(&version(7, 1)));
+    assert!(Capability::EventsLost.supported_by(&version(7, 2)));
+    assert!(Capability::CrashReport.supported_by(&version(8, 0)));
+}
+
+#[cfg(feature = "bluenrg2")]
+#[test]
+fn supported_by() {
+    assert!(Capability::SetBroadcastMode.supported_by(&version(2, 1)));
+    assert!(Capability::CrashReport.supported_by(&version(2, 1)));
+}
+
+#[test]
+fn min_version() {
+    assert_eq!(Capability::GattTxPoolAvailable.min_version(), (7, 0));
+    assert_eq!(
+        Capability::UpdateLongCharacteristicValue.min_version(),
+        (7, 1)
+    );
+    assert_eq!(Capability::CrashReport.min_version(), (7, 2));
+}
+
+#[cfg(not(feature = "bluenrg2"))]
+mod commands {
+    use super::version;
+    use crate::fixture::{Fixture, RecordingSink};
+    use bluenrg::capability::Capability;
+    use bluenrg::dialect::Dialect;
+    use bluenrg::gap::{AddressType, BroadcastModeParameters, Commands as GapCommands};
+    use bluenrg::gatt::{
+        CharacteristicHandle, Commands as GattCommands, Error as GattError, ServiceHandle,
+        UpdateLongCharacteristicValueParameters, UpdateType,
+    };
+    use bluenrg::Firmware;
+    use std::time::Duration;
+
+    #[test]
+    fn unknown_version_is_supported() {
+        let mut sink = RecordingSink::new();
+        {
+            let mut fixture = Fixture::new(&mut sink);
+            fixture.set_dialect(Dialect::BlueNRGMS);
+            fixture
+                .act(|controller| {
+                    assert_eq!(controller.version(), None);
+                    controller.read_handle_value_offset(CharacteristicHandle(0x0201), 0x3)
+                })
+                .unwrap();
+        }
+        assert!(sink.wrote_header());
+    }
+
+    #[test]
+    fn read_handle_value_offset_unsupported() {
+        let mut sink = RecordingSink::new();
+        {
+            let mut fixture = Fixture::new(&mut sink);
+            fixture.set_version(version(7, 0));
+            let err = fixture
+                .act(|controller| {
+                    controller.read_handle_value_offset(CharacteristicHandle(0x0201), 0x3)
+                })
+                .err()
+                .unwrap();
+            assert_eq!(
+                err,
+                nb::Error::Other(GattError::Unsupported(Capability::ReadHandleValueOffset))
+            );
+        }
+        assert!(!sink.wrote_header());
+    }
+
+    #[test]
+    fn read_handle_value_offset_supported() {
+        let mut sink = RecordingSink::new();
+        {
+            let mut fixture = Fixture::new(&mut sink);
+            fixture.set_version(version(7, 1));
+            fixture
+                .act(|controller| {
+                    assert_eq!(controller.version(), Some(version(7, 1)));
+                    controller.read_handle_value_offset(CharacteristicHandle(0x0201), 0x3)
+                })
+                .unwrap();
+        }
+        assert!(sink.wrote(&[1, 0x2B, 0xFD, 3, 0x01, 0x02, 0x03]));
+    }
+
+    #[test]
+    fn update_long_characteristic_value_unsupported() {
+        let mut sink = RecordingSink::new();
+        {
+            let mut fixture = Fixture::new(&mut sink);
+            fixture.set_version(version(7, 0));
+            let err = fixture
+                .act(|controller| {
+                    controller.update_long_characteristic_value(
+                        &UpdateLongCharacteristicValueParameters {
+                            service_handle: ServiceHandle(0x0201),
+                            characteristic_handle: CharacteristicHandle(0x0403),
+                            update_type: UpdateType::NOTIFICATION,
+                            total_len: 4,
+                            offset: 0,
+                            value: &[0x9, 0xA, 0xB, 0xC],
+                        },
+                    )
+                })
+                .err()
+                .unwrap();
+            assert_eq!(
+                err,
+                nb::Error::Other(GattError::Unsupported(
+                    Capability::UpdateLongCharacteristicValue
+                ))
+            );
+        }
+        assert!(!sink.wrote_header());
+    }
+
+    #[test]
+    fn set_broadcast_mode_unsupported() {
+        let mut sink = RecordingSink::new();
+        {
+            let mut fixture = Fixture::new(&mut sink);
+            fixture.set_version(version(6, 4));
+            let err = fixture
+                .act(|controller| {
+                    controller.set_broadcast_mode(&BroadcastModeParameters {
+                        advertising_interval: hci::types::AdvertisingInterval::for_type(
+                            hci::types::AdvertisingType::ScannableUndirected,
+                        )
+                        .with_range(Duration::from_millis(100), Duration::from_millis(1000))
+                        .unwrap(),
+                        own_address_type: AddressType::Public,
+                        advertising_data: &[],
+                        white_list: &[],
+                    })
+                })
+                .err()
+                .unwrap();
+            assert_eq!(
+                err,
+                nb::Error::Other(bluenrg::gap::Error::Unsupported(
+                    Capability::SetBroadcastMode
+                ))
+            );
+        }
+        assert!(!sink.wrote_header());
+    }
+}
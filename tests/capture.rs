@@ -0,0 +1,232 @@
+extern crate bluenrg;
+extern crate bluetooth_hci as hci;
+extern crate nb;
+
+mod fixture;
+
+use bluenrg::capture::{Capture, Clock, Direction, Format, Sink};
+use bluenrg::gap::Commands;
+use bluenrg::{BlueNRG, SpiOptions, Timeout};
+use core::time::Duration;
+use fixture::{DummyPin, PollCountTimer, ScriptedSink};
+use hci::host::uart::Hci;
+
+const COMMAND: [u8; 4] = [0x01, 0x81, 0xFC, 0x00];
+const EVENT: [u8; 7] = [0x04, 0x0F, 0x04, 0x00, 0x01, 0x81, 0xFC];
+
+#[derive(Default)]
+struct VecSink {
+    bytes: Vec<u8>,
+    fail: bool,
+}
+
+impl Sink for VecSink {
+    type Error = ();
+
+    fn write_all(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        if self.fail {
+            return Err(());
+        }
+        self.bytes.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+/// Clock that starts at 1 600 000 000.25 s after the epoch and ticks one second per reading.
+struct SteppingClock {
+    now: Duration,
+}
+
+impl SteppingClock {
+    fn new() -> SteppingClock {
+        SteppingClock {
+            now: Duration::new(1_600_000_000, 250_000_000),
+        }
+    }
+}
+
+impl Clock for SteppingClock {
+    fn now(&mut self) -> Duration {
+        let now = self.now;
+        self.now += Duration::from_secs(1);
+        now
+    }
+}
+
+fn read_event<C>(controller: &mut C)
+where
+    C: Hci<
+        bluenrg::Error<(), fixture::NeverError>,
+        bluenrg::event::BlueNRGEvent,
+        bluenrg::event::BlueNRGError,
+    >,
+{
+    controller.read().unwrap();
+}
+
+fn command_then_event(capture: &mut Capture<VecSink, SteppingClock>) {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(COMMAND.len()).event(&EVENT);
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.with_spi_options(
+        &mut sink,
+        SpiOptions::new().capture(capture),
+        |controller| {
+            controller.set_nondiscoverable().unwrap();
+            read_event(controller);
+        },
+    );
+}
+
+fn btsnoop_record(flags: u32, timestamp: u64, packet: &[u8]) -> Vec<u8> {
+    let mut record = Vec::new();
+    record.extend_from_slice(&(packet.len() as u32).to_be_bytes());
+    record.extend_from_slice(&(packet.len() as u32).to_be_bytes());
+    record.extend_from_slice(&flags.to_be_bytes());
+    record.extend_from_slice(&[0; 4]);
+    record.extend_from_slice(&timestamp.to_be_bytes());
+    record.extend_from_slice(packet);
+    record
+}
+
+fn pcap_record(secs: u32, micros: u32, direction: u32, packet: &[u8]) -> Vec<u8> {
+    let mut record = Vec::new();
+    record.extend_from_slice(&secs.to_le_bytes());
+    record.extend_from_slice(&micros.to_le_bytes());
+    record.extend_from_slice(&(4 + packet.len() as u32).to_le_bytes());
+    record.extend_from_slice(&(4 + packet.len() as u32).to_le_bytes());
+    record.extend_from_slice(&direction.to_be_bytes());
+    record.extend_from_slice(packet);
+    record
+}
+
+#[test]
+fn btsnoop() {
+    let mut capture = Capture::new(Format::Btsnoop, VecSink::default(), SteppingClock::new());
+    command_then_event(&mut capture);
+    assert_eq!(capture.packets(), 2);
+    assert_eq!(capture.write_errors(), 0);
+
+    let mut expected = Vec::new();
+    expected.extend_from_slice(b"btsnoop\0");
+    expected.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0x03, 0xEA]);
+    expected.extend(btsnoop_record(0x02, 0x00E2_8CE4_16D7_5090, &COMMAND));
+    expected.extend(btsnoop_record(
+        0x03,
+        0x00E2_8CE4_16D7_5090 + 1_000_000,
+        &EVENT,
+    ));
+    let (sink, _) = capture.release();
+    assert_eq!(sink.bytes, expected);
+}
+
+#[test]
+fn pcap() {
+    let mut capture = Capture::new(Format::Pcap, VecSink::default(), SteppingClock::new());
+    command_then_event(&mut capture);
+    assert_eq!(capture.packets(), 2);
+    assert_eq!(capture.write_errors(), 0);
+
+    let mut expected = vec![
+        0xD4, 0xC3, 0xB2, 0xA1, 0x02, 0x00, 0x04, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0xFF, 0xFF, 0x00, 0x00, 0xC9, 0x00, 0x00, 0x00,
+    ];
+    expected.extend(pcap_record(1_600_000_000, 250_000, 0, &COMMAND));
+    expected.extend(pcap_record(1_600_000_001, 250_000, 1, &EVENT));
+    let (sink, _) = capture.release();
+    assert_eq!(sink.bytes, expected);
+}
+
+#[test]
+fn header_written_once() {
+    let mut capture = Capture::new(Format::Pcap, VecSink::default(), SteppingClock::new());
+    command_then_event(&mut capture);
+    command_then_event(&mut capture);
+    assert_eq!(capture.packets(), 4);
+
+    let (sink, _) = capture.release();
+    let record_len = |packet: &[u8]| 20 + packet.len();
+    assert_eq!(
+        sink.bytes.len(),
+        24 + 2 * (record_len(&COMMAND) + record_len(&EVENT))
+    );
+}
+
+#[test]
+fn sink_errors_are_counted() {
+    let sink = VecSink {
+        fail: true,
+        ..VecSink::default()
+    };
+    let mut capture = Capture::new(Format::Btsnoop, sink, SteppingClock::new());
+
+    // The HCI traffic succeeds even though the capture cannot be written.
+    command_then_event(&mut capture);
+    assert_eq!(capture.packets(), 2);
+    assert_eq!(capture.write_errors(), 3);
+}
+
+#[test]
+fn capture_with_timeout() {
+    let mut capture = Capture::new(Format::Btsnoop, VecSink::default(), SteppingClock::new());
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(COMMAND.len()).event(&EVENT);
+    let mut timer = PollCountTimer::new();
+    let mut timeout = Timeout::new(&mut timer, 3);
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    let options = SpiOptions::new()
+        .timeout(&mut timeout)
+        .capture(&mut capture);
+    let err = bnrg.with_spi_options(&mut sink, options, |controller| {
+        controller.set_nondiscoverable().unwrap();
+        read_event(controller);
+
+        // The controller never becomes ready again.
+        Hci::<_, bluenrg::event::BlueNRGEvent, bluenrg::event::BlueNRGError>::read(controller).err()
+    });
+    assert_eq!(
+        err,
+        Some(nb::Error::Other(hci::host::uart::Error::Comm(
+            bluenrg::Error::Timeout
+        )))
+    );
+    assert_eq!(capture.packets(), 2);
+}
+
+#[test]
+fn capture_probe() {
+    const READY: [u8; 5] = [0x02, 0xFF, 0x00, 0x00, 0x00];
+    let mut capture = Capture::new(Format::Btsnoop, VecSink::default(), SteppingClock::new());
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&READY)
+        .reply(&READY)
+        .reply(&[0x00; 4])
+        .event(&[0x04, 0x0E, 0x06, 0x01, 0x00, 0xFC, 0x00, 0x34, 0x12]);
+    let mut timer = PollCountTimer::new();
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    let report = bnrg
+        .with_spi_options(
+            &mut sink,
+            SpiOptions::new().capture(&mut capture),
+            |controller| controller.probe(&mut timer, 10),
+        )
+        .unwrap();
+    assert!(report.passed());
+    assert_eq!(capture.packets(), 2);
+}
+
+#[test]
+fn record_split_packet() {
+    let mut capture = Capture::new(Format::Btsnoop, VecSink::default(), SteppingClock::new());
+    capture.record(Direction::Sent, &COMMAND[..1], &COMMAND[1..]);
+
+    let mut expected = Vec::new();
+    expected.extend_from_slice(b"btsnoop\0");
+    expected.extend_from_slice(&[0, 0, 0, 1, 0, 0, 0x03, 0xEA]);
+    expected.extend(btsnoop_record(0x02, 0x00E2_8CE4_16D7_5090, &COMMAND));
+    let (sink, _) = capture.release();
+    assert_eq!(sink.bytes, expected);
+}
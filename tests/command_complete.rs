@@ -67,13 +67,21 @@ status_only! {
     gap_clear_security_database(0x94, 0xFC, BNRGParams::GapClearSecurityDatabase);
     gap_allow_rebond(0x95, 0xFC, BNRGParams::GapAllowRebond);
     gap_terminate_procedure(0x9D, 0xFC, BNRGParams::GapTerminateProcedure);
-    #[cfg(not(feature = "ms"))]
-    gap_resolve_private_address(0xA0, 0xFC, BNRGParams::GapResolvePrivateAddress);
     #[cfg(feature = "ms")]
     gap_set_broadcast_mode(0xA1, 0xFC, BNRGParams::GapSetBroadcastMode);
     #[cfg(feature = "ms")]
     gap_start_observation_procedure(0xA2, 0xFC, BNRGParams::GapStartObservationProcedure);
     gap_is_device_bonded(0xA4, 0xFC, BNRGParams::GapIsDeviceBonded);
+    #[cfg(feature = "bluenrg2")]
+    gap_numeric_comparison_value_confirm(
+        0xA5,
+        0xFC,
+        BNRGParams::GapNumericComparisonValueConfirm
+    );
+    #[cfg(feature = "bluenrg2")]
+    gap_pass_key_input(0xA6, 0xFC, BNRGParams::GapPassKeyInput);
+    #[cfg(feature = "bluenrg2")]
+    gap_remove_bonded_device(0xAA, 0xFC, BNRGParams::GapRemoveBondedDevice);
 
     gatt_init(0x01, 0xFD, BNRGParams::GattInit);
     gatt_update_characteristic_value(0x06, 0xFD, BNRGParams::GattUpdateCharacteristicValue);
@@ -94,6 +102,13 @@ status_only! {
         0xFD,
         BNRGParams::GattUpdateLongCharacteristicValue
     );
+
+    updater_start(0x20, 0xFC, BNRGParams::UpdaterStart);
+    updater_reboot(0x21, 0xFC, BNRGParams::UpdaterReboot);
+    updater_erase_blue_flag(0x24, 0xFC, BNRGParams::UpdaterEraseBlueFlag);
+    updater_reset_blue_flag(0x25, 0xFC, BNRGParams::UpdaterResetBlueFlag);
+    updater_erase_sector(0x26, 0xFC, BNRGParams::UpdaterEraseSector);
+    updater_program_data_block(0x27, 0xFC, BNRGParams::UpdaterProgramDataBlock);
 }
 
 #[test]
@@ -413,9 +428,9 @@ fn gap_get_security_level() {
             match event.return_params {
                 HciParams::Vendor(BNRGParams::GapGetSecurityLevel(params)) => {
                     assert_eq!(params.status, hci::Status::Success);
-                    assert_eq!(params.mitm_protection_required, false);
-                    assert_eq!(params.bonding_required, true);
-                    assert_eq!(params.out_of_band_data_present, false);
+                    assert!(!params.mitm_protection_required);
+                    assert!(params.bonding_required);
+                    assert!(!params.out_of_band_data_present);
                     assert_eq!(params.pass_key_required, PassKeyRequirement::Generated);
                 }
                 other => panic!("Wrong return parameters: {:?}", other),
@@ -447,6 +462,25 @@ fn gap_get_security_level_bad_pass_key_requirement() {
     }
 }
 
+#[cfg(not(feature = "ms"))]
+#[test]
+fn gap_resolve_private_address() {
+    let buffer = [0x0E, 4, 1, 0xA0, 0xFC, 0];
+    match Event::new(Packet(&buffer)) {
+        Ok(HciEvent::CommandComplete(event)) => {
+            assert_eq!(event.num_hci_command_packets, 1);
+            match event.return_params {
+                HciParams::Vendor(BNRGParams::GapResolvePrivateAddress(params)) => {
+                    assert_eq!(params.status, hci::Status::Success);
+                    assert_eq!(params.bd_addr, None);
+                }
+                other => panic!("Wrong return parameters: {:?}", other),
+            }
+        }
+        other => panic!("Did not get command complete event: {:?}", other),
+    }
+}
+
 #[cfg(feature = "ms")]
 #[test]
 fn gap_resolve_private_address() {
@@ -702,3 +736,105 @@ fn gatt_read_handle_value_offset() {
         other => panic!("Did not get command complete event: {:?}", other),
     }
 }
+
+#[test]
+fn updater_get_version() {
+    let buffer = [0x0E, 5, 1, 0x22, 0xFC, 0x00, 0x03];
+    match Event::new(Packet(&buffer)) {
+        Ok(HciEvent::CommandComplete(event)) => {
+            assert_eq!(event.num_hci_command_packets, 1);
+            match event.return_params {
+                HciParams::Vendor(BNRGParams::UpdaterGetVersion(params)) => {
+                    assert_eq!(params.status, hci::Status::Success);
+                    assert_eq!(params.version, 3);
+                }
+                other => panic!("Wrong return parameters: {:?}", other),
+            }
+        }
+        other => panic!("Did not get command complete event: {:?}", other),
+    }
+}
+
+#[test]
+fn updater_get_buffer_size() {
+    let buffer = [0x0E, 5, 1, 0x23, 0xFC, 0x00, 0xF9];
+    match Event::new(Packet(&buffer)) {
+        Ok(HciEvent::CommandComplete(event)) => {
+            assert_eq!(event.num_hci_command_packets, 1);
+            match event.return_params {
+                HciParams::Vendor(BNRGParams::UpdaterGetBufferSize(params)) => {
+                    assert_eq!(params.status, hci::Status::Success);
+                    assert_eq!(params.buffer_size, 0xF9);
+                }
+                other => panic!("Wrong return parameters: {:?}", other),
+            }
+        }
+        other => panic!("Did not get command complete event: {:?}", other),
+    }
+}
+
+#[test]
+fn updater_read_data_block() {
+    let buffer = [0x0E, 7, 1, 0x28, 0xFC, 0x00, 1, 2, 3];
+    match Event::new(Packet(&buffer)) {
+        Ok(HciEvent::CommandComplete(event)) => {
+            assert_eq!(event.num_hci_command_packets, 1);
+            match event.return_params {
+                HciParams::Vendor(BNRGParams::UpdaterReadDataBlock(params)) => {
+                    assert_eq!(params.status, hci::Status::Success);
+                    assert_eq!(params.data(), &[1, 2, 3]);
+                }
+                other => panic!("Wrong return parameters: {:?}", other),
+            }
+        }
+        other => panic!("Did not get command complete event: {:?}", other),
+    }
+}
+
+#[test]
+fn updater_calc_crc() {
+    let buffer = [0x0E, 8, 1, 0x29, 0xFC, 0x00, 0x01, 0x02, 0x03, 0x04];
+    match Event::new(Packet(&buffer)) {
+        Ok(HciEvent::CommandComplete(event)) => {
+            assert_eq!(event.num_hci_command_packets, 1);
+            match event.return_params {
+                HciParams::Vendor(BNRGParams::UpdaterCalcCrc(params)) => {
+                    assert_eq!(params.status, hci::Status::Success);
+                    assert_eq!(params.crc, 0x0403_0201);
+                }
+                other => panic!("Wrong return parameters: {:?}", other),
+            }
+        }
+        other => panic!("Did not get command complete event: {:?}", other),
+    }
+}
+
+#[test]
+fn updater_calc_crc_bad_length() {
+    let buffer = [0x0E, 6, 1, 0x29, 0xFC, 0x00, 0x01, 0x02];
+    match Event::new(Packet(&buffer)) {
+        Err(HciError::BadLength(actual, expected)) => {
+            assert_eq!(actual, 3);
+            assert_eq!(expected, 5);
+        }
+        other => panic!("Did not get bad length: {:?}", other),
+    }
+}
+
+#[test]
+fn updater_hw_version() {
+    let buffer = [0x0E, 5, 1, 0x2A, 0xFC, 0x00, 0x31];
+    match Event::new(Packet(&buffer)) {
+        Ok(HciEvent::CommandComplete(event)) => {
+            assert_eq!(event.num_hci_command_packets, 1);
+            match event.return_params {
+                HciParams::Vendor(BNRGParams::UpdaterHardwareVersion(params)) => {
+                    assert_eq!(params.status, hci::Status::Success);
+                    assert_eq!(params.version, 0x31);
+                }
+                other => panic!("Wrong return parameters: {:?}", other),
+            }
+        }
+        other => panic!("Did not get command complete event: {:?}", other),
+    }
+}
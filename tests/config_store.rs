@@ -0,0 +1,227 @@
+extern crate bluenrg;
+extern crate bluetooth_hci as hci;
+extern crate embedded_hal as hal;
+extern crate nb;
+
+mod fixture;
+
+use bluenrg::config_store::*;
+use bluenrg::hal::{ConfigParameter, Role};
+use bluenrg::request::{self, Requester};
+use bluenrg::BlueNRG;
+use fixture::{DummyPin, NeverError, ScriptedSink};
+use hci::host::EncryptionKey;
+use hci::BdAddr;
+
+const ADDRESS: [u8; 6] = [1, 2, 3, 4, 5, 6];
+const NEW_ADDRESS: [u8; 6] = [6, 5, 4, 3, 2, 1];
+const ENCRYPTION_ROOT: [u8; 16] = [0x11; 16];
+const IDENTITY_ROOT: [u8; 16] = [0x22; 16];
+
+fn read_value(sink: &mut ScriptedSink, value: &[u8]) {
+    sink.accept_command(5);
+    let mut packet = vec![0x04, 0x0E, 4 + value.len() as u8, 0x01, 0x0D, 0xFC, 0x00];
+    packet.extend_from_slice(value);
+    sink.event(&packet);
+}
+
+fn read_failed(sink: &mut ScriptedSink, status: u8) {
+    sink.accept_command(5);
+    sink.event(&[0x04, 0x0E, 0x04, 0x01, 0x0D, 0xFC, status]);
+}
+
+fn write_value(sink: &mut ScriptedSink, len: usize, status: u8) {
+    sink.accept_command(6 + len);
+    sink.event(&[0x04, 0x0E, 0x04, 0x01, 0x0C, 0xFC, status]);
+}
+
+fn read_area(sink: &mut ScriptedSink, address: &[u8], role: u8) {
+    read_value(sink, address);
+    read_value(sink, &[0x34, 0x12]);
+    read_value(sink, &ENCRYPTION_ROOT);
+    read_value(sink, &IDENTITY_ROOT);
+    read_value(sink, &[0]);
+    read_value(sink, &[role]);
+}
+
+fn act<T, F>(sink: &mut ScriptedSink, body: F) -> T
+where
+    F: FnOnce(
+        &mut ConfigStore<4>,
+        &mut bluenrg::ActiveBlueNRG<ScriptedSink, DummyPin, DummyPin, DummyPin, NeverError>,
+    ) -> T,
+{
+    let mut requester: Requester<4> = Requester::new();
+    let mut store = ConfigStore::new(&mut requester);
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.set_retry_limit(Some(4));
+    bnrg.with_spi(sink, |controller| body(&mut store, controller))
+}
+
+fn contains(sent: &[u8], bytes: &[u8]) -> bool {
+    sent.windows(bytes.len()).any(|window| window == bytes)
+}
+
+#[test]
+fn read() {
+    let mut sink = ScriptedSink::new(0x00);
+    read_area(&mut sink, &ADDRESS, 2);
+
+    let area = act(&mut sink, |store, controller| store.read(controller)).unwrap();
+    assert_eq!(area.public_address, Some(BdAddr(ADDRESS)));
+    assert_eq!(area.diversifier, Some(0x1234));
+    assert_eq!(area.encryption_root, Some(EncryptionKey(ENCRYPTION_ROOT)));
+    assert_eq!(area.identity_root, Some(EncryptionKey(IDENTITY_ROOT)));
+    assert_eq!(area.link_layer_only, Some(false));
+    assert_eq!(area.role, Some(Role::Peripheral12Kb));
+}
+
+#[test]
+fn read_unknown_role() {
+    let mut sink = ScriptedSink::new(0x00);
+    read_area(&mut sink, &ADDRESS, 0);
+
+    let area = act(&mut sink, |store, controller| store.read(controller)).unwrap();
+    assert_eq!(area.role, None);
+}
+
+#[test]
+fn read_unsupported_parameter() {
+    let mut sink = ScriptedSink::new(0x00);
+    read_value(&mut sink, &ADDRESS);
+    read_value(&mut sink, &[0x34, 0x12]);
+    read_value(&mut sink, &ENCRYPTION_ROOT);
+    read_value(&mut sink, &IDENTITY_ROOT);
+    read_value(&mut sink, &[0]);
+    read_failed(&mut sink, 0x12);
+
+    let area = act(&mut sink, |store, controller| store.read(controller)).unwrap();
+    assert_eq!(area.public_address, Some(BdAddr(ADDRESS)));
+    assert_eq!(area.link_layer_only, Some(false));
+    assert_eq!(area.role, None);
+}
+
+#[test]
+fn read_bad_value() {
+    let mut sink = ScriptedSink::new(0x00);
+    read_value(&mut sink, &[0x34, 0x12]);
+
+    let err = act(&mut sink, |store, controller| store.read(controller))
+        .err()
+        .unwrap();
+    assert_eq!(err.param, ConfigParameter::PublicAddress);
+    assert_eq!(err.cause, Cause::BadValue);
+}
+
+#[test]
+fn diff() {
+    let current = ConfigArea {
+        public_address: Some(BdAddr(ADDRESS)),
+        diversifier: Some(0x1234),
+        role: None,
+        ..ConfigArea::default()
+    };
+    let desired = ConfigArea {
+        public_address: Some(BdAddr(NEW_ADDRESS)),
+        diversifier: Some(0x1234),
+        link_layer_only: None,
+        role: Some(Role::Peripheral6Kb),
+        ..ConfigArea::default()
+    };
+    assert_eq!(
+        current.diff(&desired),
+        ConfigFields::PUBLIC_ADDRESS | ConfigFields::ROLE
+    );
+    assert_eq!(current.diff(&ConfigArea::default()), ConfigFields::empty());
+}
+
+#[test]
+fn apply_writes_changed_fields() {
+    let mut sink = ScriptedSink::new(0x00);
+    read_area(&mut sink, &ADDRESS, 2);
+    write_value(&mut sink, 6, 0x00);
+    read_value(&mut sink, &NEW_ADDRESS);
+
+    let desired = ConfigArea {
+        public_address: Some(BdAddr(NEW_ADDRESS)),
+        role: Some(Role::Peripheral12Kb),
+        ..ConfigArea::default()
+    };
+    let written = act(&mut sink, |store, controller| {
+        store.apply(controller, &desired)
+    })
+    .unwrap();
+    assert_eq!(written, ConfigFields::PUBLIC_ADDRESS);
+
+    let mut write_address = vec![0x01, 0x0C, 0xFC, 8, 0, 6];
+    write_address.extend_from_slice(&NEW_ADDRESS);
+    assert!(contains(&sink.sent, &write_address));
+    assert!(!contains(&sink.sent, &[0x01, 0x0C, 0xFC, 3, 41, 1]));
+}
+
+#[test]
+fn apply_unchanged() {
+    let mut sink = ScriptedSink::new(0x00);
+    read_area(&mut sink, &ADDRESS, 2);
+
+    let desired = ConfigArea {
+        public_address: Some(BdAddr(ADDRESS)),
+        ..ConfigArea::default()
+    };
+    let written = act(&mut sink, |store, controller| {
+        store.apply(controller, &desired)
+    })
+    .unwrap();
+    assert_eq!(written, ConfigFields::empty());
+    assert!(!contains(&sink.sent, &[0x01, 0x0C, 0xFC]));
+}
+
+#[test]
+fn apply_verify_failed() {
+    let mut sink = ScriptedSink::new(0x00);
+    read_area(&mut sink, &ADDRESS, 1);
+    write_value(&mut sink, 6, 0x00);
+    read_value(&mut sink, &NEW_ADDRESS);
+    write_value(&mut sink, 1, 0x00);
+    read_value(&mut sink, &[1]);
+
+    let desired = ConfigArea {
+        public_address: Some(BdAddr(NEW_ADDRESS)),
+        role: Some(Role::Primary12Kb),
+        ..ConfigArea::default()
+    };
+    let err = act(&mut sink, |store, controller| {
+        store.apply(controller, &desired)
+    })
+    .err()
+    .unwrap();
+    assert_eq!(err.param, ConfigParameter::Role);
+    assert_eq!(err.persisted, ConfigFields::PUBLIC_ADDRESS);
+    assert_eq!(err.cause, Cause::VerifyFailed);
+}
+
+#[test]
+fn apply_write_failed() {
+    let mut sink = ScriptedSink::new(0x00);
+    read_area(&mut sink, &ADDRESS, 1);
+    write_value(&mut sink, 2, 0x41);
+
+    let desired = ConfigArea {
+        diversifier: Some(0x5678),
+        ..ConfigArea::default()
+    };
+    let err = act(&mut sink, |store, controller| {
+        store.apply(controller, &desired)
+    })
+    .err()
+    .unwrap();
+    assert_eq!(err.param, ConfigParameter::Diversifier);
+    assert_eq!(err.persisted, ConfigFields::empty());
+    assert_eq!(
+        err.cause,
+        Cause::Request(request::Error::CommandFailed(hci::Status::Vendor(
+            bluenrg::event::Status::Failed
+        )))
+    );
+}
@@ -0,0 +1,1365 @@
+extern crate bluenrg;
+extern crate bluetooth_hci as hci;
+extern crate nb;
+
+mod fixture;
+
+use bluenrg::decode::{Error, VendorCommand};
+use bluenrg::dialect::Dialect;
+use bluenrg::gap::{
+    AddressType, AdvertisingDataType, AuthenticationRequirements, Authorization,
+    AutoConnectionEstablishmentParameters, BroadcastModeParameters, Commands as GapCommands,
+    ConnectionParameters, ConnectionUpdateParameters, DirectConnectableParameters,
+    DiscoverableParameters, DiscoveryProcedureParameters, EventFlags,
+    GeneralConnectionEstablishmentParameters, IoCapability, LocalName,
+    NameDiscoveryProcedureParameters, ObservationProcedureParameters, OutOfBandAuthentication,
+    PairingRequest, Pin, Procedure, Role, SecurityRequestParameters,
+    SelectiveConnectionEstablishmentParameters,
+};
+use bluenrg::gatt::{
+    AccessPermission, AddCharacteristicParameters, AddDescriptorParameters, AddServiceParameters,
+    CharacteristicEvent, CharacteristicHandle, CharacteristicPermission, CharacteristicProperty,
+    CharacteristicValue, Commands as GattCommands, DeleteIncludedServiceParameters,
+    DescriptorHandle, DescriptorPermission, DescriptorValueParameters, EncryptionKeySize,
+    Event as GattEvent, FindByTypeValueParameters, IncludeServiceParameters,
+    LongCharacteristicReadParameters, LongCharacteristicValue,
+    MultipleCharacteristicReadParameters, Range, ReadByTypeParameters,
+    SecurityPermissionParameters, ServiceHandle, ServiceType, UpdateCharacteristicValueParameters,
+    UpdateLongCharacteristicValueParameters, UpdateType, Uuid, Uuid16, WriteRequest,
+    WriteResponseParameters,
+};
+use bluenrg::hal::{Commands as HalCommands, ConfigData, ConfigParameter, PowerLevel};
+use bluenrg::l2cap::{
+    Commands as L2capCommands, ConnectionParameterUpdateRequest, ConnectionParameterUpdateResponse,
+};
+use bluenrg::updater::Commands as UpdaterCommands;
+use bluenrg::{AdvertisingFilterPolicy, AdvertisingType, OwnAddressType};
+use fixture::{Fixture, RecordingSink};
+use hci::host::{PeerAddrType, ScanType};
+use hci::types::{
+    AdvertisingInterval, ConnectionInterval, ConnectionIntervalBuilder, ExpectedConnectionLength,
+    ScanWindow,
+};
+use hci::{BdAddr, BdAddrType, ConnectionHandle};
+use std::time::Duration;
+
+#[cfg(feature = "bluenrg2")]
+use bluenrg::gap::{PassKeyInput, Privacy};
+
+// Returns the bytes written to the controller by the commands in the body, in the given dialect
+// or the BlueNRG-MS dialect.
+macro_rules! written {
+    ($dialect:expr, $body:expr) => {{
+        let mut sink = RecordingSink::new();
+        {
+            let mut fixture = Fixture::new(&mut sink);
+            fixture.set_dialect($dialect);
+            fixture.act($body);
+        }
+        sink.written_data
+    }};
+    ($body:expr) => {
+        written!(Dialect::BlueNRGMS, $body)
+    };
+}
+
+fn scan_window() -> ScanWindow {
+    ScanWindow::start_every(Duration::from_millis(5))
+        .unwrap()
+        .open_for(Duration::from_micros(2500))
+        .unwrap()
+}
+
+fn conn_interval() -> ConnectionInterval {
+    ConnectionIntervalBuilder::new()
+        .with_range(Duration::from_millis(50), Duration::from_millis(250))
+        .with_latency(10)
+        .with_supervision_timeout(Duration::from_millis(6000))
+        .build()
+        .unwrap()
+}
+
+fn expected_connection_length() -> ExpectedConnectionLength {
+    ExpectedConnectionLength::new(Duration::from_millis(25), Duration::from_millis(50)).unwrap()
+}
+
+fn peer_address() -> PeerAddrType {
+    PeerAddrType::RandomDeviceAddress(BdAddr([1, 2, 3, 4, 5, 6]))
+}
+
+const WHITE_LIST: [PeerAddrType; 2] = [
+    PeerAddrType::PublicDeviceAddress(BdAddr([1, 2, 3, 4, 5, 6])),
+    PeerAddrType::RandomDeviceAddress(BdAddr([7, 8, 9, 10, 11, 12])),
+];
+
+// Writes one of each command, each through its own fixture.
+fn write_commands(packets: &mut Vec<Vec<u8>>) {
+    packets.push(written!(|controller| controller
+        .get_firmware_revision()
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .write_config_data(&ConfigData::public_address(BdAddr([1, 2, 3, 4, 5, 6])).build())
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .read_config_data(ConfigParameter::EncryptionRoot)
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .set_tx_power_level(PowerLevel::DbmNeg2_1)
+        .unwrap()));
+    packets.push(written!(|controller| controller.device_standby().unwrap()));
+    packets.push(written!(|controller| controller
+        .get_tx_test_packet_count()
+        .unwrap()));
+    packets.push(written!(|controller| controller.start_tone(11).unwrap()));
+    packets.push(written!(|controller| controller.stop_tone().unwrap()));
+    packets.push(written!(|controller| controller.get_link_status().unwrap()));
+    packets.push(written!(|controller| controller
+        .get_anchor_period()
+        .unwrap()));
+
+    packets.push(written!(|controller| controller
+        .set_nondiscoverable()
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .set_limited_discoverable(&DiscoverableParameters {
+            advertising_type: AdvertisingType::ConnectableUndirected,
+            advertising_interval: Some((Duration::from_millis(1280), Duration::from_millis(2560),)),
+            address_type: OwnAddressType::Public,
+            filter_policy: AdvertisingFilterPolicy::AllowConnectionAndScan,
+            local_name: Some(LocalName::Shortened(b"testdev")),
+            advertising_data: &[0x01, 0x02, 0x03, 0x04],
+            conn_interval: (Some(Duration::from_millis(5000)), None),
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .set_discoverable(&DiscoverableParameters {
+            advertising_type: AdvertisingType::ScannableUndirected,
+            advertising_interval: None,
+            address_type: OwnAddressType::Random,
+            filter_policy: AdvertisingFilterPolicy::WhiteListConnectionAndScan,
+            local_name: None,
+            advertising_data: &[],
+            conn_interval: (None, Some(Duration::from_millis(250))),
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .set_discoverable(&DiscoverableParameters {
+            advertising_type: AdvertisingType::NonConnectableUndirected,
+            advertising_interval: None,
+            address_type: OwnAddressType::Public,
+            filter_policy: AdvertisingFilterPolicy::AllowConnectionAndScan,
+            local_name: Some(LocalName::Complete(b"dev")),
+            advertising_data: &[0x05],
+            conn_interval: (None, None),
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .set_direct_connectable(&DirectConnectableParameters {
+            own_address_type: OwnAddressType::Public,
+            advertising_type: AdvertisingType::ConnectableDirectedLowDutyCycle,
+            initiator_address: BdAddrType::Random(BdAddr([1, 2, 3, 4, 5, 6])),
+            advertising_interval: (Duration::from_millis(100), Duration::from_millis(1000)),
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .set_io_capability(IoCapability::KeyboardDisplay)
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .set_authentication_requirement(&AuthenticationRequirements {
+            mitm_protection_required: true,
+            out_of_band_auth: OutOfBandAuthentication::Enabled([
+                1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15, 16,
+            ]),
+            encryption_key_size_range: (7, 16),
+            fixed_pin: Pin::Fixed(123_456),
+            bonding_required: true,
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .set_authentication_requirement(&AuthenticationRequirements {
+            mitm_protection_required: false,
+            out_of_band_auth: OutOfBandAuthentication::Disabled,
+            encryption_key_size_range: (16, 16),
+            fixed_pin: Pin::Requested,
+            bonding_required: false,
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .set_authorization_requirement(ConnectionHandle(0x0201), true)
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .pass_key_response(ConnectionHandle(0x0201), 654_321)
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .authorization_response(ConnectionHandle(0x0201), Authorization::Rejected)
+        .unwrap()));
+    #[cfg(not(feature = "bluenrg2"))]
+    packets.push(written!(|controller| GapCommands::init(
+        controller,
+        Role::PERIPHERAL | Role::CENTRAL,
+        true,
+        16
+    )
+    .unwrap()));
+    #[cfg(feature = "bluenrg2")]
+    packets.push(written!(|controller| GapCommands::init(
+        controller,
+        Role::PERIPHERAL,
+        Privacy::Controller,
+        16
+    )
+    .unwrap()));
+    packets.push(written!(|controller| controller
+        .set_nonconnectable(AdvertisingType::ScannableUndirected, AddressType::Public)
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .set_undirected_connectable(
+            AdvertisingFilterPolicy::WhiteListConnectionAndScan,
+            AddressType::ResolvablePrivate,
+        )
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .peripheral_security_request(&SecurityRequestParameters {
+            conn_handle: ConnectionHandle(0x0201),
+            bonding: true,
+            mitm_protection: false,
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .update_advertising_data(&[1, 2, 3])
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .delete_ad_type(AdvertisingDataType::TxPowerLevel)
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .get_security_level()
+        .unwrap()));
+    packets.push(written!(|controller| GapCommands::set_event_mask(
+        controller,
+        EventFlags::LIMITED_DISCOVERABLE_TIMEOUT | EventFlags::PAIRING_COMPLETE,
+    )
+    .unwrap()));
+    packets.push(written!(|controller| controller
+        .configure_white_list()
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .terminate(
+            ConnectionHandle(0x0201),
+            hci::Status::RemoteTerminationByUser,
+        )
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .clear_security_database()
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .allow_rebond(Some(ConnectionHandle(0x0201)))
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .start_limited_discovery_procedure(&DiscoveryProcedureParameters {
+            scan_window: scan_window(),
+            own_address_type: OwnAddressType::Random,
+            filter_duplicates: true,
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .start_general_discovery_procedure(&DiscoveryProcedureParameters {
+            scan_window: scan_window(),
+            own_address_type: OwnAddressType::Public,
+            filter_duplicates: false,
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .start_name_discovery_procedure(&NameDiscoveryProcedureParameters {
+            scan_window: scan_window(),
+            peer_address: peer_address(),
+            own_address_type: OwnAddressType::Public,
+            conn_interval: conn_interval(),
+            expected_connection_length: expected_connection_length(),
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .start_auto_connection_establishment(&AutoConnectionEstablishmentParameters {
+            scan_window: scan_window(),
+            own_address_type: OwnAddressType::Public,
+            conn_interval: conn_interval(),
+            expected_connection_length: expected_connection_length(),
+            reconnection_address: None,
+            white_list: &WHITE_LIST,
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .start_general_connection_establishment(&GeneralConnectionEstablishmentParameters {
+            scan_window: scan_window(),
+            own_address_type: OwnAddressType::Random,
+            filter_duplicates: true,
+            reconnection_address: None,
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .start_selective_connection_establishment(&SelectiveConnectionEstablishmentParameters {
+            scan_type: ScanType::Active,
+            scan_window: scan_window(),
+            own_address_type: OwnAddressType::Public,
+            filter_duplicates: false,
+            white_list: &WHITE_LIST,
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .create_connection(&ConnectionParameters {
+            scan_window: scan_window(),
+            peer_address: peer_address(),
+            own_address_type: OwnAddressType::Random,
+            conn_interval: conn_interval(),
+            expected_connection_length: expected_connection_length(),
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .terminate_procedure(Procedure::GENERAL_DISCOVERY)
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .start_connection_update(&ConnectionUpdateParameters {
+            conn_handle: ConnectionHandle(0x0201),
+            conn_interval: conn_interval(),
+            expected_connection_length: expected_connection_length(),
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .send_pairing_request(&PairingRequest {
+            conn_handle: ConnectionHandle(0x0201),
+            force_rebond: true,
+            force_reencrypt: false,
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .resolve_private_address(BdAddr([1, 2, 3, 4, 5, 6]))
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .set_broadcast_mode(&BroadcastModeParameters {
+            advertising_interval: AdvertisingInterval::for_type(
+                AdvertisingType::ScannableUndirected,
+            )
+            .with_range(Duration::from_millis(100), Duration::from_millis(1000))
+            .unwrap(),
+            own_address_type: AddressType::Public,
+            advertising_data: &[1, 2, 3],
+            white_list: &WHITE_LIST,
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .start_observation_procedure(&ObservationProcedureParameters {
+            scan_window: scan_window(),
+            scan_type: ScanType::Passive,
+            own_address_type: AddressType::Random,
+            filter_duplicates: true,
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .get_bonded_devices()
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .is_device_bonded(peer_address())
+        .unwrap()));
+    #[cfg(feature = "bluenrg2")]
+    packets.push(written!(|controller| controller
+        .numeric_comparison_value_confirm(ConnectionHandle(0x0201), true)
+        .unwrap()));
+    #[cfg(feature = "bluenrg2")]
+    packets.push(written!(|controller| controller
+        .pass_key_input(ConnectionHandle(0x0201), PassKeyInput::DigitEntered)
+        .unwrap()));
+    #[cfg(feature = "bluenrg2")]
+    packets.push(written!(|controller| controller
+        .remove_bonded_device(peer_address())
+        .unwrap()));
+
+    packets.push(written!(
+        |controller| GattCommands::init(controller).unwrap()
+    ));
+    packets.push(written!(|controller| controller
+        .add_service(&AddServiceParameters {
+            uuid: Uuid::Uuid16(0x0201),
+            service_type: ServiceType::Primary,
+            max_attribute_records: 3,
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .include_service(&IncludeServiceParameters {
+            service_handle: ServiceHandle(0x0201),
+            include_handle_range: Range::new(ServiceHandle(0x0403), ServiceHandle(0x0605)).unwrap(),
+            include_uuid: Uuid::Uuid128([
+                0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x1B, 0x1C, 0x1D,
+                0x1E, 0x1F,
+            ]),
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .add_characteristic(&AddCharacteristicParameters {
+            service_handle: ServiceHandle(0x0201),
+            characteristic_uuid: Uuid::Uuid16(0x0403),
+            characteristic_value_len: 0x0605,
+            characteristic_properties: CharacteristicProperty::READ
+                | CharacteristicProperty::NOTIFY,
+            security_permissions: CharacteristicPermission::AUTHENTICATED_READ,
+            gatt_event_mask: CharacteristicEvent::CONFIRM_WRITE,
+            encryption_key_size: EncryptionKeySize::with_value(8).unwrap(),
+            is_variable: true,
+            #[cfg(not(feature = "bluenrg2"))]
+            fw_version_before_v72: false,
+        })
+        .unwrap()));
+    #[cfg(not(feature = "bluenrg2"))]
+    packets.push(written!(|controller| controller
+        .add_characteristic(&AddCharacteristicParameters {
+            service_handle: ServiceHandle(0x0201),
+            characteristic_uuid: Uuid::Uuid16(0x0403),
+            characteristic_value_len: 0x05,
+            characteristic_properties: CharacteristicProperty::WRITE,
+            security_permissions: CharacteristicPermission::empty(),
+            gatt_event_mask: CharacteristicEvent::empty(),
+            encryption_key_size: EncryptionKeySize::with_value(16).unwrap(),
+            is_variable: false,
+            fw_version_before_v72: true,
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .add_characteristic_descriptor(&AddDescriptorParameters {
+            service_handle: ServiceHandle(0x0201),
+            characteristic_handle: CharacteristicHandle(0x0403),
+            descriptor_uuid: Uuid::Uuid16(0x0605),
+            descriptor_value_max_len: 8,
+            descriptor_value: &[1, 2, 3],
+            security_permissions: DescriptorPermission::AUTHENTICATED,
+            access_permissions: AccessPermission::READ_WRITE,
+            gatt_event_mask: CharacteristicEvent::ATTRIBUTE_WRITE,
+            encryption_key_size: EncryptionKeySize::with_value(10).unwrap(),
+            is_variable: true,
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .update_characteristic_value(&UpdateCharacteristicValueParameters {
+            service_handle: ServiceHandle(0x0201),
+            characteristic_handle: CharacteristicHandle(0x0403),
+            offset: 5,
+            value: &[1, 2, 3],
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .delete_characteristic(ServiceHandle(0x0201), CharacteristicHandle(0x0403))
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .delete_service(ServiceHandle(0x0201))
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .delete_included_service(&DeleteIncludedServiceParameters {
+            service: ServiceHandle(0x0201),
+            included_service: ServiceHandle(0x0403),
+        })
+        .unwrap()));
+    packets.push(written!(|controller| GattCommands::set_event_mask(
+        controller,
+        GattEvent::ATTRIBUTE_MODIFIED | GattEvent::PROCEDURE_COMPLETE,
+    )
+    .unwrap()));
+    packets.push(written!(|controller| controller
+        .exchange_configuration(ConnectionHandle(0x0201))
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .find_information_request(
+            ConnectionHandle(0x0201),
+            Range::new(CharacteristicHandle(0x0403), CharacteristicHandle(0x0605)).unwrap(),
+        )
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .find_by_type_value_request(&FindByTypeValueParameters {
+            conn_handle: ConnectionHandle(0x0201),
+            attribute_handle_range: Range::new(
+                CharacteristicHandle(0x0403),
+                CharacteristicHandle(0x0605),
+            )
+            .unwrap(),
+            uuid: Uuid16(0x0807),
+            value: &[1, 2, 3],
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .read_by_type_request(&ReadByTypeParameters {
+            conn_handle: ConnectionHandle(0x0201),
+            attribute_handle_range: Range::new(
+                CharacteristicHandle(0x0403),
+                CharacteristicHandle(0x0605),
+            )
+            .unwrap(),
+            uuid: Uuid::Uuid16(0x0807),
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .read_by_group_type_request(&ReadByTypeParameters {
+            conn_handle: ConnectionHandle(0x0201),
+            attribute_handle_range: Range::new(
+                CharacteristicHandle(0x0403),
+                CharacteristicHandle(0x0605),
+            )
+            .unwrap(),
+            uuid: Uuid::Uuid16(0x0807),
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .prepare_write_request(&WriteRequest {
+            conn_handle: ConnectionHandle(0x0201),
+            attribute_handle: CharacteristicHandle(0x0403),
+            offset: 0x0605,
+            value: &[1, 2, 3],
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .execute_write_request(ConnectionHandle(0x0201))
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .cancel_write_request(ConnectionHandle(0x0201))
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .discover_all_primary_services(ConnectionHandle(0x0201))
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .discover_primary_services_by_uuid(ConnectionHandle(0x0201), Uuid::Uuid16(0x0403))
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .find_included_services(
+            ConnectionHandle(0x0201),
+            Range::new(ServiceHandle(0x0403), ServiceHandle(0x0605)).unwrap(),
+        )
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .discover_all_characteristics_of_service(
+            ConnectionHandle(0x0201),
+            Range::new(CharacteristicHandle(0x0403), CharacteristicHandle(0x0605)).unwrap(),
+        )
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .discover_characteristics_by_uuid(
+            ConnectionHandle(0x0201),
+            Range::new(CharacteristicHandle(0x0403), CharacteristicHandle(0x0605)).unwrap(),
+            Uuid::Uuid16(0x0807),
+        )
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .discover_all_characteristic_descriptors(
+            ConnectionHandle(0x0201),
+            Range::new(CharacteristicHandle(0x0403), CharacteristicHandle(0x0605)).unwrap(),
+        )
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .read_characteristic_value(ConnectionHandle(0x0201), CharacteristicHandle(0x0403))
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .read_characteristic_using_uuid(
+            ConnectionHandle(0x0201),
+            Range::new(CharacteristicHandle(0x0403), CharacteristicHandle(0x0605)).unwrap(),
+            Uuid::Uuid16(0x0807),
+        )
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .read_long_characteristic_value(&LongCharacteristicReadParameters {
+            conn_handle: ConnectionHandle(0x0201),
+            attribute: CharacteristicHandle(0x0403),
+            offset: 0x0605,
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .read_multiple_characteristic_values(&MultipleCharacteristicReadParameters {
+            conn_handle: ConnectionHandle(0x0201),
+            handles: &[CharacteristicHandle(0x0403), CharacteristicHandle(0x0605)],
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .write_characteristic_value(&CharacteristicValue {
+            conn_handle: ConnectionHandle(0x0201),
+            characteristic_handle: CharacteristicHandle(0x0403),
+            value: &[1, 2, 3],
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .write_long_characteristic_value(&LongCharacteristicValue {
+            conn_handle: ConnectionHandle(0x0201),
+            characteristic_handle: CharacteristicHandle(0x0403),
+            offset: 0x0605,
+            value: &[1, 2, 3],
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .write_characteristic_value_reliably(&LongCharacteristicValue {
+            conn_handle: ConnectionHandle(0x0201),
+            characteristic_handle: CharacteristicHandle(0x0403),
+            offset: 0x0605,
+            value: &[4, 5],
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .write_long_characteristic_descriptor(&LongCharacteristicValue {
+            conn_handle: ConnectionHandle(0x0201),
+            characteristic_handle: CharacteristicHandle(0x0403),
+            offset: 0x0605,
+            value: &[6],
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .read_long_characteristic_descriptor(&LongCharacteristicReadParameters {
+            conn_handle: ConnectionHandle(0x0201),
+            attribute: CharacteristicHandle(0x0403),
+            offset: 0x0605,
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .write_characteristic_descriptor(&CharacteristicValue {
+            conn_handle: ConnectionHandle(0x0201),
+            characteristic_handle: CharacteristicHandle(0x0403),
+            value: &[1, 2],
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .read_characteristic_descriptor(ConnectionHandle(0x0201), CharacteristicHandle(0x0403))
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .write_without_response(&CharacteristicValue {
+            conn_handle: ConnectionHandle(0x0201),
+            characteristic_handle: CharacteristicHandle(0x0403),
+            value: &[1],
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .signed_write_without_response(&CharacteristicValue {
+            conn_handle: ConnectionHandle(0x0201),
+            characteristic_handle: CharacteristicHandle(0x0403),
+            value: &[],
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .confirm_indication(ConnectionHandle(0x0201))
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .write_response(&WriteResponseParameters {
+            conn_handle: ConnectionHandle(0x0201),
+            attribute_handle: CharacteristicHandle(0x0403),
+            status: Ok(()),
+            value: &[1, 2, 3],
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .write_response(&WriteResponseParameters {
+            conn_handle: ConnectionHandle(0x0201),
+            attribute_handle: CharacteristicHandle(0x0403),
+            status: Err(hci::Status::InvalidParameters),
+            value: &[],
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .allow_read(ConnectionHandle(0x0201))
+        .unwrap()));
+
+    packets.push(written!(|controller| controller
+        .set_security_permission(&SecurityPermissionParameters {
+            service_handle: ServiceHandle(0x0201),
+            attribute_handle: CharacteristicHandle(0x0403),
+            permission: CharacteristicPermission::ENCRYPTED_WRITE,
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .set_descriptor_value(&DescriptorValueParameters {
+            service_handle: ServiceHandle(0x0201),
+            characteristic_handle: CharacteristicHandle(0x0403),
+            descriptor_handle: DescriptorHandle(0x0605),
+            offset: 0x0807,
+            value: &[1, 2, 3],
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .read_handle_value(CharacteristicHandle(0x0201))
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .read_handle_value_offset(CharacteristicHandle(0x0201), 3)
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .update_long_characteristic_value(&UpdateLongCharacteristicValueParameters {
+            service_handle: ServiceHandle(0x0201),
+            characteristic_handle: CharacteristicHandle(0x0403),
+            update_type: UpdateType::NOTIFICATION,
+            total_len: 0x0605,
+            offset: 0x0807,
+            value: &[1, 2, 3],
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .connection_parameter_update_request(&ConnectionParameterUpdateRequest {
+            conn_handle: ConnectionHandle(0x0201),
+            conn_interval: conn_interval(),
+        })
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .connection_parameter_update_response(&ConnectionParameterUpdateResponse {
+            conn_handle: ConnectionHandle(0x0201),
+            conn_interval: conn_interval(),
+            expected_connection_length_range: expected_connection_length(),
+            identifier: 0x0F,
+            accepted: true,
+        })
+        .unwrap()));
+    packets.push(written!(
+        |controller| UpdaterCommands::start(controller).unwrap()
+    ));
+    packets.push(written!(|controller| controller.reboot().unwrap()));
+    packets.push(written!(|controller| controller.get_version().unwrap()));
+    packets.push(written!(|controller| controller.get_buffer_size().unwrap()));
+    packets.push(written!(|controller| controller.erase_blue_flag().unwrap()));
+    packets.push(written!(|controller| controller.reset_blue_flag().unwrap()));
+    packets.push(written!(|controller| controller
+        .erase_sector(0x1004_0000)
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .program_data_block(0x1004_0000, &[1, 2, 3, 4])
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .read_data_block(0x1004_0000, 16)
+        .unwrap()));
+    packets.push(written!(|controller| controller
+        .calc_crc(0x1004_0000, 2)
+        .unwrap()));
+    packets.push(written!(|controller| controller.hw_version().unwrap()));
+}
+
+// Writes the decoded command with the command method it was decoded from.
+fn reencode(dialect: Dialect, command: &VendorCommand) -> Vec<u8> {
+    written!(dialect, |controller| match command {
+        VendorCommand::HalGetFirmwareRevision => controller.get_firmware_revision().unwrap(),
+        VendorCommand::HalWriteConfigData(config) => controller.write_config_data(config).unwrap(),
+        VendorCommand::HalReadConfigData(param) => controller.read_config_data(*param).unwrap(),
+        VendorCommand::HalSetTxPowerLevel(level) => controller.set_tx_power_level(*level).unwrap(),
+        VendorCommand::HalDeviceStandby => controller.device_standby().unwrap(),
+        VendorCommand::HalGetTxTestPacketCount => controller.get_tx_test_packet_count().unwrap(),
+        VendorCommand::HalStartTone(channel) => controller.start_tone(*channel).unwrap(),
+        VendorCommand::HalStopTone => controller.stop_tone().unwrap(),
+        VendorCommand::HalGetLinkStatus => controller.get_link_status().unwrap(),
+        VendorCommand::HalGetAnchorPeriod => controller.get_anchor_period().unwrap(),
+        VendorCommand::GapSetNonDiscoverable => controller.set_nondiscoverable().unwrap(),
+        VendorCommand::GapSetLimitedDiscoverable(params) => {
+            controller.set_limited_discoverable(params).unwrap()
+        }
+        VendorCommand::GapSetDiscoverable(params) => controller.set_discoverable(params).unwrap(),
+        VendorCommand::GapSetDirectConnectable(params) => {
+            controller.set_direct_connectable(params).unwrap()
+        }
+        VendorCommand::GapSetIoCapability(capability) => {
+            controller.set_io_capability(*capability).unwrap()
+        }
+        VendorCommand::GapSetAuthenticationRequirement(requirements) => controller
+            .set_authentication_requirement(requirements)
+            .unwrap(),
+        VendorCommand::GapSetAuthorizationRequirement {
+            conn_handle,
+            authorization_required,
+        } => controller
+            .set_authorization_requirement(*conn_handle, *authorization_required)
+            .unwrap(),
+        VendorCommand::GapPassKeyResponse { conn_handle, pin } => {
+            controller.pass_key_response(*conn_handle, *pin).unwrap()
+        }
+        VendorCommand::GapAuthorizationResponse {
+            conn_handle,
+            authorization,
+        } => controller
+            .authorization_response(*conn_handle, *authorization)
+            .unwrap(),
+        #[cfg(not(feature = "bluenrg2"))]
+        VendorCommand::GapInit {
+            role,
+            privacy_enabled,
+            dev_name_characteristic_len,
+        } => GapCommands::init(
+            controller,
+            *role,
+            *privacy_enabled,
+            *dev_name_characteristic_len,
+        )
+        .unwrap(),
+        #[cfg(feature = "bluenrg2")]
+        VendorCommand::GapInit {
+            role,
+            privacy,
+            dev_name_characteristic_len,
+        } => GapCommands::init(controller, *role, *privacy, *dev_name_characteristic_len).unwrap(),
+        VendorCommand::GapSetNonConnectable {
+            advertising_type,
+            address_type,
+        } => controller
+            .set_nonconnectable(*advertising_type, *address_type)
+            .unwrap(),
+        VendorCommand::GapSetUndirectedConnectable {
+            filter_policy,
+            address_type,
+        } => controller
+            .set_undirected_connectable(*filter_policy, *address_type)
+            .unwrap(),
+        VendorCommand::GapPeripheralSecurityRequest(params) => {
+            controller.peripheral_security_request(params).unwrap()
+        }
+        VendorCommand::GapUpdateAdvertisingData(data) => {
+            controller.update_advertising_data(data).unwrap()
+        }
+        VendorCommand::GapDeleteAdType(ad_type) => controller.delete_ad_type(*ad_type).unwrap(),
+        VendorCommand::GapGetSecurityLevel => controller.get_security_level().unwrap(),
+        VendorCommand::GapSetEventMask(flags) => {
+            GapCommands::set_event_mask(controller, *flags).unwrap()
+        }
+        VendorCommand::GapConfigureWhiteList => controller.configure_white_list().unwrap(),
+        VendorCommand::GapTerminate {
+            conn_handle,
+            reason,
+        } => controller.terminate(*conn_handle, *reason).unwrap(),
+        VendorCommand::GapClearSecurityDatabase => controller.clear_security_database().unwrap(),
+        VendorCommand::GapAllowRebond(conn_handle) => {
+            controller.allow_rebond(*conn_handle).unwrap()
+        }
+        VendorCommand::GapStartLimitedDiscoveryProcedure(params) => controller
+            .start_limited_discovery_procedure(params)
+            .unwrap(),
+        VendorCommand::GapStartGeneralDiscoveryProcedure(params) => controller
+            .start_general_discovery_procedure(params)
+            .unwrap(),
+        VendorCommand::GapStartNameDiscoveryProcedure(params) => {
+            controller.start_name_discovery_procedure(params).unwrap()
+        }
+        VendorCommand::GapStartAutoConnectionEstablishment(params) => controller
+            .start_auto_connection_establishment(&params.parameters())
+            .unwrap(),
+        VendorCommand::GapStartGeneralConnectionEstablishment(params) => controller
+            .start_general_connection_establishment(params)
+            .unwrap(),
+        VendorCommand::GapStartSelectiveConnectionEstablishment(params) => controller
+            .start_selective_connection_establishment(&params.parameters())
+            .unwrap(),
+        VendorCommand::GapCreateConnection(params) => controller.create_connection(params).unwrap(),
+        VendorCommand::GapTerminateProcedure(procedure) => {
+            controller.terminate_procedure(*procedure).unwrap()
+        }
+        VendorCommand::GapStartConnectionUpdate(params) => {
+            controller.start_connection_update(params).unwrap()
+        }
+        VendorCommand::GapSendPairingRequest(params) => {
+            controller.send_pairing_request(params).unwrap()
+        }
+        VendorCommand::GapResolvePrivateAddress(addr) => {
+            controller.resolve_private_address(*addr).unwrap()
+        }
+        VendorCommand::GapSetBroadcastMode(params) => {
+            controller.set_broadcast_mode(&params.parameters()).unwrap()
+        }
+        VendorCommand::GapStartObservationProcedure(params) => {
+            controller.start_observation_procedure(params).unwrap()
+        }
+        VendorCommand::GapGetBondedDevices => controller.get_bonded_devices().unwrap(),
+        VendorCommand::GapIsDeviceBonded(addr) => controller.is_device_bonded(*addr).unwrap(),
+        #[cfg(feature = "bluenrg2")]
+        VendorCommand::GapNumericComparisonValueConfirm {
+            conn_handle,
+            confirmed,
+        } => controller
+            .numeric_comparison_value_confirm(*conn_handle, *confirmed)
+            .unwrap(),
+        #[cfg(feature = "bluenrg2")]
+        VendorCommand::GapPassKeyInput { conn_handle, input } => {
+            controller.pass_key_input(*conn_handle, *input).unwrap()
+        }
+        #[cfg(feature = "bluenrg2")]
+        VendorCommand::GapRemoveBondedDevice(addr) => {
+            controller.remove_bonded_device(*addr).unwrap()
+        }
+        VendorCommand::GattInit => GattCommands::init(controller).unwrap(),
+        VendorCommand::GattAddService(params) => controller.add_service(params).unwrap(),
+        VendorCommand::GattIncludeService(params) => controller.include_service(params).unwrap(),
+        VendorCommand::GattAddCharacteristic(params) => {
+            controller.add_characteristic(params).unwrap()
+        }
+        VendorCommand::GattAddCharacteristicDescriptor(params) => {
+            controller.add_characteristic_descriptor(params).unwrap()
+        }
+        VendorCommand::GattUpdateCharacteristicValue(params) => {
+            controller.update_characteristic_value(params).unwrap()
+        }
+        VendorCommand::GattDeleteCharacteristic {
+            service,
+            characteristic,
+        } => controller
+            .delete_characteristic(*service, *characteristic)
+            .unwrap(),
+        VendorCommand::GattDeleteService(service) => controller.delete_service(*service).unwrap(),
+        VendorCommand::GattDeleteIncludedService(params) => {
+            controller.delete_included_service(params).unwrap()
+        }
+        VendorCommand::GattSetEventMask(mask) => {
+            GattCommands::set_event_mask(controller, *mask).unwrap()
+        }
+        VendorCommand::GattExchangeConfiguration(conn_handle) => {
+            controller.exchange_configuration(*conn_handle).unwrap()
+        }
+        VendorCommand::GattFindInformationRequest {
+            conn_handle,
+            attribute_range,
+        } => controller
+            .find_information_request(*conn_handle, copy_range(attribute_range))
+            .unwrap(),
+        VendorCommand::GattFindByTypeValueRequest(params) => {
+            controller.find_by_type_value_request(params).unwrap()
+        }
+        VendorCommand::GattReadByTypeRequest(params) => {
+            controller.read_by_type_request(params).unwrap()
+        }
+        VendorCommand::GattReadByGroupTypeRequest(params) => {
+            controller.read_by_group_type_request(params).unwrap()
+        }
+        VendorCommand::GattPrepareWriteRequest(params) => {
+            controller.prepare_write_request(params).unwrap()
+        }
+        VendorCommand::GattExecuteWriteRequest(conn_handle) => {
+            controller.execute_write_request(*conn_handle).unwrap()
+        }
+        VendorCommand::GattCancelWriteRequest(conn_handle) => {
+            controller.cancel_write_request(*conn_handle).unwrap()
+        }
+        VendorCommand::GattDiscoverAllPrimaryServices(conn_handle) => controller
+            .discover_all_primary_services(*conn_handle)
+            .unwrap(),
+        VendorCommand::GattDiscoverPrimaryServicesByUuid { conn_handle, uuid } => controller
+            .discover_primary_services_by_uuid(*conn_handle, *uuid)
+            .unwrap(),
+        VendorCommand::GattFindIncludedServices {
+            conn_handle,
+            service_handle_range,
+        } => controller
+            .find_included_services(*conn_handle, copy_range(service_handle_range))
+            .unwrap(),
+        VendorCommand::GattDiscoverAllCharacteristicsOfService {
+            conn_handle,
+            attribute_handle_range,
+        } => controller
+            .discover_all_characteristics_of_service(
+                *conn_handle,
+                copy_range(attribute_handle_range),
+            )
+            .unwrap(),
+        VendorCommand::GattDiscoverCharacteristicsByUuid {
+            conn_handle,
+            attribute_handle_range,
+            uuid,
+        } => controller
+            .discover_characteristics_by_uuid(
+                *conn_handle,
+                copy_range(attribute_handle_range),
+                *uuid,
+            )
+            .unwrap(),
+        VendorCommand::GattDiscoverAllCharacteristicDescriptors {
+            conn_handle,
+            characteristic_handle_range,
+        } => controller
+            .discover_all_characteristic_descriptors(
+                *conn_handle,
+                copy_range(characteristic_handle_range),
+            )
+            .unwrap(),
+        VendorCommand::GattReadCharacteristicValue {
+            conn_handle,
+            characteristic_handle,
+        } => controller
+            .read_characteristic_value(*conn_handle, *characteristic_handle)
+            .unwrap(),
+        VendorCommand::GattReadCharacteristicUsingUuid {
+            conn_handle,
+            characteristic_handle_range,
+            uuid,
+        } => controller
+            .read_characteristic_using_uuid(
+                *conn_handle,
+                copy_range(characteristic_handle_range),
+                *uuid,
+            )
+            .unwrap(),
+        VendorCommand::GattReadLongCharacteristicValue(params) => {
+            controller.read_long_characteristic_value(params).unwrap()
+        }
+        VendorCommand::GattReadMultipleCharacteristicValues(params) => controller
+            .read_multiple_characteristic_values(&params.parameters())
+            .unwrap(),
+        VendorCommand::GattWriteCharacteristicValue(params) => {
+            controller.write_characteristic_value(params).unwrap()
+        }
+        VendorCommand::GattWriteLongCharacteristicValue(params) => {
+            controller.write_long_characteristic_value(params).unwrap()
+        }
+        VendorCommand::GattWriteCharacteristicValueReliably(params) => controller
+            .write_characteristic_value_reliably(params)
+            .unwrap(),
+        VendorCommand::GattWriteLongCharacteristicDescriptor(params) => controller
+            .write_long_characteristic_descriptor(params)
+            .unwrap(),
+        VendorCommand::GattReadLongCharacteristicDescriptor(params) => controller
+            .read_long_characteristic_descriptor(params)
+            .unwrap(),
+        VendorCommand::GattWriteCharacteristicDescriptor(params) => {
+            controller.write_characteristic_descriptor(params).unwrap()
+        }
+        VendorCommand::GattReadCharacteristicDescriptor {
+            conn_handle,
+            characteristic_handle,
+        } => controller
+            .read_characteristic_descriptor(*conn_handle, *characteristic_handle)
+            .unwrap(),
+        VendorCommand::GattWriteWithoutResponse(params) => {
+            controller.write_without_response(params).unwrap()
+        }
+        VendorCommand::GattSignedWriteWithoutResponse(params) => {
+            controller.signed_write_without_response(params).unwrap()
+        }
+        VendorCommand::GattConfirmIndication(conn_handle) => {
+            controller.confirm_indication(*conn_handle).unwrap()
+        }
+        VendorCommand::GattWriteResponse(params) => controller.write_response(params).unwrap(),
+        VendorCommand::GattAllowRead(conn_handle) => controller.allow_read(*conn_handle).unwrap(),
+        VendorCommand::GattSetSecurityPermission(params) => {
+            controller.set_security_permission(params).unwrap()
+        }
+        VendorCommand::GattSetDescriptorValue(params) => {
+            controller.set_descriptor_value(params).unwrap()
+        }
+        VendorCommand::GattReadHandleValue(handle) => {
+            controller.read_handle_value(*handle).unwrap()
+        }
+        VendorCommand::GattReadHandleValueOffset { handle, offset } => controller
+            .read_handle_value_offset(*handle, *offset)
+            .unwrap(),
+        VendorCommand::GattUpdateLongCharacteristicValue(params) => {
+            controller.update_long_characteristic_value(params).unwrap()
+        }
+        VendorCommand::L2CapConnectionParameterUpdateRequest(params) => controller
+            .connection_parameter_update_request(params)
+            .unwrap(),
+        VendorCommand::L2CapConnectionParameterUpdateResponse(params) => controller
+            .connection_parameter_update_response(params)
+            .unwrap(),
+        VendorCommand::UpdaterStart => UpdaterCommands::start(controller).unwrap(),
+        VendorCommand::UpdaterReboot => controller.reboot().unwrap(),
+        VendorCommand::UpdaterGetVersion => controller.get_version().unwrap(),
+        VendorCommand::UpdaterGetBufferSize => controller.get_buffer_size().unwrap(),
+        VendorCommand::UpdaterEraseBlueFlag => controller.erase_blue_flag().unwrap(),
+        VendorCommand::UpdaterResetBlueFlag => controller.reset_blue_flag().unwrap(),
+        VendorCommand::UpdaterEraseSector(address) => controller.erase_sector(*address).unwrap(),
+        VendorCommand::UpdaterProgramDataBlock { address, data } => {
+            controller.program_data_block(*address, data).unwrap()
+        }
+        VendorCommand::UpdaterReadDataBlock { address, len } => {
+            controller.read_data_block(*address, *len).unwrap()
+        }
+        VendorCommand::UpdaterCalcCrc {
+            address,
+            sector_count,
+        } => controller.calc_crc(*address, *sector_count).unwrap(),
+        VendorCommand::UpdaterHwVersion => controller.hw_version().unwrap(),
+    })
+}
+
+fn copy_range<T: Copy + PartialOrd>(range: &Range<T>) -> Range<T> {
+    Range::new(*range.from(), *range.to()).unwrap()
+}
+
+#[test]
+fn round_trip() {
+    let mut packets = Vec::new();
+    write_commands(&mut packets);
+    for packet in packets {
+        let command = VendorCommand::from_packet(Dialect::BlueNRGMS, &packet).unwrap();
+        assert_eq!(reencode(Dialect::BlueNRGMS, &command), packet);
+    }
+}
+
+#[test]
+fn round_trip_original_dialect() {
+    let mut packets = Vec::new();
+    #[cfg(not(feature = "bluenrg2"))]
+    packets.push(written!(Dialect::BlueNRG, |controller| GapCommands::init(
+        controller,
+        Role::PERIPHERAL,
+        false,
+        bluenrg::gap::BLUENRG_DEVICE_NAME_LEN
+    )
+    .unwrap()));
+    packets.push(written!(Dialect::BlueNRG, |controller| controller
+        .set_nonconnectable(AdvertisingType::ScannableUndirected, AddressType::Public)
+        .unwrap()));
+    packets.push(written!(Dialect::BlueNRG, |controller| controller
+        .allow_rebond(None)
+        .unwrap()));
+    packets.push(written!(Dialect::BlueNRG, |controller| controller
+        .set_direct_connectable(&DirectConnectableParameters {
+            own_address_type: OwnAddressType::Random,
+            advertising_type: AdvertisingType::ConnectableDirectedHighDutyCycle,
+            initiator_address: BdAddrType::Public(BdAddr([1, 2, 3, 4, 5, 6])),
+            advertising_interval: (Duration::from_millis(20), Duration::from_millis(20)),
+        })
+        .unwrap()));
+    packets.push(written!(Dialect::BlueNRG, |controller| controller
+        .start_auto_connection_establishment(&AutoConnectionEstablishmentParameters {
+            scan_window: scan_window(),
+            own_address_type: OwnAddressType::Public,
+            conn_interval: conn_interval(),
+            expected_connection_length: expected_connection_length(),
+            reconnection_address: Some(BdAddr([6, 5, 4, 3, 2, 1])),
+            white_list: &WHITE_LIST,
+        })
+        .unwrap()));
+    packets.push(written!(Dialect::BlueNRG, |controller| controller
+        .start_general_connection_establishment(&GeneralConnectionEstablishmentParameters {
+            scan_window: scan_window(),
+            own_address_type: OwnAddressType::Random,
+            filter_duplicates: true,
+            reconnection_address: Some(BdAddr([6, 5, 4, 3, 2, 1])),
+        })
+        .unwrap()));
+    for packet in packets {
+        let command = VendorCommand::from_packet(Dialect::BlueNRG, &packet).unwrap();
+        assert_eq!(reencode(Dialect::BlueNRG, &command), packet);
+    }
+}
+
+#[test]
+fn ms_only_commands_unknown_in_original_dialect() {
+    let packet = written!(|controller| controller
+        .read_handle_value_offset(CharacteristicHandle(0x0201), 3)
+        .unwrap());
+    let err = VendorCommand::from_packet(Dialect::BlueNRG, &packet)
+        .err()
+        .unwrap();
+    assert_eq!(err, Error::UnknownOpcode(hci::Opcode(0xFD2B)));
+}
+
+#[test]
+fn decodes_parameters() {
+    let data = written!(|controller| controller
+        .set_discoverable(&DiscoverableParameters {
+            advertising_type: AdvertisingType::ConnectableUndirected,
+            advertising_interval: Some((Duration::from_millis(100), Duration::from_millis(200))),
+            address_type: OwnAddressType::Public,
+            filter_policy: AdvertisingFilterPolicy::AllowConnectionAndScan,
+            local_name: Some(LocalName::Complete(b"dev")),
+            advertising_data: &[1, 2],
+            conn_interval: (Some(Duration::from_millis(50)), None),
+        })
+        .unwrap());
+    match VendorCommand::from_packet(Dialect::BlueNRGMS, &data).unwrap() {
+        VendorCommand::GapSetDiscoverable(params) => {
+            assert_eq!(
+                params.advertising_type,
+                AdvertisingType::ConnectableUndirected
+            );
+            assert_eq!(
+                params.advertising_interval,
+                Some((Duration::from_millis(100), Duration::from_millis(200)))
+            );
+            match params.local_name {
+                Some(LocalName::Complete(name)) => assert_eq!(name, b"dev"),
+                _ => panic!("Wrong local name"),
+            }
+            assert_eq!(params.advertising_data, [1, 2]);
+            assert_eq!(
+                params.conn_interval,
+                (Some(Duration::from_millis(50)), None)
+            );
+        }
+        _ => panic!("Wrong command"),
+    }
+
+    let data = written!(|controller| controller
+        .start_selective_connection_establishment(&SelectiveConnectionEstablishmentParameters {
+            scan_type: ScanType::Active,
+            scan_window: scan_window(),
+            own_address_type: OwnAddressType::Public,
+            filter_duplicates: true,
+            white_list: &WHITE_LIST,
+        })
+        .unwrap());
+    match VendorCommand::from_packet(Dialect::BlueNRGMS, &data).unwrap() {
+        VendorCommand::GapStartSelectiveConnectionEstablishment(params) => {
+            assert_eq!(params.scan_type, ScanType::Active);
+            assert!(params.filter_duplicates);
+            assert_eq!(params.white_list().len(), 2);
+            match params.white_list()[1] {
+                PeerAddrType::RandomDeviceAddress(addr) => {
+                    assert_eq!(addr, BdAddr([7, 8, 9, 10, 11, 12]))
+                }
+                _ => panic!("Wrong white list address"),
+            }
+        }
+        _ => panic!("Wrong command"),
+    }
+}
+
+#[test]
+fn execute_and_cancel_write_share_opcode() {
+    let data = written!(|controller| controller
+        .execute_write_request(ConnectionHandle(0x0201))
+        .unwrap());
+    match VendorCommand::from_packet(Dialect::BlueNRGMS, &data).unwrap() {
+        VendorCommand::GattExecuteWriteRequest(ConnectionHandle(0x0201)) => (),
+        _ => panic!("Wrong command"),
+    }
+
+    let data = written!(|controller| controller
+        .cancel_write_request(ConnectionHandle(0x0201))
+        .unwrap());
+    match VendorCommand::from_packet(Dialect::BlueNRGMS, &data).unwrap() {
+        VendorCommand::GattCancelWriteRequest(ConnectionHandle(0x0201)) => (),
+        _ => panic!("Wrong command"),
+    }
+}
+
+#[cfg(not(feature = "bluenrg2"))]
+#[test]
+fn gap_init_original_dialect() {
+    match VendorCommand::with_dialect(Dialect::BlueNRG, hci::Opcode(0xFC8A), &[0x03]).unwrap() {
+        VendorCommand::GapInit {
+            role,
+            privacy_enabled,
+            dev_name_characteristic_len,
+        } => {
+            assert_eq!(role, Role::PERIPHERAL | Role::BROADCASTER);
+            assert!(!privacy_enabled);
+            assert_eq!(
+                dev_name_characteristic_len,
+                bluenrg::gap::BLUENRG_DEVICE_NAME_LEN
+            );
+        }
+        _ => panic!("Wrong command"),
+    }
+}
+
+#[test]
+fn set_nonconnectable_dialects() {
+    match VendorCommand::with_dialect(Dialect::BlueNRG, hci::Opcode(0xFC8B), &[0x02]).unwrap() {
+        VendorCommand::GapSetNonConnectable {
+            advertising_type,
+            address_type,
+        } => {
+            assert_eq!(advertising_type, AdvertisingType::ScannableUndirected);
+            assert_eq!(address_type, AddressType::Public);
+        }
+        _ => panic!("Wrong command"),
+    }
+    match VendorCommand::with_dialect(Dialect::BlueNRGMS, hci::Opcode(0xFC8B), &[0x03, 0x01])
+        .unwrap()
+    {
+        VendorCommand::GapSetNonConnectable {
+            advertising_type,
+            address_type,
+        } => {
+            assert_eq!(advertising_type, AdvertisingType::NonConnectableUndirected);
+            assert_eq!(address_type, AddressType::Random);
+        }
+        _ => panic!("Wrong command"),
+    }
+    let err = VendorCommand::with_dialect(Dialect::BlueNRG, hci::Opcode(0xFC8B), &[0x03, 0x01])
+        .err()
+        .unwrap();
+    assert_eq!(err, Error::BadLength(2, 1));
+}
+
+#[test]
+fn unknown_opcode() {
+    let err = VendorCommand::from_packet(Dialect::BlueNRGMS, &[0x01, 0xFF, 0xFC, 0x00])
+        .err()
+        .unwrap();
+    assert_eq!(err, Error::UnknownOpcode(hci::Opcode(0xFCFF)));
+
+    let err = VendorCommand::from_packet(Dialect::BlueNRGMS, &[0x01, 0x03, 0x0C, 0x00])
+        .err()
+        .unwrap();
+    assert_eq!(err, Error::UnknownOpcode(hci::Opcode(0x0C03)));
+}
+
+#[test]
+fn bad_packet_type() {
+    let err = VendorCommand::from_packet(Dialect::BlueNRGMS, &[0x04, 0x00, 0xFC, 0x00])
+        .err()
+        .unwrap();
+    assert_eq!(err, Error::BadPacketType(0x04));
+}
+
+#[test]
+fn bad_packet_length() {
+    let err = VendorCommand::from_packet(Dialect::BlueNRGMS, &[0x01, 0x00, 0xFC])
+        .err()
+        .unwrap();
+    assert_eq!(err, Error::BadLength(3, 4));
+
+    let err = VendorCommand::from_packet(Dialect::BlueNRGMS, &[0x01, 0x00, 0xFC, 0x01])
+        .err()
+        .unwrap();
+    assert_eq!(err, Error::BadLength(4, 5));
+}
+
+#[test]
+fn bad_parameter_length() {
+    let err = VendorCommand::with_dialect(Dialect::BlueNRGMS, hci::Opcode(0xFC00), &[0x00])
+        .err()
+        .unwrap();
+    assert_eq!(err, Error::BadLength(1, 0));
+
+    // Write Characteristic Value, with one byte fewer than its value length.
+    let err = VendorCommand::with_dialect(
+        Dialect::BlueNRGMS,
+        hci::Opcode(0xFD1C),
+        &[0x01, 0x02, 0x03, 0x04, 0x02, 0x05],
+    )
+    .err()
+    .unwrap();
+    assert_eq!(err, Error::BadLength(6, 7));
+}
+
+#[test]
+fn bad_io_capability() {
+    let err = VendorCommand::with_dialect(Dialect::BlueNRGMS, hci::Opcode(0xFC85), &[0x05])
+        .err()
+        .unwrap();
+    assert_eq!(err, Error::BadIoCapability(0x05));
+}
+
+#[test]
+fn bad_uuid_type() {
+    let err = VendorCommand::with_dialect(
+        Dialect::BlueNRGMS,
+        hci::Opcode(0xFD02),
+        &[0x03, 0x01, 0x02, 0x01, 0x03],
+    )
+    .err()
+    .unwrap();
+    assert_eq!(err, Error::BadUuidType(0x03));
+}
+
+#[test]
+fn bad_handle_range() {
+    let err = VendorCommand::with_dialect(
+        Dialect::BlueNRGMS,
+        hci::Opcode(0xFD0C),
+        &[0x01, 0x02, 0x06, 0x05, 0x04, 0x03],
+    )
+    .err()
+    .unwrap();
+    assert_eq!(err, Error::BadHandleRange(0x0506, 0x0304));
+}
+
+#[test]
+fn debug_format() {
+    let command =
+        VendorCommand::with_dialect(Dialect::BlueNRGMS, hci::Opcode(0xFC8B), &[0x03, 0x01])
+            .unwrap();
+    assert_eq!(
+        format!("{:?}", command),
+        "GapSetNonConnectable { advertising_type: NonConnectableUndirected, address_type: Random }"
+    );
+}
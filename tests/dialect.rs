@@ -0,0 +1,371 @@
+extern crate bluenrg;
+extern crate bluetooth_hci as hci;
+extern crate embedded_hal as hal;
+extern crate nb;
+
+mod fixture;
+
+use bluenrg::capability::Capability;
+use bluenrg::dialect::Dialect;
+use bluenrg::event::command::ReturnParameters;
+use bluenrg::event::{BlueNRGError, BlueNRGEvent};
+use bluenrg::gap::{AdvertisingType, Commands as GapCommands};
+use bluenrg::gatt::{CharacteristicHandle, Commands as GattCommands};
+use bluenrg::{BlueNRG, Firmware, Version};
+use fixture::{DummyPin, Fixture, RecordingSink, ScriptedSink};
+use hci::event::Error as HciError;
+use hci::host::uart::{Error as UartError, Packet};
+use hci::ConnectionHandle;
+
+fn version(major: u8) -> Version {
+    Version {
+        hw_version: 0x31,
+        major,
+        minor: 1,
+        patch: 2,
+    }
+}
+
+#[cfg(not(feature = "bluenrg2"))]
+#[test]
+fn from_version() {
+    assert_eq!(Dialect::from_version(&version(6)), Dialect::BlueNRG);
+    assert_eq!(Dialect::from_version(&version(7)), Dialect::BlueNRGMS);
+}
+
+#[cfg(feature = "bluenrg2")]
+#[test]
+fn from_version() {
+    assert_eq!(Dialect::from_version(&version(2)), Dialect::BlueNRGMS);
+    assert_eq!(Dialect::from_version(&version(7)), Dialect::BlueNRGMS);
+}
+
+#[test]
+fn default_follows_feature() {
+    let expected = if cfg!(feature = "ms") {
+        Dialect::BlueNRGMS
+    } else {
+        Dialect::BlueNRG
+    };
+    assert_eq!(Dialect::default(), expected);
+
+    let mut rx_buffer = [0; 8];
+    let bnrg: BlueNRG<RecordingSink, _, _, _, _> =
+        BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    assert_eq!(bnrg.dialect(), expected);
+}
+
+#[test]
+fn controller_reports_own_dialect() {
+    let mut sink = RecordingSink::new();
+    let mut rx_buffer = [0; 8];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.set_dialect(Dialect::BlueNRG);
+
+    let mut other_rx_buffer = [0; 8];
+    let mut other: BlueNRG<RecordingSink, _, _, _, _> =
+        BlueNRG::new(&mut other_rx_buffer, DummyPin, DummyPin, DummyPin);
+    other.set_dialect(Dialect::BlueNRGMS);
+
+    assert_eq!(bnrg.dialect(), Dialect::BlueNRG);
+    assert_eq!(
+        bnrg.with_spi(&mut sink, |controller| controller.dialect()),
+        Dialect::BlueNRG
+    );
+    assert_eq!(other.dialect(), Dialect::BlueNRGMS);
+}
+
+#[cfg(not(feature = "bluenrg2"))]
+#[test]
+fn init() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRG);
+        fixture
+            .act(|controller| {
+                controller.init_gap(
+                    bluenrg::gap::Role::PERIPHERAL,
+                    false,
+                    bluenrg::gap::BLUENRG_DEVICE_NAME_LEN,
+                )
+            })
+            .unwrap();
+        assert!(fixture.wrote(&[1, 0x8A, 0xFC, 1, 0x01]));
+
+        let err = fixture
+            .act(|controller| controller.init_gap(bluenrg::gap::Role::PERIPHERAL, true, 3))
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            nb::Error::Other(bluenrg::gap::Error::UnsupportedInDialect(Dialect::BlueNRG))
+        );
+    }
+
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRGMS);
+        fixture
+            .act(|controller| controller.init_gap(bluenrg::gap::Role::PERIPHERAL, true, 3))
+            .unwrap();
+        assert!(fixture.wrote(&[1, 0x8A, 0xFC, 3, 0x01, 0x01, 0x03]));
+    }
+}
+
+#[test]
+fn set_nonconnectable() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRG);
+        fixture
+            .act(|controller| {
+                controller.set_nonconnectable(
+                    AdvertisingType::ScannableUndirected,
+                    bluenrg::gap::AddressType::Public,
+                )
+            })
+            .unwrap();
+        assert!(fixture.wrote(&[1, 0x8B, 0xFC, 1, 0x02]));
+
+        let err = fixture
+            .act(|controller| {
+                controller.set_nonconnectable(
+                    AdvertisingType::ScannableUndirected,
+                    bluenrg::gap::AddressType::Random,
+                )
+            })
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            nb::Error::Other(bluenrg::gap::Error::UnsupportedInDialect(Dialect::BlueNRG))
+        );
+    }
+
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRGMS);
+        fixture
+            .act(|controller| {
+                controller.set_nonconnectable(
+                    AdvertisingType::ScannableUndirected,
+                    bluenrg::gap::AddressType::Random,
+                )
+            })
+            .unwrap();
+        assert!(fixture.wrote(&[1, 0x8B, 0xFC, 2, 0x02, 0x01]));
+    }
+}
+
+#[test]
+fn allow_rebond() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRG);
+        fixture
+            .act(|controller| controller.allow_rebond(None))
+            .unwrap();
+        assert!(fixture.wrote(&[1, 0x95, 0xFC, 0]));
+    }
+
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRGMS);
+        fixture
+            .act(|controller| controller.allow_rebond(Some(ConnectionHandle(0x0201))))
+            .unwrap();
+        assert!(fixture.wrote(&[1, 0x95, 0xFC, 2, 0x01, 0x02]));
+    }
+}
+
+#[test]
+fn ms_only_commands_unsupported_in_original_dialect() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRG);
+        let err = fixture
+            .act(|controller| controller.read_handle_value_offset(CharacteristicHandle(0x0201), 3))
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            nb::Error::Other(bluenrg::gatt::Error::Unsupported(
+                Capability::ReadHandleValueOffset
+            ))
+        );
+    }
+    assert!(!sink.wrote_header());
+}
+
+#[test]
+fn read_uses_controller_dialect() {
+    const EVENTS_LOST: [u8; 13] = [
+        0x04, 0xFF, 0x0A, 0x02, 0x00, 0b00000001, 0, 0, 0, 0, 0, 0, 0,
+    ];
+
+    let mut sink = ScriptedSink::new(0x00);
+    sink.event(&EVENTS_LOST);
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.set_dialect(Dialect::BlueNRGMS);
+    match bnrg.with_spi(&mut sink, |controller| {
+        nb::block!(bluenrg::event::read(controller))
+    }) {
+        Ok(Packet::Event(hci::Event::Vendor(BlueNRGEvent::EventsLost(flags)))) => {
+            assert_eq!(flags, bluenrg::event::EventFlags::DISCONNECTION_COMPLETE)
+        }
+        other => panic!("Did not get events lost event: {:?}", other),
+    }
+
+    let mut sink = ScriptedSink::new(0x00);
+    sink.event(&EVENTS_LOST);
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.set_dialect(Dialect::BlueNRG);
+    match bnrg.with_spi(&mut sink, |controller| {
+        nb::block!(bluenrg::event::read(controller))
+    }) {
+        Err(UartError::BLE(HciError::Vendor(BlueNRGError::UnknownEvent(0x0002)))) => (),
+        other => panic!("Did not get unknown event: {:?}", other),
+    }
+}
+
+#[test]
+fn read_parses_return_parameters_in_controller_dialect() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.event(&[0x04, 0x0E, 0x04, 0x01, 0xA0, 0xFC, 0x00]);
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.set_dialect(Dialect::BlueNRG);
+    match bnrg.with_spi(&mut sink, |controller| {
+        nb::block!(bluenrg::event::read(controller))
+    }) {
+        Ok(Packet::Event(hci::Event::CommandComplete(complete))) => match complete.return_params {
+            hci::event::command::ReturnParameters::Vendor(
+                ReturnParameters::GapResolvePrivateAddress(params),
+            ) => {
+                assert_eq!(params.status, hci::Status::Success);
+                assert_eq!(params.bd_addr, None);
+            }
+            other => panic!("Wrong return parameters: {:?}", other),
+        },
+        other => panic!("Did not get command complete: {:?}", other),
+    }
+}
+
+#[test]
+fn events_lost() {
+    let buffer = [0x02, 0x00, 0b00000001, 0, 0, 0, 0, 0, 0, 0];
+    match BlueNRGEvent::with_dialect(Dialect::BlueNRGMS, &buffer) {
+        Ok(BlueNRGEvent::EventsLost(flags)) => {
+            assert_eq!(flags, bluenrg::event::EventFlags::DISCONNECTION_COMPLETE)
+        }
+        other => panic!("Did not get events lost event: {:?}", other),
+    }
+    match BlueNRGEvent::with_dialect(Dialect::BlueNRG, &buffer) {
+        Err(HciError::Vendor(BlueNRGError::UnknownEvent(0x0002))) => (),
+        other => panic!("Did not get unknown event: {:?}", other),
+    }
+}
+
+#[test]
+fn gatt_attribute_modified() {
+    let buffer = [0x01, 0x0C, 0x01, 0x02, 0x03, 0x04, 0x02, 0x07, 0x08];
+    match BlueNRGEvent::with_dialect(Dialect::BlueNRG, &buffer) {
+        Ok(BlueNRGEvent::GattAttributeModified(event)) => {
+            assert_eq!(event.conn_handle, ConnectionHandle(0x0201));
+            assert_eq!(event.offset, 0);
+            assert!(!event.continued);
+            assert_eq!(event.data(), [0x07, 0x08]);
+        }
+        other => panic!("Did not get Gatt attribute modified: {:?}", other),
+    }
+
+    #[cfg(not(feature = "bluenrg2"))]
+    let buffer = [
+        0x01, 0x0C, 0x01, 0x02, 0x03, 0x04, 0x02, 0x05, 0x86, 0x07, 0x08,
+    ];
+    #[cfg(feature = "bluenrg2")]
+    let buffer = [
+        0x01, 0x0C, 0x01, 0x02, 0x03, 0x04, 0x05, 0x86, 0x02, 0x00, 0x07, 0x08,
+    ];
+    match BlueNRGEvent::with_dialect(Dialect::BlueNRGMS, &buffer) {
+        Ok(BlueNRGEvent::GattAttributeModified(event)) => {
+            assert_eq!(event.offset, 0x0605);
+            assert!(event.continued);
+            assert_eq!(event.data(), [0x07, 0x08]);
+        }
+        other => panic!("Did not get Gatt attribute modified: {:?}", other),
+    }
+}
+
+#[test]
+fn gap_resolve_private_address() {
+    let bytes = [1, 0xA0, 0xFC, 0, 1, 2, 3, 4, 5, 6];
+    match ReturnParameters::with_dialect(Dialect::BlueNRGMS, &bytes) {
+        Ok(ReturnParameters::GapResolvePrivateAddress(params)) => {
+            assert_eq!(params.bd_addr, Some(hci::BdAddr([1, 2, 3, 4, 5, 6])));
+        }
+        other => panic!("Wrong return parameters: {:?}", other),
+    }
+
+    let bytes = [1, 0xA0, 0xFC, 0];
+    match ReturnParameters::with_dialect(Dialect::BlueNRG, &bytes) {
+        Ok(ReturnParameters::GapResolvePrivateAddress(params)) => {
+            assert_eq!(params.status, hci::Status::Success);
+            assert_eq!(params.bd_addr, None);
+        }
+        other => panic!("Wrong return parameters: {:?}", other),
+    }
+}
+
+#[cfg(not(feature = "bluenrg2"))]
+#[test]
+fn bring_up_selects_dialect_from_version() {
+    use bluenrg::bring_up::BringUpConfig;
+    use fixture::PollCountTimer;
+
+    let mut sink = ScriptedSink::new(0x00);
+    sink.event(&[0x04, 0xFF, 0x03, 0x01, 0x00, 0x01]);
+    sink.accept_command(4);
+    sink.event(&[
+        0x04, 0x0E, 0x0C, 0x01, 0x01, 0x10, 0x00, 0x07, 0x06, 0x31, 0x06, 0x30, 0x00, 0x12, 0x00,
+    ]);
+    sink.accept_command(4);
+    sink.event(&[0x04, 0x0E, 0x04, 0x01, 0x01, 0xFD, 0x00]);
+    // The original BlueNRG only takes the role.
+    sink.accept_command(5);
+    sink.event(&[
+        0x04, 0x0E, 0x0A, 0x01, 0x8A, 0xFC, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06,
+    ]);
+
+    let mut timer = PollCountTimer::new();
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.set_retry_limit(Some(4));
+    bnrg.set_dialect(Dialect::BlueNRGMS);
+    let result = bnrg
+        .bring_up(
+            &mut sink,
+            &mut timer,
+            &BringUpConfig {
+                reset_time: 0,
+                timeout: 10,
+                config_data: &[],
+                gap_role: bluenrg::gap::Role::PERIPHERAL,
+                privacy_enabled: false,
+                dev_name_characteristic_len: bluenrg::gap::BLUENRG_DEVICE_NAME_LEN,
+            },
+        )
+        .unwrap();
+    assert_eq!(result.version.major, 6);
+    assert_eq!(bnrg.version(), Some(result.version));
+    assert_eq!(bnrg.dialect(), Dialect::BlueNRG);
+}
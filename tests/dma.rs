@@ -0,0 +1,214 @@
+#![cfg(feature = "dma")]
+
+extern crate bluenrg;
+extern crate bluetooth_hci as hci;
+extern crate embedded_hal as hal;
+extern crate nb;
+
+mod fixture;
+
+use bluenrg::event::{BlueNRGError, BlueNRGEvent};
+use bluenrg::{BlueNRG, Error, RxStats};
+use fixture::{DummyPin, NeverError, ScriptedSink};
+use hci::host::uart::{Error as UartError, Hci, Packet};
+use hci::Event;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+type ReadResult = nb::Result<Packet<BlueNRGEvent>, UartError<Error<(), NeverError>, BlueNRGError>>;
+
+fn read<C>(controller: &mut C) -> ReadResult
+where
+    C: Hci<Error<(), NeverError>, BlueNRGEvent, BlueNRGError>,
+{
+    controller.read()
+}
+
+const COMMAND_COMPLETE: [u8; 9] = [0x04, 0x0E, 0x06, 0x02, 0x00, 0xFC, 0x00, 0x34, 0x12];
+const COMMAND_STATUS: [u8; 7] = [0x04, 0x0F, 0x04, 0x00, 0x01, 0x81, 0xFC];
+
+fn assert_command_complete(result: ReadResult) {
+    match result {
+        Ok(Packet::Event(Event::CommandComplete(event))) => {
+            assert_eq!(event.num_hci_command_packets, 2);
+        }
+        other => panic!("Did not get command complete: {:?}", other),
+    }
+}
+
+fn assert_command_status(result: ReadResult) {
+    match result {
+        Ok(Packet::Event(Event::CommandStatus(event))) => {
+            assert_eq!(event.opcode, hci::Opcode(0xFC81));
+        }
+        other => panic!("Did not get command status: {:?}", other),
+    }
+}
+
+#[test]
+fn wrapped_buffer_read_in_one_transfer() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.event(&COMMAND_COMPLETE).event(&COMMAND_COMPLETE);
+
+    // After the first event, there are 7 bytes before the end of the buffer, so the second event
+    // wraps around.
+    let mut rx_buffer = [0; 16];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.with_spi_dma(&mut sink, |controller| {
+        assert_command_complete(read(controller));
+        assert_command_complete(read(controller));
+    });
+    assert_eq!(sink.transactions, 2);
+    assert_eq!(
+        bnrg.rx_stats(),
+        RxStats {
+            bytes: 18,
+            transfers: 2,
+            time: Duration::default(),
+            failed_reads: 0,
+        }
+    );
+}
+
+#[test]
+fn blocking_path_splits_wrapped_read() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.event(&COMMAND_COMPLETE).event(&COMMAND_COMPLETE);
+    let mut rx_buffer = [0; 16];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.with_spi(&mut sink, |controller| {
+        assert_command_complete(read(controller));
+        assert_command_complete(read(controller));
+    });
+    assert_eq!(
+        bnrg.rx_stats(),
+        RxStats {
+            bytes: 18,
+            transfers: 3,
+            time: Duration::default(),
+            failed_reads: 0,
+        }
+    );
+
+    bnrg.reset_rx_stats();
+    assert_eq!(bnrg.rx_stats(), RxStats::default());
+}
+
+#[test]
+fn dma_overflow() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&[0x02, 0x00, 0x00, 16, 0x00])
+        .reply(&COMMAND_COMPLETE)
+        .reply(&COMMAND_STATUS)
+        .event(&COMMAND_STATUS);
+
+    // Holds 15 bytes, so the command status event does not fit
+    let mut rx_buffer = [0; 16];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.with_spi_dma(&mut sink, |controller| {
+        assert_eq!(
+            read(controller).err(),
+            Some(nb::Error::Other(UartError::Comm(Error::RxOverflow)))
+        );
+        assert_command_complete(read(controller));
+        assert_command_status(read(controller));
+    });
+    assert_eq!(bnrg.dropped_bytes(), COMMAND_STATUS.len() as u32);
+    assert_eq!(
+        bnrg.rx_stats(),
+        RxStats {
+            bytes: 15 + COMMAND_STATUS.len() as u32,
+            transfers: 2,
+            time: Duration::default(),
+            failed_reads: 0,
+        }
+    );
+}
+
+#[test]
+fn dma_read_completes_on_poll() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.event(&COMMAND_COMPLETE);
+    sink.dma_latency = 2;
+    let mut rx_buffer = [0; 16];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.with_spi_dma(&mut sink, |controller| {
+        assert_eq!(read(controller).err(), Some(nb::Error::WouldBlock));
+        assert_eq!(read(controller).err(), Some(nb::Error::WouldBlock));
+        assert_command_complete(read(controller));
+    });
+    assert_eq!(sink.transactions, 1);
+    assert!(!sink.dma_in_progress());
+}
+
+#[test]
+fn dma_read_in_progress_completes_before_release() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.event(&COMMAND_COMPLETE);
+    sink.dma_latency = 3;
+    let mut rx_buffer = [0; 16];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.with_spi_dma(&mut sink, |controller| {
+        assert_eq!(read(controller).err(), Some(nb::Error::WouldBlock));
+    });
+    assert!(!sink.dma_in_progress());
+    assert_eq!(bnrg.rx_stats().bytes, COMMAND_COMPLETE.len() as u32);
+
+    // The data read after the body returned is kept for the next call.
+    bnrg.with_spi_dma(&mut sink, |controller| {
+        assert_command_complete(read(controller));
+    });
+    assert_eq!(sink.transactions, 1);
+}
+
+#[test]
+fn dma_read_failing_after_release_is_counted() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.event(&COMMAND_COMPLETE);
+    sink.dma_latency = 1;
+    sink.dma_fails = true;
+    let mut rx_buffer = [0; 16];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.with_spi_dma(&mut sink, |controller| {
+        assert_eq!(read(controller).err(), Some(nb::Error::WouldBlock));
+    });
+    assert!(!sink.dma_in_progress());
+    assert_eq!(
+        bnrg.rx_stats(),
+        RxStats {
+            bytes: 0,
+            transfers: 0,
+            time: Duration::default(),
+            failed_reads: 1,
+        }
+    );
+}
+
+fn tick() -> Duration {
+    static NOW: AtomicU64 = AtomicU64::new(0);
+    Duration::from_millis(NOW.fetch_add(1, Ordering::SeqCst))
+}
+
+#[test]
+fn rx_stats_time() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.event(&COMMAND_COMPLETE).event(&COMMAND_COMPLETE);
+    let mut rx_buffer = [0; 16];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.set_rx_clock(Some(tick));
+    bnrg.with_spi(&mut sink, |controller| {
+        assert_command_complete(read(controller));
+        assert_command_complete(read(controller));
+    });
+
+    // The clock advances by 1 ms each time it is read, at the start and end of each transfer.
+    assert_eq!(
+        bnrg.rx_stats(),
+        RxStats {
+            bytes: 18,
+            transfers: 3,
+            time: Duration::from_millis(3),
+            failed_reads: 0,
+        }
+    );
+}
@@ -93,9 +93,8 @@ fn hal_events_lost_unknown() {
     }
 }
 
-#[test]
 #[cfg(feature = "ms")]
-fn hal_crash_info() {
+fn crash_info_buffer() -> [u8; 46] {
     let mut buffer = [0; 46];
     buffer[0] = 0x03; // event code
     buffer[1] = 0x00;
@@ -143,7 +142,13 @@ fn hal_crash_info() {
     buffer[43] = 0x28;
     buffer[44] = 0x29;
     buffer[45] = 0x2a;
-    let buffer = buffer;
+    buffer
+}
+
+#[test]
+#[cfg(feature = "ms")]
+fn hal_crash_info() {
+    let buffer = crash_info_buffer();
     match BlueNRGEvent::new(&buffer) {
         Ok(BlueNRGEvent::CrashReport(info)) => {
             assert_eq!(info.reason, CrashReason::Assertion);
@@ -193,6 +198,177 @@ fn hal_crash_info_failed_bad_debug_data_len() {
     }
 }
 
+#[cfg(feature = "ms")]
+fn crash_info(buffer: &[u8]) -> FaultData {
+    match BlueNRGEvent::new(buffer) {
+        Ok(BlueNRGEvent::CrashReport(info)) => info,
+        other => panic!("Did not get crash info: {:?}", other),
+    }
+}
+
+#[test]
+#[cfg(feature = "ms")]
+fn crash_record_round_trip() {
+    let info = crash_info(&crash_info_buffer());
+    let mut record = [0; bluenrg::crash::MAX_RECORD_LEN];
+    let len = info.serialize(&mut record).unwrap();
+    assert_eq!(len, 45);
+    assert_eq!(len, info.serialized_len());
+    assert_eq!(record[0], bluenrg::crash::FORMAT_VERSION);
+    assert_eq!(record[1], 0x00);
+    assert_eq!(record[2..6], [0x01, 0x02, 0x03, 0x04]);
+    assert_eq!(record[38], 6);
+    assert_eq!(record[39..45], [0x25, 0x26, 0x27, 0x28, 0x29, 0x2a]);
+
+    let (decoded, decoded_len) = FaultData::deserialize(&record[..len]).unwrap();
+    assert_eq!(decoded_len, len);
+    assert_eq!(decoded.reason, CrashReason::Assertion);
+    assert_eq!(decoded.sp, 0x04030201);
+    assert_eq!(decoded.r0, 0x08070605);
+    assert_eq!(decoded.r1, 0x0c0b0a09);
+    assert_eq!(decoded.r2, 0x100f0e0d);
+    assert_eq!(decoded.r3, 0x14131211);
+    assert_eq!(decoded.r12, 0x18171615);
+    assert_eq!(decoded.lr, 0x1c1b1a19);
+    assert_eq!(decoded.pc, 0x201f1e1d);
+    assert_eq!(decoded.xpsr, 0x24232221);
+    assert_eq!(decoded.debug_data(), info.debug_data());
+}
+
+#[test]
+#[cfg(feature = "ms")]
+fn crash_record_errors() {
+    use bluenrg::crash::Error;
+
+    let info = crash_info(&crash_info_buffer());
+    let mut record = [0; 64];
+    assert_eq!(
+        info.serialize(&mut record[..44]),
+        Err(Error::BufferTooSmall(45))
+    );
+    assert_eq!(record[0], 0);
+
+    let len = info.serialize(&mut record).unwrap();
+    assert_eq!(
+        FaultData::deserialize(&record[..len - 1]).err(),
+        Some(Error::Truncated)
+    );
+    assert_eq!(
+        FaultData::deserialize(&record[..38]).err(),
+        Some(Error::Truncated)
+    );
+    assert_eq!(FaultData::deserialize(&[]).err(), Some(Error::Truncated));
+
+    let mut bad = record;
+    bad[0] = 2;
+    assert_eq!(
+        FaultData::deserialize(&bad).err(),
+        Some(Error::UnsupportedVersion(2))
+    );
+
+    let mut bad = record;
+    bad[1] = 6;
+    assert_eq!(
+        FaultData::deserialize(&bad).err(),
+        Some(Error::BadCrashReason(6))
+    );
+
+    let mut bad = record;
+    bad[38] = 216;
+    assert_eq!(
+        FaultData::deserialize(&bad).err(),
+        Some(Error::BadDebugDataLength(216))
+    );
+}
+
+#[test]
+#[cfg(feature = "ms")]
+fn crash_records() {
+    let first = crash_info(&crash_info_buffer());
+    let mut buffer = [0; 40];
+    buffer[0] = 0x03;
+    buffer[2] = 0x07; // Hard fault
+    let second = crash_info(&buffer);
+
+    // Records stored back to back in flash, followed by erased flash.
+    let mut flash = [0xFF; 128];
+    let first_len = first.serialize(&mut flash).unwrap();
+    second.serialize(&mut flash[first_len..]).unwrap();
+
+    let mut records = bluenrg::crash::records(&flash);
+    let decoded = records.next().unwrap().unwrap();
+    assert_eq!(decoded.reason, CrashReason::Assertion);
+    assert_eq!(decoded.debug_data().len(), 6);
+    let decoded = records.next().unwrap().unwrap();
+    assert_eq!(decoded.reason, CrashReason::HardFault);
+    assert!(decoded.debug_data().is_empty());
+    assert!(records.next().is_none());
+
+    // A damaged record ends the iteration.
+    flash[0] = 0;
+    let mut records = bluenrg::crash::records(&flash);
+    assert_eq!(
+        records.next().unwrap().err(),
+        Some(bluenrg::crash::Error::UnsupportedVersion(0))
+    );
+    assert!(records.next().is_none());
+}
+
+#[test]
+#[cfg(feature = "ms")]
+fn crash_report() {
+    let info = crash_info(&crash_info_buffer());
+    assert_eq!(
+        format!("{}", bluenrg::crash::Report(&info)),
+        "assertion failed at pc 0x201f1e1d, lr 0x1c1b1a19\n\
+         \x20 sp   0x04030201  xpsr 0x24232221\n\
+         \x20 r0   0x08070605  r1   0x0c0b0a09  r2   0x100f0e0d  r3   0x14131211\n\
+         \x20 r12  0x18171615\n\
+         \x20 debug data (6 bytes):\n\
+         \x20   0000  25 26 27 28 29 2a\n"
+    );
+
+    let mut buffer = [0; 40];
+    buffer[0] = 0x03;
+    buffer[2] = 0x01; // NMI fault
+    let info = crash_info(&buffer);
+    assert!(format!("{}", bluenrg::crash::Report(&info)).ends_with("  no debug data\n"));
+}
+
+#[test]
+#[cfg(feature = "ms")]
+fn crash_groups() {
+    use bluenrg::crash::{Groups, GroupsFull};
+
+    let first = crash_info(&crash_info_buffer());
+    let mut buffer = crash_info_buffer();
+    buffer[40..].copy_from_slice(&[0; 6]);
+    let same_crash = crash_info(&buffer);
+    buffer[31] = 0x00; // pc
+    let other_crash = crash_info(&buffer);
+
+    let mut groups: Groups<2> = Groups::new();
+    assert!(groups.is_empty());
+    assert_eq!(groups.add(&first).unwrap().count, 1);
+    assert_eq!(groups.add(&same_crash).unwrap().count, 2);
+    assert_eq!(groups.add(&other_crash).unwrap().count, 1);
+    assert_eq!(groups.len(), 2);
+
+    let counted: Vec<_> = groups
+        .iter()
+        .map(|group| (group.signature.pc, group.count))
+        .collect();
+    assert_eq!(counted, [(0x201f1e1d, 2), (0x201f1e00, 1)]);
+
+    buffer[27] = 0x00; // lr
+    let third_crash = crash_info(&buffer);
+    assert_eq!(
+        groups.add(&third_crash),
+        Err(GroupsFull(third_crash.signature()))
+    );
+    assert_eq!(groups.len(), 2);
+}
+
 #[test]
 #[cfg(not(feature = "ms"))]
 fn hal_crash_info_unknown() {
@@ -702,6 +878,19 @@ fn gap_addr_not_resolved() {
     }
 }
 
+#[cfg(feature = "bluenrg2")]
+#[test]
+fn gap_numeric_comparison_value() {
+    let buffer = [0x09, 0x04, 0x01, 0x02, 0x3F, 0x42, 0x0F, 0x00];
+    match BlueNRGEvent::new(&buffer) {
+        Ok(BlueNRGEvent::GapNumericComparisonValue(event)) => {
+            assert_eq!(event.conn_handle, ConnectionHandle(0x0201));
+            assert_eq!(event.value, 999_999);
+        }
+        other => panic!("Did not get Numeric Comparison Value event: {:?}", other),
+    }
+}
+
 #[cfg(not(feature = "ms"))]
 #[test]
 fn gap_addr_not_resolved() {
@@ -714,7 +903,7 @@ fn gap_addr_not_resolved() {
     }
 }
 
-#[cfg(feature = "ms")]
+#[cfg(all(feature = "ms", not(feature = "bluenrg2")))]
 #[test]
 fn gatt_attribute_modified() {
     let buffer = [
@@ -725,14 +914,14 @@ fn gatt_attribute_modified() {
             assert_eq!(event.conn_handle, ConnectionHandle(0x0201));
             assert_eq!(event.attr_handle, AttributeHandle(0x0403));
             assert_eq!(event.offset, 0x0605);
-            assert_eq!(event.continued, true);
+            assert!(event.continued);
             assert_eq!(event.data(), [0x07, 0x08]);
         }
         other => panic!("Did not get Gatt attribute modified: {:?}", other),
     }
 }
 
-#[cfg(feature = "ms")]
+#[cfg(all(feature = "ms", not(feature = "bluenrg2")))]
 #[test]
 fn gatt_attribute_modified_failed_bad_data_len() {
     let buffer = [
@@ -747,6 +936,39 @@ fn gatt_attribute_modified_failed_bad_data_len() {
     }
 }
 
+#[cfg(feature = "bluenrg2")]
+#[test]
+fn gatt_attribute_modified() {
+    let buffer = [
+        0x01, 0x0C, 0x01, 0x02, 0x03, 0x04, 0x05, 0x86, 0x02, 0x00, 0x07, 0x08,
+    ];
+    match BlueNRGEvent::new(&buffer) {
+        Ok(BlueNRGEvent::GattAttributeModified(event)) => {
+            assert_eq!(event.conn_handle, ConnectionHandle(0x0201));
+            assert_eq!(event.attr_handle, AttributeHandle(0x0403));
+            assert_eq!(event.offset, 0x0605);
+            assert!(event.continued);
+            assert_eq!(event.data(), [0x07, 0x08]);
+        }
+        other => panic!("Did not get Gatt attribute modified: {:?}", other),
+    }
+}
+
+#[cfg(feature = "bluenrg2")]
+#[test]
+fn gatt_attribute_modified_failed_bad_data_len() {
+    let buffer = [
+        0x01, 0x0C, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x03, 0x00, 0x07, 0x08,
+    ];
+    match BlueNRGEvent::new(&buffer) {
+        Err(HciError::BadLength(actual, expected)) => {
+            assert_eq!(actual, buffer.len());
+            assert_eq!(expected, buffer.len() + 1);
+        }
+        other => panic!("Did not get bad length: {:?}", other),
+    }
+}
+
 #[cfg(not(feature = "ms"))]
 #[test]
 fn gatt_attribute_modified() {
@@ -819,9 +1041,8 @@ fn att_find_information_response_16bit_uuids() {
                 assert_eq!(actual.handle, AttributeHandle(0x0c0b));
                 assert_eq!(actual.uuid, Uuid16(0x0e0d));
 
-                match iter.next() {
-                    Some(actual) => panic!("Found extra HandleUuidPair: {:?}", actual),
-                    None => (),
+                if let Some(actual) = iter.next() {
+                    panic!("Found extra HandleUuidPair: {:?}", actual);
                 }
             } else {
                 panic!("Did not get HandleUuidPair::Format16")
@@ -862,9 +1083,8 @@ fn att_find_information_response_128bit_uuids() {
                     ])
                 );
 
-                match iter.next() {
-                    Some(actual) => panic!("Found extra HandleUuidPair: {:?}", actual),
-                    None => (),
+                if let Some(actual) = iter.next() {
+                    panic!("Found extra HandleUuidPair: {:?}", actual);
                 }
             } else {
                 panic!("Did not get HandleUuidPair::Format128")
@@ -958,9 +1178,8 @@ fn att_read_by_type_response() {
             assert_eq!(actual.handle, AttributeHandle(0x1211));
             assert_eq!(actual.value, [0x13, 0x14, 0x15, 0x16]);
 
-            match iter.next() {
-                Some(_) => panic!("Found extra HandleValuePair"),
-                None => (),
+            if iter.next().is_some() {
+                panic!("Found extra HandleValuePair");
             }
         }
         other => panic!("Did not get read-by-type response: {:?}", other),
@@ -1108,9 +1327,8 @@ fn att_read_by_group_type_response() {
             assert_eq!(actual.group_end_handle, GroupEndHandle(0x1413));
             assert_eq!(actual.value, [0x15, 0x16, 0x17, 0x18]);
 
-            match iter.next() {
-                Some(_) => panic!("Found extra HandleValuePair"),
-                None => (),
+            if iter.next().is_some() {
+                panic!("Found extra HandleValuePair");
             }
         }
         other => panic!("Did not get Read by Group Type Response: {:?}", other),
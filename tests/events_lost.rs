@@ -0,0 +1,271 @@
+extern crate bluenrg;
+extern crate bluetooth_hci as hci;
+extern crate embedded_hal as hal;
+extern crate nb;
+
+mod fixture;
+
+use bluenrg::event::command::{HalLinkStatus, LinkState};
+use bluenrg::event::{BlueNRGEvent, EventFlags, GattProcedureComplete, GattProcedureStatus};
+use bluenrg::events_lost::*;
+use bluenrg::request::{self, Requester};
+use bluenrg::BlueNRG;
+use fixture::{DummyPin, NeverError, PollCountTimer, ScriptedSink};
+use hci::{ConnectionHandle, Event};
+
+#[derive(Default)]
+struct Recorded {
+    lost: Vec<EventFlags>,
+    link_status: Vec<HalLinkStatus>,
+    failed: Vec<ConnectionHandle>,
+}
+
+impl Hooks for Recorded {
+    fn events_lost(&mut self, flags: EventFlags) {
+        self.lost.push(flags);
+    }
+
+    fn link_status(&mut self, status: &HalLinkStatus) {
+        self.link_status.push(status.clone());
+    }
+
+    fn gatt_procedure_failed(&mut self, conn_handle: ConnectionHandle) {
+        self.failed.push(conn_handle);
+    }
+}
+
+fn events_lost(flags: EventFlags) -> Event<BlueNRGEvent> {
+    Event::Vendor(BlueNRGEvent::EventsLost(flags))
+}
+
+fn gatt_procedure_complete(conn_handle: u16) -> Event<BlueNRGEvent> {
+    Event::Vendor(BlueNRGEvent::GattProcedureComplete(GattProcedureComplete {
+        conn_handle: ConnectionHandle(conn_handle),
+        status: GattProcedureStatus::Success,
+    }))
+}
+
+fn link_status(sink: &mut ScriptedSink, status: u8) {
+    sink.accept_command(4);
+    sink.event(&[
+        0x04, 0x0E, 28, 0x01, 0x17, 0xFC, status, 2, 1, 0, 0, 0, 0, 0, 0, 0x01, 0x08, 0x02, 0x08,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+    ]);
+}
+
+/// Passes each event to the monitor, and returns the results.
+fn handle<const N: usize>(
+    sink: &mut ScriptedSink,
+    monitor: &mut Monitor<N>,
+    events: &[Event<BlueNRGEvent>],
+    hooks: &mut Recorded,
+) -> Vec<Result<(), request::Error<bluenrg::Error<(), NeverError>>>> {
+    let mut requester: Requester<4> = Requester::new();
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.set_retry_limit(Some(4));
+    let mut timer = PollCountTimer::new();
+    bnrg.with_spi(sink, |controller| {
+        events
+            .iter()
+            .map(|event| monitor.handle(&mut requester, controller, event, hooks, &mut timer, 3))
+            .collect()
+    })
+}
+
+#[test]
+fn counts_lost_events() {
+    let mut sink = ScriptedSink::new(0x00);
+    let mut monitor: Monitor<2> = Monitor::new();
+    let mut hooks = Recorded::default();
+    let results = handle(
+        &mut sink,
+        &mut monitor,
+        &[
+            events_lost(EventFlags::ENCRYPTION_CHANGE | EventFlags::GAP_BOND_LOST),
+            events_lost(EventFlags::GAP_BOND_LOST),
+            events_lost(EventFlags::GATT_INDICATION),
+        ],
+        &mut hooks,
+    );
+    assert!(results.iter().all(Result::is_ok));
+
+    assert_eq!(monitor.count(EventFlags::ENCRYPTION_CHANGE), 1);
+    assert_eq!(monitor.count(EventFlags::GAP_BOND_LOST), 2);
+    assert_eq!(
+        monitor.count(EventFlags::ENCRYPTION_CHANGE | EventFlags::GAP_BOND_LOST),
+        3
+    );
+    assert_eq!(monitor.count(EventFlags::HAL_INITIALIZED), 0);
+    assert_eq!(
+        monitor.counts().collect::<Vec<_>>(),
+        [
+            (EventFlags::ENCRYPTION_CHANGE, 1),
+            (EventFlags::GAP_BOND_LOST, 2),
+            (EventFlags::GATT_INDICATION, 1),
+        ]
+    );
+    assert_eq!(
+        hooks.lost,
+        [
+            EventFlags::ENCRYPTION_CHANGE | EventFlags::GAP_BOND_LOST,
+            EventFlags::GAP_BOND_LOST,
+            EventFlags::GATT_INDICATION,
+        ]
+    );
+    assert!(hooks.link_status.is_empty());
+    assert!(hooks.failed.is_empty());
+    assert!(sink.sent.is_empty());
+
+    monitor.clear_counts();
+    assert_eq!(monitor.counts().count(), 0);
+}
+
+#[test]
+fn lost_disconnection_queries_link_status() {
+    let mut sink = ScriptedSink::new(0x00);
+    link_status(&mut sink, 0x00);
+
+    let mut monitor: Monitor<2> = Monitor::new();
+    let mut hooks = Recorded::default();
+    let results = handle(
+        &mut sink,
+        &mut monitor,
+        &[events_lost(EventFlags::DISCONNECTION_COMPLETE)],
+        &mut hooks,
+    );
+    assert_eq!(results, [Ok(())]);
+
+    assert_eq!(monitor.count(EventFlags::DISCONNECTION_COMPLETE), 1);
+    assert_eq!(hooks.link_status.len(), 1);
+    let status = &hooks.link_status[0];
+    assert_eq!(status.clients[0].state, LinkState::ConnectedAsPeripheral);
+    assert_eq!(status.clients[0].conn_handle, ConnectionHandle(0x0801));
+    assert_eq!(status.clients[1].state, LinkState::Advertising);
+    assert_eq!(status.clients[2].state, LinkState::Idle);
+    assert!(sink
+        .sent
+        .windows(4)
+        .any(|bytes| bytes == [0x01, 0x17, 0xFC, 0]));
+}
+
+#[test]
+fn link_status_failed() {
+    let mut sink = ScriptedSink::new(0x00);
+    link_status(&mut sink, 0x41);
+
+    let mut monitor: Monitor<2> = Monitor::new();
+    let mut hooks = Recorded::default();
+    let results = handle(
+        &mut sink,
+        &mut monitor,
+        &[events_lost(EventFlags::DISCONNECTION_COMPLETE)],
+        &mut hooks,
+    );
+    assert_eq!(
+        results,
+        [Err(request::Error::CommandFailed(hci::Status::Vendor(
+            bluenrg::event::Status::Failed
+        )))]
+    );
+    assert_eq!(hooks.lost, [EventFlags::DISCONNECTION_COMPLETE]);
+    assert!(hooks.link_status.is_empty());
+}
+
+#[test]
+fn lost_gatt_procedure_complete_fails_pending_procedures() {
+    let mut sink = ScriptedSink::new(0x00);
+    let mut monitor: Monitor<2> = Monitor::new();
+    monitor
+        .start_gatt_procedure(ConnectionHandle(0x0801))
+        .unwrap();
+    monitor
+        .start_gatt_procedure(ConnectionHandle(0x0802))
+        .unwrap();
+    assert_eq!(
+        monitor.start_gatt_procedure(ConnectionHandle(0x0803)),
+        Err(TooManyProcedures(ConnectionHandle(0x0803)))
+    );
+
+    let mut hooks = Recorded::default();
+    let results = handle(
+        &mut sink,
+        &mut monitor,
+        &[
+            gatt_procedure_complete(0x0802),
+            events_lost(EventFlags::GATT_PROCEDURE_COMPLETE),
+        ],
+        &mut hooks,
+    );
+    assert!(results.iter().all(Result::is_ok));
+
+    assert_eq!(hooks.failed, [ConnectionHandle(0x0801)]);
+    assert!(!monitor.gatt_procedure_pending(ConnectionHandle(0x0801)));
+    assert!(!monitor.gatt_procedure_pending(ConnectionHandle(0x0802)));
+    assert!(sink.sent.is_empty());
+}
+
+#[test]
+fn link_status_times_out() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(4);
+    for _ in 0..4 {
+        // The controller is awake, but never responds.
+        sink.reply(&[0x02, 0x00, 0x00, 0x00, 0x00]);
+    }
+
+    let mut monitor: Monitor<2> = Monitor::new();
+    let mut hooks = Recorded::default();
+    let results = handle(
+        &mut sink,
+        &mut monitor,
+        &[events_lost(EventFlags::DISCONNECTION_COMPLETE)],
+        &mut hooks,
+    );
+    assert_eq!(results, [Err(request::Error::Timeout)]);
+    assert_eq!(monitor.count(EventFlags::DISCONNECTION_COMPLETE), 1);
+    assert!(hooks.link_status.is_empty());
+}
+
+#[test]
+fn gatt_procedures_end() {
+    let mut sink = ScriptedSink::new(0x00);
+    let mut monitor: Monitor<2> = Monitor::new();
+    monitor
+        .start_gatt_procedure(ConnectionHandle(0x0801))
+        .unwrap();
+    monitor
+        .start_gatt_procedure(ConnectionHandle(0x0802))
+        .unwrap();
+    assert!(monitor.gatt_procedure_pending(ConnectionHandle(0x0801)));
+
+    let disconnected = |status| {
+        Event::DisconnectionComplete(hci::event::DisconnectionComplete {
+            status,
+            conn_handle: ConnectionHandle(0x0802),
+            reason: hci::Status::ConnectionTerminatedByHost,
+        })
+    };
+    let mut hooks = Recorded::default();
+    handle(
+        &mut sink,
+        &mut monitor,
+        &[
+            gatt_procedure_complete(0x0801),
+            disconnected(hci::Status::CommandDisallowed),
+        ],
+        &mut hooks,
+    );
+    assert!(!monitor.gatt_procedure_pending(ConnectionHandle(0x0801)));
+    assert!(monitor.gatt_procedure_pending(ConnectionHandle(0x0802)));
+
+    handle(
+        &mut sink,
+        &mut monitor,
+        &[disconnected(hci::Status::Success)],
+        &mut hooks,
+    );
+    assert!(!monitor.gatt_procedure_pending(ConnectionHandle(0x0802)));
+    assert!(hooks.lost.is_empty());
+    assert!(hooks.failed.is_empty());
+}
@@ -7,7 +7,6 @@ use core::convert::TryInto;
 macro_rules! assert_eq_hw_error {
     ($val:expr, $expected:path) => {
         if let Ok($expected) = $val.try_into() {
-            ()
         } else {
             panic!("{:?} !==> {:?}", $val, $expected)
         }
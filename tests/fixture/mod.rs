@@ -1,32 +1,172 @@
 #![allow(dead_code)]
 
 extern crate bluenrg;
+extern crate bluetooth_hci as hci;
+#[cfg(feature = "dma")]
+extern crate embedded_dma;
 extern crate embedded_hal as hal;
+#[cfg(any(feature = "async", feature = "spi-device"))]
+extern crate embedded_hal_1 as hal1;
+#[cfg(feature = "async")]
+extern crate embedded_hal_async as hal_async;
 extern crate nb;
 
 use bluenrg::{ActiveBlueNRG, BlueNRG};
 use std::cmp;
+use std::collections::VecDeque;
 
-static mut DUMMY_RX_BUFFER: [u8; 8] = [0; 8];
+const RX_BUFFER_LEN: usize = 259;
 
-pub struct Fixture<'sink, 'buf> {
+type FixtureBlueNRG =
+    BlueNRG<'static, RecordingSink, DummyPin, DummyPin, DummyPin, NeverError, [u8; RX_BUFFER_LEN]>;
+
+pub struct Fixture<'sink> {
     pub sink: &'sink mut RecordingSink,
-    bnrg: BlueNRG<'buf, RecordingSink, DummyPin, DummyPin, DummyPin, NeverError>,
+    bnrg: FixtureBlueNRG,
+
+    #[cfg(feature = "async")]
+    async_sink: RecordingSink,
+    #[cfg(feature = "async")]
+    async_bnrg: FixtureBlueNRG,
+}
+
+type Active<'bnrg, 'spi, 'dbuf> = ActiveBlueNRG<
+    'bnrg,
+    'spi,
+    'dbuf,
+    RecordingSink,
+    DummyPin,
+    DummyPin,
+    DummyPin,
+    NeverError,
+    [u8; RX_BUFFER_LEN],
+>;
+
+/// Controller handed to test bodies when the async transport is enabled. Every command is written
+/// through both the blocking and the async transports, so the same encoding tests cover both.
+#[cfg(feature = "async")]
+pub struct DualController<'a, 'bnrg, 'spi, 'dbuf> {
+    blocking: &'a mut Active<'bnrg, 'spi, 'dbuf>,
+    async_sink: &'a mut RecordingSink,
+    async_bnrg: &'a mut FixtureBlueNRG,
+}
+
+#[cfg(feature = "async")]
+impl<'a, 'bnrg, 'spi, 'dbuf> hci::Controller for DualController<'a, 'bnrg, 'spi, 'dbuf> {
+    type Error = bluenrg::Error<(), NeverError>;
+    type Header = hci::host::uart::CommandHeader;
+    type Vendor = bluenrg::BlueNRGTypes;
+
+    fn write(&mut self, header: &[u8], payload: &[u8]) -> nb::Result<(), Self::Error> {
+        self.blocking.write(header, payload)?;
+        block_on(
+            self.async_bnrg
+                .with_spi_async(self.async_sink, |mut controller| async move {
+                    controller.write(header, payload).await
+                }),
+        )
+        .expect("async transport failed");
+
+        Ok(())
+    }
+
+    fn read_into(&mut self, buffer: &mut [u8]) -> nb::Result<(), Self::Error> {
+        self.blocking.read_into(buffer)
+    }
+
+    fn peek(&mut self, n: usize) -> nb::Result<u8, Self::Error> {
+        self.blocking.peek(n)
+    }
+}
+
+#[cfg(feature = "async")]
+impl<'a, 'bnrg, 'spi, 'dbuf> bluenrg::Firmware for DualController<'a, 'bnrg, 'spi, 'dbuf> {
+    fn version(&self) -> Option<bluenrg::Version> {
+        self.blocking.version()
+    }
+
+    fn dialect(&self) -> bluenrg::dialect::Dialect {
+        self.blocking.dialect()
+    }
+}
+
+/// Drives a future to completion. The fakes never wait on anything external, so polling in a loop
+/// is enough.
+#[cfg(feature = "async")]
+pub fn block_on<F: std::future::Future>(future: F) -> F::Output {
+    use std::task::{RawWaker, RawWakerVTable, Waker};
+
+    fn noop_raw_waker() -> RawWaker {
+        fn clone(_: *const ()) -> RawWaker {
+            noop_raw_waker()
+        }
+        fn noop(_: *const ()) {}
+        static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+        RawWaker::new(std::ptr::null(), &VTABLE)
+    }
+
+    let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+    let mut future = std::pin::pin!(future);
+    let mut context = std::task::Context::from_waker(&waker);
+    loop {
+        if let std::task::Poll::Ready(output) = future.as_mut().poll(&mut context) {
+            return output;
+        }
+    }
 }
 
-impl<'sink, 'buf> Fixture<'sink, 'buf> {
-    pub fn new(sink: &'sink mut RecordingSink) -> Fixture<'sink, 'buf> {
+impl<'sink> Fixture<'sink> {
+    pub fn new(sink: &'sink mut RecordingSink) -> Fixture<'sink> {
         Fixture {
             sink,
-            bnrg: unsafe { BlueNRG::new(&mut DUMMY_RX_BUFFER, DummyPin, DummyPin, DummyPin) },
+            bnrg: BlueNRG::new_owned([0; RX_BUFFER_LEN], DummyPin, DummyPin, DummyPin),
+
+            #[cfg(feature = "async")]
+            async_sink: RecordingSink::new(),
+            #[cfg(feature = "async")]
+            async_bnrg: BlueNRG::new_owned([0; RX_BUFFER_LEN], DummyPin, DummyPin, DummyPin),
         }
     }
 
+    pub fn set_dialect(&mut self, dialect: bluenrg::dialect::Dialect) {
+        self.bnrg.set_dialect(dialect);
+        #[cfg(feature = "async")]
+        self.async_bnrg.set_dialect(dialect);
+    }
+
+    pub fn set_version(&mut self, version: bluenrg::Version) {
+        self.bnrg.set_version(version);
+        #[cfg(feature = "async")]
+        self.async_bnrg.set_version(version);
+    }
+
+    #[cfg(not(feature = "async"))]
     pub fn act<T, F>(&mut self, body: F) -> T
     where
-        F: FnOnce(&mut ActiveBlueNRG<RecordingSink, DummyPin, DummyPin, DummyPin, NeverError>) -> T,
+        F: FnOnce(&mut Active) -> T,
     {
-        self.bnrg.with_spi(&mut self.sink, body)
+        self.bnrg.with_spi(self.sink, body)
+    }
+
+    #[cfg(feature = "async")]
+    pub fn act<T, F>(&mut self, body: F) -> T
+    where
+        F: FnOnce(&mut DualController) -> T,
+    {
+        let async_sink = &mut self.async_sink;
+        let async_bnrg = &mut self.async_bnrg;
+        let result = self.bnrg.with_spi(self.sink, |blocking| {
+            body(&mut DualController {
+                blocking,
+                async_sink,
+                async_bnrg,
+            })
+        });
+
+        assert_eq!(self.sink.written_header, self.async_sink.written_header);
+        assert_eq!(self.sink.written_data, self.async_sink.written_data);
+
+        result
     }
 
     pub fn wrote_header(&self) -> bool {
@@ -68,6 +208,19 @@ impl RecordingSink {
         }
     }
 
+    /// Returns a sink whose SPI header reports `data` as ready to read, followed by `data` itself.
+    pub fn with_reply(data: &[u8]) -> RecordingSink {
+        let mut canned_reply = vec![0x02, 0xFF, 0xFF, data.len() as u8, 0x00];
+        canned_reply.extend_from_slice(data);
+        canned_reply.reverse();
+
+        RecordingSink {
+            written_header: Vec::new(),
+            written_data: Vec::new(),
+            canned_reply,
+        }
+    }
+
     pub fn wrote_header(&self) -> bool {
         self.written_header == [0x0A, 0x00, 0x00, 0x00, 0x00]
     }
@@ -109,6 +262,57 @@ impl hal::spi::FullDuplex<u8> for RecordingSink {
 
 impl hal::blocking::spi::transfer::Default<u8> for RecordingSink {}
 
+#[cfg(feature = "async")]
+impl RecordingSink {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        hal::spi::FullDuplex::send(self, byte).unwrap();
+        hal::spi::FullDuplex::read(self).unwrap()
+    }
+}
+
+#[cfg(feature = "async")]
+impl hal1::spi::ErrorType for RecordingSink {
+    type Error = std::convert::Infallible;
+}
+
+#[cfg(feature = "async")]
+impl hal_async::spi::SpiBus<u8> for RecordingSink {
+    async fn read(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        for word in words.iter_mut() {
+            *word = self.exchange(0);
+        }
+        Ok(())
+    }
+
+    async fn write(&mut self, words: &[u8]) -> Result<(), Self::Error> {
+        for word in words {
+            self.exchange(*word);
+        }
+        Ok(())
+    }
+
+    async fn transfer(&mut self, read: &mut [u8], write: &[u8]) -> Result<(), Self::Error> {
+        for i in 0..cmp::max(read.len(), write.len()) {
+            let byte = self.exchange(write.get(i).copied().unwrap_or(0));
+            if let Some(word) = read.get_mut(i) {
+                *word = byte;
+            }
+        }
+        Ok(())
+    }
+
+    async fn transfer_in_place(&mut self, words: &mut [u8]) -> Result<(), Self::Error> {
+        for word in words.iter_mut() {
+            *word = self.exchange(*word);
+        }
+        Ok(())
+    }
+
+    async fn flush(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
 impl hal::blocking::spi::write::Default<u8> for RecordingSink {}
 
 pub struct DummyPin;
@@ -150,4 +354,278 @@ impl hal::digital::v2::InputPin for DummyPin {
     }
 }
 
+/// Data-ready pin that stays low for the given number of polls, then goes high.
+pub struct DataReadyAfter {
+    pub low_polls: std::cell::Cell<u32>,
+    pub polls: std::cell::Cell<u32>,
+}
+
+impl DataReadyAfter {
+    pub fn new(low_polls: u32) -> DataReadyAfter {
+        DataReadyAfter {
+            low_polls: std::cell::Cell::new(low_polls),
+            polls: std::cell::Cell::new(0),
+        }
+    }
+}
+
+impl hal::digital::v2::InputPin for &DataReadyAfter {
+    type Error = NeverError;
+
+    fn is_high(&self) -> Result<bool, Self::Error> {
+        self.polls.set(self.polls.get() + 1);
+        if self.low_polls.get() == 0 {
+            return Ok(true);
+        }
+        self.low_polls.set(self.low_polls.get() - 1);
+        Ok(false)
+    }
+
+    fn is_low(&self) -> Result<bool, Self::Error> {
+        self.is_high().map(|high| !high)
+    }
+}
+
+#[cfg(feature = "async")]
+impl hal1::digital::Error for NeverError {
+    fn kind(&self) -> hal1::digital::ErrorKind {
+        match *self {}
+    }
+}
+
+#[cfg(feature = "async")]
+impl hal1::digital::ErrorType for DummyPin {
+    type Error = NeverError;
+}
+
+#[cfg(feature = "async")]
+impl hal_async::digital::Wait for DummyPin {
+    async fn wait_for_high(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn wait_for_low(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn wait_for_rising_edge(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn wait_for_falling_edge(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    async fn wait_for_any_edge(&mut self) -> Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+/// SPI bus that replies with a scripted sequence of bytes, then with `idle` once the script runs
+/// out. Records every byte sent.
+/// Pointer to and length of a buffer given to a DMA read.
+type DmaBuffer = (*mut u8, usize);
+
+pub struct ScriptedSink {
+    replies: VecDeque<u8>,
+    idle: u8,
+    pub sent: Vec<u8>,
+    pub transactions: usize,
+
+    /// Number of times each DMA read reports `WouldBlock` before it completes.
+    pub dma_latency: usize,
+
+    /// Whether DMA reads fail with an SPI error instead of completing.
+    pub dma_fails: bool,
+    dma_read: Option<(DmaBuffer, DmaBuffer)>,
+    dma_polls: usize,
+}
+
+impl ScriptedSink {
+    pub fn new(idle: u8) -> ScriptedSink {
+        ScriptedSink {
+            replies: VecDeque::new(),
+            idle,
+            sent: Vec::new(),
+            transactions: 0,
+            dma_latency: 0,
+            dma_fails: false,
+            dma_read: None,
+            dma_polls: 0,
+        }
+    }
+
+    /// Returns true if a DMA read has been started and not yet seen to complete.
+    pub fn dma_in_progress(&self) -> bool {
+        self.dma_read.is_some()
+    }
+
+    /// Queues the bytes the controller returns next.
+    pub fn reply(&mut self, bytes: &[u8]) -> &mut ScriptedSink {
+        self.replies.extend(bytes);
+        self
+    }
+
+    /// Queues the replies for writing a command of `len` bytes, including the 4-byte header.
+    pub fn accept_command(&mut self, len: usize) -> &mut ScriptedSink {
+        self.reply(&[0x02, 0xFF, 0x00, 0x00, 0x00])
+            .reply(&vec![0x00; len])
+    }
+
+    /// Queues the replies for reading the given packet.
+    pub fn event(&mut self, packet: &[u8]) -> &mut ScriptedSink {
+        let len = packet.len() as u16;
+        self.reply(&[0x02, 0x00, 0x00, len as u8, (len >> 8) as u8])
+            .reply(packet)
+    }
+}
+
+impl hal::spi::FullDuplex<u8> for ScriptedSink {
+    type Error = ();
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        Ok(self.replies.pop_front().unwrap_or(self.idle))
+    }
+
+    fn send(&mut self, byte: u8) -> nb::Result<(), Self::Error> {
+        self.sent.push(byte);
+        Ok(())
+    }
+}
+
+impl hal::blocking::spi::transfer::Default<u8> for ScriptedSink {}
+
+impl hal::blocking::spi::write::Default<u8> for ScriptedSink {}
+
+/// The data is only written to the buffers when the read completes.
+#[cfg(feature = "dma")]
+impl bluenrg::dma::DmaRead for ScriptedSink {
+    type Error = ();
+
+    fn start_read<B>(&mut self, mut first: B, mut second: B) -> Result<(), Self::Error>
+    where
+        B: embedded_dma::WriteBuffer<Word = u8>,
+    {
+        assert!(self.dma_read.is_none());
+        self.transactions += 1;
+        self.dma_read = Some(unsafe { (first.write_buffer(), second.write_buffer()) });
+        self.dma_polls = 0;
+        Ok(())
+    }
+
+    fn poll_read(&mut self) -> nb::Result<(), Self::Error> {
+        let ((first, first_len), (second, second_len)) = self.dma_read.expect("no DMA read");
+        if self.dma_polls < self.dma_latency {
+            self.dma_polls += 1;
+            return Err(nb::Error::WouldBlock);
+        }
+
+        self.dma_read = None;
+        if self.dma_fails {
+            return Err(nb::Error::Other(()));
+        }
+
+        let (first, second) = unsafe {
+            (
+                std::slice::from_raw_parts_mut(first, first_len),
+                std::slice::from_raw_parts_mut(second, second_len),
+            )
+        };
+        for byte in first.iter_mut().chain(second.iter_mut()) {
+            hal::spi::FullDuplex::send(self, 0).unwrap();
+            *byte = hal::spi::FullDuplex::read(self).unwrap();
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "spi-device")]
+impl ScriptedSink {
+    fn exchange(&mut self, byte: u8) -> u8 {
+        hal::spi::FullDuplex::send(self, byte).unwrap();
+        hal::spi::FullDuplex::read(self).unwrap()
+    }
+}
+
+#[cfg(feature = "spi-device")]
+impl hal1::spi::ErrorType for ScriptedSink {
+    type Error = std::convert::Infallible;
+}
+
+/// Each transaction is one chip select frame.
+#[cfg(feature = "spi-device")]
+impl hal1::spi::SpiDevice<u8> for ScriptedSink {
+    fn transaction(
+        &mut self,
+        operations: &mut [hal1::spi::Operation<'_, u8>],
+    ) -> Result<(), Self::Error> {
+        self.transactions += 1;
+        for operation in operations {
+            match operation {
+                hal1::spi::Operation::Read(words) => {
+                    for word in words.iter_mut() {
+                        *word = self.exchange(0);
+                    }
+                }
+                hal1::spi::Operation::Write(words) => {
+                    for word in words.iter() {
+                        self.exchange(*word);
+                    }
+                }
+                hal1::spi::Operation::Transfer(read, write) => {
+                    for i in 0..cmp::max(read.len(), write.len()) {
+                        let byte = self.exchange(write.get(i).copied().unwrap_or(0));
+                        if let Some(word) = read.get_mut(i) {
+                            *word = byte;
+                        }
+                    }
+                }
+                hal1::spi::Operation::TransferInPlace(words) => {
+                    for word in words.iter_mut() {
+                        *word = self.exchange(*word);
+                    }
+                }
+                hal1::spi::Operation::DelayNs(_) => (),
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Timer that expires after it has been polled as many times as the count it was started with.
+pub struct PollCountTimer {
+    remaining: u32,
+    pub starts: usize,
+}
+
+impl PollCountTimer {
+    pub fn new() -> PollCountTimer {
+        PollCountTimer {
+            remaining: 0,
+            starts: 0,
+        }
+    }
+}
+
+impl hal::timer::CountDown for PollCountTimer {
+    type Time = u32;
+
+    fn start<T>(&mut self, count: T)
+    where
+        T: Into<u32>,
+    {
+        self.remaining = count.into();
+        self.starts += 1;
+    }
+
+    fn wait(&mut self) -> nb::Result<(), void::Void> {
+        if self.remaining == 0 {
+            Ok(())
+        } else {
+            self.remaining -= 1;
+            Err(nb::Error::WouldBlock)
+        }
+    }
+}
+
 pub struct DummySpi;
@@ -0,0 +1,129 @@
+extern crate bluenrg;
+extern crate bluetooth_hci as hci;
+extern crate embedded_hal as hal;
+extern crate nb;
+
+mod fixture;
+
+use bluenrg::hal::Commands;
+use bluenrg::BlueNRG;
+use fixture::{DummyPin, PollCountTimer, ScriptedSink};
+use hci::host::uart::Hci;
+
+fn read_event<C>(controller: &mut C)
+where
+    C: Hci<
+        bluenrg::Error<(), fixture::NeverError>,
+        bluenrg::event::BlueNRGEvent,
+        bluenrg::event::BlueNRGError,
+    >,
+{
+    controller.read().unwrap();
+}
+
+#[test]
+fn one_credit_after_start() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(4);
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    assert_eq!(bnrg.command_credits(), 1);
+    assert_eq!(bnrg.outstanding_commands(), 0);
+    bnrg.with_spi(&mut sink, |controller| {
+        controller.get_firmware_revision().unwrap();
+        assert_eq!(controller.command_credits(), 0);
+        assert_eq!(controller.outstanding_commands(), 1);
+        assert_eq!(
+            controller.get_firmware_revision().err(),
+            Some(nb::Error::WouldBlock)
+        );
+    });
+
+    // The blocked command did not touch the bus.
+    assert_eq!(sink.sent.len(), 9);
+}
+
+#[test]
+fn command_complete_returns_credits() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(4)
+        .event(&[0x04, 0x0E, 0x06, 0x02, 0x00, 0xFC, 0x00, 0x34, 0x12])
+        .accept_command(4)
+        .accept_command(4);
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.with_spi(&mut sink, |controller| {
+        controller.get_firmware_revision().unwrap();
+        read_event(controller);
+        assert_eq!(controller.command_credits(), 2);
+        assert_eq!(controller.outstanding_commands(), 0);
+
+        controller.get_firmware_revision().unwrap();
+        controller.get_firmware_revision().unwrap();
+        assert_eq!(controller.command_credits(), 0);
+        assert_eq!(controller.outstanding_commands(), 2);
+        assert_eq!(
+            controller.get_firmware_revision().err(),
+            Some(nb::Error::WouldBlock)
+        );
+    });
+}
+
+#[test]
+fn command_status_returns_credits() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(4)
+        .event(&[0x04, 0x0F, 0x04, 0x00, 0x01, 0x00, 0xFC])
+        .accept_command(4);
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.with_spi(&mut sink, |controller| {
+        controller.get_firmware_revision().unwrap();
+        read_event(controller);
+        assert_eq!(controller.command_credits(), 1);
+        assert_eq!(controller.outstanding_commands(), 0);
+        controller.get_firmware_revision().unwrap();
+    });
+}
+
+#[test]
+fn zero_credits_block_commands() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.event(&[0x04, 0x0E, 0x03, 0x00, 0x00, 0x00])
+        .event(&[0x04, 0x0E, 0x03, 0x01, 0x00, 0x00])
+        .accept_command(4);
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.with_spi(&mut sink, |controller| {
+        // Spontaneous command complete with no credits
+        read_event(controller);
+        assert_eq!(controller.command_credits(), 0);
+        assert_eq!(
+            controller.get_firmware_revision().err(),
+            Some(nb::Error::WouldBlock)
+        );
+
+        // Spontaneous command complete returns a credit, without completing a command
+        read_event(controller);
+        assert_eq!(controller.command_credits(), 1);
+        assert_eq!(controller.outstanding_commands(), 0);
+        controller.get_firmware_revision().unwrap();
+    });
+}
+
+#[test]
+fn reset_restores_credit() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(4);
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.with_spi(&mut sink, |controller| {
+        controller.get_firmware_revision().unwrap();
+    });
+    assert_eq!(bnrg.command_credits(), 0);
+    assert_eq!(bnrg.outstanding_commands(), 1);
+
+    bnrg.reset(&mut PollCountTimer::new(), 0u32).unwrap();
+    assert_eq!(bnrg.command_credits(), 1);
+    assert_eq!(bnrg.outstanding_commands(), 0);
+}
@@ -5,6 +5,8 @@ extern crate nb;
 
 mod fixture;
 
+use bluenrg::capability::Capability;
+use bluenrg::dialect::Dialect;
 use bluenrg::gap::*;
 use fixture::{Fixture, RecordingSink};
 use hci::types::{ConnectionIntervalBuilder, ExpectedConnectionLength, ScanWindow};
@@ -188,6 +190,35 @@ fn set_discoverable() {
     ]));
 }
 
+#[test]
+fn set_discoverable_without_local_name() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture
+            .act(|controller| {
+                controller.set_discoverable(&DiscoverableParameters {
+                    advertising_type: AdvertisingType::ConnectableUndirected,
+                    advertising_interval: Some((
+                        Duration::from_millis(1280),
+                        Duration::from_millis(2560),
+                    )),
+                    address_type: OwnAddressType::Public,
+                    filter_policy: AdvertisingFilterPolicy::AllowConnectionAndScan,
+                    local_name: None,
+                    advertising_data: &[0x01, 0x02, 0x03, 0x04],
+                    conn_interval: (Some(Duration::from_millis(5000)), None),
+                })
+            })
+            .unwrap();
+    }
+    assert!(sink.wrote_header());
+    assert!(sink.wrote(&[
+        1, 0x83, 0xFC, 17, 0x00, 0x00, 0x08, 0x00, 0x10, 0x00, 0x00, 0, 4, 0x01, 0x02, 0x03, 0x04,
+        0xA0, 0x0F, 0xFF, 0xFF
+    ]));
+}
+
 #[test]
 fn set_discoverable_bad_adv_type() {
     let mut sink = RecordingSink::new();
@@ -292,17 +323,19 @@ fn set_discoverable_bad_conn_interval() {
     assert!(!sink.wrote_header());
 }
 
-#[cfg(not(feature = "ms"))]
 #[test]
-fn set_direct_connectable() {
+fn set_direct_connectable_original_dialect() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRG);
         fixture
             .act(|controller| {
                 controller.set_direct_connectable(&DirectConnectableParameters {
                     own_address_type: OwnAddressType::Public,
+                    advertising_type: AdvertisingType::ConnectableDirectedHighDutyCycle,
                     initiator_address: BdAddrType::Public(BdAddr([1, 2, 3, 4, 5, 6])),
+                    advertising_interval: (Duration::from_millis(20), Duration::from_millis(20)),
                 })
             })
             .unwrap();
@@ -311,12 +344,37 @@ fn set_direct_connectable() {
     assert!(sink.wrote(&[1, 0x84, 0xFC, 8, 0x00, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06]));
 }
 
-#[cfg(feature = "ms")]
+#[test]
+fn set_direct_connectable_low_duty_cycle_original_dialect() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRG);
+        let err = fixture
+            .act(|controller| {
+                controller.set_direct_connectable(&DirectConnectableParameters {
+                    own_address_type: OwnAddressType::Public,
+                    advertising_type: AdvertisingType::ConnectableDirectedLowDutyCycle,
+                    initiator_address: BdAddrType::Public(BdAddr([1, 2, 3, 4, 5, 6])),
+                    advertising_interval: (Duration::from_millis(20), Duration::from_millis(50)),
+                })
+            })
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            nb::Error::Other(Error::UnsupportedInDialect(Dialect::BlueNRG))
+        );
+    }
+    assert!(!sink.wrote_header());
+}
+
 #[test]
 fn set_direct_connectable() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRGMS);
         fixture
             .act(|controller| {
                 controller.set_direct_connectable(&DirectConnectableParameters {
@@ -335,12 +393,12 @@ fn set_direct_connectable() {
     ]));
 }
 
-#[cfg(feature = "ms")]
 #[test]
 fn set_direct_connectable_bad_adv_type() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRGMS);
         let err = fixture
             .act(|controller| {
                 controller.set_direct_connectable(&DirectConnectableParameters {
@@ -362,12 +420,12 @@ fn set_direct_connectable_bad_adv_type() {
     assert!(!sink.wrote_header());
 }
 
-#[cfg(feature = "ms")]
 #[test]
 fn set_direct_connectable_bad_adv_interval() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRGMS);
         for (min, max) in [
             (Duration::from_millis(19), Duration::from_millis(50)),
             (Duration::from_millis(20), Duration::from_millis(10241)),
@@ -566,26 +624,61 @@ fn authorization_response() {
     assert!(sink.wrote(&[1, 0x89, 0xFC, 3, 0x01, 0x02, 0x01]));
 }
 
-#[cfg(not(feature = "ms"))]
+#[cfg(not(feature = "bluenrg2"))]
 #[test]
-fn init() {
+fn init_original_dialect() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRG);
         fixture
-            .act(|controller| controller.init_gap(Role::PERIPHERAL | Role::BROADCASTER))
+            .act(|controller| {
+                controller.init_gap(
+                    Role::PERIPHERAL | Role::BROADCASTER,
+                    false,
+                    BLUENRG_DEVICE_NAME_LEN,
+                )
+            })
             .unwrap();
     }
     assert!(sink.wrote_header());
     assert!(sink.wrote(&[1, 0x8A, 0xFC, 1, 0x03]));
 }
 
-#[cfg(feature = "ms")]
+#[cfg(not(feature = "bluenrg2"))]
+#[test]
+fn init_unsupported_in_original_dialect() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRG);
+        let err = fixture
+            .act(|controller| controller.init_gap(Role::PERIPHERAL, true, BLUENRG_DEVICE_NAME_LEN))
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            nb::Error::Other(Error::UnsupportedInDialect(Dialect::BlueNRG))
+        );
+        let err = fixture
+            .act(|controller| controller.init_gap(Role::PERIPHERAL, false, 3))
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            nb::Error::Other(Error::UnsupportedInDialect(Dialect::BlueNRG))
+        );
+    }
+    assert!(!sink.wrote_header());
+}
+
+#[cfg(not(feature = "bluenrg2"))]
 #[test]
 fn init() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRGMS);
         fixture
             .act(|controller| controller.init_gap(Role::PERIPHERAL | Role::BROADCASTER, true, 3))
             .unwrap();
@@ -594,29 +687,74 @@ fn init() {
     assert!(sink.wrote(&[1, 0x8A, 0xFC, 3, 0x03, 0x01, 0x03]));
 }
 
-#[cfg(not(feature = "ms"))]
+#[cfg(feature = "bluenrg2")]
 #[test]
-fn set_nonconnectable() {
+fn init() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
         fixture
-            .act(|controller| controller.set_nonconnectable(AdvertisingType::ScannableUndirected))
+            .act(|controller| {
+                controller.init_gap(Role::PERIPHERAL | Role::BROADCASTER, Privacy::Controller, 3)
+            })
+            .unwrap();
+    }
+    assert!(sink.wrote_header());
+    assert!(sink.wrote(&[1, 0x8A, 0xFC, 3, 0x03, 0x02, 0x03]));
+}
+
+#[test]
+fn set_nonconnectable_original_dialect() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRG);
+        fixture
+            .act(|controller| {
+                controller
+                    .set_nonconnectable(AdvertisingType::ScannableUndirected, AddressType::Public)
+            })
             .unwrap();
     }
     assert!(sink.wrote_header());
     assert!(sink.wrote(&[1, 0x8B, 0xFC, 1, 0x02]));
 }
 
-#[cfg(not(feature = "ms"))]
 #[test]
-fn set_nonconnectable_bad_type() {
+fn set_nonconnectable_address_type_original_dialect() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRG);
         let err = fixture
             .act(|controller| {
-                controller.set_nonconnectable(AdvertisingType::ConnectableDirectedHighDutyCycle)
+                controller.set_nonconnectable(
+                    AdvertisingType::ScannableUndirected,
+                    AddressType::ResolvablePrivate,
+                )
+            })
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            nb::Error::Other(Error::UnsupportedInDialect(Dialect::BlueNRG))
+        );
+    }
+    assert!(!sink.wrote_header());
+}
+
+#[test]
+fn set_nonconnectable_bad_type_original_dialect() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRG);
+        let err = fixture
+            .act(|controller| {
+                controller.set_nonconnectable(
+                    AdvertisingType::ConnectableDirectedHighDutyCycle,
+                    AddressType::Public,
+                )
             })
             .err()
             .unwrap();
@@ -630,12 +768,12 @@ fn set_nonconnectable_bad_type() {
     assert!(!sink.wrote_header());
 }
 
-#[cfg(feature = "ms")]
 #[test]
 fn set_nonconnectable() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRGMS);
         fixture
             .act(|controller| {
                 controller.set_nonconnectable(
@@ -649,12 +787,12 @@ fn set_nonconnectable() {
     assert!(sink.wrote(&[1, 0x8B, 0xFC, 2, 0x02, 0x02]));
 }
 
-#[cfg(feature = "ms")]
 #[test]
 fn set_nonconnectable_bad_type() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRGMS);
         let err = fixture
             .act(|controller| {
                 controller.set_nonconnectable(
@@ -868,26 +1006,56 @@ fn clear_security_database() {
     assert!(sink.wrote(&[1, 0x94, 0xFC, 0]));
 }
 
-#[cfg(not(feature = "ms"))]
 #[test]
-fn allow_rebond() {
+fn allow_rebond_original_dialect() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
-        fixture.act(|controller| controller.allow_rebond()).unwrap();
+        fixture.set_dialect(Dialect::BlueNRG);
+        fixture
+            .act(|controller| controller.allow_rebond(None))
+            .unwrap();
     }
     assert!(sink.wrote_header());
     assert!(sink.wrote(&[1, 0x95, 0xFC, 0]));
 }
 
-#[cfg(feature = "ms")]
+#[test]
+fn allow_rebond_unsupported_in_dialect() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRG);
+        let err = fixture
+            .act(|controller| controller.allow_rebond(Some(hci::ConnectionHandle(0x0201))))
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            nb::Error::Other(Error::UnsupportedInDialect(Dialect::BlueNRG))
+        );
+
+        fixture.set_dialect(Dialect::BlueNRGMS);
+        let err = fixture
+            .act(|controller| controller.allow_rebond(None))
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            nb::Error::Other(Error::UnsupportedInDialect(Dialect::BlueNRGMS))
+        );
+    }
+    assert!(!sink.wrote_header());
+}
+
 #[test]
 fn allow_rebond() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRGMS);
         fixture
-            .act(|controller| controller.allow_rebond(hci::ConnectionHandle(0x0201)))
+            .act(|controller| controller.allow_rebond(Some(hci::ConnectionHandle(0x0201))))
             .unwrap();
     }
     assert!(sink.wrote_header());
@@ -976,12 +1144,12 @@ fn start_name_discovery_procedure() {
     ]));
 }
 
-#[cfg(not(feature = "ms"))]
 #[test]
-fn start_auto_connection_establishment() {
+fn start_auto_connection_establishment_original_dialect() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRG);
         fixture
             .act(|controller| {
                 controller.start_auto_connection_establishment(
@@ -1027,12 +1195,12 @@ fn start_auto_connection_establishment() {
     assert!(&sink.wrote(&expected));
 }
 
-#[cfg(feature = "ms")]
 #[test]
 fn start_auto_connection_establishment() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRGMS);
         fixture
             .act(|controller| {
                 controller.start_auto_connection_establishment(
@@ -1053,6 +1221,7 @@ fn start_auto_connection_establishment() {
                             Duration::from_millis(1500),
                         )
                         .unwrap(),
+                        reconnection_address: None,
                         white_list: &[
                             hci::host::PeerAddrType::PublicDeviceAddress(hci::BdAddr([
                                 1, 2, 3, 4, 5, 6,
@@ -1076,12 +1245,12 @@ fn start_auto_connection_establishment() {
     assert!(sink.wrote(&expected));
 }
 
-#[cfg(not(feature = "ms"))]
 #[test]
-fn start_auto_connection_establishment_white_list_too_long() {
+fn start_auto_connection_establishment_white_list_too_long_original_dialect() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRG);
         let err = fixture
             .act(|controller| {
                 controller.start_auto_connection_establishment(
@@ -1116,12 +1285,12 @@ fn start_auto_connection_establishment_white_list_too_long() {
     assert!(!sink.wrote_header());
 }
 
-#[cfg(feature = "ms")]
 #[test]
 fn start_auto_connection_establishment_white_list_too_long() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRGMS);
         let err = fixture
             .act(|controller| {
                 controller.start_auto_connection_establishment(
@@ -1142,6 +1311,7 @@ fn start_auto_connection_establishment_white_list_too_long() {
                             Duration::from_millis(1500),
                         )
                         .unwrap(),
+                        reconnection_address: None,
                         white_list: &[hci::host::PeerAddrType::PublicDeviceAddress(hci::BdAddr([
                             1, 2, 3, 4, 5, 6,
                         ])); 34],
@@ -1155,12 +1325,12 @@ fn start_auto_connection_establishment_white_list_too_long() {
     assert!(!sink.wrote_header());
 }
 
-#[cfg(not(feature = "ms"))]
 #[test]
-fn start_general_connection_establishment() {
+fn start_general_connection_establishment_original_dialect() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRG);
         fixture
             .act(|controller| {
                 controller.start_general_connection_establishment(
@@ -1183,12 +1353,12 @@ fn start_general_connection_establishment() {
     );
 }
 
-#[cfg(feature = "ms")]
 #[test]
 fn start_general_connection_establishment() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRGMS);
         fixture
             .act(|controller| {
                 controller.start_general_connection_establishment(
@@ -1199,6 +1369,7 @@ fn start_general_connection_establishment() {
                             .unwrap(),
                         own_address_type: hci::host::OwnAddressType::Random,
                         filter_duplicates: true,
+                        reconnection_address: None,
                     },
                 )
             })
@@ -1208,6 +1379,36 @@ fn start_general_connection_establishment() {
     assert!(sink.wrote(&[1, 0x9A, 0xFC, 6, 0x04, 0x00, 0x04, 0x00, 0x01, 0x1]));
 }
 
+#[test]
+fn start_general_connection_establishment_reconnection_address() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRGMS);
+        let err = fixture
+            .act(|controller| {
+                controller.start_general_connection_establishment(
+                    &GeneralConnectionEstablishmentParameters {
+                        scan_window: ScanWindow::start_every(Duration::from_micros(2500))
+                            .unwrap()
+                            .open_for(Duration::from_micros(2500))
+                            .unwrap(),
+                        own_address_type: hci::host::OwnAddressType::Random,
+                        filter_duplicates: true,
+                        reconnection_address: Some(hci::BdAddr([1, 2, 3, 4, 5, 6])),
+                    },
+                )
+            })
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            nb::Error::Other(Error::UnsupportedInDialect(Dialect::BlueNRGMS))
+        );
+    }
+    assert!(!sink.wrote_header());
+}
+
 #[test]
 fn start_selective_connection_establishment() {
     let mut sink = RecordingSink::new();
@@ -1430,12 +1631,12 @@ fn get_bonded_devices() {
     assert!(sink.wrote(&[1, 0xA3, 0xFC, 0]));
 }
 
-#[cfg(feature = "ms")]
 #[test]
 fn set_broadcast_mode() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRGMS);
         fixture
             .act(|controller| {
                 controller.set_broadcast_mode(&BroadcastModeParameters {
@@ -1467,12 +1668,12 @@ fn set_broadcast_mode() {
     assert!(sink.wrote(&expected));
 }
 
-#[cfg(feature = "ms")]
 #[test]
 fn set_broadcast_mode_bad_advertising_type() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRGMS);
         let err = fixture
             .act(|controller| {
                 controller.set_broadcast_mode(&BroadcastModeParameters {
@@ -1505,12 +1706,12 @@ fn set_broadcast_mode_bad_advertising_type() {
     assert!(!sink.wrote_header());
 }
 
-#[cfg(feature = "ms")]
 #[test]
 fn set_broadcast_mode_advertising_data_too_long() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRGMS);
         let err = fixture
             .act(|controller| {
                 controller.set_broadcast_mode(&BroadcastModeParameters {
@@ -1538,12 +1739,12 @@ fn set_broadcast_mode_advertising_data_too_long() {
     assert!(!sink.wrote_header());
 }
 
-#[cfg(feature = "ms")]
 #[test]
 fn set_broadcast_mode_white_list_too_long() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRGMS);
         let err = fixture
             .act(|controller| {
                 controller.set_broadcast_mode(&BroadcastModeParameters {
@@ -1569,12 +1770,12 @@ fn set_broadcast_mode_white_list_too_long() {
     assert!(!sink.wrote_header());
 }
 
-#[cfg(feature = "ms")]
 #[test]
 fn set_broadcast_mode_white_list_too_long_no_adv_data() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRGMS);
         let err = fixture
             .act(|controller| {
                 controller.set_broadcast_mode(&BroadcastModeParameters {
@@ -1618,12 +1819,12 @@ fn set_broadcast_mode_white_list_too_long_no_adv_data() {
     // don't check all of the written data.
 }
 
-#[cfg(feature = "ms")]
 #[test]
 fn start_observation_procedure() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRGMS);
         fixture
             .act(|controller| {
                 controller.start_observation_procedure(&ObservationProcedureParameters {
@@ -1642,6 +1843,34 @@ fn start_observation_procedure() {
     assert!(sink.wrote(&[1, 0xA2, 0xFC, 7, 0x04, 0x00, 0x04, 0x00, 0x00, 0x01, 0x01]));
 }
 
+#[test]
+fn start_observation_procedure_original_dialect() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRG);
+        let err = fixture
+            .act(|controller| {
+                controller.start_observation_procedure(&ObservationProcedureParameters {
+                    scan_window: ScanWindow::start_every(Duration::from_micros(2500))
+                        .unwrap()
+                        .open_for(Duration::from_micros(2500))
+                        .unwrap(),
+                    scan_type: hci::host::ScanType::Passive,
+                    own_address_type: AddressType::Random,
+                    filter_duplicates: true,
+                })
+            })
+            .err()
+            .unwrap();
+        assert_eq!(
+            err,
+            nb::Error::Other(Error::Unsupported(Capability::StartObservationProcedure))
+        );
+    }
+    assert!(!sink.wrote_header());
+}
+
 #[test]
 fn is_device_bonded() {
     let mut sink = RecordingSink::new();
@@ -1658,3 +1887,53 @@ fn is_device_bonded() {
     assert!(sink.wrote_header());
     assert!(sink.wrote(&[1, 0xA4, 0xFC, 7, 0x00, 1, 2, 3, 4, 5, 6]));
 }
+
+#[cfg(feature = "bluenrg2")]
+#[test]
+fn numeric_comparison_value_confirm() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture
+            .act(|controller| {
+                controller.numeric_comparison_value_confirm(hci::ConnectionHandle(0x0201), true)
+            })
+            .unwrap();
+    }
+    assert!(sink.wrote_header());
+    assert!(sink.wrote(&[1, 0xA5, 0xFC, 3, 0x01, 0x02, 0x01]));
+}
+
+#[cfg(feature = "bluenrg2")]
+#[test]
+fn pass_key_input() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture
+            .act(|controller| {
+                controller.pass_key_input(hci::ConnectionHandle(0x0201), PassKeyInput::DigitErased)
+            })
+            .unwrap();
+    }
+    assert!(sink.wrote_header());
+    assert!(sink.wrote(&[1, 0xA6, 0xFC, 3, 0x01, 0x02, 0x02]));
+}
+
+#[cfg(feature = "bluenrg2")]
+#[test]
+fn remove_bonded_device() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture
+            .act(|controller| {
+                controller.remove_bonded_device(hci::host::PeerAddrType::RandomDeviceAddress(
+                    hci::BdAddr([1, 2, 3, 4, 5, 6]),
+                ))
+            })
+            .unwrap();
+    }
+    assert!(sink.wrote_header());
+    assert!(sink.wrote(&[1, 0xAA, 0xFC, 7, 0x01, 1, 2, 3, 4, 5, 6]));
+}
@@ -4,6 +4,7 @@ extern crate nb;
 
 mod fixture;
 
+use bluenrg::dialect::Dialect;
 use bluenrg::gatt::*;
 use fixture::{Fixture, RecordingSink};
 
@@ -136,6 +137,7 @@ fn add_characteristic_16() {
                         | CharacteristicEvent::CONFIRM_READ,
                     encryption_key_size: EncryptionKeySize::with_value(8).unwrap(),
                     is_variable: true,
+                    #[cfg(not(feature = "bluenrg2"))]
                     fw_version_before_v72: false,
                 })
             })
@@ -171,6 +173,7 @@ fn add_characteristic_128() {
                         | CharacteristicEvent::CONFIRM_READ,
                     encryption_key_size: EncryptionKeySize::with_value(8).unwrap(),
                     is_variable: true,
+                    #[cfg(not(feature = "bluenrg2"))]
                     fw_version_before_v72: false,
                 })
             })
@@ -183,6 +186,7 @@ fn add_characteristic_128() {
     ]));
 }
 
+#[cfg(not(feature = "bluenrg2"))]
 #[test]
 fn add_characteristic_pre_v72_16() {
     let mut sink = RecordingSink::new();
@@ -214,6 +218,7 @@ fn add_characteristic_pre_v72_16() {
         .wrote(&[1, 0x04, 0xFD, 11, 0x01, 0x02, 0x01, 0x03, 0x04, 0x05, 0x13, 0x09, 0x07, 8, 1]));
 }
 
+#[cfg(not(feature = "bluenrg2"))]
 #[test]
 fn add_characteristic_pre_v72_128() {
     let mut sink = RecordingSink::new();
@@ -265,6 +270,7 @@ fn encryption_key_size_range() {
     );
 }
 
+#[cfg(not(feature = "bluenrg2"))]
 #[test]
 fn add_characteristic_descriptor_16() {
     let mut sink = RecordingSink::new();
@@ -297,6 +303,40 @@ fn add_characteristic_descriptor_16() {
     ]));
 }
 
+#[cfg(feature = "bluenrg2")]
+#[test]
+fn add_characteristic_descriptor_16() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture
+            .act(|controller| {
+                controller.add_characteristic_descriptor(&AddDescriptorParameters {
+                    service_handle: ServiceHandle(0x0201),
+                    characteristic_handle: CharacteristicHandle(0x0403),
+                    descriptor_uuid: KnownDescriptor::CharacteristicExtendedProperties.into(),
+                    descriptor_value_max_len: 7,
+                    descriptor_value: &[1, 2, 3, 4],
+                    security_permissions: DescriptorPermission::AUTHENTICATED
+                        | DescriptorPermission::AUTHORIZED,
+                    access_permissions: AccessPermission::READ | AccessPermission::WRITE,
+                    gatt_event_mask: CharacteristicEvent::ATTRIBUTE_WRITE
+                        | CharacteristicEvent::CONFIRM_WRITE
+                        | CharacteristicEvent::CONFIRM_READ,
+                    encryption_key_size: EncryptionKeySize::with_value(8).unwrap(),
+                    is_variable: true,
+                })
+            })
+            .unwrap();
+    }
+    assert!(sink.wrote_header());
+    assert!(sink.wrote(&[
+        1, 0x05, 0xFD, 20, 0x01, 0x02, 0x03, 0x04, 0x01, 0x00, 0x29, 7, 0, 4, 0, 1, 2, 3, 4, 0x03,
+        0x03, 0x07, 8, 1,
+    ]));
+}
+
+#[cfg(not(feature = "bluenrg2"))]
 #[test]
 fn add_characteristic_descriptor_128() {
     let mut sink = RecordingSink::new();
@@ -1484,12 +1524,12 @@ fn read_handle_value() {
     assert!(sink.wrote(&[1, 0x2A, 0xFD, 2, 0x01, 0x02]));
 }
 
-#[cfg(feature = "ms")]
 #[test]
 fn read_handle_value_offset() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRGMS);
         fixture
             .act(|controller| {
                 controller.read_handle_value_offset(CharacteristicHandle(0x0201), 0x3)
@@ -1500,12 +1540,12 @@ fn read_handle_value_offset() {
     assert!(sink.wrote(&[1, 0x2B, 0xFD, 3, 0x01, 0x02, 0x03]));
 }
 
-#[cfg(feature = "ms")]
 #[test]
 fn update_long_characteristic_value() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRGMS);
         fixture
             .act(|controller| {
                 controller.update_long_characteristic_value(
@@ -1527,12 +1567,12 @@ fn update_long_characteristic_value() {
     ]));
 }
 
-#[cfg(feature = "ms")]
 #[test]
 fn update_long_characteristic_value_too_long() {
     let mut sink = RecordingSink::new();
     {
         let mut fixture = Fixture::new(&mut sink);
+        fixture.set_dialect(Dialect::BlueNRGMS);
         let err = fixture
             .act(|controller| {
                 controller.update_long_characteristic_value(
@@ -0,0 +1,436 @@
+#![cfg(feature = "mock")]
+
+extern crate bluenrg;
+extern crate bluetooth_hci as hci;
+extern crate embedded_hal as hal;
+extern crate nb;
+
+mod fixture;
+
+use bluenrg::bring_up::{self, BringUp, BringUpConfig};
+use bluenrg::config_store::{ConfigArea, ConfigFields, ConfigStore};
+use bluenrg::event::command::{GattCharacteristic, GattHandleValue, GattService, HalConfigData};
+use bluenrg::event::{AttributeHandle, BlueNRGEvent};
+use bluenrg::gatt::{
+    AddCharacteristicParameters, AddServiceParameters, CharacteristicEvent, CharacteristicHandle,
+    CharacteristicPermission, CharacteristicProperty, EncryptionKeySize, ServiceHandle,
+    ServiceType, UpdateCharacteristicValueParameters, Uuid,
+};
+use bluenrg::hal::{ConfigData, ConfigParameter, Role};
+use bluenrg::mock::{ChipSelect, DataReady, Mock, ResetPin, Spi};
+use bluenrg::request::{self, Requester};
+use bluenrg::BlueNRG;
+use core::convert::Infallible;
+use fixture::PollCountTimer;
+use hci::event::command::ReturnParameters;
+use hci::{BdAddr, ConnectionHandle, Event};
+
+type MockBlueNRG<'buf, 'm> =
+    BlueNRG<'buf, Spi<'m>, ChipSelect<'m>, ResetPin<'m>, DataReady<'m>, Infallible>;
+type Controller<'bnrg, 'spi, 'buf, 'm> = bluenrg::ActiveBlueNRG<
+    'bnrg,
+    'spi,
+    'buf,
+    Spi<'m>,
+    ChipSelect<'m>,
+    ResetPin<'m>,
+    DataReady<'m>,
+    Infallible,
+>;
+type Error = bluenrg::Error<Infallible, Infallible>;
+
+fn config(config_data: &[ConfigData]) -> BringUpConfig<'_, u32> {
+    BringUpConfig {
+        reset_time: 0,
+        timeout: 10,
+        config_data,
+        gap_role: bluenrg::gap::Role::PERIPHERAL,
+        #[cfg(not(feature = "bluenrg2"))]
+        privacy_enabled: false,
+        #[cfg(feature = "bluenrg2")]
+        privacy: bluenrg::gap::Privacy::Disabled,
+        dev_name_characteristic_len: 8,
+    }
+}
+
+fn new_bluenrg<'buf, 'm>(rx_buffer: &'buf mut [u8], mock: &'m Mock) -> MockBlueNRG<'buf, 'm> {
+    let mut bnrg = BlueNRG::new(
+        rx_buffer,
+        mock.chip_select(),
+        mock.data_ready(),
+        mock.reset_pin(),
+    );
+    bnrg.set_retry_limit(Some(4));
+    bnrg
+}
+
+fn start<'m>(
+    bnrg: &mut MockBlueNRG<'_, 'm>,
+    mock: &'m Mock,
+    config_data: &[ConfigData],
+) -> Result<BringUp, bring_up::BringUpError<Error>> {
+    bnrg.bring_up(
+        &mut mock.spi(),
+        &mut PollCountTimer::new(),
+        &config(config_data),
+    )
+}
+
+fn service_params(uuid: u16, max_attribute_records: usize) -> AddServiceParameters {
+    AddServiceParameters {
+        uuid: Uuid::Uuid16(uuid),
+        service_type: ServiceType::Primary,
+        max_attribute_records,
+    }
+}
+
+fn characteristic(
+    service: ServiceHandle,
+    uuid: u16,
+    properties: CharacteristicProperty,
+) -> AddCharacteristicParameters {
+    AddCharacteristicParameters {
+        service_handle: service,
+        characteristic_uuid: Uuid::Uuid16(uuid),
+        characteristic_value_len: 4,
+        characteristic_properties: properties,
+        security_permissions: CharacteristicPermission::empty(),
+        gatt_event_mask: CharacteristicEvent::ATTRIBUTE_WRITE,
+        encryption_key_size: EncryptionKeySize::with_value(16).unwrap(),
+        is_variable: true,
+        #[cfg(not(feature = "bluenrg2"))]
+        fw_version_before_v72: false,
+    }
+}
+
+fn add_service(
+    requester: &mut Requester<4>,
+    controller: &mut Controller,
+    params: &AddServiceParameters,
+) -> Result<ServiceHandle, request::Error<Error>> {
+    let service: GattService = requester.call(controller, |controller| {
+        bluenrg::gatt::Commands::add_service(controller, params)
+    })?;
+    Ok(service.service_handle)
+}
+
+fn add_characteristic(
+    requester: &mut Requester<4>,
+    controller: &mut Controller,
+    params: &AddCharacteristicParameters,
+) -> Result<CharacteristicHandle, request::Error<Error>> {
+    let characteristic: GattCharacteristic = requester.call(controller, |controller| {
+        bluenrg::gatt::Commands::add_characteristic(controller, params)
+    })?;
+    Ok(characteristic.characteristic_handle)
+}
+
+#[test]
+fn bring_up() {
+    let mock = Mock::new();
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = new_bluenrg(&mut rx_buffer, &mock);
+
+    let public_address = ConfigData::public_address(BdAddr([1, 2, 3, 4, 5, 6])).build();
+    let bring_up = start(&mut bnrg, &mock, &[public_address]).unwrap();
+    assert_eq!(bring_up.version.hw_version, 0x31);
+    assert_eq!(bring_up.version.major, 7);
+    assert_eq!(bring_up.version.minor, 2);
+    assert_eq!(bring_up.gap.service_handle, ServiceHandle(0x0005));
+    assert_eq!(bring_up.gap.dev_name_handle, CharacteristicHandle(0x0006));
+    assert_eq!(bring_up.gap.appearance_handle, CharacteristicHandle(0x0008));
+    assert_eq!(mock.reset_count(), 1);
+    assert_eq!(mock.command_count(), 4);
+    assert_eq!(mock.config_data()[..6], [1, 2, 3, 4, 5, 6]);
+    assert_eq!(
+        mock.attribute_value(AttributeHandle(0x0007), <[u8]>::len),
+        Some(0)
+    );
+    assert!(!mock.has_pending_events());
+}
+
+#[test]
+fn bring_up_command_failed() {
+    let mock = Mock::new();
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = new_bluenrg(&mut rx_buffer, &mock);
+
+    mock.fail_next(
+        hci::Opcode::new(0x3F, 0x101),
+        hci::Status::Vendor(bluenrg::event::Status::Failed),
+    );
+    assert_eq!(
+        start(&mut bnrg, &mock, &[]).err().unwrap(),
+        bring_up::BringUpError {
+            step: bring_up::Step::GattInit,
+            cause: bring_up::Cause::CommandFailed(hci::Status::Vendor(
+                bluenrg::event::Status::Failed
+            )),
+        }
+    );
+
+    // The failed command did not initialize GATT, so GAP cannot be initialized either.
+    let mut requester: Requester<4> = Requester::new();
+    let result: Result<bluenrg::event::command::GapInit, _> =
+        bnrg.with_spi(&mut mock.spi(), |controller| {
+            requester.call(controller, |controller| {
+                bluenrg::gap::Commands::init(
+                    controller,
+                    bluenrg::gap::Role::PERIPHERAL,
+                    #[cfg(not(feature = "bluenrg2"))]
+                    false,
+                    #[cfg(feature = "bluenrg2")]
+                    bluenrg::gap::Privacy::Disabled,
+                    8,
+                )
+            })
+        });
+    assert_eq!(
+        result.err().unwrap(),
+        request::Error::CommandFailed(hci::Status::CommandDisallowed)
+    );
+}
+
+#[test]
+fn gatt_database() {
+    let mock = Mock::new();
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = new_bluenrg(&mut rx_buffer, &mock);
+    start(&mut bnrg, &mock, &[]).unwrap();
+
+    let mut requester: Requester<4> = Requester::new();
+    bnrg.with_spi(&mut mock.spi(), |controller| {
+        // Handles follow the GATT service (1-4) and the GAP service (5-11).
+        let service = add_service(&mut requester, controller, &service_params(0x180F, 6)).unwrap();
+        assert_eq!(service, ServiceHandle(0x000C));
+
+        // A characteristic that notifies also gets a Client Characteristic Configuration
+        // descriptor.
+        let notifying = add_characteristic(
+            &mut requester,
+            controller,
+            &characteristic(service, 0x2A19, CharacteristicProperty::NOTIFY),
+        )
+        .unwrap();
+        assert_eq!(notifying, CharacteristicHandle(0x000D));
+        let plain = add_characteristic(
+            &mut requester,
+            controller,
+            &characteristic(service, 0x2A1A, CharacteristicProperty::READ),
+        )
+        .unwrap();
+        assert_eq!(plain, CharacteristicHandle(0x0010));
+
+        // The service is full.
+        assert_eq!(
+            add_characteristic(
+                &mut requester,
+                controller,
+                &characteristic(service, 0x2A1B, CharacteristicProperty::READ),
+            ),
+            Err(request::Error::CommandFailed(hci::Status::Vendor(
+                bluenrg::event::Status::InsufficientResources
+            )))
+        );
+
+        let next = add_service(&mut requester, controller, &service_params(0x180A, 2)).unwrap();
+        assert_eq!(next, ServiceHandle(0x0012));
+
+        requester
+            .call::<_, _, (), _>(controller, |controller| {
+                bluenrg::gatt::Commands::update_characteristic_value(
+                    controller,
+                    &UpdateCharacteristicValueParameters {
+                        service_handle: service,
+                        characteristic_handle: notifying,
+                        offset: 0,
+                        value: &[0x55, 0x66],
+                    },
+                )
+            })
+            .unwrap();
+        let value: GattHandleValue = requester
+            .call(controller, |controller| {
+                bluenrg::gatt::Commands::read_handle_value(
+                    controller,
+                    CharacteristicHandle(notifying.0 + 1),
+                )
+            })
+            .unwrap();
+        assert_eq!(value.value(), [0x55, 0x66]);
+
+        assert_eq!(
+            requester
+                .call::<_, _, GattHandleValue, _>(controller, |controller| {
+                    bluenrg::gatt::Commands::read_handle_value(
+                        controller,
+                        CharacteristicHandle(0x0100),
+                    )
+                })
+                .err()
+                .unwrap(),
+            request::Error::CommandFailed(hci::Status::Vendor(
+                bluenrg::event::Status::InvalidHandle
+            ))
+        );
+    });
+
+    assert_eq!(
+        mock.attribute_value(AttributeHandle(0x000E), <[u8]>::to_vec),
+        Some(vec![0x55, 0x66])
+    );
+    assert_eq!(
+        mock.attribute_value(AttributeHandle(0x000F), <[u8]>::to_vec),
+        Some(vec![0, 0])
+    );
+}
+
+#[test]
+fn peer_writes_attribute() {
+    let mock = Mock::new();
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = new_bluenrg(&mut rx_buffer, &mock);
+    let bring_up = start(&mut bnrg, &mock, &[]).unwrap();
+
+    let dev_name = AttributeHandle(bring_up.gap.dev_name_handle.0 + 1);
+    mock.write_attribute(ConnectionHandle(0x0801), dev_name, b"mock");
+    assert!(mock.has_pending_events());
+    assert_eq!(
+        mock.attribute_value(dev_name, <[u8]>::to_vec),
+        Some(b"mock".to_vec())
+    );
+
+    let mut requester: Requester<4> = Requester::new();
+    let event = bnrg.with_spi(&mut mock.spi(), |controller| {
+        nb::block!(requester.read(controller)).unwrap()
+    });
+    match event {
+        Event::Vendor(BlueNRGEvent::GattAttributeModified(modified)) => {
+            assert_eq!(modified.conn_handle, ConnectionHandle(0x0801));
+            assert_eq!(modified.attr_handle, dev_name);
+            assert_eq!(modified.data(), b"mock");
+        }
+        other => panic!("Did not get attribute modified: {:?}", other),
+    }
+}
+
+#[test]
+fn config_store() {
+    let mock = Mock::new();
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = new_bluenrg(&mut rx_buffer, &mock);
+    start(&mut bnrg, &mock, &[]).unwrap();
+
+    let desired = ConfigArea {
+        public_address: Some(BdAddr([6, 5, 4, 3, 2, 1])),
+        role: Some(Role::Peripheral12Kb),
+        ..ConfigArea::default()
+    };
+    let mut requester: Requester<4> = Requester::new();
+    let (written, read_back) = bnrg.with_spi(&mut mock.spi(), |controller| {
+        let mut store = ConfigStore::new(&mut requester);
+        let written = store.apply(controller, &desired).unwrap();
+        (written, store.read(controller).unwrap())
+    });
+    assert_eq!(written, ConfigFields::PUBLIC_ADDRESS | ConfigFields::ROLE);
+    assert_eq!(read_back.public_address, desired.public_address);
+    assert_eq!(read_back.role, desired.role);
+    assert_eq!(mock.config_data()[41], Role::Peripheral12Kb as u8);
+}
+
+#[test]
+fn read_config_data() {
+    let mock = Mock::new();
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = new_bluenrg(&mut rx_buffer, &mock);
+    start(&mut bnrg, &mock, &[ConfigData::diversifier(0x1234).build()]).unwrap();
+
+    let mut requester: Requester<4> = Requester::new();
+    let data: HalConfigData = bnrg.with_spi(&mut mock.spi(), |controller| {
+        requester
+            .call(controller, |controller| {
+                bluenrg::hal::Commands::read_config_data(controller, ConfigParameter::Diversifier)
+            })
+            .unwrap()
+    });
+    assert_eq!(
+        data.value,
+        bluenrg::event::command::HalConfigParameter::Diversifier(0x1234)
+    );
+}
+
+#[test]
+fn read_config_data_past_documented_area() {
+    let mock = Mock::new();
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = new_bluenrg(&mut rx_buffer, &mock);
+    start(&mut bnrg, &mock, &[]).unwrap();
+
+    let mut requester: Requester<4> = Requester::new();
+    let result: Result<HalConfigData, _> = bnrg.with_spi(&mut mock.spi(), |controller| {
+        requester.call(controller, |controller| {
+            // Read Config Data at offset 42, which is not a documented parameter.
+            hci::Controller::write(controller, &[0x01, 0x0D, 0xFC, 1], &[42])
+        })
+    });
+    assert_eq!(
+        result.err(),
+        Some(request::Error::CommandFailed(hci::Status::Vendor(
+            bluenrg::event::Status::InvalidParameters
+        )))
+    );
+}
+
+#[test]
+fn unmodeled_command_succeeds() {
+    let mock = Mock::new();
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = new_bluenrg(&mut rx_buffer, &mock);
+    start(&mut bnrg, &mock, &[]).unwrap();
+
+    let mut requester: Requester<4> = Requester::new();
+    let params: ReturnParameters<BlueNRGEvent> = bnrg.with_spi(&mut mock.spi(), |controller| {
+        requester
+            .call(controller, |controller| {
+                bluenrg::hal::Commands::device_standby(controller)
+            })
+            .unwrap()
+    });
+    assert!(matches!(
+        params,
+        ReturnParameters::Vendor(bluenrg::event::command::ReturnParameters::HalDeviceStandby(
+            hci::Status::Success
+        ))
+    ));
+    assert_eq!(mock.last_opcode(), Some(hci::Opcode::new(0x3F, 0x013)));
+}
+
+#[test]
+fn held_in_reset() {
+    let mock = Mock::new();
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = new_bluenrg(&mut rx_buffer, &mock);
+
+    // The mock starts with a HAL Initialized event.
+    let mut requester: Requester<4> = Requester::new();
+    let event = bnrg.with_spi(&mut mock.spi(), |controller| {
+        nb::block!(requester.read(controller)).unwrap()
+    });
+    assert!(matches!(
+        event,
+        Event::Vendor(BlueNRGEvent::HalInitialized(
+            bluenrg::event::ResetReason::Normal
+        ))
+    ));
+    assert!(!mock.has_pending_events());
+
+    mock.push_event(&[0x04, 0x10, 0x01, 0x01]);
+    hal::digital::v2::OutputPin::set_low(&mut mock.reset_pin()).unwrap();
+    assert!(!mock.has_pending_events());
+
+    let result = bnrg.with_spi(&mut mock.spi(), |controller| {
+        nb::block!(bluenrg::hal::Commands::get_firmware_revision(controller))
+    });
+    assert_eq!(result, Err(bluenrg::Error::Timeout));
+    assert_eq!(mock.command_count(), 0);
+}
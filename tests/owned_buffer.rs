@@ -0,0 +1,88 @@
+extern crate bluenrg;
+extern crate bluetooth_hci as hci;
+extern crate embedded_hal as hal;
+extern crate nb;
+
+mod fixture;
+
+use bluenrg::hal::Commands;
+use bluenrg::BlueNRG;
+use fixture::{DummyPin, NeverError, ScriptedSink};
+use hci::host::uart::{Hci, Packet};
+use hci::Event;
+
+/// An application struct that holds the BlueNRG without any lifetime parameters.
+struct App {
+    bnrg: BlueNRG<'static, ScriptedSink, DummyPin, DummyPin, DummyPin, NeverError, [u8; 512]>,
+}
+
+impl App {
+    fn new() -> App {
+        App {
+            bnrg: BlueNRG::new_owned([0; 512], DummyPin, DummyPin, DummyPin),
+        }
+    }
+}
+
+#[test]
+fn write_command() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(4);
+    let mut app = App::new();
+    app.bnrg.with_spi(&mut sink, |controller| {
+        controller.get_firmware_revision().unwrap();
+    });
+    assert_eq!(sink.sent[5..], [0x01, 0x00, 0xFC, 0x00]);
+}
+
+#[test]
+fn read_event() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.event(&[0x04, 0x0F, 0x04, 0x00, 0x01, 0x81, 0xFC]);
+    let mut app = App::new();
+    let packet = app.bnrg.with_spi(&mut sink, |controller| {
+        Hci::<_, bluenrg::event::BlueNRGEvent, bluenrg::event::BlueNRGError>::read(controller)
+    });
+    match packet {
+        Ok(Packet::Event(Event::CommandStatus(status))) => {
+            assert_eq!(status.opcode, hci::Opcode(0xFC81));
+        }
+        other => panic!("Did not get command status: {:?}", other),
+    }
+}
+
+#[test]
+fn minimum_length() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(4);
+    let mut bnrg: BlueNRG<ScriptedSink, _, _, _, NeverError, _> =
+        BlueNRG::new_owned([0; 259], DummyPin, DummyPin, DummyPin);
+    bnrg.with_spi(&mut sink, |controller| {
+        controller.get_firmware_revision().unwrap();
+    });
+}
+
+#[test]
+fn minimum_length_holds_longest_event() {
+    // A GATT notification with 255 bytes of parameters: the event code, connection handle, data
+    // length, attribute handle and a 248-byte value.
+    let mut packet = vec![0x04, 0xFF, 0xFF, 0x0F, 0x0C, 0x01, 0x08, 250, 0x03, 0x02];
+    packet.extend((0..248).map(|i| i as u8));
+    assert_eq!(packet.len(), 258);
+
+    let mut sink = ScriptedSink::new(0x00);
+    sink.event(&packet);
+    let mut bnrg: BlueNRG<ScriptedSink, _, _, _, NeverError, _> =
+        BlueNRG::new_owned([0; 259], DummyPin, DummyPin, DummyPin);
+    let packet = bnrg.with_spi(&mut sink, |controller| {
+        Hci::<_, bluenrg::event::BlueNRGEvent, bluenrg::event::BlueNRGError>::read(controller)
+    });
+    match packet {
+        Ok(Packet::Event(Event::Vendor(bluenrg::event::BlueNRGEvent::GattNotification(
+            notification,
+        )))) => {
+            assert_eq!(notification.value().len(), 248);
+        }
+        other => panic!("Did not get GATT notification: {:?}", other),
+    }
+}
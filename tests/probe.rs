@@ -0,0 +1,159 @@
+extern crate bluenrg;
+extern crate bluetooth_hci as hci;
+extern crate embedded_hal as hal;
+extern crate nb;
+
+mod fixture;
+
+use bluenrg::probe::*;
+use bluenrg::BlueNRG;
+use fixture::{DummyPin, PollCountTimer, ScriptedSink};
+
+const READY: [u8; 5] = [0x02, 0xFF, 0x00, 0x00, 0x00];
+
+fn probe(sink: &mut ScriptedSink) -> ProbeReport {
+    let mut timer = PollCountTimer::new();
+    let mut rx_buffer = [0; 16];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.probe(sink, &mut timer, 10).unwrap()
+}
+
+#[test]
+fn not_present_high() {
+    let mut sink = ScriptedSink::new(0xFF);
+    let report = probe(&mut sink);
+    assert_eq!(report.presence, Presence::NotPresent(MisoLevel::High));
+    assert_eq!(report.header, [0xFF; 5]);
+    assert_eq!(report.header_attempts, 16);
+    assert_eq!(report.loopback, None);
+    assert!(!report.passed());
+}
+
+#[test]
+fn not_present_low() {
+    let mut sink = ScriptedSink::new(0x00);
+    let report = probe(&mut sink);
+    assert_eq!(report.presence, Presence::NotPresent(MisoLevel::Low));
+    assert_eq!(report.loopback, None);
+}
+
+#[test]
+fn not_present_unstable() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&[0xFF; 5]);
+    let report = probe(&mut sink);
+    assert_eq!(report.presence, Presence::NotPresent(MisoLevel::Unstable));
+}
+
+#[test]
+fn asleep() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&[0x00, 0x00, 0x00, 0x00, 0x00])
+        .reply(&[0x00, 0xFF, 0xFF, 0x00, 0x00]);
+    let report = probe(&mut sink);
+    assert_eq!(report.presence, Presence::Asleep);
+    assert_eq!(report.loopback, None);
+}
+
+#[test]
+fn loopback_passed() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&[0x00; 5])
+        .reply(&READY)
+        // Write the command
+        .reply(&READY)
+        .reply(&[0x00; 4])
+        // Read an unrelated event, then the command complete event
+        .reply(&[0x02, 0x00, 0x00, 15, 0x00])
+        .reply(&[0x04, 0xFF, 0x03, 0x01, 0x00, 0x01])
+        .reply(&[0x04, 0x0E, 0x06, 0x01, 0x00, 0xFC, 0x00, 0x34, 0x12]);
+    let report = probe(&mut sink);
+    assert_eq!(
+        report.presence,
+        Presence::Ready {
+            write_len: 0xFF,
+            read_len: 0
+        }
+    );
+    assert_eq!(report.header, READY);
+    assert_eq!(report.header_attempts, 2);
+    assert_eq!(report.loopback, Some(Loopback::Passed { revision: 0x1234 }));
+    assert_eq!(report.skipped_events, 1);
+    assert!(report.passed());
+    assert_eq!(sink.sent[15..19], [0x01, 0x00, 0xFC, 0x00]);
+}
+
+#[test]
+fn loopback_command_failed() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&READY)
+        .reply(&READY)
+        .reply(&[0x00; 4])
+        .reply(&[0x02, 0x00, 0x00, 9, 0x00])
+        .reply(&[0x04, 0x0E, 0x06, 0x01, 0x00, 0xFC, 0x12, 0x00, 0x00]);
+    let report = probe(&mut sink);
+    assert_eq!(
+        report.loopback,
+        Some(Loopback::CommandFailed(hci::Status::InvalidParameters))
+    );
+}
+
+#[test]
+fn loopback_write_timeout() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&READY);
+    for _ in 0..11 {
+        sink.reply(&[0x02, 0x00, 0x00, 0x00, 0x00]);
+    }
+    let report = probe(&mut sink);
+    assert_eq!(report.loopback, Some(Loopback::WriteTimeout));
+}
+
+#[test]
+fn loopback_write_timeout_not_ready() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&READY);
+
+    // The controller never becomes ready to accept the command.
+    let report = probe(&mut sink);
+    assert_eq!(report.loopback, Some(Loopback::WriteTimeout));
+}
+
+#[test]
+fn loopback_no_command_credits() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(4).reply(&READY);
+    let mut timer = PollCountTimer::new();
+    let mut rx_buffer = [0; 16];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+
+    // Use up the only credit without reading the command complete event.
+    bnrg.with_spi(&mut sink, |controller| {
+        bluenrg::hal::Commands::get_firmware_revision(controller).unwrap()
+    });
+    let report = bnrg.probe(&mut sink, &mut timer, 10).unwrap();
+    assert_eq!(report.loopback, Some(Loopback::NoCommandCredits));
+    assert_eq!(timer.starts, 0);
+}
+
+#[test]
+fn loopback_read_timeout() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&READY).reply(&READY).reply(&[0x00; 4]);
+    for _ in 0..11 {
+        sink.reply(&[0x02, 0x00, 0x00, 0x00, 0x00]);
+    }
+    let report = probe(&mut sink);
+    assert_eq!(report.loopback, Some(Loopback::ReadTimeout));
+}
+
+#[test]
+fn loopback_bad_packet_type() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&READY)
+        .reply(&READY)
+        .reply(&[0x00; 4])
+        .reply(&[0x02, 0x00, 0x00, 1, 0x00, 0x03]);
+    let report = probe(&mut sink);
+    assert_eq!(report.loopback, Some(Loopback::BadPacketType(0x03)));
+}
@@ -0,0 +1,194 @@
+extern crate bluenrg;
+extern crate bluetooth_hci as hci;
+extern crate embedded_hal as hal;
+extern crate nb;
+
+mod fixture;
+
+use bluenrg::dialect::Dialect;
+use bluenrg::queue::*;
+use bluenrg::BlueNRG;
+use fixture::{DummyPin, ScriptedSink};
+use hci::Event;
+
+const COMMAND_COMPLETE: [u8; 9] = [0x04, 0x0E, 0x06, 0x02, 0x00, 0xFC, 0x00, 0x34, 0x12];
+const COMMAND_STATUS: [u8; 7] = [0x04, 0x0F, 0x04, 0x00, 0x01, 0x81, 0xFC];
+
+/// SPI header for a read when the controller has no more data.
+const NO_DATA: [u8; 5] = [0x02, 0x00, 0x00, 0x00, 0x00];
+
+fn header(read_len: u8) -> [u8; 5] {
+    [0x02, 0x00, 0x00, read_len, 0x00]
+}
+
+fn assert_command_complete<const N: usize>(consumer: &mut Consumer<N>) {
+    match consumer.pop(Dialect::default()) {
+        Some(Ok(Event::CommandComplete(event))) => {
+            assert_eq!(event.num_hci_command_packets, 2);
+        }
+        other => panic!("Did not get command complete: {:?}", other),
+    }
+}
+
+fn assert_command_status<const N: usize>(consumer: &mut Consumer<N>) {
+    match consumer.pop(Dialect::default()) {
+        Some(Ok(Event::CommandStatus(event))) => {
+            assert_eq!(event.opcode, hci::Opcode(0xFC81));
+        }
+        other => panic!("Did not get command status: {:?}", other),
+    }
+}
+
+#[test]
+fn empty() {
+    let mut queue = EventQueue::<64>::new();
+    let (_, mut consumer) = queue.split();
+    assert!(consumer.is_empty());
+    assert!(consumer.pop(Dialect::default()).is_none());
+}
+
+#[test]
+fn drain_all_events() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&header(16))
+        .reply(&COMMAND_COMPLETE)
+        .reply(&COMMAND_STATUS)
+        .reply(&NO_DATA);
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    let mut queue = EventQueue::<64>::new();
+    let (mut producer, mut consumer) = queue.split();
+
+    assert_eq!(bnrg.drain_events(&mut sink, &mut producer), Ok(2));
+    assert_eq!(bnrg.command_credits(), 1);
+
+    assert_command_complete(&mut consumer);
+    assert_command_status(&mut consumer);
+    assert!(consumer.pop(Dialect::default()).is_none());
+}
+
+#[test]
+fn partial_event_waits_for_rest() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&header(12))
+        .reply(&COMMAND_COMPLETE)
+        .reply(&COMMAND_STATUS[..3])
+        .reply(&NO_DATA)
+        .reply(&header(4))
+        .reply(&COMMAND_STATUS[3..])
+        .reply(&NO_DATA);
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    let mut queue = EventQueue::<64>::new();
+    let (mut producer, mut consumer) = queue.split();
+
+    assert_eq!(bnrg.drain_events(&mut sink, &mut producer), Ok(1));
+    assert_command_complete(&mut consumer);
+    assert!(consumer.is_empty());
+
+    assert_eq!(bnrg.drain_events(&mut sink, &mut producer), Ok(1));
+    assert_command_status(&mut consumer);
+}
+
+#[test]
+fn queue_full() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&header(16))
+        .reply(&COMMAND_COMPLETE)
+        .reply(&COMMAND_STATUS)
+        .reply(&NO_DATA);
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+
+    // Holds 15 bytes, so only the first event fits.
+    let mut queue = EventQueue::<16>::new();
+    let (mut producer, mut consumer) = queue.split();
+
+    assert_eq!(
+        bnrg.drain_events(&mut sink, &mut producer),
+        Err(DrainError::QueueFull)
+    );
+    assert_command_complete(&mut consumer);
+
+    // The second event was kept in the RX buffer.
+    assert_eq!(bnrg.drain_events(&mut sink, &mut producer), Ok(1));
+    assert_command_status(&mut consumer);
+    assert!(consumer.is_empty());
+}
+
+#[test]
+fn bad_packet_type() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&header(3)).reply(&[0x02, 0x00, 0x00]);
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    let mut queue = EventQueue::<64>::new();
+    let (mut producer, consumer) = queue.split();
+
+    assert_eq!(
+        bnrg.drain_events(&mut sink, &mut producer),
+        Err(DrainError::BadPacketType(0x02))
+    );
+    assert!(consumer.is_empty());
+}
+
+#[test]
+fn wrap_around() {
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    let mut queue = EventQueue::<20>::new();
+    let (mut producer, mut consumer) = queue.split();
+
+    for _ in 0..10 {
+        let mut sink = ScriptedSink::new(0x00);
+        sink.reply(&header(16))
+            .reply(&COMMAND_COMPLETE)
+            .reply(&COMMAND_STATUS)
+            .reply(&NO_DATA);
+        assert_eq!(bnrg.drain_events(&mut sink, &mut producer), Ok(2));
+        assert_command_complete(&mut consumer);
+        assert_command_status(&mut consumer);
+        assert!(consumer.is_empty());
+    }
+}
+
+#[test]
+fn producer_and_consumer_on_different_threads() {
+    const EVENTS: usize = 100;
+    let mut queue = EventQueue::<32>::new();
+    let (mut producer, mut consumer) = queue.split();
+
+    std::thread::scope(|scope| {
+        scope.spawn(move || {
+            let mut rx_buffer = [0; 64];
+            let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+            let mut queued = 0;
+            let mut sink = ScriptedSink::new(0x00);
+            while queued < EVENTS {
+                sink.reply(&header(COMMAND_STATUS.len() as u8))
+                    .reply(&COMMAND_STATUS)
+                    .reply(&NO_DATA);
+                loop {
+                    match bnrg.drain_events(&mut sink, &mut producer) {
+                        Ok(n) => {
+                            queued += n;
+                            break;
+                        }
+                        Err(DrainError::QueueFull) => std::thread::yield_now(),
+                        Err(e) => panic!("{:?}", e),
+                    }
+                }
+            }
+        });
+
+        let mut popped = 0;
+        while popped < EVENTS {
+            if consumer.is_empty() {
+                std::thread::yield_now();
+                continue;
+            }
+            assert_command_status(&mut consumer);
+            popped += 1;
+        }
+    });
+}
@@ -0,0 +1,757 @@
+#![cfg(feature = "mock")]
+
+extern crate bluenrg;
+extern crate bluetooth_hci as hci;
+extern crate embedded_hal as hal;
+extern crate nb;
+
+mod fixture;
+
+use bluenrg::bring_up::BringUpConfig;
+use bluenrg::event::command::{GattCharacteristic, GattService};
+use bluenrg::event::{
+    AttError, AttRequest, AttributeHandle, BlueNRGEvent, GapProcedure, GapProcedureStatus,
+    GattProcedureStatus,
+};
+use bluenrg::gap::{
+    ConnectionParameters, DiscoverableParameters, DiscoveryProcedureParameters, LocalName,
+    Procedure,
+};
+use bluenrg::gatt::{
+    AddCharacteristicParameters, AddServiceParameters, CharacteristicEvent, CharacteristicHandle,
+    CharacteristicPermission, CharacteristicProperty, CharacteristicValue, EncryptionKeySize,
+    ServiceHandle, ServiceType, UpdateCharacteristicValueParameters, Uuid,
+};
+use bluenrg::hal::ConfigData;
+use bluenrg::mock::{ChipSelect, DataReady, Mock, Radio, ResetPin, Spi};
+use bluenrg::request::{self, Requester};
+use bluenrg::BlueNRG;
+use core::convert::Infallible;
+use core::time::Duration;
+use fixture::PollCountTimer;
+use hci::event::ConnectionRole;
+use hci::host::{AdvertisingFilterPolicy, OwnAddressType, PeerAddrType};
+use hci::types::{
+    AdvertisingType, ConnectionIntervalBuilder, ExpectedConnectionLength, ScanWindow,
+};
+use hci::{BdAddr, BdAddrType, ConnectionHandle, Event};
+
+type MockBlueNRG<'buf, 'm> =
+    BlueNRG<'buf, Spi<'m>, ChipSelect<'m>, ResetPin<'m>, DataReady<'m>, Infallible>;
+type Controller<'bnrg, 'spi, 'buf, 'm> = bluenrg::ActiveBlueNRG<
+    'bnrg,
+    'spi,
+    'buf,
+    Spi<'m>,
+    ChipSelect<'m>,
+    ResetPin<'m>,
+    DataReady<'m>,
+    Infallible,
+>;
+type Error = bluenrg::Error<Infallible, Infallible>;
+
+const PERIPHERAL_ADDRESS: BdAddr = BdAddr([1, 2, 3, 4, 5, 6]);
+const CENTRAL_ADDRESS: BdAddr = BdAddr([6, 5, 4, 3, 2, 1]);
+const CONN_HANDLE: ConnectionHandle = ConnectionHandle(0x0801);
+
+fn start<'buf, 'm>(
+    rx_buffer: &'buf mut [u8],
+    mock: &'m Mock,
+    address: BdAddr,
+    gap_role: bluenrg::gap::Role,
+) -> MockBlueNRG<'buf, 'm> {
+    let mut bnrg = BlueNRG::new(
+        rx_buffer,
+        mock.chip_select(),
+        mock.data_ready(),
+        mock.reset_pin(),
+    );
+    bnrg.set_retry_limit(Some(4));
+    bnrg.bring_up(
+        &mut mock.spi(),
+        &mut PollCountTimer::new(),
+        &BringUpConfig {
+            reset_time: 0,
+            timeout: 10,
+            config_data: &[ConfigData::public_address(address).build()],
+            gap_role,
+            #[cfg(not(feature = "bluenrg2"))]
+            privacy_enabled: false,
+            #[cfg(feature = "bluenrg2")]
+            privacy: bluenrg::gap::Privacy::Disabled,
+            dev_name_characteristic_len: 8,
+        },
+    )
+    .unwrap();
+    bnrg
+}
+
+/// Returns the next event for the host, if there is one.
+fn event(requester: &mut Requester<4>, controller: &mut Controller) -> Option<Event<BlueNRGEvent>> {
+    match requester.read(controller) {
+        Ok(event) => Some(event),
+        Err(nb::Error::WouldBlock) => None,
+        Err(nb::Error::Other(e)) => panic!("Could not read event: {:?}", e),
+    }
+}
+
+fn scan_window() -> ScanWindow {
+    ScanWindow::start_every(Duration::from_millis(10))
+        .unwrap()
+        .open_for(Duration::from_millis(10))
+        .unwrap()
+}
+
+fn advertise(
+    requester: &mut Requester<4>,
+    controller: &mut Controller,
+    advertising_type: AdvertisingType,
+) {
+    requester
+        .call::<_, _, (), _>(controller, |controller| {
+            bluenrg::gap::Commands::set_discoverable(
+                controller,
+                &DiscoverableParameters {
+                    advertising_type,
+                    advertising_interval: None,
+                    address_type: OwnAddressType::Public,
+                    filter_policy: AdvertisingFilterPolicy::AllowConnectionAndScan,
+                    local_name: Some(LocalName::Complete(b"mock")),
+                    advertising_data: &[0x03, 0x03, 0x0F, 0x18],
+                    conn_interval: (None, None),
+                },
+            )
+        })
+        .unwrap();
+}
+
+fn create_connection(
+    requester: &mut Requester<4>,
+    controller: &mut Controller,
+    peer: BdAddr,
+) -> Result<(), request::Error<Error>> {
+    requester.call(controller, |controller| {
+        bluenrg::gap::Commands::create_connection(
+            controller,
+            &ConnectionParameters {
+                scan_window: scan_window(),
+                peer_address: PeerAddrType::PublicDeviceAddress(peer),
+                own_address_type: OwnAddressType::Public,
+                conn_interval: ConnectionIntervalBuilder::new()
+                    .with_range(Duration::from_millis(50), Duration::from_millis(100))
+                    .with_latency(0)
+                    .with_supervision_timeout(Duration::from_millis(4000))
+                    .build()
+                    .unwrap(),
+                expected_connection_length: ExpectedConnectionLength::new(
+                    Duration::from_millis(10),
+                    Duration::from_millis(20),
+                )
+                .unwrap(),
+            },
+        )
+    })
+}
+
+fn expect_connection(
+    requester: &mut Requester<4>,
+    controller: &mut Controller,
+    role: ConnectionRole,
+    peer: BdAddr,
+) {
+    match event(requester, controller) {
+        Some(Event::LeConnectionComplete(complete)) => {
+            assert_eq!(complete.status, hci::Status::Success);
+            assert_eq!(complete.conn_handle, CONN_HANDLE);
+            assert_eq!(complete.role, role);
+            assert_eq!(complete.peer_bd_addr, BdAddrType::Public(peer));
+            assert_eq!(complete.conn_interval.interval(), Duration::from_millis(50));
+        }
+        other => panic!("Did not get connection complete: {:?}", other),
+    }
+}
+
+fn expect_disconnection(
+    requester: &mut Requester<4>,
+    controller: &mut Controller,
+    reason: hci::Status<bluenrg::event::Status>,
+) {
+    match event(requester, controller) {
+        Some(Event::DisconnectionComplete(complete)) => {
+            assert_eq!(complete.status, hci::Status::Success);
+            assert_eq!(complete.conn_handle, CONN_HANDLE);
+            assert_eq!(complete.reason, reason);
+        }
+        other => panic!("Did not get disconnection complete: {:?}", other),
+    }
+}
+
+fn expect_gatt_procedure_complete(
+    requester: &mut Requester<4>,
+    controller: &mut Controller,
+    status: GattProcedureStatus,
+) {
+    match event(requester, controller) {
+        Some(Event::Vendor(BlueNRGEvent::GattProcedureComplete(complete))) => {
+            assert_eq!(complete.conn_handle, CONN_HANDLE);
+            assert_eq!(complete.status, status);
+        }
+        other => panic!("Did not get GATT procedure complete: {:?}", other),
+    }
+}
+
+/// Connects the central to the peripheral, and reads the connection events on both sides.
+fn connect(
+    radio: &Radio,
+    peripheral_requester: &mut Requester<4>,
+    peripheral_controller: &mut Controller,
+    central_requester: &mut Requester<4>,
+    central_controller: &mut Controller,
+) {
+    advertise(
+        peripheral_requester,
+        peripheral_controller,
+        AdvertisingType::ConnectableUndirected,
+    );
+    create_connection(central_requester, central_controller, PERIPHERAL_ADDRESS).unwrap();
+    assert!(radio.run());
+
+    expect_connection(
+        central_requester,
+        central_controller,
+        ConnectionRole::Central,
+        PERIPHERAL_ADDRESS,
+    );
+    expect_connection(
+        peripheral_requester,
+        peripheral_controller,
+        ConnectionRole::Peripheral,
+        CENTRAL_ADDRESS,
+    );
+}
+
+/// Adds a battery service with a level characteristic that can be read, written and notified.
+/// Returns the service and the characteristic.
+fn add_battery_service(
+    requester: &mut Requester<4>,
+    controller: &mut Controller,
+) -> (ServiceHandle, CharacteristicHandle) {
+    let service: GattService = requester
+        .call(controller, |controller| {
+            bluenrg::gatt::Commands::add_service(
+                controller,
+                &AddServiceParameters {
+                    uuid: Uuid::Uuid16(0x180F),
+                    service_type: ServiceType::Primary,
+                    max_attribute_records: 4,
+                },
+            )
+        })
+        .unwrap();
+    let characteristic: GattCharacteristic = requester
+        .call(controller, |controller| {
+            bluenrg::gatt::Commands::add_characteristic(
+                controller,
+                &AddCharacteristicParameters {
+                    service_handle: service.service_handle,
+                    characteristic_uuid: Uuid::Uuid16(0x2A19),
+                    characteristic_value_len: 4,
+                    characteristic_properties: CharacteristicProperty::READ
+                        | CharacteristicProperty::WRITE
+                        | CharacteristicProperty::NOTIFY,
+                    security_permissions: CharacteristicPermission::empty(),
+                    gatt_event_mask: CharacteristicEvent::ATTRIBUTE_WRITE,
+                    encryption_key_size: EncryptionKeySize::with_value(16).unwrap(),
+                    is_variable: true,
+                    #[cfg(not(feature = "bluenrg2"))]
+                    fw_version_before_v72: false,
+                },
+            )
+        })
+        .unwrap();
+
+    (service.service_handle, characteristic.characteristic_handle)
+}
+
+fn update_value(
+    requester: &mut Requester<4>,
+    controller: &mut Controller,
+    service: ServiceHandle,
+    characteristic: CharacteristicHandle,
+    value: &[u8],
+) {
+    requester
+        .call::<_, _, (), _>(controller, |controller| {
+            bluenrg::gatt::Commands::update_characteristic_value(
+                controller,
+                &UpdateCharacteristicValueParameters {
+                    service_handle: service,
+                    characteristic_handle: characteristic,
+                    offset: 0,
+                    value,
+                },
+            )
+        })
+        .unwrap();
+}
+
+fn read_value(requester: &mut Requester<4>, controller: &mut Controller, handle: u16) {
+    requester
+        .call::<_, _, (), _>(controller, |controller| {
+            bluenrg::gatt::Commands::read_characteristic_value(
+                controller,
+                CONN_HANDLE,
+                CharacteristicHandle(handle),
+            )
+        })
+        .unwrap();
+}
+
+fn write_value(
+    requester: &mut Requester<4>,
+    controller: &mut Controller,
+    handle: u16,
+    value: &[u8],
+) {
+    requester
+        .call::<_, _, (), _>(controller, |controller| {
+            bluenrg::gatt::Commands::write_characteristic_value(
+                controller,
+                &CharacteristicValue {
+                    conn_handle: CONN_HANDLE,
+                    characteristic_handle: CharacteristicHandle(handle),
+                    value,
+                },
+            )
+        })
+        .unwrap();
+}
+
+#[test]
+fn discover_and_connect() {
+    let peripheral = Mock::new();
+    let central = Mock::new();
+    let radio = Radio::new(&peripheral, &central);
+    let mut peripheral_rx = [0; 128];
+    let mut central_rx = [0; 128];
+    let mut peripheral_bnrg = start(
+        &mut peripheral_rx,
+        &peripheral,
+        PERIPHERAL_ADDRESS,
+        bluenrg::gap::Role::PERIPHERAL,
+    );
+    let mut central_bnrg = start(
+        &mut central_rx,
+        &central,
+        CENTRAL_ADDRESS,
+        bluenrg::gap::Role::CENTRAL,
+    );
+    let mut peripheral_requester: Requester<4> = Requester::new();
+    let mut central_requester: Requester<4> = Requester::new();
+
+    peripheral_bnrg.with_spi(&mut peripheral.spi(), |peripheral_controller| {
+        central_bnrg.with_spi(&mut central.spi(), |central_controller| {
+            // Nothing is on air yet.
+            assert!(!radio.run());
+
+            advertise(
+                &mut peripheral_requester,
+                peripheral_controller,
+                AdvertisingType::ConnectableUndirected,
+            );
+            central_requester
+                .call::<_, _, (), _>(central_controller, |controller| {
+                    bluenrg::gap::Commands::start_general_discovery_procedure(
+                        controller,
+                        &DiscoveryProcedureParameters {
+                            scan_window: scan_window(),
+                            own_address_type: OwnAddressType::Public,
+                            filter_duplicates: true,
+                        },
+                    )
+                })
+                .unwrap();
+            assert!(radio.run());
+
+            match event(&mut central_requester, central_controller) {
+                Some(Event::Vendor(BlueNRGEvent::GapDeviceFound(found))) => {
+                    assert_eq!(
+                        found.event,
+                        bluenrg::event::GapDeviceFoundEvent::Advertisement
+                    );
+                    assert_eq!(found.bdaddr, BdAddrType::Public(PERIPHERAL_ADDRESS));
+                    assert_eq!(
+                        found.data(),
+                        [5, 0x09, b'm', b'o', b'c', b'k', 0x03, 0x03, 0x0F, 0x18]
+                    );
+                    assert_eq!(found.rssi, Some(-50));
+                }
+                other => panic!("Did not get device found: {:?}", other),
+            }
+            assert!(event(&mut central_requester, central_controller).is_none());
+
+            // The peripheral is reported once per discovery procedure.
+            assert!(!radio.run());
+
+            central_requester
+                .call::<_, _, (), _>(central_controller, |controller| {
+                    bluenrg::gap::Commands::terminate_procedure(
+                        controller,
+                        Procedure::GENERAL_DISCOVERY,
+                    )
+                })
+                .unwrap();
+            match event(&mut central_requester, central_controller) {
+                Some(Event::Vendor(BlueNRGEvent::GapProcedureComplete(complete))) => {
+                    assert!(matches!(complete.procedure, GapProcedure::GeneralDiscovery));
+                    assert_eq!(complete.status, GapProcedureStatus::Success);
+                }
+                other => panic!("Did not get procedure complete: {:?}", other),
+            }
+
+            create_connection(
+                &mut central_requester,
+                central_controller,
+                PERIPHERAL_ADDRESS,
+            )
+            .unwrap();
+            assert!(radio.run());
+            expect_connection(
+                &mut central_requester,
+                central_controller,
+                ConnectionRole::Central,
+                PERIPHERAL_ADDRESS,
+            );
+            expect_connection(
+                &mut peripheral_requester,
+                peripheral_controller,
+                ConnectionRole::Peripheral,
+                CENTRAL_ADDRESS,
+            );
+
+            // Only one connection at a time.
+            assert_eq!(
+                create_connection(
+                    &mut central_requester,
+                    central_controller,
+                    PERIPHERAL_ADDRESS
+                ),
+                Err(request::Error::CommandFailed(
+                    hci::Status::CommandDisallowed
+                ))
+            );
+
+            central_requester
+                .call::<_, _, (), _>(central_controller, |controller| {
+                    bluenrg::gap::Commands::terminate(
+                        controller,
+                        CONN_HANDLE,
+                        hci::Status::RemoteTerminationByUser,
+                    )
+                })
+                .unwrap();
+            assert!(radio.run());
+            expect_disconnection(
+                &mut central_requester,
+                central_controller,
+                hci::Status::ConnectionTerminatedByHost,
+            );
+            expect_disconnection(
+                &mut peripheral_requester,
+                peripheral_controller,
+                hci::Status::RemoteTerminationByUser,
+            );
+        })
+    });
+}
+
+#[test]
+fn nonconnectable_peer() {
+    let peripheral = Mock::new();
+    let central = Mock::new();
+    let radio = Radio::new(&peripheral, &central);
+    let mut peripheral_rx = [0; 128];
+    let mut central_rx = [0; 128];
+    let mut peripheral_bnrg = start(
+        &mut peripheral_rx,
+        &peripheral,
+        PERIPHERAL_ADDRESS,
+        bluenrg::gap::Role::PERIPHERAL,
+    );
+    let mut central_bnrg = start(
+        &mut central_rx,
+        &central,
+        CENTRAL_ADDRESS,
+        bluenrg::gap::Role::CENTRAL,
+    );
+    let mut peripheral_requester: Requester<4> = Requester::new();
+    let mut central_requester: Requester<4> = Requester::new();
+
+    peripheral_bnrg.with_spi(&mut peripheral.spi(), |peripheral_controller| {
+        central_bnrg.with_spi(&mut central.spi(), |central_controller| {
+            advertise(
+                &mut peripheral_requester,
+                peripheral_controller,
+                AdvertisingType::NonConnectableUndirected,
+            );
+            create_connection(
+                &mut central_requester,
+                central_controller,
+                PERIPHERAL_ADDRESS,
+            )
+            .unwrap();
+            assert!(!radio.run());
+
+            central_requester
+                .call::<_, _, (), _>(central_controller, |controller| {
+                    bluenrg::gap::Commands::terminate_procedure(
+                        controller,
+                        Procedure::DIRECT_CONNECTION_ESTABLISHMENT,
+                    )
+                })
+                .unwrap();
+            match event(&mut central_requester, central_controller) {
+                Some(Event::Vendor(BlueNRGEvent::GapProcedureComplete(complete))) => {
+                    assert!(matches!(
+                        complete.procedure,
+                        GapProcedure::DirectConnectionEstablishment
+                    ));
+                    assert_eq!(complete.status, GapProcedureStatus::Success);
+                }
+                other => panic!("Did not get procedure complete: {:?}", other),
+            }
+            assert!(event(&mut peripheral_requester, peripheral_controller).is_none());
+        })
+    });
+}
+
+#[test]
+fn att_traffic() {
+    let peripheral = Mock::new();
+    let central = Mock::new();
+    let radio = Radio::new(&peripheral, &central);
+    let mut peripheral_rx = [0; 128];
+    let mut central_rx = [0; 128];
+    let mut peripheral_bnrg = start(
+        &mut peripheral_rx,
+        &peripheral,
+        PERIPHERAL_ADDRESS,
+        bluenrg::gap::Role::PERIPHERAL,
+    );
+    let mut central_bnrg = start(
+        &mut central_rx,
+        &central,
+        CENTRAL_ADDRESS,
+        bluenrg::gap::Role::CENTRAL,
+    );
+    let mut peripheral_requester: Requester<4> = Requester::new();
+    let mut central_requester: Requester<4> = Requester::new();
+
+    peripheral_bnrg.with_spi(&mut peripheral.spi(), |peripheral_controller| {
+        central_bnrg.with_spi(&mut central.spi(), |central_controller| {
+            let (service, characteristic) =
+                add_battery_service(&mut peripheral_requester, peripheral_controller);
+            let value_handle = characteristic.0 + 1;
+            let cccd_handle = characteristic.0 + 2;
+            update_value(
+                &mut peripheral_requester,
+                peripheral_controller,
+                service,
+                characteristic,
+                &[0x64],
+            );
+            connect(
+                &radio,
+                &mut peripheral_requester,
+                peripheral_controller,
+                &mut central_requester,
+                central_controller,
+            );
+
+            // Read the value.
+            read_value(&mut central_requester, central_controller, value_handle);
+            assert!(radio.run());
+            match event(&mut central_requester, central_controller) {
+                Some(Event::Vendor(BlueNRGEvent::AttReadResponse(response))) => {
+                    assert_eq!(response.conn_handle, CONN_HANDLE);
+                    assert_eq!(response.value(), [0x64]);
+                }
+                other => panic!("Did not get read response: {:?}", other),
+            }
+            expect_gatt_procedure_complete(
+                &mut central_requester,
+                central_controller,
+                GattProcedureStatus::Success,
+            );
+
+            // Write the value.
+            write_value(
+                &mut central_requester,
+                central_controller,
+                value_handle,
+                &[0x32, 0x01],
+            );
+            assert!(radio.run());
+            match event(&mut peripheral_requester, peripheral_controller) {
+                Some(Event::Vendor(BlueNRGEvent::GattAttributeModified(modified))) => {
+                    assert_eq!(modified.conn_handle, CONN_HANDLE);
+                    assert_eq!(modified.attr_handle, AttributeHandle(value_handle));
+                    assert_eq!(modified.data(), [0x32, 0x01]);
+                }
+                other => panic!("Did not get attribute modified: {:?}", other),
+            }
+            expect_gatt_procedure_complete(
+                &mut central_requester,
+                central_controller,
+                GattProcedureStatus::Success,
+            );
+            assert_eq!(
+                peripheral.attribute_value(AttributeHandle(value_handle), |value| value.to_vec()),
+                Some(vec![0x32, 0x01])
+            );
+
+            // Write without response.
+            central_requester
+                .call::<_, _, (), _>(central_controller, |controller| {
+                    bluenrg::gatt::Commands::write_without_response(
+                        controller,
+                        &CharacteristicValue {
+                            conn_handle: CONN_HANDLE,
+                            characteristic_handle: CharacteristicHandle(value_handle),
+                            value: &[0x10],
+                        },
+                    )
+                })
+                .unwrap();
+            assert!(radio.run());
+            match event(&mut peripheral_requester, peripheral_controller) {
+                Some(Event::Vendor(BlueNRGEvent::GattAttributeModified(modified))) => {
+                    assert_eq!(modified.data(), [0x10]);
+                }
+                other => panic!("Did not get attribute modified: {:?}", other),
+            }
+            assert!(event(&mut central_requester, central_controller).is_none());
+
+            // Read an attribute the peer does not have.
+            read_value(&mut central_requester, central_controller, 0x0050);
+            assert!(radio.run());
+            match event(&mut central_requester, central_controller) {
+                Some(Event::Vendor(BlueNRGEvent::AttErrorResponse(response))) => {
+                    assert_eq!(response.conn_handle, CONN_HANDLE);
+                    assert_eq!(response.request, AttRequest::ReadRequest);
+                    assert_eq!(response.attribute_handle, AttributeHandle(0x0050));
+                    assert_eq!(response.error, AttError::InvalidHandle);
+                }
+                other => panic!("Did not get error response: {:?}", other),
+            }
+            expect_gatt_procedure_complete(
+                &mut central_requester,
+                central_controller,
+                GattProcedureStatus::Failed,
+            );
+
+            // Updates are not sent until the client enables notifications.
+            update_value(
+                &mut peripheral_requester,
+                peripheral_controller,
+                service,
+                characteristic,
+                &[0x63],
+            );
+            assert!(!radio.run());
+
+            write_value(
+                &mut central_requester,
+                central_controller,
+                cccd_handle,
+                &[0x01, 0x00],
+            );
+            assert!(radio.run());
+            assert!(matches!(
+                event(&mut peripheral_requester, peripheral_controller),
+                Some(Event::Vendor(BlueNRGEvent::GattAttributeModified(_)))
+            ));
+            expect_gatt_procedure_complete(
+                &mut central_requester,
+                central_controller,
+                GattProcedureStatus::Success,
+            );
+
+            update_value(
+                &mut peripheral_requester,
+                peripheral_controller,
+                service,
+                characteristic,
+                &[0x62],
+            );
+            assert!(radio.run());
+            match event(&mut central_requester, central_controller) {
+                Some(Event::Vendor(BlueNRGEvent::GattNotification(notification))) => {
+                    assert_eq!(notification.conn_handle, CONN_HANDLE);
+                    assert_eq!(notification.attribute_handle, AttributeHandle(value_handle));
+                    assert_eq!(notification.value(), [0x62]);
+                }
+                other => panic!("Did not get notification: {:?}", other),
+            }
+        })
+    });
+}
+
+#[test]
+fn peer_reset_times_out() {
+    let peripheral = Mock::new();
+    let central = Mock::new();
+    let radio = Radio::new(&peripheral, &central);
+    let mut peripheral_rx = [0; 128];
+    let mut central_rx = [0; 128];
+    let mut peripheral_bnrg = start(
+        &mut peripheral_rx,
+        &peripheral,
+        PERIPHERAL_ADDRESS,
+        bluenrg::gap::Role::PERIPHERAL,
+    );
+    let mut central_bnrg = start(
+        &mut central_rx,
+        &central,
+        CENTRAL_ADDRESS,
+        bluenrg::gap::Role::CENTRAL,
+    );
+    let mut peripheral_requester: Requester<4> = Requester::new();
+    let mut central_requester: Requester<4> = Requester::new();
+
+    peripheral_bnrg.with_spi(&mut peripheral.spi(), |peripheral_controller| {
+        central_bnrg.with_spi(&mut central.spi(), |central_controller| {
+            connect(
+                &radio,
+                &mut peripheral_requester,
+                peripheral_controller,
+                &mut central_requester,
+                central_controller,
+            );
+        })
+    });
+
+    hal::digital::v2::OutputPin::set_low(&mut peripheral.reset_pin()).unwrap();
+    assert!(radio.run());
+    central_bnrg.with_spi(&mut central.spi(), |central_controller| {
+        expect_disconnection(
+            &mut central_requester,
+            central_controller,
+            hci::Status::ConnectionTimeout,
+        );
+
+        // There is no connection left to use.
+        assert_eq!(
+            central_requester.call::<_, _, (), _>(central_controller, |controller| {
+                bluenrg::gatt::Commands::read_characteristic_value(
+                    controller,
+                    CONN_HANDLE,
+                    CharacteristicHandle(0x000E),
+                )
+            }),
+            Err(request::Error::CommandFailed(
+                hci::Status::UnknownConnectionId
+            ))
+        );
+    });
+}
@@ -0,0 +1,388 @@
+extern crate bluenrg;
+extern crate bluetooth_hci as hci;
+extern crate embedded_hal as hal;
+extern crate nb;
+
+mod fixture;
+
+use bluenrg::event::command::{GapInit, HalFirmwareRevision};
+use bluenrg::event::BlueNRGEvent;
+use bluenrg::gap::Commands as GapCommands;
+use bluenrg::hal::Commands as HalCommands;
+use bluenrg::request::{Error, Requester};
+use bluenrg::BlueNRG;
+use fixture::{DummyPin, PollCountTimer, ScriptedSink};
+use hci::Event;
+
+const GAP_INIT_COMPLETE: [u8; 13] = [
+    0x04, 0x0E, 0x0A, 0x01, 0x8A, 0xFC, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06,
+];
+const FIRMWARE_REVISION_COMPLETE: [u8; 9] = [0x04, 0x0E, 0x06, 0x01, 0x00, 0xFC, 0x00, 0x34, 0x12];
+const HAL_INITIALIZED: [u8; 6] = [0x04, 0xFF, 0x03, 0x01, 0x00, 0x01];
+
+fn act<T, F>(sink: &mut ScriptedSink, body: F) -> T
+where
+    F: FnOnce(
+        &mut bluenrg::ActiveBlueNRG<ScriptedSink, DummyPin, DummyPin, DummyPin, fixture::NeverError>,
+    ) -> T,
+{
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.set_retry_limit(Some(4));
+    bnrg.with_spi(sink, body)
+}
+
+fn gap_init<C>(controller: &mut C) -> nb::Result<(), bluenrg::gap::Error<C::Error>>
+where
+    C: GapCommands,
+{
+    #[cfg(not(feature = "bluenrg2"))]
+    {
+        controller.init(bluenrg::gap::Role::PERIPHERAL, false, 7)
+    }
+    #[cfg(feature = "bluenrg2")]
+    {
+        controller
+            .init(
+                bluenrg::gap::Role::PERIPHERAL,
+                bluenrg::gap::Privacy::Disabled,
+                7,
+            )
+            .map_err(|e| e.map(bluenrg::gap::Error::Comm))
+    }
+}
+
+#[cfg(feature = "ms")]
+const GAP_INIT_LEN: usize = 7;
+#[cfg(not(feature = "ms"))]
+const GAP_INIT_LEN: usize = 5;
+
+#[test]
+fn call_returns_typed_response() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(GAP_INIT_LEN).event(&GAP_INIT_COMPLETE);
+    let gap: GapInit = act(&mut sink, |controller| {
+        Requester::<2>::new()
+            .call(controller, |c| gap_init(c))
+            .unwrap()
+    });
+    assert_eq!(gap.service_handle, bluenrg::gatt::ServiceHandle(0x0201));
+    assert_eq!(
+        gap.appearance_handle,
+        bluenrg::gatt::CharacteristicHandle(0x0605)
+    );
+}
+
+#[test]
+fn unrelated_events_are_buffered() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(4)
+        .event(&HAL_INITIALIZED)
+        // Command complete for a different command
+        .event(&GAP_INIT_COMPLETE)
+        .event(&FIRMWARE_REVISION_COMPLETE);
+    act(&mut sink, |controller| {
+        let mut requester = Requester::<2>::new();
+        let revision: HalFirmwareRevision = requester
+            .call(controller, |c| c.get_firmware_revision())
+            .unwrap();
+        assert_eq!(revision.revision, 0x1234);
+        assert_eq!(requester.pending(), None);
+        assert_eq!(requester.buffered_events(), 2);
+
+        match requester.read(controller) {
+            Ok(Event::Vendor(BlueNRGEvent::HalInitialized(_))) => (),
+            other => panic!("Did not get HalInitialized: {:?}", other),
+        }
+        match requester.read(controller) {
+            Ok(Event::CommandComplete(_)) => (),
+            other => panic!("Did not get CommandComplete: {:?}", other),
+        }
+        assert_eq!(requester.buffered_events(), 0);
+    });
+}
+
+#[test]
+fn send_and_poll() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(4)
+        .event(&HAL_INITIALIZED)
+        .event(&FIRMWARE_REVISION_COMPLETE);
+    act(&mut sink, |controller| {
+        let mut requester = Requester::<1>::new();
+        requester
+            .send(controller, |c| c.get_firmware_revision())
+            .unwrap();
+        assert_eq!(requester.pending(), Some(hci::Opcode(0xFC00)));
+        assert_eq!(
+            requester
+                .poll::<_, HalFirmwareRevision, ()>(controller)
+                .err(),
+            Some(nb::Error::WouldBlock)
+        );
+        let revision = requester
+            .poll::<_, HalFirmwareRevision, ()>(controller)
+            .unwrap();
+        assert_eq!(revision.revision, 0x1234);
+    });
+}
+
+#[test]
+fn command_failed() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(4)
+        .event(&[0x04, 0x0E, 0x06, 0x01, 0x00, 0xFC, 0x12, 0x00, 0x00]);
+    let err = act(&mut sink, |controller| {
+        Requester::<2>::new()
+            .call::<_, _, HalFirmwareRevision, _>(controller, |c| c.get_firmware_revision())
+            .err()
+            .unwrap()
+    });
+    assert_eq!(err, Error::CommandFailed(hci::Status::InvalidParameters));
+}
+
+#[test]
+fn command_failed_with_status_only() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(4)
+        .event(&[0x04, 0x0E, 0x04, 0x01, 0x00, 0xFC, 0x12]);
+    let err = act(&mut sink, |controller| {
+        Requester::<2>::new()
+            .call::<_, _, HalFirmwareRevision, _>(controller, |c| c.get_firmware_revision())
+            .err()
+            .unwrap()
+    });
+    assert_eq!(err, Error::CommandFailed(hci::Status::InvalidParameters));
+}
+
+#[test]
+fn unknown_status() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(4)
+        .event(&[0x04, 0x0E, 0x06, 0x01, 0x00, 0xFC, 0x7F, 0x00, 0x00]);
+    let err = act(&mut sink, |controller| {
+        Requester::<2>::new()
+            .call::<_, _, HalFirmwareRevision, _>(controller, |c| c.get_firmware_revision())
+            .err()
+            .unwrap()
+    });
+    assert_eq!(err, Error::BadEvent(hci::event::Error::BadStatus(0x7F)));
+}
+
+#[test]
+fn call_with_timeout() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(4).event(&FIRMWARE_REVISION_COMPLETE);
+    let mut timer = PollCountTimer::new();
+    let revision = act(&mut sink, |controller| {
+        Requester::<2>::new()
+            .call_with_timeout::<_, _, HalFirmwareRevision, _, _>(
+                controller,
+                |c| c.get_firmware_revision(),
+                &mut timer,
+                3,
+            )
+            .unwrap()
+    });
+    assert_eq!(revision.revision, 0x1234);
+    assert_eq!(timer.starts, 1);
+}
+
+#[test]
+fn call_times_out() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(4);
+    for _ in 0..8 {
+        // The controller is awake, but has nothing to read.
+        sink.reply(&[0x02, 0x00, 0x00, 0x00, 0x00]);
+    }
+    let mut timer = PollCountTimer::new();
+    act(&mut sink, |controller| {
+        let mut requester = Requester::<2>::new();
+        let err = requester
+            .call_with_timeout::<_, _, HalFirmwareRevision, _, _>(
+                controller,
+                |c| c.get_firmware_revision(),
+                &mut timer,
+                3,
+            )
+            .err()
+            .unwrap();
+        assert_eq!(err, Error::Timeout);
+        assert_eq!(requester.pending(), Some(hci::Opcode(0xFC00)));
+    });
+}
+
+/// Scripts a [`call_times_out`]-style wait: the controller is awake, but has nothing to read
+/// before a timer started with 3 expires.
+fn nothing_to_read(sink: &mut ScriptedSink) {
+    for _ in 0..4 {
+        sink.reply(&[0x02, 0x00, 0x00, 0x00, 0x00]);
+    }
+}
+
+#[test]
+fn call_after_timeout_waits_for_credit() {
+    const LATE_REVISION_COMPLETE: [u8; 9] = [0x04, 0x0E, 0x06, 0x01, 0x00, 0xFC, 0x00, 0x78, 0x56];
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(4);
+    nothing_to_read(&mut sink);
+
+    // The response to the first command returns the command credit, and is dropped.
+    sink.event(&LATE_REVISION_COMPLETE)
+        .accept_command(4)
+        .event(&FIRMWARE_REVISION_COMPLETE);
+    let mut timer = PollCountTimer::new();
+    act(&mut sink, |controller| {
+        let mut requester = Requester::<2>::new();
+        let err = requester
+            .call_with_timeout::<_, _, HalFirmwareRevision, _, _>(
+                controller,
+                |c| c.get_firmware_revision(),
+                &mut timer,
+                3,
+            )
+            .err()
+            .unwrap();
+        assert_eq!(err, Error::Timeout);
+        assert_eq!(controller.command_credits(), 0);
+
+        let revision: HalFirmwareRevision = requester
+            .call_with_timeout(controller, |c| c.get_firmware_revision(), &mut timer, 3)
+            .unwrap();
+        assert_eq!(revision.revision, 0x1234);
+        assert_eq!(requester.pending(), None);
+        assert_eq!(requester.buffered_events(), 0);
+    });
+}
+
+#[test]
+fn call_after_timeout_times_out_without_credit() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(4);
+    nothing_to_read(&mut sink);
+    nothing_to_read(&mut sink);
+    let mut timer = PollCountTimer::new();
+    act(&mut sink, |controller| {
+        let mut requester = Requester::<2>::new();
+        for _ in 0..2 {
+            let err = requester
+                .call_with_timeout::<_, _, HalFirmwareRevision, _, _>(
+                    controller,
+                    |c| c.get_firmware_revision(),
+                    &mut timer,
+                    3,
+                )
+                .err()
+                .unwrap();
+            assert_eq!(err, Error::Timeout);
+        }
+        assert_eq!(requester.pending(), Some(hci::Opcode(0xFC00)));
+    });
+
+    // Only the first command was written: 0x0A starts the SPI header of a write.
+    assert_eq!(sink.sent.iter().filter(|&&byte| byte == 0x0A).count(), 1);
+}
+
+#[test]
+fn command_status_completes_status_only_command() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(4)
+        .event(&[0x04, 0x0F, 0x04, 0x00, 0x01, 0x81, 0xFC]);
+    act(&mut sink, |controller| {
+        Requester::<2>::new()
+            .call::<_, _, (), _>(controller, |c| c.set_nondiscoverable())
+            .unwrap()
+    });
+}
+
+#[test]
+fn command_status_failure() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(GAP_INIT_LEN)
+        .event(&[0x04, 0x0F, 0x04, 0x0C, 0x01, 0x8A, 0xFC]);
+    let err = act(&mut sink, |controller| {
+        Requester::<2>::new()
+            .call::<_, _, GapInit, _>(controller, |c| gap_init(c))
+            .err()
+            .unwrap()
+    });
+    assert_eq!(err, Error::CommandFailed(hci::Status::CommandDisallowed));
+}
+
+#[test]
+fn unexpected_response() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(4).event(&FIRMWARE_REVISION_COMPLETE);
+    let err = act(&mut sink, |controller| {
+        Requester::<2>::new()
+            .call::<_, _, GapInit, _>(controller, |c| c.get_firmware_revision())
+            .err()
+            .unwrap()
+    });
+    assert_eq!(err, Error::UnexpectedResponse);
+}
+
+#[test]
+fn event_buffer_full() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.accept_command(4)
+        .event(&HAL_INITIALIZED)
+        .event(&HAL_INITIALIZED)
+        .event(&FIRMWARE_REVISION_COMPLETE);
+    act(&mut sink, |controller| {
+        let mut requester = Requester::<1>::new();
+        let err = requester
+            .call::<_, _, HalFirmwareRevision, _>(controller, |c| c.get_firmware_revision())
+            .err()
+            .unwrap();
+        assert_eq!(err, Error::EventBufferFull);
+
+        // Draining the buffer lets the response through.
+        assert!(requester.read(controller).is_ok());
+        let revision =
+            nb::block!(requester.poll::<_, HalFirmwareRevision, ()>(controller)).unwrap();
+        assert_eq!(revision.revision, 0x1234);
+        assert_eq!(requester.buffered_events(), 1);
+    });
+}
+
+#[test]
+fn command_error() {
+    let mut sink = ScriptedSink::new(0x00);
+    let err = act(&mut sink, |controller| {
+        Requester::<2>::new()
+            .call::<_, _, (), _>(controller, |c| {
+                c.set_limited_discoverable(&bluenrg::gap::DiscoverableParameters {
+                    advertising_type: hci::host::AdvertisingType::ConnectableDirectedHighDutyCycle,
+                    advertising_interval: None,
+                    address_type: hci::host::OwnAddressType::Public,
+                    filter_policy: hci::host::AdvertisingFilterPolicy::AllowConnectionAndScan,
+                    local_name: None,
+                    advertising_data: &[],
+                    conn_interval: (None, None),
+                })
+            })
+            .err()
+            .unwrap()
+    });
+    assert_eq!(
+        err,
+        Error::Command(bluenrg::gap::Error::BadAdvertisingType(
+            hci::host::AdvertisingType::ConnectableDirectedHighDutyCycle
+        ))
+    );
+    assert!(sink.sent.is_empty());
+}
+
+#[test]
+fn poll_without_command() {
+    let mut sink = ScriptedSink::new(0x00);
+    let err = act(&mut sink, |controller| {
+        Requester::<2>::new()
+            .poll::<_, (), ()>(controller)
+            .err()
+            .unwrap()
+    });
+    assert_eq!(err, nb::Error::Other(Error::NoPendingCommand));
+}
@@ -0,0 +1,121 @@
+extern crate bluenrg;
+extern crate bluetooth_hci as hci;
+extern crate embedded_hal as hal;
+extern crate nb;
+
+mod fixture;
+
+use bluenrg::event::{BlueNRGError, BlueNRGEvent};
+use bluenrg::{BlueNRG, Error};
+use fixture::{DummyPin, NeverError, ScriptedSink};
+use hci::host::uart::{Error as UartError, Hci, Packet};
+use hci::Event;
+
+type ReadResult = nb::Result<Packet<BlueNRGEvent>, UartError<Error<(), NeverError>, BlueNRGError>>;
+
+fn read<C>(controller: &mut C) -> ReadResult
+where
+    C: Hci<Error<(), NeverError>, BlueNRGEvent, BlueNRGError>,
+{
+    controller.read()
+}
+
+const COMMAND_COMPLETE: [u8; 9] = [0x04, 0x0E, 0x06, 0x02, 0x00, 0xFC, 0x00, 0x34, 0x12];
+const COMMAND_STATUS: [u8; 7] = [0x04, 0x0F, 0x04, 0x00, 0x01, 0x81, 0xFC];
+
+fn assert_command_complete(result: ReadResult) {
+    match result {
+        Ok(Packet::Event(Event::CommandComplete(event))) => {
+            assert_eq!(event.num_hci_command_packets, 2);
+        }
+        other => panic!("Did not get command complete: {:?}", other),
+    }
+}
+
+fn assert_command_status(result: ReadResult) {
+    match result {
+        Ok(Packet::Event(Event::CommandStatus(event))) => {
+            assert_eq!(event.opcode, hci::Opcode(0xFC81));
+        }
+        other => panic!("Did not get command status: {:?}", other),
+    }
+}
+
+#[test]
+fn no_overflow() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.event(&COMMAND_COMPLETE);
+    let mut rx_buffer = [0; 16];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.with_spi(&mut sink, |controller| {
+        assert_command_complete(read(controller));
+    });
+    assert_eq!(bnrg.dropped_bytes(), 0);
+}
+
+#[test]
+fn overflow_keeps_complete_packets() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&[0x02, 0x00, 0x00, 16, 0x00])
+        .reply(&COMMAND_COMPLETE)
+        .reply(&COMMAND_STATUS)
+        .event(&COMMAND_STATUS);
+
+    // Holds 15 bytes, so the command status event does not fit
+    let mut rx_buffer = [0; 16];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.with_spi(&mut sink, |controller| {
+        assert_eq!(
+            read(controller).err(),
+            Some(nb::Error::Other(UartError::Comm(Error::RxOverflow)))
+        );
+        assert_command_complete(read(controller));
+
+        // The next packet from the controller is read from the start.
+        assert_command_status(read(controller));
+    });
+    assert_eq!(bnrg.dropped_bytes(), COMMAND_STATUS.len() as u32);
+}
+
+#[test]
+fn overflow_packet_larger_than_buffer() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.event(&[
+        0x04, 0x0E, 0x11, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00, 0x00, 0x00, 0x00, 0x00,
+    ])
+    .event(&COMMAND_STATUS);
+    let mut rx_buffer = [0; 16];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.with_spi(&mut sink, |controller| {
+        assert_eq!(
+            read(controller).err(),
+            Some(nb::Error::Other(UartError::Comm(Error::RxOverflow)))
+        );
+        assert_command_status(read(controller));
+    });
+    assert_eq!(bnrg.dropped_bytes(), 20);
+}
+
+#[test]
+fn dropped_bytes_accumulate() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&[0x02, 0x00, 0x00, 16, 0x00])
+        .reply(&COMMAND_COMPLETE)
+        .reply(&COMMAND_STATUS)
+        .reply(&[0x02, 0x00, 0x00, 16, 0x00])
+        .reply(&COMMAND_COMPLETE)
+        .reply(&COMMAND_STATUS);
+    let mut rx_buffer = [0; 16];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.with_spi(&mut sink, |controller| {
+        for _ in 0..2 {
+            assert_eq!(
+                read(controller).err(),
+                Some(nb::Error::Other(UartError::Comm(Error::RxOverflow)))
+            );
+            assert_command_complete(read(controller));
+        }
+    });
+    assert_eq!(bnrg.dropped_bytes(), 2 * COMMAND_STATUS.len() as u32);
+}
@@ -0,0 +1,423 @@
+extern crate bluenrg;
+extern crate bluetooth_hci as hci;
+
+use bluenrg::dialect::Dialect;
+use bluenrg::event::command::ReturnParameters;
+use bluenrg::event::*;
+
+// Parses the event, serializes it again and checks that the same bytes come back out, and that they
+// parse to the same event.
+fn assert_event_round_trip(dialect: Dialect, bytes: &[u8]) {
+    let event = BlueNRGEvent::with_dialect(dialect, bytes).unwrap();
+
+    let mut buffer = [0; 258];
+    let len = event.serialize(dialect, &mut buffer).unwrap();
+    assert_eq!(&buffer[..len], bytes);
+    assert_eq!(
+        BlueNRGEvent::with_dialect(dialect, &buffer[..len]).unwrap(),
+        event
+    );
+
+    let len = event.serialize_packet(dialect, &mut buffer).unwrap();
+    assert_eq!(&buffer[..3], &[0x04, 0xFF, bytes.len() as u8]);
+    assert_eq!(&buffer[3..len], bytes);
+}
+
+fn assert_events_round_trip(dialect: Dialect, events: &[&[u8]]) {
+    for bytes in events {
+        assert_event_round_trip(dialect, bytes);
+    }
+}
+
+fn assert_return_parameters_round_trip(dialect: Dialect, bytes: &[u8]) {
+    let params = ReturnParameters::with_dialect(dialect, bytes).unwrap();
+    assert_eq!(
+        params.opcode().0,
+        u16::from(bytes[1]) | u16::from(bytes[2]) << 8
+    );
+
+    let mut buffer = [0; 258];
+    let len = params.serialize(dialect, &mut buffer).unwrap();
+    assert_eq!(&buffer[..len], bytes);
+    assert_eq!(
+        ReturnParameters::with_dialect(dialect, &buffer[..len]).unwrap(),
+        params
+    );
+
+    let len = params.serialize_packet(dialect, &mut buffer).unwrap();
+    assert_eq!(&buffer[..3], &[0x04, 0x0E, bytes.len() as u8]);
+    assert_eq!(&buffer[3..len], bytes);
+}
+
+fn assert_all_return_parameters_round_trip(dialect: Dialect, all_params: &[&[u8]]) {
+    for bytes in all_params {
+        assert_return_parameters_round_trip(dialect, bytes);
+    }
+}
+
+// Events that are encoded the same way in both dialects.
+const COMMON_EVENTS: &[&[u8]] = &[
+    &[0x01, 0x00, 0x08],
+    &[0x00, 0x04],
+    &[0x01, 0x04, 0x01, 0x02, 0x02],
+    &[0x02, 0x04, 0x01, 0x02],
+    &[0x03, 0x04, 0x01, 0x02],
+    &[0x04, 0x04],
+    &[0x05, 0x04],
+    &[
+        0x06, 0x04, 0x04, 0x01, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 3, 0x0A, 0x0B, 0x0C, 0xCE,
+    ],
+    &[
+        0x06, 0x04, 0x00, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0, 127,
+    ],
+    &[0x07, 0x04, 0x01, 0x00],
+    &[0x07, 0x04, 0x02, 0x41],
+    &[0x07, 0x04, 0x04, 0x00, b'n', b'a', b'm', b'e'],
+    &[0x07, 0x04, 0x08, 0x05],
+    &[0x07, 0x04, 0x10, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06],
+    &[0x07, 0x04, 0x20, 0x00],
+    &[0x07, 0x04, 0x40, 0x00],
+    &[0x00, 0x08, 0x01, 0x02, 6, 0x01, 0, 2, 0, 0x02, 0x00],
+    &[0x00, 0x08, 0x01, 0x02, 6, 0x13, 0, 2, 0, 0x00, 0x00],
+    &[0x00, 0x08, 0x01, 0x02, 6, 0x13, 0, 2, 0, 0x01, 0x00],
+    &[0x01, 0x08, 0x01, 0x02, 0],
+    &[
+        0x02, 0x08, 0x01, 0x02, 11, 0x07, 8, 0, 0x28, 0x00, 0x50, 0x00, 0x00, 0x00, 0x64, 0x00,
+    ],
+    &[0x02, 0x0C, 0x01, 0x02],
+    &[0x03, 0x0C, 0x01, 0x02, 1, 0x03, 0x04],
+    &[
+        0x04, 0x0C, 0x01, 0x02, 9, 1, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A,
+    ],
+    &[
+        0x04, 0x0C, 0x01, 0x02, 19, 2, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C,
+        0x0D, 0x0E, 0x0F, 0x10, 0x11, 0x12, 0x13, 0x14,
+    ],
+    &[
+        0x05, 0x0C, 0x01, 0x02, 8, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A,
+    ],
+    &[
+        0x06, 0x0C, 0x01, 0x02, 9, 4, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A,
+    ],
+    &[0x07, 0x0C, 0x01, 0x02, 3, 0x03, 0x04, 0x05],
+    &[0x08, 0x0C, 0x01, 0x02, 2, 0x03, 0x04],
+    &[0x09, 0x0C, 0x01, 0x02, 0],
+    &[
+        0x0A, 0x0C, 0x01, 0x02, 13, 6, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09, 0x0A, 0x0B, 0x0C,
+        0x0D, 0x0E,
+    ],
+    &[
+        0x0C, 0x0C, 0x01, 0x02, 7, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08, 0x09,
+    ],
+    &[0x0D, 0x0C, 0x01, 0x02, 0],
+    &[0x0E, 0x0C, 0x01, 0x02, 4, 0x03, 0x04, 0x05, 0x06],
+    &[0x0F, 0x0C, 0x01, 0x02, 3, 0x03, 0x04, 0x05],
+    &[0x10, 0x0C, 0x01, 0x02, 1, 0x41],
+    &[0x11, 0x0C, 0x01, 0x02, 4, 0x0A, 0x03, 0x04, 0x0A],
+    &[0x12, 0x0C, 0x01, 0x02, 2, 0x03, 0x04],
+    &[0x13, 0x0C, 0x01, 0x02, 0x03, 0x04, 2, 0x05, 0x06],
+    &[0x14, 0x0C, 0x01, 0x02, 0x03, 0x04, 2, 0x05, 0x06],
+    &[0x15, 0x0C, 0x01, 0x02, 4, 0x03, 0x04, 0x05, 0x06],
+];
+
+// Events that only exist, or are only encoded this way, in the BlueNRG-MS dialect.
+const MS_EVENTS: &[&[u8]] = &[
+    &[0x02, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x01, 0x00],
+    &[
+        0x03, 0x00, 0x02, 0x01, 0x00, 0x00, 0x00, 0x02, 0x00, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00,
+        0x04, 0x00, 0x00, 0x00, 0x05, 0x00, 0x00, 0x00, 0x06, 0x00, 0x00, 0x00, 0x07, 0x00, 0x00,
+        0x00, 0x08, 0x00, 0x00, 0x00, 0x09, 0x00, 0x00, 0x00, 2, 0xAA, 0xBB,
+    ],
+    &[0x08, 0x04, 0x01, 0x02],
+    #[cfg(not(feature = "bluenrg2"))]
+    &[
+        0x01, 0x0C, 0x01, 0x02, 0x03, 0x04, 2, 0x05, 0x80, 0x07, 0x08,
+    ],
+    #[cfg(feature = "bluenrg2")]
+    &[
+        0x01, 0x0C, 0x01, 0x02, 0x03, 0x04, 0x05, 0x80, 2, 0, 0x07, 0x08,
+    ],
+    #[cfg(feature = "bluenrg2")]
+    &[0x09, 0x04, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06],
+    &[0x16, 0x0C, 0x01, 0x02, 0x03, 0x04],
+    &[0x17, 0x0C, 0x01, 0x02],
+    &[
+        0x18, 0x0C, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 2, 0x07, 0x08,
+    ],
+];
+
+// Events that are only encoded this way in the original BlueNRG dialect.
+const ORIGINAL_EVENTS: &[&[u8]] = &[
+    &[0x08, 0x04, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06],
+    &[0x01, 0x0C, 0x01, 0x02, 0x03, 0x04, 2, 0x05, 0x06],
+];
+
+#[test]
+fn events_round_trip_in_ms_dialect() {
+    assert_events_round_trip(Dialect::BlueNRGMS, COMMON_EVENTS);
+    assert_events_round_trip(Dialect::BlueNRGMS, MS_EVENTS);
+}
+
+#[test]
+fn events_round_trip_in_original_dialect() {
+    assert_events_round_trip(Dialect::BlueNRG, COMMON_EVENTS);
+    assert_events_round_trip(Dialect::BlueNRG, ORIGINAL_EVENTS);
+}
+
+#[test]
+fn ms_events_are_not_in_original_dialect() {
+    let mut buffer = [0; 258];
+    // The modified attribute has an original layout, and the numeric comparison value is not
+    // checked against the dialect.
+    let in_both = |bytes: &&&[u8]| bytes[..2] == [0x01, 0x0C] || bytes[..2] == [0x09, 0x04];
+    for bytes in MS_EVENTS.iter().filter(|bytes| !in_both(bytes)) {
+        let event = BlueNRGEvent::with_dialect(Dialect::BlueNRGMS, bytes).unwrap();
+        assert_eq!(
+            event.serialize(Dialect::BlueNRG, &mut buffer),
+            Err(SerializeError::NotInDialect),
+            "{:?}",
+            event
+        );
+    }
+}
+
+#[test]
+fn reconnection_address_is_not_in_ms_dialect() {
+    let event =
+        BlueNRGEvent::with_dialect(Dialect::BlueNRG, &[0x08, 0x04, 1, 2, 3, 4, 5, 6]).unwrap();
+    let mut buffer = [0; 258];
+    assert_eq!(
+        event.serialize(Dialect::BlueNRGMS, &mut buffer),
+        Err(SerializeError::NotInDialect)
+    );
+}
+
+#[test]
+fn attribute_modified_drops_offset_in_original_dialect() {
+    let mut bytes = [0; 258];
+    let len = {
+        let event = BlueNRGEvent::with_dialect(Dialect::BlueNRGMS, MS_EVENTS[3]).unwrap();
+        event.serialize(Dialect::BlueNRG, &mut bytes).unwrap()
+    };
+    assert_eq!(
+        &bytes[..len],
+        &[0x01, 0x0C, 0x01, 0x02, 0x03, 0x04, 2, 0x07, 0x08]
+    );
+
+    match BlueNRGEvent::with_dialect(Dialect::BlueNRG, &bytes[..len]) {
+        Ok(BlueNRGEvent::GattAttributeModified(event)) => {
+            assert_eq!(event.offset, 0);
+            assert!(!event.continued);
+            assert_eq!(event.data(), [0x07, 0x08]);
+        }
+        other => panic!("Did not get attribute modified: {:?}", other),
+    }
+}
+
+#[test]
+fn attribute_modified_too_long_for_ms_dialect() {
+    let mut bytes = [0; 255];
+    bytes[0] = 0x01;
+    bytes[1] = 0x0C;
+    bytes[6] = 248;
+    let event = BlueNRGEvent::with_dialect(Dialect::BlueNRG, &bytes).unwrap();
+
+    let mut buffer = [0; 258];
+    #[cfg(not(feature = "bluenrg2"))]
+    let expected = 257;
+    #[cfg(feature = "bluenrg2")]
+    let expected = 258;
+    assert_eq!(
+        event.serialize_packet(Dialect::BlueNRGMS, &mut buffer),
+        Err(SerializeError::TooLong(expected))
+    );
+}
+
+#[test]
+fn event_buffer_too_small() {
+    let event = BlueNRGEvent::with_dialect(Dialect::BlueNRGMS, &[0x02, 0x0C, 0x01, 0x02]).unwrap();
+
+    let mut buffer = [0; 6];
+    assert_eq!(
+        event.serialize(Dialect::BlueNRGMS, &mut buffer[..3]),
+        Err(SerializeError::BufferTooSmall(4))
+    );
+    assert_eq!(
+        event.serialize_packet(Dialect::BlueNRGMS, &mut buffer),
+        Err(SerializeError::BufferTooSmall(7))
+    );
+}
+
+#[test]
+fn event_packet_parses_as_hci_event() {
+    let event = BlueNRGEvent::with_dialect(Dialect::default(), COMMON_EVENTS[2]).unwrap();
+
+    let mut buffer = [0; 258];
+    let len = event
+        .serialize_packet(Dialect::default(), &mut buffer)
+        .unwrap();
+    match hci::event::Event::<BlueNRGEvent>::new(hci::event::Packet(&buffer[1..len])) {
+        Ok(hci::event::Event::Vendor(parsed)) => assert_eq!(parsed, event),
+        other => panic!("Did not get vendor event: {:?}", other),
+    }
+}
+
+// Return parameters that are encoded the same way in both dialects.
+const COMMON_RETURN_PARAMETERS: &[&[u8]] = &[
+    &[1, 0x00, 0xFC, 0x00, 0x01, 0x02],
+    &[1, 0x0C, 0xFC, 0x41],
+    &[1, 0x0D, 0xFC, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06],
+    &[1, 0x0D, 0xFC, 0x00, 0x01, 0x02],
+    &[
+        1, 0x0D, 0xFC, 0x00, 0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0x8, 0x9, 0xA, 0xB, 0xC, 0xD,
+        0xE, 0xF,
+    ],
+    &[1, 0x0D, 0xFC, 0x00, 0x01],
+    &[1, 0x0F, 0xFC, 0x00],
+    &[1, 0x13, 0xFC, 0x00],
+    &[1, 0x14, 0xFC, 0x00, 0x01, 0x02, 0x03, 0x04],
+    &[1, 0x15, 0xFC, 0x00],
+    &[1, 0x16, 0xFC, 0x00],
+    &[
+        1, 0x17, 0xFC, 0x00, 0, 1, 2, 3, 4, 5, 6, 7, 0x0, 0x1, 0x2, 0x3, 0x4, 0x5, 0x6, 0x7, 0x8,
+        0x9, 0xA, 0xB, 0xC, 0xD, 0xE, 0xF,
+    ],
+    &[
+        1, 0x19, 0xFC, 0x00, 0x01, 0x02, 0x00, 0x00, 0x03, 0x00, 0x00, 0x00,
+    ],
+    &[1, 0x81, 0xFC, 0x00],
+    &[1, 0x83, 0xFC, 0x00],
+    &[1, 0x84, 0xFC, 0x00],
+    &[1, 0x85, 0xFC, 0x00],
+    &[1, 0x86, 0xFC, 0x00],
+    &[1, 0x87, 0xFC, 0x00],
+    &[1, 0x88, 0xFC, 0x00],
+    &[1, 0x89, 0xFC, 0x00],
+    &[1, 0x8A, 0xFC, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06],
+    &[1, 0x8B, 0xFC, 0x00],
+    &[1, 0x8C, 0xFC, 0x00],
+    &[1, 0x8E, 0xFC, 0x00],
+    &[1, 0x8F, 0xFC, 0x00],
+    &[1, 0x90, 0xFC, 0x00, 1, 0, 1, 0x02],
+    &[1, 0x91, 0xFC, 0x00],
+    &[1, 0x92, 0xFC, 0x00],
+    &[1, 0x94, 0xFC, 0x00],
+    &[1, 0x95, 0xFC, 0x00],
+    &[1, 0x9D, 0xFC, 0x00],
+    &[1, 0xA0, 0xFC, 0x48],
+    &[
+        1, 0xA3, 0xFC, 0x00, 2, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x01, 0x11, 0x12, 0x13,
+        0x14, 0x15, 0x16,
+    ],
+    &[1, 0xA3, 0xFC, 0x41],
+    &[1, 0xA4, 0xFC, 0x00],
+    &[1, 0x01, 0xFD, 0x00],
+    &[1, 0x02, 0xFD, 0x00, 0x01, 0x02],
+    &[1, 0x03, 0xFD, 0x00, 0x01, 0x02],
+    &[1, 0x04, 0xFD, 0x00, 0x01, 0x02],
+    &[1, 0x05, 0xFD, 0x00, 0x01, 0x02],
+    &[1, 0x06, 0xFD, 0x00],
+    &[1, 0x07, 0xFD, 0x00],
+    &[1, 0x08, 0xFD, 0x00],
+    &[1, 0x09, 0xFD, 0x00],
+    &[1, 0x0A, 0xFD, 0x00],
+    &[1, 0x23, 0xFD, 0x00],
+    &[1, 0x24, 0xFD, 0x00],
+    &[1, 0x25, 0xFD, 0x00],
+    &[1, 0x26, 0xFD, 0x00],
+    &[1, 0x27, 0xFD, 0x00],
+    &[1, 0x28, 0xFD, 0x00],
+    &[1, 0x29, 0xFD, 0x00],
+    &[1, 0x2A, 0xFD, 0x00, 3, 0, 0x01, 0x02, 0x03],
+    &[1, 0x82, 0xFD, 0x00],
+    &[1, 0x20, 0xFC, 0x00],
+    &[1, 0x21, 0xFC, 0x00],
+    &[1, 0x22, 0xFC, 0x00, 0x03],
+    &[1, 0x23, 0xFC, 0x00, 0x10],
+    &[1, 0x24, 0xFC, 0x00],
+    &[1, 0x25, 0xFC, 0x00],
+    &[1, 0x26, 0xFC, 0x00],
+    &[1, 0x27, 0xFC, 0x00],
+    &[1, 0x28, 0xFC, 0x00, 0x01, 0x02, 0x03],
+    &[1, 0x29, 0xFC, 0x00, 0x01, 0x02, 0x03, 0x04],
+    &[1, 0x2A, 0xFC, 0x00, 0x31],
+];
+
+// Return parameters that only exist, or are only encoded this way, in the BlueNRG-MS dialect.
+const MS_RETURN_PARAMETERS: &[&[u8]] = &[
+    &[1, 0xA0, 0xFC, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06],
+    #[cfg(feature = "ms")]
+    &[1, 0xA1, 0xFC, 0x00],
+    #[cfg(feature = "ms")]
+    &[1, 0xA2, 0xFC, 0x00],
+    #[cfg(feature = "bluenrg2")]
+    &[1, 0xA5, 0xFC, 0x00],
+    #[cfg(feature = "bluenrg2")]
+    &[1, 0xA6, 0xFC, 0x00],
+    #[cfg(feature = "bluenrg2")]
+    &[1, 0xAA, 0xFC, 0x00],
+    #[cfg(feature = "ms")]
+    &[1, 0x2B, 0xFD, 0x00, 2, 0, 0x01, 0x02],
+    #[cfg(feature = "ms")]
+    &[1, 0x2C, 0xFD, 0x00],
+];
+
+#[test]
+fn return_parameters_round_trip_in_ms_dialect() {
+    assert_all_return_parameters_round_trip(Dialect::BlueNRGMS, COMMON_RETURN_PARAMETERS);
+    assert_all_return_parameters_round_trip(Dialect::BlueNRGMS, MS_RETURN_PARAMETERS);
+}
+
+#[test]
+fn return_parameters_round_trip_in_original_dialect() {
+    assert_all_return_parameters_round_trip(Dialect::BlueNRG, COMMON_RETURN_PARAMETERS);
+}
+
+#[test]
+fn resolved_address_is_dropped_in_original_dialect() {
+    let params =
+        ReturnParameters::with_dialect(Dialect::BlueNRGMS, MS_RETURN_PARAMETERS[0]).unwrap();
+
+    let mut buffer = [0; 258];
+    let len = params.serialize(Dialect::BlueNRG, &mut buffer).unwrap();
+    assert_eq!(&buffer[..len], &[1, 0xA0, 0xFC, 0x00]);
+}
+
+#[test]
+fn return_parameters_buffer_too_small() {
+    let params =
+        ReturnParameters::with_dialect(Dialect::BlueNRGMS, &[1, 0x01, 0xFD, 0x00]).unwrap();
+
+    let mut buffer = [0; 6];
+    assert_eq!(
+        params.serialize(Dialect::BlueNRGMS, &mut buffer[..3]),
+        Err(SerializeError::BufferTooSmall(4))
+    );
+    assert_eq!(
+        params.serialize_packet(Dialect::BlueNRGMS, &mut buffer),
+        Err(SerializeError::BufferTooSmall(7))
+    );
+}
+
+#[test]
+fn return_parameters_packet_parses_as_command_complete() {
+    let params =
+        ReturnParameters::with_dialect(Dialect::default(), COMMON_RETURN_PARAMETERS[0]).unwrap();
+
+    let mut buffer = [0; 258];
+    let len = params
+        .serialize_packet(Dialect::default(), &mut buffer)
+        .unwrap();
+    match hci::event::Event::<BlueNRGEvent>::new(hci::event::Packet(&buffer[1..len])) {
+        Ok(hci::event::Event::CommandComplete(event)) => {
+            assert_eq!(event.num_hci_command_packets, 1);
+            match event.return_params {
+                hci::event::command::ReturnParameters::Vendor(parsed) => {
+                    assert_eq!(parsed, params)
+                }
+                other => panic!("Did not get vendor return parameters: {:?}", other),
+            }
+        }
+        other => panic!("Did not get command complete: {:?}", other),
+    }
+}
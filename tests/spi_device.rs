@@ -0,0 +1,196 @@
+#![cfg(feature = "spi-device")]
+
+extern crate bluenrg;
+extern crate bluetooth_hci as hci;
+extern crate embedded_hal as hal;
+extern crate nb;
+
+mod fixture;
+
+use bluenrg::device::BlueNRGDevice;
+use bluenrg::event::{BlueNRGError, BlueNRGEvent};
+use bluenrg::hal::Commands;
+use bluenrg::Error;
+use fixture::{DummyPin, ScriptedSink};
+use hci::host::uart::{Error as UartError, Hci, Packet};
+use hci::Event;
+
+const READY: [u8; 5] = [0x02, 0xFF, 0x00, 0x00, 0x00];
+const ASLEEP: [u8; 5] = [0x00; 5];
+const GET_FIRMWARE_REVISION: [u8; 4] = [0x01, 0x00, 0xFC, 0x00];
+const COMMAND_STATUS: [u8; 7] = [0x04, 0x0F, 0x04, 0x00, 0x01, 0x81, 0xFC];
+
+fn read_header(read_len: u8) -> [u8; 5] {
+    [0x02, 0x00, 0x00, read_len, 0x00]
+}
+
+#[test]
+fn write_command() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&READY).reply(&READY);
+    let mut rx_buffer = [0; 64];
+    {
+        let mut bnrg = BlueNRGDevice::new(&mut rx_buffer, &mut sink, DummyPin, DummyPin);
+        bnrg.get_firmware_revision().unwrap();
+        assert_eq!(bnrg.command_credits(), 0);
+        assert_eq!(bnrg.outstanding_commands(), 1);
+    }
+
+    // One transaction to check for space, and one to write the command
+    assert_eq!(sink.transactions, 2);
+    assert_eq!(sink.sent[..10], [0x0A, 0, 0, 0, 0, 0x0A, 0, 0, 0, 0]);
+    assert_eq!(sink.sent[10..], GET_FIRMWARE_REVISION);
+}
+
+#[test]
+fn write_waits_for_controller_to_wake() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&ASLEEP)
+        .reply(&ASLEEP)
+        .reply(&READY)
+        .reply(&READY);
+    let mut rx_buffer = [0; 64];
+    {
+        let mut bnrg = BlueNRGDevice::new(&mut rx_buffer, &mut sink, DummyPin, DummyPin);
+        bnrg.get_firmware_revision().unwrap();
+    }
+    assert_eq!(sink.transactions, 4);
+    assert_eq!(sink.sent[20..], GET_FIRMWARE_REVISION);
+}
+
+#[test]
+fn write_not_accepted_if_controller_falls_asleep() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&READY)
+        .reply(&ASLEEP)
+        .reply(&[0x00; 4])
+        .reply(&READY)
+        .reply(&READY);
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRGDevice::new(&mut rx_buffer, &mut sink, DummyPin, DummyPin);
+    assert_eq!(
+        bnrg.get_firmware_revision().err(),
+        Some(nb::Error::WouldBlock)
+    );
+    assert_eq!(bnrg.command_credits(), 1);
+    assert_eq!(bnrg.outstanding_commands(), 0);
+
+    bnrg.get_firmware_revision().unwrap();
+    assert_eq!(bnrg.command_credits(), 0);
+}
+
+#[test]
+fn write_waits_for_space() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&[0x02, 0x03, 0x00, 0x00, 0x00]);
+    let mut rx_buffer = [0; 64];
+    {
+        let mut bnrg = BlueNRGDevice::new(&mut rx_buffer, &mut sink, DummyPin, DummyPin);
+        assert_eq!(
+            bnrg.get_firmware_revision().err(),
+            Some(nb::Error::WouldBlock)
+        );
+    }
+    assert_eq!(sink.transactions, 1);
+}
+
+#[test]
+fn write_timeout() {
+    let mut sink = ScriptedSink::new(0x00);
+    let mut rx_buffer = [0; 64];
+    {
+        let mut bnrg = BlueNRGDevice::new(&mut rx_buffer, &mut sink, DummyPin, DummyPin);
+        bnrg.set_retry_limit(Some(3));
+        assert_eq!(
+            bnrg.get_firmware_revision().err(),
+            Some(nb::Error::Other(Error::Timeout))
+        );
+    }
+    assert_eq!(sink.transactions, 3);
+}
+
+#[test]
+fn read_event() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&READY)
+        .reply(&READY)
+        .reply(&[0x00; 4])
+        .reply(&read_header(7))
+        .reply(&read_header(7))
+        .reply(&COMMAND_STATUS);
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRGDevice::new(&mut rx_buffer, &mut sink, DummyPin, DummyPin);
+    bnrg.get_firmware_revision().unwrap();
+    assert_eq!(bnrg.command_credits(), 0);
+    match Hci::<_, BlueNRGEvent, BlueNRGError>::read(&mut bnrg) {
+        Ok(Packet::Event(Event::CommandStatus(status))) => {
+            assert_eq!(status.opcode, hci::Opcode(0xFC81));
+        }
+        other => panic!("Did not get command status: {:?}", other),
+    }
+    assert_eq!(bnrg.command_credits(), 1);
+}
+
+#[test]
+fn read_retries_if_controller_falls_asleep() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&read_header(7))
+        .reply(&ASLEEP)
+        .reply(&[0xFF; 7])
+        .reply(&read_header(7))
+        .reply(&COMMAND_STATUS);
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRGDevice::new(&mut rx_buffer, &mut sink, DummyPin, DummyPin);
+    match Hci::<_, BlueNRGEvent, BlueNRGError>::read(&mut bnrg) {
+        Ok(Packet::Event(Event::CommandStatus(status))) => {
+            assert_eq!(status.opcode, hci::Opcode(0xFC81));
+        }
+        other => panic!("Did not get command status: {:?}", other),
+    }
+}
+
+#[test]
+fn read_nothing_available() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&read_header(0));
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRGDevice::new(&mut rx_buffer, &mut sink, DummyPin, DummyPin);
+    assert_eq!(
+        Hci::<_, BlueNRGEvent, BlueNRGError>::read(&mut bnrg).err(),
+        Some(nb::Error::WouldBlock)
+    );
+}
+
+#[test]
+fn read_overflow() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&read_header(9))
+        .reply(&read_header(9))
+        .reply(&COMMAND_STATUS)
+        .reply(&read_header(2))
+        .reply(&[0x04, 0x0F]);
+
+    // Holds 7 bytes, so only the command status event fits.
+    let mut rx_buffer = [0; 8];
+    let mut bnrg = BlueNRGDevice::new(&mut rx_buffer, &mut sink, DummyPin, DummyPin);
+    assert_eq!(
+        Hci::<_, BlueNRGEvent, BlueNRGError>::read(&mut bnrg).err(),
+        Some(nb::Error::Other(UartError::Comm(Error::RxOverflow)))
+    );
+    assert_eq!(bnrg.dropped_bytes(), 2);
+    match Hci::<_, BlueNRGEvent, BlueNRGError>::read(&mut bnrg) {
+        Ok(Packet::Event(Event::CommandStatus(_))) => (),
+        other => panic!("Did not get command status: {:?}", other),
+    }
+}
+
+#[test]
+fn owned_device() {
+    let mut sink = ScriptedSink::new(0x00);
+    sink.reply(&READY).reply(&READY);
+    let mut bnrg = BlueNRGDevice::new_owned([0; 259], sink, DummyPin, DummyPin);
+    bnrg.get_firmware_revision().unwrap();
+
+    let sink = bnrg.release();
+    assert_eq!(sink.sent[10..], GET_FIRMWARE_REVISION);
+}
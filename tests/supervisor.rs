@@ -0,0 +1,253 @@
+extern crate bluenrg;
+extern crate bluetooth_hci as hci;
+extern crate embedded_hal as hal;
+extern crate nb;
+
+mod fixture;
+
+use bluenrg::bring_up::{self, BringUpConfig};
+use bluenrg::event::BlueNRGEvent;
+use bluenrg::supervisor::*;
+use bluenrg::{BlueNRG, HardwareError};
+use fixture::{DummyPin, NeverError, PollCountTimer, ScriptedSink};
+use hci::Event;
+
+fn hal_initialized(sink: &mut ScriptedSink) {
+    sink.event(&[0x04, 0xFF, 0x03, 0x01, 0x00, 0x01]);
+}
+
+fn bring_up(sink: &mut ScriptedSink) {
+    hal_initialized(sink);
+    sink.accept_command(4);
+    sink.event(&[
+        0x04, 0x0E, 0x0C, 0x01, 0x01, 0x10, 0x00, 0x07, 0x07, 0x31, 0x07, 0x30, 0x00, 0x12, 0x00,
+    ]);
+    sink.accept_command(4);
+    sink.event(&[0x04, 0x0E, 0x04, 0x01, 0x01, 0xFD, 0x00]);
+    sink.accept_command(7);
+    sink.event(&[
+        0x04, 0x0E, 0x0A, 0x01, 0x8A, 0xFC, 0x00, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06,
+    ]);
+}
+
+fn hardware_error(code: u8) -> Event<BlueNRGEvent> {
+    Event::HardwareError(hci::event::HardwareError { code })
+}
+
+type Result<T> = std::result::Result<T, RecoveryError<bluenrg::Error<(), NeverError>, u8>>;
+
+/// Runs `body` against a supervisor with the given delays. Returns its result, the supervisor's
+/// attempt count, and the number of times the timer was started.
+fn supervise<T, F>(sink: &mut ScriptedSink, delays: &[u32], body: F) -> (T, usize, usize)
+where
+    F: FnOnce(
+        &mut Supervisor<u32>,
+        &mut bluenrg::ActiveBlueNRG<ScriptedSink, DummyPin, DummyPin, DummyPin, NeverError>,
+        &mut PollCountTimer,
+    ) -> T,
+{
+    let config = BringUpConfig {
+        reset_time: 0,
+        timeout: 10,
+        config_data: &[],
+        gap_role: bluenrg::gap::Role::PERIPHERAL,
+        #[cfg(not(feature = "bluenrg2"))]
+        privacy_enabled: false,
+        #[cfg(feature = "bluenrg2")]
+        privacy: bluenrg::gap::Privacy::Disabled,
+        dev_name_characteristic_len: 8,
+    };
+    let mut supervisor = Supervisor::new(&config, delays);
+    let mut timer = PollCountTimer::new();
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.set_retry_limit(Some(4));
+    let result = bnrg.with_spi(sink, |controller| {
+        body(&mut supervisor, controller, &mut timer)
+    });
+    (result, supervisor.attempts(), timer.starts)
+}
+
+#[test]
+fn fault_from_event() {
+    assert!(matches!(
+        Fault::from_event(&hardware_error(0)),
+        Some(Fault::Hardware(HardwareError::SpiFraming))
+    ));
+    assert!(matches!(
+        Fault::from_event(&hardware_error(1)),
+        Some(Fault::Hardware(HardwareError::RadioState))
+    ));
+    assert!(matches!(
+        Fault::from_event(&hardware_error(2)),
+        Some(Fault::Hardware(HardwareError::TimerOverrun))
+    ));
+    assert!(matches!(
+        Fault::from_event(&hardware_error(0x80)),
+        Some(Fault::UnknownHardware(0x80))
+    ));
+    assert!(
+        Fault::from_event(&Event::Vendor(BlueNRGEvent::GapLimitedDiscoverableTimeout)).is_none()
+    );
+}
+
+#[test]
+#[cfg(feature = "ms")]
+fn fault_from_crash_report() {
+    use hci::event::VendorEvent;
+
+    let mut buffer = [0; 40];
+    buffer[0] = 0x03;
+    buffer[1] = 0x00;
+    buffer[2] = 0x02; // Hard fault
+    buffer[31] = 0x1d; // pc
+    let event = Event::Vendor(BlueNRGEvent::new(&buffer).unwrap());
+
+    match Fault::from_event(&event) {
+        Some(fault @ Fault::Crash(data)) => {
+            assert_eq!(data.pc, 0x1d);
+            assert!(fault.needs_reset());
+        }
+        other => panic!("Did not get crash fault: {:?}", other),
+    }
+}
+
+#[test]
+fn needs_reset() {
+    assert!(!Fault::Hardware(HardwareError::SpiFraming).needs_reset());
+    assert!(Fault::Hardware(HardwareError::RadioState).needs_reset());
+    assert!(Fault::Hardware(HardwareError::TimerOverrun).needs_reset());
+    assert!(Fault::UnknownHardware(0x80).needs_reset());
+}
+
+#[test]
+fn recovers_from_hardware_error() {
+    let mut sink = ScriptedSink::new(0x00);
+    bring_up(&mut sink);
+
+    let mut restored = None;
+    let (result, attempts, starts) =
+        supervise(&mut sink, &[0, 5], |supervisor, controller, timer| {
+            let result: Result<_> =
+                supervisor.handle(controller, timer, &hardware_error(1), |_, bring_up| {
+                    restored = Some(bring_up.version);
+                    Ok(())
+                });
+            result
+        });
+    let recovery = result.unwrap().unwrap();
+    assert!(matches!(
+        recovery.fault,
+        Fault::Hardware(HardwareError::RadioState)
+    ));
+    assert_eq!(recovery.attempt, 1);
+    assert_eq!(recovery.bring_up.version.hw_version, 0x31);
+    assert_eq!(restored.unwrap().hw_version, 0x31);
+    assert_eq!(attempts, 1);
+
+    // The back-off delay and the two halves of the reset. The bring-up only starts the timer when
+    // the controller is not ready, and the script always has the next packet ready.
+    assert_eq!(starts, 3);
+}
+
+#[test]
+fn ignores_other_events() {
+    let mut sink = ScriptedSink::new(0x00);
+
+    let (result, attempts, starts) = supervise(&mut sink, &[0], |supervisor, controller, timer| {
+        let spi_framing: Result<_> =
+            supervisor.handle(controller, timer, &hardware_error(0), |_, _| Ok(()));
+        let other: Result<_> = supervisor.handle(
+            controller,
+            timer,
+            &Event::Vendor(BlueNRGEvent::GapLimitedDiscoverableTimeout),
+            |_, _| Ok(()),
+        );
+        (spi_framing, other)
+    });
+    assert!(result.0.unwrap().is_none());
+    assert!(result.1.unwrap().is_none());
+    assert_eq!(attempts, 0);
+    assert_eq!(starts, 0);
+    assert!(sink.sent.is_empty());
+}
+
+#[test]
+fn gives_up_after_delays() {
+    let mut sink = ScriptedSink::new(0x00);
+    bring_up(&mut sink);
+    bring_up(&mut sink);
+
+    let (result, attempts, _) = supervise(&mut sink, &[0, 1], |supervisor, controller, timer| {
+        let mut results: Vec<Result<_>> = Vec::new();
+        for _ in 0..3 {
+            results.push(supervisor.handle(controller, timer, &hardware_error(2), |_, _| Ok(())));
+        }
+        assert!(supervisor.gave_up());
+        results
+    });
+    assert_eq!(result[0].as_ref().unwrap().unwrap().attempt, 1);
+    assert_eq!(result[1].as_ref().unwrap().unwrap().attempt, 2);
+    let err = result[2].as_ref().err().unwrap();
+    assert_eq!(err.attempt, 2);
+    assert_eq!(err.cause, Cause::GaveUp);
+    assert_eq!(attempts, 2);
+}
+
+#[test]
+fn reset_back_off() {
+    let mut sink = ScriptedSink::new(0x00);
+    bring_up(&mut sink);
+    bring_up(&mut sink);
+
+    let (result, attempts, _) = supervise(&mut sink, &[0], |supervisor, controller, timer| {
+        let first: Result<_> =
+            supervisor.handle(controller, timer, &hardware_error(1), |_, _| Ok(()));
+        assert!(supervisor.gave_up());
+        supervisor.reset_back_off();
+        assert!(!supervisor.gave_up());
+        let second: Result<_> =
+            supervisor.handle(controller, timer, &hardware_error(1), |_, _| Ok(()));
+        (first, second)
+    });
+    assert_eq!(result.0.unwrap().unwrap().attempt, 1);
+    assert_eq!(result.1.unwrap().unwrap().attempt, 1);
+    assert_eq!(attempts, 1);
+}
+
+#[test]
+fn bring_up_failed() {
+    let mut sink = ScriptedSink::new(0x00);
+
+    let (result, attempts, _) = supervise(&mut sink, &[0, 0], |supervisor, controller, timer| {
+        let result: Result<_> =
+            supervisor.handle(controller, timer, &hardware_error(1), |_, _| Ok(()));
+        result
+    });
+    let err = result.err().unwrap();
+    assert_eq!(err.attempt, 1);
+    assert_eq!(
+        err.cause,
+        Cause::BringUp(bring_up::BringUpError {
+            step: bring_up::Step::HalInitialized,
+            cause: bring_up::Cause::Timeout,
+        })
+    );
+    assert_eq!(attempts, 1);
+}
+
+#[test]
+fn restore_failed() {
+    let mut sink = ScriptedSink::new(0x00);
+    bring_up(&mut sink);
+
+    let (result, attempts, _) = supervise(&mut sink, &[0], |supervisor, controller, timer| {
+        let fault = Fault::UnknownHardware(0x80);
+        let result: Result<_> = supervisor.recover(controller, timer, fault, |_, _| Err(7));
+        result
+    });
+    let err = result.err().unwrap();
+    assert_eq!(err.attempt, 1);
+    assert_eq!(err.cause, Cause::Restore(7));
+    assert_eq!(attempts, 1);
+}
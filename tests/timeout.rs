@@ -0,0 +1,139 @@
+extern crate bluenrg;
+extern crate embedded_hal as hal;
+extern crate nb;
+
+mod fixture;
+
+use bluenrg::gap::Commands;
+use bluenrg::{BlueNRG, Error, SpiOptions, Timeout};
+use fixture::{DummyPin, NeverError, PollCountTimer};
+
+/// SPI bus that replies to every SPI header with the same bytes, and counts the headers it
+/// receives.
+struct HeaderOnlySink {
+    reply: [u8; 5],
+    index: usize,
+    headers: usize,
+}
+
+impl HeaderOnlySink {
+    fn new(reply: [u8; 5]) -> HeaderOnlySink {
+        HeaderOnlySink {
+            reply,
+            index: 0,
+            headers: 0,
+        }
+    }
+}
+
+impl hal::spi::FullDuplex<u8> for HeaderOnlySink {
+    type Error = ();
+
+    fn read(&mut self) -> nb::Result<u8, Self::Error> {
+        let byte = self.reply[self.index];
+        self.index = (self.index + 1) % self.reply.len();
+        if self.index == 0 {
+            self.headers += 1;
+        }
+        Ok(byte)
+    }
+
+    fn send(&mut self, _byte: u8) -> nb::Result<(), Self::Error> {
+        Ok(())
+    }
+}
+
+impl hal::blocking::spi::transfer::Default<u8> for HeaderOnlySink {}
+
+impl hal::blocking::spi::write::Default<u8> for HeaderOnlySink {}
+
+const ASLEEP: [u8; 5] = [0x00, 0x00, 0x00, 0x00, 0x00];
+const NO_WRITE_SPACE: [u8; 5] = [0x02, 0x00, 0x00, 0x00, 0x00];
+
+#[test]
+fn retry_limit_asleep() {
+    let mut sink = HeaderOnlySink::new(ASLEEP);
+    let mut rx_buffer = [0; 8];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.set_retry_limit(Some(10));
+    let err = bnrg
+        .with_spi(&mut sink, |controller| controller.set_nondiscoverable())
+        .err()
+        .unwrap();
+    assert_eq!(err, nb::Error::Other(Error::<(), NeverError>::Timeout));
+    assert_eq!(sink.headers, 10);
+}
+
+#[test]
+fn retry_limit_no_write_space() {
+    let mut sink = HeaderOnlySink::new(NO_WRITE_SPACE);
+    let mut rx_buffer = [0; 8];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.set_retry_limit(Some(3));
+    bnrg.with_spi(&mut sink, |controller| {
+        for _ in 0..2 {
+            assert_eq!(
+                controller.set_nondiscoverable().err().unwrap(),
+                nb::Error::WouldBlock
+            );
+        }
+        assert_eq!(
+            controller.set_nondiscoverable().err().unwrap(),
+            nb::Error::Other(Error::Timeout)
+        );
+    });
+    assert_eq!(sink.headers, 3);
+}
+
+#[test]
+fn deadline_asleep() {
+    let mut sink = HeaderOnlySink::new(ASLEEP);
+    let mut timer = PollCountTimer::new();
+    let mut rx_buffer = [0; 8];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    let mut timeout = Timeout::new(&mut timer, 5);
+    let options = SpiOptions::new().timeout(&mut timeout);
+    let err = bnrg
+        .with_spi_options(&mut sink, options, |controller| {
+            controller.set_nondiscoverable()
+        })
+        .err()
+        .unwrap();
+    assert_eq!(err, nb::Error::Other(Error::<(), NeverError>::Timeout));
+    assert_eq!(timer.starts, 1);
+    assert_eq!(sink.headers, 7);
+}
+
+#[test]
+fn deadline_no_write_space() {
+    let mut sink = HeaderOnlySink::new(NO_WRITE_SPACE);
+    let mut timer = PollCountTimer::new();
+    let mut rx_buffer = [0; 8];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    let mut timeout = Timeout::new(&mut timer, 2);
+    let options = SpiOptions::new().timeout(&mut timeout);
+    let err = bnrg
+        .with_spi_options(&mut sink, options, |controller| {
+            nb::block!(controller.set_nondiscoverable())
+        })
+        .err()
+        .unwrap();
+    assert_eq!(err, Error::<(), NeverError>::Timeout);
+    assert_eq!(timer.starts, 1);
+    assert_eq!(sink.headers, 4);
+}
+
+#[test]
+fn unlimited_by_default() {
+    let mut sink = HeaderOnlySink::new(NO_WRITE_SPACE);
+    let mut rx_buffer = [0; 8];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.with_spi(&mut sink, |controller| {
+        for _ in 0..1000 {
+            assert_eq!(
+                controller.set_nondiscoverable().err().unwrap(),
+                nb::Error::WouldBlock
+            );
+        }
+    });
+}
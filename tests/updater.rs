@@ -0,0 +1,390 @@
+extern crate bluenrg;
+extern crate bluetooth_hci as hci;
+extern crate nb;
+
+mod fixture;
+
+use bluenrg::event::ResetReason;
+use bluenrg::flasher::*;
+use bluenrg::updater::{Commands, Error, FIRMWARE_ADDRESS, MAX_FIRMWARE_LEN, SECTOR_SIZE};
+use bluenrg::BlueNRG;
+use fixture::{DummyPin, Fixture, NeverError, RecordingSink, ScriptedSink};
+
+#[test]
+fn start() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture.act(|controller| controller.start()).unwrap();
+    }
+    assert!(sink.wrote_header());
+    assert!(sink.wrote(&[1, 0x20, 0xFC, 0]));
+}
+
+#[test]
+fn reboot() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture.act(|controller| controller.reboot()).unwrap();
+    }
+    assert!(sink.wrote_header());
+    assert!(sink.wrote(&[1, 0x21, 0xFC, 0]));
+}
+
+#[test]
+fn get_version() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture.act(|controller| controller.get_version()).unwrap();
+    }
+    assert!(sink.wrote_header());
+    assert!(sink.wrote(&[1, 0x22, 0xFC, 0]));
+}
+
+#[test]
+fn get_buffer_size() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture
+            .act(|controller| controller.get_buffer_size())
+            .unwrap();
+    }
+    assert!(sink.wrote_header());
+    assert!(sink.wrote(&[1, 0x23, 0xFC, 0]));
+}
+
+#[test]
+fn erase_blue_flag() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture
+            .act(|controller| controller.erase_blue_flag())
+            .unwrap();
+    }
+    assert!(sink.wrote_header());
+    assert!(sink.wrote(&[1, 0x24, 0xFC, 0]));
+}
+
+#[test]
+fn reset_blue_flag() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture
+            .act(|controller| controller.reset_blue_flag())
+            .unwrap();
+    }
+    assert!(sink.wrote_header());
+    assert!(sink.wrote(&[1, 0x25, 0xFC, 0]));
+}
+
+#[test]
+fn erase_sector() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture
+            .act(|controller| controller.erase_sector(0x1001_0800))
+            .unwrap();
+    }
+    assert!(sink.wrote_header());
+    assert!(sink.wrote(&[1, 0x26, 0xFC, 4, 0x00, 0x08, 0x01, 0x10]));
+}
+
+#[test]
+fn program_data_block() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture
+            .act(|controller| controller.program_data_block(0x1001_0800, &[1, 2, 3]))
+            .unwrap();
+    }
+    assert!(sink.wrote_header());
+    assert!(sink.wrote(&[1, 0x27, 0xFC, 9, 0x00, 0x08, 0x01, 0x10, 3, 0, 1, 2, 3]));
+}
+
+#[test]
+fn program_data_block_too_long() {
+    let mut sink = RecordingSink::new();
+    let mut fixture = Fixture::new(&mut sink);
+    let err = fixture
+        .act(|controller| controller.program_data_block(0x1001_0800, &[0; 250]))
+        .err()
+        .unwrap();
+    assert_eq!(err, nb::Error::Other(Error::DataBlockTooLong(250)));
+    assert!(!fixture.wrote_header());
+}
+
+#[test]
+fn read_data_block() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture
+            .act(|controller| controller.read_data_block(0x1001_0800, 16))
+            .unwrap();
+    }
+    assert!(sink.wrote_header());
+    assert!(sink.wrote(&[1, 0x28, 0xFC, 6, 0x00, 0x08, 0x01, 0x10, 16, 0]));
+}
+
+#[test]
+fn read_data_block_too_long() {
+    let mut sink = RecordingSink::new();
+    let mut fixture = Fixture::new(&mut sink);
+    let err = fixture
+        .act(|controller| controller.read_data_block(0x1001_0800, 250))
+        .err()
+        .unwrap();
+    assert_eq!(err, nb::Error::Other(Error::DataBlockTooLong(250)));
+    assert!(!fixture.wrote_header());
+}
+
+#[test]
+fn calc_crc() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture
+            .act(|controller| controller.calc_crc(0x1001_0800, 2))
+            .unwrap();
+    }
+    assert!(sink.wrote_header());
+    assert!(sink.wrote(&[1, 0x29, 0xFC, 5, 0x00, 0x08, 0x01, 0x10, 2]));
+}
+
+#[test]
+fn hw_version() {
+    let mut sink = RecordingSink::new();
+    {
+        let mut fixture = Fixture::new(&mut sink);
+        fixture.act(|controller| controller.hw_version()).unwrap();
+    }
+    assert!(sink.wrote_header());
+    assert!(sink.wrote(&[1, 0x2A, 0xFC, 0]));
+}
+
+#[test]
+fn crc32_known_answer() {
+    // Read as little-endian words, these bytes are "123456789" with each word reversed, so
+    // this is the CRC-32/POSIX check value (0x765E7680) without its final inversion. The leading
+    // zeros do not change a CRC that starts at 0.
+    assert_eq!(crc32(b"1\0\0\x0054329876"), 0x89A1_897F);
+    assert_eq!(crc32(&[0x01, 0x02, 0x03, 0x04]), 0xDAAF_3A34);
+}
+
+fn image(len: usize) -> Vec<u8> {
+    (0..len).map(|i| (i * 7) as u8).collect()
+}
+
+/// Returns the CRC of the sector, with the part past the end of the image erased.
+fn sector_crc(image: &[u8], sector: usize) -> u32 {
+    let mut data = vec![0xFF; SECTOR_SIZE];
+    let start = sector * SECTOR_SIZE;
+    let end = std::cmp::min(start + SECTOR_SIZE, image.len());
+    data[..end - start].copy_from_slice(&image[start..end]);
+
+    // updater_calc_crc, from ST's BlueNRG utilities.
+    let mut crc = 0u32;
+    for word in data.chunks(4) {
+        crc ^= u32::from_le_bytes([word[0], word[1], word[2], word[3]]);
+        for _ in 0..32 {
+            crc = (crc << 1) ^ ((crc >> 31) * 0x04C1_1DB7);
+        }
+    }
+    crc
+}
+
+fn command_complete(sink: &mut ScriptedSink, command_len: usize, ocf: u8) {
+    sink.accept_command(command_len);
+    sink.event(&[0x04, 0x0E, 0x04, 0x01, ocf, 0xFC, 0x00]);
+}
+
+fn start_updater(sink: &mut ScriptedSink) {
+    command_complete(sink, 4, 0x20);
+    sink.event(&[0x04, 0xFF, 0x03, 0x01, 0x00, 0x02]);
+}
+
+fn write_sector(sink: &mut ScriptedSink, image: &[u8], sector: usize, crc: u32) {
+    command_complete(sink, 8, 0x26);
+    let len = std::cmp::min(SECTOR_SIZE, image.len() - sector * SECTOR_SIZE);
+    for _ in 0..len.div_ceil(BLOCK_LEN) {
+        command_complete(sink, 10 + BLOCK_LEN, 0x27);
+    }
+    sink.accept_command(9);
+    let crc = crc.to_le_bytes();
+    sink.event(&[
+        0x04, 0x0E, 0x08, 0x01, 0x29, 0xFC, 0x00, crc[0], crc[1], crc[2], crc[3],
+    ]);
+}
+
+fn finish(sink: &mut ScriptedSink) {
+    command_complete(sink, 4, 0x25);
+    command_complete(sink, 4, 0x21);
+}
+
+fn run(
+    sink: &mut ScriptedSink,
+    flasher: &mut Flasher,
+) -> Result<(), FlashError<bluenrg::Error<(), NeverError>>> {
+    let mut rx_buffer = [0; 64];
+    let mut bnrg = BlueNRG::new(&mut rx_buffer, DummyPin, DummyPin, DummyPin);
+    bnrg.with_spi(sink, |controller| {
+        for _ in 0..1000 {
+            match flasher.poll(controller) {
+                Ok(()) => return Ok(()),
+                Err(nb::Error::WouldBlock) => (),
+                Err(nb::Error::Other(e)) => return Err(e),
+            }
+        }
+        panic!("flasher did not finish");
+    })
+}
+
+fn contains(sent: &[u8], bytes: &[u8]) -> bool {
+    sent.windows(bytes.len()).any(|window| window == bytes)
+}
+
+const RESET_BLUE_FLAG: [u8; 4] = [1, 0x25, 0xFC, 0];
+
+#[test]
+fn flash_image() {
+    let image = image(SECTOR_SIZE + 100);
+    let mut sink = ScriptedSink::new(0x00);
+    start_updater(&mut sink);
+    command_complete(&mut sink, 4, 0x24);
+    write_sector(&mut sink, &image, 0, sector_crc(&image, 0));
+    write_sector(&mut sink, &image, 1, sector_crc(&image, 1));
+    finish(&mut sink);
+
+    let mut flasher = Flasher::new(&image).unwrap();
+    assert_eq!(flasher.sector_count(), 2);
+    run(&mut sink, &mut flasher).unwrap();
+    assert!(flasher.is_done());
+    assert_eq!(flasher.verified_sectors(), 2);
+
+    // The second block of the last sector is padded with erased bytes.
+    let address = (FIRMWARE_ADDRESS + SECTOR_SIZE as u32 + BLOCK_LEN as u32).to_le_bytes();
+    let mut block = vec![
+        1,
+        0x27,
+        0xFC,
+        6 + BLOCK_LEN as u8,
+        address[0],
+        address[1],
+        address[2],
+        address[3],
+        BLOCK_LEN as u8,
+        0,
+    ];
+    block.extend_from_slice(&image[SECTOR_SIZE + BLOCK_LEN..]);
+    block.resize(10 + BLOCK_LEN, 0xFF);
+    assert!(contains(&sink.sent, &block));
+    assert!(contains(&sink.sent, &RESET_BLUE_FLAG));
+}
+
+#[test]
+fn crc_mismatch_is_retried() {
+    let image = image(100);
+    let mut sink = ScriptedSink::new(0x00);
+    start_updater(&mut sink);
+    command_complete(&mut sink, 4, 0x24);
+    write_sector(&mut sink, &image, 0, 0);
+    write_sector(&mut sink, &image, 0, sector_crc(&image, 0));
+    finish(&mut sink);
+
+    let mut flasher = Flasher::new(&image).unwrap();
+    run(&mut sink, &mut flasher).unwrap();
+    assert_eq!(flasher.verified_sectors(), 1);
+}
+
+#[test]
+fn crc_mismatch_keeps_blue_flag_erased() {
+    let image = image(100);
+    let mut sink = ScriptedSink::new(0x00);
+    start_updater(&mut sink);
+    command_complete(&mut sink, 4, 0x24);
+    for _ in 0..SECTOR_ATTEMPTS {
+        write_sector(&mut sink, &image, 0, 0x1234_5678);
+    }
+
+    let mut flasher = Flasher::new(&image).unwrap();
+    let err = run(&mut sink, &mut flasher).err().unwrap();
+    assert_eq!(err.step, Step::CalcCrc(0));
+    assert_eq!(
+        err.cause,
+        Cause::CrcMismatch {
+            expected: sector_crc(&image, 0),
+            actual: 0x1234_5678
+        }
+    );
+    assert_eq!(flasher.verified_sectors(), 0);
+    assert!(!flasher.is_done());
+    assert!(!contains(&sink.sent, &RESET_BLUE_FLAG));
+}
+
+#[test]
+fn resume() {
+    let image = image(2 * SECTOR_SIZE);
+    let mut sink = ScriptedSink::new(0x00);
+    command_complete(&mut sink, 4, 0x24);
+    write_sector(&mut sink, &image, 1, sector_crc(&image, 1));
+    finish(&mut sink);
+
+    let mut flasher = Flasher::resume(&image, 1).unwrap();
+    assert_eq!(flasher.verified_sectors(), 1);
+    run(&mut sink, &mut flasher).unwrap();
+    assert_eq!(flasher.verified_sectors(), 2);
+
+    // Neither starts the updater nor rewrites the first sector.
+    assert!(!contains(&sink.sent, &[1, 0x20, 0xFC, 0]));
+    let address = FIRMWARE_ADDRESS.to_le_bytes();
+    assert!(!contains(
+        &sink.sent,
+        &[1, 0x26, 0xFC, 4, address[0], address[1], address[2], address[3]]
+    ));
+}
+
+#[test]
+fn unexpected_reset_reason() {
+    let image = image(100);
+    let mut sink = ScriptedSink::new(0x00);
+    command_complete(&mut sink, 4, 0x20);
+    sink.event(&[0x04, 0xFF, 0x03, 0x01, 0x00, 0x01]);
+
+    let mut flasher = Flasher::new(&image).unwrap();
+    let err = run(&mut sink, &mut flasher).err().unwrap();
+    assert_eq!(err.step, Step::UpdaterInitialized);
+    assert_eq!(err.cause, Cause::UnexpectedResetReason(ResetReason::Normal));
+}
+
+#[test]
+fn command_failed() {
+    let image = image(100);
+    let mut sink = ScriptedSink::new(0x00);
+    start_updater(&mut sink);
+    sink.accept_command(4);
+    sink.event(&[0x04, 0x0E, 0x04, 0x01, 0x24, 0xFC, 0x41]);
+
+    let mut flasher = Flasher::new(&image).unwrap();
+    let err = run(&mut sink, &mut flasher).err().unwrap();
+    assert_eq!(err.step, Step::EraseBlueFlag);
+    assert_eq!(
+        err.cause,
+        Cause::CommandFailed(hci::Status::Vendor(bluenrg::event::Status::Failed))
+    );
+}
+
+#[test]
+fn image_too_large() {
+    let image = image(MAX_FIRMWARE_LEN + 1);
+    assert_eq!(
+        Flasher::new(&image).err(),
+        Some(ImageTooLarge(MAX_FIRMWARE_LEN + 1))
+    );
+}